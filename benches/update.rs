@@ -0,0 +1,156 @@
+//! Timings of `World::update` for each allocation strategy, of the boxed and
+//! structure-of-arrays stores, and of the reporting allocator against the
+//! system allocator it wraps, at 1k, 10k and 100k particles.
+//!
+//! ```text
+//! cargo bench --bench update
+//! cargo bench --bench update -- update/pooled
+//! ```
+//!
+//! Criterion isn't among the dependencies, so the timing is done here the
+//! way it does it: a warm-up to find how many turns fill a sample, then
+//! samples of that many turns, and the fastest, median and slowest time per
+//! turn of them printed as `[min median max]`.
+
+use memory_particles::allocator;
+use memory_particles::config::{Config, ParticleSettings};
+use memory_particles::freelist::AllocationStrategy;
+use memory_particles::store::{BoxedStore, ParticleStore, SoaStore};
+use memory_particles::{Particle, World};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// The world's allocations are timed and counted as in the binary, unless
+// the allocator is switched to passthrough. With `ffi`, `python` or `wasm`
+// the library installs it
+#[cfg(not(any(feature = "ffi", feature = "python", feature = "wasm")))]
+#[global_allocator]
+static ALLOCATOR: allocator::ReportingAllocator = allocator::ReportingAllocator;
+
+const POPULATIONS: [usize; 3] = [1_000, 10_000, 100_000];
+
+const WARM_UP: Duration = Duration::from_millis(300);
+const SAMPLE_TIME: Duration = Duration::from_millis(25);
+const SAMPLES: usize = 20;
+
+// A steady population, of particles living 120 to 240 turns and replaced
+// in their box when they die, so every turn moves as many of them
+const STEADY: &str = "spawn_rate = 0
+fade = { kind = \"linear\", lifetime = 240 }
+
+[particle]
+speed = [0.5, 2]
+
+[population]
+lifetime = [120, 240]
+respawn = true
+";
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let selected = |name: &str| filter.as_deref().is_none_or(|filter| name.contains(filter));
+
+    for strategy in [AllocationStrategy::Boxed, AllocationStrategy::Pooled, AllocationStrategy::Arena] {
+        for particles in POPULATIONS {
+            let name = format!("update/{}/{}", strategy.name(), particles);
+            if selected(&name) {
+                let mut world = steady_world(particles);
+                world.set_allocation_strategy(strategy);
+                bench(&name, || world.update());
+                world.set_allocation_strategy(AllocationStrategy::Boxed);
+            }
+        }
+    }
+
+    for layout in ["boxed", "soa"] {
+        for particles in POPULATIONS {
+            let name = format!("store/{}/{}", layout, particles);
+            if selected(&name) {
+                let mut rng = StdRng::seed_from_u64(1);
+                let mut store: Box<dyn ParticleStore> = match layout {
+                    "soa" => Box::new(SoaStore::with_capacity(particles)),
+                    _ => Box::new(BoxedStore::with_capacity(particles)),
+                };
+                (0..particles).for_each(|_| store.push(spawn(&mut rng)));
+                bench(&name, || store.step(1.0, &mut || spawn(&mut rng)));
+            }
+        }
+    }
+
+    // Boxes allocated for the particles dying every turn instead of reused,
+    // with and without the counters. Last, and with worlds made and dropped
+    // while passthrough is on: what was allocated around the counters
+    // mustn't be freed through them
+    for passthrough in [false, true] {
+        for particles in POPULATIONS {
+            let name = format!("allocator/{}/{}", if passthrough { "system" } else { "reporting" }, particles);
+            if selected(&name) {
+                allocator::set_passthrough(passthrough);
+                let mut world = steady_world(particles);
+                world.population.respawn = false;
+                bench(&name, || {
+                    world.update();
+                    world.add_shapes(particles.saturating_sub(world.particles.len()) as i32);
+                });
+                drop(world);
+                allocator::set_passthrough(false);
+            }
+        }
+    }
+}
+
+fn steady_world(particles: usize) -> World {
+    let mut world = World::from_config(1280.0, 960.0, Config::from_toml(STEADY).expect("steady scene"));
+    world.rng = StdRng::seed_from_u64(1);
+    world.add_shapes(particles as i32);
+    world
+}
+
+fn spawn(rng: &mut StdRng) -> Particle {
+    let position = [rng.gen_range(0.0..1280.0), rng.gen_range(0.0..960.0)];
+    Particle::new(position, [0.0, -1.0], [1.0, 1.0, 1.0, 0.99], &ParticleSettings::default(), rng)
+}
+
+// Runs `turn` through a warm-up then `SAMPLES` samples, and prints the time
+// per turn
+fn bench<T>(name: &str, mut turn: impl FnMut() -> T) {
+    let start = Instant::now();
+    let mut warm_up_turns = 0u32;
+    while start.elapsed() < WARM_UP {
+        black_box(turn());
+        warm_up_turns += 1;
+    }
+    let per_turn = start.elapsed() / warm_up_turns;
+    let turns = (SAMPLE_TIME.as_nanos() / per_turn.as_nanos().max(1)).max(1) as u32;
+
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            (0..turns).for_each(|_| {
+                black_box(turn());
+            });
+            start.elapsed() / turns
+        })
+        .collect();
+    samples.sort();
+    println!(
+        "{:<28} time: [{} {} {}]",
+        name,
+        format_time(samples[0]),
+        format_time(samples[SAMPLES / 2]),
+        format_time(samples[SAMPLES - 1])
+    );
+}
+
+fn format_time(time: Duration) -> String {
+    let nanos = time.as_nanos() as f64;
+    if nanos >= 1e6 {
+        format!("{:.3} ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.3} µs", nanos / 1e3)
+    } else {
+        format!("{:.0} ns", nanos)
+    }
+}
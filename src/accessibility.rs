@@ -0,0 +1,79 @@
+use crate::camera::CameraEase;
+use crate::config::{Config, ParticleSettings};
+use crate::fade::FadeModel;
+use crate::palette::Palette;
+use serde::Deserialize;
+
+// Factor reduced motion slows the particles down by, and the fastest they go
+const REDUCED_SPEED: f64 = 0.5;
+const REDUCED_MAX_SPEED: f64 = 3.0;
+
+// How many times longer reduced motion makes fades last
+const GENTLE_FADE: f64 = 2.0;
+
+/// Modes for showing the demo to a room, like a classroom, set with
+/// `[accessibility]` in scene files or `--high-contrast` and
+/// `--reduced-motion`. They change the scene as it loads, so runs in the
+/// window, benches and the bindings all get the same changed scene.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Accessibility {
+    /// Bright particles of the `contrast` palette on a solid black
+    /// background, without trails, a day and night cycle or see-through
+    /// overlays
+    #[serde(default)]
+    pub high_contrast: bool,
+
+    /// Slower particles, fades lasting longer and never ending at once,
+    /// a camera that glides instead of cutting, and warnings that don't
+    /// flash on and off
+    #[serde(default)]
+    pub reduced_motion: bool,
+}
+
+impl Accessibility {
+    /// Changes `config` as the modes set ask.
+    pub fn apply(&self, config: &mut Config) {
+        if self.high_contrast {
+            config.background = Some([0.0, 0.0, 0.0, 1.0]);
+            config.ambient = None;
+            config.palette = Palette::named("contrast").ok();
+        }
+        if self.reduced_motion {
+            slow_down(&mut config.particle);
+            config.max_speed = Some(config.max_speed.map_or(REDUCED_MAX_SPEED, |speed| speed.min(REDUCED_MAX_SPEED)));
+            config.jitter *= REDUCED_SPEED;
+            config.fade = config.fade.map(gentle);
+            for emitter in &mut config.emitters {
+                if let Some(particle) = &mut emitter.particle {
+                    slow_down(particle);
+                }
+                emitter.max_speed = emitter.max_speed.map(|speed| speed.min(REDUCED_MAX_SPEED));
+                emitter.fade = emitter.fade.map(gentle);
+            }
+            if let Some(sparks) = &mut config.sparks {
+                sparks.speed *= REDUCED_SPEED;
+                sparks.fade = sparks.fade.powf(1.0 / GENTLE_FADE as f32);
+            }
+            for key in &mut config.camera {
+                if key.ease == CameraEase::Cut {
+                    key.ease = CameraEase::Smooth;
+                }
+            }
+        }
+    }
+}
+
+fn slow_down(particle: &mut ParticleSettings) {
+    particle.speed = particle.speed.map(|speed| speed * REDUCED_SPEED);
+}
+
+// The same fade over longer, stepped ones fading out linearly instead of
+// disappearing at once
+fn gentle(fade: FadeModel) -> FadeModel {
+    let longer = |lifetime: u32| (lifetime as f64 * GENTLE_FADE) as u32;
+    match fade {
+        FadeModel::Exponential { half_life } => FadeModel::Exponential { half_life: half_life * GENTLE_FADE },
+        FadeModel::Linear { lifetime } | FadeModel::Step { lifetime } => FadeModel::Linear { lifetime: longer(lifetime) },
+    }
+}
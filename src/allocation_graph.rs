@@ -0,0 +1,88 @@
+use crate::dashboard::format_bytes;
+use memory_particles::allocator::MemoryStats;
+use piston_window::*;
+use std::collections::VecDeque;
+
+// Number of most recent frames plotted, across the width of the window
+const GRAPH_FRAMES: usize = 600;
+
+// Height of each of the two charts, stacked
+const CHART_HEIGHT: f64 = 40.0;
+
+/// Height of the graph along the bottom of the window.
+pub const GRAPH_HEIGHT: f64 = 2.0 * CHART_HEIGHT;
+
+const BYTES_COLOR: [f32; 4] = [0.35, 0.7, 1.0, 0.9];
+const LATENCY_COLOR: [f32; 4] = [1.0, 0.55, 0.3, 0.9];
+
+// What the allocator did during a frame
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    bytes: u64,
+
+    // Mean time of the frame's allocations, 0 without any
+    mean_nanos: f64,
+}
+
+/// What the allocator did every frame, read from its counters at the end of
+/// each one: the bytes allocated and the mean time an allocation took.
+pub struct AllocationGraph {
+    samples: VecDeque<Sample>,
+    last: MemoryStats,
+}
+
+impl AllocationGraph {
+    /// Counts frames from the counters `stats`.
+    pub fn new(stats: &MemoryStats) -> AllocationGraph {
+        AllocationGraph { samples: VecDeque::with_capacity(GRAPH_FRAMES), last: *stats }
+    }
+
+    /// Ends a frame with the counters `stats`.
+    pub fn push(&mut self, stats: &MemoryStats) {
+        let allocations = stats.allocations - self.last.allocations;
+        let nanos = stats.allocation_nanos - self.last.allocation_nanos;
+        let mean_nanos = if allocations == 0 { 0.0 } else { nanos as f64 / allocations as f64 };
+        if self.samples.len() == GRAPH_FRAMES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample { bytes: stats.allocated_bytes - self.last.allocated_bytes, mean_nanos });
+        self.last = *stats;
+    }
+
+    /// Shift+G plots the bytes of every frame above the mean allocation
+    /// time, as lines scrolling to the left along the window, up to
+    /// `bottom`. Each chart is scaled to its largest value shown.
+    pub fn draw<G: Graphics>(&self, bottom: f64, width: f64, ctx: Context, g: &mut G) {
+        let top = bottom - GRAPH_HEIGHT;
+        rectangle([0.0, 0.0, 0.0, 0.6], [0.0, top, width, GRAPH_HEIGHT], ctx.transform, g);
+        self.draw_line(|sample| sample.bytes as f64, BYTES_COLOR, top + CHART_HEIGHT, width, ctx, g);
+        self.draw_line(|sample| sample.mean_nanos, LATENCY_COLOR, bottom, width, ctx, g);
+    }
+
+    fn draw_line<G: Graphics>(&self, value: impl Fn(&Sample) -> f64, color: [f32; 4], bottom: f64, width: f64, ctx: Context, g: &mut G) {
+        let largest = self.samples.iter().map(&value).fold(1.0, f64::max);
+
+        // The latest frame at the right edge
+        let step = width / (GRAPH_FRAMES - 1) as f64;
+        let start = width - (self.samples.len() as f64 - 1.0) * step;
+        let point = |i: usize, sample: &Sample| [start + i as f64 * step, bottom - 1.0 - value(sample) / largest * (CHART_HEIGHT - 2.0)];
+        for (i, (from, to)) in self.samples.iter().zip(self.samples.iter().skip(1)).enumerate() {
+            line_from_to(color, 0.6, point(i, from), point(i + 1, to), ctx.transform, g);
+        }
+    }
+
+    /// What the graph can't draw without text, for the window title: the
+    /// latest frame and the largest values shown.
+    pub fn describe(&self) -> String {
+        let latest = self.samples.back().copied().unwrap_or(Sample { bytes: 0, mean_nanos: 0.0 });
+        let most_bytes = self.samples.iter().map(|sample| sample.bytes).max().unwrap_or(0);
+        let longest = self.samples.iter().map(|sample| sample.mean_nanos).fold(0.0, f64::max);
+        format!(
+            "{} allocated last frame (most {}), {:.0} ns per allocation (longest {:.0} ns)",
+            format_bytes(latest.bytes),
+            format_bytes(most_bytes),
+            latest.mean_nanos,
+            longest
+        )
+    }
+}
@@ -0,0 +1,768 @@
+use crate::backend::Backing;
+use crate::fidelity::Fidelity;
+use crate::telemetry::{self, Record};
+use crate::trace::{size_bucket, SIZE_BUCKETS};
+use crate::{arena, callsite, pool, pprof, reuse};
+use serde::Deserialize;
+use std::alloc::{GlobalAlloc, Layout}; // Controls for memory allocation
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+use std::time::Instant;
+
+// `Instant::now` panics in browsers, which have `performance.now()` instead
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+use crate::wasm::Instant;
+
+// Running counters shared by every thread that allocates.
+// Live bytes goes up on `alloc` and down on `dealloc`
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static FREED_BYTES: AtomicU64 = AtomicU64::new(0);
+static VEC_REALLOCS: AtomicU64 = AtomicU64::new(0);
+
+// Of the allocations, those moving or resizing a block and those asking
+// for zeroed memory
+static REALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ZEROED_ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+// Time spent in the backing allocator, the block pool or the arena
+static ALLOCATION_NANOS: AtomicU64 = AtomicU64::new(0);
+
+// Allocations by power-of-two size class, as in trace summaries
+static SIZE_COUNTS: [AtomicU64; SIZE_BUCKETS] = [const { AtomicU64::new(0) }; SIZE_BUCKETS];
+
+// And by power-of-two class of nanoseconds taken
+static LATENCY_COUNTS: [AtomicU64; SIZE_BUCKETS] = [const { AtomicU64::new(0) }; SIZE_BUCKETS];
+
+// Allocations and frees by thread, each thread taking the next slot the
+// first time it allocates. Fixed-size like the counters above; the threads
+// past the last slot all share it
+const THREAD_SLOTS: usize = 64;
+static THREAD_ALLOCATIONS: [AtomicU64; THREAD_SLOTS] = [const { AtomicU64::new(0) }; THREAD_SLOTS];
+static THREAD_BYTES: [AtomicU64; THREAD_SLOTS] = [const { AtomicU64::new(0) }; THREAD_SLOTS];
+static THREAD_NANOS: [AtomicU64; THREAD_SLOTS] = [const { AtomicU64::new(0) }; THREAD_SLOTS];
+static THREAD_FREES: [AtomicU64; THREAD_SLOTS] = [const { AtomicU64::new(0) }; THREAD_SLOTS];
+static NEXT_THREAD_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+// What each slot's thread told it was, as `ThreadRole::to_u8` and the
+// index of rayon threads, set without allocating so a thread can say so
+// while the counts of a run are being taken
+static THREAD_ROLES: [AtomicU8; THREAD_SLOTS] = [const { AtomicU8::new(0) }; THREAD_SLOTS];
+static THREAD_INDEXES: [AtomicUsize; THREAD_SLOTS] = [const { AtomicUsize::new(0) }; THREAD_SLOTS];
+
+/// What an allocation was made for, when the code making it says so
+/// through `tagged`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AllocationTag {
+    /// The particle `Vec` grew past its capacity
+    VecGrow,
+
+    /// The particle `Vec` was shrunk to its length
+    VecShrink,
+
+    /// The positions of a particle trail were allocated or grew
+    Trail,
+}
+
+impl AllocationTag {
+    /// Name used in traces, like `vec-grow`.
+    pub fn label(self) -> &'static str {
+        match self {
+            AllocationTag::VecGrow => "vec-grow",
+            AllocationTag::VecShrink => "vec-shrink",
+            AllocationTag::Trail => "trail",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<AllocationTag> {
+        match label {
+            "vec-grow" => Some(AllocationTag::VecGrow),
+            "vec-shrink" => Some(AllocationTag::VecShrink),
+            "trail" => Some(AllocationTag::Trail),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(tag: Option<AllocationTag>) -> u8 {
+        match tag {
+            None => 0,
+            Some(AllocationTag::VecGrow) => 1,
+            Some(AllocationTag::VecShrink) => 2,
+            Some(AllocationTag::Trail) => 3,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<AllocationTag> {
+        match value {
+            1 => Some(AllocationTag::VecGrow),
+            2 => Some(AllocationTag::VecShrink),
+            3 => Some(AllocationTag::Trail),
+            _ => None,
+        }
+    }
+}
+
+/// What the allocator was asked to do, as told apart in traces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AllocationKind {
+    /// A fresh block, from `alloc`
+    #[default]
+    Alloc,
+
+    /// A block moved or resized by `realloc`, like a `Vec` growing
+    Realloc,
+
+    /// A fresh block of zeroes, from `alloc_zeroed`
+    Zeroed,
+
+    /// A block given back by `dealloc`
+    Free,
+}
+
+impl AllocationKind {
+    /// Name used in traces, like `realloc`.
+    pub fn label(self) -> &'static str {
+        match self {
+            AllocationKind::Alloc => "alloc",
+            AllocationKind::Realloc => "realloc",
+            AllocationKind::Zeroed => "zeroed",
+            AllocationKind::Free => "free",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<AllocationKind> {
+        match label {
+            "alloc" => Some(AllocationKind::Alloc),
+            "realloc" => Some(AllocationKind::Realloc),
+            "zeroed" => Some(AllocationKind::Zeroed),
+            "free" => Some(AllocationKind::Free),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            AllocationKind::Alloc => 0,
+            AllocationKind::Realloc => 1,
+            AllocationKind::Zeroed => 2,
+            AllocationKind::Free => 3,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> AllocationKind {
+        match value {
+            1 => AllocationKind::Realloc,
+            2 => AllocationKind::Zeroed,
+            3 => AllocationKind::Free,
+            _ => AllocationKind::Alloc,
+        }
+    }
+}
+
+// Tag of the allocations made by this thread, set for the duration of
+// `tagged`. Per thread so allocations of other threads are not mislabeled
+thread_local! {
+    static TAG: Cell<Option<AllocationTag>> = const { Cell::new(None) };
+    static THREAD_SLOT: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+/// What a thread does, for the allocations by thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadRole {
+    /// One that never said
+    Unknown,
+    Main,
+
+    /// Of the rayon pool updating the particles with `--parallel`, by index
+    Rayon(usize),
+
+    /// The trace writer
+    Telemetry,
+
+    /// Running the turns of the world apart from the window drawing them
+    Simulation,
+}
+
+/// Tells the allocations by thread what the current thread does.
+pub fn set_thread_role(role: ThreadRole) {
+    let slot = thread_slot();
+    let (value, index) = match role {
+        ThreadRole::Unknown => (0, 0),
+        ThreadRole::Main => (1, 0),
+        ThreadRole::Rayon(index) => (2, index),
+        ThreadRole::Telemetry => (3, 0),
+        ThreadRole::Simulation => (4, 0),
+    };
+    THREAD_INDEXES[slot].store(index, Ordering::Relaxed);
+    THREAD_ROLES[slot].store(value, Ordering::Relaxed);
+}
+
+// The slot of the current thread's counters, taken on first use. Fails,
+// sharing the last slot, while the thread is being torn down
+fn thread_slot() -> usize {
+    THREAD_SLOT
+        .try_with(|slot| {
+            if slot.get() == usize::MAX {
+                slot.set(NEXT_THREAD_SLOT.fetch_add(1, Ordering::Relaxed).min(THREAD_SLOTS - 1));
+            }
+            slot.get()
+        })
+        .unwrap_or(THREAD_SLOTS - 1)
+}
+
+// Whether allocations are reported on stderr. The counters above are
+// always kept up to date so they stay correct across a warm-up phase
+static CAPTURING: AtomicBool = AtomicBool::new(true);
+
+// Whether allocations go straight to the backing allocator, neither timed,
+// counted nor reported, as with --safe-mode
+static PASSTHROUGH: AtomicBool = AtomicBool::new(false);
+
+// Whether every allocation is reported, rather than a summary per frame of
+// the ones counted below, which printing each of them would slow down
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static FRAME_COUNT: AtomicU64 = AtomicU64::new(0);
+static FRAME_BYTES: AtomicU64 = AtomicU64::new(0);
+static FRAME_NANOS: AtomicU64 = AtomicU64::new(0);
+static FRAME_MIN_NANOS: AtomicU64 = AtomicU64::new(u64::MAX);
+static FRAME_MAX_NANOS: AtomicU64 = AtomicU64::new(0);
+
+// Of the events `VERBOSE` reports, one in this many is traced, 0 for none,
+// the others counted in the frame as without it. Lowered by the window
+// while its frames run late
+static TRACE_EVERY: AtomicU32 = AtomicU32::new(1);
+static EVENTS_SEEN: AtomicU64 = AtomicU64::new(0);
+
+// Allocations logged with their time since the start of the log, for
+// looking inside a single turn. Fixed-size so the allocator can fill it
+// without allocating; allocations past the capacity are only counted
+const ALLOCATION_LOG_CAPACITY: usize = 8192;
+static LOG_OFFSETS: [AtomicU64; ALLOCATION_LOG_CAPACITY] = [const { AtomicU64::new(0) }; ALLOCATION_LOG_CAPACITY];
+static LOG_SIZES: [AtomicUsize; ALLOCATION_LOG_CAPACITY] = [const { AtomicUsize::new(0) }; ALLOCATION_LOG_CAPACITY];
+static LOG_TAGS: [AtomicU8; ALLOCATION_LOG_CAPACITY] = [const { AtomicU8::new(0) }; ALLOCATION_LOG_CAPACITY];
+static LOG_KINDS: [AtomicU8; ALLOCATION_LOG_CAPACITY] = [const { AtomicU8::new(0) }; ALLOCATION_LOG_CAPACITY];
+static LOG_COUNT: AtomicUsize = AtomicUsize::new(0);
+static LOGGING: AtomicBool = AtomicBool::new(false);
+
+// Start of the log in nanoseconds since `EPOCH`, which is set outside the
+// allocator since `Instant` can't be built in a static
+static LOG_START: AtomicU64 = AtomicU64::new(0);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// The allocator every request is deferred to, the backend selected with
+/// `MEMORY_PARTICLES_ALLOCATOR`.
+pub static BACKING: Backing = Backing;
+
+// Provides a fairly accurate indication of time taken for dynamic memory allocation
+pub struct ReportingAllocator;
+
+unsafe impl GlobalAlloc for ReportingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if PASSTHROUGH.load(Ordering::Relaxed) {
+            return BACKING.alloc(layout);
+        }
+        let start = Instant::now();
+        let ptr = allocate(layout);
+        if !ptr.is_null() {
+            reuse::on_alloc(ptr as usize);
+        }
+        measure(AllocationKind::Alloc, layout, ptr, start)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if PASSTHROUGH.load(Ordering::Relaxed) {
+            return BACKING.alloc_zeroed(layout);
+        }
+        let start = Instant::now();
+
+        // The backing allocator can often skip the zeroing, handing out pages
+        // fresh from the system; blocks of the arena and the pool are reused
+        let ptr = if arena::serves(layout) || pool::serves(layout) {
+            let ptr = allocate(layout);
+            if !ptr.is_null() {
+                ptr::write_bytes(ptr, 0, layout.size());
+            }
+            ptr
+        } else {
+            BACKING.alloc_zeroed(layout)
+        };
+        if !ptr.is_null() {
+            reuse::on_alloc(ptr as usize);
+        }
+        measure(AllocationKind::Zeroed, layout, ptr, start)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let start = Instant::now();
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        // The arena and the pool only hold blocks of one size, so a block
+        // moving into or out of them is copied; the backing allocator may
+        // resize others in place
+        let copied = (arena::may_hold(layout) && arena::owns(ptr))
+            || pool::serves(layout)
+            || arena::serves(new_layout)
+            || pool::serves(new_layout);
+        let new_ptr = if copied {
+            let new_ptr = allocate(new_layout);
+            if !new_ptr.is_null() {
+                ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+                release(ptr, layout);
+            }
+            new_ptr
+        } else {
+            BACKING.realloc(ptr, layout, new_size)
+        };
+        if PASSTHROUGH.load(Ordering::Relaxed) {
+            return new_ptr;
+        }
+
+        // The old block is gone, counted as freed as `dealloc` would, unless
+        // the new one couldn't be had
+        if !new_ptr.is_null() {
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            FREED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+            THREAD_FREES[thread_slot()].fetch_add(1, Ordering::Relaxed);
+            if new_ptr != ptr {
+                reuse::on_free(ptr as usize);
+                reuse::on_alloc(new_ptr as usize);
+            }
+        }
+        measure(AllocationKind::Realloc, new_layout, new_ptr, start)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Only timed when frees are written to the trace
+        let start = traces_event().then(Instant::now);
+        release(ptr, layout);
+        if PASSTHROUGH.load(Ordering::Relaxed) {
+            return;
+        }
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        FREED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        THREAD_FREES[thread_slot()].fetch_add(1, Ordering::Relaxed);
+        reuse::on_free(ptr as usize);
+
+        if let Some(start) = start {
+            telemetry::record(Record {
+                size: layout.size(),
+                align: layout.align(),
+                timestamp: timestamp(start),
+                nanos: start.elapsed().as_nanos() as u64,
+                tag: None,
+                kind: AllocationKind::Free,
+            });
+        }
+    }
+}
+
+// Defers the memory allocation to the backend, or to the
+// arena or the block pool for particle-sized blocks when enabled
+unsafe fn allocate(layout: Layout) -> *mut u8 {
+    if arena::serves(layout) {
+        arena::alloc(layout)
+    } else if pool::serves(layout) {
+        pool::alloc()
+    } else {
+        BACKING.alloc(layout)
+    }
+}
+
+// Frees `ptr` to whichever of them it came from
+unsafe fn release(ptr: *mut u8, layout: Layout) {
+    if arena::may_hold(layout) && arena::owns(ptr) {
+        arena::dealloc(ptr);
+    } else if pool::serves(layout) {
+        pool::dealloc(ptr);
+    } else {
+        BACKING.dealloc(ptr, layout);
+    }
+}
+
+// Whether this allocation or free is queued for the trace, rather than
+// counted in the summary of the frame
+fn traces_event() -> bool {
+    if !CAPTURING.load(Ordering::Relaxed) || !VERBOSE.load(Ordering::Relaxed) || telemetry::on_worker() {
+        return false;
+    }
+    match TRACE_EVERY.load(Ordering::Relaxed) {
+        0 => false,
+        1 => true,
+        every => EVENTS_SEEN.fetch_add(1, Ordering::Relaxed).is_multiple_of(every as u64),
+    }
+}
+
+// Nanoseconds from the start of the allocator clock to `start`
+fn timestamp(start: Instant) -> u64 {
+    EPOCH.get().map_or(0, |epoch| start.saturating_duration_since(*epoch).as_nanos() as u64)
+}
+
+// Counts an allocation of `kind` that started at `start` and gave `ptr`,
+// for the counters, the profile, the log and the trace, and returns `ptr`
+fn measure(kind: AllocationKind, layout: Layout, ptr: *mut u8, start: Instant) -> *mut u8 {
+    let time_taken = start.elapsed();
+    let bytes_requested = layout.size();
+
+    if !ptr.is_null() {
+        let live = LIVE_BYTES.fetch_add(bytes_requested, Ordering::Relaxed) + bytes_requested;
+        PEAK_LIVE_BYTES.fetch_max(live, Ordering::Relaxed);
+    }
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATION_NANOS.fetch_add(time_taken.as_nanos() as u64, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(bytes_requested as u64, Ordering::Relaxed);
+    SIZE_COUNTS[size_bucket(bytes_requested)].fetch_add(1, Ordering::Relaxed);
+    LATENCY_COUNTS[size_bucket(time_taken.as_nanos() as usize)].fetch_add(1, Ordering::Relaxed);
+    let slot = thread_slot();
+    THREAD_ALLOCATIONS[slot].fetch_add(1, Ordering::Relaxed);
+    THREAD_BYTES[slot].fetch_add(bytes_requested as u64, Ordering::Relaxed);
+    THREAD_NANOS[slot].fetch_add(time_taken.as_nanos() as u64, Ordering::Relaxed);
+    match kind {
+        AllocationKind::Realloc => REALLOCATIONS.fetch_add(1, Ordering::Relaxed),
+        AllocationKind::Zeroed => ZEROED_ALLOCATIONS.fetch_add(1, Ordering::Relaxed),
+        AllocationKind::Alloc | AllocationKind::Free => 0,
+    };
+
+    // Fails while the thread is being torn down, leaving the allocation untagged
+    let tag = TAG.try_with(Cell::get).ok().flatten();
+    if matches!(tag, Some(AllocationTag::VecGrow | AllocationTag::VecShrink)) {
+        VEC_REALLOCS.fetch_add(1, Ordering::Relaxed);
+    }
+    pprof::on_alloc(tag, bytes_requested, time_taken.as_nanos() as u64);
+    callsite::on_alloc(bytes_requested);
+
+    if LOGGING.load(Ordering::Relaxed) {
+        if let Some(epoch) = EPOCH.get() {
+            let slot = LOG_COUNT.fetch_add(1, Ordering::Relaxed);
+            if slot < ALLOCATION_LOG_CAPACITY {
+                // Saturating: an allocation may have started just before the log
+                let since_epoch = start.saturating_duration_since(*epoch).as_nanos() as u64;
+                let offset = since_epoch.saturating_sub(LOG_START.load(Ordering::Relaxed));
+                LOG_OFFSETS[slot].store(offset, Ordering::Relaxed);
+                LOG_SIZES[slot].store(bytes_requested, Ordering::Relaxed);
+                LOG_TAGS[slot].store(AllocationTag::to_u8(tag), Ordering::Relaxed);
+                LOG_KINDS[slot].store(kind.to_u8(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Only queued: writing here could allocate, or find stderr already
+    // borrowed by the write this allocation is made for. The telemetry
+    // worker writes them, so a slow stderr never holds up the thread
+    // allocating either
+    if traces_event() {
+        telemetry::record(Record {
+            size: bytes_requested,
+            align: layout.align(),
+            timestamp: timestamp(start),
+            nanos: time_taken.as_nanos() as u64,
+            tag,
+            kind,
+        });
+    } else if CAPTURING.load(Ordering::Relaxed) && !telemetry::on_worker() {
+        count_in_frame(bytes_requested, time_taken.as_nanos() as u64);
+    }
+    ptr
+}
+
+/// Runs `f`, tagging the allocations it makes on this thread with `tag`.
+pub fn tagged<T>(tag: AllocationTag, f: impl FnOnce() -> T) -> T {
+    let previous = TAG.replace(Some(tag));
+    let result = f();
+    TAG.set(previous);
+    result
+}
+
+/// Starts or stops reporting allocations on stderr.
+pub fn set_capturing(enabled: bool) {
+    CAPTURING.store(enabled, Ordering::Relaxed);
+}
+
+/// Hands every allocation from now on straight to the backing allocator,
+/// with none of the timing, counting and reporting around it. The arena and
+/// the block pool must not be enabled, or their blocks would be freed to the
+/// wrong allocator.
+pub fn set_passthrough(enabled: bool) {
+    PASSTHROUGH.store(enabled, Ordering::Relaxed);
+}
+
+/// Traces the allocations reported one by one at `fidelity`, the others
+/// being counted in the summary of their frame.
+pub fn set_fidelity(fidelity: Fidelity) {
+    TRACE_EVERY.store(fidelity.trace_every(), Ordering::Relaxed);
+}
+
+/// Reports every allocation on its own line, as the trace tools read, rather
+/// than one summary line per frame.
+pub fn set_verbose(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn count_in_frame(bytes: usize, nanos: u64) {
+    FRAME_COUNT.fetch_add(1, Ordering::Relaxed);
+    FRAME_BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+    FRAME_NANOS.fetch_add(nanos, Ordering::Relaxed);
+    FRAME_MIN_NANOS.fetch_min(nanos, Ordering::Relaxed);
+    FRAME_MAX_NANOS.fetch_max(nanos, Ordering::Relaxed);
+}
+
+/// Allocations reported during a frame, when they are not reported one by one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameAllocations {
+    pub count: u64,
+    pub bytes: u64,
+
+    /// Shortest, longest and total time taken by the allocations
+    pub min_nanos: u64,
+    pub max_nanos: u64,
+    pub total_nanos: u64,
+}
+
+impl FrameAllocations {
+    pub fn mean_nanos(&self) -> u64 {
+        self.total_nanos.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// Writes the summary line of frame `frame`, a `#` comment the trace
+    /// tools skip.
+    pub fn write_summary<W: Write>(&self, frame: u64, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "# frame {}\t{} allocations\t{} bytes\t{} ns min\t{} ns mean\t{} ns max",
+            frame,
+            self.count,
+            self.bytes,
+            self.min_nanos,
+            self.mean_nanos(),
+            self.max_nanos
+        )
+    }
+}
+
+/// Returns the allocations counted since the last frame and starts counting
+/// the next one.
+pub fn finish_frame() -> FrameAllocations {
+    let count = FRAME_COUNT.swap(0, Ordering::Relaxed);
+    let min_nanos = FRAME_MIN_NANOS.swap(u64::MAX, Ordering::Relaxed);
+    FrameAllocations {
+        count,
+        bytes: FRAME_BYTES.swap(0, Ordering::Relaxed),
+        min_nanos: if count == 0 { 0 } else { min_nanos },
+        max_nanos: FRAME_MAX_NANOS.swap(0, Ordering::Relaxed),
+        total_nanos: FRAME_NANOS.swap(0, Ordering::Relaxed),
+    }
+}
+
+/// Ends frame `frame`, writing the summary of its allocations to the trace
+/// unless all of them are reported one by one or none at all, and the allocations
+/// queued when there is no telemetry worker to write them. Returns the
+/// allocations of the frame.
+pub fn report_frame(frame: u64) -> FrameAllocations {
+    telemetry::flush();
+    let allocations = finish_frame();
+    if !CAPTURING.load(Ordering::Relaxed) || (VERBOSE.load(Ordering::Relaxed) && TRACE_EVERY.load(Ordering::Relaxed) == 1) {
+        return allocations;
+    }
+    // Formatted on the stack, or the summary would count in the next frame
+    let mut buffer = [0u8; 160];
+    let capacity = buffer.len();
+    let written = {
+        let mut out = &mut buffer[..];
+        let _ = allocations.write_summary(frame, &mut out);
+        capacity - out.len()
+    };
+    if let Ok(text) = std::str::from_utf8(&buffer[..written]) {
+        telemetry::comment_or_drop(text);
+    }
+    allocations
+}
+
+/// Snapshot of the allocator counters at a point in time.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryStats {
+    /// Bytes currently allocated and not yet freed
+    pub live_bytes: usize,
+
+    /// Most bytes that were ever live at once
+    pub peak_live_bytes: usize,
+
+    /// Number of allocations made since the program started
+    pub allocations: u64,
+
+    /// Bytes requested by all those allocations, freed or not
+    pub allocated_bytes: u64,
+
+    /// Number of blocks freed since the program started
+    pub deallocations: u64,
+
+    /// Bytes of all those blocks
+    pub freed_bytes: u64,
+
+    /// Allocations made to grow or shrink the particle `Vec`
+    pub vec_reallocs: u64,
+
+    /// Of the allocations, those made by `realloc`, each also freeing a
+    /// block, and those made by `alloc_zeroed`
+    pub reallocations: u64,
+    pub zeroed_allocations: u64,
+
+    /// Time all those allocations took, in nanoseconds
+    pub allocation_nanos: u64,
+}
+
+/// Reads the counters, cheap enough to do every frame.
+pub fn memory_stats() -> MemoryStats {
+    MemoryStats {
+        live_bytes: LIVE_BYTES.load(Ordering::Relaxed),
+        peak_live_bytes: PEAK_LIVE_BYTES.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        freed_bytes: FREED_BYTES.load(Ordering::Relaxed),
+        vec_reallocs: VEC_REALLOCS.load(Ordering::Relaxed),
+        reallocations: REALLOCATIONS.load(Ordering::Relaxed),
+        zeroed_allocations: ZEROED_ALLOCATIONS.load(Ordering::Relaxed),
+        allocation_nanos: ALLOCATION_NANOS.load(Ordering::Relaxed),
+    }
+}
+
+/// Allocations made since the program started in each size class of
+/// `trace::size_bucket`, from 0 bytes up.
+pub fn size_histogram() -> [u64; SIZE_BUCKETS] {
+    std::array::from_fn(|bucket| SIZE_COUNTS[bucket].load(Ordering::Relaxed))
+}
+
+/// Allocations made since the program started in each power-of-two class of
+/// nanoseconds taken, in the classes of `size_histogram`.
+pub fn latency_histogram() -> [u64; SIZE_BUCKETS] {
+    std::array::from_fn(|bucket| LATENCY_COUNTS[bucket].load(Ordering::Relaxed))
+}
+
+/// The allocations and frees of one thread since the program started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadAllocations {
+    pub role: ThreadRole,
+
+    /// In the order the threads first allocated, from 0
+    pub slot: usize,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+    pub allocation_nanos: u64,
+    pub frees: u64,
+}
+
+impl ThreadAllocations {
+    /// What was counted between `before` and `self`, of the same thread.
+    pub fn since(&self, before: &ThreadAllocations) -> ThreadAllocations {
+        ThreadAllocations {
+            allocations: self.allocations - before.allocations,
+            allocated_bytes: self.allocated_bytes - before.allocated_bytes,
+            allocation_nanos: self.allocation_nanos - before.allocation_nanos,
+            frees: self.frees - before.frees,
+            ..*self
+        }
+    }
+
+    /// Like `main` or `rayon-3`, and `thread 5` for threads that never said
+    /// what they do.
+    pub fn label(&self) -> String {
+        match self.role {
+            ThreadRole::Unknown => format!("thread {}", self.slot),
+            ThreadRole::Main => "main".to_string(),
+            ThreadRole::Rayon(index) => format!("rayon-{}", index),
+            ThreadRole::Telemetry => "telemetry".to_string(),
+            ThreadRole::Simulation => "simulation".to_string(),
+        }
+    }
+}
+
+/// The counters of every thread that allocated so far, by slot. The last
+/// slot, when shared by several threads, is of no role.
+pub fn thread_stats() -> Vec<ThreadAllocations> {
+    let threads = NEXT_THREAD_SLOT.load(Ordering::Relaxed);
+    (0..threads.min(THREAD_SLOTS))
+        .map(|slot| ThreadAllocations {
+            role: match THREAD_ROLES[slot].load(Ordering::Relaxed) {
+                _ if slot == THREAD_SLOTS - 1 && threads > THREAD_SLOTS => ThreadRole::Unknown,
+                1 => ThreadRole::Main,
+                2 => ThreadRole::Rayon(THREAD_INDEXES[slot].load(Ordering::Relaxed)),
+                3 => ThreadRole::Telemetry,
+                4 => ThreadRole::Simulation,
+                _ => ThreadRole::Unknown,
+            },
+            slot,
+            allocations: THREAD_ALLOCATIONS[slot].load(Ordering::Relaxed),
+            allocated_bytes: THREAD_BYTES[slot].load(Ordering::Relaxed),
+            allocation_nanos: THREAD_NANOS[slot].load(Ordering::Relaxed),
+            frees: THREAD_FREES[slot].load(Ordering::Relaxed),
+        })
+        .collect()
+}
+
+/// An allocation made while the allocation log was running.
+#[derive(Debug, Clone, Copy)]
+pub struct LoggedAllocation {
+    /// Time since the log was started
+    pub offset: Duration,
+    pub size: usize,
+    pub tag: Option<AllocationTag>,
+    pub kind: AllocationKind,
+}
+
+/// Every allocation made between `start_allocation_log` and `finish_allocation_log`.
+#[derive(Debug, Clone, Default)]
+pub struct AllocationLog {
+    /// How long the log ran
+    pub span: Duration,
+    pub allocations: Vec<LoggedAllocation>,
+
+    /// Allocations that didn't fit in the log and were only counted
+    pub missed: usize,
+}
+
+/// Starts the clock allocation timestamps count from, if not yet started.
+pub fn start_clock() {
+    EPOCH.get_or_init(Instant::now);
+}
+
+/// Starts logging every allocation with its time, discarding the previous log.
+pub fn start_allocation_log() {
+    let epoch = EPOCH.get_or_init(Instant::now);
+    LOG_COUNT.store(0, Ordering::Relaxed);
+    LOG_START.store(epoch.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    LOGGING.store(true, Ordering::Relaxed);
+}
+
+/// Stops logging and returns what was logged since `start_allocation_log`.
+pub fn finish_allocation_log() -> AllocationLog {
+    if !LOGGING.swap(false, Ordering::Relaxed) {
+        return AllocationLog::default();
+    }
+
+    let epoch = EPOCH.get_or_init(Instant::now);
+    let span = (epoch.elapsed().as_nanos() as u64).saturating_sub(LOG_START.load(Ordering::Relaxed));
+    let count = LOG_COUNT.load(Ordering::Relaxed);
+    let stored = count.min(ALLOCATION_LOG_CAPACITY);
+
+    // Logging is off, so the log can allocate its own copy
+    let allocations = (0..stored)
+        .map(|slot| LoggedAllocation {
+            offset: Duration::from_nanos(LOG_OFFSETS[slot].load(Ordering::Relaxed)),
+            size: LOG_SIZES[slot].load(Ordering::Relaxed),
+            tag: AllocationTag::from_u8(LOG_TAGS[slot].load(Ordering::Relaxed)),
+            kind: AllocationKind::from_u8(LOG_KINDS[slot].load(Ordering::Relaxed)),
+        })
+        .collect();
+    AllocationLog { span: Duration::from_nanos(span), allocations, missed: count - stored }
+}
@@ -0,0 +1,83 @@
+use crate::params::{ParamVisitor, Tunable};
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// A slow cycle from day to night and back, changing the background and
+/// tinting the particles as they spawn, so long runs don't look the same
+/// from one hour to the next.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmbientCycle {
+    /// Turns from one noon to the next
+    #[serde(default = "default_period")]
+    pub period: u64,
+
+    /// Turn of the cycle the run starts at, 0 being noon
+    #[serde(default)]
+    pub offset: u64,
+
+    #[serde(default = "default_day")]
+    pub day: AmbientLook,
+
+    #[serde(default = "default_night")]
+    pub night: AmbientLook,
+}
+
+/// How the scene looks at one end of the cycle.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmbientLook {
+    pub background: [f32; 4],
+
+    /// Multiplies the red, green and blue of every particle spawned
+    #[serde(default = "no_tint")]
+    pub tint: [f32; 3],
+}
+
+fn default_period() -> u64 {
+    // A minute at 60 turns per second
+    3600
+}
+
+fn default_day() -> AmbientLook {
+    AmbientLook { background: crate::BACKGROUND, tint: no_tint() }
+}
+
+fn default_night() -> AmbientLook {
+    AmbientLook { background: [0.02, 0.03, 0.08, 1.0], tint: [0.6, 0.7, 1.0] }
+}
+
+fn no_tint() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl AmbientCycle {
+    /// How far into the night the cycle is on `turn`, from 0 at noon to 1 at
+    /// midnight, easing in and out along a cosine.
+    pub fn darkness(&self, turn: u64) -> f32 {
+        let phase = ((turn + self.offset) % self.period.max(1)) as f64 / self.period.max(1) as f64;
+        ((1.0 - (phase * TAU).cos()) / 2.0) as f32
+    }
+
+    pub fn background(&self, turn: u64) -> [f32; 4] {
+        mix(self.day.background, self.night.background, self.darkness(turn))
+    }
+
+    /// `color` as tinted by the cycle on `turn`, keeping its alpha.
+    pub fn tint(&self, color: [f32; 4], turn: u64) -> [f32; 4] {
+        let darkness = self.darkness(turn);
+        let [r, g, b] = [0, 1, 2].map(|i| self.day.tint[i] + (self.night.tint[i] - self.day.tint[i]) * darkness);
+        [color[0] * r, color[1] * g, color[2] * b, color[3]]
+    }
+}
+
+impl Tunable for AmbientCycle {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.integer("period", &mut self.period, 1..=u64::MAX);
+        visitor.integer("offset", &mut self.offset, 0..=u64::MAX);
+    }
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], amount: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * amount)
+}
@@ -0,0 +1,276 @@
+use crate::allocator::BACKING;
+use crate::pool::{BLOCK_ALIGN, BLOCK_SIZE};
+use std::alloc::{GlobalAlloc, Layout};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+/// Size of the chunks the arena takes from the backing allocator. They are
+/// aligned to it, so a block finds the chunk it was bumped out of by masking
+/// its address.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+// Start of every chunk, with the lock held to read or write it
+struct Header {
+    // Blocks bumped out of the chunk since it was last empty, and those of
+    // them not freed yet
+    bumped: usize,
+    live: usize,
+
+    // Next chunk on the list of empty ones
+    next: *mut u8,
+}
+
+// The blocks of a chunk follow its header
+const FIRST_BLOCK: usize = std::mem::size_of::<Header>().next_multiple_of(BLOCK_ALIGN);
+pub const CHUNK_BLOCKS: usize = (CHUNK_SIZE - FIRST_BLOCK) / BLOCK_SIZE;
+
+// Addresses of every chunk taken, hashed into a table the allocator can
+// search without allocating, to tell arena blocks from the others when they
+// are freed. Chunks are never given back, so entries are never removed; the
+// table is kept at most half full, past which blocks come from the backing
+// allocator again
+const TABLE_SLOTS: usize = 8192;
+static CHUNKS: [AtomicUsize; TABLE_SLOTS] = [const { AtomicUsize::new(0) }; TABLE_SLOTS];
+
+// Whether particle-sized allocations are bumped out of the arena. Blocks
+// freed once it is turned off still find their chunk, as long as one was
+// ever taken
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Chunk blocks are bumped out of, and the list of empty chunks, guarded by
+// a spin lock like the block pool
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static CURRENT: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+static EMPTY: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
+static CHUNK_COUNT: AtomicUsize = AtomicUsize::new(0);
+static EMPTY_CHUNKS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+static DEAD_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+static SERVED: AtomicU64 = AtomicU64::new(0);
+static RECLAIMED: AtomicU64 = AtomicU64::new(0);
+
+/// Bumps every particle-sized allocation out of the arena from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Hands particle-sized allocations back to the block pool or the backing
+/// allocator from now on; the blocks already bumped out of the arena go
+/// back to it when freed.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether a block of this layout being freed may come from the arena, as
+/// it may once any chunk was taken, enabled or not.
+pub fn may_hold(layout: Layout) -> bool {
+    CHUNK_COUNT.load(Ordering::Relaxed) > 0 && layout.size() == BLOCK_SIZE && layout.align() <= BLOCK_ALIGN
+}
+
+/// Whether the arena serves allocations with this layout, the layouts of
+/// the block pool.
+pub fn serves(layout: Layout) -> bool {
+    is_enabled() && layout.size() == BLOCK_SIZE && layout.align() <= BLOCK_ALIGN
+}
+
+/// Whether `block` was bumped out of one of the chunks of the arena.
+pub fn owns(block: *mut u8) -> bool {
+    let chunk = block as usize & !(CHUNK_SIZE - 1);
+    let mut slot = slot(chunk);
+    loop {
+        match CHUNKS[slot].load(Ordering::Acquire) {
+            0 => return false,
+            taken if taken == chunk => return true,
+            _ => slot = (slot + 1) % TABLE_SLOTS,
+        }
+    }
+}
+
+/// Takes chunks from the backing allocator up front until at least
+/// `blocks` blocks fit in empty ones, so that many particles can be
+/// allocated without the arena taking any more memory. Stops early when
+/// out of memory.
+pub fn prefill(blocks: usize) {
+    lock();
+    while EMPTY_CHUNKS.load(Ordering::Relaxed) * CHUNK_BLOCKS < blocks {
+        // Safe: the new chunk is linked in front of the empty ones, under the lock
+        unsafe {
+            let chunk = new_chunk();
+            if chunk.is_null() {
+                break;
+            }
+            push_empty(chunk);
+        }
+    }
+    unlock();
+}
+
+/// Bumps a block out of the current chunk, moving on to an empty chunk when
+/// it is full, or to a new one from the backing allocator when none is.
+/// Falls back to the backing allocator for `layout` once the table of
+/// chunks is full.
+///
+/// # Safety
+/// Called from the global allocator, for a layout the arena `serves`.
+pub unsafe fn alloc(layout: Layout) -> *mut u8 {
+    lock();
+    let mut chunk = CURRENT.load(Ordering::Relaxed);
+    if chunk.is_null() || (*header(chunk)).bumped == CHUNK_BLOCKS {
+        chunk = pop_empty();
+        if chunk.is_null() {
+            chunk = new_chunk();
+        }
+        if !chunk.is_null() {
+            CURRENT.store(chunk, Ordering::Relaxed);
+        }
+    }
+    let block = if chunk.is_null() {
+        ptr::null_mut()
+    } else {
+        let header = &mut *header(chunk);
+        let block = chunk.add(FIRST_BLOCK + header.bumped * BLOCK_SIZE);
+        header.bumped += 1;
+        header.live += 1;
+        LIVE_BLOCKS.fetch_add(1, Ordering::Relaxed);
+        SERVED.fetch_add(1, Ordering::Relaxed);
+        block
+    };
+    unlock();
+    if block.is_null() {
+        BACKING.alloc(layout)
+    } else {
+        block
+    }
+}
+
+/// Counts a block of a chunk dead. Its memory is only used again once every
+/// block bumped out of the chunk is dead, when the chunk starts over.
+///
+/// # Safety
+/// The arena `owns` `block`, which is no longer used.
+pub unsafe fn dealloc(block: *mut u8) {
+    lock();
+    let chunk = (block as usize & !(CHUNK_SIZE - 1)) as *mut u8;
+    let header = &mut *header(chunk);
+    header.live -= 1;
+    LIVE_BLOCKS.fetch_sub(1, Ordering::Relaxed);
+    if header.live == 0 {
+        DEAD_BLOCKS.fetch_sub(header.bumped - 1, Ordering::Relaxed);
+        header.bumped = 0;
+        RECLAIMED.fetch_add(1, Ordering::Relaxed);
+        // The current chunk carries on from its start
+        if chunk != CURRENT.load(Ordering::Relaxed) {
+            push_empty(chunk);
+        }
+    } else {
+        DEAD_BLOCKS.fetch_add(1, Ordering::Relaxed);
+    }
+    unlock();
+}
+
+unsafe fn header(chunk: *mut u8) -> *mut Header {
+    chunk as *mut Header
+}
+
+// Takes a chunk from the backing allocator, with the lock held. Returns null
+// when out of memory or when the table of chunks is half full
+unsafe fn new_chunk() -> *mut u8 {
+    if CHUNK_COUNT.load(Ordering::Relaxed) >= TABLE_SLOTS / 2 {
+        return ptr::null_mut();
+    }
+    let chunk = BACKING.alloc(Layout::from_size_align_unchecked(CHUNK_SIZE, CHUNK_SIZE));
+    if chunk.is_null() {
+        return chunk;
+    }
+    ptr::write(header(chunk), Header { bumped: 0, live: 0, next: ptr::null_mut() });
+
+    let mut slot = slot(chunk as usize);
+    while CHUNKS[slot].load(Ordering::Relaxed) != 0 {
+        slot = (slot + 1) % TABLE_SLOTS;
+    }
+    CHUNKS[slot].store(chunk as usize, Ordering::Release);
+    CHUNK_COUNT.fetch_add(1, Ordering::Relaxed);
+    chunk
+}
+
+// With the lock held
+unsafe fn push_empty(chunk: *mut u8) {
+    (*header(chunk)).next = EMPTY.load(Ordering::Relaxed);
+    EMPTY.store(chunk, Ordering::Relaxed);
+    EMPTY_CHUNKS.fetch_add(1, Ordering::Relaxed);
+}
+
+// With the lock held; null when no chunk is empty
+unsafe fn pop_empty() -> *mut u8 {
+    let chunk = EMPTY.load(Ordering::Relaxed);
+    if !chunk.is_null() {
+        EMPTY.store((*header(chunk)).next, Ordering::Relaxed);
+        EMPTY_CHUNKS.fetch_sub(1, Ordering::Relaxed);
+    }
+    chunk
+}
+
+fn slot(chunk: usize) -> usize {
+    (chunk / CHUNK_SIZE).wrapping_mul(0x9e37_79b9) % TABLE_SLOTS
+}
+
+fn lock() {
+    while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        std::hint::spin_loop();
+    }
+}
+
+fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+/// Internal counters of the arena.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaStats {
+    /// Chunks taken from the backing allocator, and those of them empty
+    pub chunks: usize,
+    pub empty_chunks: usize,
+
+    /// Blocks not freed yet, and freed ones whose chunk still holds live
+    /// blocks, so their memory can't be used again yet
+    pub live_blocks: usize,
+    pub dead_blocks: usize,
+
+    /// Allocations bumped out of the chunks
+    pub served: u64,
+
+    /// Times a chunk emptied out and started over
+    pub reclaimed: u64,
+}
+
+pub fn arena_stats() -> ArenaStats {
+    ArenaStats {
+        chunks: CHUNK_COUNT.load(Ordering::Relaxed),
+        empty_chunks: EMPTY_CHUNKS.load(Ordering::Relaxed),
+        live_blocks: LIVE_BLOCKS.load(Ordering::Relaxed),
+        dead_blocks: DEAD_BLOCKS.load(Ordering::Relaxed),
+        served: SERVED.load(Ordering::Relaxed),
+        reclaimed: RECLAIMED.load(Ordering::Relaxed),
+    }
+}
+
+impl ArenaStats {
+    /// Short summary of the counters, for the window title and reports.
+    pub fn describe(&self) -> String {
+        format!(
+            "arena: {} chunks of {} KiB ({} empty), {} live blocks, {} dead held, {} served, {} chunks reclaimed",
+            self.chunks,
+            CHUNK_SIZE / 1024,
+            self.empty_chunks,
+            self.live_blocks,
+            self.dead_blocks,
+            self.served,
+            self.reclaimed
+        )
+    }
+}
@@ -0,0 +1,218 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// Environment variable naming the backend, read by the first allocation.
+pub const ENV_VAR: &str = "MEMORY_PARTICLES_ALLOCATOR";
+
+// Where the choice of backend stands: not made yet, being made by the first
+// allocation, or made, as 2 plus the index of the backend
+const UNRESOLVED: u8 = 0;
+const RESOLVING: u8 = 1;
+const RESOLVED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNRESOLVED);
+
+// The backend asked for that couldn't be loaded, as 1 plus its index
+static FAILED: AtomicU8 = AtomicU8::new(0);
+
+// Entry points of the backend loaded, as addresses
+static ALLOC: AtomicUsize = AtomicUsize::new(0);
+static ZEROED: AtomicUsize = AtomicUsize::new(0);
+static REALLOC: AtomicUsize = AtomicUsize::new(0);
+static FREE: AtomicUsize = AtomicUsize::new(0);
+
+/// The allocator underneath the `ReportingAllocator`, which every request
+/// is deferred to once timed and counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum AllocatorBackend {
+    /// The allocator of the platform, `malloc` on Unix
+    #[default]
+    System,
+
+    /// jemalloc, loaded from `libjemalloc.so.2` at startup
+    Jemalloc,
+
+    /// mimalloc, loaded from `libmimalloc.so.2` at startup
+    Mimalloc,
+}
+
+const BACKENDS: [AllocatorBackend; 3] = [AllocatorBackend::System, AllocatorBackend::Jemalloc, AllocatorBackend::Mimalloc];
+
+impl AllocatorBackend {
+    pub fn name(self) -> &'static str {
+        match self {
+            AllocatorBackend::System => "system",
+            AllocatorBackend::Jemalloc => "jemalloc",
+            AllocatorBackend::Mimalloc => "mimalloc",
+        }
+    }
+
+    /// Shared libraries the backend is looked for in, in order.
+    pub fn libraries(self) -> &'static [&'static str] {
+        match self {
+            AllocatorBackend::System => &[],
+            AllocatorBackend::Jemalloc => &["libjemalloc.so.2", "libjemalloc.so", "libjemalloc.dylib"],
+            AllocatorBackend::Mimalloc => &["libmimalloc.so.2", "libmimalloc.so", "libmimalloc.dylib"],
+        }
+    }
+
+    fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// The backend serving the allocations of this process, chosen by the first
+/// of them from `MEMORY_PARTICLES_ALLOCATOR` and kept to the end, since
+/// blocks must be freed by the allocator they came from.
+pub fn selected() -> AllocatorBackend {
+    loop {
+        match STATE.load(Ordering::Acquire) {
+            UNRESOLVED => {
+                if STATE.compare_exchange(UNRESOLVED, RESOLVING, Ordering::Acquire, Ordering::Acquire).is_ok() {
+                    let backend = resolve();
+                    STATE.store(RESOLVED + backend.index(), Ordering::Release);
+                    return backend;
+                }
+            }
+            // Only while another thread makes the first allocation, which
+            // loading a library doesn't make through this allocator
+            RESOLVING => std::hint::spin_loop(),
+            state => return BACKENDS[(state - RESOLVED) as usize],
+        }
+    }
+}
+
+/// The backend `MEMORY_PARTICLES_ALLOCATOR` asked for when none of its
+/// libraries could be loaded, the system allocator serving in its place.
+pub fn load_error() -> Option<AllocatorBackend> {
+    selected();
+    match FAILED.load(Ordering::Relaxed) {
+        0 => None,
+        failed => Some(BACKENDS[failed as usize - 1]),
+    }
+}
+
+// Reads the backend asked for without allocating, this being the first
+// allocation, and loads it
+fn resolve() -> AllocatorBackend {
+    let Some(backend) = requested() else {
+        return AllocatorBackend::System;
+    };
+    if backend == AllocatorBackend::System || load(backend) {
+        return backend;
+    }
+    FAILED.store(backend.index() + 1, Ordering::Relaxed);
+    AllocatorBackend::System
+}
+
+#[cfg(unix)]
+fn requested() -> Option<AllocatorBackend> {
+    let mut name = [0u8; 32];
+    name[..ENV_VAR.len()].copy_from_slice(ENV_VAR.as_bytes());
+    let value = unsafe { libc::getenv(name.as_ptr().cast()) };
+    if value.is_null() {
+        return None;
+    }
+    let value = unsafe { std::ffi::CStr::from_ptr(value) }.to_bytes();
+    BACKENDS.into_iter().find(|backend| backend.name().as_bytes().eq_ignore_ascii_case(value))
+}
+
+// Without `getenv` before the first allocation, the system allocator serves
+#[cfg(not(unix))]
+fn requested() -> Option<AllocatorBackend> {
+    None
+}
+
+// Opens the first library of `backend` there is, kept to itself so its
+// `malloc` doesn't replace that of the rest of the process, and takes its
+// entry points
+#[cfg(unix)]
+fn load(backend: AllocatorBackend) -> bool {
+    let symbols: [&[u8]; 4] = match backend {
+        AllocatorBackend::System => return true,
+        AllocatorBackend::Jemalloc => [b"mallocx\0", b"mallocx\0", b"rallocx\0", b"sdallocx\0"],
+        AllocatorBackend::Mimalloc => [b"mi_malloc_aligned\0", b"mi_zalloc_aligned\0", b"mi_realloc_aligned\0", b"mi_free\0"],
+    };
+    for library in backend.libraries() {
+        let mut path = [0u8; 64];
+        path[..library.len()].copy_from_slice(library.as_bytes());
+        let handle = unsafe { libc::dlopen(path.as_ptr().cast(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            continue;
+        }
+        let entries = symbols.map(|symbol| unsafe { libc::dlsym(handle, symbol.as_ptr().cast()) } as usize);
+        if entries.contains(&0) {
+            unsafe { libc::dlclose(handle) };
+            continue;
+        }
+        for (slot, entry) in [&ALLOC, &ZEROED, &REALLOC, &FREE].into_iter().zip(entries) {
+            slot.store(entry, Ordering::Relaxed);
+        }
+        return true;
+    }
+    false
+}
+
+#[cfg(not(unix))]
+fn load(backend: AllocatorBackend) -> bool {
+    backend == AllocatorBackend::System
+}
+
+// Flags of jemalloc's `mallocx` family: the alignment as its log2, and
+// zeroed memory
+fn jemalloc_flags(layout: Layout) -> i32 {
+    layout.align().trailing_zeros() as i32
+}
+const MALLOCX_ZERO: i32 = 0x40;
+
+type JemallocAlloc = unsafe extern "C" fn(usize, i32) -> *mut u8;
+type JemallocRealloc = unsafe extern "C" fn(*mut u8, usize, i32) -> *mut u8;
+type JemallocFree = unsafe extern "C" fn(*mut u8, usize, i32);
+type MimallocAlloc = unsafe extern "C" fn(usize, usize) -> *mut u8;
+type MimallocRealloc = unsafe extern "C" fn(*mut u8, usize, usize) -> *mut u8;
+type MimallocFree = unsafe extern "C" fn(*mut u8);
+
+// Only called once `selected` named the backend, whose entry point `slot`
+// then holds
+unsafe fn entry<F: Copy>(slot: &AtomicUsize) -> F {
+    let address = slot.load(Ordering::Relaxed);
+    std::mem::transmute_copy(&address)
+}
+
+/// The selected backend as a `GlobalAlloc`, for what allocates underneath
+/// the counters: the arena, the block pool and `trace replay`.
+pub struct Backing;
+
+unsafe impl GlobalAlloc for Backing {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match selected() {
+            AllocatorBackend::System => System.alloc(layout),
+            AllocatorBackend::Jemalloc => entry::<JemallocAlloc>(&ALLOC)(layout.size(), jemalloc_flags(layout)),
+            AllocatorBackend::Mimalloc => entry::<MimallocAlloc>(&ALLOC)(layout.size(), layout.align()),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match selected() {
+            AllocatorBackend::System => System.alloc_zeroed(layout),
+            AllocatorBackend::Jemalloc => entry::<JemallocAlloc>(&ZEROED)(layout.size(), jemalloc_flags(layout) | MALLOCX_ZERO),
+            AllocatorBackend::Mimalloc => entry::<MimallocAlloc>(&ZEROED)(layout.size(), layout.align()),
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        match selected() {
+            AllocatorBackend::System => System.realloc(ptr, layout, new_size),
+            AllocatorBackend::Jemalloc => entry::<JemallocRealloc>(&REALLOC)(ptr, new_size, jemalloc_flags(layout)),
+            AllocatorBackend::Mimalloc => entry::<MimallocRealloc>(&REALLOC)(ptr, new_size, layout.align()),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match selected() {
+            AllocatorBackend::System => System.dealloc(ptr, layout),
+            AllocatorBackend::Jemalloc => entry::<JemallocFree>(&FREE)(ptr, layout.size(), jemalloc_flags(layout)),
+            AllocatorBackend::Mimalloc => entry::<MimallocFree>(&FREE)(ptr),
+        }
+    }
+}
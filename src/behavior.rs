@@ -0,0 +1,339 @@
+use crate::fade::Fade;
+use crate::spatial::SpatialHash;
+use crate::Particle;
+use graphics::math::Vec2d;
+use rand::Rng;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// A state of the behavior state machine, as written in the scene file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateSpec {
+    pub name: String,
+
+    /// Turns spent in this state, forever when missing
+    pub duration: Option<u32>,
+
+    /// State entered when `duration` is over; the particle is removed when missing
+    pub next: Option<String>,
+
+    /// Added to the velocity every turn, like gravity or thrust
+    #[serde(default)]
+    pub force: Vec2d<f64>,
+
+    /// Fraction of the velocity lost every turn
+    #[serde(default)]
+    pub drag: f64,
+
+    /// Random velocity of up to this speed added when entering the state,
+    /// which throws particles in every direction like an explosion
+    #[serde(default)]
+    pub scatter: f64,
+
+    /// How the particle looks from this state on, unchanged when missing
+    pub color: Option<[f32; 4]>,
+    pub size: Option<f64>,
+    pub fade: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    spec: StateSpec,
+    next: Option<usize>,
+}
+
+/// Sequence of states every particle goes through, like rising, exploding and
+/// fading out as an ember, each with its own forces and looks.
+/// Particles are left alone when the machine has no states.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<StateSpec>")]
+pub struct StateMachine {
+    states: Vec<State>,
+}
+
+impl TryFrom<Vec<StateSpec>> for StateMachine {
+    type Error = String;
+
+    fn try_from(specs: Vec<StateSpec>) -> Result<StateMachine, String> {
+        let find = |name: &str| specs.iter().position(|spec| spec.name == name);
+
+        let mut states = Vec::with_capacity(specs.len());
+        for (i, spec) in specs.iter().enumerate() {
+            if find(&spec.name) != Some(i) {
+                return Err(format!("state `{}` is defined twice", spec.name));
+            }
+            let next = match &spec.next {
+                Some(name) => Some(find(name).ok_or_else(|| format!("state `{}` goes to unknown state `{}`", spec.name, name))?),
+                None => None,
+            };
+            states.push(State { spec: spec.clone(), next });
+        }
+        Ok(StateMachine { states })
+    }
+}
+
+impl StateMachine {
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Name of the state a particle is in, for inspecting particles.
+    pub fn state_name(&self, particle: &Particle) -> Option<&str> {
+        self.name_of(particle.state)
+    }
+
+    /// Name of the state at `index`.
+    pub fn name_of(&self, index: usize) -> Option<&str> {
+        self.states.get(index).map(|state| state.spec.name.as_str())
+    }
+
+    /// Puts a newly spawned particle in the first state.
+    pub fn start<R: Rng>(&self, particle: &mut Particle, rng: &mut R) {
+        if !self.is_empty() {
+            self.enter(0, particle, rng);
+        }
+    }
+
+    /// Applies the forces of the particle's state for one turn, moving it on
+    /// to the next state when its time is up. Marks the particle as expired
+    /// once it leaves a state without a next one.
+    pub fn apply<R: Rng>(&self, particle: &mut Particle, rng: &mut R) {
+        let Some(state) = self.states.get(particle.state) else {
+            return;
+        };
+
+        let spec = &state.spec;
+        particle.velocity[0] = (particle.velocity[0] + spec.force[0]) * (1.0 - spec.drag);
+        particle.velocity[1] = (particle.velocity[1] + spec.force[1]) * (1.0 - spec.drag);
+
+        particle.state_turns += 1;
+        if spec.duration.is_some_and(|duration| particle.state_turns >= duration) {
+            match state.next {
+                Some(next) => self.enter(next, particle, rng),
+                None => particle.expired = true,
+            }
+        }
+    }
+
+    fn enter<R: Rng>(&self, index: usize, particle: &mut Particle, rng: &mut R) {
+        let spec = &self.states[index].spec;
+        particle.state = index;
+        particle.state_turns = 0;
+
+        if spec.scatter > 0.0 {
+            let angle = rng.gen_range(0.0..TAU);
+            let speed = rng.gen_range(0.0..=spec.scatter);
+            particle.velocity[0] += angle.cos() * speed;
+            particle.velocity[1] += angle.sin() * speed;
+        }
+        if let Some(color) = spec.color {
+            particle.color = color;
+        }
+        if let Some(size) = spec.size {
+            particle.width = size;
+            particle.height = size;
+        }
+        if let Some(fade) = spec.fade {
+            particle.fade = Fade::Multiply(fade);
+        }
+    }
+}
+
+/// Dynamics of its own a program using the library gives every particle,
+/// on top of the forces, zones and states of the scene, with
+/// `World::add_behavior`. `apply` runs once a turn for every particle, after
+/// the state machine and before the particle moves, with `dt` the length of
+/// the turn in turns of `BASE_TURN_RATE`.
+///
+/// Behaviors run on the rayon thread pool when the world is parallel, so
+/// they must be `Send + Sync`, and they get no random generator: to keep
+/// seeded runs the same, what they do may only depend on the particle and
+/// on `world`. Pinned particles get them too, but aren't moved by the
+/// velocity they are given.
+pub trait Behavior: Send + Sync {
+    /// Name of the behavior, for the particles it breaks.
+    fn name(&self) -> &'static str;
+
+    /// How far a particle looks for the others, for behaviors reading
+    /// `WorldView::neighbors`, none for those never reading it. The world only
+    /// gathers where the particles are when one of its behaviors asks.
+    fn neighbor_radius(&self) -> Option<f64> {
+        None
+    }
+
+    fn apply(&self, particle: &mut Particle, world: &WorldView, dt: f64);
+}
+
+/// A particle as the others see it in `WorldView::neighbors`: as it was
+/// before the behaviors of the turn ran.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    pub center: Vec2d<f64>,
+    pub velocity: Vec2d<f64>,
+}
+
+impl Neighbor {
+    pub fn of(particle: &Particle) -> Neighbor {
+        Neighbor { center: center(particle), velocity: particle.velocity }
+    }
+}
+
+/// What a `Behavior` sees of the world while the particles move.
+#[derive(Clone, Copy)]
+pub struct WorldView<'a> {
+    pub width: f64,
+    pub height: f64,
+    pub turn: u64,
+    neighbors: Option<(&'a SpatialHash, &'a [Neighbor])>,
+}
+
+impl<'a> WorldView<'a> {
+    /// A world of `width` by `height` on `turn`, without neighbors.
+    pub fn new(width: f64, height: f64, turn: u64) -> WorldView<'a> {
+        WorldView { width, height, turn, neighbors: None }
+    }
+
+    /// The world along with the `neighbors` of every particle, put in the
+    /// cells of `grid` by their centers.
+    pub fn with_neighbors(self, grid: &'a SpatialHash, neighbors: &'a [Neighbor]) -> WorldView<'a> {
+        WorldView { neighbors: Some((grid, neighbors)), ..self }
+    }
+
+    /// The particles closer than `radius` to `point`, which are those of
+    /// `Behavior::neighbor_radius` at most; the particle at `point` itself
+    /// is among them. None when no behavior asked for neighbors.
+    pub fn neighbors(&self, point: Vec2d<f64>, radius: f64) -> impl Iterator<Item = &'a Neighbor> + 'a {
+        self.neighbors.into_iter().flat_map(move |(grid, neighbors)| {
+            grid.near(point).map(|index| &neighbors[index as usize]).filter(move |neighbor| {
+                (neighbor.center[0] - point[0]).hypot(neighbor.center[1] - point[1]) < radius
+            })
+        })
+    }
+}
+
+/// Center of a particle, which the neighbors of behaviors are found around.
+pub fn center(particle: &Particle) -> Vec2d<f64> {
+    [particle.position[0] + particle.width / 2.0, particle.position[1] + particle.height / 2.0]
+}
+
+/// Takes `rate` off the alpha of the particle every turn, fading it out in
+/// `1 / rate` turns whatever its fade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FadeOut {
+    pub rate: f32,
+}
+
+impl Behavior for FadeOut {
+    fn name(&self) -> &'static str {
+        "fade out"
+    }
+
+    fn apply(&self, particle: &mut Particle, _: &WorldView, dt: f64) {
+        particle.color[3] = (particle.color[3] - self.rate * dt as f32).max(0.0);
+    }
+}
+
+/// The particle loses `coefficient` of its velocity every turn, like the
+/// drag of the scene but kept to the particles of a program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drag {
+    pub coefficient: f64,
+}
+
+impl Behavior for Drag {
+    fn name(&self) -> &'static str {
+        "drag"
+    }
+
+    fn apply(&self, particle: &mut Particle, _: &WorldView, dt: f64) {
+        let kept = (1.0 - self.coefficient).clamp(0.0, 1.0).powf(dt);
+        particle.velocity = [particle.velocity[0] * kept, particle.velocity[1] * kept];
+    }
+}
+
+/// Swirls the particles along a smooth field of eddies about `scale`
+/// pixels wide, drifting over `period` turns, with accelerations of up to
+/// `strength`. The field only depends on the place and the turn, so
+/// particles close together swirl together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Turbulence {
+    pub strength: f64,
+    pub scale: f64,
+    pub period: u64,
+}
+
+impl Behavior for Turbulence {
+    fn name(&self) -> &'static str {
+        "turbulence"
+    }
+
+    fn apply(&self, particle: &mut Particle, world: &WorldView, dt: f64) {
+        let [x, y] = center(particle);
+        let phase = TAU * (world.turn % self.period.max(1)) as f64 / self.period.max(1) as f64;
+        let (u, v) = (x / self.scale.max(1.0), y / self.scale.max(1.0));
+        let angle = TAU * ((u + phase.sin()).sin() + (v + phase.cos()).cos() + (u - v).sin() / 2.0);
+        particle.velocity[0] += angle.cos() * self.strength * dt;
+        particle.velocity[1] += angle.sin() * self.strength * dt;
+    }
+}
+
+/// Boids flocking: particles steer towards the middle of the others within
+/// `radius` by `cohesion`, towards their mean velocity by `alignment`, and
+/// away from those within `separation_radius` by `separation`. Scenes turn
+/// it on with a `[flocking]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flocking {
+    pub radius: f64,
+    pub cohesion: f64,
+    pub alignment: f64,
+    pub separation: f64,
+    pub separation_radius: f64,
+}
+
+impl Default for Flocking {
+    fn default() -> Flocking {
+        Flocking { radius: 40.0, cohesion: 0.005, alignment: 0.05, separation: 0.05, separation_radius: 12.0 }
+    }
+}
+
+impl Behavior for Flocking {
+    fn name(&self) -> &'static str {
+        "flocking"
+    }
+
+    fn neighbor_radius(&self) -> Option<f64> {
+        Some(self.radius.max(self.separation_radius))
+    }
+
+    fn apply(&self, particle: &mut Particle, world: &WorldView, dt: f64) {
+        let here = center(particle);
+        let (mut count, mut middle, mut heading, mut away) = (0.0, [0.0; 2], [0.0; 2], [0.0; 2]);
+        for neighbor in world.neighbors(here, self.radius.max(self.separation_radius)) {
+            let offset = [neighbor.center[0] - here[0], neighbor.center[1] - here[1]];
+            let distance = offset[0].hypot(offset[1]);
+            // The particle itself, or one right on top of it
+            if distance == 0.0 {
+                continue;
+            }
+            if distance < self.separation_radius {
+                away = [away[0] - offset[0] / distance, away[1] - offset[1] / distance];
+            }
+            if distance < self.radius {
+                count += 1.0;
+                middle = [middle[0] + offset[0], middle[1] + offset[1]];
+                heading = [heading[0] + neighbor.velocity[0], heading[1] + neighbor.velocity[1]];
+            }
+        }
+        if count > 0.0 {
+            for i in 0..2 {
+                let alignment = heading[i] / count - particle.velocity[i];
+                particle.velocity[i] += (middle[i] / count * self.cohesion + alignment * self.alignment) * dt;
+            }
+        }
+        for (i, away) in away.into_iter().enumerate() {
+            particle.velocity[i] += away * self.separation * dt;
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use crate::allocator::{memory_stats, thread_stats, ThreadAllocations};
+use crate::drops::{drop_stats, DropStats};
+use crate::energy::Energy;
+use crate::locale::{bundle, fill};
+use crate::freelist::ParticlePoolStats;
+use crate::trail::{TrailStats, TrailStorage};
+use crate::metadata::RunMetadata;
+use crate::{arena, pool, pprof, reuse, telemetry};
+use crate::shutdown;
+use crate::warmup::Warmup;
+use crate::World;
+use std::time::{Duration, Instant};
+
+/// Results of a headless benchmark run.
+pub struct BenchReport {
+    pub turns: u64,
+    pub requested_turns: u64,
+    pub elapsed: Duration,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+
+    /// Of the allocations, those made by `realloc` and `alloc_zeroed`, and
+    /// the blocks freed
+    pub reallocations: u64,
+    pub zeroed_allocations: u64,
+    pub frees: u64,
+
+    /// Allocations that grew or shrank the particle `Vec`
+    pub vec_reallocs: u64,
+
+    /// What each thread allocated and freed during the run, of those that did
+    pub threads: Vec<ThreadAllocations>,
+    pub peak_live_bytes: usize,
+
+    /// Time the allocations of the run took, whatever served them
+    pub allocation_nanos: u64,
+
+    /// The particles dropped in batches during the run, by site, of the
+    /// sites that dropped any
+    pub drops: Vec<DropStats>,
+
+    /// Chunks the block allocator took from the system during the run
+    pub pool_refills: u64,
+
+    /// Chunks the arena took from the system during the run
+    pub arena_chunks: usize,
+
+    /// What the pool of particle boxes did during the run, if the world has one
+    pub particle_pool: Option<ParticlePoolStats>,
+
+    /// What the trails of the particles took during the run, how they were
+    /// stored and the bytes those left hold, if the scene has trails
+    pub trails: Option<(TrailStorage, TrailStats, usize)>,
+    pub particles: usize,
+
+    /// Of the particles left at the end of the run
+    pub energy: Energy,
+}
+
+/// Runs the world for `turns` turns without a window, after an optional warm-up
+/// that is left out of the report. Stops early, with a partial report, on Ctrl+C.
+/// `on_turn` is called after every turn past the warm-up, with its number from 1
+/// and the time the turn took.
+pub fn run(world: &mut World, turns: u64, warmup: Option<Warmup>, on_turn: &mut dyn FnMut(u64, Duration, &World)) -> BenchReport {
+    if let Some(warmup) = warmup {
+        let (start, start_turn) = (Instant::now(), world.current_turn);
+        while !warmup.is_over(world.current_turn - start_turn, start.elapsed()) && !shutdown::requested() {
+            world.update();
+        }
+    }
+
+    reuse::reset();
+    pprof::reset();
+    let threads_before = thread_stats();
+    let drops_before = drop_stats();
+    let before = memory_stats();
+    let refills_before = pool::pool_stats().refills;
+    let chunks_before = arena::arena_stats().chunks;
+    let particle_pool_before = world.particle_pool.as_ref().map(|pool| pool.stats());
+    let trails_before = world.trail_stats;
+    let mut peak_live_bytes = before.live_bytes;
+    let start = Instant::now();
+
+    let mut done = 0;
+    while done < turns && !shutdown::requested() {
+        let turn_start = Instant::now();
+        world.update();
+        let time = turn_start.elapsed();
+        peak_live_bytes = peak_live_bytes.max(memory_stats().live_bytes);
+        done += 1;
+        on_turn(done, time, world);
+    }
+
+    let elapsed = start.elapsed();
+    let after = memory_stats();
+    BenchReport {
+        turns: done,
+        requested_turns: turns,
+        elapsed,
+        allocations: after.allocations - before.allocations,
+        allocated_bytes: after.allocated_bytes - before.allocated_bytes,
+        reallocations: after.reallocations - before.reallocations,
+        zeroed_allocations: after.zeroed_allocations - before.zeroed_allocations,
+        frees: after.deallocations - before.deallocations,
+        vec_reallocs: after.vec_reallocs - before.vec_reallocs,
+        threads: thread_stats()
+            .iter()
+            .map(|thread| threads_before.get(thread.slot).map_or(*thread, |before| thread.since(before)))
+            .filter(|thread| thread.allocations + thread.frees > 0)
+            .collect(),
+        peak_live_bytes,
+        allocation_nanos: after.allocation_nanos - before.allocation_nanos,
+        drops: drop_stats().iter().zip(&drops_before).map(|(after, before)| after.since(before)).filter(|site| site.batches > 0).collect(),
+        pool_refills: pool::pool_stats().refills - refills_before,
+        arena_chunks: arena::arena_stats().chunks - chunks_before,
+        particle_pool: world.particle_pool.as_ref().zip(particle_pool_before).map(|(pool, before)| {
+            let after = pool.stats();
+            ParticlePoolStats { recycled: after.recycled - before.recycled, allocated: after.allocated - before.allocated, ..after }
+        }),
+        trails: world.trail.map(|settings| (settings.storage, world.trail_stats.since(&trails_before), world.trail_bytes())),
+        particles: world.particles.len(),
+        energy: world.energy(),
+    }
+}
+
+impl BenchReport {
+    /// Like `main 2400 (80%), rayon-0 300 (10%), ...`: the allocations of
+    /// each thread and their share.
+    pub fn describe_threads(&self) -> String {
+        let threads: Vec<String> = self
+            .threads
+            .iter()
+            .map(|thread| {
+                let share = thread.allocations as f64 / self.allocations.max(1) as f64 * 100.0;
+                format!("{} {} ({:.0}%)", thread.label(), thread.allocations, share)
+            })
+            .collect();
+        threads.join(", ")
+    }
+
+    /// Whether Ctrl+C stopped the run before all the requested turns ran.
+    pub fn interrupted(&self) -> bool {
+        self.turns < self.requested_turns
+    }
+
+    pub fn turns_per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        (seconds > 0.0).then(|| self.turns as f64 / seconds)
+    }
+
+    pub fn print(&self, metadata: &RunMetadata) {
+        print!("{}", metadata.comment_header());
+        // Names can run longer in other languages than in English
+        let rows = self.rows();
+        let width = rows.iter().map(|(name, _)| name.chars().count() + 1).max().unwrap_or(0).max(20);
+        for (name, value) in rows {
+            println!("{:<width$}{}", name, value);
+        }
+    }
+
+    /// The lines of the report, by name, as printed and put in HTML reports,
+    /// in the language set with `locale::set_language`.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let text = bundle();
+        let status = if self.interrupted() { text.interrupted } else { "" };
+        let mut rows = vec![
+            (text.turns, fill(text.turns_of, &[&self.turns, &self.requested_turns]) + status),
+            (text.elapsed, format!("{:.3} s", self.elapsed.as_secs_f64())),
+        ];
+        if let Some(turns_per_second) = self.turns_per_second() {
+            rows.push((text.turns_per_second, format!("{:.0}", turns_per_second)));
+        }
+        rows.push((text.allocations, self.allocations.to_string()));
+        rows.push((text.allocated_bytes, self.allocated_bytes.to_string()));
+        let fresh = self.allocations - self.reallocations - self.zeroed_allocations;
+        rows.push((
+            text.operations,
+            fill(text.operation_counts, &[&fresh, &self.reallocations, &self.zeroed_allocations, &self.frees]),
+        ));
+        if self.allocations > 0 {
+            rows.push((text.mean_allocation, format!("{:.0} ns", self.allocation_nanos as f64 / self.allocations as f64)));
+        }
+        rows.push((text.vec_reallocations, self.vec_reallocs.to_string()));
+        if !self.drops.is_empty() {
+            let drops: Vec<String> = self.drops.iter().map(DropStats::describe).collect();
+            rows.push((text.drops, drops.join("; ")));
+        }
+        if self.threads.len() > 1 {
+            rows.push((text.by_thread, self.describe_threads()));
+        }
+        rows.push((text.peak_live_bytes, self.peak_live_bytes.to_string()));
+        rows.push((text.particles, self.particles.to_string()));
+        rows.push((text.kinetic_energy, format!("{:.3}", self.energy.kinetic)));
+        rows.push((text.momentum, format!("{:.3}", self.energy.momentum_magnitude())));
+        if arena::is_enabled() {
+            rows.push((text.allocator, arena::arena_stats().describe()));
+            rows.push((text.arena_chunks, self.arena_chunks.to_string()));
+        } else if pool::is_enabled() {
+            rows.push((text.allocator, pool::pool_stats().describe()));
+            rows.push((text.pool_refills, self.pool_refills.to_string()));
+        }
+        if let Some(particle_pool) = &self.particle_pool {
+            rows.push((text.particle_pool, particle_pool.describe()));
+        }
+        if let Some((storage, stats, held)) = &self.trails {
+            rows.push((text.trails, stats.describe(*storage, *held)));
+        }
+        if reuse::is_enabled() {
+            rows.push((text.reuse_distance, reuse::distribution().describe()));
+        }
+        let health = telemetry::health();
+        if !health.is_ok() {
+            rows.push((text.telemetry, health.describe()));
+        }
+        rows
+    }
+}
@@ -0,0 +1,34 @@
+use graphics::draw_state::Blend;
+use serde::{Deserialize, Serialize};
+
+/// How the particles of an emitter are blended into what is behind them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Painted over the background by their opacity, like smoke
+    #[default]
+    Alpha,
+
+    /// Added to the background by their opacity, brightening it like sparks
+    /// or glowing embers; overlapping particles get brighter
+    Additive,
+
+    /// Multiplied with the background, darkening it like soot. Opacity
+    /// plays no part
+    Multiply,
+}
+
+/// Order particles are drawn in, so glowing ones light up what was
+/// painted before them.
+pub const DRAW_ORDER: [BlendMode; 3] = [BlendMode::Alpha, BlendMode::Multiply, BlendMode::Additive];
+
+impl BlendMode {
+    /// The draw state blend the renderer uses for this mode.
+    pub fn blend(&self) -> Blend {
+        match self {
+            BlendMode::Alpha => Blend::Alpha,
+            BlendMode::Additive => Blend::Lighter,
+            BlendMode::Multiply => Blend::Multiply,
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use graphics::math::Vec2d;
+use rand::Rng;
+use serde::Deserialize;
+
+/// A side of the window, or a horizontal line at a custom height.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Top,
+    #[default]
+    Bottom,
+    Left,
+    Right,
+    Y(f64),
+}
+
+impl Edge {
+    /// Keeps a custom line at the same height relative to a window made
+    /// `scale` times as high; the sides follow the window by themselves.
+    pub fn rescale(&mut self, scale: f64) {
+        if let Edge::Y(y) = self {
+            *y *= scale;
+        }
+    }
+
+    /// Random point along the edge, and the direction pointing into the window.
+    /// Particles spawned on a custom line rise, like the ones on the bottom edge.
+    pub fn spawn_point<R: Rng>(&self, rng: &mut R, width: f64, height: f64) -> (Vec2d<f64>, Vec2d<f64>) {
+        match *self {
+            Edge::Top => ([rng.gen_range(0.0..=width), 0.0], [0.0, 1.0]),
+            Edge::Bottom => ([rng.gen_range(0.0..=width), height], [0.0, -1.0]),
+            Edge::Left => ([0.0, rng.gen_range(0.0..=height)], [1.0, 0.0]),
+            Edge::Right => ([width, rng.gen_range(0.0..=height)], [-1.0, 0.0]),
+            Edge::Y(y) => ([rng.gen_range(0.0..=width), y], [0.0, -1.0]),
+        }
+    }
+
+    /// Point `along` the way across the edge, from 0 to 1, and the direction
+    /// pointing into the window.
+    pub fn point_at(&self, along: f64, width: f64, height: f64) -> (Vec2d<f64>, Vec2d<f64>) {
+        match *self {
+            Edge::Top => ([along * width, 0.0], [0.0, 1.0]),
+            Edge::Bottom => ([along * width, height], [0.0, -1.0]),
+            Edge::Left => ([0.0, along * height], [1.0, 0.0]),
+            Edge::Right => ([width, along * height], [-1.0, 0.0]),
+            Edge::Y(y) => ([along * width, y], [0.0, -1.0]),
+        }
+    }
+
+    pub fn length(&self, width: f64, height: f64) -> f64 {
+        match self {
+            Edge::Left | Edge::Right => height,
+            Edge::Top | Edge::Bottom | Edge::Y(_) => width,
+        }
+    }
+
+    /// Whether a particle has gone past the edge used as a kill plane.
+    /// A custom line only kills particles moving away from it on the far side,
+    /// so it works for rising and falling streams alike.
+    pub fn is_past(&self, position: Vec2d<f64>, velocity: Vec2d<f64>, width: f64, height: f64) -> bool {
+        let [x, y] = position;
+        match *self {
+            Edge::Top => y < 0.0,
+            Edge::Bottom => y > height,
+            Edge::Left => x < 0.0,
+            Edge::Right => x > width,
+            Edge::Y(line) => (y < line && velocity[1] < 0.0) || (y > line && velocity[1] > 0.0),
+        }
+    }
+}
+
+/// What becomes of particles reaching the sides of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeBehavior {
+    /// They come back in on the opposite side once all the way out, as if
+    /// the window were a torus
+    Wrap,
+
+    /// They bounce back in off the sides without losing speed
+    Bounce,
+
+    /// They are removed once all the way out
+    #[default]
+    Kill,
+
+    /// They go on out of sight, until they fade out or die of age
+    Ignore,
+}
+
+impl EdgeBehavior {
+    /// Moves a particle of `size` back into a window of `bounds`, turning
+    /// its velocity around for a bounce. Particles with a NaN position are
+    /// left where they are.
+    pub fn apply(&self, position: &mut Vec2d<f64>, velocity: &mut Vec2d<f64>, size: Vec2d<f64>, bounds: Vec2d<f64>) {
+        for axis in 0..2 {
+            let (p, v, size, bound) = (&mut position[axis], &mut velocity[axis], size[axis], bounds[axis]);
+            match self {
+                EdgeBehavior::Wrap if *p > bound || *p + size < 0.0 => {
+                    // Into [-size, bound), however far out it went
+                    *p = (*p + size).rem_euclid(bound + size) - size;
+                }
+                EdgeBehavior::Bounce if *p < 0.0 && *v < 0.0 => {
+                    *p = -*p;
+                    *v = -*v;
+                }
+                EdgeBehavior::Bounce if *p + size > bound && *v > 0.0 => {
+                    *p = 2.0 * (bound - size) - *p;
+                    *v = -*v;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Brings a particle of `size` outside a window shrunk to `bounds` back
+    /// in when the edges keep particles in: wrapped around for `Wrap`, onto
+    /// the side it is past for `Bounce`.
+    pub fn confine(&self, position: &mut Vec2d<f64>, velocity: &mut Vec2d<f64>, size: Vec2d<f64>, bounds: Vec2d<f64>) {
+        match self {
+            EdgeBehavior::Wrap => self.apply(position, velocity, size, bounds),
+            EdgeBehavior::Bounce => {
+                // Left alone when NaN, for the guard to find
+                for (p, (size, bound)) in position.iter_mut().zip(size.into_iter().zip(bounds)) {
+                    if !p.is_nan() {
+                        *p = p.min(bound - size).max(0.0);
+                    }
+                }
+            }
+            EdgeBehavior::Kill | EdgeBehavior::Ignore => {}
+        }
+    }
+
+    /// Whether particles all the way out of the window are removed.
+    pub fn kills(&self) -> bool {
+        *self == EdgeBehavior::Kill
+    }
+}
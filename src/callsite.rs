@@ -0,0 +1,156 @@
+use crate::locale::{bundle, fill};
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Frames a call site is told by, from the first past the allocator.
+pub const SITE_FRAMES: usize = 4;
+
+/// Call sites printed at exit, those sampled the most.
+pub const TOP_SITES: usize = 20;
+
+// Frames of the backtrace itself, of the allocator and of Rust's allocation
+// shims, skipped to reach the code allocating
+const INTERNAL_FRAMES: [&str; 9] = [
+    "std::backtrace",
+    "<std::backtrace",
+    "backtrace::",
+    "memory_particles::callsite",
+    "memory_particles::allocator",
+    "<memory_particles::allocator",
+    "__rust_",
+    "__rg_",
+    "alloc::alloc::",
+];
+
+// One allocation in this many has its backtrace taken, none at 0
+static EVERY: AtomicU32 = AtomicU32::new(0);
+static SEEN: AtomicU64 = AtomicU64::new(0);
+
+// Allocations made while taking and resolving the backtraces, counted with
+// the rest by the allocator
+static OWN_ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static SITES: Mutex<BTreeMap<String, SiteCounts>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    static SAMPLING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Samples of a call site.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SiteCounts {
+    pub samples: u64,
+    pub bytes: u64,
+}
+
+/// Takes the backtrace of one allocation in `every` from now on, for the
+/// call sites printed at exit. Taking one costs microseconds and
+/// allocations of its own, so only a sample is.
+pub fn enable(every: u32) {
+    EVERY.store(every, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    EVERY.load(Ordering::Relaxed) > 0
+}
+
+// Counts an allocation of `bytes`, taking its backtrace when its turn comes.
+// The allocations of the backtrace come back here, and are only counted
+pub(crate) fn on_alloc(bytes: usize) {
+    let every = EVERY.load(Ordering::Relaxed);
+    if every == 0 {
+        return;
+    }
+    match SAMPLING.try_with(Cell::get) {
+        Ok(false) => {}
+        Ok(true) => {
+            OWN_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        // The thread is being torn down
+        Err(_) => return,
+    }
+
+    // A panic may be printing its own backtrace, whose lock taking another would wait for
+    if !SEEN.fetch_add(1, Ordering::Relaxed).is_multiple_of(every as u64) || std::thread::panicking() {
+        return;
+    }
+    unsampled(|| {
+        let site = call_site(&Backtrace::force_capture().to_string());
+        let mut sites = SITES.lock().unwrap_or_else(PoisonError::into_inner);
+        let counts = sites.entry(site).or_default();
+        counts.samples += 1;
+        counts.bytes += bytes as u64;
+    });
+}
+
+// Runs `f` without sampling the allocations of this thread, which must be
+// the case whenever the sites are locked: a sample would wait on the lock
+fn unsampled<T>(f: impl FnOnce() -> T) -> T {
+    let previous = SAMPLING.replace(true);
+    let result = f();
+    SAMPLING.set(previous);
+    result
+}
+
+/// The call site of a backtrace as printed by `Backtrace`: its first
+/// `SITE_FRAMES` frames past the allocator, innermost first, like
+/// `alloc::raw_vec::RawVec<T,A>::grow_one < memory_particles::World::add_shapes`.
+pub fn call_site(backtrace: &str) -> String {
+    let frames = backtrace.lines().filter_map(|line| {
+        let (index, symbol) = line.trim_start().split_once(": ")?;
+        index.chars().all(|c| c.is_ascii_digit()).then(|| strip_hash(symbol.trim()))
+    });
+    let site: Vec<&str> = frames
+        .skip_while(|symbol| INTERNAL_FRAMES.iter().any(|internal| symbol.starts_with(internal)))
+        .take(SITE_FRAMES)
+        .collect();
+    if site.is_empty() {
+        return "unknown".to_string();
+    }
+    site.join(" < ")
+}
+
+// Without the hash of the symbol, like `::h0123456789abcdef`
+fn strip_hash(symbol: &str) -> &str {
+    match symbol.rsplit_once("::h") {
+        Some((name, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => name,
+        _ => symbol,
+    }
+}
+
+/// The call sites sampled so far, the most sampled first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSites {
+    pub every: u32,
+    pub sites: Vec<(String, SiteCounts)>,
+    pub own_allocations: u64,
+}
+
+impl CallSites {
+    pub fn now() -> CallSites {
+        let sites = unsampled(|| SITES.lock().unwrap_or_else(PoisonError::into_inner).clone());
+        let mut sites: Vec<(String, SiteCounts)> = sites.into_iter().collect();
+        sites.sort_by(|a, b| b.1.samples.cmp(&a.1.samples).then_with(|| a.0.cmp(&b.0)));
+        CallSites { every: EVERY.load(Ordering::Relaxed), sites, own_allocations: OWN_ALLOCATIONS.load(Ordering::Relaxed) }
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.sites.iter().map(|(_, counts)| counts.samples).sum()
+    }
+
+    /// Prints the `TOP_SITES` sites sampled the most with their share of the
+    /// samples and the allocations and bytes they stand for, `every` each.
+    pub fn print(&self) {
+        let (samples, text) = (self.samples(), bundle());
+        println!("{}", fill(text.call_sites_sampled, &[&self.every, &samples, &self.own_allocations]));
+        println!("{:>8}{:>12}{:>14}  {}", text.share, text.count, "bytes", text.call_site);
+        for (site, counts) in self.sites.iter().take(TOP_SITES) {
+            let share = counts.samples as f64 / samples.max(1) as f64 * 100.0;
+            let every = self.every as u64;
+            println!("{:>7.1}%{:>12}{:>14}  {}", share, counts.samples * every, counts.bytes * every, site);
+        }
+    }
+}
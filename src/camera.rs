@@ -0,0 +1,111 @@
+use graphics::math::Vec2d;
+use graphics::{Context, Transformed};
+use serde::Deserialize;
+
+/// Where the camera is on one turn of the run, the turns in between going
+/// smoothly from one key to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraKey {
+    pub turn: u64,
+
+    /// Point of the world shown in the middle of the window, the middle of
+    /// the window when missing
+    pub center: Option<[f64; 2]>,
+
+    /// How many times larger than in the window the world is shown, 1 when
+    /// missing
+    #[serde(default = "default_zoom")]
+    pub zoom: f64,
+
+    /// How the camera gets here from the key before
+    #[serde(default)]
+    pub ease: CameraEase,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CameraEase {
+    /// Starting and stopping slowly
+    #[default]
+    Smooth,
+
+    /// At the same speed all the way
+    Linear,
+
+    /// Holding still, then jumping here on the turn of the key
+    Cut,
+}
+
+fn default_zoom() -> f64 {
+    1.0
+}
+
+/// The keys of the camera of a scene, by turn, so recorded demos can pan and
+/// zoom across the particles. Where the camera is depends on the turn alone,
+/// so seeded runs show the same frames every time. The simulation never
+/// reads it: particles still move in window coordinates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+}
+
+/// What the camera shows on a turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraView {
+    pub center: Vec2d,
+    pub zoom: f64,
+}
+
+impl CameraPath {
+    /// The path through `keys`, in any order.
+    pub fn new(mut keys: Vec<CameraKey>) -> CameraPath {
+        keys.sort_by_key(|key| key.turn);
+        CameraPath { keys }
+    }
+
+    pub fn keys(&self) -> &[CameraKey] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Where the camera is on `turn` over a window of `size`: at the first
+    /// key before it and the last one after it, and between two keys as
+    /// eased by the later one. The zoom changes by the same factor every
+    /// turn, so zooming in looks as fast as zooming out. None without keys.
+    pub fn view(&self, turn: u64, size: Vec2d) -> Option<CameraView> {
+        let at = |key: &CameraKey| CameraView { center: key.center.unwrap_or([size[0] / 2.0, size[1] / 2.0]), zoom: key.zoom };
+        let next = self.keys.iter().position(|key| key.turn > turn);
+        let (from, to) = match next {
+            None => return self.keys.last().map(at),
+            Some(0) => return Some(at(&self.keys[0])),
+            Some(next) => (&self.keys[next - 1], &self.keys[next]),
+        };
+        let t = (turn - from.turn) as f64 / (to.turn - from.turn) as f64;
+        let t = match to.ease {
+            CameraEase::Smooth => t * t * (3.0 - 2.0 * t),
+            CameraEase::Linear => t,
+            CameraEase::Cut => 0.0,
+        };
+        let (from, to) = (at(from), at(to));
+        Some(CameraView {
+            center: [0, 1].map(|i| from.center[i] + (to.center[i] - from.center[i]) * t),
+            zoom: from.zoom * (to.zoom / from.zoom).powf(t),
+        })
+    }
+}
+
+impl CameraView {
+    /// `ctx` drawing the world as the camera shows it in a window of `size`.
+    pub fn apply(&self, ctx: Context, size: Vec2d) -> Context {
+        ctx.trans(size[0] / 2.0, size[1] / 2.0).zoom(self.zoom).trans(-self.center[0], -self.center[1])
+    }
+
+    /// The point of the world under `point` of a window of `size`.
+    pub fn to_world(&self, point: Vec2d, size: Vec2d) -> Vec2d {
+        [0, 1].map(|i| self.center[i] + (point[i] - size[i] / 2.0) / self.zoom)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::params::{self, ParamVisitor, Tunable};
+use serde::Deserialize;
+
+/// Population target above which particles start dying early at random,
+/// more likely the further the population is over it. Unlike a hard cap the
+/// population can overshoot, but it is pulled back smoothly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SoftCap {
+    pub target: usize,
+
+    /// How quickly the culling probability rises with the excess population
+    #[serde(default = "default_steepness")]
+    pub steepness: f64,
+
+    /// Chance every particle has of being culled each turn, far over the target
+    #[serde(default = "default_max_rate")]
+    pub max_rate: f64,
+}
+
+fn default_steepness() -> f64 {
+    4.0
+}
+
+fn default_max_rate() -> f64 {
+    0.05
+}
+
+impl Tunable for SoftCap {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        params::size(visitor, "target", &mut self.target, 1..=10_000_000);
+        visitor.float("steepness", &mut self.steepness, 0.0..=100.0);
+        visitor.float("max_rate", &mut self.max_rate, 0.0..=1.0);
+    }
+}
+
+impl SoftCap {
+    /// Chance each particle has of being culled this turn. Zero up to the
+    /// target, then rising as `max_rate * (1 - e^(-steepness * excess))`,
+    /// where `excess` is how far over the target the population is, as a
+    /// fraction of the target.
+    pub fn cull_probability(&self, population: usize) -> f64 {
+        if population <= self.target {
+            return 0.0;
+        }
+
+        let excess = (population - self.target) as f64 / self.target.max(1) as f64;
+        (self.max_rate * (1.0 - (-self.steepness * excess).exp())).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::OnceLock;
+use std::thread;
+
+// Renderers drawing on the CPU, which a smaller render target helps most
+const SOFTWARE_RENDERERS: [&str; 5] = ["llvmpipe", "softpipe", "swiftshader", "software rasterizer", "basic render"];
+
+// Found out by the window once it is open, for the metadata written after
+static DISPLAY: OnceLock<DisplayInfo> = OnceLock::new();
+
+/// What the machine running the simulation offers, detected at launch, for
+/// the run metadata and the defaults of the options not given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Threads that can run at once, 1 when unknown
+    pub cpu_cores: usize,
+
+    /// Size of a memory page in bytes, known on Unix
+    pub page_size: Option<usize>,
+
+    /// SIMD extensions of the CPU, like `avx2`, found at runtime whatever
+    /// the binary was compiled for
+    pub simd: Vec<&'static str>,
+
+    /// Of the window, once one was opened
+    pub display: Option<DisplayInfo>,
+}
+
+/// What the window found out about the display it opened on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayInfo {
+    /// Like `3.3 Mesa 23.2.1`
+    pub gl_version: String,
+    pub gl_renderer: String,
+
+    /// Of the monitor, in hertz, when the windowing system tells
+    pub refresh_rate: Option<u32>,
+}
+
+/// Records what the window found out about its display, for every
+/// `Capabilities` detected from then on.
+pub fn set_display(display: DisplayInfo) {
+    let _ = DISPLAY.set(display);
+}
+
+impl Capabilities {
+    pub fn detect() -> Capabilities {
+        Capabilities {
+            cpu_cores: thread::available_parallelism().map_or(1, |cores| cores.get()),
+            page_size: page_size(),
+            simd: simd_features(),
+            display: DISPLAY.get().cloned(),
+        }
+    }
+
+    /// Threads updating the particles in `--parallel` mode: every core, but
+    /// the one drawing the window when there is one.
+    pub fn default_threads(&self, windowed: bool) -> usize {
+        if windowed {
+            self.cpu_cores.saturating_sub(1).max(1)
+        } else {
+            self.cpu_cores
+        }
+    }
+
+    /// Fraction of the window resolution the world is drawn at: half on a
+    /// renderer drawing on the CPU, the whole otherwise.
+    pub fn default_render_scale(&self) -> f64 {
+        if self.display.as_ref().is_some_and(DisplayInfo::is_software) {
+            0.5
+        } else {
+            1.0
+        }
+    }
+
+    /// One line for the log, like `8 cores, 4096 B pages, OpenGL 3.3 Mesa
+    /// on llvmpipe, 60 Hz`.
+    pub fn describe(&self) -> String {
+        let mut text = format!("{} cores", self.cpu_cores);
+        if let Some(page_size) = self.page_size {
+            text.push_str(&format!(", {} B pages", page_size));
+        }
+        if let Some(display) = &self.display {
+            text.push_str(&format!(", OpenGL {} on {}", display.gl_version, display.gl_renderer));
+            match display.refresh_rate {
+                Some(rate) => text.push_str(&format!(", {} Hz", rate)),
+                None => text.push_str(", unknown refresh rate"),
+            }
+        }
+        text
+    }
+}
+
+impl DisplayInfo {
+    /// Whether the renderer draws on the CPU, like Mesa's llvmpipe.
+    pub fn is_software(&self) -> bool {
+        let renderer = self.gl_renderer.to_lowercase();
+        SOFTWARE_RENDERERS.iter().any(|name| renderer.contains(name))
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn simd_features() -> Vec<&'static str> {
+    let mut found = Vec::new();
+    macro_rules! detect {
+        ($($feature:tt),*) => {
+            $(if std::arch::is_x86_feature_detected!($feature) {
+                found.push($feature);
+            })*
+        };
+    }
+    detect!("sse2", "sse4.1", "sse4.2", "avx", "avx2", "fma", "avx512f");
+    found
+}
+
+#[cfg(target_arch = "aarch64")]
+fn simd_features() -> Vec<&'static str> {
+    let mut found = Vec::new();
+    macro_rules! detect {
+        ($($feature:tt),*) => {
+            $(if std::arch::is_aarch64_feature_detected!($feature) {
+                found.push($feature);
+            })*
+        };
+    }
+    detect!("neon", "sve", "sve2");
+    found
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn simd_features() -> Vec<&'static str> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn page_size() -> Option<usize> {
+    // Safe: sysconf only reads a configuration value
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (size > 0).then_some(size as usize)
+}
+
+#[cfg(not(unix))]
+fn page_size() -> Option<usize> {
+    None
+}
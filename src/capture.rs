@@ -0,0 +1,252 @@
+use crate::telemetry;
+use graphics::draw_state::{Blend, DrawState};
+use graphics::{Context, Graphics, ImageSize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Draws with the same code as the window, on the CPU, into an image.
+/// Frames come out the same on every machine, whatever its GPU and driver.
+pub struct Canvas {
+    width: usize,
+    height: usize,
+
+    // Row by row, from the top left corner
+    pixels: Vec<[f32; 4]>,
+}
+
+/// The canvas draws no textures, this only satisfies `Graphics`.
+pub struct NoTexture;
+
+impl ImageSize for NoTexture {
+    fn get_size(&self) -> (u32, u32) {
+        (0, 0)
+    }
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Canvas {
+        Canvas { width, height, pixels: vec![[0.0, 0.0, 0.0, 1.0]; width * height] }
+    }
+
+    /// A canvas of the given size drawn by `draw`.
+    pub fn render<F>(width: usize, height: usize, draw: F) -> Canvas
+    where
+        F: FnOnce(Context, &mut Canvas),
+    {
+        let mut canvas = Canvas::new(width, height);
+        draw(canvas.context(), &mut canvas);
+        canvas
+    }
+
+    /// Context mapping pixel coordinates to the canvas, like a window's.
+    pub fn context(&self) -> Context {
+        Context::new_abs(self.width as f64, self.height as f64)
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        // Opaque, as the window shows it
+        let data: Vec<u8> = self.pixels.iter().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]].map(to_byte)).collect();
+        write_rgb_png(path, self.width as u32, self.height as u32, &data)
+    }
+
+    /// The pixels as opaque RGBA bytes, row by row from the top, like textures take them.
+    pub fn rgba(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0].map(to_byte)).collect()
+    }
+
+    // Fills a triangle given in normalized device coordinates, blending
+    // `color` into the pixels whose centers fall inside it as the GPU would
+    fn fill_triangle(&mut self, vertices: &[[f32; 2]], color: [f32; 4], blend: Option<Blend>) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let [a, mut b, mut c] =
+            [0, 1, 2].map(|i| [(vertices[i][0] + 1.0) / 2.0 * width, (1.0 - vertices[i][1]) / 2.0 * height]);
+
+        // Wound the same way whatever the order of the vertices
+        if edge(a, b, c) < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+        }
+        if edge(a, b, c) == 0.0 {
+            return;
+        }
+
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let max_x = a[0].max(b[0]).max(c[0]).ceil().min(width) as usize;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let max_y = a[1].max(b[1]).max(c[1]).ceil().min(height) as usize;
+
+        let edges = [(b, c), (c, a), (a, b)];
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let inside = edges.iter().all(|&(from, to)| {
+                    let w = edge(from, to, p);
+                    w > 0.0 || (w == 0.0 && owns_edge(from, to))
+                });
+                if !inside {
+                    continue;
+                }
+
+                blend_pixel(&mut self.pixels[y * self.width + x], color, blend);
+            }
+        }
+    }
+}
+
+fn to_byte(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Blends like the piston renderer, see `Blend` for the equations
+fn blend_pixel(pixel: &mut [f32; 4], color: [f32; 4], blend: Option<Blend>) {
+    let alpha = color[3];
+    match blend {
+        Some(Blend::Lighter) => {
+            for i in 0..3 {
+                pixel[i] += color[i] * alpha;
+            }
+        }
+        Some(Blend::Add) => {
+            for i in 0..4 {
+                pixel[i] += color[i];
+            }
+        }
+        Some(Blend::Multiply) => {
+            for i in 0..4 {
+                pixel[i] *= color[i];
+            }
+        }
+        Some(Blend::Invert) => {
+            for i in 0..3 {
+                pixel[i] = 1.0 - color[i];
+            }
+        }
+        Some(Blend::Alpha) | None => {
+            for i in 0..3 {
+                pixel[i] = color[i] * alpha + pixel[i] * (1.0 - alpha);
+            }
+            pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+        }
+    }
+}
+
+// Twice the signed area of the triangle `a`, `b`, `p`
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+// Pixel centers right on an edge shared by two triangles belong to only one
+// of them, which walks the edge in the other direction, or the pixel would
+// be blended twice
+fn owns_edge(from: [f32; 2], to: [f32; 2]) -> bool {
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    dy > 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+impl Graphics for Canvas {
+    type Texture = NoTexture;
+
+    fn clear_color(&mut self, color: [f32; 4]) {
+        self.pixels.fill(color);
+    }
+
+    fn clear_stencil(&mut self, _value: u8) {}
+
+    fn tri_list<F>(&mut self, draw_state: &DrawState, color: &[f32; 4], mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+    {
+        f(&mut |vertices| {
+            for triangle in vertices.chunks_exact(3) {
+                self.fill_triangle(triangle, *color, draw_state.blend);
+            }
+        });
+    }
+
+    fn tri_list_c<F>(&mut self, draw_state: &DrawState, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 4]])),
+    {
+        // Flat shaded with the color of the first vertex of each triangle
+        f(&mut |vertices, colors| {
+            for (triangle, colors) in vertices.chunks_exact(3).zip(colors.chunks_exact(3)) {
+                self.fill_triangle(triangle, colors[0], draw_state.blend);
+            }
+        });
+    }
+
+    fn tri_list_uv<F>(&mut self, draw_state: &DrawState, color: &[f32; 4], _texture: &NoTexture, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+    {
+        self.tri_list(draw_state, color, |receive| f(&mut |vertices, _| receive(vertices)));
+    }
+
+    fn tri_list_uv_c<F>(&mut self, draw_state: &DrawState, _texture: &NoTexture, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]], &[[f32; 4]])),
+    {
+        self.tri_list_c(draw_state, |receive| f(&mut |vertices, _, colors| receive(vertices, colors)));
+    }
+}
+
+/// Writes `data`, RGB bytes row by row from the top, as a PNG of `width`
+/// by `height` pixels.
+pub fn write_rgb_png(path: &Path, width: u32, height: u32, data: &[u8]) -> io::Result<()> {
+    let out = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .map_err(io::Error::other)
+}
+
+/// `rows` of `width` bytes, the last first: GL reads the framebuffer back
+/// from the bottom up.
+pub fn flip_rows(rows: &[u8], width: usize) -> Vec<u8> {
+    rows.chunks_exact(width).rev().flatten().copied().collect()
+}
+
+/// Saves a picture of the world at a fixed list of turns, so seeded runs
+/// give the very same frames from one version of the code to the next.
+pub struct FrameCapture {
+    // Sorted from the last, so the next one can be popped
+    turns: Vec<u64>,
+    directory: PathBuf,
+}
+
+impl FrameCapture {
+    pub fn new(mut turns: Vec<u64>, directory: PathBuf) -> FrameCapture {
+        turns.sort_unstable();
+        turns.dedup();
+        turns.reverse();
+        FrameCapture { turns, directory }
+    }
+
+    /// Whether a frame is due on `turn`.
+    pub fn is_due(&self, turn: u64) -> bool {
+        self.turns.last() == Some(&turn)
+    }
+
+    /// Draws the frame of `turn` with `draw` and hands it to the telemetry
+    /// worker to save as `frame-<turn>.png`. Returns false when the worker
+    /// was too far behind to take it.
+    pub fn save<F>(&mut self, turn: u64, width: f64, height: f64, draw: F) -> bool
+    where
+        F: FnOnce(Context, &mut Canvas),
+    {
+        self.turns.pop();
+        let canvas = Canvas::render(width as usize, height as usize, draw);
+
+        let directory = self.directory.clone();
+        telemetry::submit(move || {
+            let path = directory.join(format!("frame-{:06}.png", turn));
+            match fs::create_dir_all(&directory).and_then(|()| canvas.write_png(&path)) {
+                Ok(()) => println!("turn {}: saved frame {}", turn, path.display()),
+                Err(err) => eprintln!("Could not save the frame of turn {}: {}", turn, err),
+            }
+        })
+    }
+}
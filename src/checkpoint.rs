@@ -0,0 +1,57 @@
+use crate::binary;
+use crate::config::Config;
+use crate::session::WorldState;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// A checkpoint is this header, then the checkpoint in the format of
+// `binary`. The last byte is the version, bumped whenever what is written
+// changes, since the format can't tell old fields from new ones
+const MAGIC: &[u8; 8] = b"MPCHKPT\x01";
+
+/// A world saved with `F5` or `World::save`, to carry on from with `F9` or
+/// `World::load`: the scene and size it was built from and its state. Far
+/// smaller and quicker to read than a session, for worlds of millions of
+/// particles run for days.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Text of the scene file of the world, the default scene when missing
+    pub scene_text: Option<String>,
+    pub width: f64,
+    pub height: f64,
+    pub world: WorldState,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to `path`, replacing it only once written in
+    /// full, so a crash while writing leaves the last one as it was.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        writer.write_all(MAGIC)?;
+        binary::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&partial, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let bytes = fs::read(path)?;
+        match bytes.strip_prefix(MAGIC) {
+            Some(rest) => Ok(binary::from_slice(rest)?),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "not a checkpoint of this version")),
+        }
+    }
+
+    /// The scene the world was built from, to build it again.
+    pub fn config(&self) -> io::Result<Config> {
+        match &self.scene_text {
+            Some(text) => Config::from_toml(text),
+            None => Ok(Config::default()),
+        }
+    }
+}
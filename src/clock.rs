@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+// After a stall, no more than this many turns are caught up on; the rest of
+// the time is dropped so the window doesn't fall ever further behind
+const MAX_CATCH_UP_TURNS: u32 = 5;
+
+// Frames that may go missing in a row before spawning is throttled
+const STALL_FRAMES: u32 = 4;
+
+/// Turns of the simulation at a fixed rate of real time, however often
+/// window events and frames come: the time passed is added up and spent in
+/// whole turns.
+pub struct TurnClock {
+    turn: Duration,
+    behind: Duration,
+    last_tick: Instant,
+}
+
+impl TurnClock {
+    pub fn new(rate: f64) -> TurnClock {
+        TurnClock { turn: Duration::from_secs_f64(1.0 / rate), behind: Duration::ZERO, last_tick: Instant::now() }
+    }
+
+    /// Counts the time since the last tick, unless the simulation was held.
+    pub fn tick(&mut self, now: Instant, running: bool) {
+        if running {
+            self.behind = (self.behind + now.saturating_duration_since(self.last_tick)).min(self.turn * MAX_CATCH_UP_TURNS);
+        }
+        self.last_tick = now;
+    }
+
+    /// Takes a turn from the time counted, when there is a whole one left.
+    pub fn next_turn(&mut self) -> bool {
+        if self.behind < self.turn {
+            return false;
+        }
+        self.behind -= self.turn;
+        true
+    }
+}
+
+/// Notices when turns keep running while no frame is rendered, as when the
+/// window is dragged or the GL driver hiccups, and throttles spawning in
+/// proportion to how late the next frame is: once `STALL_FRAMES` frames are
+/// missed, a stall twice as long lets half as many spawns through, so the
+/// population and heap can't run away before rendering catches up.
+pub struct StallGuard {
+    after: Duration,
+    last_frame: Instant,
+    throttled_turns: u64,
+}
+
+/// A stall that throttled spawning, reported once the next frame came.
+pub struct Stall {
+    pub time: Duration,
+    pub throttled_turns: u64,
+}
+
+impl StallGuard {
+    pub fn new(frame_time: Duration) -> StallGuard {
+        StallGuard { after: frame_time * STALL_FRAMES, last_frame: Instant::now(), throttled_turns: 0 }
+    }
+
+    /// Fraction of its spawns to let through on a turn run at `now`.
+    pub fn throttle(&mut self, now: Instant) -> f64 {
+        let since = now.saturating_duration_since(self.last_frame);
+        if since <= self.after {
+            return 1.0;
+        }
+        self.throttled_turns += 1;
+        self.after.as_secs_f64() / since.as_secs_f64()
+    }
+
+    /// A frame was rendered at `now`, ending the stall if there was one.
+    pub fn frame_rendered(&mut self, now: Instant) -> Option<Stall> {
+        let time = now.saturating_duration_since(self.last_frame);
+        self.last_frame = now;
+        let throttled_turns = std::mem::take(&mut self.throttled_turns);
+        (throttled_turns > 0).then_some(Stall { time, throttled_turns })
+    }
+}
+
+// Short of the deadline, the limiter spins rather than sleeps, sleeps
+// overshooting by up to a millisecond or so
+const SPIN: Duration = Duration::from_millis(1);
+
+/// Holds the window to `--max-fps`, waiting at the end of each frame for the
+/// time a frame at that rate takes to be up, however early drawing ended.
+/// Frames that ran late start the next deadline from when they ended, so a
+/// slow frame isn't followed by a burst of fast ones.
+pub struct FrameLimiter {
+    frame: Option<Duration>,
+    deadline: Instant,
+}
+
+impl FrameLimiter {
+    /// A limiter to `max_fps` frames a second, or none at 0.
+    pub fn new(max_fps: u64) -> FrameLimiter {
+        let frame = (max_fps > 0).then(|| Duration::from_secs_f64(1.0 / max_fps as f64));
+        FrameLimiter { frame, deadline: Instant::now() }
+    }
+
+    pub fn is_capped(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// When the frame ending at `now` may be followed by the next one.
+    pub fn next_frame(&mut self, now: Instant) -> Instant {
+        let Some(frame) = self.frame else {
+            return now;
+        };
+        self.deadline = (self.deadline + frame).max(now);
+        self.deadline
+    }
+
+    /// Waits out what is left of the frame just rendered.
+    pub fn wait(&mut self) {
+        let deadline = self.next_frame(Instant::now());
+        let now = Instant::now();
+        if deadline > now + SPIN {
+            std::thread::sleep(deadline - now - SPIN);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
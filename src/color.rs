@@ -0,0 +1,92 @@
+use rand::Rng;
+use serde::Deserialize;
+
+/// How the color of a newly spawned particle is chosen.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ColorSpec {
+    /// Always the same color
+    Fixed { color: [f32; 4] },
+
+    /// One of the listed colors, picked at random
+    Palette { colors: Vec<[f32; 4]> },
+
+    /// Sampled uniformly from ranges of hue (degrees, may wrap past 360),
+    /// saturation and value
+    Hsv {
+        hue: [f32; 2],
+        #[serde(default = "full_range")]
+        saturation: [f32; 2],
+        #[serde(default = "full_range")]
+        value: [f32; 2],
+        #[serde(default = "default_alpha")]
+        alpha: f32,
+    },
+}
+
+fn full_range() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+fn default_alpha() -> f32 {
+    0.99
+}
+
+impl Default for ColorSpec {
+    fn default() -> ColorSpec {
+        ColorSpec::Fixed {
+            color: [1.0, 1.0, 1.0, 0.99], // almost transparent white color
+        }
+    }
+}
+
+impl ColorSpec {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> [f32; 4] {
+        match self {
+            ColorSpec::Fixed { color } => *color,
+            ColorSpec::Palette { colors } => {
+                if colors.is_empty() {
+                    return ColorSpec::default().sample(rng);
+                }
+                colors[rng.gen_range(0..colors.len())]
+            }
+            ColorSpec::Hsv { hue, saturation, value, alpha } => {
+                // A range like [330, 30] goes through red instead of around the wheel
+                let (start, mut end) = (hue[0], hue[1]);
+                if end < start {
+                    end += 360.0;
+                }
+                let h = between(rng, start, end).rem_euclid(360.0);
+                let s = between(rng, saturation[0], saturation[1]);
+                let v = between(rng, value[0], value[1]);
+                let [r, g, b] = hsv_to_rgb(h, s, v);
+                [r, g, b, *alpha]
+            }
+        }
+    }
+}
+
+fn between<R: Rng>(rng: &mut R, a: f32, b: f32) -> f32 {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    if low == high {
+        return low;
+    }
+    rng.gen_range(low..=high)
+}
+
+/// Converts a hue in degrees, saturation and value in `0..=1` to RGB.
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r + m, g + m, b + m]
+}
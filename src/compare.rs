@@ -0,0 +1,75 @@
+use crate::allocator::memory_stats;
+use crate::locale::bundle;
+use crate::World;
+use std::time::{Duration, Instant};
+
+/// What a world of `--compare` took from the allocator and how long its
+/// turns took, counted over its own turns only. The process shares one
+/// allocator, so the worlds metered side by side are updated one after the
+/// other, each turn being counted as the allocations made while it ran.
+#[derive(Debug, Clone, Default)]
+pub struct WorldMeter {
+    turns: u64,
+    update_time: Duration,
+
+    allocations: u64,
+    allocated_bytes: u64,
+    freed_bytes: u64,
+    vec_reallocs: u64,
+}
+
+impl WorldMeter {
+    pub fn new() -> WorldMeter {
+        WorldMeter::default()
+    }
+
+    /// Runs a turn of `world`, counting it and what was allocated meanwhile.
+    pub fn update(&mut self, world: &mut World) {
+        let (before, start) = (memory_stats(), Instant::now());
+        world.update();
+        let (after, time) = (memory_stats(), start.elapsed());
+        self.turns += 1;
+        self.update_time += time;
+        self.allocations += after.allocations - before.allocations;
+        self.allocated_bytes += after.allocated_bytes - before.allocated_bytes;
+        self.freed_bytes += after.freed_bytes - before.freed_bytes;
+        self.vec_reallocs += after.vec_reallocs - before.vec_reallocs;
+    }
+
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    /// Mean time of the turns counted, none before the first.
+    pub fn mean_update_time(&self) -> Option<Duration> {
+        (self.turns > 0).then(|| Duration::from_nanos((self.update_time.as_nanos() / self.turns as u128) as u64))
+    }
+
+    /// Bytes the turns counted allocated and didn't free, less when they
+    /// freed more than they allocated.
+    pub fn net_bytes(&self) -> i64 {
+        self.allocated_bytes as i64 - self.freed_bytes as i64
+    }
+
+    /// The lines of the panel of `world`, by name, in the language set with
+    /// `locale::set_language`. Its vec reallocations are those of the
+    /// particle list alone, so worlds with trails compare by their policy.
+    pub fn rows(&self, world: &World) -> Vec<(&'static str, String)> {
+        let text = bundle();
+        let per_turn = self.allocations as f64 / self.turns.max(1) as f64;
+        let mut rows = vec![
+            (text.turns, self.turns.to_string()),
+            (text.particles, world.particles.len().to_string()),
+            (text.allocations, format!("{} ({:.1} {})", self.allocations, per_turn, text.per_turn)),
+            (text.allocated_bytes, self.allocated_bytes.to_string()),
+            (text.net_heap, self.net_bytes().to_string()),
+            (text.vec_reallocations, self.vec_reallocs.to_string()),
+            (text.update_time, self.mean_update_time().map_or("-".to_string(), |mean| format!("{:.3} ms", mean.as_secs_f64() * 1e3))),
+        ];
+        if let Some(pool) = &world.particle_pool {
+            let stats = pool.stats();
+            rows.push((text.particle_pool, format!("{} free, {} recycled, {} allocated", stats.free, stats.recycled, stats.allocated)));
+        }
+        rows
+    }
+}
@@ -0,0 +1,265 @@
+use crate::accessibility::Accessibility;
+use crate::ambient::AmbientCycle;
+use crate::behavior::{Flocking, StateMachine};
+use crate::blend::BlendMode;
+use crate::boundary::{Edge, EdgeBehavior};
+use crate::camera::CameraKey;
+use crate::cap::SoftCap;
+use crate::color::ColorSpec;
+use crate::controller::PopulationController;
+use crate::emitter::{Emitter, SubEmitter};
+use crate::fade::FadeModel;
+use crate::lifetime::PopulationPolicy;
+use crate::force::Force;
+use crate::integrator::Integrator;
+use crate::falloff::Falloff;
+use crate::migrate::migrate;
+use crate::obstacle::Obstacle;
+use crate::palette::Palette;
+use crate::params::{ParamVisitor, Tunable};
+use crate::region::SpawnRegion;
+use crate::shape::ShapeSpec;
+use crate::spatial::Collisions;
+use crate::trail::TrailSettings;
+use crate::units;
+use crate::zone::Zone;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Contents of a scene file, written in TOML.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Sources of particles; the bottom edge of the window is used when empty
+    #[serde(default, rename = "emitter")]
+    pub emitters: Vec<Emitter>,
+
+    /// Edge particles spawn from when no emitter shares the random spawns:
+    /// `"top"`, `"bottom"`, `"left"`, `"right"` or a line like `{ y = 300 }`
+    #[serde(default)]
+    pub spawn_edge: Edge,
+
+    /// Some or all edges, the whole window, a rectangle or a polygon particles
+    /// spawn in when there are no emitters, in place of `spawn_edge`
+    pub spawn_region: Option<SpawnRegion>,
+
+    /// Color of the particles spawned along `spawn_edge`
+    #[serde(default)]
+    pub color: ColorSpec,
+
+    /// How the particles spawned along `spawn_edge` fade out, the built-in decay when missing
+    pub fade: Option<FadeModel>,
+
+    /// How the particles spawned along `spawn_edge` are blended: `"alpha"`,
+    /// `"additive"` or `"multiply"`
+    #[serde(default)]
+    pub blend: BlendMode,
+
+    /// Radial opacity falloff all particles and sparks are drawn with, as
+    /// soft discs; solid squares when missing
+    pub falloff: Option<Falloff>,
+
+    /// Colors the particles by their age or speed from then on, in place of
+    /// the colors they spawned with; none when missing
+    pub palette: Option<Palette>,
+
+    /// Particles going past this edge or line are removed, never when missing
+    pub kill_plane: Option<Edge>,
+
+    /// What becomes of particles reaching the sides of the window: `"wrap"`,
+    /// `"bounce"`, `"kill"` (the default, once all the way out) or `"ignore"`
+    #[serde(default)]
+    pub edges: EdgeBehavior,
+
+    /// How the motion of particles is integrated: `"euler"`,
+    /// `"semi-implicit"` (the default), `"verlet"` or `"rk4"`
+    #[serde(default)]
+    pub integrator: Integrator,
+
+    /// Draws every particle with a fading line through its last positions,
+    /// none when missing
+    pub trail: Option<TrailSettings>,
+
+    /// Population over which particles are culled early at random, none when missing
+    pub soft_cap: Option<SoftCap>,
+
+    /// Steers the population towards a particle count, one swinging over
+    /// time or a heap budget, in place of the random spawns of `spawn_rate`;
+    /// none when missing
+    pub controller: Option<PopulationController>,
+
+    /// Lifetime of the particles and whether the dead are respawned; they
+    /// live until they fade out or leave the window when missing
+    pub population: Option<PopulationPolicy>,
+
+    /// Particles bouncing off each other, which they pass through when missing
+    pub collisions: Option<Collisions>,
+
+    /// Particles flocking like boids, each steering by the others around
+    /// it; they move on their own when missing
+    pub flocking: Option<Flocking>,
+
+    /// Static shapes particles bounce off
+    #[serde(default, rename = "obstacle")]
+    pub obstacles: Vec<Obstacle>,
+
+    /// Regions applying a force only to the particles inside them
+    #[serde(default, rename = "zone")]
+    pub zones: Vec<Zone>,
+
+    /// Gravity, wind and attractors acting on every particle
+    #[serde(default, rename = "force")]
+    pub forces: Vec<Force>,
+
+    /// Fraction of their velocity all particles lose every turn, like air resistance
+    #[serde(default)]
+    pub drag: f64,
+
+    /// Speed in pixels per turn no particle goes past, whatever pushes it;
+    /// unlimited when missing. Emitters can set their own
+    pub max_speed: Option<f64>,
+
+    /// Largest random velocity change applied to every particle each turn,
+    /// a Brownian-like jitter for dust motes and fireflies
+    #[serde(default)]
+    pub jitter: f64,
+
+    /// Spreads the particles spawned on a turn evenly over it instead of
+    /// starting them all at once, so high spawn rates don't leave rows
+    #[serde(default)]
+    pub spawn_stagger: bool,
+
+    /// States every particle goes through, each with its own forces and looks
+    #[serde(default, rename = "state")]
+    pub behavior: StateMachine,
+
+    /// Spark bursts thrown at every collision, disabled when missing
+    pub sparks: Option<SubEmitter>,
+
+    /// Color the window is painted with before every frame, by its alpha
+    /// over the frame before, `BACKGROUND` when missing
+    pub background: Option<[f32; 4]>,
+
+    /// Day and night cycle of the background and the particle colors, none when missing
+    pub ambient: Option<AmbientCycle>,
+
+    /// Where the window looks on the turns of a scripted demo, the whole
+    /// window all along when empty
+    #[serde(default, rename = "camera")]
+    pub camera: Vec<CameraKey>,
+
+    /// Size and motion of the spawned particles
+    #[serde(default)]
+    pub particle: ParticleSettings,
+
+    /// High-contrast colors and reduced motion, both off when missing
+    #[serde(default)]
+    pub accessibility: Accessibility,
+
+    /// Version of the scene format the file is written for, 1 when missing.
+    /// Files for older versions are upgraded as they load
+    pub version: Option<u32>,
+
+    /// Text the scene was read from, which worlds keep for their
+    /// checkpoints; none for the default scene
+    #[serde(skip)]
+    pub source: Option<String>,
+
+    /// Particles spawned before the first turn, 1000 when missing
+    pub initial_particles: Option<i32>,
+
+    /// Most particles spawned or culled at random on a turn, 3 when missing.
+    /// Also written as a rate like `"500/s"`, rounded to whole particles per turn
+    #[serde(default, deserialize_with = "units::spawn_rate::deserialize")]
+    pub spawn_rate: Option<i32>,
+
+    /// Live heap the world stops spawning under, like `"256MiB"`, or in
+    /// bytes; no limit when missing
+    #[serde(default, deserialize_with = "units::bytes::deserialize")]
+    pub max_heap: Option<usize>,
+
+    /// Particles room is made for before the first turn with `--preallocate`,
+    /// twice the soft cap's target or 4096 when missing
+    pub preallocate: Option<usize>,
+}
+
+/// Size and motion of the particles spawned along `spawn_edge` or by
+/// emitters. Sparks have their own.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParticleSettings {
+    /// Side of the square particles in pixels
+    #[serde(default = "default_size")]
+    pub size: f64,
+
+    /// Range the initial speed is drawn from, in pixels per turn
+    #[serde(default = "default_speed")]
+    pub speed: [f64; 2],
+
+    /// Range of the deceleration along the direction of travel, which wears
+    /// off over the first turns
+    #[serde(default = "default_braking")]
+    pub braking: [f64; 2],
+
+    /// What the particles are drawn as: `"square"`, `"circle"`, `"triangle"`
+    /// or `"trail"`, or a list of them picked from at random
+    #[serde(default)]
+    pub shape: ShapeSpec,
+
+    /// Factor the opacity of the particles is multiplied by every turn,
+    /// unless the scene's `fade` or their emitter's decays it
+    #[serde(default = "default_fade")]
+    pub fade: f64,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> ParticleSettings {
+        ParticleSettings {
+            size: default_size(),
+            speed: default_speed(),
+            braking: default_braking(),
+            shape: ShapeSpec::default(),
+            fade: default_fade(),
+        }
+    }
+}
+
+impl Tunable for ParticleSettings {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("size", &mut self.size, 0.1..=100.0);
+        visitor.float("fade", &mut self.fade, 0.0..=1.0);
+    }
+}
+
+fn default_size() -> f64 {
+    4.0
+}
+
+fn default_speed() -> [f64; 2] {
+    [0.0, 2.0]
+}
+
+fn default_braking() -> [f64; 2] {
+    [0.0, 0.15]
+}
+
+fn default_fade() -> f64 {
+    0.995
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Config> {
+        Config::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// A scene from the text of a scene file, upgraded like `load` does.
+    pub fn from_toml(text: &str) -> io::Result<Config> {
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+        let migration = migrate(text).map_err(invalid)?;
+        let mut config: Config = toml::from_str(&migration.text).map_err(|err| invalid(err.to_string()))?;
+        config.source = Some(text.to_string());
+        Ok(config)
+    }
+}
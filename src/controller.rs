@@ -0,0 +1,121 @@
+use crate::params::{self, ParamVisitor, Tunable};
+use crate::units;
+use crate::Particle;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// Heap bytes a particle takes, its box and its place in the list, which
+/// the heap controller counts in.
+pub const PARTICLE_BYTES: usize = std::mem::size_of::<Particle>() + std::mem::size_of::<Box<Particle>>();
+
+/// Decides how many particles every turn spawns, or culls when negative,
+/// in place of the random spawns of `spawn_rate`, to steer the population
+/// towards a target. Every turn closes `gain` of the gap to it, at least a
+/// particle and at most `max_step`, so the population settles on the target
+/// without overshooting it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PopulationController {
+    /// Holds the population at `target` particles
+    Count {
+        target: usize,
+        #[serde(default = "default_gain")]
+        gain: f64,
+        #[serde(default = "default_max_step")]
+        max_step: usize,
+    },
+
+    /// Swings the target from `mean - amplitude` to `mean + amplitude` and
+    /// back over `period` turns
+    Wave {
+        mean: usize,
+        amplitude: usize,
+        #[serde(default = "default_period")]
+        period: u64,
+        #[serde(default = "default_gain")]
+        gain: f64,
+        #[serde(default = "default_max_step")]
+        max_step: usize,
+    },
+
+    /// Keeps the live heap under `max_bytes`, like `"10MB"`, read from the
+    /// allocator every turn and counted in particles of `PARTICLE_BYTES`.
+    /// Unlike `max_heap`, which only stops spawns near the limit, it spawns
+    /// up to it too
+    Heap {
+        #[serde(deserialize_with = "units::bytes::required")]
+        max_bytes: usize,
+        #[serde(default = "default_gain")]
+        gain: f64,
+        #[serde(default = "default_max_step")]
+        max_step: usize,
+    },
+}
+
+fn default_gain() -> f64 {
+    0.05
+}
+
+fn default_max_step() -> usize {
+    50
+}
+
+fn default_period() -> u64 {
+    1200
+}
+
+impl PopulationController {
+    /// Particle count aimed at on `turn`, for the heap from how far
+    /// `live_bytes` are from its budget.
+    pub fn target(&self, turn: u64, population: usize, live_bytes: usize) -> f64 {
+        match *self {
+            PopulationController::Count { target, .. } => target as f64,
+            PopulationController::Wave { mean, amplitude, period, .. } => {
+                let phase = (turn % period.max(1)) as f64 / period.max(1) as f64;
+                (mean as f64 + amplitude as f64 * (TAU * phase).sin()).max(0.0)
+            }
+            PopulationController::Heap { max_bytes, .. } => {
+                let room = max_bytes as f64 - live_bytes as f64;
+                (population as f64 + room / PARTICLE_BYTES as f64).max(0.0)
+            }
+        }
+    }
+
+    /// Particles to spawn on `turn`, or to cull when negative.
+    pub fn decide(&self, turn: u64, population: usize, live_bytes: usize) -> i32 {
+        let (gain, max_step) = match *self {
+            PopulationController::Count { gain, max_step, .. }
+            | PopulationController::Wave { gain, max_step, .. }
+            | PopulationController::Heap { gain, max_step, .. } => (gain, max_step),
+        };
+        let gap = self.target(turn, population, live_bytes) - population as f64;
+        if gap.abs() < 1.0 {
+            return 0;
+        }
+        let step = (gap * gain.clamp(0.0, 1.0)).round();
+        let step = if step == 0.0 { gap.signum() } else { step };
+        step.clamp(-(max_step as f64), max_step as f64) as i32
+    }
+}
+
+impl Tunable for PopulationController {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        let (gain, max_step) = match self {
+            PopulationController::Count { target, gain, max_step } => {
+                params::size(visitor, "target", target, 0..=10_000_000);
+                (gain, max_step)
+            }
+            PopulationController::Wave { mean, amplitude, gain, max_step, .. } => {
+                params::size(visitor, "mean", mean, 0..=10_000_000);
+                params::size(visitor, "amplitude", amplitude, 0..=10_000_000);
+                (gain, max_step)
+            }
+            PopulationController::Heap { max_bytes, gain, max_step } => {
+                params::size(visitor, "max_bytes", max_bytes, 0..=u64::MAX);
+                (gain, max_step)
+            }
+        };
+        visitor.float("gain", gain, 0.0..=1.0);
+        params::size(visitor, "max_step", max_step, 1..=100_000);
+    }
+}
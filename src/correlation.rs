@@ -0,0 +1,130 @@
+use piston_window::*;
+use std::collections::VecDeque;
+
+const CHART_TURNS: usize = 240;
+const CHART_HEIGHT: f64 = 80.0;
+
+const PARTICLES_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 0.9];
+const HEAP_COLOR: [f32; 4] = [0.4, 0.9, 0.5, 0.9];
+
+/// Pearson correlation of two series, updated one pair at a time with
+/// Welford's method so long runs don't lose precision.
+#[derive(Debug, Clone, Default)]
+pub struct Correlation {
+    count: u64,
+    mean_x: f64,
+    mean_y: f64,
+    // Sums of squared deviations from the means, and of their products
+    m2_x: f64,
+    m2_y: f64,
+    co_moment: f64,
+}
+
+impl Correlation {
+    pub fn add(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / n;
+        let dy = y - self.mean_y;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.co_moment += dx * (y - self.mean_y);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The coefficient, from -1 to 1, or `None` while either series is constant.
+    pub fn coefficient(&self) -> Option<f64> {
+        let spread = (self.m2_x * self.m2_y).sqrt();
+        (self.count > 1 && spread > 0.0).then(|| (self.co_moment / spread).clamp(-1.0, 1.0))
+    }
+}
+
+/// C shows the particle count and the live heap of the last turns on the
+/// same time axis, each scaled to its own range, with their correlation.
+pub struct CorrelationChart {
+    turns: VecDeque<(usize, usize)>,
+
+    /// Over every turn recorded, not only the ones on the chart
+    pub run: Correlation,
+}
+
+impl CorrelationChart {
+    pub fn new() -> CorrelationChart {
+        CorrelationChart { turns: VecDeque::with_capacity(CHART_TURNS), run: Correlation::default() }
+    }
+
+    pub fn push(&mut self, particles: usize, live_bytes: usize) {
+        if self.turns.len() == CHART_TURNS {
+            self.turns.pop_front();
+        }
+        self.turns.push_back((particles, live_bytes));
+        self.run.add(particles as f64, live_bytes as f64);
+    }
+
+    /// Correlation over the turns on the chart.
+    pub fn recent(&self) -> Correlation {
+        let mut correlation = Correlation::default();
+        for &(particles, live_bytes) in &self.turns {
+            correlation.add(particles as f64, live_bytes as f64);
+        }
+        correlation
+    }
+
+    /// What the chart can't draw without text, for the window title: the
+    /// coefficient and the range of both axes.
+    pub fn describe(&self) -> String {
+        let coefficient = self.recent().coefficient().map_or("-".to_string(), |r| format!("{:.3}", r));
+        let (particles, heap) = self.ranges();
+        format!(
+            "particles vs heap r = {} ({}-{} particles, {}-{} KiB)",
+            coefficient,
+            particles.0,
+            particles.1,
+            heap.0 / 1024,
+            heap.1 / 1024
+        )
+    }
+
+    fn ranges(&self) -> ((usize, usize), (usize, usize)) {
+        let min_max = |values: &mut dyn Iterator<Item = usize>| {
+            values.fold(None, |range: Option<(usize, usize)>, value| match range {
+                Some((min, max)) => Some((min.min(value), max.max(value))),
+                None => Some((value, value)),
+            })
+        };
+        (
+            min_max(&mut self.turns.iter().map(|turn| turn.0)).unwrap_or((0, 0)),
+            min_max(&mut self.turns.iter().map(|turn| turn.1)).unwrap_or((0, 0)),
+        )
+    }
+
+    /// Draws the chart in the top left corner. The particle count is scaled
+    /// to the left axis, the heap to the right one, each from its own minimum
+    /// to maximum over the chart so their shapes can be compared.
+    pub fn draw<G: Graphics>(&self, ctx: Context, g: &mut G) {
+        let (left, top, width) = (10.0, 16.0, CHART_TURNS as f64);
+        rectangle([0.0, 0.0, 0.0, 0.6], [left, top, width, CHART_HEIGHT], ctx.transform, g);
+
+        // Axes in the color of their series
+        rectangle(PARTICLES_COLOR, [left, top, 2.0, CHART_HEIGHT], ctx.transform, g);
+        rectangle(HEAP_COLOR, [left + width - 2.0, top, 2.0, CHART_HEIGHT], ctx.transform, g);
+
+        let (particles, heap) = self.ranges();
+        let scale = |value: usize, (min, max): (usize, usize)| {
+            let spread = (max - min).max(1) as f64;
+            top + CHART_HEIGHT - (value - min) as f64 / spread * CHART_HEIGHT
+        };
+        for (i, (from, to)) in self.turns.iter().zip(self.turns.iter().skip(1)).enumerate() {
+            let (x0, x1) = (left + i as f64, left + i as f64 + 1.0);
+            let (p0, p1) = (scale(from.0, particles), scale(to.0, particles));
+            line_from_to(PARTICLES_COLOR, 0.7, [x0, p0], [x1, p1], ctx.transform, g);
+            let (h0, h1) = (scale(from.1, heap), scale(to.1, heap));
+            line_from_to(HEAP_COLOR, 0.7, [x0, h0], [x1, h1], ctx.transform, g);
+        }
+    }
+}
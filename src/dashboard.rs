@@ -0,0 +1,200 @@
+use memory_particles::allocator::{memory_stats, report_frame, MemoryStats};
+use memory_particles::shutdown;
+use memory_particles::World;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+// How often the numbers are taken and the dashboard redrawn
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+// Samples kept for the sparklines, wider than most terminals
+const HISTORY: usize = 300;
+
+/// What the dashboard shows at one point in time.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    particles: usize,
+    allocations_per_second: f64,
+    bytes_per_second: f64,
+    frees_per_second: f64,
+    live_bytes: usize,
+    peak_live_bytes: usize,
+    turns_per_second: f64,
+}
+
+/// Runs the world without a window, showing a live dashboard of its
+/// particles, allocations, heap and turn rate in the terminal instead.
+/// `rate` caps the turns per second, 0 running them as fast as possible.
+/// q, Esc or Ctrl+C quit.
+pub fn run(world: &mut World, rate: u32) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run_loop(&mut terminal, world, rate);
+    ratatui::restore();
+    result
+}
+
+fn run_loop(terminal: &mut DefaultTerminal, world: &mut World, rate: u32) -> io::Result<()> {
+    let turn_interval = (rate > 0).then(|| Duration::from_secs_f64(1.0 / rate as f64));
+    let mut history: VecDeque<Sample> = VecDeque::with_capacity(HISTORY);
+
+    let start = Instant::now();
+    let mut next_turn = start;
+    let mut last_sample = (start, memory_stats(), world.current_turn);
+    loop {
+        let now = Instant::now();
+        if now >= next_turn {
+            world.update();
+            report_frame(world.current_turn);
+            if let Some(interval) = turn_interval {
+                // Turns missed while drawing are not caught up on
+                next_turn = (next_turn + interval).max(now);
+            }
+        }
+
+        let since_sample = now.duration_since(last_sample.0);
+        if since_sample >= SAMPLE_INTERVAL {
+            let stats = memory_stats();
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(sample(world, &last_sample.1, &stats, world.current_turn - last_sample.2, since_sample));
+            last_sample = (now, stats, world.current_turn);
+
+            terminal.draw(|frame| draw(frame, world, &history, rate, start.elapsed()))?;
+            if quit_requested(Duration::ZERO)? {
+                return Ok(());
+            }
+        } else if let Some(wait) = next_turn.checked_duration_since(now) {
+            // Waiting for the next turn on the keyboard rather than sleeping
+            if quit_requested(wait.min(SAMPLE_INTERVAL - since_sample))? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn sample(world: &World, before: &MemoryStats, after: &MemoryStats, turns: u64, elapsed: Duration) -> Sample {
+    let seconds = elapsed.as_secs_f64();
+    Sample {
+        particles: world.particles.len(),
+        allocations_per_second: (after.allocations - before.allocations) as f64 / seconds,
+        bytes_per_second: (after.allocated_bytes - before.allocated_bytes) as f64 / seconds,
+        frees_per_second: (after.deallocations - before.deallocations) as f64 / seconds,
+        live_bytes: after.live_bytes,
+        peak_live_bytes: after.peak_live_bytes,
+        turns_per_second: turns as f64 / seconds,
+    }
+}
+
+// Waits up to `timeout` for a key, telling whether it asks to quit. Ctrl+C
+// arrives as a key in raw mode, but the handler may have seen it first
+fn quit_requested(timeout: Duration) -> io::Result<bool> {
+    if shutdown::requested() {
+        return Ok(true);
+    }
+    if !event::poll(timeout)? {
+        return Ok(false);
+    }
+    Ok(match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => {
+            matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        }
+        _ => false,
+    })
+}
+
+fn draw(frame: &mut Frame, world: &World, history: &VecDeque<Sample>, rate: u32, elapsed: Duration) {
+    let [header, particles, allocations, heap, turns] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+        Constraint::Fill(1),
+    ])
+    .areas(frame.area());
+
+    let rate = if rate == 0 { "as fast as possible".to_string() } else { format!("{} turns/s max", rate) };
+    let summary = Line::from(format!(
+        "turn {}  running {:.0} s  {}  q to quit",
+        world.current_turn,
+        elapsed.as_secs_f64(),
+        rate
+    ));
+    frame.render_widget(Paragraph::new(summary).block(Block::bordered().title(" memory particles ")), header);
+
+    let Some(last) = history.back() else {
+        return;
+    };
+    // Only the most recent samples that fit, right aligned like a scrolling chart
+    let width = particles.width.saturating_sub(2) as usize;
+    let recent = |value: fn(&Sample) -> u64| -> Vec<u64> {
+        history.iter().skip(history.len().saturating_sub(width)).map(value).collect()
+    };
+
+    let charts = [
+        (
+            format!(" particles: {} ", last.particles),
+            recent(|sample| sample.particles as u64),
+            Color::LightBlue,
+            particles,
+        ),
+        (
+            format!(
+                " allocations: {:.0}/s, {}/s ",
+                last.allocations_per_second,
+                format_bytes(last.bytes_per_second as u64)
+            ),
+            recent(|sample| sample.allocations_per_second as u64),
+            Color::LightRed,
+            allocations,
+        ),
+        (
+            format!(
+                " live heap: {}, peak {}, {:.0} frees/s ",
+                format_bytes(last.live_bytes as u64),
+                format_bytes(last.peak_live_bytes as u64),
+                last.frees_per_second
+            ),
+            recent(|sample| sample.live_bytes as u64),
+            Color::LightGreen,
+            heap,
+        ),
+        (
+            format!(" turns: {:.0}/s ", last.turns_per_second),
+            recent(|sample| sample.turns_per_second as u64),
+            Color::Yellow,
+            turns,
+        ),
+    ];
+    for (title, data, color, area) in charts {
+        let sparkline = Sparkline::default()
+            .block(Block::bordered().title(title))
+            .data(&data)
+            .style(Style::default().fg(color));
+        frame.render_widget(sparkline, area);
+    }
+}
+
+/// Bytes in the largest unit that keeps them above 1, like `1.5 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,205 @@
+use crate::allocator::FrameAllocations;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Most frames kept however short the window, which headless runs go
+// through by the hundred thousand a second
+const MAX_SAMPLES: usize = 100_000;
+
+// Most lines of the log kept
+const MAX_LOG_LINES: usize = 500;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BUNDLE: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// What a frame of the window or a turn of `bench` left in the telemetry.
+#[derive(Debug, Clone, Copy)]
+pub struct BundleSample {
+    /// Since recording started
+    pub seconds: f64,
+    pub frame: u64,
+    pub turn: u64,
+    pub particles: usize,
+    pub live_bytes: usize,
+    pub allocations: FrameAllocations,
+}
+
+// The telemetry of the last seconds, the log and the files of the bundle,
+// all made room for up front: frames are recorded at the end of each, where
+// allocating would count in the next one
+struct Recorder {
+    start: Instant,
+    seconds: f64,
+    samples: VecDeque<BundleSample>,
+    log: VecDeque<String>,
+    files: Vec<(String, String)>,
+}
+
+/// Starts keeping the telemetry of the last `seconds` for `write`.
+pub fn enable(seconds: f64) {
+    *lock() = Some(Recorder {
+        start: Instant::now(),
+        seconds: seconds.max(0.0),
+        samples: VecDeque::with_capacity(MAX_SAMPLES),
+        log: VecDeque::with_capacity(MAX_LOG_LINES),
+        files: Vec::new(),
+    });
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Adds the frame just ended to the telemetry, dropping what is older than
+/// the seconds kept.
+pub fn record(frame: u64, turn: u64, particles: usize, live_bytes: usize, allocations: FrameAllocations) {
+    if !is_enabled() {
+        return;
+    }
+    let Ok(mut bundle) = BUNDLE.try_lock() else {
+        return;
+    };
+    let Some(recorder) = bundle.as_mut() else {
+        return;
+    };
+    let seconds = recorder.start.elapsed().as_secs_f64();
+    while recorder.samples.front().is_some_and(|sample| sample.seconds < seconds - recorder.seconds)
+        || recorder.samples.len() >= MAX_SAMPLES
+    {
+        recorder.samples.pop_front();
+    }
+    recorder.samples.push_back(BundleSample { seconds, frame, turn, particles, live_bytes, allocations });
+}
+
+/// Adds `line` to the log of the bundle, like the status messages of the window.
+pub fn log(line: &str) {
+    if !is_enabled() {
+        return;
+    }
+    if let Some(recorder) = lock().as_mut() {
+        if recorder.log.len() >= MAX_LOG_LINES {
+            recorder.log.pop_front();
+        }
+        let seconds = recorder.start.elapsed().as_secs_f64();
+        recorder.log.push_back(format!("{:10.3}  {}", seconds, line));
+    }
+}
+
+/// Puts `contents` in the bundle as `name`, replacing what was there, for
+/// what is known before anything goes wrong, like the scene and the environment.
+pub fn set_file(name: &str, contents: String) {
+    if let Some(recorder) = lock().as_mut() {
+        match recorder.files.iter_mut().find(|(file, _)| file == name) {
+            Some(file) => file.1 = contents,
+            None => recorder.files.push((name.to_string(), contents)),
+        }
+    }
+}
+
+/// Writes the bundle to `path` as a zip archive: the files set, the log
+/// and the telemetry as `telemetry.csv`.
+pub fn write(path: &Path) -> io::Result<()> {
+    let guard = lock();
+    let Some(recorder) = guard.as_ref() else {
+        return Err(io::Error::other("the debug bundle isn't being recorded"));
+    };
+    let mut files: Vec<(String, Vec<u8>)> = recorder.files.iter().map(|(name, text)| (name.clone(), text.clone().into_bytes())).collect();
+    files.push(("log.txt".to_string(), recorder.log.iter().flat_map(|line| [line.as_str(), "\n"]).collect::<String>().into_bytes()));
+    files.push(("telemetry.csv".to_string(), telemetry_csv(&recorder.samples).into_bytes()));
+    drop(guard);
+    write_zip(path, &files)
+}
+
+fn telemetry_csv(samples: &VecDeque<BundleSample>) -> String {
+    let mut csv = String::from("seconds,frame,turn,particles,live_bytes,allocations,allocated_bytes,min_ns,mean_ns,max_ns\n");
+    for sample in samples {
+        let a = &sample.allocations;
+        csv.push_str(&format!(
+            "{:.4},{},{},{},{},{},{},{},{},{}\n",
+            sample.seconds,
+            sample.frame,
+            sample.turn,
+            sample.particles,
+            sample.live_bytes,
+            a.count,
+            a.bytes,
+            a.min_nanos,
+            a.mean_nanos(),
+            a.max_nanos
+        ));
+    }
+    csv
+}
+
+// Also taken by the panic hook, which may run while a panic held it
+fn lock() -> MutexGuard<'static, Option<Recorder>> {
+    BUNDLE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// A zip archive of `files`, deflated, in the order given. Written by hand
+// like the other formats: a local header before each file, then the
+// central directory listing them all and its end record
+fn write_zip(path: &Path, files: &[(String, Vec<u8>)]) -> io::Result<()> {
+    let (time, date) = dos_time(SystemTime::now());
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut directory = Vec::new();
+    let mut offset = 0u32;
+    for (name, data) in files {
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let crc = crc32fast::hash(data);
+
+        // Shared by the local header and the directory entry
+        let mut fields = Vec::with_capacity(26);
+        fields.extend_from_slice(&20u16.to_le_bytes()); // version needed, for deflate
+        fields.extend_from_slice(&0u16.to_le_bytes()); // flags
+        fields.extend_from_slice(&8u16.to_le_bytes()); // deflate
+        fields.extend_from_slice(&time.to_le_bytes());
+        fields.extend_from_slice(&date.to_le_bytes());
+        fields.extend_from_slice(&crc.to_le_bytes());
+        fields.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0u16.to_le_bytes()); // extra field
+
+        out.write_all(&0x04034b50u32.to_le_bytes())?;
+        out.write_all(&fields)?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&compressed)?;
+
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes()); // made by
+        directory.extend_from_slice(&fields);
+        directory.extend_from_slice(&[0; 6]); // comment, disk, internal attributes
+        directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+        offset += (30 + name.len() + compressed.len()) as u32;
+    }
+    out.write_all(&directory)?;
+    out.write_all(&0x06054b50u32.to_le_bytes())?;
+    out.write_all(&[0; 4])?; // disks
+    out.write_all(&(files.len() as u16).to_le_bytes())?;
+    out.write_all(&(files.len() as u16).to_le_bytes())?;
+    out.write_all(&(directory.len() as u32).to_le_bytes())?;
+    out.write_all(&offset.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?; // comment
+    out.flush()
+}
+
+// The time and date fields of zip archives, in UTC
+fn dos_time(time: SystemTime) -> (u16, u16) {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+    let (year, month, day) = crate::metadata::civil_date(days);
+    let time = ((rest / 3600) << 11) | ((rest % 3600 / 60) << 5) | ((rest % 60) / 2);
+    let date = (((year - 1980).max(0) as u64) << 9) | ((month as u64) << 5) | day as u64;
+    (time as u16, date as u16)
+}
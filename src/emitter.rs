@@ -0,0 +1,313 @@
+use crate::blend::BlendMode;
+use crate::color::ColorSpec;
+use crate::config::ParticleSettings;
+use crate::fade::FadeModel;
+use crate::motion::MotionPath;
+use crate::params::{self, ParamVisitor, Tunable};
+use crate::units;
+use graphics::math::Vec2d;
+use serde::{Deserialize, Serialize};
+
+/// A source of particles placed in the scene.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Emitter {
+    /// Where the emitter sits when it doesn't follow a path
+    #[serde(default)]
+    pub position: Vec2d<f64>,
+
+    /// Moves the emitter over time, leaving comet-like trails of particles
+    pub path: Option<MotionPath>,
+
+    /// Other end of the line particles spawn along, making a line emitter;
+    /// they all spawn at `position` when missing. Moves along with the path
+    pub to: Option<Vec2d<f64>>,
+
+    /// Direction particles leave in, straight up by default
+    #[serde(default = "default_direction")]
+    pub direction: Vec2d<f64>,
+
+    /// Half the angle of the cone around `direction` particles leave in, in
+    /// degrees; 0 sends them all the same way, 180 any way
+    #[serde(default)]
+    pub spread: f64,
+
+    /// Particles spawned every turn by this emitter alone, fractions adding
+    /// up over turns, or a rate like `"30/s"`. Emitters without one share
+    /// the random spawns of the scene
+    #[serde(default, deserialize_with = "units::rate::deserialize")]
+    pub rate: Option<f64>,
+
+    /// Saves up the particles of `rate` and throws them all at once every
+    /// this many turns, as bursts
+    pub burst_every: Option<u64>,
+
+    /// Size and motion of the particles it spawns, the scene's `[particle]` when missing
+    pub particle: Option<ParticleSettings>,
+
+    /// Color given to the particles it spawns
+    #[serde(default)]
+    pub color: ColorSpec,
+
+    /// How the particles it spawns fade out, the built-in decay when missing
+    pub fade: Option<FadeModel>,
+
+    /// Terminal velocity of the particles it spawns, the scene's `max_speed` when missing
+    pub max_speed: Option<f64>,
+
+    /// Spawns pinned particles, which stay where they appear, like dust
+    /// settled on an obstacle or the anchors of a cloth
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// How the particles it spawns are blended, `"alpha"` by default
+    #[serde(default)]
+    pub blend: BlendMode,
+
+    /// Makes the particles it spawns rockets, bursting into others when
+    /// their fuse burns out; none when missing
+    pub firework: Option<Firework>,
+
+    // Particles of `rate` not spawned yet
+    #[serde(skip)]
+    owed: f64,
+}
+
+fn default_direction() -> Vec2d<f64> {
+    [0.0, -1.0]
+}
+
+// Particles a fountain dropped with the mouse spawns every turn, and the
+// half angle of its cone
+const FOUNTAIN_RATE: f64 = 2.0;
+const FOUNTAIN_SPREAD: f64 = 15.0;
+
+impl Emitter {
+    /// A fountain at `position`, throwing particles up in a narrow cone
+    /// at a rate of its own.
+    pub fn fountain(position: Vec2d<f64>) -> Emitter {
+        Emitter {
+            position,
+            path: None,
+            to: None,
+            direction: default_direction(),
+            spread: FOUNTAIN_SPREAD,
+            rate: Some(FOUNTAIN_RATE),
+            burst_every: None,
+            particle: None,
+            color: ColorSpec::default(),
+            fade: None,
+            max_speed: None,
+            pinned: false,
+            blend: BlendMode::default(),
+            firework: None,
+            owed: 0.0,
+        }
+    }
+
+    /// Particles due this turn from its `rate`, none without one. `dt` is
+    /// the length of the turn in turns of `BASE_TURN_RATE`.
+    pub fn due(&mut self, turn: u64, dt: f64) -> usize {
+        let Some(rate) = self.rate else {
+            return 0;
+        };
+        self.owed += rate * dt;
+        if let Some(every) = self.burst_every {
+            if !turn.is_multiple_of(every.max(1)) {
+                return 0;
+            }
+        }
+        let due = self.owed.floor();
+        self.owed -= due;
+        due as usize
+    }
+
+    /// Where a particle spawned on `turn` is placed, `along` the way from
+    /// `position` to `to` for line emitters.
+    pub fn point_at(&self, turn: u64, along: f64) -> Vec2d<f64> {
+        let [x, y] = self.position_at(turn);
+        match self.to {
+            Some(to) => [x + along * (to[0] - self.position[0]), y + along * (to[1] - self.position[1])],
+            None => [x, y],
+        }
+    }
+
+    /// Moves the emitter, its line and its path to the same place relative
+    /// to a window resized by `scale` on each axis.
+    pub fn rescale(&mut self, scale: Vec2d<f64>) {
+        for point in std::iter::once(&mut self.position).chain(self.to.as_mut()) {
+            *point = [point[0] * scale[0], point[1] * scale[1]];
+        }
+        if let Some(path) = &mut self.path {
+            path.rescale(scale);
+        }
+    }
+
+    /// Direction of a particle leaving at `side` of the cone, from -1 on
+    /// one side to 1 on the other.
+    pub fn direction_at(&self, side: f64) -> Vec2d<f64> {
+        let [x, y] = self.direction;
+        let length = x.hypot(y);
+        if length == 0.0 {
+            return default_direction();
+        }
+        if side == 0.0 {
+            return [x / length, y / length];
+        }
+        let angle = y.atan2(x) + side * self.spread.to_radians();
+        [angle.cos(), angle.sin()]
+    }
+
+    /// Where particles spawned on `turn` are placed.
+    pub fn position_at(&self, turn: u64) -> Vec2d<f64> {
+        match &self.path {
+            Some(path) => path.position(turn),
+            None => self.position,
+        }
+    }
+}
+
+impl Tunable for Emitter {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("x", &mut self.position[0], f64::NEG_INFINITY..=f64::INFINITY);
+        visitor.float("y", &mut self.position[1], f64::NEG_INFINITY..=f64::INFINITY);
+        if let Some(max_speed) = &mut self.max_speed {
+            visitor.float("max_speed", max_speed, 0.0..=f64::INFINITY);
+        }
+        visitor.float("spread", &mut self.spread, 0.0..=180.0);
+        if let Some(rate) = &mut self.rate {
+            visitor.float("rate", rate, 0.0..=f64::INFINITY);
+        }
+    }
+}
+
+/// A stage of fireworks: rockets flying for `fuse` turns, then bursting
+/// into `count` particles thrown evenly around where they were, which are
+/// rockets of the stage `then` when there is one, bursting in turn. The
+/// particles of the bursts are spawned into the population like any other,
+/// so every burst is an allocation spike.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Firework {
+    /// Range of turns rockets fly before bursting, drawn for each
+    #[serde(default = "default_fuse")]
+    pub fuse: [u32; 2],
+
+    /// Particles thrown by every burst
+    #[serde(default = "default_firework_count")]
+    pub count: u32,
+
+    /// Size, speed and shape of the particles of the bursts, their speed
+    /// being outwards from the rocket
+    #[serde(default = "default_firework_particle")]
+    pub particle: ParticleSettings,
+
+    /// Share of the velocity of the rocket its burst keeps, so bursts of
+    /// rockets still rising carry on upwards
+    #[serde(default = "default_inherit")]
+    pub inherit: f64,
+
+    #[serde(default)]
+    pub color: ColorSpec,
+
+    /// How the particles of the bursts fade out, the built-in decay when missing
+    pub fade: Option<FadeModel>,
+
+    #[serde(default)]
+    pub blend: BlendMode,
+
+    /// The next stage, which the particles of the bursts are rockets of
+    pub then: Option<Box<Firework>>,
+}
+
+impl Firework {
+    /// The stage `depth` stages after this one, this one at 0.
+    pub fn stage(&self, depth: u8) -> Option<&Firework> {
+        match depth {
+            0 => Some(self),
+            _ => self.then.as_deref()?.stage(depth - 1),
+        }
+    }
+}
+
+fn default_fuse() -> [u32; 2] {
+    [50, 80]
+}
+
+fn default_firework_count() -> u32 {
+    60
+}
+
+fn default_firework_particle() -> ParticleSettings {
+    ParticleSettings { size: 3.0, speed: [1.0, 2.5], braking: [0.0, 0.0], ..ParticleSettings::default() }
+}
+
+fn default_inherit() -> f64 {
+    0.3
+}
+
+/// What makes a particle a rocket: the emitter and the stage of its
+/// firework, and the age it bursts at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rocket {
+    pub emitter: u16,
+    pub stage: u8,
+    pub fuse: u32,
+}
+
+/// Spawns a short burst of particles in reaction to a world event,
+/// like the sparks thrown where a particle hits an obstacle.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubEmitter {
+    /// Particles spawned per event
+    #[serde(default = "default_burst_count")]
+    pub count: u32,
+
+    /// Speed in pixels per turn, in a random direction
+    #[serde(default = "default_burst_speed")]
+    pub speed: f64,
+
+    /// Alpha multiplier applied every turn, lower values die out sooner
+    #[serde(default = "default_burst_fade")]
+    pub fade: f32,
+
+    #[serde(default = "default_burst_color")]
+    pub color: [f32; 4],
+
+    #[serde(default = "default_burst_size")]
+    pub size: f64,
+
+    /// How the particles of the bursts are blended, `"alpha"` by default
+    #[serde(default)]
+    pub blend: BlendMode,
+}
+
+impl Tunable for SubEmitter {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        params::integer32(visitor, "count", &mut self.count, 0..=1000);
+        visitor.float("speed", &mut self.speed, 0.0..=100.0);
+        params::float32(visitor, "fade", &mut self.fade, 0.0..=1.0);
+        visitor.float("size", &mut self.size, 0.0..=100.0);
+    }
+}
+
+fn default_burst_count() -> u32 {
+    8
+}
+
+fn default_burst_speed() -> f64 {
+    2.0
+}
+
+fn default_burst_fade() -> f32 {
+    0.85
+}
+
+fn default_burst_color() -> [f32; 4] {
+    [1.0, 0.7, 0.2, 1.0]
+}
+
+fn default_burst_size() -> f64 {
+    2.0
+}
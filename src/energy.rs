@@ -0,0 +1,67 @@
+use crate::metadata::RunMetadata;
+use crate::Particle;
+use graphics::math::Vec2d;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Kinetic energy and momentum of a set of particles, every particle
+/// counting as a unit mass. Pinned particles hold still whatever their
+/// velocity, so they are left out.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Energy {
+    pub kinetic: f64,
+    pub momentum: Vec2d<f64>,
+}
+
+impl Energy {
+    pub fn of<'a, I: IntoIterator<Item = &'a Particle>>(particles: I) -> Energy {
+        let mut energy = Energy::default();
+        for particle in particles.into_iter().filter(|particle| !particle.pinned) {
+            let [x, y] = particle.velocity;
+            energy.kinetic += 0.5 * (x * x + y * y);
+            energy.momentum[0] += x;
+            energy.momentum[1] += y;
+        }
+        energy
+    }
+
+    /// Length of the total momentum vector.
+    pub fn momentum_magnitude(&self) -> f64 {
+        self.momentum[0].hypot(self.momentum[1])
+    }
+}
+
+/// Energy and momentum of the world after every turn of the run, to check
+/// that integrators and collisions conserve them, or lose them only as fast
+/// as drag should.
+#[derive(Default)]
+pub struct EnergyHistory {
+    samples: Vec<(u64, usize, Energy)>,
+}
+
+impl EnergyHistory {
+    pub fn new() -> EnergyHistory {
+        EnergyHistory::default()
+    }
+
+    /// Makes room for `turns` more turns, so recording them allocates nothing.
+    pub fn reserve(&mut self, turns: usize) {
+        self.samples.reserve(turns);
+    }
+
+    pub fn record(&mut self, turn: u64, particles: usize, energy: Energy) {
+        self.samples.push((turn, particles, energy));
+    }
+
+    pub fn write_csv(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}", metadata.comment_header())?;
+        writeln!(out, "turn,particles,kinetic_energy,momentum_x,momentum_y")?;
+        for (turn, particles, energy) in &self.samples {
+            let [x, y] = energy.momentum;
+            writeln!(out, "{},{},{},{},{}", turn, particles, energy.kinetic, x, y)?;
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,9 @@
+use graphics::math::Vec2d;
+
+/// Something that happened in the world during a turn, which sub-emitters
+/// can react to.
+#[derive(Debug, Clone, Copy)]
+pub enum WorldEvent {
+    /// A particle hit an obstacle at the given contact point
+    Collision { position: Vec2d<f64> },
+}
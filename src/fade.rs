@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// How the opacity of the particles spawned by an emitter decays, as set in
+/// a scene file. Particles keep the built-in decay when it is missing.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum FadeModel {
+    /// Opacity halves every `half_life` turns
+    Exponential { half_life: f64 },
+
+    /// Opacity drops by the same amount every turn, reaching zero after `lifetime` turns
+    Linear { lifetime: u32 },
+
+    /// Opacity stays the same for `lifetime` turns and then drops to zero at once
+    Step { lifetime: u32 },
+}
+
+impl FadeModel {
+    /// The fade of a particle spawned with opacity `alpha`.
+    pub fn start(&self, alpha: f32) -> Fade {
+        match *self {
+            FadeModel::Exponential { half_life } => Fade::Multiply(0.5f64.powf(1.0 / half_life.max(f64::EPSILON)) as f32),
+            FadeModel::Linear { lifetime } => Fade::Subtract(alpha / lifetime.max(1) as f32),
+            FadeModel::Step { lifetime } => Fade::Step { turns_left: lifetime },
+        }
+    }
+}
+
+/// Decay of a particle's opacity, applied every turn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Fade {
+    /// Multiplies the opacity by this factor
+    Multiply(f32),
+
+    /// Takes this amount from the opacity, down to zero
+    Subtract(f32),
+
+    /// Leaves the opacity alone until the count runs out
+    Step { turns_left: u32 },
+}
+
+impl Fade {
+    pub fn apply(&mut self, alpha: &mut f32) {
+        match self {
+            Fade::Multiply(factor) => *alpha *= *factor,
+            Fade::Subtract(amount) => *alpha = (*alpha - *amount).max(0.0),
+            Fade::Step { turns_left } => {
+                if *turns_left == 0 {
+                    *alpha = 0.0;
+                } else {
+                    *turns_left -= 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::blend::BlendMode;
+use crate::params::{ParamVisitor, Tunable};
+use serde::Deserialize;
+
+/// Nested discs a soft particle is drawn with.
+pub const LAYERS: usize = 6;
+
+/// Radial falloff of the opacity of particles, drawn as soft discs filling
+/// their square instead of solid squares so dense clouds blend smoothly.
+/// The opacity goes from the particle's own at the center to nothing at the
+/// rim as `(1 - r²)^sharpness`, `r` going from 0 to 1: low values make fuzzy
+/// balls, high ones small bright cores.
+///
+/// There is no texture or shader, the falloff is drawn as nested discs whose
+/// opacities compound to it, so frames drawn on the CPU show it too.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Falloff {
+    #[serde(default = "default_sharpness")]
+    pub sharpness: f64,
+}
+
+fn default_sharpness() -> f64 {
+    2.0
+}
+
+impl Default for Falloff {
+    fn default() -> Falloff {
+        Falloff { sharpness: default_sharpness() }
+    }
+}
+
+impl Tunable for Falloff {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("sharpness", &mut self.sharpness, 0.1..=16.0);
+    }
+}
+
+/// Fraction of a particle's opacity each disc is to show, computed once per
+/// frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Profile {
+    // From the outermost disc in, each the falloff at the middle of the ring
+    // it alone covers
+    levels: [f32; LAYERS],
+}
+
+impl Falloff {
+    pub fn profile(&self) -> Profile {
+        let levels = std::array::from_fn(|k| {
+            let r = (LAYERS - k) as f64 / LAYERS as f64 - 0.5 / LAYERS as f64;
+            (1.0 - r * r).powf(self.sharpness) as f32
+        });
+        Profile { levels }
+    }
+}
+
+impl Profile {
+    /// Discs to draw for a particle of `color`, outermost first: each one's
+    /// diameter as a fraction of the particle's size, and the color to draw
+    /// it with so what was drawn inside it ends up showing its level.
+    pub fn layers(&self, color: [f32; 4], mode: BlendMode) -> [(f64, [f32; 4]); LAYERS] {
+        let mut previous = 0.0;
+        std::array::from_fn(|k| {
+            let level = self.levels[k];
+            let diameter = (LAYERS - k) as f64 / LAYERS as f64;
+            let layer = match mode {
+                // Coverage compounds as 1 - (1 - a)(1 - b)
+                BlendMode::Alpha => {
+                    let (covered, target) = (color[3] * previous, color[3] * level);
+                    let alpha = if covered < 1.0 { 1.0 - (1.0 - target) / (1.0 - covered) } else { 0.0 };
+                    [color[0], color[1], color[2], alpha]
+                }
+                // Light adds up
+                BlendMode::Additive => [color[0], color[1], color[2], color[3] * (level - previous)],
+                // Each channel is darkened towards its share of the color
+                BlendMode::Multiply => color.map(|channel| {
+                    let tint = |level: f32| 1.0 - level * (1.0 - channel);
+                    let before = tint(previous);
+                    if before > 0.0 { tint(level) / before } else { channel }
+                }),
+            };
+            previous = level;
+            (diameter, layer)
+        })
+    }
+}
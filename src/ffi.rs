@@ -0,0 +1,161 @@
+//! C interface to the simulation and the allocator counters, for hosts that
+//! aren't written in Rust, like a notebook loading the library with ctypes.
+//! Build it as a shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+//!
+//! Worlds are opaque pointers owned by the caller, made by
+//! `mp_world_create` and freed by `mp_world_destroy`. Nothing here is
+//! thread-safe: a world is used from one thread at a time.
+
+use crate::allocator::{memory_stats, set_capturing};
+use crate::config::Config;
+use crate::{World, INITIAL_PARTICLES};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::ffi::{c_char, CStr};
+use std::path::Path;
+
+/// A particle as copied out by `mp_world_read_particles`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MpParticle {
+    pub x: f64,
+    pub y: f64,
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub color: [f32; 4],
+}
+
+/// The counters of `allocator::memory_stats`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MpMemoryStats {
+    pub live_bytes: u64,
+    pub peak_live_bytes: u64,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+    pub deallocations: u64,
+    pub freed_bytes: u64,
+    pub vec_reallocs: u64,
+}
+
+/// Makes a world of `width` by `height` pixels from the scene file at
+/// `config_path`, or the default scene when it is null, with its initial
+/// particles spawned from `seed`. Returns null when the scene can't be read.
+/// Allocations are no longer reported on stderr from then on.
+///
+/// # Safety
+///
+/// `config_path` is null or points to a NUL-terminated UTF-8 path.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_create(width: f64, height: f64, seed: u64, config_path: *const c_char) -> *mut World {
+    set_capturing(false);
+    let config = if config_path.is_null() {
+        Config::default()
+    } else {
+        let Ok(path) = CStr::from_ptr(config_path).to_str() else {
+            return std::ptr::null_mut();
+        };
+        match Config::load(Path::new(path)) {
+            Ok(config) => config,
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+    let initial_particles = config.initial_particles.unwrap_or(INITIAL_PARTICLES);
+    let mut world = World::from_config(width, height, config);
+    world.rng = StdRng::seed_from_u64(seed);
+    world.add_shapes(initial_particles);
+    Box::into_raw(Box::new(world))
+}
+
+/// Frees a world made by `mp_world_create`.
+///
+/// # Safety
+///
+/// `world` is null or a world of `mp_world_create` not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_destroy(world: *mut World) {
+    if !world.is_null() {
+        drop(Box::from_raw(world));
+    }
+}
+
+/// Runs `turns` turns of the world.
+///
+/// # Safety
+///
+/// `world` is a live world of `mp_world_create`.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_step(world: *mut World, turns: u64) {
+    let world = &mut *world;
+    for _ in 0..turns {
+        world.update();
+    }
+}
+
+/// Turns the world has run.
+///
+/// # Safety
+///
+/// `world` is a live world of `mp_world_create`.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_turn(world: *const World) -> u64 {
+    (*world).current_turn
+}
+
+/// Particles in the world, what `mp_world_read_particles` needs room for.
+///
+/// # Safety
+///
+/// `world` is a live world of `mp_world_create`.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_particle_count(world: *const World) -> usize {
+    (*world).particles.len()
+}
+
+/// Copies up to `capacity` particles of the world into `out`, in the
+/// order they are drawn, and returns how many were copied.
+///
+/// # Safety
+///
+/// `world` is a live world of `mp_world_create` and `out` points to room
+/// for `capacity` particles.
+#[no_mangle]
+pub unsafe extern "C" fn mp_world_read_particles(world: *const World, out: *mut MpParticle, capacity: usize) -> usize {
+    let particles = &(*world).particles;
+    let count = particles.len().min(capacity);
+    let out = std::slice::from_raw_parts_mut(out, count);
+    for (slot, particle) in out.iter_mut().zip(particles.iter()) {
+        *slot = MpParticle {
+            x: particle.position[0],
+            y: particle.position[1],
+            velocity_x: particle.velocity[0],
+            velocity_y: particle.velocity[1],
+            width: particle.width,
+            height: particle.height,
+            color: particle.color,
+        };
+    }
+    count
+}
+
+/// Fills `out` with the allocator counters of the whole process.
+///
+/// # Safety
+///
+/// `out` points to an `MpMemoryStats`.
+#[no_mangle]
+pub unsafe extern "C" fn mp_memory_stats(out: *mut MpMemoryStats) {
+    let stats = memory_stats();
+    *out = MpMemoryStats {
+        live_bytes: stats.live_bytes as u64,
+        peak_live_bytes: stats.peak_live_bytes as u64,
+        allocations: stats.allocations,
+        allocated_bytes: stats.allocated_bytes,
+        deallocations: stats.deallocations,
+        freed_bytes: stats.freed_bytes,
+        vec_reallocs: stats.vec_reallocs,
+    };
+}
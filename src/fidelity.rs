@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+// Fidelities stepped through, from the most detailed down
+const LEVELS: [Fidelity; 4] = [Fidelity::Full, Fidelity::Sampled(8), Fidelity::Sampled(64), Fidelity::Aggregates];
+
+// Frames go down a level once their mean time takes this much of the budget,
+// and back up once this many frames in a row took less than half of it
+const BUSY_SHARE: f64 = 0.85;
+const IDLE_SHARE: f64 = 0.5;
+const IDLE_FRAMES: u32 = 120;
+
+// Frames at a level before going further down, for the mean to catch up
+// with what the level before it saved
+const SETTLE_FRAMES: u32 = 10;
+
+/// How much of the allocations the trace gets one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fidelity {
+    /// Every allocation and free, with `--alloc-verbose` or `--trace-output`
+    Full,
+
+    /// One allocation or free in this many, the others counted in the
+    /// summary of their frame
+    Sampled(u32),
+
+    /// None of them, only the summary of each frame
+    Aggregates,
+}
+
+impl Fidelity {
+    /// Of the events traced, one in this many, 0 for none.
+    pub fn trace_every(self) -> u32 {
+        match self {
+            Fidelity::Full => 1,
+            Fidelity::Sampled(every) => every.max(1),
+            Fidelity::Aggregates => 0,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Fidelity::Full => "full".to_string(),
+            Fidelity::Sampled(every) => format!("1 in {} events", every),
+            Fidelity::Aggregates => "aggregates only".to_string(),
+        }
+    }
+}
+
+/// Lowers the fidelity of the trace while frames take close to their
+/// budget, so tracing the allocations isn't what makes them late, and
+/// raises it again once they have room to spare.
+#[derive(Debug, Clone)]
+pub struct TelemetryGovernor {
+    budget: Duration,
+    level: usize,
+
+    // Mean frame time, in seconds, weighted towards the last frames
+    mean: f64,
+    frames_at_level: u32,
+    idle_frames: u32,
+}
+
+impl TelemetryGovernor {
+    pub fn new(budget: Duration) -> TelemetryGovernor {
+        TelemetryGovernor { budget, level: 0, mean: 0.0, frames_at_level: 0, idle_frames: 0 }
+    }
+
+    pub fn fidelity(&self) -> Fidelity {
+        LEVELS[self.level]
+    }
+
+    /// Counts a frame that took `time`, and returns the fidelity to trace
+    /// with from now on when it changes.
+    pub fn frame(&mut self, time: Duration) -> Option<Fidelity> {
+        let (time, budget) = (time.as_secs_f64(), self.budget.as_secs_f64());
+        self.mean = 0.8 * self.mean + 0.2 * time;
+        self.frames_at_level = self.frames_at_level.saturating_add(1);
+        self.idle_frames = if time < IDLE_SHARE * budget { self.idle_frames + 1 } else { 0 };
+
+        let level = if self.mean > BUSY_SHARE * budget && self.frames_at_level >= SETTLE_FRAMES {
+            (self.level + 1).min(LEVELS.len() - 1)
+        } else if self.idle_frames >= IDLE_FRAMES {
+            self.level.saturating_sub(1)
+        } else {
+            self.level
+        };
+        if level == self.level {
+            return None;
+        }
+        self.level = level;
+        self.frames_at_level = 0;
+        self.idle_frames = 0;
+        Some(self.fidelity())
+    }
+}
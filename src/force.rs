@@ -0,0 +1,185 @@
+use crate::noise;
+use crate::params::{ParamVisitor, Tunable};
+use graphics::math::{add, Vec2d};
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// A force acting on every particle of the world, wherever it is, unlike
+/// the forces of zones. Accelerations are in pixels per turn per turn at
+/// `BASE_TURN_RATE`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Force {
+    /// Same pull everywhere and all the time
+    Gravity { acceleration: Vec2d<f64> },
+
+    /// A push in a constant direction whose strength swings by `gust`, a
+    /// fraction of it, over `period` turns
+    Wind {
+        acceleration: Vec2d<f64>,
+        #[serde(default = "default_gust")]
+        gust: f64,
+        #[serde(default = "default_period")]
+        period: u64,
+    },
+
+    /// Pulls towards `center` with `strength` up to `radius` pixels from it,
+    /// and with the inverse square of the distance beyond; pushes away when
+    /// `strength` is negative, as a repeller
+    Attractor {
+        center: Vec2d<f64>,
+        strength: f64,
+        #[serde(default = "default_radius")]
+        radius: f64,
+    },
+
+    /// Pushes of up to `amplitude` along a smooth field of gradient noise,
+    /// with eddies about `1 / frequency` pixels wide that drift by `speed`
+    /// of one every turn, and finer ones over them for every octave past the
+    /// first. The field of a `seed` is the same in every run
+    Noise {
+        amplitude: f64,
+        #[serde(default = "default_frequency")]
+        frequency: f64,
+        #[serde(default = "default_drift")]
+        speed: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u32,
+    },
+}
+
+fn default_gust() -> f64 {
+    0.5
+}
+
+fn default_period() -> u64 {
+    600
+}
+
+fn default_radius() -> f64 {
+    100.0
+}
+
+fn default_frequency() -> f64 {
+    0.005
+}
+
+fn default_drift() -> f64 {
+    0.005
+}
+
+fn default_octaves() -> u32 {
+    2
+}
+
+impl Force {
+    /// Name of the force as written in scene files.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Force::Gravity { .. } => "gravity",
+            Force::Wind { .. } => "wind",
+            Force::Attractor { .. } => "attractor",
+            Force::Noise { .. } => "noise",
+        }
+    }
+
+    /// The force of a kind added from the window when the scene has none,
+    /// at `position` for those with one.
+    pub fn default_of(kind: &str, position: Vec2d<f64>) -> Option<Force> {
+        match kind {
+            "gravity" => Some(Force::Gravity { acceleration: [0.0, 0.05] }),
+            "wind" => Some(Force::Wind { acceleration: [0.03, 0.0], gust: default_gust(), period: default_period() }),
+            "attractor" => Some(Force::Attractor { center: position, strength: 0.1, radius: default_radius() }),
+            "noise" => Some(Force::Noise {
+                amplitude: 0.05,
+                frequency: default_frequency(),
+                speed: default_drift(),
+                octaves: default_octaves(),
+                seed: 0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Acceleration of a particle at `position` on `turn`.
+    pub fn acceleration(&self, position: Vec2d<f64>, turn: u64) -> Vec2d<f64> {
+        match *self {
+            Force::Gravity { acceleration } => acceleration,
+            Force::Wind { acceleration, gust, period } => {
+                let phase = (turn % period.max(1)) as f64 / period.max(1) as f64;
+                let scale = 1.0 + gust * (TAU * phase).sin();
+                [acceleration[0] * scale, acceleration[1] * scale]
+            }
+            Force::Attractor { center, strength, radius } => {
+                // Every particle feels it, those beyond `radius` by the tail,
+                // so finding those within through the quadtree of the world
+                // would save none of these sums
+                let offset = [center[0] - position[0], center[1] - position[1]];
+                let distance = offset[0].hypot(offset[1]);
+                if distance == 0.0 {
+                    return [0.0, 0.0];
+                }
+                let falloff = if distance > radius { (radius / distance).powi(2) } else { 1.0 };
+                let pull = strength * falloff / distance;
+                [offset[0] * pull, offset[1] * pull]
+            }
+            Force::Noise { amplitude, frequency, speed, octaves, seed } => {
+                let point = [position[0] * frequency, position[1] * frequency, turn as f64 * speed];
+                noise::vector(point, octaves, seed).map(|value| value * amplitude)
+            }
+        }
+    }
+}
+
+impl Tunable for Force {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        match self {
+            Force::Gravity { acceleration } => {
+                visitor.float("x", &mut acceleration[0], -10.0..=10.0);
+                visitor.float("y", &mut acceleration[1], -10.0..=10.0);
+            }
+            Force::Wind { acceleration, gust, period } => {
+                visitor.float("x", &mut acceleration[0], -10.0..=10.0);
+                visitor.float("y", &mut acceleration[1], -10.0..=10.0);
+                visitor.float("gust", gust, 0.0..=10.0);
+                visitor.integer("period", period, 1..=1_000_000);
+            }
+            Force::Attractor { strength, radius, .. } => {
+                visitor.float("strength", strength, -10.0..=10.0);
+                visitor.float("radius", radius, 1.0..=f64::INFINITY);
+            }
+            Force::Noise { amplitude, frequency, speed, .. } => {
+                visitor.float("amplitude", amplitude, 0.0..=10.0);
+                visitor.float("frequency", frequency, 0.0001..=1.0);
+                visitor.float("speed", speed, 0.0..=1.0);
+            }
+        }
+    }
+}
+
+/// The forces of the world on one turn, leaving out the kinds switched off,
+/// and the pull of the mouse cursor, which is never switched off.
+#[derive(Clone, Copy)]
+pub struct ForceField<'a> {
+    pub forces: &'a [Force],
+    pub disabled: &'a [&'static str],
+    pub cursor: Option<&'a Force>,
+    pub turn: u64,
+}
+
+impl ForceField<'_> {
+    /// No forces at all.
+    pub const NONE: ForceField<'static> = ForceField { forces: &[], disabled: &[], cursor: None, turn: 0 };
+
+    pub fn active(&self) -> impl Iterator<Item = (usize, &Force)> {
+        self.forces.iter().enumerate().filter(|(_, force)| !self.disabled.contains(&force.kind()))
+    }
+
+    /// The acceleration of every active force and the cursor's at `position` together.
+    pub fn acceleration(&self, position: Vec2d<f64>) -> Vec2d<f64> {
+        let forces = self.active().map(|(_, force)| force).chain(self.cursor);
+        forces.fold([0.0, 0.0], |sum, force| add(sum, force.acceleration(position, self.turn)))
+    }
+}
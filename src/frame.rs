@@ -0,0 +1,197 @@
+use piston_window::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Part of the event loop a frame spends its time in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Inside `window.next()`: polling events and swapping buffers in Piston
+    Wait,
+
+    /// From receiving an event to taking the latest frame, sending the
+    /// simulation the commands of the event
+    Dispatch,
+
+    /// Taking the latest frame the simulation thread handed over
+    Update,
+
+    /// Our own drawing code, inside the `draw_2d` closure
+    Draw,
+
+    /// The rest of `draw_2d`, setting up and flushing the Piston renderer
+    DrawOverhead,
+}
+
+pub const PHASES: [Phase; 5] = [Phase::Wait, Phase::Dispatch, Phase::Update, Phase::Draw, Phase::DrawOverhead];
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Wait => "event wait",
+            Phase::Dispatch => "dispatch",
+            Phase::Update => "update",
+            Phase::Draw => "draw",
+            Phase::DrawOverhead => "draw overhead",
+        }
+    }
+
+    /// Whether the time is spent in Piston rather than in this crate.
+    pub fn is_engine(&self) -> bool {
+        matches!(self, Phase::Wait | Phase::DrawOverhead)
+    }
+}
+
+/// Time a frame spent in each phase.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimes([Duration; PHASES.len()]);
+
+impl PhaseTimes {
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.0[phase as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.0.iter().sum()
+    }
+}
+
+/// Splits the time of every frame between the phases of the event loop, so
+/// the overhead of Piston can be told apart from the cost of this crate.
+///
+/// The loop handles several events per frame; times add up until the render
+/// event that ends the frame.
+#[derive(Debug, Default)]
+pub struct FrameTimer {
+    current: PhaseTimes,
+    totals: PhaseTimes,
+    slowest: PhaseTimes,
+    frames: u64,
+}
+
+impl FrameTimer {
+    pub fn new() -> FrameTimer {
+        FrameTimer::default()
+    }
+
+    pub fn add(&mut self, phase: Phase, elapsed: Duration) {
+        self.current.0[phase as usize] += elapsed;
+    }
+
+    /// Ends the current frame, returning the time it spent in each phase.
+    pub fn end_frame(&mut self) -> PhaseTimes {
+        let frame = std::mem::take(&mut self.current);
+        for phase in PHASES {
+            let i = phase as usize;
+            self.totals.0[i] += frame.0[i];
+            self.slowest.0[i] = self.slowest.0[i].max(frame.0[i]);
+        }
+        self.frames += 1;
+        frame
+    }
+
+    /// Prints the mean and worst time per frame of every phase, and how the
+    /// time splits between Piston and this crate.
+    pub fn print_summary(&self) {
+        let frames = self.frames.max(1) as f64;
+        let total = self.totals.total().as_secs_f64().max(f64::EPSILON);
+        println!("{:<16}{:>12}{:>12}{:>8}", "phase", "mean (us)", "max (us)", "share");
+        for phase in PHASES {
+            let time = self.totals.get(phase).as_secs_f64();
+            println!(
+                "{:<16}{:>12.1}{:>12.1}{:>7.1}%",
+                phase.name(),
+                time / frames * 1e6,
+                self.slowest.get(phase).as_secs_f64() * 1e6,
+                time / total * 100.0
+            );
+        }
+
+        let engine: f64 = PHASES.iter().filter(|p| p.is_engine()).map(|&p| self.totals.get(p).as_secs_f64()).sum();
+        println!(
+            "{} frames, {:.1}% of the time in Piston, {:.1}% in the simulation and rendering",
+            self.frames,
+            engine / total * 100.0,
+            100.0 - engine / total * 100.0
+        );
+    }
+}
+
+/// A frame that took more than twice the target frame time, with what the
+/// world was doing at the time.
+#[derive(Debug, Clone)]
+pub struct FrameDrop {
+    pub frame: u64,
+    pub turn: u64,
+    pub time: Duration,
+    pub particles: usize,
+
+    /// Bytes allocated during the frame
+    pub allocated_bytes: u64,
+    pub phases: PhaseTimes,
+}
+
+impl FrameDrop {
+    /// Whether a frame of `time` counts as dropped against `target`.
+    pub fn is_drop(time: Duration, target: Duration) -> bool {
+        time > target * 2
+    }
+
+    /// One line describing the drop, for the log.
+    pub fn describe(&self, target: Duration) -> String {
+        let phases: Vec<String> = PHASES
+            .iter()
+            .map(|&phase| format!("{} {:.1}", phase.name(), self.phases.get(phase).as_secs_f64() * 1e3))
+            .collect();
+        format!(
+            "frame drop: frame {} (turn {}) took {:.1} ms, {:.1}x the target; {} particles, {} bytes allocated; ms per phase: {}",
+            self.frame,
+            self.turn,
+            self.time.as_secs_f64() * 1e3,
+            self.time.as_secs_f64() / target.as_secs_f64(),
+            self.particles,
+            self.allocated_bytes,
+            phases.join(", ")
+        )
+    }
+}
+
+// Frames shown by the chart, one pixel column each
+const CHART_FRAMES: usize = 240;
+const CHART_HEIGHT: f64 = 60.0;
+
+/// Times of the most recent frames, drawn as bars against the target time
+/// with dropped frames in red.
+#[derive(Debug, Default)]
+pub struct FrameChart {
+    frames: VecDeque<(Duration, bool)>,
+}
+
+impl FrameChart {
+    pub fn new() -> FrameChart {
+        FrameChart { frames: VecDeque::with_capacity(CHART_FRAMES) }
+    }
+
+    pub fn push(&mut self, time: Duration, dropped: bool) {
+        if self.frames.len() == CHART_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((time, dropped));
+    }
+
+    /// Draws the chart in the top right corner. Bars are scaled so three
+    /// times the target fills the chart, and the line marks the drop threshold.
+    pub fn draw<G: Graphics>(&self, target: Duration, width: f64, ctx: Context, g: &mut G) {
+        let (left, top) = (width - CHART_FRAMES as f64 - 10.0, 16.0);
+        rectangle([0.0, 0.0, 0.0, 0.6], [left, top, CHART_FRAMES as f64, CHART_HEIGHT], ctx.transform, g);
+
+        let scale = CHART_HEIGHT / (target.as_secs_f64() * 3.0);
+        for (i, (time, dropped)) in self.frames.iter().enumerate() {
+            let bar = (time.as_secs_f64() * scale).min(CHART_HEIGHT);
+            let color = if *dropped { [1.0, 0.25, 0.2, 0.9] } else { [0.4, 0.8, 0.5, 0.7] };
+            rectangle(color, [left + i as f64, top + CHART_HEIGHT - bar, 1.0, bar], ctx.transform, g);
+        }
+
+        let threshold = top + CHART_HEIGHT - target.as_secs_f64() * 2.0 * scale;
+        rectangle([1.0, 1.0, 1.0, 0.4], [left, threshold, CHART_FRAMES as f64, 1.0], ctx.transform, g);
+    }
+}
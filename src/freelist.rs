@@ -0,0 +1,118 @@
+use crate::config::ParticleSettings;
+use crate::Particle;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+
+/// Where the boxes of the particles come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AllocationStrategy {
+    /// A new box from the allocator for every spawn, freed when it dies
+    #[default]
+    Boxed,
+
+    /// Boxes of dead particles recycled for the next spawns, from a
+    /// `ParticlePool` filled up front
+    Pooled,
+
+    /// Blocks bumped out of the chunks of the allocator's arena, which only
+    /// take memory back once all of their blocks are dead
+    Arena,
+}
+
+impl AllocationStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            AllocationStrategy::Boxed => "boxed",
+            AllocationStrategy::Pooled => "pooled",
+            AllocationStrategy::Arena => "arena",
+        }
+    }
+
+    /// The strategy the storage key switches to from this one.
+    pub fn next(self) -> AllocationStrategy {
+        match self {
+            AllocationStrategy::Boxed => AllocationStrategy::Pooled,
+            AllocationStrategy::Pooled => AllocationStrategy::Arena,
+            AllocationStrategy::Arena => AllocationStrategy::Boxed,
+        }
+    }
+}
+
+/// Boxes of dead particles kept for the next ones, so a population that
+/// comes and goes stops allocating altogether: spawning overwrites a box
+/// taken from the pool, and dying puts it back. Unlike the block allocator
+/// of `--block-allocator`, the allocator never hears of them again, so
+/// neither do the allocation traces.
+///
+/// The pool is filled with `capacity` boxes up front and never holds more;
+/// boxes dying while it is full are freed, and spawns finding it empty
+/// allocate.
+#[derive(Clone)]
+pub struct ParticlePool {
+    #[allow(clippy::vec_box)]
+    free: Vec<Box<Particle>>,
+    capacity: usize,
+    stats: ParticlePoolStats,
+}
+
+/// What a `ParticlePool` did so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParticlePoolStats {
+    pub free: usize,
+    pub capacity: usize,
+
+    /// Spawns served from the pool, and those that found it empty
+    pub recycled: u64,
+    pub allocated: u64,
+}
+
+impl ParticlePool {
+    pub fn with_capacity(capacity: usize) -> ParticlePool {
+        // What the boxes hold until their first particle doesn't matter, so
+        // it is drawn from a generator of its own rather than the world's
+        let filler = Particle::new([0.0, 0.0], [0.0, -1.0], [0.0; 4], &ParticleSettings::default(), &mut StdRng::seed_from_u64(0));
+        let mut free = Vec::with_capacity(capacity);
+        free.extend((0..capacity).map(|_| Box::new(filler.clone())));
+        ParticlePool { free, capacity, stats: ParticlePoolStats { capacity, ..ParticlePoolStats::default() } }
+    }
+
+    /// A box holding `particle`, recycled when the pool has one.
+    pub fn take(&mut self, particle: Particle) -> Box<Particle> {
+        match self.free.pop() {
+            Some(mut boxed) => {
+                *boxed = particle;
+                self.stats.recycled += 1;
+                boxed
+            }
+            None => {
+                self.stats.allocated += 1;
+                Box::new(particle)
+            }
+        }
+    }
+
+    /// Keeps the boxes of dead particles for the next spawns, as many as
+    /// there is room for.
+    pub fn recycle<I: IntoIterator<Item = Box<Particle>>>(&mut self, dead: I) {
+        for boxed in dead {
+            if self.free.len() < self.capacity {
+                self.free.push(boxed);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> ParticlePoolStats {
+        ParticlePoolStats { free: self.free.len(), ..self.stats }
+    }
+}
+
+impl ParticlePoolStats {
+    pub fn describe(&self) -> String {
+        format!(
+            "particle pool: {} of {} free, {} spawns recycled, {} allocated",
+            self.free, self.capacity, self.recycled, self.allocated
+        )
+    }
+}
@@ -0,0 +1,44 @@
+// Most particles spawned in a single turn, so a burst of allocations,
+// like a large snapshot, doesn't flood the world
+const MAX_SPAWNS_PER_TURN: u64 = 200;
+
+/// Turns the allocation rate of the whole process into a spawn rate: one
+/// particle for every `bytes_per_particle` bytes allocated between turns, by
+/// any thread. The particle density then gauges the memory churn of the
+/// application, Piston and telemetry included.
+///
+/// What the world allocates during its own update is left out: spawning and
+/// resizing the particle list would otherwise feed the gauge and run away.
+#[derive(Debug, Clone)]
+pub struct AllocationGauge {
+    bytes_per_particle: u64,
+    last_allocated_bytes: u64,
+
+    // Bytes allocated that didn't add up to a whole particle yet
+    carry: u64,
+}
+
+impl AllocationGauge {
+    /// Starts measuring from the allocator's current total of allocated bytes.
+    pub fn new(bytes_per_particle: u64, allocated_bytes: u64) -> AllocationGauge {
+        AllocationGauge {
+            bytes_per_particle: bytes_per_particle.max(1),
+            last_allocated_bytes: allocated_bytes,
+            carry: 0,
+        }
+    }
+
+    /// Number of particles to spawn for the bytes allocated since the end of
+    /// the last update.
+    pub fn spawns(&mut self, allocated_bytes: u64) -> u64 {
+        let bytes = self.carry + allocated_bytes.saturating_sub(self.last_allocated_bytes);
+        self.last_allocated_bytes = allocated_bytes;
+        self.carry = bytes % self.bytes_per_particle;
+        (bytes / self.bytes_per_particle).min(MAX_SPAWNS_PER_TURN)
+    }
+
+    /// Skips what was allocated since `spawns`, at the end of the update.
+    pub fn skip_to(&mut self, allocated_bytes: u64) {
+        self.last_allocated_bytes = allocated_bytes;
+    }
+}
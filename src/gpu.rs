@@ -0,0 +1,520 @@
+use crate::clock::{FrameLimiter, TurnClock};
+use memory_particles::allocator::{report_frame, set_thread_role, ThreadRole};
+use memory_particles::blend::{BlendMode, DRAW_ORDER};
+use memory_particles::handoff::{handoff, SnapshotReader, SnapshotWriter};
+use memory_particles::shape::{Shape, TRAIL_TURNS};
+use memory_particles::shutdown;
+use memory_particles::summary::RunSummary;
+use memory_particles::World;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+use winit::window::{Fullscreen, Window, WindowId};
+
+// Instances the buffer is first made for, doubled whenever the particles
+// outgrow it
+const INITIAL_INSTANCES: usize = 4096;
+
+// How often the title shows the frame rate
+const TITLE_EVERY: Duration = Duration::from_secs(1);
+
+/// How `--frontend wgpu` opens its window.
+pub struct GpuOptions {
+    pub turn_rate: f64,
+    pub vsync: bool,
+    pub fullscreen: bool,
+    pub max_fps: u64,
+}
+
+// A particle as the shader takes it
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Instance {
+    rect: [f32; 4],
+    color: [f32; 4],
+    velocity: [f32; 2],
+    shape: u32,
+    _padding: u32,
+}
+
+impl Instance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x2, 3 => Uint32];
+}
+
+// The size of the window and the length of trails, as the shader takes them
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Viewport {
+    size: [f32; 2],
+    trail_turns: f32,
+    _padding: f32,
+}
+
+fn shape_index(shape: Shape) -> u32 {
+    match shape {
+        Shape::Square => 0,
+        Shape::Circle => 1,
+        Shape::Triangle => 2,
+        Shape::Trail => 3,
+    }
+}
+
+// The blend of the Piston window for each mode: over by opacity, added by
+// opacity, or multiplied
+fn blend_state(mode: BlendMode) -> wgpu::BlendState {
+    let color = match mode {
+        BlendMode::Alpha => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        BlendMode::Additive => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        BlendMode::Multiply => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Dst,
+            dst_factor: wgpu::BlendFactor::Zero,
+            operation: wgpu::BlendOperation::Add,
+        },
+    };
+    wgpu::BlendState { color, alpha: wgpu::BlendComponent::OVER }
+}
+
+/// Runs `world` in a window drawn with wgpu, until it is closed or Escape is
+/// pressed. Space pauses. Only the background and the particles are drawn,
+/// every particle of a blend mode in a single instanced draw, so hundreds
+/// of thousands of them stay smooth; the overlays, scene and camera of the
+/// Piston window aren't.
+///
+/// The turns run on a thread of their own at the turn rate, which hands
+/// the window a snapshot of the world after them through a triple buffer:
+/// frames draw the latest whole turn, never one half updated, and neither
+/// waits for the other, so the display and the simulation keep rates of
+/// their own.
+pub fn run(world: World, options: GpuOptions) -> Result<(), String> {
+    let event_loop = EventLoop::new().map_err(|err| err.to_string())?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+    let size = PhysicalSize::new(world.width, world.height);
+    let (writer, reader) = handoff();
+    let (commands, received) = mpsc::channel();
+    let simulation = spawn_simulation(world, options.turn_rate, writer, received)?;
+    let mut app = App {
+        limiter: FrameLimiter::new(options.max_fps),
+        size,
+        options,
+        snapshots: reader,
+        commands,
+        renderer: None,
+        error: None,
+        instances: Vec::with_capacity(INITIAL_INSTANCES),
+        summary: RunSummary::new(),
+        started: Instant::now(),
+        title: (Instant::now(), 0),
+    };
+    let ran = event_loop.run_app(&mut app).map_err(|err| err.to_string());
+    let _ = app.commands.send(Command::Stop);
+    let world = simulation.join().map_err(|_| "the simulation thread panicked".to_string())?;
+    ran?;
+    if let Some(err) = app.error {
+        return Err(err);
+    }
+    let seconds = app.started.elapsed().as_secs_f64();
+    app.summary.print(world.current_turn);
+    println!("{:<20}{:.1}", "frames per second", app.summary.frames() as f64 / seconds.max(f64::EPSILON));
+    println!("{:<20}{}", "particles", world.particles.len());
+    Ok(())
+}
+
+// What the window tells the thread running the simulation
+enum Command {
+    Pause,
+    Resize(f64, f64),
+    Stop,
+}
+
+// Runs the turns of `world` due at `turn_rate`, publishing it to `writer`
+// after them, until told to stop or the window is gone, and gives the world
+// back
+fn spawn_simulation(
+    mut world: World,
+    turn_rate: f64,
+    mut writer: SnapshotWriter,
+    commands: Receiver<Command>,
+) -> Result<JoinHandle<World>, String> {
+    let spawned = thread::Builder::new().name("simulation".to_string()).spawn(move || {
+        set_thread_role(ThreadRole::Simulation);
+        let mut clock = TurnClock::new(turn_rate);
+        let turn = Duration::from_secs_f64(1.0 / turn_rate);
+        let mut paused = false;
+        writer.publish(&world);
+        loop {
+            clock.tick(Instant::now(), !paused);
+            let mut ran = false;
+            while clock.next_turn() {
+                world.update();
+                ran = true;
+            }
+            if ran {
+                writer.publish(&world);
+            }
+
+            // Waits out the next turn, unless the window has something to say
+            match commands.recv_timeout(turn) {
+                Ok(Command::Pause) => paused = !paused,
+                Ok(Command::Resize(width, height)) => {
+                    world.resize(width, height);
+                    writer.publish(&world);
+                }
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => return world,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+        }
+    });
+    spawned.map_err(|err| format!("Could not start the simulation thread: {}", err))
+}
+
+struct App {
+    // Of the window, from the world until it is open
+    size: PhysicalSize<f64>,
+    options: GpuOptions,
+    limiter: FrameLimiter,
+    snapshots: SnapshotReader,
+    commands: Sender<Command>,
+    renderer: Option<Renderer>,
+    error: Option<String>,
+
+    // Filled again every frame, in `DRAW_ORDER`
+    instances: Vec<Instance>,
+    summary: RunSummary,
+    started: Instant,
+
+    // When the title last changed, and the frames drawn by then
+    title: (Instant, u64),
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.renderer.is_some() {
+            return;
+        }
+        let mut attributes = Window::default_attributes()
+            .with_title("particles")
+            .with_inner_size(self.size);
+        if self.options.fullscreen {
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        let renderer = event_loop
+            .create_window(attributes)
+            .map_err(|err| err.to_string())
+            .and_then(|window| Renderer::new(event_loop, Arc::new(window), self.options.vsync));
+        match renderer {
+            Ok(renderer) => {
+                println!("wgpu window on {}", renderer.adapter);
+                self.renderer = Some(renderer);
+            }
+            Err(err) => {
+                self.error = Some(err);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _window: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.resize(size);
+                }
+                let _ = self.commands.send(Command::Resize(size.width as f64, size.height as f64));
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed && !event.repeat => {
+                match event.logical_key {
+                    Key::Named(NamedKey::Escape) => event_loop.exit(),
+                    Key::Named(NamedKey::Space) => {
+                        let _ = self.commands.send(Command::Pause);
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::RedrawRequested => self.frame(),
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if shutdown::requested() {
+            event_loop.exit();
+            return;
+        }
+        if let Some(renderer) = &self.renderer {
+            renderer.window.request_redraw();
+        }
+    }
+}
+
+impl App {
+    // Draws the latest turn of the simulation and waits out the frame
+    fn frame(&mut self) {
+        let start = Instant::now();
+        let snapshot = self.snapshots.latest();
+        self.instances.clear();
+        let ranges = DRAW_ORDER.map(|mode| {
+            let start = self.instances.len() as u32;
+            self.instances.extend(snapshot.sprites.iter().filter(|s| s.blend == mode).map(|s| Instance {
+                rect: [s.position[0] as f32, s.position[1] as f32, s.size[0] as f32, s.size[1] as f32],
+                color: s.color,
+                velocity: [s.velocity[0] as f32, s.velocity[1] as f32],
+                shape: shape_index(s.shape),
+                _padding: 0,
+            }));
+            start..self.instances.len() as u32
+        });
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        renderer.draw(snapshot.background, &self.instances, &ranges);
+        report_frame(self.summary.frames());
+        self.summary.frame(start.elapsed(), snapshot.sprites.len(), snapshot.turn);
+
+        let (since, frames) = self.title;
+        if since.elapsed() >= TITLE_EVERY {
+            let fps = (self.summary.frames() - frames) as f64 / since.elapsed().as_secs_f64();
+            renderer.window.set_title(&format!("particles - {} particles, {:.0} fps", snapshot.sprites.len(), fps));
+            self.title = (Instant::now(), self.summary.frames());
+        }
+        self.limiter.wait();
+    }
+}
+
+// The surface of the window and what draws on it
+struct Renderer {
+    window: Arc<Window>,
+    adapter: String,
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    viewport: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+
+    // One pipeline per blend mode, in `DRAW_ORDER`
+    pipelines: Vec<wgpu::RenderPipeline>,
+    instances: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl Renderer {
+    fn new(event_loop: &ActiveEventLoop, window: Arc<Window>, vsync: bool) -> Result<Renderer, String> {
+        // Backends can be picked with WGPU_BACKEND, like `WGPU_BACKEND=gl`
+        let display = Box::new(event_loop.owned_display_handle());
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_with_display_handle_from_env(display));
+        let surface = instance.create_surface(window.clone()).map_err(|err| err.to_string())?;
+        let adapter = futures_executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .map_err(|err| err.to_string())?;
+        let (device, queue) =
+            futures_executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                label: Some("particles"),
+                required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                ..Default::default()
+            }))
+            .map_err(|err| err.to_string())?;
+        let info = adapter.get_info();
+
+        // Colors are written as they are, like the Piston window and the
+        // CPU canvas do, rather than made linear
+        let size = window.inner_size();
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| !format.is_srgb())
+            .or_else(|| capabilities.formats.first().copied())
+            .ok_or("the surface can't be drawn on by this adapter")?;
+        let present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            desired_maximum_frame_latency: 2,
+            present_mode,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        surface.configure(&device, &config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("particles"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let viewport = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("viewport"),
+            size: std::mem::size_of::<Viewport>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("viewport"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("viewport"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: viewport.as_entire_binding() }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("particles"),
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let pipelines = DRAW_ORDER
+            .into_iter()
+            .map(|mode| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("particles"),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: Some("vertex"),
+                        compilation_options: Default::default(),
+                        buffers: &[wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Instance>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &Instance::ATTRIBUTES,
+                        }],
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: Some("fragment"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(blend_state(mode)),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview_mask: None,
+                    cache: None,
+                })
+            })
+            .collect();
+        let instances = instance_buffer(&device, INITIAL_INSTANCES);
+        Ok(Renderer {
+            window,
+            adapter: format!("{} ({:?})", info.name, info.backend),
+            surface,
+            device,
+            queue,
+            config,
+            viewport,
+            bind_group,
+            pipelines,
+            instances,
+            capacity: INITIAL_INSTANCES,
+        })
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    // Clears to `background` and draws each range of `instances` with the
+    // pipeline of its blend mode, the ranges being in `DRAW_ORDER`
+    fn draw(&mut self, background: [f32; 4], instances: &[Instance], ranges: &[Range<u32>]) {
+        let frame = match self.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(frame) | wgpu::CurrentSurfaceTexture::Suboptimal(frame) => frame,
+            wgpu::CurrentSurfaceTexture::Outdated | wgpu::CurrentSurfaceTexture::Lost => {
+                self.surface.configure(&self.device, &self.config);
+                return;
+            }
+            _ => return,
+        };
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instances = instance_buffer(&self.device, self.capacity);
+        }
+        let viewport = Viewport {
+            size: [self.config.width as f32, self.config.height as f32],
+            trail_turns: TRAIL_TURNS as f32,
+            _padding: 0.0,
+        };
+        self.queue.write_buffer(&self.viewport, 0, bytemuck::bytes_of(&viewport));
+        self.queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("particles") });
+        {
+            let [r, g, b, _] = background.map(f64::from);
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("particles"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.set_vertex_buffer(0, self.instances.slice(..));
+            for (pipeline, range) in self.pipelines.iter().zip(ranges).filter(|(_, range)| !range.is_empty()) {
+                pass.set_pipeline(pipeline);
+                pass.draw(0..6, range.clone());
+            }
+        }
+        self.queue.submit([encoder.finish()]);
+        self.window.pre_present_notify();
+        frame.present();
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("instances"),
+        size: (capacity * std::mem::size_of::<Instance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,90 @@
+// Particles of the wgpu window, one instance each, drawn as the quad of
+// their shape and cut to it in the fragment shader
+
+struct Viewport {
+    size: vec2<f32>,
+    trail_turns: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0) var<uniform> viewport: Viewport;
+
+struct Instance {
+    // x, y, width, height, in pixels from the top left corner
+    @location(0) rect: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) velocity: vec2<f32>,
+    @location(3) shape: u32,
+};
+
+struct Varyings {
+    @builtin(position) position: vec4<f32>,
+    // From -1 to 1 across the quad, y pointing where the particle heads
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) shape: u32,
+};
+
+const SQUARE: u32 = 0u;
+const CIRCLE: u32 = 1u;
+const TRIANGLE: u32 = 2u;
+const TRAIL: u32 = 3u;
+
+const CORNERS = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+);
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32, instance: Instance) -> Varyings {
+    let corner = CORNERS[index];
+    let size = instance.rect.zw;
+    let center = instance.rect.xy + size / 2.0;
+
+    // Up when standing still, as the Piston window draws triangles
+    let speed = length(instance.velocity);
+    var heading = vec2<f32>(0.0, -1.0);
+    if speed > 0.0 {
+        heading = instance.velocity / speed;
+    }
+    let across = vec2<f32>(-heading.y, heading.x);
+
+    var point = center + corner * size / 2.0;
+    switch instance.shape {
+        case TRIANGLE: {
+            point = center + heading * corner.y * max(size.x, size.y) / 2.0 + across * corner.x * min(size.x, size.y) / 2.0;
+        }
+        case TRAIL: {
+            // From the particle back along its velocity, half as thick as it is small
+            let tail = instance.velocity * viewport.trail_turns;
+            point = center - tail * (1.0 - corner.y) / 2.0 + across * corner.x * min(size.x, size.y) / 4.0;
+        }
+        default: {}
+    }
+
+    var out: Varyings;
+    out.position = vec4<f32>(point.x / viewport.size.x * 2.0 - 1.0, 1.0 - point.y / viewport.size.y * 2.0, 0.0, 1.0);
+    out.local = corner;
+    out.color = instance.color;
+    out.shape = instance.shape;
+    return out;
+}
+
+@fragment
+fn fragment(in: Varyings) -> @location(0) vec4<f32> {
+    switch in.shape {
+        case CIRCLE: {
+            if dot(in.local, in.local) > 1.0 {
+                discard;
+            }
+        }
+        case TRIANGLE: {
+            // Tip at the top middle, base along the bottom
+            if abs(in.local.x) > (1.0 - in.local.y) / 2.0 {
+                discard;
+            }
+        }
+        default: {}
+    }
+    return in.color;
+}
@@ -0,0 +1,120 @@
+use crate::Particle;
+use graphics::math::Vec2d;
+
+/// Part of a particle's step after which a value was first found not finite.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultSource {
+    /// The particle was already broken when its step started, e.g. spawned
+    /// with a bad value or thrown by a collision
+    Before,
+
+    /// The force of the zone at this index in the scene
+    Zone { index: usize, force: &'static str },
+
+    /// The force of the world at this index in the scene
+    Force { index: usize, kind: &'static str },
+
+    /// The pull or push of the mouse cursor
+    Cursor,
+    Drag,
+    Jitter,
+
+    /// The behavior state the particle was in, by name
+    State(String),
+
+    /// A behavior added by a program using the library, by name
+    Behavior(&'static str),
+
+    /// Adding the acceleration and velocity up and moving the particle
+    Integrator,
+}
+
+/// A NaN or infinite value found in a particle during its step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub source: FaultSource,
+
+    /// Field holding the value, like `velocity`
+    pub field: &'static str,
+    pub value: Vec2d<f64>,
+}
+
+impl Fault {
+    pub fn describe(&self) -> String {
+        let source = match &self.source {
+            FaultSource::Before => "before its step".to_string(),
+            FaultSource::Zone { index, force } => format!("after zone {} ({})", index, force),
+            FaultSource::Force { index, kind } => format!("after force {} ({})", index, kind),
+            FaultSource::Cursor => "after the cursor's pull".to_string(),
+            FaultSource::Drag => "after drag".to_string(),
+            FaultSource::Jitter => "after jitter".to_string(),
+            FaultSource::State(name) => format!("after state \"{}\"", name),
+            FaultSource::Behavior(name) => format!("after behavior \"{}\"", name),
+            FaultSource::Integrator => "after the integrator".to_string(),
+        };
+        format!("{} became [{}, {}] {}", self.field, self.value[0], self.value[1], source)
+    }
+}
+
+/// Checks the state of a particle, returning the first non-finite field.
+pub fn check(particle: &Particle, source: impl FnOnce() -> FaultSource) -> Option<Fault> {
+    let alpha = particle.color[3] as f64;
+    let fields = [
+        ("position", particle.position),
+        ("velocity", particle.velocity),
+        ("acceleration", particle.acceleration),
+        ("size", [particle.width, particle.height]),
+        ("alpha", [alpha, alpha]),
+    ];
+    fields
+        .into_iter()
+        .find(|(_, value)| !value[0].is_finite() || !value[1].is_finite())
+        .map(|(field, value)| Fault { source: source(), field, value })
+}
+
+/// Particles taken out of the simulation after going non-finite.
+#[derive(Clone, Default)]
+pub struct Quarantine {
+    /// The most recent offenders, with what went wrong, for inspection
+    pub particles: Vec<(u64, Particle, Fault)>,
+    pub total: u64,
+
+    // Lines logged so far, so a force breaking every particle doesn't flood stdout
+    logged: u64,
+}
+
+// Offending particles kept, and faults logged one by one before only
+// counts per turn are
+const KEPT: usize = 64;
+const LOGGED: u64 = 20;
+
+impl Quarantine {
+    pub fn new() -> Quarantine {
+        Quarantine { particles: Vec::new(), total: 0, logged: 0 }
+    }
+
+    /// Takes in the particles that went bad on `turn`, logging them on stdout.
+    pub fn admit(&mut self, turn: u64, faulty: Vec<(Particle, Fault)>) {
+        let count = faulty.len() as u64;
+        for (particle, fault) in faulty {
+            if self.logged < LOGGED {
+                println!(
+                    "turn {}: quarantined a particle at [{:.1}, {:.1}]: {}",
+                    turn,
+                    particle.position[0],
+                    particle.position[1],
+                    fault.describe()
+                );
+                self.logged += 1;
+            } else if self.logged == LOGGED {
+                println!("turn {}: more particles quarantined, only counting them from now on", turn);
+                self.logged += 1;
+            }
+            if self.particles.len() == KEPT {
+                self.particles.remove(0);
+            }
+            self.particles.push((turn, particle, fault));
+        }
+        self.total += count;
+    }
+}
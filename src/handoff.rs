@@ -0,0 +1,147 @@
+use crate::blend::BlendMode;
+use crate::camera::CameraView;
+use crate::force::Force;
+use crate::obstacle::Obstacle;
+use crate::shape::Shape;
+use crate::{Particle, World};
+use graphics::math::Vec2d;
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// What a renderer needs of a particle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub position: Vec2d<f64>,
+    pub size: Vec2d<f64>,
+    pub velocity: Vec2d<f64>,
+    pub color: [f32; 4],
+    pub shape: Shape,
+    pub blend: BlendMode,
+
+    /// Positions of its trail in the `trails` of the snapshot, oldest first,
+    /// after those of the sprites before it
+    pub trail: u32,
+}
+
+impl Sprite {
+    /// The sprite of `particle`, its trail left out.
+    pub fn of(particle: &Particle) -> Sprite {
+        Sprite {
+            position: particle.position,
+            size: [particle.width, particle.height],
+            velocity: particle.velocity,
+            color: particle.color,
+            shape: particle.shape,
+            blend: particle.blend,
+            trail: 0,
+        }
+    }
+}
+
+/// The world as drawn at the end of a turn, handed from the thread running
+/// the simulation to the one rendering it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorldSnapshot {
+    pub turn: u64,
+    pub background: [f32; 4],
+    pub camera: Option<CameraView>,
+    pub sprites: Vec<Sprite>,
+    pub sparks: Vec<Sprite>,
+
+    /// The trails of the sprites one after the other, for scenes with a
+    /// `[trail]`
+    pub trails: Vec<Vec2d<f64>>,
+    pub cursor_obstacle: Option<Obstacle>,
+    pub cursor_force: Option<Force>,
+}
+
+impl WorldSnapshot {
+    /// Copies what is drawn of `world` over the snapshot, in the room of
+    /// its sprites and trails, which only allocates when the particles
+    /// outgrow it.
+    pub fn capture(&mut self, world: &World) {
+        self.turn = world.current_turn;
+        self.background = world.background(world.current_turn);
+        self.camera = world.camera_view(world.current_turn);
+        self.sprites.clear();
+        self.trails.clear();
+        for particle in &world.particles {
+            let mut sprite = Sprite::of(particle);
+            if let Some(trail) = &particle.trail {
+                sprite.trail = trail.len() as u32;
+                self.trails.extend(trail.points());
+            }
+            self.sprites.push(sprite);
+        }
+        self.sparks.clear();
+        self.sparks.extend(world.sparks.iter().map(|spark| Sprite::of(spark)));
+        self.cursor_obstacle.clone_from(&world.cursor_obstacle);
+        self.cursor_force.clone_from(&world.cursor_force);
+    }
+}
+
+// The snapshot between the two ends, and whether the reader has yet to take it
+struct Shared<S> {
+    middle: S,
+    fresh: bool,
+}
+
+/// The end of a triple buffer the simulation writes to: it fills its own
+/// snapshot, then trades it for the one in the middle, so neither end ever
+/// waits for the other to finish with a snapshot. Snapshots are of the world
+/// unless a program hands over one of its own, with more than is drawn.
+pub struct SnapshotWriter<S = WorldSnapshot> {
+    back: S,
+    shared: Arc<Mutex<Shared<S>>>,
+}
+
+/// The end the renderer reads from, always holding the latest snapshot
+/// published, which it keeps to itself until it asks for the next.
+pub struct SnapshotReader<S = WorldSnapshot> {
+    front: S,
+    shared: Arc<Mutex<Shared<S>>>,
+}
+
+/// Both ends of a new triple buffer of snapshots, empty until the first is
+/// published. Its three snapshots are reused from then on, so handing one
+/// over allocates nothing once they have room for the particles.
+pub fn handoff<S: Default>() -> (SnapshotWriter<S>, SnapshotReader<S>) {
+    let shared = Arc::new(Mutex::new(Shared { middle: S::default(), fresh: false }));
+    (
+        SnapshotWriter { back: S::default(), shared: Arc::clone(&shared) },
+        SnapshotReader { front: S::default(), shared },
+    )
+}
+
+impl SnapshotWriter {
+    /// Captures `world` and hands it to the reader, in place of a snapshot
+    /// it hasn't taken yet, if any.
+    pub fn publish(&mut self, world: &World) {
+        self.publish_with(|snapshot| snapshot.capture(world));
+    }
+}
+
+impl<S> SnapshotWriter<S> {
+    /// Fills the writer's own snapshot with `fill`, over what it held two
+    /// snapshots ago, and hands it to the reader like `publish`.
+    pub fn publish_with(&mut self, fill: impl FnOnce(&mut S)) {
+        fill(&mut self.back);
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        mem::swap(&mut self.back, &mut shared.middle);
+        shared.fresh = true;
+    }
+}
+
+impl<S> SnapshotReader<S> {
+    /// Takes the latest snapshot published, or keeps the one the reader has
+    /// when none was since.
+    pub fn latest(&mut self) -> &S {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.fresh {
+            mem::swap(&mut self.front, &mut shared.middle);
+            shared.fresh = false;
+        }
+        drop(shared);
+        &self.front
+    }
+}
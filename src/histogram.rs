@@ -0,0 +1,64 @@
+use crate::allocator::{latency_histogram, size_histogram};
+use crate::locale::bundle;
+use crate::trace::{bucket_label, SIZE_BUCKETS};
+
+/// Percentiles of the allocation times reported at exit.
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 100.0];
+
+/// The allocations made so far by power-of-two size class and time class,
+/// counted by the allocator as they are made, for the table printed when a
+/// run ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationHistogram {
+    pub sizes: [u64; SIZE_BUCKETS],
+
+    /// By time taken in nanoseconds, in the classes of the sizes
+    pub latencies: [u64; SIZE_BUCKETS],
+}
+
+impl AllocationHistogram {
+    /// The counts of the allocator now.
+    pub fn now() -> AllocationHistogram {
+        AllocationHistogram { sizes: size_histogram(), latencies: latency_histogram() }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.sizes.iter().sum()
+    }
+
+    /// Longest time in nanoseconds of the class holding the allocation `p`
+    /// percent of the way through them, fastest first, so at least `p`
+    /// percent of them took no longer. None before any allocation.
+    pub fn latency_percentile(&self, p: f64) -> Option<u64> {
+        let count: u64 = self.latencies.iter().sum();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((p / 100.0 * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        let bucket = self.latencies.iter().position(|&in_bucket| {
+            seen += in_bucket;
+            seen >= rank
+        })?;
+        Some(if bucket == 0 { 0 } else { u64::MAX >> (u64::BITS as usize - bucket) })
+    }
+
+    /// Prints the sizes with their share of the allocations, then the
+    /// percentiles of their times.
+    pub fn print(&self) {
+        let (count, text) = (self.count(), bundle());
+        println!("{:<24}{:>12}{:>10}", text.allocation_size, text.count, text.share);
+        for bucket in (0..SIZE_BUCKETS).filter(|&bucket| self.sizes[bucket] > 0) {
+            let share = self.sizes[bucket] as f64 / count as f64 * 100.0;
+            println!("  {:<22}{:>12}{:>9.1}%", bucket_label(bucket), self.sizes[bucket], share);
+        }
+        println!("{:<24}{:>12}", text.allocation_time, text.at_most);
+        for p in PERCENTILES {
+            let name = if p == 100.0 { "max".to_string() } else { format!("p{}", p) };
+            match self.latency_percentile(p) {
+                Some(nanos) => println!("  {:<22}{:>9} ns", name, nanos),
+                None => println!("  {:<22}{:>12}", name, "-"),
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use memory_particles::bench::BenchReport;
+use memory_particles::metadata::RunMetadata;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One completed `bench` run, as stored in the history file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Hash of the scene file, or `default`
+    pub config_hash: String,
+    pub machine: String,
+    pub parallel: bool,
+    pub version: String,
+    pub started: String,
+    pub turns: u64,
+    pub turns_per_second: f64,
+    pub allocations_per_turn: f64,
+    pub peak_live_bytes: usize,
+}
+
+impl HistoryEntry {
+    pub fn new(report: &BenchReport, metadata: &RunMetadata, parallel: bool) -> HistoryEntry {
+        let field = |name: &str| {
+            metadata.fields().into_iter().find(|(key, _)| *key == name).map(|(_, value)| value).unwrap_or_default()
+        };
+        HistoryEntry {
+            config_hash: field("config_hash"),
+            machine: machine_name(),
+            parallel,
+            version: metadata.version.to_string(),
+            started: field("started"),
+            turns: report.turns,
+            turns_per_second: report.turns_per_second().unwrap_or(0.0),
+            allocations_per_turn: report.allocations as f64 / report.turns.max(1) as f64,
+            peak_live_bytes: report.peak_live_bytes,
+        }
+    }
+
+    // Only runs of the same scene, in the same mode and on the same machine are compared
+    fn comparable(&self, other: &HistoryEntry) -> bool {
+        self.config_hash == other.config_hash && self.machine == other.machine && self.parallel == other.parallel
+    }
+}
+
+/// Past `bench` results, kept in a JSON file next to the project.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// Outcome of comparing a run against the best comparable one in the history.
+#[derive(Debug)]
+pub enum Verdict {
+    /// No earlier run of the same scene on this machine
+    First,
+    Ok { best: f64, change: f64 },
+    Regressed { best: f64, change: f64 },
+}
+
+impl History {
+    /// Reads the history, or starts an empty one when the file doesn't exist yet.
+    pub fn load(path: &Path) -> io::Result<History> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(History::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text + "\n")
+    }
+
+    /// Compares the throughput of `entry` with the best comparable run so far.
+    /// `threshold` is the fraction of the best throughput that may be lost
+    /// before the run counts as a regression.
+    pub fn compare(&self, entry: &HistoryEntry, threshold: f64) -> Verdict {
+        let best = self
+            .entries
+            .iter()
+            .filter(|past| past.comparable(entry))
+            .map(|past| past.turns_per_second)
+            .fold(None, |best: Option<f64>, tps| Some(best.map_or(tps, |b| b.max(tps))));
+
+        match best {
+            None => Verdict::First,
+            Some(best) => {
+                let change = entry.turns_per_second / best - 1.0;
+                if change < -threshold {
+                    Verdict::Regressed { best, change }
+                } else {
+                    Verdict::Ok { best, change }
+                }
+            }
+        }
+    }
+}
+
+// Host name when the platform exposes one, so histories shared between
+// machines never compare their results
+fn machine_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH))
+}
@@ -0,0 +1,194 @@
+use crate::dashboard::format_bytes;
+use memory_particles::allocator::MemoryStats;
+use memory_particles::fidelity::Fidelity;
+use memory_particles::locale::{bundle, fill};
+use memory_particles::palette::Palette;
+use memory_particles::telemetry;
+use piston_window::*;
+use std::time::{Duration, Instant};
+
+// Bundled so the HUD doesn't depend on the fonts of the system
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+const FONT_SIZE: u32 = 14;
+const LINE_HEIGHT: f64 = 18.0;
+const MARGIN: f64 = 10.0;
+
+// Width of the legend of the palette, and the steps its ramp is drawn in
+const LEGEND_WIDTH: f64 = 200.0;
+const LEGEND_STEPS: usize = 40;
+
+// Rates are averaged over this long so the numbers can be read
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// H shows the frame rate, the particle count and the heap in a corner of
+/// the window, drawn as text over the scene.
+pub struct Hud {
+    glyphs: Glyphs,
+    pub visible: bool,
+
+    // Panels are opaque, hiding the particles behind the text
+    pub high_contrast: bool,
+
+    // Frames and allocations since the rates were last worked out
+    since: Instant,
+    frames: u32,
+    allocations_at_since: u64,
+
+    fps: f64,
+    allocations_per_frame: f64,
+}
+
+impl Hud {
+    pub fn new(window: &mut PistonWindow, stats: &MemoryStats) -> Result<Hud, String> {
+        let glyphs = Glyphs::from_bytes(FONT, window.create_texture_context(), TextureSettings::new())
+            .map_err(|_| "Could not load the HUD font".to_string())?;
+        Ok(Hud {
+            glyphs,
+            visible: false,
+            high_contrast: false,
+            since: Instant::now(),
+            frames: 0,
+            allocations_at_since: stats.allocations,
+            fps: 0.0,
+            allocations_per_frame: 0.0,
+        })
+    }
+
+    /// Counts a rendered frame, updating the rates twice a second.
+    pub fn end_frame(&mut self, stats: &MemoryStats) {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed >= RATE_INTERVAL {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.allocations_per_frame = (stats.allocations - self.allocations_at_since) as f64 / self.frames as f64;
+            self.since = Instant::now();
+            self.frames = 0;
+            self.allocations_at_since = stats.allocations;
+        }
+    }
+
+    /// Draws the HUD in the top left corner, over a dark panel so it can be
+    /// read on any background. The glyphs are flushed to `device` once drawn.
+    ///
+    /// The median lifetime is shown when the world counts its survival
+    /// curve, as `-` while fewer than half the particles have died, and what
+    /// the allocation trace is missing once it misses anything. The fidelity
+    /// is that of the trace of the allocations, lowered while frames run late.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        particles: usize,
+        median_lifetime: Option<Option<u32>>,
+        fidelity: Fidelity,
+        stats: &MemoryStats,
+        ctx: Context,
+        g: &mut G2d,
+        device: &mut GfxDevice,
+    ) {
+        let text = bundle();
+        let mut lines = vec![
+            fill(text.fps, &[&format!("{:.0}", self.fps)]),
+            fill(text.particles_count, &[&particles]),
+            fill(text.heap, &[&format_bytes(stats.live_bytes as u64), &format_bytes(stats.peak_live_bytes as u64)]),
+            fill(text.allocations_per_frame, &[&format!("{:.1}", self.allocations_per_frame)]),
+        ];
+        if let Some(median) = median_lifetime {
+            lines.push(median.map_or(text.median_lifetime_unknown.to_string(), |age| fill(text.median_lifetime, &[&age])));
+        }
+        lines.push(fill(text.telemetry_fidelity, &[&fidelity.describe()]));
+        let health = telemetry::health();
+        if !health.is_ok() {
+            lines.push(health.headline());
+        }
+
+        let widest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let panel = [
+            MARGIN / 2.0,
+            MARGIN / 2.0,
+            widest as f64 * FONT_SIZE as f64 * 0.62 + MARGIN,
+            lines.len() as f64 * LINE_HEIGHT + MARGIN,
+        ];
+        rectangle(self.panel_color(), panel, ctx.transform, g);
+
+        for (i, line) in lines.iter().enumerate() {
+            let baseline = MARGIN + (i + 1) as f64 * LINE_HEIGHT - 4.0;
+            if !self.draw_text(line, [MARGIN, baseline], ctx, g) {
+                self.visible = false;
+                break;
+            }
+        }
+        self.glyphs.factory.encoder.flush(device);
+    }
+
+    /// Draws the legend of the palette coloring the particles in the bottom
+    /// right corner, above `bottom`: what it colors by, and its ramp with
+    /// the values at both ends, read from the palette every frame so it
+    /// follows changes to its range.
+    pub fn draw_legend(&mut self, palette: &Palette, width: f64, bottom: f64, ctx: Context, g: &mut G2d, device: &mut GfxDevice) {
+        let [title, from, to] = palette.legend();
+        let (left, top) = (width - LEGEND_WIDTH - MARGIN, bottom - 3.0 * LINE_HEIGHT - MARGIN);
+        rectangle(self.panel_color(), [left - MARGIN / 2.0, top, LEGEND_WIDTH + MARGIN, 3.0 * LINE_HEIGHT + MARGIN / 2.0], ctx.transform, g);
+
+        let [start, end] = palette.range;
+        let step = LEGEND_WIDTH / LEGEND_STEPS as f64;
+        for i in 0..LEGEND_STEPS {
+            let value = start + (end - start) * i as f64 / (LEGEND_STEPS - 1) as f64;
+            let [r, green, b] = palette.color_at(value);
+            rectangle([r, green, b, 1.0], [left + i as f64 * step, top + LINE_HEIGHT + 2.0, step + 0.5, LINE_HEIGHT - 6.0], ctx.transform, g);
+        }
+
+        let baseline = |line: f64| top + line * LINE_HEIGHT - 4.0;
+        let right = left + LEGEND_WIDTH - to.len() as f64 * FONT_SIZE as f64 * 0.62;
+        for (label, at) in [(&title, [left, baseline(1.0)]), (&from, [left, baseline(3.0)]), (&to, [right, baseline(3.0)])] {
+            if !self.draw_text(label, at, ctx, g) {
+                break;
+            }
+        }
+        self.glyphs.factory.encoder.flush(device);
+    }
+
+    /// Draws each label from its point, on its baseline, for panels drawn
+    /// by others, like the tuning panel. The glyphs are flushed to `device`
+    /// once drawn.
+    pub fn draw_labels(&mut self, labels: &[(String, [f64; 2])], ctx: Context, g: &mut G2d, device: &mut GfxDevice) {
+        for (label, at) in labels {
+            if !self.draw_text(label, *at, ctx, g) {
+                break;
+            }
+        }
+        self.glyphs.factory.encoder.flush(device);
+    }
+
+    /// Draws `lines` in a panel of their own with its top left corner at
+    /// `at`, like the HUD's.
+    pub fn draw_panel(&mut self, lines: &[String], at: [f64; 2], ctx: Context, g: &mut G2d, device: &mut GfxDevice) {
+        let widest = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let panel = [
+            at[0] + MARGIN / 2.0,
+            at[1] + MARGIN / 2.0,
+            widest as f64 * FONT_SIZE as f64 * 0.62 + MARGIN,
+            lines.len() as f64 * LINE_HEIGHT + MARGIN,
+        ];
+        rectangle(self.panel_color(), panel, ctx.transform, g);
+        let labels: Vec<(String, [f64; 2])> =
+            lines.iter().enumerate().map(|(i, line)| (line.clone(), [at[0] + MARGIN, at[1] + MARGIN + (i + 1) as f64 * LINE_HEIGHT - 4.0])).collect();
+        self.draw_labels(&labels, ctx, g, device);
+    }
+
+    fn panel_color(&self) -> [f32; 4] {
+        [0.0, 0.0, 0.0, if self.high_contrast { 1.0 } else { 0.6 }]
+    }
+
+    // Draws `line` from `at`, on its baseline. False when it couldn't be drawn
+    fn draw_text(&mut self, line: &str, at: [f64; 2], ctx: Context, g: &mut G2d) -> bool {
+        let text = Text::new_color([1.0, 1.0, 1.0, 1.0], FONT_SIZE);
+        let transform = ctx.transform.trans(at[0], at[1]);
+        match text.draw(line, &mut self.glyphs, &ctx.draw_state, transform, g) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Could not draw the HUD: {:?}", err);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+// Side of the square window icon, in pixels
+pub const ICON_SIZE: u32 = 32;
+
+// A few particles rising over the background color, fainter as they go up
+const DOTS: [(i32, i32, u8); 7] = [
+    (8, 26, 255),
+    (17, 24, 240),
+    (24, 27, 250),
+    (12, 16, 190),
+    (22, 13, 160),
+    (7, 7, 110),
+    (18, 5, 90),
+];
+
+/// RGBA pixels of the window icon, row by row.
+pub fn icon_rgba() -> Vec<u8> {
+    let size = ICON_SIZE as i32;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            let mut pixel = [38, 43, 43, 255];
+            for &(dx, dy, brightness) in &DOTS {
+                if (x - dx).abs() <= 2 && (y - dy).abs() <= 2 {
+                    pixel = [brightness, brightness, brightness, 255];
+                }
+            }
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+    pixels
+}
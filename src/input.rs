@@ -0,0 +1,105 @@
+use piston_window::Key;
+
+/// Particles the burst key spawns at once.
+pub const BURST_PARTICLES: i32 = 500;
+
+/// What a key does in the simulation window. Modifiers are tracked by the
+/// window, which decides with Ctrl whether a slot is saved or restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    /// Space: holds the simulation, which is still drawn along with the heap
+    /// timeline, or resumes it
+    Pause,
+
+    /// `.`: runs a single turn while paused
+    Step,
+
+    /// B: spawns `BURST_PARTICLES` particles the way the scene spawns them
+    Burst,
+
+    /// Delete or Backspace: removes every particle and spark
+    Clear,
+
+    /// F: shows or hides the frame time chart
+    FrameChart,
+
+    /// C: shows or hides the chart of the particle count against the heap
+    Correlation,
+
+    /// L: shows or hides the survival curve of the particles
+    Survival,
+
+    /// H: shows or hides the HUD
+    Hud,
+
+    /// T: shows or hides the sliders of the tuning panel
+    Tuning,
+
+    /// M: changes a few tunable parameters at random, a little
+    Mutate,
+
+    /// U: undoes the last mutation still applied
+    UndoMutation,
+
+    /// S: moves the particles to the next allocation strategy, boxed,
+    /// pooled then arena, and spawns from it
+    Storage,
+
+    /// F12: saves the frame as the window shows it, overlays included
+    Screenshot,
+
+    /// F5: saves the world to the `--checkpoint` file
+    SaveCheckpoint,
+
+    /// F9: carries on from the world saved to the `--checkpoint` file
+    LoadCheckpoint,
+
+    /// R: shows the whole window again after zooming or panning
+    ResetView,
+
+    /// V: shows or hides the arrows of the noise forces over the world
+    NoiseField,
+
+    /// G, W, A and N: switch gravity, wind, attractors or noise off or on.
+    /// G with Shift shows or hides the allocation graph instead
+    Force(&'static str),
+
+    /// 1 to 9: restores the snapshot in that slot, counted from 0, or saves
+    /// the world to it with Ctrl
+    Slot(usize),
+}
+
+pub fn key_action(key: Key) -> Option<KeyAction> {
+    let action = match key {
+        Key::Space => KeyAction::Pause,
+        Key::Period => KeyAction::Step,
+        Key::B => KeyAction::Burst,
+        Key::Delete | Key::Backspace => KeyAction::Clear,
+        Key::F => KeyAction::FrameChart,
+        Key::C => KeyAction::Correlation,
+        Key::L => KeyAction::Survival,
+        Key::H => KeyAction::Hud,
+        Key::T => KeyAction::Tuning,
+        Key::M => KeyAction::Mutate,
+        Key::U => KeyAction::UndoMutation,
+        Key::S => KeyAction::Storage,
+        Key::F12 => KeyAction::Screenshot,
+        Key::F5 => KeyAction::SaveCheckpoint,
+        Key::F9 => KeyAction::LoadCheckpoint,
+        Key::R => KeyAction::ResetView,
+        Key::G => KeyAction::Force("gravity"),
+        Key::W => KeyAction::Force("wind"),
+        Key::A => KeyAction::Force("attractor"),
+        Key::N => KeyAction::Force("noise"),
+        Key::V => KeyAction::NoiseField,
+        _ => return digit_slot(key).map(KeyAction::Slot),
+    };
+    Some(action)
+}
+
+fn digit_slot(key: Key) -> Option<usize> {
+    let digits = [
+        Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9,
+    ];
+    digits.iter().position(|&digit| digit == key)
+}
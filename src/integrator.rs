@@ -0,0 +1,84 @@
+use graphics::math::{add, mul_scalar, Vec2d};
+use serde::Deserialize;
+
+/// How the motion of particles is integrated over a turn, from their
+/// position, velocity and the acceleration where they are.
+///
+/// `semi-implicit`, the default, applies every force to the velocity as it
+/// comes, then moves the particle along the new velocity. The others take
+/// the forces and braking together as a single acceleration, which they
+/// evaluate where their stages need it, for forces varying over space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrator {
+    /// Explicit Euler: moves along the velocity of the start of the turn,
+    /// then accelerates. Gains energy in orbits, for comparison only
+    Euler,
+
+    /// Semi-implicit (symplectic) Euler: accelerates, then moves along the
+    /// new velocity
+    #[default]
+    SemiImplicit,
+
+    /// Velocity Verlet: second order, and keeps the energy of orbits from
+    /// drifting, for one more evaluation of the acceleration a turn
+    Verlet,
+
+    /// The classic Runge-Kutta of the fourth order, four evaluations of the
+    /// acceleration a turn
+    Rk4,
+}
+
+impl Integrator {
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::Euler => "euler",
+            Integrator::SemiImplicit => "semi-implicit",
+            Integrator::Verlet => "verlet",
+            Integrator::Rk4 => "rk4",
+        }
+    }
+
+    /// Position and velocity after `dt` from `position` and `velocity`,
+    /// under `acceleration` as a function of position.
+    pub fn step(
+        self,
+        position: Vec2d<f64>,
+        velocity: Vec2d<f64>,
+        dt: f64,
+        acceleration: impl Fn(Vec2d<f64>) -> Vec2d<f64>,
+    ) -> (Vec2d<f64>, Vec2d<f64>) {
+        match self {
+            Integrator::Euler => {
+                let moved = add(position, mul_scalar(velocity, dt));
+                (moved, add(velocity, mul_scalar(acceleration(position), dt)))
+            }
+            Integrator::SemiImplicit => {
+                let velocity = add(velocity, mul_scalar(acceleration(position), dt));
+                (add(position, mul_scalar(velocity, dt)), velocity)
+            }
+            Integrator::Verlet => {
+                let start = acceleration(position);
+                let moved = add(add(position, mul_scalar(velocity, dt)), mul_scalar(start, dt * dt / 2.0));
+                let end = acceleration(moved);
+                (moved, add(velocity, mul_scalar(add(start, end), dt / 2.0)))
+            }
+            Integrator::Rk4 => {
+                // The slopes of position and velocity at the start, twice
+                // halfway along and at the end of the turn
+                let (p1, v1) = (velocity, acceleration(position));
+                let (p2, v2) = {
+                    let velocity = add(velocity, mul_scalar(v1, dt / 2.0));
+                    (velocity, acceleration(add(position, mul_scalar(p1, dt / 2.0))))
+                };
+                let (p3, v3) = {
+                    let velocity = add(velocity, mul_scalar(v2, dt / 2.0));
+                    (velocity, acceleration(add(position, mul_scalar(p2, dt / 2.0))))
+                };
+                let (p4, v4) = (add(velocity, mul_scalar(v3, dt)), acceleration(add(position, mul_scalar(p3, dt))));
+                let weighted = |k1, k2, k3, k4| mul_scalar(add(add(k1, mul_scalar(add(k2, k3), 2.0)), k4), dt / 6.0);
+                (add(position, weighted(p1, p2, p3, p4)), add(velocity, weighted(v1, v2, v3, v4)))
+            }
+        }
+    }
+}
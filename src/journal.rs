@@ -0,0 +1,257 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// A journal is a header, the seed, size and scene of the run, then one
+// entry per turn until the file ends. An entry is a varint of the spawn
+// decision, zigzagged, shifted left once with the low bit set when the
+// bursts and clears made before the turn follow: their count, then each as
+// a varint, a zigzagged burst shifted left once or 1 for a clear. Most turns
+// take a byte. Everything is little-endian
+const MAGIC: &[u8; 8] = b"MPJRNL\0\x01";
+
+// Entries are flushed this often, so a run that crashes loses little of
+// what led to the crash
+const FLUSH_EVERY: u64 = 1024;
+
+/// The run a journal was written by, which replaying it starts again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalHeader {
+    pub width: f64,
+    pub height: f64,
+    pub seed: u64,
+
+    /// Text of the scene file, the default scene when empty
+    pub scene: String,
+}
+
+/// What happened to the particles before a turn, besides what the turn decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalEvent {
+    /// Particles spawned at once, like `B` does
+    Burst(i32),
+
+    /// Every particle removed, like `Delete` does
+    Clear,
+}
+
+/// One turn of a journal: the particles it spawned when above 0 or culled
+/// otherwise, and the events before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JournalTurn {
+    pub decision: i32,
+    pub events: Vec<JournalEvent>,
+}
+
+/// Writes the spawn decisions of every turn of a run as it goes, the
+/// compact way to reproduce it: with the seed and the scene, they are all
+/// `--replay` needs to run the very same turns again, allocations and all,
+/// on any machine. Shared by the copies of a world, like the snapshots of
+/// the window, which all add to the same journal.
+#[derive(Clone)]
+pub struct JournalWriter {
+    inner: Arc<Mutex<Writer>>,
+}
+
+struct Writer {
+    out: BufWriter<File>,
+    events: Vec<JournalEvent>,
+    turns: u64,
+
+    // The first write that failed, after which nothing more is written
+    error: Option<io::Error>,
+}
+
+impl JournalWriter {
+    pub fn create(path: &Path, header: &JournalHeader) -> io::Result<JournalWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&header.width.to_le_bytes())?;
+        out.write_all(&header.height.to_le_bytes())?;
+        out.write_all(&header.seed.to_le_bytes())?;
+        out.write_all(&(header.scene.len() as u32).to_le_bytes())?;
+        out.write_all(header.scene.as_bytes())?;
+        let writer = Writer { out, events: Vec::new(), turns: 0, error: None };
+        Ok(JournalWriter { inner: Arc::new(Mutex::new(writer)) })
+    }
+
+    /// Adds `event` to those of the next turn.
+    pub fn event(&self, event: JournalEvent) {
+        self.lock().events.push(event);
+    }
+
+    /// Writes the decision of the turn just taken, after its events.
+    pub fn write_turn(&self, decision: i32) {
+        let mut writer = self.lock();
+        if writer.error.is_some() {
+            return;
+        }
+        let Writer { out, events, turns, error } = &mut *writer;
+        let mut result = varint(out, zigzag(decision) << 1 | !events.is_empty() as u64);
+        if !events.is_empty() {
+            result = result.and_then(|()| varint(out, events.len() as u64));
+            for event in events.drain(..) {
+                result = result.and_then(|()| varint(out, event_code(event)));
+            }
+        }
+        *turns += 1;
+        if turns.is_multiple_of(FLUSH_EVERY) {
+            result = result.and_then(|()| out.flush());
+        }
+        *error = result.err();
+    }
+
+    /// Turns written so far.
+    pub fn turns(&self) -> u64 {
+        self.lock().turns
+    }
+
+    /// Writes what is left, telling the first write that failed if any.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut writer = self.lock();
+        match writer.error.take() {
+            Some(err) => Err(err),
+            None => writer.out.flush(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Writer> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A journal read back whole, to be replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal {
+    pub header: JournalHeader,
+    pub turns: Vec<JournalTurn>,
+}
+
+impl Journal {
+    /// Reads a journal, leaving out a last entry written halfway by a run
+    /// that ended without finishing it.
+    pub fn open(path: &Path) -> io::Result<Journal> {
+        Journal::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> io::Result<Journal> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        if bytes.len() < 36 || &bytes[..8] != MAGIC {
+            return Err(invalid("not a journal"));
+        }
+        let float = |at: usize| f64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        let scene_len = u32::from_le_bytes(bytes[32..36].try_into().expect("4 bytes")) as usize;
+        let scene = bytes.get(36..36 + scene_len).ok_or_else(|| invalid("journal cut short in its scene"))?;
+        let header = JournalHeader {
+            width: float(8),
+            height: float(16),
+            seed: u64::from_le_bytes(bytes[24..32].try_into().expect("8 bytes")),
+            scene: String::from_utf8(scene.to_vec()).map_err(|_| invalid("scene of the journal is not UTF-8"))?,
+        };
+
+        let mut reader = Reader { bytes, at: 36 + scene_len };
+        let mut turns = Vec::new();
+        while let Some(turn) = reader.turn() {
+            turns.push(turn);
+        }
+        Ok(Journal { header, turns })
+    }
+
+    pub fn len(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+}
+
+/// Where a world replaying a journal is in it. Copies of the world, like
+/// snapshots, go on from where they were copied.
+#[derive(Debug, Clone)]
+pub struct JournalReplay {
+    journal: Arc<Journal>,
+    next: usize,
+}
+
+impl JournalReplay {
+    pub fn new(journal: Journal) -> JournalReplay {
+        JournalReplay { journal: Arc::new(journal), next: 0 }
+    }
+
+    /// The next turn to replay, none once the journal is over.
+    pub fn next_turn(&mut self) -> Option<JournalTurn> {
+        let turn = self.journal.turns.get(self.next)?.clone();
+        self.next += 1;
+        Some(turn)
+    }
+
+    /// Turns replayed so far, and in the whole journal.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.next, self.journal.len())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.at)?;
+            self.at += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn turn(&mut self) -> Option<JournalTurn> {
+        let entry = self.varint()?;
+        let mut turn = JournalTurn { decision: unzigzag(entry >> 1), events: Vec::new() };
+        if entry & 1 == 1 {
+            for _ in 0..self.varint()? {
+                turn.events.push(match self.varint()? {
+                    1 => JournalEvent::Clear,
+                    code => JournalEvent::Burst(unzigzag(code >> 1)),
+                });
+            }
+        }
+        Some(turn)
+    }
+}
+
+fn event_code(event: JournalEvent) -> u64 {
+    match event {
+        JournalEvent::Burst(particles) => zigzag(particles) << 1,
+        JournalEvent::Clear => 1,
+    }
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u64) -> i32 {
+    let value = value as u32;
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+// Writes `value` 7 bits at a time from the lowest
+fn varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    let mut bytes = [0; 10];
+    let mut len = 0;
+    while value >= 0x80 {
+        bytes[len] = value as u8 | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+    bytes[len] = value as u8;
+    out.write_all(&bytes[..=len])
+}
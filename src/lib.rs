@@ -0,0 +1,3134 @@
+//! Particles that depict the memory allocations of the program, and the
+//! instrumented allocator measuring them. The `memory-particles` binary
+//! shows a `World` in a Piston window; install `ReportingAllocator` as the
+//! global allocator to get the same statistics elsewhere.
+
+use graphics::math::{Vec2d, add, mul_scalar, sub};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod accessibility;
+pub mod allocator;
+pub mod ambient;
+pub mod arena;
+pub mod backend;
+pub mod behavior;
+pub mod bench;
+pub mod binary;
+pub mod blend;
+pub mod boundary;
+pub mod callsite;
+pub mod camera;
+pub mod cap;
+pub mod capabilities;
+pub mod capture;
+pub mod checkpoint;
+pub mod color;
+pub mod compare;
+pub mod config;
+pub mod controller;
+pub mod debug_bundle;
+pub mod drops;
+pub mod emitter;
+pub mod energy;
+pub mod event;
+pub mod fade;
+pub mod falloff;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fidelity;
+pub mod force;
+pub mod freelist;
+pub mod gauge;
+pub mod guard;
+pub mod handoff;
+pub mod histogram;
+pub mod integrator;
+pub mod journal;
+pub mod lifetime;
+pub mod locale;
+pub mod metadata;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod migrate;
+#[cfg(unix)]
+pub mod mmap;
+pub mod motion;
+pub mod mutate;
+pub mod noise;
+pub mod obstacle;
+pub mod palette;
+pub mod parallel;
+pub mod params;
+pub mod pool;
+pub mod population;
+pub mod pprof;
+pub mod precision;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod quadtree;
+pub mod recording;
+pub mod region;
+pub mod replay;
+pub mod report;
+pub mod reuse;
+pub mod scenario;
+pub mod selftest;
+pub mod session;
+pub mod shape;
+pub mod shutdown;
+pub mod spatial;
+pub mod statehash;
+#[cfg(unix)]
+pub mod storage;
+pub mod store;
+pub mod summary;
+pub mod survival;
+pub mod sweep;
+pub mod telemetry;
+pub mod trace;
+pub mod trail;
+pub mod units;
+pub mod validate;
+pub mod vec_policy;
+pub mod video;
+pub mod view;
+pub mod warmup;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zone;
+
+use accessibility::Accessibility;
+use ambient::AmbientCycle;
+use allocator::{memory_stats, tagged, AllocationTag};
+use behavior::{center, Behavior, Neighbor, StateMachine, WorldView};
+use camera::{CameraPath, CameraView};
+use checkpoint::Checkpoint;
+use drops::{DropSite, TimedDrop};
+use journal::{JournalEvent, JournalReplay, JournalWriter};
+use blend::BlendMode;
+use boundary::{Edge, EdgeBehavior};
+use region::SpawnRegion;
+use cap::SoftCap;
+use color::ColorSpec;
+use config::{Config, ParticleSettings};
+use controller::PopulationController;
+use scenario::ScenarioRun;
+use emitter::{Emitter, Firework, Rocket, SubEmitter};
+use energy::Energy;
+use event::WorldEvent;
+use fade::{Fade, FadeModel};
+use falloff::Falloff;
+use force::{Force, ForceField};
+use integrator::Integrator;
+use freelist::{AllocationStrategy, ParticlePool};
+use gauge::AllocationGauge;
+use guard::{Fault, FaultSource, Quarantine};
+use lifetime::PopulationPolicy;
+use obstacle::Obstacle;
+use palette::Palette;
+use params::{ParamRegistry, ParamVisitor, Prefixed, Tunable};
+use quadtree::QuadTree;
+use session::WorldState;
+use shape::Shape;
+use spatial::{Collisions, SpatialHash};
+use statehash::StateHasher;
+use survival::SurvivalCurve;
+use trail::{Trail, TrailSettings, TrailStats};
+use vec_policy::StoragePolicy;
+use std::f64::consts::TAU;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use zone::Zone;
+
+// Hosts embedding the library get the counters of every allocation made on
+// their behalf, and the binary gets its allocator from here
+#[cfg(any(feature = "ffi", feature = "python", feature = "wasm"))]
+#[global_allocator]
+static ALLOCATOR: allocator::ReportingAllocator = allocator::ReportingAllocator;
+
+// Fraction of the heap cap from which the world stops spawning particles
+pub const MEMORY_PRESSURE_THRESHOLD: f64 = 0.9;
+
+// Particles fainter than this are considered invisible
+pub const MIN_VISIBLE_ALPHA: f32 = 0.02;
+
+// Color behind the scene, unless the scene or its ambient cycle changes it
+pub const BACKGROUND: [f32; 4] = [0.15, 0.17, 0.17, 1.0];
+
+// Particles a world starts with unless told otherwise
+pub const INITIAL_PARTICLES: i32 = 1000;
+
+// Size in bytes of the user data every particle carries
+pub const USER_DATA_SIZE: usize = 16;
+
+/// Turns per second that velocities and accelerations are given for: a
+/// particle with a speed of 1 moves 60 pixels a second, whatever the turn
+/// rate of the window.
+pub const BASE_TURN_RATE: f64 = 60.0;
+
+// Fraction of its acceleration a particle keeps after a turn, the braking
+// of spawn wearing off
+const ACCELERATION_DECAY: f64 = 0.7;
+
+// Substeps of a fast particle's move are no shorter than this many pixels,
+// and there are no more than this many of them, however fast it goes
+const MIN_SUBSTEP: f64 = 1.0;
+const MAX_SUBSTEPS: u32 = 64;
+
+/// Contains the data that will be used through the lifetime of the program.
+#[derive(Clone)]
+pub struct World {
+    pub current_turn: u64,
+    #[allow(clippy::vec_box)]
+    pub particles: Vec<Box<Particle>>,
+
+    // Capacity of `particles` made up front by `reserve`, which the list is
+    // never shrunk below, and the turns its spare room is handed back on
+    reserved: usize,
+    storage_policy: StoragePolicy,
+
+    // What the storage policy replaced: off, the list never shrinks whatever
+    // the policy, as with `StoragePolicy::NeverShrink`
+    #[deprecated(note = "use `set_storage_policy` with `StoragePolicy::NeverShrink` or `StoragePolicy::ShrinkEvery(1)`")]
+    pub shrink: bool,
+    pub height: f64,
+    pub width: f64,
+    pub rng: StdRng,
+
+    // Updates the particles in fixed chunks on the rayon thread pool,
+    // each chunk with its own random stream
+    pub parallel: bool,
+
+    // Running totals of particles that were spawned and removed
+    pub total_spawned: u64,
+    pub total_died: u64,
+
+    // Live heap bytes the world tries to stay under, if any
+    pub max_heap_bytes: Option<usize>,
+
+    // Population over which particles are culled early at random, if any
+    soft_cap: Option<SoftCap>,
+
+    // Spawns particles from the allocation rate of the process instead of
+    // at random, leaving them until they fade out
+    pub allocation_gauge: Option<AllocationGauge>,
+
+    // How long particles live and whether the dead are replaced
+    pub population: PopulationPolicy,
+
+    // Decides the spawns of every turn when set, steering the population
+    // towards a target
+    pub controller: Option<PopulationController>,
+
+    // The built-in scenario of `--scenario` the world runs, if any
+    pub scenario: Option<ScenarioRun>,
+
+    // Otherwise every turn spawns, or culls when negative, a random number
+    // of particles between minus and plus this
+    pub spawn_rate: i32,
+
+    // Fraction of the spawns of a turn that happen, all of them at 1. The
+    // window lowers it while its frames stall, so turns run without frames
+    // can't make the population and heap run away
+    pub spawn_throttle: f64,
+
+    // Particles are spawned from each emitter in turn, or from the
+    // bottom of the window when there are none
+    emitters: Vec<Emitter>,
+    next_emitter: usize,
+
+    // Where particles appear without emitters, and the boundary past
+    // which they are removed, if any
+    spawn_edge: Edge,
+    spawn_region: Option<SpawnRegion>,
+    kill_plane: Option<Edge>,
+    spawn_color: ColorSpec,
+
+    // What becomes of particles reaching the sides of the window, which
+    // the window moves along when resized
+    pub edges: EdgeBehavior,
+
+    // How the motion of the particles is integrated over a turn
+    pub integrator: Integrator,
+
+    // Lines through the last positions of every particle, if any, and what
+    // their positions took
+    pub trail: Option<TrailSettings>,
+    pub trail_stats: TrailStats,
+    spawn_fade: Option<FadeModel>,
+    spawn_blend: BlendMode,
+
+    // Speed limit of the particles of emitters that don't set their own
+    max_speed: f64,
+
+    // Size and motion of every spawned particle
+    particle_settings: ParticleSettings,
+
+    pub obstacles: Vec<Obstacle>,
+
+    // Color the window is painted with before every frame, by its alpha over
+    // the frame before, so below 1 what moved leaves trails
+    pub background: [f32; 4],
+
+    // Slowly changes the background and the colors of new particles
+    pub ambient: Option<AmbientCycle>,
+
+    // Pans and zooms of the view; the simulation never reads it
+    pub camera: CameraPath,
+
+    // How particles are drawn, solid squares when missing; the simulation
+    // never reads it
+    pub falloff: Option<Falloff>,
+
+    // Recolors the particles every turn by their age or speed, if any
+    pub palette: Option<Palette>,
+
+    // Set by the window to a circle around the mouse cursor, which particles
+    // flow around without colliding
+    pub cursor_obstacle: Option<Obstacle>,
+
+    // Set by the window while a mouse button is held, an attractor at the
+    // cursor pulling the particles around it in or pushing them away
+    pub cursor_force: Option<Force>,
+    pub zones: Vec<Zone>,
+
+    // Act on every particle, unless their kind is switched off
+    pub forces: Vec<Force>,
+    disabled_forces: Vec<&'static str>,
+    drag: f64,
+    jitter: f64,
+
+    // Spawns of a turn are spread over it rather than all at its start
+    spawn_stagger: bool,
+
+    // Length of a turn in turns of `BASE_TURN_RATE`, which velocities and
+    // accelerations are given in. The window sets it from its turn rate
+    pub time_step: f64,
+    pub behavior: StateMachine,
+
+    // Dynamics added by programs using the library, run on every particle
+    // after the state machine, and the particles as they were at the start
+    // of the turn for those looking at their neighbors. Checkpoints and
+    // sessions don't keep them, being code
+    behaviors: Vec<Arc<dyn Behavior>>,
+    neighbors: Vec<Neighbor>,
+
+    // What happened during the last turn, e.g. collisions with obstacles
+    events: Vec<WorldEvent>,
+
+    // Particles bouncing off each other when set, with the candidate pairs
+    // of the broad phase kept from turn to turn, and the number of pairs
+    // that touched on the last turn
+    pub collisions: Option<Collisions>,
+    pairs: Vec<(u32, u32)>,
+    contacts: usize,
+
+    // The grid the neighbors of behaviors are found in, and the quadtree
+    // over the centers of the particles once they moved on the last turn,
+    // for `query_radius` and the broad phase of collisions. Both are
+    // rebuilt every turn in the buffers they keep
+    grid: SpatialHash,
+    index: QuadTree,
+
+    // Rockets burst this turn, as where they were, how they moved and what
+    // they were, queued to spawn their bursts once the particles are no
+    // longer being walked through. Kept from turn to turn
+    detonations: Vec<(Vec2d<f64>, Vec2d<f64>, Rocket)>,
+
+    // Short-lived particles thrown by sub-emitters, kept apart from the
+    // main population so they don't take part in its spawning and culling
+    sparks_emitter: Option<SubEmitter>,
+    #[allow(clippy::vec_box)]
+    pub sparks: Vec<Box<Particle>>,
+
+    // Particles taken out after going NaN or infinite, only checked when set
+    pub quarantine: Option<Quarantine>,
+
+    // Boxes of dead particles recycled for the next spawns when set, so
+    // they stop allocating; otherwise every spawn allocates its box
+    pub particle_pool: Option<ParticlePool>,
+
+    // Counts the ages particles die at when set, sparks left out
+    pub survival: Option<SurvivalCurve>,
+
+    // The modes the scene was loaded with, already applied to it; the
+    // window reads them for what it draws over the world
+    pub accessibility: Accessibility,
+
+    // Gets the spawn decision of every turn when set, for `--journal`
+    pub journal: Option<JournalWriter>,
+
+    // The journal of an earlier run when set, whose decisions and events
+    // the next turns take in place of their own until it is over
+    pub replay: Option<JournalReplay>,
+
+    // Text of the scene file the world was built from, kept by checkpoints;
+    // none for the default scene
+    pub scene_text: Option<String>,
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        drop(TimedDrop::new(DropSite::World, self.particles.len(), self.particles.drain(..)));
+    }
+}
+
+// Defines the shape of and object in 2D space
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Particle {
+    pub height: f64,
+    pub width: f64,
+    pub position: Vec2d<f64>,
+    pub velocity: Vec2d<f64>,
+    pub acceleration: Vec2d<f64>,
+    pub color: [f32; 4],
+
+    // Decay of the alpha applied every turn
+    fade: Fade,
+
+    // Terminal velocity in pixels per turn, infinite when unlimited
+    #[serde(with = "session::unlimited")]
+    max_speed: f64,
+
+    // Zeroed at spawn and never touched by the simulation itself, so custom
+    // behaviors can keep per-particle state in it, like an "infected" flag
+    // or a counter, without adding fields to the particle
+    pub user_data: [u8; USER_DATA_SIZE],
+
+    // Index of the particle's state in the world's behavior state machine,
+    // and how many turns it has been in it
+    pub state: usize,
+    pub state_turns: u32,
+
+    // Set when the particle went through its last state, to be removed
+    expired: bool,
+
+    // Pinned particles hold still: no force moves them and they are not
+    // integrated, but they still fade, collide and are drawn
+    pub pinned: bool,
+
+    // How the particle is blended into what is drawn behind it
+    pub blend: BlendMode,
+
+    // What the particle is drawn as
+    pub shape: Shape,
+
+    // Turns since the particle spawned, and the age it dies at,
+    // `u32::MAX` when it may live forever
+    pub age: u32,
+    pub max_age: u32,
+
+    // Turn the particle was spawned on, for the cohort statistics; never
+    // read by the simulation
+    #[serde(default)]
+    pub born: u64,
+
+    // Set for the rockets of fireworks, which burst when their age reaches
+    // its fuse
+    #[serde(default)]
+    pub rocket: Option<Rocket>,
+
+    // Where the particle was over its last turns, in scenes with trails;
+    // drawn only, never read by the simulation
+    #[serde(skip)]
+    pub trail: Option<Trail>,
+}
+
+impl Particle {
+    /// A particle at `position` heading along `direction`, a unit vector,
+    /// upwards for particles rising from the bottom of the window.
+    pub fn new<R: Rng>(
+        position: Vec2d<f64>,
+        direction: Vec2d<f64>,
+        color: [f32; 4],
+        settings: &ParticleSettings,
+        rng: &mut R,
+    ) -> Particle {
+        let [x, y] = position;
+        let speed = sample_range(settings.speed, rng);
+        let [x_velocity, y_velocity] = mul_scalar(direction, speed);
+        let braking = sample_range(settings.braking, rng);
+        let [x_acceleration, y_acceleration] = mul_scalar(direction, -braking);
+        let shape = settings.shape.sample(rng);
+
+        Particle {
+            height: settings.size,
+            width: settings.size,
+            position: [x, y],
+            velocity: [x_velocity, y_velocity],
+            // Slows down the particle as it travels along the screen
+            acceleration: [x_acceleration, y_acceleration],
+            color,
+            fade: Fade::Multiply(settings.fade as f32),
+            max_speed: f64::INFINITY,
+            user_data: [0; USER_DATA_SIZE],
+            state: 0,
+            state_turns: 0,
+            expired: false,
+            pinned: false,
+            blend: BlendMode::Alpha,
+            shape,
+            age: 0,
+            max_age: u32::MAX,
+            born: 0,
+            rocket: None,
+            trail: None,
+        }
+    }
+
+    // Whether any of the particle's square is inside a window of this size.
+    // A particle with a NaN position is nowhere
+    fn on_screen(&self, width: f64, height: f64) -> bool {
+        let [x, y] = self.position;
+        x + self.width >= 0.0 && x <= width && y + self.height >= 0.0 && y <= height
+    }
+
+    // A particle thrown by a sub-emitter burst, flying in a random direction
+    fn burst<R: Rng>(position: Vec2d<f64>, emitter: &SubEmitter, rng: &mut R) -> Particle {
+        let angle = rng.gen_range(0.0..TAU);
+        let speed = emitter.speed * rng.gen_range(0.5..=1.0);
+
+        Particle {
+            height: emitter.size,
+            width: emitter.size,
+            position,
+            velocity: [angle.cos() * speed, angle.sin() * speed],
+            acceleration: [0.0, 0.0],
+            color: emitter.color,
+            fade: Fade::Multiply(emitter.fade),
+            max_speed: f64::INFINITY,
+            user_data: [0; USER_DATA_SIZE],
+            state: 0,
+            state_turns: 0,
+            expired: false,
+            pinned: false,
+            blend: emitter.blend,
+            shape: Shape::Square,
+            age: 0,
+            max_age: u32::MAX,
+            born: 0,
+            rocket: None,
+            trail: None,
+        }
+    }
+
+    // Applies the zones, forces, drag, jitter and behavior of the world, then moves the particle.
+    // Integrators other than the semi-implicit one take the forces along with the move.
+    // With `guard`, the particle is checked after every stage and the step stops
+    // at the first one leaving a NaN or infinite value, which is returned
+    #[allow(clippy::too_many_arguments)]
+    fn step<R: Rng>(
+        &mut self,
+        zones: &[Zone],
+        field: ForceField,
+        integrator: Integrator,
+        drag: f64,
+        jitter: f64,
+        behavior: &StateMachine,
+        behaviors: &[Arc<dyn Behavior>],
+        view: &WorldView,
+        dt: f64,
+        guard: bool,
+        rng: &mut R,
+    ) -> Option<Fault> {
+        if let Some(fault) = self.checked(guard, || FaultSource::Before) {
+            return Some(fault);
+        }
+        // Pinned particles feel no force, whatever their zone
+        if !self.pinned {
+            for (index, zone) in zones.iter().enumerate() {
+                zone.apply(self.position, &mut self.velocity);
+                if let Some(fault) = self.checked(guard, || FaultSource::Zone { index, force: zone.force.kind() }) {
+                    return Some(fault);
+                }
+            }
+            let impulses = integrator == Integrator::SemiImplicit;
+            for (index, force) in field.active().filter(|_| impulses) {
+                let [x, y] = force.acceleration(self.position, field.turn);
+                self.velocity = [self.velocity[0] + x * dt, self.velocity[1] + y * dt];
+                if let Some(fault) = self.checked(guard, || FaultSource::Force { index, kind: force.kind() }) {
+                    return Some(fault);
+                }
+            }
+            if let Some(cursor) = field.cursor.filter(|_| impulses) {
+                let [x, y] = cursor.acceleration(self.position, field.turn);
+                self.velocity = [self.velocity[0] + x * dt, self.velocity[1] + y * dt];
+                if let Some(fault) = self.checked(guard, || FaultSource::Cursor) {
+                    return Some(fault);
+                }
+            }
+            if drag > 0.0 {
+                self.velocity = mul_scalar(self.velocity, 1.0 - drag);
+                if let Some(fault) = self.checked(guard, || FaultSource::Drag) {
+                    return Some(fault);
+                }
+            }
+
+            // Drawn from the world's generator, never from a fresh one, so
+            // runs stay reproducible. Skipped when disabled to leave the
+            // random sequence untouched
+            if jitter > 0.0 {
+                self.velocity[0] += rng.gen_range(-jitter..=jitter);
+                self.velocity[1] += rng.gen_range(-jitter..=jitter);
+                if let Some(fault) = self.checked(guard, || FaultSource::Jitter) {
+                    return Some(fault);
+                }
+            }
+        }
+
+        let state = self.state;
+        behavior.apply(self, rng);
+        let state_name = || FaultSource::State(behavior.name_of(state).unwrap_or_default().to_string());
+        if let Some(fault) = self.checked(guard && !behavior.is_empty(), state_name) {
+            return Some(fault);
+        }
+        for behavior in behaviors {
+            behavior.apply(self, view, dt);
+            if let Some(fault) = self.checked(guard, || FaultSource::Behavior(behavior.name())) {
+                return Some(fault);
+            }
+        }
+
+        match integrator {
+            Integrator::SemiImplicit => self.update(dt),
+            _ => self.integrate(integrator, &field, dt),
+        }
+        self.checked(guard, || FaultSource::Integrator)
+    }
+
+    fn checked(&self, guard: bool, source: impl FnOnce() -> FaultSource) -> Option<Fault> {
+        if guard {
+            guard::check(self, source)
+        } else {
+            None
+        }
+    }
+
+    // Everything about the particle that the turns change or read
+    fn hash(&self, hasher: &mut StateHasher) {
+        for value in [self.width, self.height, self.max_speed] {
+            hasher.write_f64(value);
+        }
+        for vector in [self.position, self.velocity, self.acceleration] {
+            vector.iter().for_each(|&value| hasher.write_f64(value));
+        }
+        self.color.iter().for_each(|&channel| hasher.write_f32(channel));
+        match self.fade {
+            Fade::Multiply(factor) => hasher.write_f32(factor),
+            Fade::Subtract(amount) => hasher.write_f32(amount),
+            Fade::Step { turns_left } => hasher.write_u64(turns_left as u64),
+        }
+        hasher.write(&self.user_data);
+        hasher.write_u64(self.state as u64);
+        hasher.write_u64(self.state_turns as u64);
+        hasher.write_u64(self.age as u64);
+        hasher.write_u64(self.max_age as u64);
+        hasher.write(&[self.expired as u8, self.pinned as u8, self.blend as u8, self.shape as u8]);
+        // Left out of particles that aren't rockets, whose hashes are those of before fireworks
+        if let Some(rocket) = self.rocket {
+            hasher.write(&[rocket.emitter as u8, (rocket.emitter >> 8) as u8, rocket.stage]);
+            hasher.write_u64(rocket.fuse as u64);
+        }
+    }
+
+    // Separates two overlapping particles, as discs as wide as their squares,
+    // and bounces them off each other when they are getting closer, as if
+    // they weighed the same; pinned ones don't move. Returns whether they touched
+    fn bounce_off(&mut self, other: &mut Particle, restitution: f64) -> bool {
+        let weights = [!self.pinned as u8 as f64, !other.pinned as u8 as f64];
+        let total = weights[0] + weights[1];
+        let reach = (self.width.max(self.height) + other.width.max(other.height)) / 2.0;
+        let center = |p: &Particle| [p.position[0] + p.width / 2.0, p.position[1] + p.height / 2.0];
+        let offset = sub(center(other), center(self));
+        let distance = offset[0].hypot(offset[1]);
+        if total == 0.0 || distance.is_nan() || distance >= reach {
+            return false;
+        }
+
+        let normal = if distance > 0.0 { mul_scalar(offset, 1.0 / distance) } else { [1.0, 0.0] };
+        let overlap = reach - distance;
+        self.position = sub(self.position, mul_scalar(normal, overlap * weights[0] / total));
+        other.position = add(other.position, mul_scalar(normal, overlap * weights[1] / total));
+
+        let closing = sub(other.velocity, self.velocity);
+        let speed = closing[0] * normal[0] + closing[1] * normal[1];
+        if speed < 0.0 {
+            let impulse = -(1.0 + restitution) * speed / total;
+            self.velocity = sub(self.velocity, mul_scalar(normal, impulse * weights[0]));
+            other.velocity = add(other.velocity, mul_scalar(normal, impulse * weights[1]));
+        }
+        true
+    }
+
+    // Moves the particle along its velocity for a number of turns, or a fraction of one
+    fn advance(&mut self, fraction: f64) {
+        if !self.pinned {
+            self.position = add(self.position, mul_scalar(self.velocity, fraction));
+        }
+    }
+
+    // Collides the particle with the obstacles along the whole of its last
+    // move. A move longer than the particle is replayed in steps no longer
+    // than it, so fast particles don't pass through obstacles in one turn.
+    // The move lasted `dt` turns
+    fn collide(&mut self, obstacles: &[Obstacle], dt: f64, events: &mut Vec<WorldEvent>) {
+        let distance = self.velocity[0].hypot(self.velocity[1]) * dt;
+        let size = self.width.max(self.height).max(MIN_SUBSTEP);
+        let steps = if self.pinned || !distance.is_finite() {
+            1
+        } else {
+            (distance / size).ceil().clamp(1.0, MAX_SUBSTEPS as f64) as u32
+        };
+
+        // Back to where the move started, then along it again. A bounce
+        // sends the rest of the move along the new velocity
+        if steps > 1 {
+            self.position = sub(self.position, mul_scalar(self.velocity, dt));
+        }
+        for _ in 0..steps {
+            if steps > 1 {
+                self.advance(dt / steps as f64);
+            }
+            for obstacle in obstacles {
+                if let Some(position) = obstacle.collide(&mut self.position, &mut self.velocity) {
+                    events.push(WorldEvent::Collision { position });
+                }
+            }
+        }
+    }
+
+    // Integrates the motion over `dt` turns
+    fn update(&mut self, dt: f64) {
+        if !self.pinned {
+            self.velocity = add(self.velocity, mul_scalar(self.acceleration, dt));
+
+            // Clamped before moving so no force, however strong, can throw the particle further
+            let speed = self.velocity[0].hypot(self.velocity[1]);
+            if speed > self.max_speed {
+                self.velocity = mul_scalar(self.velocity, self.max_speed / speed);
+            }
+            self.position = add(self.position, mul_scalar(self.velocity, dt));
+            self.acceleration = mul_scalar(self.acceleration, ACCELERATION_DECAY.powf(dt));
+        }
+        self.age_a_turn();
+    }
+
+    // Integrates the motion over `dt` turns with `integrator`, the braking
+    // of the particle and the forces of `field` taken as one acceleration.
+    // The speed is clamped after the move
+    fn integrate(&mut self, integrator: Integrator, field: &ForceField, dt: f64) {
+        if !self.pinned {
+            let braking = self.acceleration;
+            let acceleration = |position| add(braking, field.acceleration(position));
+            let (position, velocity) = integrator.step(self.position, self.velocity, dt, acceleration);
+            let speed = velocity[0].hypot(velocity[1]);
+            self.velocity = if speed > self.max_speed { mul_scalar(velocity, self.max_speed / speed) } else { velocity };
+            self.position = position;
+            self.acceleration = mul_scalar(self.acceleration, ACCELERATION_DECAY.powf(dt));
+        }
+        self.age_a_turn();
+    }
+
+    fn age_a_turn(&mut self) {
+        // Make the particcle more transparent over time
+        self.fade.apply(&mut self.color[3]);
+        self.age = self.age.saturating_add(1);
+    }
+}
+
+// Removes the particles `dead` picks, in a single pass keeping the others
+// in order, and returns how many were removed. With a pool, their boxes go
+// back to it rather than to the allocator, and with a survival curve their
+// ages are counted in it
+#[allow(clippy::vec_box)]
+fn remove_where(
+    particles: &mut Vec<Box<Particle>>,
+    pool: Option<&mut ParticlePool>,
+    mut survival: Option<&mut SurvivalCurve>,
+    mut dead: impl FnMut(&Particle) -> bool,
+) -> usize {
+    let before = particles.len();
+    let mut dead = |p: &Particle| {
+        let died = dead(p);
+        if let (true, Some(survival)) = (died, survival.as_mut()) {
+            survival.record(p.age);
+        }
+        died
+    };
+    match pool {
+        Some(pool) => pool.recycle(particles.extract_if(.., |p| dead(p))),
+        None => particles.retain(|p| !dead(p)),
+    }
+    before - particles.len()
+}
+
+// The stage of fireworks `rocket` is a rocket of
+fn firework_stage(emitters: &[Emitter], rocket: Rocket) -> Option<&Firework> {
+    emitters.get(rocket.emitter as usize)?.firework.as_ref()?.stage(rocket.stage)
+}
+
+// Age a rocket bursts at, from `[low, high]`
+fn sample_fuse<R: Rng>([low, high]: [u32; 2], rng: &mut R) -> u32 {
+    rng.gen_range(low..=high.max(low))
+}
+
+// A value from `[low, high)`, or `low` when the range is empty
+fn sample_range<R: Rng>([low, high]: [f64; 2], rng: &mut R) -> f64 {
+    if high > low {
+        rng.gen_range(low..high)
+    } else {
+        low
+    }
+}
+
+impl World {
+    #[allow(deprecated)]
+    fn new(width: f64, height: f64) -> World {
+        World {
+            current_turn: 0,
+
+            // Use Box instead of Particle in order to use extra more memory allocation
+            particles: Vec::<Box<Particle>>::new(),
+            reserved: 0,
+            storage_policy: StoragePolicy::default(),
+            shrink: true,
+            height,
+            width,
+            rng: StdRng::from_entropy(),
+            parallel: false,
+            total_spawned: 0,
+            total_died: 0,
+            max_heap_bytes: None,
+            soft_cap: None,
+            allocation_gauge: None,
+            population: PopulationPolicy::default(),
+            controller: None,
+            scenario: None,
+            spawn_rate: 3,
+            spawn_throttle: 1.0,
+            emitters: Vec::new(),
+            next_emitter: 0,
+            spawn_edge: Edge::Bottom,
+            spawn_region: None,
+            kill_plane: None,
+            edges: EdgeBehavior::Kill,
+            integrator: Integrator::SemiImplicit,
+            trail: None,
+            trail_stats: TrailStats::default(),
+            spawn_color: ColorSpec::default(),
+            spawn_fade: None,
+            spawn_blend: BlendMode::Alpha,
+            max_speed: f64::INFINITY,
+            particle_settings: ParticleSettings::default(),
+            obstacles: Vec::new(),
+            background: BACKGROUND,
+            ambient: None,
+            camera: CameraPath::default(),
+            falloff: None,
+            palette: None,
+            cursor_obstacle: None,
+            cursor_force: None,
+            zones: Vec::new(),
+            forces: Vec::new(),
+            disabled_forces: Vec::new(),
+            drag: 0.0,
+            jitter: 0.0,
+            spawn_stagger: false,
+            time_step: 1.0,
+            behavior: StateMachine::default(),
+            behaviors: Vec::new(),
+            neighbors: Vec::new(),
+            events: Vec::new(),
+            detonations: Vec::new(),
+            sparks_emitter: None,
+            sparks: Vec::new(),
+            collisions: None,
+            pairs: Vec::new(),
+            contacts: 0,
+            grid: SpatialHash::new(),
+            index: QuadTree::default(),
+            quarantine: None,
+            particle_pool: None,
+            survival: None,
+            accessibility: Accessibility::default(),
+            journal: None,
+            replay: None,
+            scene_text: None,
+        }
+    }
+
+    pub fn from_config(width: f64, height: f64, mut config: Config) -> World {
+        let accessibility = config.accessibility;
+        accessibility.apply(&mut config);
+        let mut world = World::new(width, height);
+        world.accessibility = accessibility;
+        world.scene_text = config.source;
+        world.emitters = config.emitters;
+        world.spawn_edge = config.spawn_edge;
+        world.spawn_region = config.spawn_region;
+        world.kill_plane = config.kill_plane;
+        world.edges = config.edges;
+        world.integrator = config.integrator;
+        world.trail = config.trail;
+        world.spawn_color = config.color;
+        world.spawn_fade = config.fade;
+        world.spawn_blend = config.blend;
+        world.max_speed = config.max_speed.unwrap_or(f64::INFINITY);
+        world.obstacles = config.obstacles;
+        world.zones = config.zones;
+        world.forces = config.forces;
+        world.drag = config.drag.clamp(0.0, 1.0);
+        world.jitter = config.jitter;
+        world.spawn_stagger = config.spawn_stagger;
+        world.behavior = config.behavior;
+        world.soft_cap = config.soft_cap;
+        world.controller = config.controller;
+        world.sparks_emitter = config.sparks;
+        world.background = config.background.unwrap_or(BACKGROUND);
+        world.ambient = config.ambient;
+        world.camera = CameraPath::new(config.camera);
+        world.falloff = config.falloff;
+        world.palette = config.palette;
+        world.population = config.population.unwrap_or_default();
+        world.collisions = config.collisions;
+        if let Some(flocking) = config.flocking {
+            world.add_behavior(flocking);
+        }
+        world.particle_settings = config.particle;
+        world.spawn_rate = config.spawn_rate.unwrap_or(world.spawn_rate);
+        world.max_heap_bytes = config.max_heap;
+        world
+    }
+
+    // Creates the next particle where it should be spawned, travelling into
+    // the window, in the first state of the behavior. Emitters without a
+    // rate of their own take turns; without any, the spawn edge is used
+    fn spawn_particle(&mut self) -> Particle {
+        let shared = self.emitters.iter().filter(|emitter| emitter.rate.is_none()).count();
+        if shared > 0 {
+            let nth = self.next_emitter % shared;
+            self.next_emitter = self.next_emitter.wrapping_add(1);
+            let mut indexes = self.emitters.iter().enumerate().filter(|(_, emitter)| emitter.rate.is_none());
+            let index = indexes.nth(nth).map_or(0, |(index, _)| index);
+            return self.spawn_from(index);
+        }
+
+        // Starts at a random positin along the spawn edge of the window, or in the spawn region
+        let (position, direction) = match &self.spawn_region {
+            Some(region) => region.spawn_point(&mut self.rng, self.width, self.height),
+            None => self.spawn_edge.spawn_point(&mut self.rng, self.width, self.height),
+        };
+        self.spawn_at(position, direction)
+    }
+
+    // A particle of the scene's own settings, as spawned without emitters
+    fn spawn_at(&mut self, position: Vec2d<f64>, direction: Vec2d<f64>) -> Particle {
+        let color = self.spawn_color.sample(&mut self.rng);
+        let color = self.ambient_tint(color);
+        let mut particle = Particle::new(position, direction, color, &self.particle_settings, &mut self.rng);
+        if let Some(fade) = &self.spawn_fade {
+            particle.fade = fade.start(color[3]);
+        }
+        particle.max_speed = self.max_speed;
+        particle.blend = self.spawn_blend;
+        particle.max_age = self.population.max_age(&mut self.rng);
+        particle.born = self.current_turn;
+        self.behavior.start(&mut particle, &mut self.rng);
+        particle
+    }
+
+    // A particle of the emitter at `index`. Randomness is only drawn for the
+    // line and cone of the emitters that have them
+    fn spawn_from(&mut self, index: usize) -> Particle {
+        let emitter = &self.emitters[index];
+        let color = emitter.color.sample(&mut self.rng);
+        let color = self.ambient_tint(color);
+        let along = if emitter.to.is_some() { self.rng.gen_range(0.0..=1.0) } else { 0.0 };
+        let position = emitter.point_at(self.current_turn, along);
+        let side = if emitter.spread > 0.0 { self.rng.gen_range(-1.0..=1.0) } else { 0.0 };
+        let direction = emitter.direction_at(side);
+        let settings = emitter.particle.as_ref().unwrap_or(&self.particle_settings);
+        let mut particle = Particle::new(position, direction, color, settings, &mut self.rng);
+        if let Some(fade) = &emitter.fade {
+            particle.fade = fade.start(color[3]);
+        }
+        particle.max_speed = emitter.max_speed.unwrap_or(self.max_speed);
+        particle.pinned = emitter.pinned;
+        particle.blend = emitter.blend;
+        particle.max_age = self.population.max_age(&mut self.rng);
+        particle.born = self.current_turn;
+        if let Some(firework) = &emitter.firework {
+            particle.rocket = Some(Rocket { emitter: index as u16, stage: 0, fuse: sample_fuse(firework.fuse, &mut self.rng) });
+        }
+        self.behavior.start(&mut particle, &mut self.rng);
+        particle
+    }
+
+    // Spawns the particles due from the emitters with a rate of their own
+    fn spawn_from_rated(&mut self) {
+        for index in 0..self.emitters.len() {
+            let due = self.emitters[index].due(self.current_turn, self.time_step);
+            let due = self.throttled(due);
+            for _ in 0..due {
+                let particle = self.spawn_from(index);
+                self.push_particle(particle);
+            }
+        }
+    }
+
+    // How many of `count` spawns happen under the spawn throttle, rounding down
+    fn throttled(&self, count: usize) -> usize {
+        if self.spawn_throttle >= 1.0 {
+            return count;
+        }
+        (count as f64 * self.spawn_throttle.max(0.0)) as usize
+    }
+
+    /// Adds a force acting on every particle from the next turn on, like
+    /// `world.add_force(Force::Gravity { acceleration: [0.0, 0.2] })`.
+    /// Its kind is switched back on if it was off.
+    pub fn add_force(&mut self, force: Force) {
+        self.disabled_forces.retain(|kind| *kind != force.kind());
+        self.forces.push(force);
+    }
+
+    /// Adds a behavior run on every particle from the next turn on, one of
+    /// `behavior` like `world.add_behavior(Drag { coefficient: 0.01 })` or a
+    /// type of the program's own implementing `Behavior`. Behaviors run in
+    /// the order they were added.
+    pub fn add_behavior(&mut self, behavior: impl Behavior + 'static) {
+        self.behaviors.push(Arc::new(behavior));
+    }
+
+    /// Names of the behaviors added, in the order they run.
+    pub fn behavior_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.behaviors.iter().map(|behavior| behavior.name())
+    }
+
+    /// Removes every behavior added with `add_behavior`.
+    pub fn clear_behaviors(&mut self) {
+        self.behaviors.clear();
+    }
+
+    /// The forces acting on the particles this turn, the kinds switched off
+    /// left out, as the window draws them.
+    pub fn force_field(&self) -> ForceField<'_> {
+        ForceField {
+            forces: &self.forces,
+            disabled: &self.disabled_forces,
+            cursor: self.cursor_force.as_ref(),
+            turn: self.current_turn,
+        }
+    }
+
+    /// Switches the forces of a kind, like `"wind"`, off or back on, and
+    /// returns whether they are on now.
+    pub fn toggle_forces(&mut self, kind: &'static str) -> bool {
+        match self.disabled_forces.iter().position(|disabled| *disabled == kind) {
+            Some(index) => {
+                self.disabled_forces.swap_remove(index);
+                true
+            }
+            None => {
+                self.disabled_forces.push(kind);
+                false
+            }
+        }
+    }
+
+    /// Spawns `count` particles at `position` heading every way, of the
+    /// scene's settings as if spawned without emitters.
+    pub fn burst_at(&mut self, position: Vec2d<f64>, count: usize) {
+        for _ in 0..count {
+            let angle = self.rng.gen_range(0.0..std::f64::consts::TAU);
+            let particle = self.spawn_at(position, [angle.cos(), angle.sin()]);
+            self.push_particle(particle);
+        }
+    }
+
+    /// Makes room for `particles` particles in the list up front and keeps
+    /// it, so the population can swing up to that many without the list
+    /// being grown or shrunk.
+    pub fn reserve(&mut self, particles: usize) {
+        self.reserved = particles;
+        self.particles.reserve(particles.saturating_sub(self.particles.len()));
+    }
+
+    /// Sets when the spare room of the particle list is handed back, making
+    /// the room of `StoragePolicy::Prealloc` at once.
+    pub fn set_storage_policy(&mut self, policy: StoragePolicy) {
+        if let StoragePolicy::Prealloc(particles) = policy {
+            self.reserve(particles);
+        }
+        self.storage_policy = policy;
+    }
+
+    pub fn storage_policy(&self) -> StoragePolicy {
+        self.storage_policy
+    }
+
+    /// Adds an emitter to the scene, like a fountain dropped with the mouse.
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    fn ambient_tint(&self, color: [f32; 4]) -> [f32; 4] {
+        match &self.ambient {
+            Some(ambient) => ambient.tint(color, self.current_turn),
+            None => color,
+        }
+    }
+
+    /// Makes the world the size of a resized window. Emitters, their paths
+    /// and the custom lines particles spawn on or die past keep their place
+    /// relative to the window, and particles the edges keep in are brought
+    /// back into it. Sizes of 0, like that of a minimized window, are ignored.
+    pub fn resize(&mut self, width: f64, height: f64) {
+        if width <= 0.0 || height <= 0.0 || (width == self.width && height == self.height) {
+            return;
+        }
+        let scale = [width / self.width, height / self.height];
+        for emitter in &mut self.emitters {
+            emitter.rescale(scale);
+        }
+        self.spawn_edge.rescale(scale[1]);
+        if let Some(kill_plane) = &mut self.kill_plane {
+            kill_plane.rescale(scale[1]);
+        }
+        self.width = width;
+        self.height = height;
+        let (edges, bounds) = (self.edges, [width, height]);
+        for particle in &mut self.particles {
+            edges.confine(&mut particle.position, &mut particle.velocity, [particle.width, particle.height], bounds);
+        }
+    }
+
+    /// What the camera shows of the window on `turn`, the whole of it when
+    /// the scene has no camera keys.
+    pub fn camera_view(&self, turn: u64) -> Option<CameraView> {
+        self.camera.view(turn, [self.width, self.height])
+    }
+
+    /// Background color of the window on `turn`.
+    pub fn background(&self, turn: u64) -> [f32; 4] {
+        self.ambient.as_ref().map_or(self.background, |ambient| ambient.background(turn))
+    }
+
+    pub fn add_shapes(&mut self, n: i32) {
+        let count = n.abs();
+        for i in 0..count {
+
+            // Create a particle as local variable in the Stack (memory)
+            let mut particle = self.spawn_particle();
+
+            // The i-th of the turn's particles was spawned i/n of a turn
+            // earlier than the last one and has moved on by as much
+            if self.spawn_stagger {
+                particle.advance(i as f64 / count as f64 * self.time_step);
+            }
+
+            self.push_particle(particle);
+        }
+    }
+
+    fn push_particle(&mut self, particle: Particle) {
+        // Move the particle to the heap and create a reference to it
+        // in the Stack, in a recycled box when there is a pool
+        let boxed_particle = match &mut self.particle_pool {
+            Some(pool) => pool.take(particle),
+            None => Box::new(particle),
+        };
+        if self.particles.len() == self.particles.capacity() {
+            tagged(AllocationTag::VecGrow, || self.particles.push(boxed_particle));
+        } else {
+            self.particles.push(boxed_particle);
+        }
+        self.total_spawned += 1;
+    }
+
+    // Removes the oldest particles, all at once
+    fn remove_shapes(&mut self, n: i32) {
+        let count = (n.unsigned_abs() as usize).min(self.particles.len());
+        if let Some(survival) = &mut self.survival {
+            self.particles[..count].iter().for_each(|p| survival.record(p.age));
+        }
+        let removed = self.particles.drain(..count);
+        match &mut self.particle_pool {
+            Some(pool) => drops::timed(DropSite::Remove, count, || pool.recycle(removed)),
+            None => drop(TimedDrop::new(DropSite::Remove, count, removed)),
+        }
+        self.total_died += count as u64;
+    }
+
+    /// Removes every particle and spark, returning how many particles there
+    /// were. Their capacity is kept for the next ones.
+    pub fn clear(&mut self) -> usize {
+        let cleared = self.particles.len();
+        drops::timed(DropSite::Clear, cleared, || {
+            remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), |_| true)
+        });
+        self.sparks.clear();
+        self.total_died += cleared as u64;
+        cleared
+    }
+
+    /// Removes every particle fainter than `MIN_VISIBLE_ALPHA`, entirely
+    /// outside the window unless the edges keep them, or at the end of its
+    /// life, in a single pass keeping the others in order, and returns how
+    /// many were removed. When the population policy respawns them, each is
+    /// replaced in its box by a new particle instead, and they are counted
+    /// all the same.
+    pub fn cull(&mut self) -> usize {
+        let (width, height) = (self.width, self.height);
+        let kills = self.edges.kills();
+        let alive = |p: &Particle| {
+            p.color[3] >= MIN_VISIBLE_ALPHA && (!kills || p.on_screen(width, height)) && p.age < p.max_age
+        };
+        if self.population.respawn {
+            let mut respawned = 0;
+            for index in 0..self.particles.len() {
+                if !alive(&self.particles[index]) {
+                    if let Some(survival) = &mut self.survival {
+                        survival.record(self.particles[index].age);
+                    }
+                    *self.particles[index] = self.spawn_particle();
+                    respawned += 1;
+                }
+            }
+            self.total_died += respawned as u64;
+            self.total_spawned += respawned as u64;
+            return respawned;
+        }
+
+        let culled = remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), |p| !alive(p));
+        self.total_died += culled as u64;
+        culled
+    }
+
+    // Adds the center of every particle to its trail, giving a trail to
+    // those spawned since the last turn
+    fn record_trails(&mut self) {
+        let Some(settings) = self.trail else {
+            return;
+        };
+        let stats = &mut self.trail_stats;
+        tagged(AllocationTag::Trail, || {
+            for particle in &mut self.particles {
+                let center = [particle.position[0] + particle.width / 2.0, particle.position[1] + particle.height / 2.0];
+                let trail = particle.trail.get_or_insert_with(|| {
+                    let trail = Trail::new(&settings);
+                    stats.trails += 1;
+                    stats.allocations += (trail.capacity_bytes() > 0) as u64;
+                    trail
+                });
+                stats.allocations += trail.push(center) as u64;
+            }
+        });
+    }
+
+    /// Bytes of positions the trails of the particles have room for.
+    pub fn trail_bytes(&self) -> usize {
+        self.particles.iter().filter_map(|particle| particle.trail.as_ref()).map(Trail::capacity_bytes).sum()
+    }
+
+    // Gives every particle the same chance of dying early when the
+    // population is over the soft cap
+    fn cull_over_soft_cap(&mut self) {
+        let Some(cap) = &self.soft_cap else {
+            return;
+        };
+        let probability = cap.cull_probability(self.particles.len());
+        if probability <= 0.0 {
+            return;
+        }
+
+        let rng = &mut self.rng;
+        self.total_died += remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), |_| rng.gen_bool(probability)) as u64;
+    }
+
+    /// The quadtree of `query_radius` and `nearest_particle`, over the
+    /// centers of the particles after the last turn, by index in
+    /// `particles`, and as stale as they are between turns.
+    pub fn particle_index(&self) -> &QuadTree {
+        &self.index
+    }
+
+    /// The particles whose centers are at most `radius` from `center`, in no
+    /// particular order, through the quadtree rebuilt every turn once the
+    /// particles have moved, so it allocates nothing. Particles spawned or
+    /// pushed since are left out until the next turn, like those pushed
+    /// into the circle by collisions, obstacles or the cursor; those found
+    /// are always within `radius`. The quadtree knows the particles by their
+    /// place in `particles`, so after `clear`, `burst_at` or any removal
+    /// between turns it finds a stale subset of them, those that moved into
+    /// the places of others included, until the next `update`.
+    pub fn query_radius(&self, center: Vec2d<f64>, radius: f64) -> impl Iterator<Item = &Particle> + '_ {
+        let radius_squared = radius * radius;
+        self.index.near(center, radius).filter_map(|index| self.particles.get(index)).map(|p| &**p).filter(move |p| {
+            let offset = sub(behavior::center(p), center);
+            offset[0] * offset[0] + offset[1] * offset[1] <= radius_squared
+        })
+    }
+
+    /// Index in `particles` of the particle whose center is closest to
+    /// `point`, through the quadtree of `query_radius` and as stale as its
+    /// results between turns; `None` when there are no particles.
+    pub fn nearest_particle(&self, point: Vec2d<f64>) -> Option<usize> {
+        self.index.nearest(point).filter(|&index| index < self.particles.len())
+    }
+
+    /// Kinetic energy and momentum of the particles, sparks left out.
+    pub fn energy(&self) -> Energy {
+        Energy::of(self.particles.iter().map(|p| &**p))
+    }
+
+    /// Hash of the turn and of every particle and spark, equal at the same
+    /// turn of runs with the same seed and scene. See `--hashes`.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write_u64(self.current_turn);
+        for particles in [&self.particles, &self.sparks] {
+            hasher.write_u64(particles.len() as u64);
+            particles.iter().for_each(|particle| particle.hash(&mut hasher));
+        }
+        hasher.finish()
+    }
+
+    /// The state to carry over to a world built anew from the same scene,
+    /// with `restore_state`. Particles gone NaN or infinite are left out,
+    /// since they would only be removed on the next turn.
+    pub fn save_state(&mut self) -> WorldState {
+        let finite = |particle: &&Particle| particle.position.iter().chain(&particle.velocity).all(|value| value.is_finite());
+        WorldState {
+            turn: self.current_turn,
+            total_spawned: self.total_spawned,
+            total_died: self.total_died,
+            seed: self.rng.clone().gen(),
+            params: ParamRegistry::collect(self).params.iter().map(|param| format!("{}={}", param.name, param.current())).collect(),
+            disabled_forces: self.disabled_forces.iter().map(|kind| kind.to_string()).collect(),
+            particles: self.particles.iter().map(|particle| &**particle).filter(finite).cloned().collect(),
+            sparks: self.sparks.iter().map(|particle| &**particle).filter(finite).cloned().collect(),
+        }
+    }
+
+    /// Carries on from `state`, saved by a world of the same scene; fails
+    /// when a parameter it sets isn't one of this world.
+    pub fn restore_state(&mut self, state: WorldState) -> Result<(), String> {
+        for assignment in &state.params {
+            ParamRegistry::assign(self, assignment)?;
+        }
+        self.disabled_forces = state
+            .disabled_forces
+            .iter()
+            .filter_map(|name| self.forces.iter().map(Force::kind).find(|kind| kind == name))
+            .collect();
+        self.current_turn = state.turn;
+        self.total_spawned = state.total_spawned;
+        self.total_died = state.total_died;
+        self.rng = StdRng::seed_from_u64(state.seed);
+        drop(TimedDrop::new(DropSite::Restore, self.particles.len(), self.particles.drain(..)));
+        self.particles.extend(state.particles.into_iter().map(Box::new));
+        self.sparks.clear();
+        self.sparks.extend(state.sparks.into_iter().map(Box::new));
+        Ok(())
+    }
+
+    /// Saves the scene, the size and the state of the world to `path` in a
+    /// compact binary format, for `load` to carry on from.
+    pub fn save(&mut self, path: &Path) -> io::Result<()> {
+        let checkpoint = Checkpoint {
+            scene_text: self.scene_text.clone(),
+            width: self.width,
+            height: self.height,
+            world: self.save_state(),
+        };
+        checkpoint.save(path)
+    }
+
+    /// The world saved to `path` by `save`, built again from its scene and
+    /// carried on from where it was.
+    pub fn load(path: &Path) -> io::Result<World> {
+        let checkpoint = Checkpoint::load(path)?;
+        let mut world = World::from_config(checkpoint.width, checkpoint.height, checkpoint.config()?);
+        world.restore_state(checkpoint.world).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(world)
+    }
+
+    /// Whether the live heap is close enough to `max_heap_bytes` to stop spawning.
+    pub fn under_memory_pressure(&self) -> bool {
+        match self.max_heap_bytes {
+            Some(max) => memory_stats().live_bytes as f64 >= max as f64 * MEMORY_PRESSURE_THRESHOLD,
+            None => false,
+        }
+    }
+
+    fn collide_with_obstacles(&mut self) {
+        if self.obstacles.is_empty() {
+            return;
+        }
+        if self.parallel {
+            let (obstacles, dt) = (&self.obstacles, self.time_step);
+            let collisions = parallel::collect(&mut self.particles, |particle, events| particle.collide(obstacles, dt, events));
+            self.events.extend(collisions);
+            return;
+        }
+
+        for particle in &mut self.particles {
+            particle.collide(&self.obstacles, self.time_step, &mut self.events);
+        }
+    }
+
+    /// Makes particles bounce off each other from the next turn on, with the
+    /// restitution of the scene's `[collisions]` or 0.8, or stops it.
+    pub fn set_collisions(&mut self, enabled: bool) {
+        if !enabled {
+            self.collisions = None;
+        } else if self.collisions.is_none() {
+            self.collisions = Some(Collisions::default());
+        }
+    }
+
+    /// Where the boxes of new particles come from.
+    pub fn allocation_strategy(&self) -> AllocationStrategy {
+        if arena::is_enabled() {
+            AllocationStrategy::Arena
+        } else if self.particle_pool.is_some() {
+            AllocationStrategy::Pooled
+        } else {
+            AllocationStrategy::Boxed
+        }
+    }
+
+    /// Moves every particle to a box of `strategy` and spawns from it from
+    /// then on, in the middle of a run, so the allocation traces show both.
+    /// A pool is filled with a box per particle, which the particles move
+    /// to; the arena, which serves every particle-sized allocation of the
+    /// program, is turned on or off. Boxes left behind go back to wherever
+    /// they came from. Returns how many particles were moved.
+    pub fn set_allocation_strategy(&mut self, strategy: AllocationStrategy) -> usize {
+        if strategy == AllocationStrategy::Arena {
+            arena::enable();
+        } else {
+            arena::disable();
+        }
+        self.particle_pool = (strategy == AllocationStrategy::Pooled).then(|| ParticlePool::with_capacity(self.particles.len()));
+        let pool = &mut self.particle_pool;
+        self.particles = std::mem::take(&mut self.particles)
+            .into_iter()
+            .map(|boxed| match pool {
+                Some(pool) => pool.take(*boxed),
+                None => Box::new(*boxed),
+            })
+            .collect();
+        self.particles.len()
+    }
+
+    /// Pairs of particles that touched on the last turn.
+    pub fn contacts(&self) -> usize {
+        self.contacts
+    }
+
+    // Bounces the particles off each other: the quadtree finds the pairs
+    // closer than the largest particle is wide, so any two that touch are,
+    // then each pair is checked and bounced in turn, a collision between
+    // their centers for the sparks
+    fn collide_particles(&mut self) {
+        self.contacts = 0;
+        let Some(collisions) = self.collisions else {
+            return;
+        };
+        let reach = self.particles.iter().map(|p| p.width.max(p.height)).fold(0.0, f64::max);
+        if reach <= 0.0 {
+            return;
+        }
+
+        self.pairs.clear();
+        for (i, particle) in self.particles.iter().enumerate() {
+            let later = self.index.near(center(particle), reach).filter(|&j| j > i);
+            self.pairs.extend(later.map(|j| (i as u32, j as u32)));
+        }
+        for &(i, j) in &self.pairs {
+            let (head, tail) = self.particles.split_at_mut(j as usize);
+            if head[i as usize].bounce_off(&mut tail[0], collisions.restitution) {
+                self.contacts += 1;
+                let (a, b) = (center(&head[i as usize]), center(&tail[0]));
+                self.events.push(WorldEvent::Collision { position: [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0] });
+            }
+        }
+    }
+
+    // Pushes the particles under the cursor to the edge of its circle. Pinned
+    // particles stay where they are
+    fn flow_around_cursor(&mut self) {
+        let Some(cursor) = &self.cursor_obstacle else {
+            return;
+        };
+        let deflect = |particle: &mut Box<Particle>| {
+            if !particle.pinned {
+                cursor.deflect(&mut particle.position, &mut particle.velocity);
+            }
+        };
+        if self.parallel {
+            self.particles.par_iter_mut().for_each(deflect);
+        } else {
+            self.particles.iter_mut().for_each(deflect);
+        }
+    }
+
+    // Takes out the rockets whose fuse burnt out, then spawns their bursts,
+    // which may be rockets of the next stage. Bursts are throttled like the
+    // spawns of emitters, and skipped under memory pressure
+    fn detonate_rockets(&mut self) {
+        let detonations = &mut self.detonations;
+        let burst = |p: &Particle| match p.rocket {
+            Some(rocket) if p.age >= rocket.fuse => {
+                detonations.push((center(p), p.velocity, rocket));
+                true
+            }
+            _ => false,
+        };
+        self.total_died += remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), burst) as u64;
+
+        let pressure = self.under_memory_pressure();
+        for index in 0..self.detonations.len() {
+            let (position, velocity, rocket) = self.detonations[index];
+            let Some(stage) = firework_stage(&self.emitters, rocket) else {
+                continue;
+            };
+            let count = if pressure { 0 } else { self.throttled(stage.count as usize) };
+            let offset = self.rng.gen_range(0.0..TAU);
+            for i in 0..count {
+                // Looked up for every particle, pushing the one before borrowing the whole world
+                let Some(stage) = firework_stage(&self.emitters, rocket) else {
+                    break;
+                };
+                let angle = offset + TAU * i as f64 / count as f64;
+                let color = stage.color.sample(&mut self.rng);
+                let color = self.ambient_tint(color);
+                let corner = [position[0] - stage.particle.size / 2.0, position[1] - stage.particle.size / 2.0];
+                let mut particle = Particle::new(corner, [angle.cos(), angle.sin()], color, &stage.particle, &mut self.rng);
+                particle.velocity = [0, 1].map(|axis| particle.velocity[axis] + velocity[axis] * stage.inherit);
+                if let Some(fade) = &stage.fade {
+                    particle.fade = fade.start(color[3]);
+                }
+                particle.max_speed = self.max_speed;
+                particle.blend = stage.blend;
+                particle.max_age = self.population.max_age(&mut self.rng);
+                particle.born = self.current_turn;
+                if let Some(next) = &stage.then {
+                    let fuse = sample_fuse(next.fuse, &mut self.rng);
+                    particle.rocket = Some(Rocket { stage: rocket.stage.saturating_add(1), fuse, ..rocket });
+                }
+                self.behavior.start(&mut particle, &mut self.rng);
+                self.push_particle(particle);
+            }
+        }
+        self.detonations.clear();
+    }
+
+    // Lets sub-emitters spawn their bursts for this turn's events
+    fn react_to_events(&mut self) {
+        let Some(emitter) = &self.sparks_emitter else {
+            return;
+        };
+
+        for event in &self.events {
+            match event {
+                WorldEvent::Collision { position } => {
+                    for _ in 0..emitter.count {
+                        let mut spark = Particle::burst(*position, emitter, &mut self.rng);
+                        spark.max_speed = self.max_speed;
+                        spark.born = self.current_turn;
+                        self.sparks.push(Box::new(spark));
+                    }
+                }
+            }
+        }
+    }
+
+    // Moves the particles at the given indexes, in increasing order, to the quarantine
+    fn quarantine_faulty(&mut self, faults: Vec<(usize, Fault)>) {
+        let mut faulty = Vec::with_capacity(faults.len());
+        let mut faults = faults.into_iter().peekable();
+        let mut index = 0;
+        remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), |particle| {
+            let dead = match faults.next_if(|(faulty, _)| *faulty == index) {
+                Some((_, fault)) => {
+                    faulty.push((particle.clone(), fault));
+                    true
+                }
+                None => false,
+            };
+            index += 1;
+            dead
+        });
+        self.total_died += faulty.len() as u64;
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.admit(self.current_turn, faulty);
+        }
+    }
+
+    pub fn update(&mut self) {
+        let replayed = self.replay.as_mut().and_then(JournalReplay::next_turn);
+        for event in replayed.iter().flat_map(|turn| &turn.events) {
+            match *event {
+                JournalEvent::Burst(particles) => self.add_shapes(particles),
+                JournalEvent::Clear => {
+                    self.clear();
+                }
+            }
+        }
+
+        if let Some(run) = &mut self.scenario {
+            run.turn(self.current_turn);
+            if run.clears_at(self.particles.len()) {
+                run.clears += 1;
+                self.clear();
+            }
+        }
+
+        let mut n = match (&self.controller, &mut self.allocation_gauge) {
+            (Some(controller), _) => controller.decide(self.current_turn, self.particles.len(), memory_stats().live_bytes),
+            (None, Some(gauge)) => gauge.spawns(memory_stats().allocated_bytes) as i32,
+
+            // Generate a random number between -spawn_rate and spawn_rate inclusive
+            (None, None) => self.rng.gen_range(-self.spawn_rate..=self.spawn_rate),
+        };
+
+        // Cooperate with the memory budget: never spawn and cull twice as
+        // many particles as the worst regular turn
+        if self.under_memory_pressure() {
+            n = -2 * self.spawn_rate.max(1);
+        }
+
+        if n > 0 {
+            n = self.throttled(n as usize) as i32;
+        }
+
+        // The random number is drawn all the same, for the turns replayed
+        // to draw the same numbers as those recorded
+        if let Some(turn) = &replayed {
+            n = turn.decision;
+        }
+        if let Some(journal) = &self.journal {
+            journal.write_turn(n);
+        }
+
+        if n > 0 {
+            self.add_shapes(n);
+        } else {
+            self.remove_shapes(n);
+        }
+        if !self.under_memory_pressure() {
+            self.spawn_from_rated();
+        }
+        self.cull_over_soft_cap();
+
+        #[allow(deprecated)]
+        let shrinks = self.shrink && self.storage_policy.shrinks_on(self.current_turn);
+        if shrinks && self.particles.capacity() > self.particles.len().max(self.reserved) {
+            tagged(AllocationTag::VecShrink, || self.particles.shrink_to(self.reserved));
+        }
+
+        let guard = self.quarantine.is_some();
+        let field = ForceField {
+            forces: &self.forces,
+            disabled: &self.disabled_forces,
+            cursor: self.cursor_force.as_ref(),
+            turn: self.current_turn,
+        };
+
+        // The neighbors of behaviors borrow the grid until the moves
+        let mut view = WorldView::new(self.width, self.height, self.current_turn);
+        let radius = self.behaviors.iter().filter_map(|behavior| behavior.neighbor_radius()).fold(0.0, f64::max);
+        if radius > 0.0 {
+            self.neighbors.clear();
+            self.neighbors.extend(self.particles.iter().map(|particle| Neighbor::of(particle)));
+            self.grid.build(radius, self.neighbors.iter().map(|neighbor| neighbor.center));
+            view = view.with_neighbors(&self.grid, &self.neighbors);
+        }
+        let behaviors = &self.behaviors[..];
+        let faults = if self.parallel {
+            let (zones, drag, jitter, behavior, dt) = (&self.zones, self.drag, self.jitter, &self.behavior, self.time_step);
+            let integrator = self.integrator;
+            let turn_seed = self.rng.gen();
+            parallel::for_each(&mut self.particles, turn_seed, |shape, rng| {
+                shape.step(zones, field, integrator, drag, jitter, behavior, behaviors, &view, dt, guard, rng)
+            })
+        } else {
+            let mut faults = Vec::new();
+            for (index, shape) in self.particles.iter_mut().enumerate() {
+                let (drag, jitter, dt) = (self.drag, self.jitter, self.time_step);
+                let (state_machine, integrator) = (&self.behavior, self.integrator);
+                let moved = shape.step(&self.zones, field, integrator, drag, jitter, state_machine, behaviors, &view, dt, guard, &mut self.rng);
+                if let Some(fault) = moved {
+                    faults.push((index, fault));
+                }
+            }
+            faults
+        };
+        if !faults.is_empty() {
+            self.quarantine_faulty(faults);
+        }
+        if let Some(palette) = &self.palette {
+            for particle in &mut self.particles {
+                palette.paint(particle);
+            }
+        }
+
+        if !self.behavior.is_empty() {
+            self.total_died += remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), |p| p.expired) as u64;
+        }
+        if self.emitters.iter().any(|emitter| emitter.firework.is_some()) {
+            self.detonate_rockets();
+        }
+
+        if !self.edges.kills() {
+            let (edges, bounds) = (self.edges, [self.width, self.height]);
+            for particle in self.particles.iter_mut().filter(|p| !p.pinned) {
+                edges.apply(&mut particle.position, &mut particle.velocity, [particle.width, particle.height], bounds);
+            }
+        }
+
+        if let Some(plane) = self.kill_plane {
+            let (width, height) = (self.width, self.height);
+            let past = |p: &Particle| plane.is_past(p.position, p.velocity, width, height);
+            self.total_died += remove_where(&mut self.particles, self.particle_pool.as_mut(), self.survival.as_mut(), past) as u64;
+        }
+        self.cull();
+
+        self.index.rebuild(self.particles.iter().map(|p| center(p)));
+        self.events.clear();
+        self.collide_particles();
+        self.collide_with_obstacles();
+        self.flow_around_cursor();
+        self.react_to_events();
+        self.record_trails();
+
+        for spark in &mut self.sparks {
+            spark.update(self.time_step);
+        }
+        self.sparks.retain(|spark| spark.color[3] >= MIN_VISIBLE_ALPHA);
+
+        if let Some(gauge) = &mut self.allocation_gauge {
+            gauge.skip_to(memory_stats().allocated_bytes);
+        }
+        self.current_turn += 1;
+    }
+}
+
+impl Tunable for World {
+    // Speed limits are given to particles at spawn, so changing them only
+    // affects the particles spawned afterwards
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("drag", &mut self.drag, 0.0..=1.0);
+        visitor.float("jitter", &mut self.jitter, 0.0..=100.0);
+        visitor.float("max_speed", &mut self.max_speed, 0.0..=f64::INFINITY);
+        params::float32(visitor, "background_alpha", &mut self.background[3], 0.0..=1.0);
+
+        // Negative rates cull at random, which no scene may set
+        let mut spawn_rate = self.spawn_rate.max(0) as u64;
+        visitor.integer("spawn_rate", &mut spawn_rate, 0..=100_000);
+        if spawn_rate != self.spawn_rate.max(0) as u64 {
+            self.spawn_rate = spawn_rate as i32;
+        }
+        self.particle_settings.visit_params(&mut Prefixed::new(visitor, "particle".to_string()));
+        for (i, emitter) in self.emitters.iter_mut().enumerate() {
+            emitter.visit_params(&mut Prefixed::new(visitor, format!("emitter.{}", i)));
+        }
+        for (i, zone) in self.zones.iter_mut().enumerate() {
+            zone.force.visit_params(&mut Prefixed::new(visitor, format!("zone.{}", i)));
+        }
+        for (i, force) in self.forces.iter_mut().enumerate() {
+            force.visit_params(&mut Prefixed::new(visitor, format!("force.{}", i)));
+        }
+        if let Some(cap) = &mut self.soft_cap {
+            cap.visit_params(&mut Prefixed::new(visitor, "soft_cap".to_string()));
+        }
+        if let Some(controller) = &mut self.controller {
+            controller.visit_params(&mut Prefixed::new(visitor, "controller".to_string()));
+        }
+        if let Some(sparks) = &mut self.sparks_emitter {
+            sparks.visit_params(&mut Prefixed::new(visitor, "sparks".to_string()));
+        }
+        if let Some(ambient) = &mut self.ambient {
+            ambient.visit_params(&mut Prefixed::new(visitor, "ambient".to_string()));
+        }
+        if let Some(falloff) = &mut self.falloff {
+            falloff.visit_params(&mut Prefixed::new(visitor, "falloff".to_string()));
+        }
+        if let Some(collisions) = &mut self.collisions {
+            collisions.visit_params(&mut Prefixed::new(visitor, "collisions".to_string()));
+        }
+        if let Some(palette) = &mut self.palette {
+            palette.visit_params(&mut Prefixed::new(visitor, "palette".to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particle_at(position: Vec2d<f64>, alpha: f32) -> Particle {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut particle = Particle::new(position, [0.0, -1.0], [1.0, 1.0, 1.0, alpha], &ParticleSettings::default(), &mut rng);
+        particle.user_data[0] = position[0] as u8;
+        particle
+    }
+
+    fn world_with(particles: Vec<Particle>) -> World {
+        let mut world = World::new(100.0, 100.0);
+        world.particles = particles.into_iter().map(Box::new).collect();
+        world
+    }
+
+    fn tags(world: &World) -> Vec<u8> {
+        world.particles.iter().map(|p| p.user_data[0]).collect()
+    }
+
+    #[test]
+    fn cull_kills_at_max_age_or_respawns_in_the_same_box() {
+        let mut particles: Vec<Particle> = (1..=3).map(|i| particle_at([i as f64 * 10.0, 50.0], 1.0)).collect();
+        particles[1].age = 5;
+        particles[1].max_age = 5;
+        let mut world = world_with(particles.clone());
+        assert_eq!(world.cull(), 1);
+        assert_eq!(tags(&world), vec![10, 30]);
+
+        let mut world = world_with(particles);
+        world.population.respawn = true;
+        let boxed: *const Particle = &*world.particles[1];
+        assert_eq!(world.cull(), 1);
+        assert_eq!(world.particles.len(), 3);
+        assert_eq!((world.particles[1].age, world.particles[1].user_data[0]), (0, 0));
+        assert!(std::ptr::eq(boxed, &*world.particles[1]));
+        assert_eq!((world.total_died, world.total_spawned), (1, 1));
+    }
+
+    #[test]
+    fn mutations_stay_in_range_and_undo_back_to_the_scene() {
+        let config: Config = toml::from_str("drag = 0.1\njitter = 0.5\n[[force]]\nkind = \"gravity\"\nacceleration = [0, 0.05]").unwrap();
+        let mut world = World::from_config(100.0, 100.0, config);
+        let before = params::ParamRegistry::collect(&mut world).params;
+        let mut mutations = mutate::Mutations::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            mutations.mutate(&mut world, &mut rng);
+        }
+        for param in params::ParamRegistry::collect(&mut world).params {
+            if let params::ParamValue::Float { value, min, max } = param.value {
+                assert!((min..=max).contains(&value), "{} = {}", param.name, value);
+            }
+        }
+        assert!(!mutations.assignments(&mut world).is_empty());
+        while mutations.undo(&mut world).is_some() {}
+        assert_eq!(params::ParamRegistry::collect(&mut world).params, before);
+        assert!(mutations.assignments(&mut world).is_empty());
+    }
+
+    #[test]
+    fn spawn_rate_and_particle_settings_are_tunable() {
+        let mut world = World::from_config(100.0, 100.0, toml::from_str("spawn_rate = 5").unwrap());
+        for assignment in ["spawn_rate=40", "particle.size=7.5", "particle.fade=0.5"] {
+            params::ParamRegistry::assign(&mut world, assignment).unwrap();
+        }
+        assert!(params::ParamRegistry::assign(&mut world, "particle.fade=1.5").is_err());
+        assert_eq!(world.spawn_rate, 40);
+        let particle = world.spawn_particle();
+        assert_eq!((particle.width, particle.fade), (7.5, Fade::Multiply(0.5)));
+    }
+
+    #[test]
+    fn spatial_hash_finds_every_close_point_once() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let points: Vec<Vec2d<f64>> = (0..300).map(|_| [rng.gen_range(-50.0..150.0), rng.gen_range(-50.0..150.0)]).collect();
+        let mut grid = SpatialHash::new();
+        grid.build(8.0, points.iter().copied());
+
+        for (i, &point) in points.iter().enumerate() {
+            let mut near: Vec<u32> = grid.near(point).collect();
+            let found = near.len();
+            near.sort();
+            near.dedup();
+            assert_eq!(near.len(), found);
+            for (j, other) in points.iter().enumerate() {
+                let distance = (point[0] - other[0]).hypot(point[1] - other[1]);
+                if distance < 8.0 {
+                    assert!(near.binary_search(&(j as u32)).is_ok(), "{} and {} are {} apart", i, j, distance);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn storage_policies_shrink_the_list_on_their_turns_only() {
+        use vec_policy::StoragePolicy;
+
+        for text in ["prealloc:5000", "shrink-every:60", "never-shrink"] {
+            assert_eq!(text.parse::<StoragePolicy>().unwrap().to_string(), text);
+        }
+        assert!("shrink-every:0".parse::<StoragePolicy>().is_err());
+
+        let mut world = world_with((0..64).map(|i| particle_at([i as f64, 50.0], 1.0)).collect());
+        world.spawn_rate = 0;
+        world.set_storage_policy(StoragePolicy::ShrinkEvery(4));
+        world.particles.truncate(8);
+        world.current_turn = 1;
+        world.update();
+        assert!(world.particles.capacity() >= 64);
+        world.current_turn = 4;
+        world.update();
+        assert_eq!(world.particles.capacity(), world.particles.len());
+
+        world.set_storage_policy(StoragePolicy::Prealloc(32));
+        assert!(world.particles.capacity() >= 32);
+        world.update();
+        assert!(world.particles.capacity() >= 32);
+    }
+
+    #[test]
+    fn query_radius_finds_what_a_scan_of_every_particle_does() {
+        let mut world = World::new(200.0, 200.0);
+        world.rng = StdRng::seed_from_u64(4);
+        world.spawn_rate = 0;
+        world.add_shapes(400);
+        world.update();
+
+        let point = center(&world.particles[0]);
+        let mut found: Vec<*const Particle> = world.query_radius(point, 30.0).map(|p| p as *const Particle).collect();
+        let mut scanned: Vec<*const Particle> = world
+            .particles
+            .iter()
+            .filter(|p| (center(p)[0] - point[0]).hypot(center(p)[1] - point[1]) <= 30.0)
+            .map(|p| &**p as *const Particle)
+            .collect();
+        found.sort();
+        scanned.sort();
+        assert!(found.len() > 1);
+        assert_eq!(found, scanned);
+        assert_eq!(world.query_radius([1e6, 1e6], 30.0).count(), 0);
+
+        let nearest = world.nearest_particle(point).unwrap();
+        assert_eq!(center(&world.particles[nearest]), point);
+        world.clear();
+        assert_eq!(world.nearest_particle(point), None);
+    }
+
+    #[test]
+    fn saved_state_carries_on_in_a_world_of_the_same_scene() {
+        let scene = "drag = 0.1\nmax_speed = 3\n[[force]]\nkind = \"gravity\"\nacceleration = [0, 0.05]";
+        let mut world = World::from_config(300.0, 300.0, toml::from_str(scene).unwrap());
+        world.rng = StdRng::seed_from_u64(2);
+        world.add_shapes(50);
+        for _ in 0..20 {
+            world.update();
+        }
+        params::ParamRegistry::assign(&mut world, "drag=0.25").unwrap();
+        world.toggle_forces("gravity");
+        let json = serde_json::to_string(&world.save_state()).unwrap();
+
+        let mut resumed = World::from_config(300.0, 300.0, toml::from_str(scene).unwrap());
+        resumed.restore_state(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(resumed.state_hash(), world.state_hash());
+        assert_eq!((resumed.total_spawned, resumed.drag, resumed.disabled_forces.clone()), (world.total_spawned, 0.25, vec!["gravity"]));
+        world.rng = resumed.rng.clone();
+        world.update();
+        resumed.update();
+        assert_eq!(resumed.state_hash(), world.state_hash());
+    }
+
+    #[test]
+    fn clear_removes_every_particle_and_counts_them_dead() {
+        let mut world = world_with(vec![particle_at([10.0, 50.0], 0.5), particle_at([20.0, 50.0], 0.9)]);
+        let before = DropSite::Clear.stats();
+        assert_eq!(world.clear(), 2);
+        assert!(world.particles.is_empty());
+        assert_eq!(world.total_died, 2);
+
+        // Timed as one batch, and an empty world clears none
+        world.clear();
+        let cleared = DropSite::Clear.stats().since(&before);
+        assert_eq!((cleared.batches, cleared.objects), (1, 2));
+    }
+
+    #[test]
+    fn update_integrates_acceleration_into_velocity_then_velocity_into_position() {
+        let mut particle = particle_at([10.0, 20.0], 1.0);
+        particle.velocity = [1.0, 0.0];
+        particle.acceleration = [0.0, 2.0];
+        particle.max_speed = f64::INFINITY;
+        particle.update(0.5);
+        assert_eq!(particle.velocity, [1.0, 1.0]);
+        assert_eq!(particle.position, [10.5, 20.5]);
+        assert_eq!(particle.acceleration, [0.0, 2.0 * ACCELERATION_DECAY.powf(0.5)]);
+
+        // Pinned particles stay where they are, however they were pushed
+        particle.pinned = true;
+        particle.update(1.0);
+        assert_eq!(particle.position, [10.5, 20.5]);
+    }
+
+    #[test]
+    fn integrators_follow_analytic_trajectories_to_their_order() {
+        use integrator::Integrator::{self, Euler, Rk4, SemiImplicit, Verlet};
+
+        let run = |integrator: Integrator, turns: usize, dt: f64, acceleration: &dyn Fn(Vec2d<f64>) -> Vec2d<f64>| {
+            (0..turns).fold(([1.0, 0.0], [0.0, 1.0]), |(position, velocity), _| integrator.step(position, velocity, dt, acceleration))
+        };
+
+        // Falling: x = 1 + t, y = t - t² over 10 turns, exact for the orders above one
+        let gravity = |_: Vec2d<f64>| [0.0, -2.0];
+        for (integrator, error) in [(Euler, 10.0), (SemiImplicit, -10.0), (Verlet, 0.0), (Rk4, 0.0)] {
+            let (position, velocity) = run(integrator, 10, 1.0, &gravity);
+            assert!((position[1] - (10.0 - 100.0) - error).abs() < 1e-9, "{:?} fell to {:?}", integrator, position);
+            assert!((velocity[1] - (1.0 - 20.0)).abs() < 1e-9, "{:?} reached {:?}", integrator, velocity);
+        }
+
+        // A spring: a circle of radius 1, back to the start after 2π
+        let spring = |position: Vec2d<f64>| mul_scalar(position, -1.0);
+        let steps = 100;
+        let errors = [Euler, SemiImplicit, Verlet, Rk4].map(|integrator| {
+            let (position, _) = run(integrator, steps, TAU / steps as f64, &spring);
+            (position[0] - 1.0).hypot(position[1])
+        });
+        assert!(errors[0] > 0.1 && errors[1] < 0.1 && errors[2] < 0.01 && errors[3] < 1e-6, "{:?}", errors);
+        let (position, _) = run(Euler, steps, TAU / steps as f64, &spring);
+        assert!(position[0].hypot(position[1]) > 1.1, "Euler gains energy in orbits");
+
+        // Particles of worlds integrating with RK4 take the forces with the move
+        let scene = "integrator = \"rk4\"\nspawn_rate = 0\n[[force]]\nkind = \"gravity\"\nacceleration = [0, 0.5]";
+        let mut world = World::from_config(400.0, 400.0, Config::from_toml(scene).unwrap());
+        world.particles = Vec::new();
+        world.push_particle(particle_at([100.0, 100.0], 1.0));
+        world.particles[0].velocity = [1.0, 0.0];
+        world.particles[0].acceleration = [0.0, 0.0];
+        world.update();
+        assert_eq!(world.integrator, Rk4);
+        assert_eq!(world.particles[0].position, [101.0, 100.25]);
+    }
+
+    #[test]
+    fn trails_keep_the_last_positions_in_a_ring_allocated_once() {
+        use trail::{TrailSettings, TrailStorage};
+
+        let mut settings = TrailSettings { length: 4, storage: TrailStorage::Ring, width: 1.0 };
+        let mut trail = Trail::new(&settings);
+        let allocated: Vec<bool> = (0..6).map(|i| trail.push([i as f64, 0.0])).collect();
+        assert_eq!(allocated, [false; 6]);
+        assert_eq!(trail.points().map(|point| point[0]).collect::<Vec<_>>(), [2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(trail.capacity_bytes(), 4 * 16);
+
+        settings.storage = TrailStorage::Growing;
+        let mut trail = Trail::new(&settings);
+        assert_eq!((0..6).filter(|&i| trail.push([i as f64, 0.0])).count(), 1);
+        assert_eq!(trail.points().map(|point| point[0]).collect::<Vec<_>>(), [2.0, 3.0, 4.0, 5.0]);
+
+        let mut world = world_with((1..=3).map(|i| particle_at([i as f64 * 10.0, 50.0], 1.0)).collect());
+        world.spawn_rate = 0;
+        world.trail = Some(TrailSettings { length: 8, ..settings });
+        for _ in 0..20 {
+            world.update();
+        }
+        assert_eq!(world.trail_stats, TrailStats { trails: 3, allocations: 3 * 2 });
+        assert!(world.particles.iter().all(|particle| particle.trail.as_ref().is_some_and(|trail| trail.len() == 8)));
+        let newest = world.particles[0].trail.as_ref().and_then(|trail| trail.points().last()).unwrap();
+        assert_eq!(newest, [world.particles[0].position[0] + 2.0, world.particles[0].position[1] + 2.0]);
+    }
+
+    #[test]
+    fn snapshots_hand_the_latest_turn_over_in_reused_buffers() {
+        let mut world = world_with((0..3).map(|i| particle_at([i as f64, 0.0], 1.0)).collect());
+        world.spawn_rate = 0;
+        let (mut writer, mut reader): (handoff::SnapshotWriter, _) = handoff::handoff();
+        assert_eq!(reader.latest(), &handoff::WorldSnapshot::default());
+
+        writer.publish(&world);
+        world.update();
+        writer.publish(&world);
+        let latest = reader.latest();
+        assert_eq!((latest.turn, latest.sprites.len()), (1, 3));
+        assert_eq!(latest.sprites[0].position, world.particles[0].position);
+
+        // Nothing new, and the reader keeps its snapshot; after that the
+        // same three buffers go round
+        assert_eq!(reader.latest().turn, 1);
+        let mut buffers = Vec::new();
+        for turn in 2..10 {
+            world.update();
+            writer.publish(&world);
+            let latest = reader.latest();
+            assert_eq!(latest.turn, turn);
+            buffers.push(latest.sprites.as_ptr());
+        }
+        buffers.sort();
+        buffers.dedup();
+        assert!(buffers.len() <= 3, "{} buffers", buffers.len());
+    }
+
+    #[test]
+    fn snapshots_carry_the_trail_of_every_sprite_after_the_one_before() {
+        use trail::{TrailSettings, TrailStorage};
+
+        let mut world = world_with((1..=3).map(|i| particle_at([i as f64 * 10.0, 50.0], 1.0)).collect());
+        world.spawn_rate = 0;
+        world.trail = Some(TrailSettings { length: 4, storage: TrailStorage::Ring, width: 1.0 });
+        world.cursor_obstacle = Some(Obstacle::Circle { center: [5.0, 5.0], radius: 2.0 });
+        for _ in 0..6 {
+            world.update();
+        }
+        let mut snapshot = handoff::WorldSnapshot::default();
+        snapshot.capture(&world);
+        assert!(snapshot.sprites.iter().all(|sprite| sprite.trail == 4));
+        let second = world.particles[1].trail.as_ref().unwrap().points().collect::<Vec<_>>();
+        assert_eq!(snapshot.trails[4..8], second[..]);
+        assert_eq!(snapshot.cursor_obstacle, world.cursor_obstacle);
+    }
+
+    #[test]
+    fn run_summaries_keep_the_peak_and_mean_of_their_frames() {
+        use std::time::Duration;
+        use summary::RunSummary;
+
+        let mut summary = RunSummary::new();
+        assert_eq!(summary.mean_frame_time(), None);
+        for (millis, particles, turn) in [(10, 100, 1), (30, 400, 2), (20, 250, 3)] {
+            summary.frame(Duration::from_millis(millis), particles, turn);
+        }
+        assert_eq!((summary.frames(), summary.peak_particles()), (3, 400));
+        assert_eq!(summary.mean_frame_time(), Some(Duration::from_millis(20)));
+        let rows: Vec<String> = summary.rows(3).into_iter().map(|(_, value)| value).collect();
+        assert_eq!(&rows[1..4], ["3", "400 (turn 2)", "20.00 ms (slowest 30.00 ms)"]);
+    }
+
+    #[test]
+    fn call_sites_start_past_the_allocator_without_hashes() {
+        let backtrace = "   0: std::backtrace::Backtrace::force_capture::h0123456789abcdef
+   1: memory_particles::callsite::on_alloc
+             at ./src/callsite.rs:81:20
+   2: <memory_particles::allocator::ReportingAllocator as core::alloc::global::GlobalAlloc>::alloc
+   3: __rust_alloc
+   4: alloc::raw_vec::RawVec<T,A>::grow_one::hfedcba9876543210
+   5: memory_particles::World::push_particle
+   6: memory_particles::World::update
+   7: memory_particles::bench::run
+   8: main";
+        assert_eq!(
+            callsite::call_site(backtrace),
+            "alloc::raw_vec::RawVec<T,A>::grow_one < memory_particles::World::push_particle < memory_particles::World::update < memory_particles::bench::run"
+        );
+        assert_eq!(callsite::call_site("   0: __rust_alloc\n"), "unknown");
+    }
+
+    #[test]
+    fn world_meters_count_the_turns_of_their_own_world() {
+        use compare::WorldMeter;
+
+        let mut worlds = [World::new(100.0, 100.0), World::new(100.0, 100.0)];
+        worlds[1].particle_pool = Some(freelist::ParticlePool::with_capacity(16));
+        let mut meters = [WorldMeter::new(), WorldMeter::new()];
+        assert_eq!(meters[0].mean_update_time(), None);
+        for _ in 0..5 {
+            meters[0].update(&mut worlds[0]);
+        }
+        meters[1].update(&mut worlds[1]);
+        assert_eq!((meters[0].turns(), meters[1].turns()), (5, 1));
+        assert_eq!(worlds[0].current_turn, 5);
+        assert!(meters[0].mean_update_time().is_some());
+        assert_eq!(meters[0].rows(&worlds[0])[0].1, "5");
+        assert_eq!(meters[1].rows(&worlds[1]).len(), meters[0].rows(&worlds[0]).len() + 1);
+    }
+
+    #[test]
+    fn particles_bouncing_off_each_other_throw_sparks() {
+        let scene = "spawn_rate = 0\n[sparks]\ncount = 4";
+        let mut world = World::from_config(100.0, 100.0, toml::from_str(scene).unwrap());
+        world.set_collisions(true);
+        world.particles = vec![Box::new(particle_at([50.0, 50.0], 1.0)), Box::new(particle_at([50.5, 50.0], 1.0))];
+        world.update();
+        assert_eq!(world.contacts(), 1);
+        assert_eq!(world.sparks.len(), 4);
+    }
+
+    #[test]
+    fn fast_particles_bounce_off_obstacles_thinner_than_their_move() {
+        // Moved from y = 81 to 41 in one turn, clean over the obstacle
+        let mut particle = particle_at([50.0, 41.0], 1.0);
+        (particle.width, particle.height, particle.velocity) = (4.0, 4.0, [0.0, -40.0]);
+        let obstacles = [Obstacle::Rect { position: [0.0, 50.0], size: [100.0, 4.0] }];
+        let mut events = Vec::new();
+        particle.collide(&obstacles, 1.0, &mut events);
+        assert_eq!(events.len(), 1);
+        assert_eq!(particle.velocity, [0.0, 40.0]);
+        assert!(particle.position[1] > 54.0);
+    }
+
+    #[test]
+    fn validation_reports_problems_at_their_line() {
+        let problems = |scene: &str| -> Vec<String> { validate::validate(scene).iter().map(|problem| problem.to_string()).collect() };
+        let misspelt = problems("version = 3\nspawn_rate = 0\nmax_speeed = 3\n");
+        assert_eq!(misspelt.len(), 1);
+        assert!(misspelt[0].starts_with("line 3: error: unknown field `max_speeed`"));
+        assert_eq!(
+            problems("version = 3\nspawn_rate = 0\n\n[sparks]\ncount = 4\n"),
+            ["line 4: warning: sparks are thrown at collisions, and the scene has neither obstacles nor `[collisions]`"]
+        );
+        assert!(problems("version = 3\nspawn_rate = 0\n").is_empty());
+    }
+
+    #[test]
+    fn migration_upgrades_old_scenes_to_the_current_version() {
+        let scene = "# Old\nspawn_rate = 0\n\n[[emitter]]\nposition = [1, 2]\nbogus = 1\n\n[ambient.day]\nbackground = [0, 0, 0, 0.5]\n";
+        let migration = migrate::migrate(scene).unwrap();
+        assert_eq!(migration.from, 1);
+        let changed: Vec<String> = migration.changes.iter().map(|change| migrate::describe(&change.path)).collect();
+        assert_eq!(changed, ["emitter.0.bogus", "ambient.day.background.3"]);
+        assert_eq!(
+            migration.text,
+            "# Old\n\nversion = 3\n\nspawn_rate = 0\n\n[[emitter]]\nposition = [1, 2]\n\n[ambient.day]\nbackground = [0, 0, 0, 1.0]\n"
+        );
+        assert!(Config::from_toml(&migration.text).is_ok());
+        assert!(!migrate::migrate(&migration.text).unwrap().upgraded());
+    }
+
+    #[test]
+    fn hash_streams_diverge_at_the_first_turn_the_worlds_differ() {
+        use statehash::{Divergence, HashStream};
+        let mut streams = [HashStream::new(), HashStream::new()];
+        for (run, stream) in streams.iter_mut().enumerate() {
+            let mut world = World::from_config(100.0, 100.0, Config::from_toml("spawn_rate = 0").unwrap());
+            world.particles = vec![Box::new(particle_at([50.0, 50.0], 1.0))];
+            for turn in 1..=10 {
+                if run == 1 && turn == 6 {
+                    world.particles[0].position[0] += 1.0;
+                }
+                world.update();
+                stream.record(turn, world.state_hash());
+            }
+        }
+        let [a, mut b] = streams;
+        assert_eq!(a.first_difference(&a), None);
+        assert_eq!(a.first_difference(&b), Some(Divergence::Turn(6)));
+        b.hashes.truncate(5);
+        assert_eq!(a.first_difference(&b), Some(Divergence::Length { a: 10, b: 5 }));
+    }
+
+    #[test]
+    fn world_forces_accelerate_every_particle_until_switched_off() {
+        let worlds = || World::from_config(100.0, 100.0, Config::from_toml("spawn_rate = 0").unwrap());
+        let (mut still, mut pulled) = (worlds(), worlds());
+        for world in [&mut still, &mut pulled] {
+            world.particles = vec![Box::new(particle_at([50.0, 50.0], 1.0))];
+        }
+        pulled.add_force(Force::Gravity { acceleration: [0.0, 0.5] });
+        still.update();
+        pulled.update();
+        let gained = pulled.particles[0].velocity[1] - still.particles[0].velocity[1];
+        assert!((gained - 0.5).abs() < 1e-9);
+
+        assert!(!pulled.toggle_forces("gravity"));
+        assert_eq!(pulled.force_field().active().count(), 0);
+        still.update();
+        pulled.update();
+        assert!((pulled.particles[0].velocity[1] - still.particles[0].velocity[1] - gained).abs() < 1e-9);
+        assert!(pulled.toggle_forces("gravity"));
+    }
+
+    #[test]
+    fn the_cursor_pulls_pushes_and_bursts_particles() {
+        let mut world = World::from_config(100.0, 100.0, Config::from_toml("spawn_rate = 0").unwrap());
+        let mut sideways = |strength: f64| {
+            world.particles = vec![Box::new(particle_at([60.0, 50.0], 1.0))];
+            world.cursor_force = Some(Force::Attractor { center: [50.0, 50.0], strength, radius: 20.0 });
+            world.update();
+            world.particles[0].velocity[0]
+        };
+        assert!(sideways(0.5) < 0.0);
+        assert!(sideways(-0.5) > 0.0);
+
+        world.cursor_force = None;
+        world.particles.clear();
+        world.burst_at([30.0, 30.0], 5);
+        assert_eq!(world.particles.len(), 5);
+        assert!(world.particles.iter().all(|particle| particle.position == [30.0, 30.0]));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn the_deprecated_shrink_flag_still_keeps_the_room() {
+        let mut world = World::from_config(100.0, 100.0, Config::from_toml("spawn_rate = 0").unwrap());
+        world.particles = (0..100).map(|i| Box::new(particle_at([i as f64, 50.0], 1.0))).collect();
+        world.shrink = false;
+        world.particles.truncate(10);
+        world.update();
+        assert!(world.particles.capacity() >= 100);
+        world.shrink = true;
+        world.update();
+        assert!(world.particles.capacity() < 100);
+    }
+
+    #[test]
+    fn sweeps_run_every_point_with_its_storage() {
+        let sweep: sweep::Sweep = toml::from_str("turns = 3\nseed = 1\npopulation = [20]\nstorage = [\"boxed\", \"pooled\"]").unwrap();
+        let results = sweep::run(&sweep, &Config::default(), None);
+        let storages: Vec<_> = results.iter().map(|(point, report)| (point.storage, report.particle_pool.is_some())).collect();
+        assert_eq!(storages, [(AllocationStrategy::Boxed, false), (AllocationStrategy::Pooled, true)]);
+    }
+
+    #[test]
+    fn update_clamps_the_speed_before_moving() {
+        let mut particle = particle_at([0.0, 0.0], 1.0);
+        particle.velocity = [3.0, 4.0];
+        particle.acceleration = [0.0, 0.0];
+        particle.max_speed = 1.0;
+        particle.update(1.0);
+        assert!((particle.position[0] - 0.6).abs() < 1e-12 && (particle.position[1] - 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn alpha_decays_monotonically_under_every_fade() {
+        for model in [FadeModel::Exponential { half_life: 10.0 }, FadeModel::Linear { lifetime: 30 }, FadeModel::Step { lifetime: 5 }] {
+            let mut particle = particle_at([50.0, 50.0], 0.9);
+            particle.fade = model.start(0.9);
+            let mut last = particle.color[3];
+            for _ in 0..200 {
+                particle.update(1.0);
+                assert!(particle.color[3] <= last, "{:?} raised the alpha from {} to {}", model, last, particle.color[3]);
+                last = particle.color[3];
+            }
+            assert!(last < MIN_VISIBLE_ALPHA, "{:?} left the particle visible", model);
+        }
+    }
+
+    #[test]
+    fn updating_an_empty_world_never_panics() {
+        let mut world = world_with(Vec::new());
+        world.rng = StdRng::seed_from_u64(3);
+        world.spawn_rate = 0;
+        world.update();
+        assert!(world.particles.is_empty());
+
+        // Removals drawn on turns with fewer particles than that, or none
+        world.spawn_rate = 4;
+        for _ in 0..200 {
+            world.update();
+        }
+        world.remove_shapes(-10);
+        world.update();
+        assert_eq!(world.total_spawned - world.total_died, world.particles.len() as u64);
+    }
+
+    #[test]
+    fn cull_of_an_empty_world_removes_nothing() {
+        let mut world = world_with(Vec::new());
+        assert_eq!(world.cull(), 0);
+        assert_eq!(world.total_died, 0);
+    }
+
+    #[test]
+    fn cull_removes_every_invisible_particle_not_only_the_first() {
+        let mut world = world_with(vec![
+            particle_at([10.0, 50.0], 0.5),
+            particle_at([20.0, 50.0], 0.0),
+            particle_at([30.0, 50.0], 0.9),
+            particle_at([40.0, 50.0], 0.01),
+            particle_at([50.0, 50.0], 0.0),
+        ]);
+        assert_eq!(world.cull(), 3);
+        assert_eq!(tags(&world), vec![10, 30]);
+        assert_eq!(world.total_died, 3);
+    }
+
+    #[test]
+    fn cull_keeps_particles_exactly_at_the_visibility_threshold() {
+        let mut world = world_with(vec![particle_at([10.0, 50.0], MIN_VISIBLE_ALPHA)]);
+        assert_eq!(world.cull(), 0);
+    }
+
+    #[test]
+    fn cull_removes_particles_that_left_through_any_side() {
+        let size = ParticleSettings::default().size;
+        let mut world = world_with(vec![
+            particle_at([-size - 1.0, 50.0], 1.0),
+            particle_at([101.0, 50.0], 1.0),
+            particle_at([50.0, -size - 1.0], 1.0),
+            particle_at([60.0, 101.0], 1.0),
+            particle_at([70.0, 50.0], 1.0),
+        ]);
+        assert_eq!(world.cull(), 4);
+        assert_eq!(tags(&world), vec![70]);
+    }
+
+    #[test]
+    fn cull_keeps_particles_partly_on_screen_or_on_the_edge() {
+        let size = ParticleSettings::default().size;
+        let mut world = world_with(vec![
+            particle_at([-size / 2.0, 50.0], 1.0),
+            particle_at([100.0, 100.0], 1.0),
+            particle_at([0.0, 0.0], 1.0),
+        ]);
+        assert_eq!(world.cull(), 0);
+    }
+
+    #[test]
+    fn edges_wrap_particles_around_and_bounce_them_back_in() {
+        let (size, bounds) = ([10.0, 10.0], [100.0, 100.0]);
+        let (mut position, mut velocity) = ([105.0, -15.0], [3.0, -1.0]);
+        EdgeBehavior::Wrap.apply(&mut position, &mut velocity, size, bounds);
+        assert_eq!((position, velocity), ([-5.0, 95.0], [3.0, -1.0]));
+
+        let (mut position, mut velocity) = ([95.0, -2.0], [3.0, -1.0]);
+        EdgeBehavior::Bounce.apply(&mut position, &mut velocity, size, bounds);
+        assert_eq!((position, velocity), ([85.0, 2.0], [-3.0, 1.0]));
+
+        // Particles on their way back in are left alone
+        let (mut position, mut velocity) = ([95.0, -2.0], [-3.0, 1.0]);
+        EdgeBehavior::Bounce.apply(&mut position, &mut velocity, size, bounds);
+        assert_eq!((position, velocity), ([95.0, -2.0], [-3.0, 1.0]));
+    }
+
+    #[test]
+    fn resizing_moves_emitters_and_lines_with_the_window_and_keeps_particles_in() {
+        let mut world = world_with(vec![particle_at([80.0, 50.0], 1.0), particle_at([20.0, 90.0], 1.0)]);
+        world.add_emitter(Emitter::fountain([50.0, 50.0]));
+        world.kill_plane = Some(Edge::Y(80.0));
+        world.edges = EdgeBehavior::Bounce;
+        world.resize(50.0, 200.0);
+        assert_eq!((world.width, world.height), (50.0, 200.0));
+        assert_eq!(world.emitters[0].position, [25.0, 100.0]);
+        assert_eq!(world.kill_plane, Some(Edge::Y(160.0)));
+        let size = world.particles[0].width;
+        assert_eq!(world.particles[0].position, [50.0 - size, 50.0]);
+        assert_eq!(world.particles[1].position, [20.0, 90.0]);
+
+        // Particles spawned from the side now come in along the new bottom
+        let (point, _) = world.spawn_edge.spawn_point(&mut world.rng, world.width, world.height);
+        assert_eq!(point[1], 200.0);
+
+        // A minimized window leaves the world as it was
+        world.resize(0.0, 0.0);
+        assert_eq!((world.width, world.height), (50.0, 200.0));
+    }
+
+    #[test]
+    fn cohorts_follow_the_particles_spawned_on_the_turns_recorded() {
+        let path = std::env::temp_dir().join(format!("cohorts-{}.csv", std::process::id()));
+        let mut cohorts = population::CohortHistory::new(2);
+        // One particle from before recording started, never counted
+        cohorts.record(1, vec![0, 1, 1]);
+        cohorts.record(2, vec![0, 1, 2, 2, 2]);
+        cohorts.record(3, vec![0, 2, 3]);
+        cohorts.write_csv(&path, &metadata::RunMetadata::new(None, None)).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(rows, ["cohort,spawned,turn,alive,survival", "0,2,1,2,1.0000", "0,2,3,0,0.0000", "2,4,3,2,0.5000"]);
+    }
+
+    #[test]
+    fn boxed_and_soa_stores_move_particles_the_same() {
+        use store::{BoxedStore, ParticleStore, SoaStore};
+        let mut stores: [Box<dyn ParticleStore>; 2] = [Box::new(BoxedStore::with_capacity(0)), Box::new(SoaStore::with_capacity(0))];
+        let mut hashes = Vec::new();
+        for store in &mut stores {
+            let mut rng = StdRng::seed_from_u64(5);
+            let mut spawn = || {
+                let mut particle = particle_at([rng.gen_range(0.0..100.0), 50.0], 1.0);
+                particle.acceleration = [0.1, rng.gen_range(-0.2..0.2)];
+                (particle.max_speed, particle.fade) = (1.5, Fade::Subtract(0.05));
+                particle
+            };
+            for _ in 0..50 {
+                store.push(spawn());
+            }
+            for _ in 0..100 {
+                store.step(0.5, &mut spawn);
+            }
+            let mut hasher = StateHasher::new();
+            (0..store.len()).for_each(|index| store.get(index).hash(&mut hasher));
+            hashes.push(hasher.finish());
+        }
+        assert_eq!(hashes[0], hashes[1]);
+    }
+
+    #[test]
+    fn pooled_worlds_spawn_into_the_boxes_of_dead_particles() {
+        let mut world = world_with(vec![particle_at([10.0, 50.0], 0.001), particle_at([20.0, 50.0], 1.0)]);
+        world.particle_pool = Some(ParticlePool::with_capacity(1));
+        world.particle_pool.as_mut().unwrap().take(particle_at([0.0, 0.0], 1.0));
+        let dead: *const Particle = &*world.particles[0];
+        assert_eq!(world.cull(), 1);
+        world.add_shapes(2);
+        assert!(std::ptr::eq(dead, &*world.particles[1]));
+        let stats = world.particle_pool.as_ref().unwrap().stats();
+        assert_eq!((stats.free, stats.recycled, stats.allocated), (0, 2, 1));
+    }
+
+    #[test]
+    fn switching_storage_moves_the_particles_to_boxes_of_the_new_strategy() {
+        let mut world = world_with((1..=3).map(|i| particle_at([i as f64 * 10.0, 50.0], 1.0)).collect());
+        let boxed: *const Particle = &*world.particles[0];
+        assert_eq!(world.set_allocation_strategy(AllocationStrategy::Pooled), 3);
+        assert_eq!(world.allocation_strategy(), AllocationStrategy::Pooled);
+        assert!(!std::ptr::eq(boxed, &*world.particles[0]));
+        let stats = world.particle_pool.as_ref().unwrap().stats();
+        assert_eq!((stats.capacity, stats.free, stats.recycled), (3, 0, 3));
+
+        world.set_allocation_strategy(AllocationStrategy::Boxed);
+        assert_eq!(world.allocation_strategy(), AllocationStrategy::Boxed);
+        assert_eq!(tags(&world), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn backgrounds_keep_their_alpha_from_version_3_and_are_made_opaque_before() {
+        let world = World::from_config(100.0, 100.0, Config::from_toml("version = 3\nbackground = [0, 0, 0, 0.25]").unwrap());
+        assert_eq!(world.background(0), [0.0, 0.0, 0.0, 0.25]);
+
+        let scene = "version = 2\n[ambient]\nday = { background = [0.5, 0.5, 0.5, 0.9] }\nnight = { background = [0, 0, 0, 1] }\n";
+        let migration = migrate::migrate(scene).unwrap();
+        assert!(migration.text.contains("background = [0.5, 0.5, 0.5, 1.0] }"));
+        assert_eq!(migration.changes.len(), 1);
+        let world = World::from_config(100.0, 100.0, Config::from_toml(scene).unwrap());
+        assert_eq!(world.background(0)[3], 1.0);
+    }
+
+    #[test]
+    fn zooming_the_view_keeps_the_point_under_the_cursor_and_inverts_back() {
+        let mut view = view::Transform2D::default();
+        view.zoom_at([300.0, 200.0], 2.0);
+        assert_eq!(view.to_local([300.0, 200.0]), [300.0, 200.0]);
+        assert_eq!(view.to_local([400.0, 200.0]), [350.0, 200.0]);
+        view.pan([-50.0, 10.0]);
+        assert_eq!(view.to_local([300.0, 210.0]), [325.0, 200.0]);
+
+        // Drawn through `apply`, a point lands where `to_local` reads it from
+        let ctx = view.apply(graphics::Context::new_abs(1280.0, 960.0));
+        let drawn = graphics::math::transform_pos(ctx.transform, [325.0, 200.0]);
+        let abs = graphics::math::transform_pos(graphics::Context::new_abs(1280.0, 960.0).transform, [300.0, 210.0]);
+        assert!((drawn[0] - abs[0]).abs() < 1e-9 && (drawn[1] - abs[1]).abs() < 1e-9);
+
+        view.zoom_at([0.0, 0.0], 1e6);
+        assert_eq!(view.scale, 50.0);
+        assert!(!view.is_identity());
+    }
+
+    #[test]
+    fn camera_keys_pan_and_zoom_between_them_by_turn() {
+        let scene = "version = 3\n[[camera]]\nturn = 100\ncenter = [0, 0]\nzoom = 4\nease = \"linear\"\n\
+                     [[camera]]\nturn = 0\n[[camera]]\nturn = 200\ncenter = [0, 0]\nease = \"cut\"\n";
+        let world = World::from_config(200.0, 100.0, Config::from_toml(scene).unwrap());
+        let view = |turn| world.camera_view(turn).map(|view| (view.center, view.zoom));
+        assert_eq!(view(0), Some(([100.0, 50.0], 1.0)));
+        assert_eq!(view(50), Some(([50.0, 25.0], 2.0)));
+        assert_eq!(view(199), Some(([0.0, 0.0], 4.0)));
+        assert_eq!(view(200), Some(([0.0, 0.0], 1.0)));
+        assert_eq!(view(5000), view(200));
+
+        let zoomed = camera::CameraView { center: [30.0, 20.0], zoom: 2.0 };
+        assert_eq!(zoomed.to_world([100.0, 50.0], [200.0, 100.0]), [30.0, 20.0]);
+        assert_eq!(zoomed.to_world([0.0, 0.0], [200.0, 100.0]), [-20.0, -5.0]);
+        assert_eq!(World::from_config(200.0, 100.0, Config::default()).camera_view(0), None);
+    }
+
+    #[test]
+    fn accessibility_modes_change_the_scene_as_it_loads() {
+        let scene = "version = 3\nbackground = [0.2, 0.2, 0.3, 0.1]\nmax_speed = 8\nfade = { kind = \"step\", lifetime = 30 }\n\
+                     [particle]\nspeed = [1, 4]\n[[camera]]\nturn = 0\n[[camera]]\nturn = 100\nzoom = 4\nease = \"cut\"\n\
+                     [accessibility]\nhigh_contrast = true\nreduced_motion = true\n";
+        let world = World::from_config(200.0, 100.0, Config::from_toml(scene).unwrap());
+        assert_eq!(world.background(0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(world.palette.as_ref().map(|palette| palette.name.as_str()), Some("contrast"));
+        assert_eq!((world.max_speed, world.particle_settings.speed), (3.0, [0.5, 2.0]));
+        assert!(matches!(world.spawn_fade, Some(FadeModel::Linear { lifetime: 60 })));
+        assert_eq!(world.camera_view(50).map(|view| view.zoom), Some(2.0));
+
+        let plain = World::from_config(200.0, 100.0, Config::from_toml("version = 3\nmax_speed = 8\n").unwrap());
+        assert_eq!((plain.max_speed, plain.accessibility), (8.0, accessibility::Accessibility::default()));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn websocket_handshakes_are_answered_with_the_hash_of_their_key() {
+        // The example of RFC 6455
+        assert_eq!(metrics::websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn pprof_profiles_stack_size_classes_on_tags() {
+        let sample = |tag, bucket, count| pprof::ProfileSample { tag, bucket, count, bytes: count * 100, nanos: count * 40 };
+        let samples = [sample(None, 7, 3), sample(Some(AllocationTag::VecGrow), 12, 1)];
+        let metadata = metadata::RunMetadata::new(None, Some(4));
+        let profile = pprof::encode(&samples, metadata.started, &metadata);
+
+        // The allocations value type first, with its strings at 1 and 2
+        assert_eq!(profile[..6], [0x0a, 0x04, 0x08, 0x01, 0x10, 0x02]);
+        // The first sample: size class 7 called from the untagged function, 3 allocations
+        let first_sample = [0x12, 0x0a, 0x0a, 0x02, 5 + 7, 1, 0x12, 0x04, 3, 0x80 | 44, 2, 120];
+        assert!(profile.windows(first_sample.len()).any(|window| window == first_sample));
+        for name in ["untagged", "vec-grow", "64-127 B", "2048-4095 B", "seed: 4"] {
+            assert!(profile.windows(name.len()).any(|window| window == name.as_bytes()), "{} missing", name);
+        }
+    }
+
+    #[test]
+    fn software_renderers_draw_at_half_resolution_and_windows_leave_a_core_for_drawing() {
+        let display = |renderer: &str| capabilities::DisplayInfo {
+            gl_version: "3.3".to_string(),
+            gl_renderer: renderer.to_string(),
+            refresh_rate: Some(60),
+        };
+        let mut capabilities = capabilities::Capabilities { cpu_cores: 4, page_size: Some(4096), simd: Vec::new(), display: None };
+        assert_eq!((capabilities.default_threads(true), capabilities.default_threads(false)), (3, 4));
+        assert_eq!(capabilities.default_render_scale(), 1.0);
+        capabilities.display = Some(display("llvmpipe (LLVM 15.0.7, 256 bits)"));
+        assert_eq!(capabilities.default_render_scale(), 0.5);
+        capabilities.display = Some(display("NVIDIA GeForce RTX 3060/PCIe/SSE2"));
+        assert_eq!(capabilities.default_render_scale(), 1.0);
+        capabilities.cpu_cores = 1;
+        assert_eq!(capabilities.default_threads(true), 1);
+    }
+
+    #[test]
+    fn run_metadata_tells_the_build_and_the_simd_of_the_cpu() {
+        let metadata = metadata::RunMetadata::new(None, Some(1));
+        let fields: std::collections::HashMap<_, _> = metadata.fields().into_iter().collect();
+        assert_eq!(fields["profile"], metadata::build_profile());
+        assert_eq!(fields["features"] == "none", metadata::enabled_features().is_empty());
+        assert_eq!(metadata::enabled_features().contains(&"metrics"), cfg!(feature = "metrics"));
+        if cfg!(target_arch = "x86_64") {
+            assert!(metadata.capabilities.simd.contains(&"sse2"));
+        }
+    }
+
+    #[test]
+    fn debug_bundles_zip_the_files_the_log_and_the_telemetry() {
+        debug_bundle::enable(60.0);
+        debug_bundle::set_file("environment.txt", "seed: 1\n".to_string());
+        debug_bundle::log("loaded scene sparks");
+        let allocations = allocator::FrameAllocations { count: 2, bytes: 64, min_nanos: 10, max_nanos: 30, total_nanos: 40 };
+        for frame in 0..3 {
+            debug_bundle::record(frame, frame, 10, 4096, allocations);
+        }
+        let path = std::env::temp_dir().join(format!("debug-bundle-{}.zip", std::process::id()));
+        debug_bundle::write(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(bytes.starts_with(b"PK\x03\x04"));
+        // The end of the central directory, with the number of files
+        let end = &bytes[bytes.len() - 22..];
+        assert!(end.starts_with(b"PK\x05\x06"));
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 3);
+        let names = ["environment.txt", "log.txt", "telemetry.csv"];
+        assert!(names.iter().all(|name| bytes.windows(name.len()).filter(|window| window == &name.as_bytes()).count() == 2));
+    }
+
+    #[test]
+    fn telemetry_fidelity_drops_as_frames_near_their_budget_and_comes_back_with_headroom() {
+        use fidelity::{Fidelity, TelemetryGovernor};
+        use std::time::Duration;
+        let budget = Duration::from_millis(16);
+        let mut governor = TelemetryGovernor::new(budget);
+        let changes: Vec<_> = (0..100).filter_map(|_| governor.frame(Duration::from_millis(15))).collect();
+        assert_eq!(changes, [Fidelity::Sampled(8), Fidelity::Sampled(64), Fidelity::Aggregates]);
+        assert_eq!(Fidelity::Aggregates.trace_every(), 0);
+
+        // Frames with a little room left don't bring it back
+        assert!((0..500).all(|_| governor.frame(Duration::from_millis(10)).is_none()));
+        let changes: Vec<_> = (0..500).filter_map(|_| governor.frame(Duration::from_millis(4))).collect();
+        assert_eq!(changes, [Fidelity::Sampled(64), Fidelity::Sampled(8), Fidelity::Full]);
+    }
+
+    #[test]
+    fn the_backing_allocator_serves_aligned_blocks_from_the_backend_selected() {
+        use backend::AllocatorBackend;
+        use std::alloc::{GlobalAlloc, Layout};
+        if std::env::var_os(backend::ENV_VAR).is_none() {
+            assert_eq!(backend::selected(), AllocatorBackend::System);
+        }
+        assert_eq!(backend::load_error(), None);
+        for align in [8, 64, 4096] {
+            let layout = Layout::from_size_align(100, align).unwrap();
+            unsafe {
+                let ptr = allocator::BACKING.alloc_zeroed(layout);
+                assert!(!ptr.is_null() && (ptr as usize).is_multiple_of(align));
+                assert!(std::slice::from_raw_parts(ptr, 100).iter().all(|&byte| byte == 0));
+                let ptr = allocator::BACKING.realloc(ptr, layout, 5000);
+                assert!(!ptr.is_null() && (ptr as usize).is_multiple_of(align));
+                allocator::BACKING.dealloc(ptr, Layout::from_size_align(5000, align).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn latency_percentiles_are_the_longest_time_of_their_class() {
+        let mut histogram = histogram::AllocationHistogram { sizes: [0; 65], latencies: [0; 65] };
+        assert_eq!(histogram.latency_percentile(50.0), None);
+        histogram.latencies[6] = 90;
+        histogram.latencies[10] = 9;
+        histogram.latencies[14] = 1;
+        let percentiles: Vec<_> = [50.0, 90.0, 99.0, 100.0].iter().map(|&p| histogram.latency_percentile(p)).collect();
+        assert_eq!(percentiles, [Some(63), Some(63), Some(1023), Some(16383)]);
+    }
+
+    #[test]
+    fn a_failing_trace_sink_counts_the_records_it_missed() {
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        // Queued from several threads at once, none of them lost on the way
+        let before = telemetry::health().lost_records;
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                scope.spawn(move || {
+                    for size in 0..250 {
+                        telemetry::record(telemetry::Record { size, align: 8, timestamp: thread, nanos: 10, tag: None, kind: allocator::AllocationKind::Alloc });
+                    }
+                });
+            }
+        });
+        assert!(telemetry::drain(&mut Full, &mut String::new()).is_err());
+        assert_eq!(telemetry::health().lost_records - before, 1000);
+        assert_eq!(telemetry::drain(&mut Full, &mut String::new()).ok(), Some(false));
+    }
+
+    #[test]
+    fn frame_summaries_aggregate_the_allocations_since_the_last_frame() {
+        allocator::finish_frame();
+        [(64, 40), (16, 100), (80, 10)].into_iter().for_each(|(bytes, nanos)| allocator::count_in_frame(bytes, nanos));
+        let frame = allocator::finish_frame();
+        assert_eq!((frame.count, frame.bytes, frame.min_nanos, frame.max_nanos, frame.mean_nanos()), (3, 160, 10, 100, 50));
+        assert_eq!(allocator::finish_frame(), allocator::FrameAllocations::default());
+
+        let mut line = Vec::new();
+        frame.write_summary(7, &mut line).unwrap();
+        assert_eq!(String::from_utf8(line).unwrap(), "# frame 7\t3 allocations\t160 bytes\t10 ns min\t50 ns mean\t100 ns max\n");
+    }
+
+    #[test]
+    fn trail_allocations_are_not_counted_as_vec_reallocations() {
+        use std::alloc::{GlobalAlloc, Layout};
+        let scene = "spawn_rate = 0\n[trail]\nstorage = \"growing\"";
+        let mut world = World::from_config(100.0, 100.0, toml::from_str(scene).unwrap());
+        world.particles = (0..20).map(|i| Box::new(particle_at([i as f64 * 5.0, 50.0], 1.0))).collect();
+        world.set_storage_policy(StoragePolicy::NeverShrink);
+        world.reserve(20);
+
+        // The test binary only reports the allocations handed to it, so a
+        // trail allocation of its own stands for those of the world
+        let before = allocator::memory_stats();
+        for _ in 0..30 {
+            world.update();
+        }
+        tagged(AllocationTag::Trail, || unsafe {
+            let layout = Layout::from_size_align(64, 8).unwrap();
+            allocator::ReportingAllocator.dealloc(allocator::ReportingAllocator.alloc(layout), layout);
+        });
+        let after = allocator::memory_stats();
+        assert!(world.trail_stats.allocations > 20);
+        assert_eq!(after.vec_reallocs - before.vec_reallocs, 0);
+    }
+
+    #[test]
+    fn compare_panels_count_no_vec_reallocations_for_trails() {
+        // Counted only in builds installing the allocator, like `--features ffi`
+        let scene = "spawn_rate = 0\n[trail]\nstorage = \"growing\"";
+        let mut world = World::from_config(100.0, 100.0, toml::from_str(scene).unwrap());
+        world.particles = (0..20).map(|i| Box::new(particle_at([i as f64 * 5.0, 50.0], 1.0))).collect();
+        world.set_storage_policy(StoragePolicy::NeverShrink);
+        world.reserve(20);
+        let mut meter = compare::WorldMeter::new();
+        for _ in 0..30 {
+            meter.update(&mut world);
+        }
+        assert!(world.trail_stats.allocations > 20);
+        let text = locale::bundle();
+        let rows = meter.rows(&world);
+        assert_eq!(rows.iter().find(|(name, _)| *name == text.vec_reallocations).map(|(_, value)| value.as_str()), Some("0"));
+    }
+
+    #[test]
+    fn reallocs_and_zeroed_allocations_are_measured_and_told_apart_in_traces() {
+        use std::alloc::{GlobalAlloc, Layout};
+        let before = allocator::memory_stats();
+        unsafe {
+            let layout = Layout::from_size_align(24, 8).unwrap();
+            let block = allocator::ReportingAllocator.alloc_zeroed(layout);
+            assert!(std::slice::from_raw_parts(block, 24).iter().all(|&byte| byte == 0));
+            block.write(7);
+            let block = allocator::ReportingAllocator.realloc(block, layout, 4096);
+            assert_eq!(block.read(), 7);
+            allocator::ReportingAllocator.dealloc(block, Layout::from_size_align(4096, 8).unwrap());
+        }
+        let after = allocator::memory_stats();
+        assert_eq!((after.reallocations - before.reallocations, after.zeroed_allocations - before.zeroed_allocations), (1, 1));
+
+        // Lines from before kinds were written still read as fresh allocations
+        let path = std::env::temp_dir().join(format!("kinds-{}.tsv", std::process::id()));
+        let lines = ["64\t90", "4096\t300\tvec-grow\trealloc", "32\t80\tzeroed", "64\t20\tfree", "{\"size\":8,\"nanos\":5,\"kind\":\"free\"}"];
+        std::fs::write(&path, lines.join("\n") + "\n1,16,8,40,,zeroed\n").unwrap();
+        let summary = trace::TraceSummary::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((summary.count, summary.total_bytes, summary.vec_grows), (4, 4208, 1));
+        assert_eq!((summary.reallocs, summary.zeroed, summary.frees, summary.freed_bytes), (1, 2, 2, 72));
+        assert_eq!(summary.info.skipped_lines, 0);
+    }
+
+    #[test]
+    fn traces_read_back_what_every_format_wrote() {
+        use allocator::{AllocationKind, AllocationTag};
+        use telemetry::{Record, TraceFormat};
+
+        let records = [
+            Record { size: 64, align: 8, timestamp: 10, nanos: 90, tag: None, kind: AllocationKind::Alloc },
+            Record { size: 4096, align: 8, timestamp: 20, nanos: 300, tag: Some(AllocationTag::VecGrow), kind: AllocationKind::Realloc },
+            Record { size: 32, align: 16, timestamp: 30, nanos: 80, tag: Some(AllocationTag::Trail), kind: AllocationKind::Free },
+        ];
+        for format in [TraceFormat::Tsv, TraceFormat::Csv, TraceFormat::Json] {
+            let mut text = if format == TraceFormat::Csv { "timestamp_ns,size,align,duration_ns,tag,kind\n".to_string() } else { String::new() };
+            for record in &records {
+                format.write(&mut text, record).unwrap();
+            }
+            let path = std::env::temp_dir().join(format!("round-trip-{:?}-{}.trace", format, std::process::id()));
+            std::fs::write(&path, text).unwrap();
+            let mut events = Vec::new();
+            let info = trace::read_events(&path, |event| events.push((event.size, event.nanos, event.tag, event.kind))).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let expected: Vec<_> = records.iter().map(|record| (record.size, record.nanos, record.tag, record.kind)).collect();
+            assert_eq!((events, info.skipped_lines), (expected, 0), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn allocations_are_counted_by_the_thread_making_them() {
+        use std::alloc::{GlobalAlloc, Layout};
+        let layout = Layout::from_size_align(40, 8).unwrap();
+        std::thread::spawn(move || unsafe {
+            allocator::set_thread_role(allocator::ThreadRole::Rayon(41));
+            let blocks: Vec<_> = (0..3).map(|_| allocator::ReportingAllocator.alloc(layout)).collect();
+            blocks.into_iter().for_each(|block| allocator::ReportingAllocator.dealloc(block, layout));
+        })
+        .join()
+        .unwrap();
+        let stats = allocator::thread_stats();
+        let thread = stats.iter().find(|thread| thread.role == allocator::ThreadRole::Rayon(41)).unwrap();
+        assert_eq!((thread.label().as_str(), thread.allocations, thread.allocated_bytes, thread.frees), ("rayon-41", 3, 120, 3));
+    }
+
+    #[test]
+    fn localized_strings_are_filled_in_order_in_either_language() {
+        let spanish = locale::Language::Spanish.bundle();
+        assert_eq!(locale::fill(spanish.heap, &[&"2.0 MiB", &"3.5 MiB"]), "heap 2.0 MiB (pico 3.5 MiB)");
+        assert_eq!(locale::fill(spanish.turns_of, &[&400, &1000]), "400 de 1000");
+        assert_eq!(locale::fill(locale::Language::English.bundle().particles_count, &[&12]), "12 particles");
+        assert_eq!(locale::fill("{} and {}", &[&1]), "1 and ");
+    }
+
+    #[test]
+    fn screenshots_read_from_the_bottom_are_saved_top_row_first() {
+        // Two rows of two pixels, red and green at the bottom, as GL reads them
+        let bottom_up = [255, 0, 0, 0, 255, 0, 0, 0, 255, 9, 9, 9];
+        let data = capture::flip_rows(&bottom_up, 6);
+        assert_eq!(data, [0, 0, 255, 9, 9, 9, 255, 0, 0, 0, 255, 0]);
+
+        let path = std::env::temp_dir().join(format!("screenshot-{}.png", std::process::id()));
+        capture::write_rgb_png(&path, 2, 2, &data).unwrap();
+        let mut reader = png::Decoder::new(std::fs::File::open(&path).unwrap()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn journals_replay_the_spawn_decisions_and_bursts_of_a_run() {
+        use journal::{Journal, JournalEvent, JournalHeader, JournalWriter};
+        let path = std::env::temp_dir().join(format!("run-{}.jrn", std::process::id()));
+        let header = JournalHeader { width: 200.0, height: 100.0, seed: 7, scene: String::new() };
+        let mut world = World::from_config(200.0, 100.0, Config::default());
+        world.rng = StdRng::seed_from_u64(7);
+        world.journal = Some(JournalWriter::create(&path, &header).unwrap());
+        for turn in 0..50 {
+            if turn == 20 {
+                world.journal.as_ref().unwrap().event(JournalEvent::Burst(30));
+                world.add_shapes(30);
+            }
+            world.update();
+        }
+        world.journal.take().unwrap().finish().unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let journal = Journal::parse(&bytes).unwrap();
+        assert_eq!((journal.header.clone(), journal.len()), (header, 50));
+        assert_eq!(journal.turns[20].events, [JournalEvent::Burst(30)]);
+        bytes.push(0x80);
+        assert_eq!(Journal::parse(&bytes).unwrap().len(), 50);
+
+        let mut replayed = World::from_config(200.0, 100.0, Config::default());
+        replayed.rng = StdRng::seed_from_u64(7);
+        replayed.replay = Some(journal::JournalReplay::new(journal));
+        (0..50).for_each(|_| replayed.update());
+        assert_eq!(replayed.particles.len(), world.particles.len());
+        assert_eq!(replayed.state_hash(), world.state_hash());
+    }
+
+    #[test]
+    fn scene_and_command_line_values_take_units() {
+        assert_eq!(units::parse_bytes("256MiB"), Ok(256 << 20));
+        assert_eq!(units::parse_bytes("1.5 kb"), Ok(1500));
+        assert!(units::parse_bytes("12 parsecs").is_err());
+        assert_eq!(units::parse_duration("500ms"), Ok(std::time::Duration::from_millis(500)));
+        assert!(units::parse_duration("10").is_err());
+        assert_eq!(units::parse_spawn_rate("600/s"), Ok(10));
+        assert!(units::parse_spawn_rate("-5/s").is_err());
+        assert_eq!("2min".parse(), Ok(warmup::Warmup::Time(std::time::Duration::from_secs(120))));
+        assert_eq!("300".parse(), Ok(warmup::Warmup::Turns(300)));
+
+        let config = Config::from_toml("spawn_rate = \"120/s\"\nmax_heap = \"1 GiB\"\n[[emitter]]\nposition = [0, 0]\nrate = \"30/s\"").unwrap();
+        assert_eq!((config.spawn_rate, config.max_heap, config.emitters[0].rate), (Some(2), Some(1 << 30), Some(0.5)));
+        assert_eq!(Config::from_toml("spawn_rate = 4").unwrap().spawn_rate, Some(4));
+        assert!(Config::from_toml("spawn_rate = \"4/fortnight\"").is_err());
+    }
+
+    #[test]
+    fn checkpoints_carry_the_world_on_from_its_scene_and_state() {
+        let path = std::env::temp_dir().join(format!("world-{}.checkpoint", std::process::id()));
+        let mut world = World::from_config(200.0, 100.0, Config::from_toml("drag = 0.1\nmax_speed = 4.0").unwrap());
+        world.rng = StdRng::seed_from_u64(3);
+        (0..40).for_each(|_| world.update());
+        world.particles[0].velocity = [f64::INFINITY, 0.0];
+        world.save(&path).unwrap();
+        let loaded = World::load(&path);
+        std::fs::remove_file(&path).ok();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.current_turn, loaded.drag, loaded.max_speed), (40, 0.1, 4.0));
+        assert_eq!(loaded.particles.len(), world.particles.len() - 1);
+        let motion = |particles: &[Box<Particle>]| particles.iter().map(|p| (p.position, p.velocity, p.color, p.age)).collect::<Vec<_>>();
+        assert_eq!(motion(&loaded.particles), motion(&world.particles[1..]));
+        assert!(World::load(Path::new("Cargo.toml")).is_err());
+    }
+
+    #[test]
+    fn arena_chunks_start_over_once_all_their_blocks_are_dead() {
+        let layout = std::alloc::Layout::new::<Particle>();
+        let before = arena::arena_stats();
+        let blocks: Vec<_> = (0..arena::CHUNK_BLOCKS + 1).map(|_| unsafe { arena::alloc(layout) }).collect();
+        assert!(blocks.iter().all(|&block| arena::owns(block)));
+        assert!(!arena::owns(&mut 0u8));
+
+        // The last block went to a chunk of its own, left alone by the first
+        // ones dying, and bumped out of again once dead itself
+        blocks[..arena::CHUNK_BLOCKS].iter().for_each(|&block| unsafe { arena::dealloc(block) });
+        let stats = arena::arena_stats();
+        assert_eq!((stats.live_blocks, stats.reclaimed - before.reclaimed), (before.live_blocks + 1, 1));
+        unsafe {
+            arena::dealloc(blocks[arena::CHUNK_BLOCKS]);
+            let again = arena::alloc(layout);
+            assert_eq!(again, blocks[arena::CHUNK_BLOCKS]);
+            arena::dealloc(again);
+        }
+    }
+
+    #[test]
+    fn survival_curves_count_the_deaths_and_leave_the_living_out_once_reached() {
+        let mut world = world_with(vec![particle_at([10.0, 50.0], 0.001), particle_at([20.0, 50.0], 1.0)]);
+        world.particles[0].age = 5;
+        world.survival = Some(SurvivalCurve::new());
+        world.cull();
+        let mut curve = world.survival.take().unwrap();
+        [15, 15, 25].into_iter().for_each(|age| curve.record(age));
+        assert_eq!(curve.deaths(), 4);
+
+        let alive = [12, 40];
+        let points: Vec<_> = curve.points(alive).iter().map(|point| (point.age, point.at_risk, point.survival)).collect();
+        assert_eq!(points, [(0, 6, 5.0 / 6.0), (10, 5, 0.5), (20, 2, 0.25), (30, 1, 0.25), (40, 1, 0.25)]);
+        assert_eq!(curve.median(alive), Some(20));
+    }
+
+    #[test]
+    fn cull_removes_particles_gone_nan() {
+        let mut world = world_with(vec![particle_at([f64::NAN, 50.0], 1.0), particle_at([10.0, 50.0], f32::NAN)]);
+        assert_eq!(world.cull(), 2);
+    }
+
+    #[test]
+    fn remove_shapes_takes_the_oldest_and_stops_when_empty() {
+        let mut world = world_with((1..=4).map(|i| particle_at([i as f64 * 10.0, 50.0], 1.0)).collect());
+        world.remove_shapes(-2);
+        assert_eq!(tags(&world), vec![30, 40]);
+        world.remove_shapes(-5);
+        assert!(world.particles.is_empty());
+        assert_eq!(world.total_died, 4);
+    }
+
+    #[test]
+    fn behaviors_added_by_programs_run_on_every_particle_in_order() {
+        use behavior::{Drag, Flocking, WorldView};
+
+        // Doubles the speed, so running after the drag gives another result
+        struct Boost;
+        impl Behavior for Boost {
+            fn name(&self) -> &'static str {
+                "boost"
+            }
+
+            fn apply(&self, particle: &mut Particle, _: &WorldView, _: f64) {
+                particle.velocity = [particle.velocity[0] * 2.0, particle.velocity[1] * 2.0];
+            }
+        }
+
+        let mut world = World::new(200.0, 200.0);
+        world.rng = StdRng::seed_from_u64(3);
+        world.spawn_rate = 0;
+        world.add_shapes(2);
+        for particle in &mut world.particles {
+            particle.velocity = [1.0, 0.0];
+            particle.acceleration = [0.0, 0.0];
+        }
+        world.add_behavior(Drag { coefficient: 0.5 });
+        world.add_behavior(Boost);
+        assert_eq!(world.behavior_names().collect::<Vec<_>>(), ["drag", "boost"]);
+        world.update();
+        assert!(world.particles.iter().all(|particle| particle.velocity == [1.0, 0.0]));
+
+        // Flocking pulls two particles alone together, and gathers neighbors
+        world.clear_behaviors();
+        world.add_behavior(Flocking { radius: 500.0, separation: 0.0, ..Flocking::default() });
+        world.particles[0].position = [50.0, 100.0];
+        world.particles[1].position = [150.0, 100.0];
+        for particle in &mut world.particles {
+            particle.velocity = [0.0, 0.0];
+        }
+        world.update();
+        assert!(world.particles[0].velocity[0] > 0.0 && world.particles[1].velocity[0] < 0.0);
+    }
+
+    #[test]
+    fn noise_is_smooth_bounded_and_the_same_for_a_seed() {
+        let samples: Vec<f64> = (0..200).map(|i| noise::perlin3([i as f64 * 0.173, i as f64 * 0.071, 0.5], 3)).collect();
+        assert!(samples.iter().all(|value| value.abs() <= 1.0));
+        assert!(samples.iter().any(|&value| value > 0.1) && samples.iter().any(|&value| value < -0.1));
+        assert_eq!(noise::perlin3([4.0, -2.0, 7.0], 3), 0.0);
+
+        // Close points get close values, and another seed another field
+        let near = noise::fractal3([1.3, 2.7, 0.2], 3, 1) - noise::fractal3([1.301, 2.7, 0.2], 3, 1);
+        assert!(near.abs() < 0.01);
+        assert_ne!(noise::perlin3([1.3, 2.7, 0.2], 1), noise::perlin3([1.3, 2.7, 0.2], 2));
+
+        let force = force::Force::Noise { amplitude: 0.5, frequency: 0.01, speed: 0.01, octaves: 2, seed: 4 };
+        let [x, y] = force.acceleration([120.0, 80.0], 30);
+        assert!(x.abs() <= 0.5 && y.abs() <= 0.5 && (x, y) != (0.0, 0.0));
+        assert_eq!(force.acceleration([120.0, 80.0], 30), [x, y]);
+    }
+
+    #[test]
+    fn rockets_burst_into_the_particles_of_each_stage_in_turn() {
+        let scene = r#"
+            spawn_rate = 0
+            [[emitter]]
+            position = [100, 100]
+            rate = 1
+            burst_every = 1000
+            particle = { speed = [0.1, 0.1], braking = [0, 0] }
+            [emitter.firework]
+            fuse = [3, 3]
+            count = 5
+            particle = { speed = [0.5, 0.5], braking = [0, 0] }
+            [emitter.firework.then]
+            fuse = [2, 2]
+            count = 2
+        "#;
+        let mut world = World::from_config(200.0, 200.0, Config::from_toml(scene).unwrap());
+        world.rng = StdRng::seed_from_u64(1);
+        let mut counts = Vec::new();
+        for _ in 0..8 {
+            world.update();
+            counts.push(world.particles.len());
+        }
+        assert_eq!(counts, [1, 1, 5, 5, 10, 10, 10, 10]);
+        assert!(world.particles.iter().all(|particle| particle.rocket.is_none()));
+        assert_eq!((world.total_spawned, world.total_died), (16, 6));
+    }
+
+    #[test]
+    fn population_controllers_close_the_gap_to_their_target() {
+        let config = Config::from_toml("[controller]\nkind = \"count\"\ntarget = 200\ngain = 0.1").unwrap();
+        let mut world = World::from_config(800.0, 800.0, config);
+        world.rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            world.update();
+        }
+        assert!(world.particles.len().abs_diff(200) <= 5, "{} particles", world.particles.len());
+
+        let heap = PopulationController::Heap { max_bytes: 100 * controller::PARTICLE_BYTES, gain: 0.5, max_step: 50 };
+        assert_eq!(heap.decide(0, 10, 20 * controller::PARTICLE_BYTES), 40);
+        assert_eq!(heap.decide(0, 10, 120 * controller::PARTICLE_BYTES), -5);
+        assert_eq!(heap.decide(0, 10, 100 * controller::PARTICLE_BYTES), 0);
+        let wave = PopulationController::Wave { mean: 100, amplitude: 50, period: 4, gain: 1.0, max_step: 1000 };
+        assert_eq!([0, 1, 2, 3].map(|turn| wave.decide(turn, 100, 0)), [0, 50, 0, -50]);
+    }
+
+    #[test]
+    fn scenarios_drive_the_world_from_its_next_turn() {
+        use scenario::{Scenario, ScenarioRun, SAWTOOTH_PEAK};
+
+        let mut world = World::from_config(400.0, 400.0, Config::default());
+        world.rng = StdRng::seed_from_u64(2);
+        Scenario::VecGrowth.apply(&mut world);
+        for _ in 0..300 {
+            world.update();
+        }
+        let (population, room) = (world.particles.len(), world.particles.capacity());
+        world.controller = Some(PopulationController::Count { target: 10, gain: 1.0, max_step: usize::MAX });
+        world.update();
+        assert!(population > 1000 && world.particles.len() <= 10);
+        assert_eq!(world.particles.capacity(), room);
+
+        let run = ScenarioRun::new(Scenario::Sawtooth);
+        assert!(!run.clears_at(SAWTOOTH_PEAK / 2) && run.clears_at(SAWTOOTH_PEAK));
+        assert!(!ScenarioRun::new(Scenario::Steady).clears_at(SAWTOOTH_PEAK));
+    }
+
+    #[test]
+    fn flocking_tables_of_scenes_add_the_behavior() {
+        let config = Config::from_toml("[flocking]\nradius = 30").unwrap();
+        assert_eq!(config.flocking.map(|flocking| flocking.radius), Some(30.0));
+        assert_eq!(World::from_config(100.0, 100.0, config).behavior_names().collect::<Vec<_>>(), ["flocking"]);
+        assert_eq!(World::from_config(100.0, 100.0, Config::default()).behavior_names().count(), 0);
+    }
+
+    #[test]
+    fn animations_take_a_frame_every_so_many_turns_until_long_enough() {
+        use video::{VideoFormat, VideoRecorder};
+        let path = std::env::temp_dir().join(format!("animation-{}.gif", std::process::id()));
+        assert_eq!(VideoFormat::of(&path), Some(VideoFormat::Gif));
+        assert_eq!(VideoFormat::of(std::path::Path::new("run.rec")), None);
+
+        // 60 turns a second at 20 frames a second, for a tenth of a second
+        let mut video = VideoRecorder::create(&path, VideoFormat::Gif, [4, 3], 20, 0.1, 60).unwrap();
+        let mut captured = Vec::new();
+        for turn in 0..12 {
+            if video.turn() {
+                video.push(&capture::Canvas::new(4, 3)).unwrap();
+                captured.push(turn);
+            }
+        }
+        assert_eq!(captured, [0, 3]);
+        assert!(video.is_done());
+        video.finish().unwrap();
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&written[..6], b"GIF89a");
+    }
+}
@@ -0,0 +1,33 @@
+use rand::Rng;
+use serde::Deserialize;
+
+/// How long particles live and what becomes of them when they die. Without
+/// one, a particle only dies once faded out or off the window, or culled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PopulationPolicy {
+    /// Range of turns every particle lives, drawn when it spawns; the
+    /// particle dies on the turn its age reaches it, however visible it still
+    /// is. Unlimited when missing
+    pub lifetime: Option<[u32; 2]>,
+
+    /// A particle dying of age, of fading out or of leaving the window is
+    /// replaced where it was in the list by a new one of the scene, reusing
+    /// its allocation, so those deaths leave the population as it was.
+    /// Culling for the spawn rate, the soft cap or the memory budget still
+    /// removes particles
+    #[serde(default)]
+    pub respawn: bool,
+}
+
+impl PopulationPolicy {
+    /// Turns a new particle lives, drawing randomness only for a lifetime
+    /// with a range.
+    pub fn max_age<R: Rng>(&self, rng: &mut R) -> u32 {
+        match self.lifetime {
+            None => u32::MAX,
+            Some([low, high]) if low < high => rng.gen_range(low..=high),
+            Some([low, _]) => low,
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// The language chosen with --lang, as `Language::to_u8`
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// The languages the HUD and the reports are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    #[default]
+    #[value(name = "en")]
+    English,
+
+    #[value(name = "es")]
+    Spanish,
+}
+
+impl Language {
+    fn to_u8(self) -> u8 {
+        match self {
+            Language::English => 0,
+            Language::Spanish => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Language {
+        match value {
+            1 => Language::Spanish,
+            _ => Language::English,
+        }
+    }
+
+    /// The strings of this language.
+    pub fn bundle(self) -> &'static Bundle {
+        match self {
+            Language::English => &ENGLISH,
+            Language::Spanish => &SPANISH,
+        }
+    }
+}
+
+/// Writes the HUD and the reports in `language` from now on.
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language.to_u8(), Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    Language::from_u8(LANGUAGE.load(Ordering::Relaxed))
+}
+
+/// The strings of the language chosen, English until one is.
+pub fn bundle() -> &'static Bundle {
+    language().bundle()
+}
+
+/// The text shown to the user, one field per string so every language has
+/// them all. `{}` stands for the values `fill` puts in, in order.
+pub struct Bundle {
+    /// Of the `lang` attribute of HTML reports
+    pub code: &'static str,
+
+    pub fps: &'static str,
+    pub particles_count: &'static str,
+    pub heap: &'static str,
+    pub allocations_per_frame: &'static str,
+    pub median_lifetime: &'static str,
+    pub median_lifetime_unknown: &'static str,
+    pub telemetry_fidelity: &'static str,
+
+    // Names of the lines of the bench report
+    pub turns: &'static str,
+    pub elapsed: &'static str,
+    pub turns_per_second: &'static str,
+    pub allocations: &'static str,
+    pub allocated_bytes: &'static str,
+    pub operations: &'static str,
+    pub mean_allocation: &'static str,
+    pub vec_reallocations: &'static str,
+    pub drops: &'static str,
+    pub by_thread: &'static str,
+    pub peak_live_bytes: &'static str,
+    pub particles: &'static str,
+    pub kinetic_energy: &'static str,
+    pub momentum: &'static str,
+    pub allocator: &'static str,
+    pub arena_chunks: &'static str,
+    pub pool_refills: &'static str,
+    pub particle_pool: &'static str,
+    pub trails: &'static str,
+    pub reuse_distance: &'static str,
+    pub telemetry: &'static str,
+    pub frames: &'static str,
+    pub dropped_frames: &'static str,
+
+    // The summary printed at the end of windowed runs
+    pub peak_particles: &'static str,
+    pub mean_frame_time: &'static str,
+    pub slowest: &'static str,
+
+    // Values of the bench report
+    pub turns_of: &'static str,
+    pub interrupted: &'static str,
+    pub operation_counts: &'static str,
+
+    // The allocation histogram printed at exit
+    pub allocation_size: &'static str,
+    pub count: &'static str,
+    pub share: &'static str,
+    pub allocation_time: &'static str,
+    pub at_most: &'static str,
+
+    // The call sites of --alloc-backtraces, printed after it
+    pub call_site: &'static str,
+    pub call_sites_sampled: &'static str,
+
+    // The panels of the worlds of --compare
+    pub per_turn: &'static str,
+    pub net_heap: &'static str,
+    pub update_time: &'static str,
+
+    // The HTML report
+    pub report_title: &'static str,
+    pub bench_run: &'static str,
+    pub window_run: &'static str,
+    pub default_scene: &'static str,
+    pub turn: &'static str,
+    pub frame: &'static str,
+    pub population_per: &'static str,
+    pub time_per: &'static str,
+    pub allocations_by_size: &'static str,
+    pub population_caption: &'static str,
+    pub time_caption: &'static str,
+    pub no_allocations: &'static str,
+    pub sizes_caption: &'static str,
+}
+
+const ENGLISH: Bundle = Bundle {
+    code: "en",
+    fps: "{} fps",
+    particles_count: "{} particles",
+    heap: "heap {} (peak {})",
+    allocations_per_frame: "{} allocations/frame",
+    median_lifetime: "median lifetime {} turns",
+    median_lifetime_unknown: "median lifetime -",
+    telemetry_fidelity: "telemetry {}",
+    turns: "turns",
+    elapsed: "elapsed",
+    turns_per_second: "turns per second",
+    allocations: "allocations",
+    allocated_bytes: "allocated bytes",
+    operations: "operations",
+    mean_allocation: "mean allocation",
+    vec_reallocations: "vec reallocations",
+    drops: "drops",
+    by_thread: "by thread",
+    peak_live_bytes: "peak live bytes",
+    particles: "particles",
+    kinetic_energy: "kinetic energy",
+    momentum: "momentum",
+    allocator: "allocator",
+    arena_chunks: "arena chunks",
+    pool_refills: "pool refills",
+    particle_pool: "particle pool",
+    trails: "trails",
+    reuse_distance: "reuse distance",
+    telemetry: "telemetry",
+    frames: "frames",
+    dropped_frames: "dropped frames",
+    peak_particles: "peak particles",
+    mean_frame_time: "mean frame time",
+    slowest: "slowest",
+    turns_of: "{} of {}",
+    interrupted: " (interrupted)",
+    operation_counts: "{} alloc, {} realloc, {} zeroed, {} free",
+    allocation_size: "allocation size",
+    count: "count",
+    share: "share",
+    allocation_time: "allocation time",
+    at_most: "at most",
+    call_site: "call site",
+    call_sites_sampled: "1 in {} allocations sampled, {} samples, {} allocations made sampling",
+    per_turn: "a turn",
+    net_heap: "net heap",
+    update_time: "update time",
+    report_title: "{} of {}",
+    bench_run: "bench",
+    window_run: "window run",
+    default_scene: "default scene",
+    turn: "turn",
+    frame: "frame",
+    population_per: "population per {}",
+    time_per: "time per {}",
+    allocations_by_size: "allocations by size",
+    population_caption: "max {}, {} {}",
+    time_caption: "median {} ms, p99 {} ms, max {} ms, slowest per column",
+    no_allocations: "no allocations",
+    sizes_caption: "{} allocations, log scale",
+};
+
+const SPANISH: Bundle = Bundle {
+    code: "es",
+    fps: "{} fps",
+    particles_count: "{} partículas",
+    heap: "heap {} (pico {})",
+    allocations_per_frame: "{} asignaciones/fotograma",
+    median_lifetime: "vida mediana {} turnos",
+    median_lifetime_unknown: "vida mediana -",
+    telemetry_fidelity: "telemetría {}",
+    turns: "turnos",
+    elapsed: "duración",
+    turns_per_second: "turnos por segundo",
+    allocations: "asignaciones",
+    allocated_bytes: "bytes asignados",
+    operations: "operaciones",
+    mean_allocation: "asignación media",
+    vec_reallocations: "reasignaciones del vec",
+    drops: "destrucciones",
+    by_thread: "por hilo",
+    peak_live_bytes: "pico de bytes vivos",
+    particles: "partículas",
+    kinetic_energy: "energía cinética",
+    momentum: "momento lineal",
+    allocator: "asignador",
+    arena_chunks: "bloques de la arena",
+    pool_refills: "recargas del pool",
+    particle_pool: "pool de partículas",
+    trails: "estelas",
+    reuse_distance: "distancia de reuso",
+    telemetry: "telemetría",
+    frames: "fotogramas",
+    dropped_frames: "fotogramas perdidos",
+    peak_particles: "pico de partículas",
+    mean_frame_time: "tiempo medio por fotograma",
+    slowest: "el más lento",
+    turns_of: "{} de {}",
+    interrupted: " (interrumpido)",
+    operation_counts: "{} alloc, {} realloc, {} a cero, {} free",
+    allocation_size: "tamaño de asignación",
+    count: "cantidad",
+    share: "parte",
+    allocation_time: "tiempo de asignación",
+    at_most: "como mucho",
+    call_site: "punto de llamada",
+    call_sites_sampled: "1 de cada {} asignaciones muestreadas, {} muestras, {} asignaciones hechas al muestrear",
+    per_turn: "por turno",
+    net_heap: "heap neto",
+    update_time: "tiempo de actualización",
+    report_title: "{} de {}",
+    bench_run: "bench",
+    window_run: "ejecución en ventana",
+    default_scene: "escena por defecto",
+    turn: "turno",
+    frame: "fotograma",
+    population_per: "población por {}",
+    time_per: "tiempo por {}",
+    allocations_by_size: "asignaciones por tamaño",
+    population_caption: "máximo {}, {} {}",
+    time_caption: "mediana {} ms, p99 {} ms, máximo {} ms, el más lento por columna",
+    no_allocations: "sin asignaciones",
+    sizes_caption: "{} asignaciones, escala logarítmica",
+};
+
+/// `template` with each `{}` replaced by the next of `values`, those
+/// missing left out.
+pub fn fill(template: &str, values: &[&dyn Display]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut values = values.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        text.push_str(first);
+    }
+    for part in parts {
+        if let Some(value) = values.next() {
+            text.push_str(&value.to_string());
+        }
+        text.push_str(part);
+    }
+    text
+}
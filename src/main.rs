@@ -1,186 +1,3346 @@
-use graphics::math::{Vec2d, add, mul_scalar};
+use clap::{Parser, Subcommand};
+use graphics::math::Vec2d;
+use memory_particles::*;
 use piston_window::*; // Create a GUI program
-use rand::prelude::*;
-use std::alloc::{GlobalAlloc, System, Layout}; // Controls for memory allocation
-use std::time::Instant;
+use rayon::prelude::*;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+use warmup::Warmup;
+use vec_policy::StoragePolicy;
+use compare::WorldMeter;
 
+mod allocation_graph;
+mod clock;
+mod correlation;
+mod dashboard;
+mod frame;
+#[cfg(feature = "wgpu")]
+mod gpu;
+mod history;
+mod hud;
+mod icon;
+mod input;
+mod picker;
+mod scale;
+mod screenshot;
+mod snapshot;
+mod startup;
+mod simulation;
+mod survival_chart;
+mod timeline;
+mod tuning;
+mod watchdog;
+
+use allocator::{memory_stats, report_frame, set_capturing, set_thread_role, set_verbose, ThreadRole};
+use blend::DRAW_ORDER;
+use capabilities::{Capabilities, DisplayInfo};
+use capture::{Canvas, FrameCapture};
+use clock::{FrameLimiter, StallGuard, TurnClock};
+use recording::{Recording, RecordingWriter};
+use video::{VideoFormat, VideoRecorder};
+use scenario::Scenario;
+use telemetry::{TraceFormat, TraceSink};
+use config::Config;
+use locale::{bundle, fill, Language};
+use drops::DropSite;
+use metadata::RunMetadata;
+use frame::{FrameChart, FrameDrop, FrameTimer, Phase};
+use history::{History, HistoryEntry, Verdict};
+use hud::Hud;
+use scale::ScaledTarget;
+use screenshot::Screenshots;
+use input::{key_action, KeyAction, BURST_PARTICLES};
+use obstacle::Obstacle;
+use params::ParamRegistry;
+use picker::{PickerAction, ScenePicker, THUMBNAIL_SIZE};
+use population::{CohortHistory, PopulationHistory};
+use report::RunReport;
+use emitter::Emitter;
+use energy::EnergyHistory;
+use falloff::Falloff;
+use view::{Transform2D, ZOOM_STEP};
+use backend::AllocatorBackend;
+use fidelity::{Fidelity, TelemetryGovernor};
+use mutate::{Change, Mutations};
+use session::{Overlays, Session};
+use checkpoint::Checkpoint;
+use journal::{Journal, JournalEvent, JournalHeader, JournalReplay, JournalWriter};
+use palette::Palette;
+use boundary::EdgeBehavior;
+use freelist::{AllocationStrategy, ParticlePool};
+use survival::SurvivalCurve;
+use summary::RunSummary;
+use histogram::AllocationHistogram;
+use callsite::CallSites;
+use force::Force;
+use statehash::{Divergence, HashStream};
+use startup::StartupProfile;
+use zone::{Zone, ZoneArea, ZoneForce};
+use watchdog::Watchdog;
+use gauge::AllocationGauge;
+use guard::Quarantine;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use timeline::{Scrubber, Timeline, PAUSED_HEIGHT};
+use tuning::{TuningAction, TuningPanel};
+use allocation_graph::{AllocationGraph, GRAPH_HEIGHT};
+use simulation::{lock, Commands, Frame, Notice, Simulation};
+use handoff::Sprite;
+use camera::CameraView;
+use trail::TrailSettings;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+// With `ffi`, `python` or `wasm` the library installs it
+#[cfg(not(any(feature = "ffi", feature = "python", feature = "wasm")))]
 #[global_allocator]
-static ALLOCATOR: ReportingAllocator = ReportingAllocator;
+static ALLOCATOR: allocator::ReportingAllocator = allocator::ReportingAllocator;
+
+/// Shows particles in a window to depict the memory allocation made by rust.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Width of the window and of the world, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 1280.0)]
+    width: f64,
+
+    /// Height of the window and of the world, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 960.0)]
+    height: f64,
+
+    /// Run the simulation without a window for `--turns` turns, 10000 by
+    /// default, and print an allocation summary like `bench`
+    #[arg(long)]
+    headless: bool,
+
+    /// Run a second world beside the first in the window, with the options
+    /// given here on top of the others, like `--compare="--seed 2"` or
+    /// `--compare="--allocation-strategy pooled"`, each with a panel of
+    /// what it allocated and how long its turns took
+    #[arg(long, value_name = "OPTIONS", allow_hyphen_values = true)]
+    compare: Option<String>,
+
+    /// Particles spawned before the first turn, 1000 unless the scene says otherwise
+    #[arg(long, value_name = "N", global = true, value_parser = clap::value_parser!(i32).range(0..))]
+    initial_particles: Option<i32>,
+
+    /// Every turn spawns or culls up to this many particles at random, or
+    /// as many as a rate like `500/s` makes, 3 unless the scene says otherwise
+    #[arg(long, value_name = "RATE", global = true, value_parser = units::parse_spawn_rate)]
+    spawn_rate: Option<i32>,
+
+    /// Drive the world with a built-in scenario exercising the allocator:
+    /// `steady` holds 5000 particles, `sawtooth` grows to 100000 then clears
+    /// them all, `fragmentation` frees and allocates buffers of varied sizes
+    /// among the particles, and `vec-growth` never shrinks the particle list
+    #[arg(long, value_name = "NAME", global = true)]
+    scenario: Option<Scenario>,
+
+    /// Make particles bounce off each other, with the restitution of the
+    /// scene's `[collisions]` or 0.8
+    #[arg(long, global = true)]
+    collisions: bool,
+
+    /// What becomes of particles reaching the sides of the window, in place of
+    /// the scene's `edges`
+    #[arg(long, value_enum, value_name = "MODE", global = true)]
+    edges: Option<EdgeBehavior>,
+
+    /// Color the particles by age or speed with this palette, in place of the
+    /// scene's: fire, ice, heat, rainbow, contrast or one registered by the embedding code
+    #[arg(long, value_name = "NAME", global = true)]
+    palette: Option<String>,
+
+    /// Keep a copy of the particles every N turns so the paused timeline
+    /// can show the world as it was
+    #[arg(long, value_name = "TURNS")]
+    snapshot_every: Option<u64>,
+
+    /// Stop spawning and cull faster when the live heap approaches this
+    /// size, like `256MiB`, in place of the scene's `max_heap`
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_bytes, conflicts_with = "max_heap_mb")]
+    max_heap: Option<u64>,
+
+    /// `--max-heap` in mebibytes, as it was given before sizes had units
+    #[arg(long, value_name = "N", hide = true)]
+    max_heap_mb: Option<usize>,
+
+    /// Write a plot of spawns, deaths and population over the whole run to
+    /// this SVG file when the window closes
+    #[arg(long, value_name = "PATH")]
+    population_plot: Option<PathBuf>,
+
+    /// Write the per-turn spawns, deaths and population to this CSV file
+    /// when the window closes
+    #[arg(long, value_name = "PATH")]
+    population_csv: Option<PathBuf>,
+
+    /// Group the particles by the turn they were spawned on and write how
+    /// many of every cohort are still alive over the run to this CSV file at exit
+    #[arg(long, global = true, value_name = "PATH")]
+    cohorts_csv: Option<PathBuf>,
+
+    /// Turns of spawns making up a cohort of `--cohorts-csv`, which are also
+    /// the turns between two counts of the survivors
+    #[arg(long, global = true, value_name = "TURNS", default_value_t = 100)]
+    cohort_turns: u64,
+
+    /// Write every particle with the turn it was spawned on and its age to
+    /// this CSV file at exit
+    #[arg(long, global = true, value_name = "PATH")]
+    particles_csv: Option<PathBuf>,
+
+    /// Count the ages the particles die at and write the share of them
+    /// living past every age to this CSV file at exit
+    #[arg(long, global = true, value_name = "PATH")]
+    survival_csv: Option<PathBuf>,
+
+    /// Write the kinetic energy and momentum of the particles after every
+    /// turn to this CSV file at exit
+    #[arg(long, global = true, value_name = "PATH")]
+    energy_csv: Option<PathBuf>,
+
+    /// Write a hash of the state of the particles after every turn to this
+    /// file, to compare with another run using `hashes diff`. Needs --seed
+    #[arg(long, global = true, value_name = "PATH")]
+    hashes: Option<PathBuf>,
+
+    /// Write a standalone HTML report of a bench or window run to this file
+    /// at exit, with charts of the population, the time per turn or frame and
+    /// the sizes of the allocations, to share without any tool
+    #[arg(long, global = true, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// Language of the HUD and of the bench and HTML reports: `en` or `es`.
+    /// Messages on the terminal stay in English
+    #[arg(long, global = true, value_enum, default_value = "en", env = "MEMORY_PARTICLES_LANG")]
+    lang: Language,
+
+    /// Bright particles on solid black and opaque overlays, like
+    /// `high_contrast` in the `[accessibility]` table of a scene
+    #[arg(long, global = true)]
+    high_contrast: bool,
+
+    /// Slower particles, gentler fades, no camera cuts and no flashing
+    /// warnings, like `reduced_motion` in the `[accessibility]` table of a scene
+    #[arg(long, global = true)]
+    reduced_motion: bool,
+
+    /// Close the window after this many turns, not counting the warm-up,
+    /// showing the progress in the window title. With `--headless`, the
+    /// turns simulated
+    #[arg(long, value_name = "N")]
+    turns: Option<u64>,
+
+    /// Run this many turns, or this long like `5s` or `500ms`, before capturing statistics
+    /// and telemetry, to leave startup allocations out of the measurements
+    #[arg(long, value_name = "N", global = true)]
+    warmup: Option<Warmup>,
+
+    /// Scene file (TOML) describing emitters, obstacles, zones and sparks
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
+    /// Turns simulated per second in the windows, whatever their frame rate.
+    /// Speeds stay in pixels per 1/60 s, so particles move as fast at any rate
+    #[arg(long, value_name = "HZ", default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    turn_rate: u32,
+
+    /// Frame rate the window aims for; frames taking more than twice as long
+    /// as a frame at this rate are logged as dropped
+    #[arg(long, value_name = "FPS", default_value_t = 60)]
+    target_fps: u64,
+
+    /// Draw the particles at this fraction of the window resolution, then
+    /// stretch them over the window, for weak GPUs; overlays stay sharp.
+    /// Half on renderers drawing on the CPU, like llvmpipe, the whole on others
+    #[arg(long, value_name = "SCALE", value_parser = scale::parse_scale)]
+    render_scale: Option<f64>,
+
+    /// Open the window fullscreen, the world taking the size of the display
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// What draws the window
+    #[arg(long, value_enum, default_value_t = Frontend::Piston)]
+    frontend: Frontend,
+
+    /// Wait for the display before showing each frame, which also holds
+    /// `--target-fps` to its refresh rate, or draw frames as they come
+    #[arg(long, value_name = "on|off", default_value = "on", action = clap::ArgAction::Set,
+          value_parser = clap::builder::BoolishValueParser::new())]
+    vsync: bool,
+
+    /// The same as `--vsync off`, from before it
+    #[arg(long, hide = true)]
+    no_vsync: bool,
+
+    /// Most frames the window draws a second, waiting out the rest of each
+    /// frame once drawn; 0 draws them as fast as they come, to stress the
+    /// allocator, which with vsync on is still as fast as the display.
+    /// `--target-fps` by default
+    #[arg(long, value_name = "FPS")]
+    max_fps: Option<u64>,
+
+    /// Open the plainest window there is, to find out why the full program
+    /// doesn't start: allocations are neither timed nor reported, no overlay
+    /// is drawn and the window asks for OpenGL 2.1, without multisampling,
+    /// an icon or offscreen targets
+    #[arg(long)]
+    safe_mode: bool,
+
+    /// Report where the window is stuck when it completes no frame for this
+    /// many seconds: the phase of the event loop, the heap and the stacks of
+    /// all threads when gdb is installed
+    #[arg(long, value_name = "SECONDS")]
+    watchdog: Option<f64>,
+
+    /// Abort once the watchdog reported a hang, leaving a core dump
+    #[arg(long, requires = "watchdog")]
+    watchdog_abort: bool,
+
+    /// Print how long frames spent in each phase of the event loop, in Piston
+    /// and in this crate, when the window closes
+    #[arg(long)]
+    phase_timings: bool,
+
+    /// Spawn one particle for every N bytes allocated by the whole process,
+    /// instead of at random, so the particle density gauges memory churn
+    #[arg(long, value_name = "N", global = true)]
+    spawn_per_bytes: Option<u64>,
+
+    /// Serve particle-sized allocations from a free list of fixed-size blocks
+    /// instead of the system allocator, showing its counters in the title
+    #[arg(long, global = true)]
+    block_allocator: bool,
+
+    /// Where the boxes of new particles come from: the allocator, one per
+    /// spawn, a pool of the boxes of dead particles filled up front, as
+    /// large as what `--preallocate` makes room for, or a bump arena in the
+    /// allocator, in place of the block allocator
+    #[arg(long, alias = "alloc-mode", global = true, value_enum, value_name = "STRATEGY", default_value = "boxed")]
+    allocation_strategy: AllocationStrategy,
+
+    /// Allocator the reporting allocator defers to, jemalloc and mimalloc
+    /// being loaded from their shared libraries. It serves the process from
+    /// its first allocation, so the program is started again to change it
+    #[arg(long, global = true, value_enum, value_name = "BACKEND", default_value = "system", env = "MEMORY_PARTICLES_ALLOCATOR")]
+    allocator: AllocatorBackend,
+
+    /// Make room before the first turn for the particles of the scene's
+    /// `preallocate`, in their list and in the block allocator, which this
+    /// turns on, and for the per-turn histories of the run, so it allocates
+    /// nothing from the heap from the first turn on while the population
+    /// stays below that
+    #[arg(long, global = true)]
+    preallocate: bool,
+
+    /// When the particle list hands its spare room back: `prealloc:N` makes
+    /// room for N particles up front and never shrinks below it,
+    /// `shrink-every:K` shrinks it to the population every K turns, and
+    /// `never-shrink` keeps the room of the largest population. Every turn,
+    /// `shrink-every:1`, unless a scenario says otherwise
+    #[arg(long, value_name = "POLICY", global = true)]
+    vec_policy: Option<StoragePolicy>,
+
+    /// Record how many allocations go by before a freed address is allocated
+    /// again, and write the distribution as an SVG chart at exit
+    #[arg(long, global = true, value_name = "PATH")]
+    reuse_plot: Option<PathBuf>,
+
+    /// Write the allocations of the run at exit as a pprof profile, by tag
+    /// and size class, for `go tool pprof` and other pprof visualizers
+    #[arg(long, alias = "trace-out", global = true, value_name = "PATH")]
+    pprof: Option<PathBuf>,
+
+    /// Stream the particles, allocations and time of every frame as JSON to
+    /// the WebSocket and TCP clients connecting to this address
+    #[cfg(feature = "metrics")]
+    #[arg(long, global = true, value_name = "ADDR")]
+    metrics: Option<String>,
+
+    /// Report every allocation on its own line, rather than one summary line
+    /// per frame (per turn without a window) of their count, bytes and times
+    #[arg(long, global = true)]
+    alloc_verbose: bool,
+
+    /// Take the backtrace of one allocation in EVERY, 100 unless given as
+    /// `--alloc-backtraces=EVERY`, and print the call sites allocating the
+    /// most at exit
+    #[arg(
+        long,
+        global = true,
+        value_name = "EVERY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    alloc_backtraces: Option<u32>,
+
+    /// Trace every allocation of the window however late its frames run,
+    /// rather than fewer of them while frames take close to the target time
+    #[arg(long)]
+    full_telemetry: bool,
+
+    /// Write the allocation trace to this file instead of stderr, compressed
+    /// with zstd when its name ends in `.zst`, with every allocation as if
+    /// with `--alloc-verbose`
+    #[arg(long, global = true, value_name = "PATH", env = "MEMORY_PARTICLES_TRACE")]
+    trace_output: Option<PathBuf>,
+
+    /// Format of the allocation trace: tab-separated like it always was, or
+    /// CSV and JSON Lines with timestamps and alignments
+    #[arg(long, global = true, value_enum, default_value = "tsv", env = "MEMORY_PARTICLES_TRACE_FORMAT")]
+    trace_format: TraceFormat,
+
+    /// Record what the window shows of every turn to this file, for `play`.
+    /// A `.gif`, or a video ffmpeg writes like `.mp4`, gets an animation of
+    /// the first `--record-seconds` instead
+    #[arg(long, global = true, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Frames a second of simulated time in the animations of `--record`
+    #[arg(long, global = true, value_name = "FPS", default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    record_fps: u32,
+
+    /// Seconds of simulated time the animations of `--record` last
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = 10.0)]
+    record_seconds: f64,
+
+    /// Write the seed, the scene and the spawn decisions of every turn to
+    /// this journal, for `--replay` to run the same turns again. Runs
+    /// without `--seed` are given one at random
+    #[arg(long, global = true, value_name = "PATH")]
+    journal: Option<PathBuf>,
+
+    /// Run the turns of a journal again, with its scene, seed and window
+    /// size in place of those of the command line, in the window or in `bench`
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "journal")]
+    replay: Option<PathBuf>,
+
+    /// Write the scene, the window size, the overlays shown and the world to
+    /// this file when the window closes
+    #[arg(long, value_name = "PATH")]
+    session: Option<PathBuf>,
+
+    /// Carry on from the `--session` file, when there is one, as the window
+    /// was when it closed
+    #[arg(long, requires = "session")]
+    resume_session: bool,
+
+    /// File F5 saves the world to and F9 loads it back from
+    #[arg(long, value_name = "PATH", default_value = "world.checkpoint")]
+    checkpoint: PathBuf,
+
+    /// Write a zip archive to attach to issues when the run ends or panics:
+    /// the telemetry of the last `--debug-bundle-seconds`, the scene and
+    /// parameters in effect, the environment and the recent log. Written to
+    /// `debug-bundle.zip` unless given as `--collect-debug-bundle=PATH`
+    #[arg(long, global = true, value_name = "PATH", num_args = 0..=1, require_equals = true, default_missing_value = "debug-bundle.zip")]
+    collect_debug_bundle: Option<PathBuf>,
+
+    /// Seconds of telemetry the debug bundle keeps
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = 30.0)]
+    debug_bundle_seconds: f64,
+
+    /// Compression level of a `.zst` trace, from 1 (fastest) to 22 (smallest)
+    #[arg(long, global = true, value_name = "LEVEL", default_value_t = 3, value_parser = clap::value_parser!(i32).range(1..=22))]
+    trace_level: i32,
+
+    /// Print the allocations made from process start to the first rendered
+    /// frame, phase by phase
+    #[arg(long)]
+    profile_startup: bool,
+
+    /// Check particles for NaN and infinite values after every stage of their
+    /// step, taking offenders out and logging which force broke them
+    #[arg(long, global = true)]
+    check_finite: bool,
+
+    /// Override a tunable parameter of the scene, like `drag=0.1` or
+    /// `zone.0.strength=-0.2`; see `params` for the list. Can be repeated
+    #[arg(long, global = true, value_name = "NAME=VALUE")]
+    set: Vec<String>,
+
+    /// Save a picture of the world at exactly these turns, counted after the
+    /// warm-up, like `100,200,300`. Needs `--seed`
+    #[arg(long, global = true, value_name = "TURN,...", value_delimiter = ',')]
+    capture_frames: Vec<u64>,
+
+    /// Save the window as it shows it every N frames, like F12 does
+    #[arg(long, value_name = "N")]
+    screenshot_every: Option<u64>,
+
+    /// Directory the frames of `--capture-frames` and the screenshots are saved to
+    #[arg(long, global = true, value_name = "DIR", default_value = "frames")]
+    capture_dir: PathBuf,
+
+    /// Directory of the scene files offered by the Tab menu and `thumbnails`
+    #[arg(long, global = true, value_name = "DIR", default_value = "scenes")]
+    scenes_dir: PathBuf,
+
+    /// Make the mouse cursor a circle of this radius that particles flow
+    /// around, as long as it is over the window
+    #[arg(long, value_name = "PIXELS")]
+    cursor_radius: Option<f64>,
+
+    /// Seed the random generator so runs with the same seed and scene reproduce
+    #[arg(long, value_name = "N", global = true)]
+    seed: Option<u64>,
+
+    /// Update the particles on all cores. Runs still reproduce exactly with
+    /// `--seed`, whatever the number of threads, though not the sequential ones
+    #[arg(long, global = true)]
+    parallel: bool,
+
+    /// Threads of `--parallel`, which it implies: every core by default, but
+    /// the one drawing the window when there is one
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Frontend {
+    /// Piston on OpenGL, with every overlay, the scene and the camera
+    Piston,
+
+    /// wgpu on winit, the particles instanced on the GPU and nothing else;
+    /// built with the `wgpu` feature
+    Wgpu,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Work with allocation traces captured from previous runs
+    Trace {
+        #[command(subcommand)]
+        command: TraceCommand,
+    },
+
+    /// Run the simulation without a window and print an allocation summary.
+    /// Ctrl+C stops the run early and still prints the summary
+    Bench {
+        /// Number of turns to simulate after the warm-up
+        #[arg(long, default_value_t = 10_000)]
+        turns: u64,
+
+        /// Append the result to this JSON history file and compare it with the
+        /// best earlier run of the same scene on this machine
+        #[arg(long, value_name = "PATH")]
+        history: Option<PathBuf>,
+
+        /// Percentage of the best throughput that may be lost before the run
+        /// counts as a regression, which makes the command fail
+        #[arg(long, value_name = "PERCENT", default_value_t = 10.0)]
+        max_regression: f64,
+
+        /// Instead of the world, move its initial particles in these number
+        /// types, like `f64,f32,fixed`, and compare the throughput and drift
+        /// of each with f64 from the same seed
+        #[arg(long, value_enum, value_delimiter = ',', value_name = "TYPES")]
+        precision: Vec<precision::Precision>,
+    },
+
+    /// Run the headless benchmark for every combination of the parameters
+    /// listed in a sweep file and write the results to a CSV file
+    Sweep {
+        /// Sweep file (TOML) listing the populations, storages and drags to try
+        file: PathBuf,
+
+        /// CSV file the results are written to
+        #[arg(long, value_name = "PATH", default_value = "sweep.csv")]
+        output: PathBuf,
+    },
+
+    /// Compare where particles are stored: boxed one by one, shared through
+    /// Rc or Arc, in a single heap allocation, or in an mmap'd region, with
+    /// or without huge pages
+    #[cfg(unix)]
+    Storage {
+        /// Number of particles kept in every backend
+        #[arg(long, default_value_t = 100_000)]
+        particles: usize,
+
+        /// Number of turns the particles are moved for
+        #[arg(long, default_value_t = 500)]
+        turns: u64,
+
+        /// Backends to compare, in order
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "boxed,rc,arc,malloc,mmap,mmap-huge")]
+        backends: Vec<storage::Backend>,
+    },
+
+    /// Run the simulation without a window, showing a live dashboard of the
+    /// particle count, allocation rate, heap and turn rate in the terminal
+    Dashboard {
+        /// Turns per second to run, 0 for as many as possible
+        #[arg(long, default_value_t = 60)]
+        rate: u32,
+    },
+
+    /// Play back a recording made with `--record`, scrubbed with the mouse
+    /// along the bar at the bottom of the window
+    Play {
+        recording: PathBuf,
+    },
+
+    /// Run every scene headlessly for a number of turns and save a picture
+    /// of each, named after its file, for scene pickers and documentation
+    Thumbnails {
+        /// Scene files to picture, every one in `--scenes-dir` by default
+        scenes: Vec<PathBuf>,
+
+        /// Turns simulated before each picture is taken
+        #[arg(long, default_value_t = 600)]
+        turns: u64,
+
+        /// Width of the pictures in pixels, the height following the window's shape
+        #[arg(long, default_value_t = 320)]
+        width: u32,
+
+        /// Directory the pictures are written to
+        #[arg(long, value_name = "DIR", default_value = "thumbnails")]
+        output: PathBuf,
+    },
+
+    /// List the tunable parameters of the scene, with their types, ranges
+    /// and current values
+    Params,
+
+    /// Make allocations of known sizes, a particle `Vec` growth and a leak,
+    /// and check the instrumentation counts each of them exactly
+    Selftest,
+
+    /// Print how this binary was built and what it runs on: cargo features,
+    /// renderers, allocator, SIMD extensions of the CPU and the defaults of
+    /// the scene, to go with benchmark results
+    Info,
+
+    /// Check scene files for syntax errors, unknown keys, values out of
+    /// range and settings that cancel each other out, with their lines
+    Validate {
+        /// Scene files to check, every one in `--scenes-dir` by default
+        scenes: Vec<PathBuf>,
+    },
+
+    /// Work with the state hashes written by `--hashes`
+    Hashes {
+        #[command(subcommand)]
+        command: HashesCommand,
+    },
+
+    /// Work with scene files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Run several independent worlds at once, side by side in a grid.
+    /// Scenes and seeds are given per world and repeated when there are fewer
+    Grid {
+        /// Scene file of each world, `--config` for all of them when missing
+        scenes: Vec<PathBuf>,
+
+        /// Seed of each world, like `1,2,3`. Without them, worlds follow
+        /// `--seed` with consecutive seeds, or are unseeded
+        #[arg(long, value_name = "N,...", value_delimiter = ',')]
+        seeds: Vec<u64>,
+
+        /// Number of worlds per row, as square a grid as possible by default
+        #[arg(long, value_name = "N")]
+        columns: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HashesCommand {
+    /// Find the first turn after which two runs are in different states,
+    /// failing when there is one
+    Diff { a: PathBuf, b: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Upgrade a scene file written for an earlier version of the scene
+    /// format to the current one, keeping its comments and layout
+    Migrate {
+        scene: PathBuf,
+
+        /// File the upgraded scene is written to, in place of the scene when missing
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TraceCommand {
+    /// Compare the statistical summaries of two trace files
+    Diff { a: PathBuf, b: PathBuf },
+
+    /// Replay the allocation sizes of a trace against the backing allocator,
+    /// without running the simulation
+    Replay {
+        trace: PathBuf,
+
+        /// Number of most recent blocks kept alive before the oldest is freed
+        #[arg(long, default_value_t = 1000)]
+        live_window: usize,
+    },
+}
+
+impl Command {
+    // Long-running headless modes, which stop cleanly on Ctrl+C
+    fn stops_on_ctrl_c(&self) -> bool {
+        match self {
+            Command::Trace { command } => matches!(command, TraceCommand::Replay { .. }),
+            #[cfg(unix)]
+            Command::Storage { .. } => true,
+            Command::Sweep { .. } | Command::Thumbnails { .. } | Command::Dashboard { .. } | Command::Bench { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+// Acceleration towards or away from the cursor with Shift and a button held,
+// and the radius it is at full strength within
+const CURSOR_PULL: f64 = 0.4;
+const CURSOR_PULL_RADIUS: f64 = 80.0;
+
+// Particles thrown by Shift and the middle button
+const CURSOR_BURST: usize = 40;
+
+// Turns `--headless` runs without `--turns`, as many as `bench` does
+const HEADLESS_TURNS: u64 = 10_000;
+
+// Turns or frames of `--report` and `--preallocate` room is made for before
+// the run, enough for a bench run or quarter of an hour of the window at 60 fps
+const RESERVED_STEPS: u64 = 60_000;
+
+// Particles `--preallocate` makes room for without a soft cap or a
+// `preallocate` in the scene
+const PREALLOCATED_PARTICLES: usize = 4096;
+
+// Frames the event loop is let to draw a second, the limiter holding them
+// to `--max-fps`
+const UNCAPPED_EVENT_FPS: u64 = 100_000;
+
+// Pixels of the world between the arrows of the noise field of V
+const FIELD_ARROW_SPACING: f64 = 40.0;
+const FIELD_ARROW_COLOR: [f32; 4] = [0.6, 0.9, 1.0, 0.6];
+
+fn main() {
+    // Only reported with --profile-startup, but started before anything else allocates
+    let mut startup = StartupProfile::new();
+    let mut cli = Cli::parse();
+    select_backend(&cli);
+    set_thread_role(ThreadRole::Main);
+    cli.parallel |= cli.threads.is_some();
+    locale::set_language(cli.lang);
+    if cli.safe_mode {
+        allocator::set_passthrough(true);
+        set_capturing(false);
+    } else if cli.allocation_strategy == AllocationStrategy::Arena {
+        arena::enable();
+    } else if cli.block_allocator || cli.preallocate {
+        pool::enable();
+    }
+    if cli.reuse_plot.is_some() && !cli.safe_mode {
+        reuse::enable();
+    }
+    if cli.pprof.is_some() && !cli.safe_mode {
+        pprof::enable();
+    }
+    if let Some(every) = cli.alloc_backtraces.filter(|_| !cli.safe_mode) {
+        callsite::enable(every);
+    }
+    if cli.parallel {
+        let windowed = (cli.command.is_none() && !cli.headless) || matches!(cli.command, Some(Command::Grid { .. }));
+        let threads = cli.threads.map_or_else(|| Capabilities::detect().default_threads(windowed), |threads| threads as usize);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("rayon-{}", index))
+            .start_handler(|index| set_thread_role(ThreadRole::Rayon(index)));
+        if let Err(err) = pool.build_global() {
+            eprintln!("Could not start {} threads: {}", threads, err);
+        }
+    }
+    set_verbose(cli.alloc_verbose || cli.trace_output.is_some());
+    startup.mark("argument parsing");
+
+    // Before the trace writer, whose start waits long enough for the
+    // handler's thread to be up: allocating as it starts, it would otherwise
+    // land in the counts of seeded runs now and then
+    let headless = cli.headless && cli.command.is_none();
+    if cli.command.is_none() || cli.command.as_ref().is_some_and(Command::stops_on_ctrl_c) {
+        shutdown::install_handler();
+    }
+    start_telemetry(&cli);
+    if let Some(path) = &cli.collect_debug_bundle {
+        start_debug_bundle(&cli, path.clone());
+    }
+
+    if cli.compare.is_some() && (cli.command.is_some() || headless || cli.frontend == Frontend::Wgpu) {
+        eprintln!("--compare runs in the Piston window, without a command, --headless or --frontend wgpu");
+        process::exit(1);
+    }
+
+    match &cli.command {
+        None if headless => run_bench(&cli, cli.turns.unwrap_or(HEADLESS_TURNS), None, 0.0, frame_capture(&cli)),
+        None if cli.frontend == Frontend::Wgpu => run_gpu_window(&cli),
+        None if cli.compare.is_some() => run_compare(&cli),
+        None => run_window(&cli, cli.profile_startup.then_some(startup)),
+        Some(Command::Trace { command: TraceCommand::Diff { a, b } }) => diff_traces(a, b),
+        Some(Command::Trace { command: TraceCommand::Replay { trace, live_window } }) => replay_trace(trace, *live_window),
+        Some(Command::Sweep { file, output }) => run_sweep(&cli, file, output),
+        #[cfg(unix)]
+        Some(Command::Storage { particles, turns, backends }) => run_storage(&cli, *particles, *turns, backends),
+        Some(Command::Grid { scenes, seeds, columns }) => run_grid(&cli, scenes, seeds, *columns),
+        Some(Command::Params) => list_params(&cli),
+        Some(Command::Selftest) => run_selftest(),
+        Some(Command::Info) => print_info(&cli),
+        Some(Command::Validate { scenes }) => validate_scenes(&cli, scenes),
+        Some(Command::Hashes { command: HashesCommand::Diff { a, b } }) => diff_hashes(a, b),
+        Some(Command::Config { command: ConfigCommand::Migrate { scene, output } }) => migrate_scene(scene, output.as_deref()),
+        Some(Command::Play { recording }) => play_recording(recording),
+        Some(Command::Thumbnails { scenes, turns, width, output }) => write_thumbnails(&cli, scenes, *turns, *width, output),
+        Some(Command::Dashboard { rate }) => run_dashboard(&cli, *rate),
+        Some(Command::Bench { turns, precision, .. }) if !precision.is_empty() => run_precision(&cli, *turns, precision),
+        Some(Command::Bench { turns, history, max_regression, .. }) => run_bench(&cli, *turns, history.as_deref(), *max_regression, frame_capture(&cli)),
+    }
+    telemetry::finish();
+}
+
+// The backend was chosen by the first allocation, from the environment.
+// When the command line asks for another, the program starts over with the
+// environment asking for it
+fn select_backend(cli: &Cli) {
+    if let Some(failed) = backend::load_error() {
+        eprintln!("Could not load the {} allocator from any of {}", failed.name(), failed.libraries().join(", "));
+        process::exit(1);
+    }
+    if cli.allocator == backend::selected() {
+        return;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let program = std::env::current_exe().unwrap_or_else(|_| PathBuf::from(std::env::args_os().next().unwrap_or_default()));
+        let err = process::Command::new(program).args(std::env::args_os().skip(1)).env(backend::ENV_VAR, cli.allocator.name()).exec();
+        eprintln!("Could not start again with the {} allocator: {}", cli.allocator.name(), err);
+    }
+    #[cfg(not(unix))]
+    eprintln!("--allocator {} is only available on Unix", cli.allocator.name());
+    process::exit(1);
+}
+
+// Starts keeping what --collect-debug-bundle writes, and writes it from a
+// panic hook too, before the message of the panic
+fn start_debug_bundle(cli: &Cli, path: PathBuf) {
+    debug_bundle::enable(cli.debug_bundle_seconds);
+    let metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    let mut environment = String::new();
+    for (key, value) in metadata.fields() {
+        environment.push_str(&format!("{}: {}\n", key, value));
+    }
+    environment.push_str(&format!("capabilities: {}\n", metadata.capabilities.describe()));
+    environment.push_str(&format!("command_line: {}\n", std::env::args().collect::<Vec<_>>().join(" ")));
+    // What the window and the allocator are known to depend on
+    let mut variables: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| {
+            ["RUST_BACKTRACE", "RUST_LOG", "DISPLAY", "WAYLAND_DISPLAY", "XDG_SESSION_TYPE", "MALLOC_CONF"].contains(&name.as_str())
+                || ["MEMORY_PARTICLES_", "MESA_", "LIBGL_", "__GL"].iter().any(|prefix| name.starts_with(prefix))
+        })
+        .collect();
+    variables.sort();
+    for (name, value) in variables {
+        environment.push_str(&format!("env {}={}\n", name, value));
+    }
+    debug_bundle::set_file("environment.txt", environment);
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        debug_bundle::log(&format!("panic: {}", info));
+        match debug_bundle::write(&path) {
+            Ok(()) => eprintln!("debug bundle written to {}", path.display()),
+            Err(err) => eprintln!("Could not write debug bundle {}: {}", path.display(), err),
+        }
+        previous(info);
+    }));
+}
+
+// Puts the scene and the parameters in effect in the debug bundle
+fn bundle_config(world: &mut World) {
+    if !debug_bundle::is_enabled() {
+        return;
+    }
+    let scene = world.scene_text.clone().unwrap_or_else(|| "# the default scene\n".to_string());
+    debug_bundle::set_file("config/scene.toml", scene);
+    let registry = ParamRegistry::collect(world);
+    let params: String = registry.params.iter().map(|param| format!("{}={}\n", param.name, param.current())).collect();
+    debug_bundle::set_file("config/params.txt", params);
+}
+
+// Writes the --collect-debug-bundle archive at the end of a run, with the
+// scene and parameters the world ended with
+fn write_debug_bundle(cli: &Cli, world: &mut World) {
+    let Some(path) = &cli.collect_debug_bundle else {
+        return;
+    };
+    bundle_config(world);
+    match debug_bundle::write(path) {
+        Ok(()) => println!("debug bundle written to {}", path.display()),
+        Err(err) => eprintln!("Could not write debug bundle {}: {}", path.display(), err),
+    }
+}
+
+// Starts the writer of the allocation trace, to --trace-output or stderr
+fn start_telemetry(cli: &Cli) {
+    let sink = cli.trace_output.as_deref().map(|path| {
+        TraceSink::create(path, cli.trace_level).unwrap_or_else(|err| {
+            eprintln!("Could not create trace {}: {}", path.display(), err);
+            process::exit(1);
+        })
+    });
+    if let Err(err) = telemetry::start(sink, cli.trace_format) {
+        eprintln!("Could not start the trace writer: {}", err);
+        process::exit(1);
+    }
+}
+
+fn load_config(path: Option<&Path>) -> Config {
+    match path {
+        Some(path) => Config::load(path).unwrap_or_else(|err| {
+            eprintln!("Could not load scene {}: {}", path.display(), err);
+            process::exit(1);
+        }),
+        None => Config::default(),
+    }
+}
+
+// Builds a world from a scene and seed, with the other options of the
+// command line, and its initial particles
+fn build_world(cli: &Cli, config: Option<&Path>, seed: Option<u64>, width: f64, height: f64) -> World {
+    configure_world(cli, load_config(config), seed, width, height).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+// Like `build_world` from a loaded scene, failing when a `--set` doesn't apply to it
+fn configure_world(cli: &Cli, mut config: Config, seed: Option<u64>, width: f64, height: f64) -> Result<World, String> {
+    config.accessibility.high_contrast |= cli.high_contrast;
+    config.accessibility.reduced_motion |= cli.reduced_motion;
+    let initial_particles = cli.initial_particles.or(config.initial_particles).unwrap_or(INITIAL_PARTICLES);
+    let preallocated = config
+        .preallocate
+        .or(config.soft_cap.as_ref().map(|cap| cap.target * 2))
+        .unwrap_or(PREALLOCATED_PARTICLES);
+    let mut world = World::from_config(width, height, config);
+    if let Some(seed) = seed {
+        world.rng = StdRng::seed_from_u64(seed);
+    }
+    world.parallel = cli.parallel;
+    if let Some(spawn_rate) = cli.spawn_rate {
+        world.spawn_rate = spawn_rate;
+    }
+    if cli.collisions {
+        world.set_collisions(true);
+    }
+    if let Some(edges) = cli.edges {
+        world.edges = edges;
+    }
+    if let Some(name) = &cli.palette {
+        world.palette = Some(Palette::named(name)?);
+    }
+    if cli.check_finite {
+        world.quarantine = Some(Quarantine::new());
+    }
+    let max_heap = cli.max_heap.map(|bytes| bytes as usize).or(cli.max_heap_mb.map(|mb| mb * 1024 * 1024));
+    world.max_heap_bytes = max_heap.or(world.max_heap_bytes);
+    for assignment in &cli.set {
+        if let Err(err) = ParamRegistry::assign(&mut world, assignment) {
+            return Err(format!("Could not set {}: {}", assignment, err));
+        }
+    }
+    if cli.preallocate {
+        world.reserve(preallocated);
+        if arena::is_enabled() {
+            arena::prefill(preallocated);
+        } else {
+            pool::prefill(preallocated);
+        }
+    }
+    if cli.allocation_strategy == AllocationStrategy::Pooled {
+        world.particle_pool = Some(ParticlePool::with_capacity(preallocated));
+    }
+    if cli.survival_csv.is_some() {
+        world.survival = Some(SurvivalCurve::new());
+    }
+    world.add_shapes(initial_particles);
+    if let Some(scenario) = cli.scenario {
+        scenario.apply(&mut world);
+    }
+    if let Some(policy) = cli.vec_policy {
+        world.set_storage_policy(policy);
+    }
+    world.allocation_gauge = cli
+        .spawn_per_bytes
+        .map(|bytes| AllocationGauge::new(bytes, memory_stats().allocated_bytes));
+    Ok(world)
+}
+
+fn run_bench(cli: &Cli, turns: u64, history: Option<&Path>, max_regression: f64, mut captures: Option<FrameCapture>) {
+    let mut metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    metadata.scenario = cli.scenario;
+    metadata.vec_policy = cli.vec_policy;
+    telemetry::comment(&metadata.comment_header());
+
+    let replay = cli.replay.as_deref().map(open_journal);
+    let mut world = start_world(cli, replay, cli.width, cli.height);
+    bundle_config(&mut world);
+
+    // A replay runs all the turns of its journal, those of the warm-up of
+    // the run it replays included
+    let (turns, warmup) = match world.replay.as_ref().map(JournalReplay::progress) {
+        Some((_, replayed)) => (replayed as u64, None),
+        None => (turns, cli.warmup),
+    };
+
+    let mut recorder = start_recording(cli, &world);
+    let mut video = start_video(cli, &world);
+    let mut energy = EnergyHistory::new();
+    let mut hashes = hash_stream(cli);
+    let mut cohorts = cohort_history(cli);
+    if cli.preallocate {
+        let turns = turns.min(RESERVED_STEPS) as usize;
+        if cli.energy_csv.is_some() {
+            energy.reserve(turns);
+        }
+        if let Some(hashes) = &mut hashes {
+            hashes.reserve(turns);
+        }
+    }
+    let mut run_report = cli.report.as_ref().map(|_| RunReport::new(bundle().turn, bundle().turns, turns.min(RESERVED_STEPS) as usize));
+    #[cfg(feature = "metrics")]
+    let mut metrics = start_metrics(cli);
+    let report = bench::run(&mut world, turns, warmup, &mut |turn, time, world| {
+        let allocations = report_frame(turn);
+        debug_bundle::record(turn, world.current_turn, world.particles.len(), memory_stats().live_bytes, allocations);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &mut metrics {
+            metrics.publish(turn, world.current_turn, world.particles.len(), time);
+        }
+        if let Some(run_report) = &mut run_report {
+            run_report.record(time, world.particles.len());
+        }
+        if let Some(captures) = captures.as_mut().filter(|captures| captures.is_due(turn)) {
+            save_frame(captures, turn, world);
+        }
+        record_turn(&mut recorder, world);
+        record_video(&mut video, world);
+        if cli.energy_csv.is_some() {
+            energy.record(world.current_turn, world.particles.len(), world.energy());
+        }
+        if let Some(hashes) = &mut hashes {
+            hashes.record(world.current_turn, world.state_hash());
+        }
+        if let Some(cohorts) = &mut cohorts {
+            cohorts.record(world.current_turn - 1, world.particles.iter().map(|p| p.born));
+        }
+    });
+    if let Some(run_report) = &mut run_report {
+        run_report.finish();
+    }
+    finish_recording(recorder);
+    finish_video(cli, video);
+    finish_journal(cli, &world);
+    report.print(&metadata);
+    print_scenario(&world);
+    write_report(cli, run_report.as_ref(), bundle().bench_run, &report.rows(), &metadata);
+    write_energy_csv(cli, &energy, &metadata);
+    write_hashes(cli, hashes.as_ref(), &metadata);
+    write_cohorts(cli, cohorts.as_ref(), &world, &metadata);
+    write_survival(cli, &world, &metadata);
+    if let Some(quarantine) = &world.quarantine {
+        println!("{:<20}{}", "quarantined", quarantine.total);
+    }
+    write_reuse_plot(cli, &metadata);
+    write_pprof(cli, &metadata);
+    write_debug_bundle(cli, &mut world);
+    AllocationHistogram::now().print();
+    if callsite::is_enabled() {
+        CallSites::now().print();
+    }
+
+    if let Some(path) = history {
+        // A partial run would look like a regression, so it is left out
+        if report.interrupted() {
+            println!("{:<20}not recorded (interrupted)", "history");
+            return;
+        }
+        if !record_bench(path, HistoryEntry::new(&report, &metadata, cli.parallel), max_regression / 100.0) {
+            telemetry::finish();
+            process::exit(1);
+        }
+    }
+}
+
+// Adds a bench result to the history file, printing how it compares with the
+// best earlier one. Returns false when it regressed beyond `threshold`
+fn record_bench(path: &Path, entry: HistoryEntry, threshold: f64) -> bool {
+    let mut history = History::load(path).unwrap_or_else(|err| {
+        eprintln!("Could not read bench history {}: {}", path.display(), err);
+        process::exit(1);
+    });
+
+    let verdict = history.compare(&entry, threshold);
+    match verdict {
+        Verdict::First => println!("{:<20}first run of this scene on this machine", "history"),
+        Verdict::Ok { best, change } => {
+            println!("{:<20}{:+.1}% against the best of {:.0} turns per second", "history", change * 100.0, best)
+        }
+        Verdict::Regressed { best, change } => println!(
+            "{:<20}REGRESSION: {:+.1}% against the best of {:.0} turns per second",
+            "history",
+            change * 100.0,
+            best
+        ),
+    }
+
+    history.entries.push(entry);
+    if let Err(err) = history.save(path) {
+        eprintln!("Could not write bench history {}: {}", path.display(), err);
+        process::exit(1);
+    }
+    !matches!(verdict, Verdict::Regressed { .. })
+}
+
+fn run_dashboard(cli: &Cli, rate: u32) {
+    // Allocation lines would be drawn over the dashboard, unless redirected
+    if !telemetry::to_file() && io::stderr().is_terminal() {
+        set_capturing(false);
+    }
+    let mut metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    metadata.scenario = cli.scenario;
+    metadata.vec_policy = cli.vec_policy;
+    telemetry::comment(&metadata.comment_header());
+
+    let mut world = build_world(cli, cli.config.as_deref(), cli.seed, cli.width, cli.height);
+    if let Err(err) = dashboard::run(&mut world, rate) {
+        eprintln!("Could not run the dashboard: {}", err);
+        process::exit(1);
+    }
+}
+
+fn write_thumbnails(cli: &Cli, scenes: &[PathBuf], turns: u64, width: u32, output: &Path) {
+    set_capturing(false);
+    let mut scenes = scenes.to_vec();
+    if scenes.is_empty() {
+        scenes = picker::scene_files(&cli.scenes_dir).unwrap_or_else(|err| {
+            eprintln!("Could not list the scenes of {}: {}", cli.scenes_dir.display(), err);
+            process::exit(1);
+        });
+    }
+    if let Err(err) = std::fs::create_dir_all(output) {
+        eprintln!("Could not create {}: {}", output.display(), err);
+        process::exit(1);
+    }
+
+    // Every scene starts from the same seed, so thumbnails only change with the scenes
+    let seed = cli.seed.unwrap_or(0);
+    for scene in &scenes {
+        if shutdown::requested() {
+            break;
+        }
+        let config = match Config::load(scene) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Could not load scene {}: {}", scene.display(), err);
+                continue;
+            }
+        };
+        let (canvas, particles) = render_thumbnail(config, seed, turns, width as usize);
+        let name = scene.file_stem().map_or("scene".into(), |stem| stem.to_string_lossy());
+        let path = output.join(format!("{}.png", name));
+        match canvas.write_png(&path) {
+            Ok(()) => println!("{}: {} particles, {}", scene.display(), particles, path.display()),
+            Err(err) => eprintln!("Could not write {}: {}", path.display(), err),
+        }
+    }
+}
+
+// Runs a scene for `turns` turns from `seed` and draws it `width` pixels
+// wide, returning the picture and the number of particles in it
+fn render_thumbnail(config: Config, seed: u64, turns: u64, width: usize) -> (Canvas, usize) {
+    let initial_particles = config.initial_particles.unwrap_or(INITIAL_PARTICLES);
+    let mut world = World::from_config(1280.0, 960.0, config);
+    world.rng = StdRng::seed_from_u64(seed);
+    world.add_shapes(initial_particles);
+    for _ in 0..turns {
+        world.update();
+    }
+
+    let scale = width as f64 / world.width;
+    let height = (world.height * scale).round() as usize;
+    let canvas = Canvas::render(width, height, |ctx, canvas| {
+        let ctx = ctx.scale(scale, scale);
+        paint_background(world.background(world.current_turn), false, ctx, canvas);
+        draw_scene(&world, ctx, canvas);
+        draw_particles(world.particles.iter().map(|p| Sprite::of(p)), world.falloff.as_ref(), ctx, canvas);
+    });
+    (canvas, world.particles.len())
+}
+
+// A world for the scene of the picker at `path`, the default scene when `None`
+fn load_scene(cli: &Cli, path: Option<&Path>, width: f64, height: f64) -> Result<World, String> {
+    let config = match path {
+        Some(path) => Config::load(path).map_err(|err| format!("Could not load scene {}: {}", path.display(), err))?,
+        None => Config::default(),
+    };
+    configure_world(cli, config, cli.seed, width, height)
+}
+
+// The session of `--session` to carry on from with `--resume-session`, none
+// when there isn't one yet, as on the first launch
+fn resumed_session(cli: &Cli) -> Option<Session> {
+    let path = cli.session.as_ref().filter(|_| cli.resume_session)?;
+    match Session::load(path) {
+        Ok(session) => Some(session),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            eprintln!("Could not read session {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+// The world of a session, built from the scene text it kept and the command
+// line like any other, then carried on from where it was
+fn restore_session(cli: &Cli, session: Session) -> World {
+    let config = match &session.scene_text {
+        Some(text) => Config::from_toml(text).unwrap_or_else(|err| {
+            eprintln!("Could not load the scene of the session: {}", err);
+            process::exit(1);
+        }),
+        None => Config::default(),
+    };
+    let restored = configure_world(cli, config, cli.seed, session.width, session.height)
+        .and_then(|mut world| world.restore_state(session.world).map(|()| world));
+    restored.unwrap_or_else(|err| {
+        eprintln!("Could not resume the session: {}", err);
+        process::exit(1);
+    })
+}
+
+// The world saved to the checkpoint at `path` with F5, built from the scene
+// text it kept and the command line like any other, then carried on from
+// where it was
+fn load_checkpoint(cli: &Cli, path: &Path) -> Result<World, String> {
+    let checkpoint = Checkpoint::load(path).map_err(|err| format!("could not read checkpoint {}: {}", path.display(), err))?;
+    let config = checkpoint.config().map_err(|err| format!("could not load the scene of the checkpoint: {}", err))?;
+    let mut world = configure_world(cli, config, cli.seed, checkpoint.width, checkpoint.height)?;
+    world.restore_state(checkpoint.world)?;
+    Ok(world)
+}
+
+// Text of the scene file at `path`, kept by sessions; none for the default scene
+fn read_scene_text(path: Option<&Path>) -> Option<String> {
+    path.and_then(|path| std::fs::read_to_string(path).ok())
+}
+
+// Thumbnail of a scene of the picker. Scenes that don't load have none
+fn scene_thumbnail(cli: &Cli, entry: &picker::SceneEntry) -> Option<Canvas> {
+    let config = match &entry.path {
+        Some(path) => Config::load(path).ok()?,
+        None => Config::default(),
+    };
+    Some(render_thumbnail(config, cli.seed.unwrap_or(0), 300, THUMBNAIL_SIZE[0]).0)
+}
+
+fn list_params(cli: &Cli) {
+    set_capturing(false);
+    let mut world = build_world(cli, cli.config.as_deref(), cli.seed, cli.width, cli.height);
+    let registry = ParamRegistry::collect(&mut world);
+    println!("{:<24}{:<10}{:<20}value", "name", "type", "range");
+    for param in &registry.params {
+        println!("{:<24}{:<10}{:<20}{}", param.name, param.type_name(), param.range(), param.current());
+    }
+}
+
+// What results of this binary depend on, as `name value` lines under
+// headings. The allocator is the one this command line selects
+fn print_info(cli: &Cli) {
+    set_capturing(false);
+    let metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    let capabilities = &metadata.capabilities;
+    let list = |items: &[&str]| if items.is_empty() { "none".to_string() } else { items.join(" ") };
+    let section = |title: &str, lines: &[(&str, String)]| {
+        println!("{}", title);
+        for (name, value) in lines {
+            println!("  {:<22}{}", name, value);
+        }
+    };
+
+    section("build", &[
+        ("version", metadata.version.to_string()),
+        ("profile", metadata::build_profile().to_string()),
+        ("platform", metadata.platform.clone()),
+        ("features", list(&metadata.features)),
+    ]);
+    let window = if cli.safe_mode { "OpenGL 2.1 through gfx, --safe-mode" } else { "OpenGL 3.2 through gfx" };
+    let metrics = if cfg!(feature = "metrics") { "TCP and WebSocket with --metrics" } else { "not built in" };
+    let wgpu = if cfg!(feature = "wgpu") { "instanced particles with --frontend wgpu" } else { "not built in" };
+    section("renderers", &[
+        ("window", window.to_string()),
+        ("offscreen targets", if cli.safe_mode { "off".to_string() } else { "for --render-scale and trails".to_string() }),
+        ("canvas", "on the CPU, for --capture-frames and thumbnails".to_string()),
+        ("terminal", "the dashboard command".to_string()),
+        ("live metrics", metrics.to_string()),
+        ("wgpu window", wgpu.to_string()),
+    ]);
+    section("allocator", &[
+        ("backend", if cli.safe_mode { format!("{}, passthrough", cli.allocator.name()) } else { metadata.allocator.clone() }),
+        ("particle boxes", cli.allocation_strategy.name().to_string()),
+        ("counting", if cli.safe_mode { "off".to_string() } else { "every allocation, timed".to_string() }),
+    ]);
+    section("cpu", &[
+        ("cores", capabilities.cpu_cores.to_string()),
+        ("page size", capabilities.page_size.map_or("unknown".to_string(), |size| format!("{} B", size))),
+        ("simd", list(&capabilities.simd)),
+    ]);
+
+    let particle = Config::default().particle;
+    let mut world = World::from_config(cli.width, cli.height, Config::default());
+    let mut defaults = vec![
+        ("size", format!("{}x{}", cli.width, cli.height)),
+        ("turn rate", format!("{} Hz", cli.turn_rate)),
+        ("initial particles", INITIAL_PARTICLES.to_string()),
+        ("spawn rate", format!("{} per turn", world.spawn_rate)),
+        ("preallocated", PREALLOCATED_PARTICLES.to_string()),
+        ("particle size", format!("{} px", particle.size)),
+        ("particle speed", format!("{:?} px per turn", particle.speed)),
+        ("particle braking", format!("{:?}", particle.braking)),
+    ];
+    let registry = ParamRegistry::collect(&mut world);
+    defaults.extend(registry.params.iter().map(|param| (param.name.as_str(), param.current())));
+    section("defaults", &defaults);
+}
+
+fn run_selftest() {
+    let checks = selftest::run();
+    for check in &checks {
+        let verdict = if check.passed { "ok" } else { "FAILED" };
+        println!("{:<20}{:<8}{}", check.name, verdict, check.detail);
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if failed > 0 {
+        println!("{} of {} checks failed", failed, checks.len());
+        telemetry::finish();
+        process::exit(1);
+    }
+}
+
+fn validate_scenes(cli: &Cli, scenes: &[PathBuf]) {
+    set_capturing(false);
+    let scenes = if scenes.is_empty() {
+        picker::scene_files(&cli.scenes_dir).unwrap_or_else(|err| {
+            eprintln!("Could not list the scenes in {}: {}", cli.scenes_dir.display(), err);
+            process::exit(1);
+        })
+    } else {
+        scenes.to_vec()
+    };
+
+    let mut errors = 0;
+    for scene in &scenes {
+        let problems = match std::fs::read_to_string(scene) {
+            Ok(text) => validate::validate(&text),
+            Err(err) => {
+                eprintln!("Could not read scene {}: {}", scene.display(), err);
+                errors += 1;
+                continue;
+            }
+        };
+        if problems.is_empty() {
+            println!("{}: ok", scene.display());
+        }
+        for problem in &problems {
+            println!("{}: {}", scene.display(), problem);
+        }
+        errors += problems.iter().filter(|problem| problem.severity == validate::Severity::Error).count();
+    }
+    if errors > 0 {
+        println!("{} errors in {} scenes", errors, scenes.len());
+        telemetry::finish();
+        process::exit(1);
+    }
+}
+
+fn migrate_scene(scene: &Path, output: Option<&Path>) {
+    set_capturing(false);
+    let migration = std::fs::read_to_string(scene).map_err(|err| err.to_string()).and_then(|text| migrate::migrate(&text));
+    let migration = migration.unwrap_or_else(|err| {
+        eprintln!("Could not upgrade scene {}: {}", scene.display(), err);
+        process::exit(1);
+    });
+    if !migration.upgraded() && output.is_none() {
+        println!("{}: already version {}", scene.display(), migrate::SCHEMA_VERSION);
+        return;
+    }
+
+    // What the upgrades can't fix was wrong in the old version too. Nothing is
+    // written then, so the upgrades run again once it is fixed
+    if let Err(err) = toml::from_str::<Config>(&migration.text) {
+        eprintln!("Could not upgrade scene {}: {}", scene.display(), err.message().trim().replace('\n', ", "));
+        process::exit(1);
+    }
+
+    let output = output.unwrap_or(scene);
+    if let Err(err) = std::fs::write(output, &migration.text) {
+        eprintln!("Could not write {}: {}", output.display(), err);
+        process::exit(1);
+    }
+    for change in &migration.changes {
+        println!("{}: {}", output.display(), change.message);
+    }
+    println!(
+        "{}: upgraded from version {} to version {}",
+        output.display(),
+        migration.from,
+        migrate::SCHEMA_VERSION
+    );
+}
+
+fn run_sweep(cli: &Cli, file: &Path, output: &Path) {
+    let sweep = sweep::Sweep::load(file).unwrap_or_else(|err| {
+        eprintln!("Could not load sweep {}: {}", file.display(), err);
+        process::exit(1);
+    });
+    let metadata = RunMetadata::new(sweep.scene.as_deref(), sweep.seed);
+    telemetry::comment(&metadata.comment_header());
+
+    let config = load_config(sweep.scene.as_deref());
+    let results = sweep::run(&sweep, &config, cli.warmup);
+    if let Err(err) = sweep::write_csv(output, &results, &metadata) {
+        eprintln!("Could not write sweep results {}: {}", output.display(), err);
+        process::exit(1);
+    }
+    println!("{} of {} runs written to {}", results.len(), sweep.points().len(), output.display());
+}
+
+#[cfg(unix)]
+fn run_storage(cli: &Cli, particles: usize, turns: u64, backends: &[storage::Backend]) {
+    let mut reports = Vec::with_capacity(backends.len());
+    for &backend in backends {
+        match storage::run(backend, particles, turns, cli.seed.unwrap_or(0)) {
+            Ok(report) => reports.push(report),
+            Err(err) => eprintln!("Could not set up {} storage: {}", backend.name(), err),
+        }
+        if shutdown::requested() {
+            break;
+        }
+    }
+    storage::print_reports(&reports);
+}
+
+fn run_precision(cli: &Cli, turns: u64, precisions: &[precision::Precision]) {
+    set_capturing(false);
+    let metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    print!("{}", metadata.comment_header());
+    let world = build_world(cli, cli.config.as_deref(), cli.seed, cli.width, cli.height);
+    precision::print_reports(&precision::compare(&world, turns, precisions));
+}
+
+fn load_trace(path: &Path) -> trace::TraceSummary {
+    trace::TraceSummary::load(path).unwrap_or_else(|err| {
+        eprintln!("Could not read trace {}: {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+fn diff_traces(a: &Path, b: &Path) {
+    let summary_a = load_trace(a);
+    let summary_b = load_trace(b);
+    trace::print_diff(
+        &a.display().to_string(),
+        &summary_a,
+        &b.display().to_string(),
+        &summary_b,
+    );
+}
+
+// The recording of --record, if any
+fn start_recording(cli: &Cli, world: &World) -> Option<RecordingWriter> {
+    let path = cli.record.as_deref().filter(|path| VideoFormat::of(path).is_none())?;
+    match RecordingWriter::create(path, world.width, world.height) {
+        Ok(recorder) => Some(recorder),
+        Err(err) => {
+            eprintln!("Could not create recording {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+// Records the turn just simulated; recording stops at the first error
+fn record_turn(recorder: &mut Option<RecordingWriter>, world: &World) {
+    let Some(writer) = recorder else {
+        return;
+    };
+    let particles = world.particles.iter().map(|p| &**p);
+    if let Err(err) = writer.write_frame(world.current_turn, world.background(world.current_turn), particles) {
+        eprintln!("Could not record turn {}, recording stopped: {}", world.current_turn, err);
+        *recorder = None;
+    }
+}
+
+// The journal of --replay; it does not start if it can't be read
+fn open_journal(path: &Path) -> Journal {
+    let journal = Journal::open(path).unwrap_or_else(|err| {
+        eprintln!("Could not open journal {}: {}", path.display(), err);
+        process::exit(1);
+    });
+    if journal.is_empty() {
+        eprintln!("Journal {} has no turns", path.display());
+        process::exit(1);
+    }
+    journal
+}
+
+// The world a bench or the window starts with: that of `replay`, replaying
+// it, or that of the command line. Runs writing a journal are seeded, at
+// random without --seed, so the journal can run them again
+fn start_world(cli: &Cli, replay: Option<Journal>, width: f64, height: f64) -> World {
+    if let Some(journal) = replay {
+        let header = &journal.header;
+        let config = if header.scene.is_empty() { Ok(Config::default()) } else { Config::from_toml(&header.scene) };
+        let world = config
+            .map_err(|err| format!("Could not load the scene of the journal: {}", err))
+            .and_then(|config| configure_world(cli, config, Some(header.seed), header.width, header.height));
+        let mut world = world.unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        println!("replaying {} turns of seed {}", journal.len(), header.seed);
+        world.replay = Some(JournalReplay::new(journal));
+        return world;
+    }
+
+    let seed = cli.seed.or_else(|| cli.journal.as_ref().map(|_| rand::random()));
+    let mut world = build_world(cli, cli.config.as_deref(), seed, width, height);
+    if let Some(path) = &cli.journal {
+        let scene = read_scene_text(cli.config.as_deref()).unwrap_or_default();
+        let header = JournalHeader { width, height, seed: seed.unwrap_or_default(), scene };
+        match JournalWriter::create(path, &header) {
+            Ok(journal) => world.journal = Some(journal),
+            Err(err) => {
+                eprintln!("Could not create journal {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+    world
+}
+
+// Writes what is left of the journal of `world`, which ends with it
+fn finish_journal(cli: &Cli, world: &World) {
+    let (Some(journal), Some(path)) = (&world.journal, &cli.journal) else {
+        return;
+    };
+    match journal.finish() {
+        Ok(()) => println!("journal: {} turns written to {}", journal.turns(), path.display()),
+        Err(err) => eprintln!("Could not write journal {}: {}", path.display(), err),
+    }
+}
+
+fn finish_recording(recorder: Option<RecordingWriter>) {
+    if let Some(Err(err)) = recorder.map(RecordingWriter::finish) {
+        eprintln!("Could not finish the recording: {}", err);
+    }
+}
+
+// The animation of --record out.gif or out.mp4, if any
+fn start_video(cli: &Cli, world: &World) -> Option<VideoRecorder> {
+    let path = cli.record.as_deref()?;
+    let format = VideoFormat::of(path)?;
+    let size = [world.width as usize, world.height as usize];
+    match VideoRecorder::create(path, format, size, cli.record_fps, cli.record_seconds, cli.turn_rate) {
+        Ok(video) => Some(video),
+        Err(err) => {
+            eprintln!("Could not record {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+// Draws the turn just simulated when a frame of the animation is due on it
+fn record_video(video: &mut Option<VideoRecorder>, world: &World) {
+    let Some(recorder) = video.as_mut() else {
+        return;
+    };
+    if !recorder.turn() {
+        return;
+    }
+    let [width, height] = recorder.size();
+    let canvas = Canvas::render(width, height, |ctx, canvas| draw_frame(world, ctx, canvas));
+    if let Err(err) = recorder.push(&canvas) {
+        eprintln!("Could not record turn {}, recording stopped: {}", world.current_turn, err);
+        *video = None;
+    }
+}
+
+fn finish_video(cli: &Cli, video: Option<VideoRecorder>) {
+    let (Some(video), Some(path)) = (video, &cli.record) else {
+        return;
+    };
+    match video.finish() {
+        Ok(()) => println!("animation written to {}", path.display()),
+        Err(err) => eprintln!("Could not finish {}: {}", path.display(), err),
+    }
+}
+
+// Height of the bar along the bottom of the player, scrubbed with the mouse
+const SCRUB_BAR_HEIGHT: f64 = 14.0;
+
+// Plays a recording back at 60 turns per second. Space pauses, the arrow
+// keys step while paused and dragging along the bottom bar jumps to any turn
+fn play_recording(path: &Path) {
+    let recording = Recording::open(path).unwrap_or_else(|err| {
+        eprintln!("Could not open recording {}: {}", path.display(), err);
+        process::exit(1);
+    });
+    if recording.is_empty() {
+        eprintln!("Recording {} has no turns", path.display());
+        process::exit(1);
+    }
+    if !recording.is_complete() {
+        eprintln!("Recording {} was cut short, playing its {} whole turns", path.display(), recording.len());
+    }
+
+    let (width, height) = (recording.width, recording.height);
+    let mut window: PistonWindow = WindowSettings::new("particles", [width, height])
+        .exit_on_esc(true)
+        .build()
+        .expect("Could not create a window.");
+    set_window_icon(&window);
+    window.set_ups(60);
+
+    let last = recording.len() - 1;
+    let mut current: usize = 0;
+    let mut paused = false;
+    let mut scrubbing = false;
+    let mut cursor = [0.0, 0.0];
+    let mut title = String::new();
+
+    while let Some(event) = window.next() {
+        if let Some(position) = event.mouse_cursor_args() {
+            cursor = position;
+        }
+        match event.press_args() {
+            Some(Button::Keyboard(Key::Space)) => paused = !paused,
+            Some(Button::Keyboard(Key::Left)) if paused => current = current.saturating_sub(1),
+            Some(Button::Keyboard(Key::Right)) if paused => current = (current + 1).min(last),
+            Some(Button::Keyboard(Key::Home)) => current = 0,
+            Some(Button::Keyboard(Key::End)) => current = last,
+            Some(Button::Mouse(MouseButton::Left)) if cursor[1] >= height - SCRUB_BAR_HEIGHT => scrubbing = true,
+            _ => {}
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = event.release_args() {
+            scrubbing = false;
+        }
+        if scrubbing {
+            // Any frame is a lookup in the index, so dragging stays smooth
+            current = ((cursor[0] / width).clamp(0.0, 1.0) * last as f64).round() as usize;
+        }
+        if event.update_args().is_some() && !paused && !scrubbing {
+            current = (current + 1).min(last);
+        }
+
+        let frame = recording.frame(current).expect("frame index within the recording");
+        let new_title = format!(
+            "particles - turn {} ({} of {}), {} particles{}",
+            frame.turn,
+            current + 1,
+            recording.len(),
+            frame.len(),
+            if paused { " - paused" } else { "" }
+        );
+        if new_title != title {
+            window.set_title(new_title.clone());
+            title = new_title;
+        }
+
+        window.draw_2d(&event, |ctx, renderer, _device| {
+            frame.draw(ctx, renderer);
+            let bar = [0.0, height - SCRUB_BAR_HEIGHT, width, SCRUB_BAR_HEIGHT];
+            rectangle([0.0, 0.0, 0.0, 0.6], bar, ctx.transform, renderer);
+            let played = width * current as f64 / last.max(1) as f64;
+            rectangle([0.9, 0.8, 0.3, 0.9], [0.0, height - SCRUB_BAR_HEIGHT, played, SCRUB_BAR_HEIGHT], ctx.transform, renderer);
+        });
+    }
+}
+
+// Composes the window title from the state worth seeing in the taskbar:
+// progress of a run limited in turns, memory pressure and the last status message
+fn window_title(progress: Option<f64>, under_pressure: bool, status: Option<&str>) -> String {
+    let mut title = String::from("particles");
+    if let Some(progress) = progress {
+        title.push_str(&format!(" [{:.0}%]", progress * 100.0));
+    }
+    if under_pressure {
+        title.push_str(" - memory pressure");
+    }
+    if let Some(status) = status {
+        title.push_str(" - ");
+        title.push_str(status);
+    }
+    title
+}
+
+// Reads the OpenGL version and renderer of the window and the refresh rate
+// of its monitor, the fastest of its modes at its current size, and records
+// them for the metadata of the run
+fn detect_display(window: &PistonWindow) -> Capabilities {
+    let info = window.device.get_info();
+    let version = &info.version;
+    let gl_version = match version.vendor_info {
+        "" => format!("{}.{}", version.major, version.minor),
+        vendor => format!("{}.{} {}", version.major, version.minor, vendor),
+    };
+    let refresh_rate = window.window.ctx.window().current_monitor().and_then(|monitor| {
+        monitor.video_modes().filter(|mode| mode.size() == monitor.size()).map(|mode| mode.refresh_rate() as u32).max()
+    });
+    capabilities::set_display(DisplayInfo { gl_version, gl_renderer: info.platform_name.renderer.to_string(), refresh_rate });
+    Capabilities::detect()
+}
+
+fn set_window_icon(window: &PistonWindow) {
+    let icon = glutin::window::Icon::from_rgba(icon::icon_rgba(), icon::ICON_SIZE, icon::ICON_SIZE);
+    match icon {
+        Ok(icon) => window.window.ctx.window().set_window_icon(Some(icon)),
+        Err(err) => eprintln!("Could not set the window icon: {}", err),
+    }
+}
+
+// Radius around a picked particle in which neighbors are counted
+const NEIGHBOR_RADIUS: f64 = 20.0;
+
+// Describes the particle closest to `point`, for the window title
+fn describe_nearest(world: &World, point: Vec2d<f64>) -> String {
+    let Some(nearest) = world.nearest_particle(point) else {
+        return "no particles".to_string();
+    };
+
+    // The particle is among those within the radius of its own center
+    let particle = &world.particles[nearest];
+    let neighbors = world.query_radius(behavior::center(particle), NEIGHBOR_RADIUS).count().saturating_sub(1);
+    let [x, y] = particle.position;
+    let [vx, vy] = particle.velocity;
+    let mut description = format!(
+        "particle {} at ({:.0}, {:.0}) moving ({:.2}, {:.2}), alpha {:.2}, {} neighbors within {} px",
+        nearest,
+        x,
+        y,
+        vx,
+        vy,
+        particle.color[3],
+        neighbors,
+        NEIGHBOR_RADIUS
+    );
+    description.push_str(&format!(", born on turn {}", particle.born));
+    if particle.pinned {
+        description.push_str(", pinned");
+    }
+    if let Some(state) = world.behavior.state_name(particle) {
+        description.push_str(&format!(", {} for {} turns", state, particle.state_turns));
+    }
+    if particle.user_data.iter().any(|&byte| byte != 0) {
+        let bytes: String = particle.user_data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        description.push_str(&format!(", user data {}", bytes));
+    }
+    description
+}
+
+fn toggle_pin_nearest(world: &mut World, point: Vec2d<f64>) -> String {
+    let Some(nearest) = world.nearest_particle(point) else {
+        return "no particles".to_string();
+    };
+    let particle = &mut world.particles[nearest];
+    particle.pinned = !particle.pinned;
+    format!("particle {} {}", nearest, if particle.pinned { "pinned" } else { "unpinned" })
+}
+
+// A line like `mutation 2: drag 0 -> 0.0123, sparks.count 12 -> 15`
+fn describe_mutation(what: &str, number: usize, changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "nothing to mutate in this scene".to_string();
+    }
+    let changes: Vec<String> = changes.iter().map(|c| format!("{} {} -> {}", c.name, c.before, c.after)).collect();
+    format!("{} {}: {}", what, number, changes.join(", "))
+}
+
+// Prints what a mutation changed and the `--set` arguments giving the scene
+// file every mutation still applied
+fn log_mutation(mutations: &Mutations, world: &mut World, description: &str) {
+    println!("{}", description);
+    let assignments = mutations.assignments(world);
+    if assignments.is_empty() {
+        println!("  back to the scene as loaded");
+    } else {
+        let arguments: Vec<String> = assignments.iter().map(|assignment| format!("--set {}", assignment)).collect();
+        println!("  reproduce with: {}", arguments.join(" "));
+    }
+}
+
+// Moves the particles to the next allocation strategy, telling the trace
+// where the allocations of the new one start
+fn switch_storage(world: &mut World) -> String {
+    let strategy = world.allocation_strategy().next();
+    let (before, start) = (memory_stats(), Instant::now());
+    let moved = world.set_allocation_strategy(strategy);
+    let (after, time) = (memory_stats(), start.elapsed());
+    let text = format!(
+        "switched to {} storage at turn {}, {} particles moved in {:.1} ms with {} allocations of {} bytes",
+        strategy.name(),
+        world.current_turn,
+        moved,
+        time.as_secs_f64() * 1e3,
+        after.allocations - before.allocations,
+        after.allocated_bytes - before.allocated_bytes
+    );
+    println!("{}", text);
+    telemetry::comment(&format!("# {}\n", text));
+    text
+}
+
+// Switches the forces of a kind off or back on; when the world has none of
+// the kind, adds one, attractors at the cursor
+fn toggle_force(world: &mut World, kind: &'static str, cursor: Vec2d<f64>) -> String {
+    if !world.forces.iter().any(|force| force.kind() == kind) {
+        let Some(force) = Force::default_of(kind, cursor) else {
+            return format!("no {} force", kind);
+        };
+        world.add_force(force);
+        return format!("added {}", kind);
+    }
+    let on = world.toggle_forces(kind);
+    format!("{} {}", kind, if on { "on" } else { "off" })
+}
+
+fn draw_obstacles<G: Graphics>(obstacles: &[Obstacle], ctx: Context, renderer: &mut G) {
+    let color = [0.4, 0.45, 0.45, 1.0];
+    for obstacle in obstacles {
+        match obstacle {
+            Obstacle::Circle { center, radius } => {
+                let bounds = [center[0] - radius, center[1] - radius, radius * 2.0, radius * 2.0];
+                ellipse(color, bounds, ctx.transform, renderer);
+            }
+            Obstacle::Rect { position, size } => {
+                rectangle(color, [position[0], position[1], size[0], size[1]], ctx.transform, renderer);
+            }
+        }
+    }
+}
+
+// Zones are drawn as faint areas tinted by the kind of force they apply
+fn draw_zones<G: Graphics>(zones: &[Zone], ctx: Context, renderer: &mut G) {
+    for zone in zones {
+        let color = match zone.force {
+            ZoneForce::Constant { .. } => [0.2, 0.6, 1.0, 0.08],
+            ZoneForce::Radial { .. } => [0.8, 0.3, 1.0, 0.08],
+            ZoneForce::Drag { .. } => [1.0, 0.3, 0.2, 0.08],
+        };
+        match zone.area {
+            ZoneArea::Circle { center, radius } => {
+                let bounds = [center[0] - radius, center[1] - radius, radius * 2.0, radius * 2.0];
+                ellipse(color, bounds, ctx.transform, renderer);
+            }
+            ZoneArea::Rect { position, size } => {
+                rectangle(color, [position[0], position[1], size[0], size[1]], ctx.transform, renderer);
+            }
+        }
+    }
+}
+
+// Arrows of the noise forces on a grid over the world, pointing where they
+// push, as long as the grid is wide at the strongest push they can give
+fn draw_noise_field<G: Graphics>(frame: &Frame, view: &Transform2D, ctx: Context, renderer: &mut G) {
+    let (scenery, turn) = (&frame.scenery, frame.world.turn);
+    let strongest: f64 = scenery
+        .noises
+        .iter()
+        .map(|force| match force {
+            Force::Noise { amplitude, .. } => amplitude.abs(),
+            _ => 0.0,
+        })
+        .sum();
+    if strongest <= 0.0 {
+        return;
+    }
+
+    let size = [scenery.width, scenery.height];
+    let ctx = camera_context(scenery.camera_view(turn), size, view.apply(ctx));
+    let scale = FIELD_ARROW_SPACING / strongest;
+    let (columns, rows) = ((size[0] / FIELD_ARROW_SPACING).ceil() as usize, (size[1] / FIELD_ARROW_SPACING).ceil() as usize);
+    for (column, row) in (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))) {
+        let from = [(column as f64 + 0.5) * FIELD_ARROW_SPACING, (row as f64 + 0.5) * FIELD_ARROW_SPACING];
+        let push = scenery.noises.iter().fold([0.0, 0.0], |sum, force| {
+            let [x, y] = force.acceleration(from, turn);
+            [sum[0] + x, sum[1] + y]
+        });
+        let length = push[0].hypot(push[1]) * scale;
+        if length < 1.0 {
+            continue;
+        }
+        let to = [from[0] + push[0] * scale, from[1] + push[1] * scale];
+        line_from_to(FIELD_ARROW_COLOR, 0.5, from, to, ctx.transform, renderer);
 
-// Provides a fairly accurate indication of time taken for dynamic memory allocation
-struct ReportingAllocator; 
+        // The head, two strokes back from the tip at 30 degrees to the shaft
+        let back = [(from[0] - to[0]) / length, (from[1] - to[1]) / length];
+        let head = length.min(FIELD_ARROW_SPACING) / 4.0;
+        for side in [-0.5, 0.5] {
+            let (cos, sin) = (0.75f64.sqrt(), side);
+            let stroke = [back[0] * cos - back[1] * sin, back[0] * sin + back[1] * cos];
+            line_from_to(FIELD_ARROW_COLOR, 0.5, to, [to[0] + stroke[0] * head, to[1] + stroke[1] * head], ctx.transform, renderer);
+        }
+    }
+}
 
-unsafe impl GlobalAlloc for ReportingAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let start = Instant::now();
-        
-        // Defers the memory allocation to the default memory allocator;
-        let ptr = System.alloc(layout); 
+// The world of `frame` as the window shows it under its overlays. While
+// paused on `shown`, the particles are shown as they were at the selected
+// turn. The background is painted over the frame before by its alpha with
+// `trails`, and the rest through the camera of the scene, then the view of
+// the user
+fn draw_world<G: Graphics>(frame: &Frame, shown: Option<(&Timeline, usize)>, view: &Transform2D, trails: bool, ctx: Context, renderer: &mut G) {
+    let (world, scenery) = (&frame.world, &frame.scenery);
+    let turn = shown_turn(world.turn, shown);
+    paint_background(scenery.background(turn), trails, ctx, renderer);
 
-        let end = Instant::now();
-        let time_taken = end - start;
-        let bytes_requested = layout.size();
+    let size = [scenery.width, scenery.height];
+    let ctx = camera_context(scenery.camera_view(turn), size, view.apply(ctx));
+    draw_zones(&scenery.zones, ctx, renderer);
+    draw_obstacles(&scenery.obstacles, ctx, renderer);
+    draw_particles(world.sparks.iter().copied(), scenery.falloff.as_ref(), ctx, renderer);
+    if let Some(Obstacle::Circle { center, radius }) = world.cursor_obstacle {
+        let bounds = [center[0] - radius, center[1] - radius, radius * 2.0, radius * 2.0];
+        ellipse([1.0, 1.0, 1.0, 0.06], bounds, ctx.transform, renderer);
+    }
+    if let Some(Force::Attractor { center, radius, strength }) = world.cursor_force {
+        let color = if strength > 0.0 { [0.4, 0.7, 1.0, 0.5] } else { [1.0, 0.5, 0.3, 0.5] };
+        let bounds = [center[0] - radius, center[1] - radius, radius * 2.0, radius * 2.0];
+        Ellipse::new_border(color, 1.0).draw(bounds, &ctx.draw_state, ctx.transform, renderer);
+    }
 
-        eprintln!("{}\t{}", bytes_requested, time_taken.as_nanos());
-        ptr              
+    // Show the world as it was at the selected turn when a snapshot exists,
+    // and as it is at the latest one, where stepping leaves it
+    let snapshot = shown.and_then(|(timeline, selected)| timeline.snapshot_at(selected).filter(|_| selected + 1 < timeline.len()));
+    let settings = scenery.trail.as_ref();
+    match snapshot {
+        Some(particles) => {
+            draw_trails(settings, size, particles.iter(), ctx, renderer);
+            draw_particles(particles.iter().map(Sprite::of), scenery.falloff.as_ref(), ctx, renderer);
+        }
+        None => {
+            if let Some(settings) = settings {
+                let mut trails = &world.trails[..];
+                for sprite in &world.sprites {
+                    let (points, rest) = trails.split_at(sprite.trail as usize);
+                    draw_trail(points.iter().copied(), sprite, settings, size, ctx, renderer);
+                    trails = rest;
+                }
+            }
+            draw_particles(world.sprites.iter().copied(), scenery.falloff.as_ref(), ctx, renderer);
+        }
     }
+}
 
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        System.dealloc(ptr, layout);
+// The trails of `particles` in a world of `size`, for scenes with a `[trail]`
+fn draw_trails<'a, G, I>(settings: Option<&TrailSettings>, size: Vec2d<f64>, particles: I, ctx: Context, renderer: &mut G)
+where
+    G: Graphics,
+    I: Iterator<Item = &'a Particle>,
+{
+    let Some(settings) = settings else {
+        return;
+    };
+    for particle in particles {
+        if let Some(trail) = &particle.trail {
+            draw_trail(trail.points(), &Sprite::of(particle), settings, size, ctx, renderer);
+        }
     }
 }
 
-/// Contains the data that will be used through the lifetime of the program.
-struct World {
-    current_turn: u64,
-    particles: Vec<Box<Particle>>,
-    height: f64,
-    width: f64,
-    rng: ThreadRng,
+// The trail of `sprite` through `points`, its last positions, as a line from
+// transparent and thin at the oldest to its color and the width of the
+// scene's `[trail]` at the newest. The jumps of particles wrapping around the
+// world are left out
+fn draw_trail<G, I>(points: I, sprite: &Sprite, settings: &TrailSettings, size: Vec2d<f64>, ctx: Context, renderer: &mut G)
+where
+    G: Graphics,
+    I: Iterator<Item = Vec2d<f64>> + Clone,
+{
+    let segments = points.clone().count().saturating_sub(1);
+    if segments == 0 {
+        return;
+    }
+    let jump = size[0].min(size[1]) / 2.0;
+    let draw_state = ctx.draw_state.blend(sprite.blend.blend());
+    for (index, (from, to)) in points.clone().zip(points.skip(1)).enumerate() {
+        if (to[0] - from[0]).hypot(to[1] - from[1]) > jump {
+            continue;
+        }
+        let share = (index + 1) as f64 / segments as f64;
+        let [red, green, blue, alpha] = sprite.color;
+        let line = Line::new([red, green, blue, alpha * share as f32], settings.width * share / 2.0);
+        line.draw([from[0], from[1], to[0], to[1]], &draw_state, ctx.transform, renderer);
+    }
 }
 
-// Defines the shape of and object in 2D space
-struct Particle {
-    height: f64,
-    width: f64,
-    position: Vec2d<f64>,
-    velocity: Vec2d<f64>,
-    acceleration: Vec2d<f64>,
-    color: [f32; 4],
+// `ctx` drawing a world of `size` as its camera shows it
+fn camera_context(camera: Option<CameraView>, size: Vec2d<f64>, ctx: Context) -> Context {
+    camera.map_or(ctx, |view| view.apply(ctx, size))
+}
+
+fn shown_turn(turn: u64, shown: Option<(&Timeline, usize)>) -> u64 {
+    shown.map_or(turn, |(timeline, selected)| timeline.turn_at(selected).unwrap_or(turn))
+}
+
+// Clears to `color`, or with `trails` paints it over what is there by its
+// alpha; without the frame before, a translucent background is drawn opaque
+fn paint_background<G: Graphics>(color: [f32; 4], trails: bool, ctx: Context, renderer: &mut G) {
+    if trails && color[3] < 1.0 {
+        let [width, height] = ctx.get_view_size();
+        rectangle(color, [0.0, 0.0, width, height], ctx.transform, renderer);
+    } else {
+        clear([color[0], color[1], color[2], 1.0], renderer);
+    }
+}
+
+// Draws the zones, obstacles and sparks of a world, everything but its particles
+fn draw_scene<G: Graphics>(world: &World, ctx: Context, renderer: &mut G) {
+    draw_zones(&world.zones, ctx, renderer);
+    draw_obstacles(&world.obstacles, ctx, renderer);
+    draw_particles(world.sparks.iter().map(|p| Sprite::of(p)), world.falloff.as_ref(), ctx, renderer);
+}
+
+// The frames of --capture-frames, only when the run is seeded
+fn frame_capture(cli: &Cli) -> Option<FrameCapture> {
+    if cli.capture_frames.is_empty() {
+        return None;
+    }
+    if cli.seed.is_none() {
+        eprintln!("--capture-frames needs --seed, or the frames change from one run to the next");
+        process::exit(1);
+    }
+    Some(FrameCapture::new(cli.capture_frames.clone(), cli.capture_dir.clone()))
+}
+
+// Draws the world as the window shows it while running, without the
+// overlays that depend on the machine like the heap gauge
+fn save_frame(captures: &mut FrameCapture, turn: u64, world: &World) {
+    let queued = captures.save(turn, world.width, world.height, |ctx, canvas| draw_frame(world, ctx, canvas));
+    if !queued {
+        eprintln!("Dropped the frame of turn {}: the telemetry writer is behind", turn);
+    }
 }
 
-impl Particle {
-    fn new(world: &World) -> Particle {
-        let mut rng = thread_rng();
+// The world as frame captures and animations show it, on the CPU
+fn draw_frame(world: &World, ctx: Context, canvas: &mut Canvas) {
+    paint_background(world.background(world.current_turn), false, ctx, canvas);
+    let size = [world.width, world.height];
+    let ctx = camera_context(world.camera_view(world.current_turn), size, ctx);
+    draw_scene(world, ctx, canvas);
+    draw_trails(world.trail.as_ref(), size, world.particles.iter().map(|p| &**p), ctx, canvas);
+    draw_particles(world.particles.iter().map(|p| Sprite::of(p)), world.falloff.as_ref(), ctx, canvas);
+}
 
-        // Starts at a random positin along the bottom of the window
-        let x = rng.gen_range(0.0..=world.width);
-        let y = world.height;
-        let x_velocity = 0.0;
-        let y_velocity = rng.gen_range(-2.0..0.0);
-        let x_acceleration = 0.0;
-        let y_acceleration = rng.gen_range(0.0..0.15);
+// Sides of the discs of soft particles, enough for particles a few dozen pixels wide
+const SOFT_RESOLUTION: u32 = 16;
 
-        Particle {
-            height: 4.0,
-            width: 4.0,
-            position: [x, y].into(),
-            velocity: [x_velocity, y_velocity].into(),
-            // Slows down the particle as it travels along the screen
-            acceleration: [x_acceleration, y_acceleration].into(),
-            color: [1.0, 1.0, 1.0, 0.99], // almost transparent white color
+// Particles are drawn in one pass per blend mode, in `DRAW_ORDER`, so
+// additive ones brighten everything else whatever their place in the list
+fn draw_particles<G, I>(sprites: I, falloff: Option<&Falloff>, ctx: Context, renderer: &mut G)
+where
+    G: Graphics,
+    I: Iterator<Item = Sprite> + Clone,
+{
+    let profile = falloff.map(Falloff::profile);
+    for mode in DRAW_ORDER {
+        let draw_state = ctx.draw_state.blend(mode.blend());
+        for s in sprites.clone().filter(|s| s.blend == mode) {
+            let size = [s.position[0], s.position[1], s.size[0], s.size[1]];
+            match &profile {
+                Some(profile) if s.shape.is_soft() => {
+                    draw_soft(size, profile.layers(s.color, mode), &draw_state, ctx, renderer)
+                }
+                _ => s.shape.draw(size, s.velocity, s.color, &draw_state, ctx.transform, renderer),
+            }
         }
     }
+}
+
+// A soft particle, as discs centered on its square from the largest in
+fn draw_soft<G: Graphics>(
+    [x, y, width, height]: [f64; 4],
+    layers: [(f64, [f32; 4]); falloff::LAYERS],
+    draw_state: &DrawState,
+    ctx: Context,
+    renderer: &mut G,
+) {
+    let center = [x + width / 2.0, y + height / 2.0];
+    for (diameter, color) in layers {
+        let (w, h) = (width * diameter, height * diameter);
+        let bounds = [center[0] - w / 2.0, center[1] - h / 2.0, w, h];
+        Ellipse::new(color).resolution(SOFT_RESOLUTION).draw(bounds, draw_state, ctx.transform, renderer);
+    }
+}
+
+fn replay_trace(path: &Path, live_window: usize) {
+    let mut events = Vec::new();
+    let read = trace::read_events(path, |event| events.push(event));
+    if let Err(err) = read {
+        eprintln!("Could not read trace {}: {}", path.display(), err);
+        process::exit(1);
+    }
+
+    let recorded = trace::TraceSummary::from_events(events.iter().copied());
+    let replayed = replay::replay(&events, live_window);
+    trace::print_diff("recorded", &recorded, "replayed", &replayed);
+}
+
+// Thin bar along the top of the window showing how much of the heap
+// budget is in use, red once the world is under memory pressure. Culling
+// keeps a world under pressure around the threshold, where the bar would
+// flash between colors; with reduced motion it keeps its color and the
+// threshold is marked on it instead
+fn draw_heap_gauge<G: Graphics>(live_bytes: usize, max_bytes: usize, reduced_motion: bool, width: f64, ctx: Context, renderer: &mut G) {
+    let used = (live_bytes as f64 / max_bytes as f64).min(1.0);
+    let color = if used >= MEMORY_PRESSURE_THRESHOLD && !reduced_motion {
+        [0.9, 0.1, 0.1, 1.0]
+    } else {
+        [0.3, 0.7, 0.4, 0.8]
+    };
+    rectangle(color, [0.0, 0.0, width * used, 6.0], ctx.transform, renderer);
+    if reduced_motion {
+        rectangle([1.0, 1.0, 1.0, 1.0], [width * MEMORY_PRESSURE_THRESHOLD - 1.0, 0.0, 2.0, 8.0], ctx.transform, renderer);
+    }
+}
+
+/// Render particles along the screen using the Piston game engine.
+// The window of --frontend wgpu, for the same world as the Piston one
+#[cfg(feature = "wgpu")]
+fn run_gpu_window(cli: &Cli) {
+    let mut metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    metadata.scenario = cli.scenario;
+    metadata.vec_policy = cli.vec_policy;
+    telemetry::comment(&metadata.comment_header());
+    let mut world = build_world(cli, cli.config.as_deref(), cli.seed, cli.width, cli.height);
+    world.time_step = BASE_TURN_RATE / cli.turn_rate as f64;
+    let options = gpu::GpuOptions {
+        turn_rate: cli.turn_rate as f64,
+        vsync: cli.vsync && !cli.no_vsync,
+        fullscreen: cli.fullscreen,
+        max_fps: cli.max_fps.unwrap_or(cli.target_fps),
+    };
+    if let Err(err) = gpu::run(world, options) {
+        eprintln!("Could not run the wgpu window: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "wgpu"))]
+fn run_gpu_window(_cli: &Cli) {
+    eprintln!("--frontend wgpu needs the wgpu feature: cargo run --release --features wgpu -- --frontend wgpu");
+    process::exit(1);
+}
 
-    fn update(&mut self) {
-        self.velocity = add(self.velocity, self.acceleration);
-        self.position = add(self.position, self.velocity);
-        self.acceleration = mul_scalar(self.acceleration, 0.7);
+// OpenGL versions windows fall back on in turn when the one asked for can't
+// be had, without multisampling, which virtual machines are first to refuse
+const FALLBACK_OPENGL: [(OpenGL, &str); 2] = [(OpenGL::V3_2, "3.2"), (OpenGL::V2_1, "2.1")];
+
+// Opens a window with `settings`, or else with the fallback versions of
+// OpenGL, returning the error of the last attempt when none could be had
+fn build_window(settings: WindowSettings) -> Result<PistonWindow, String> {
+    let mut error = match try_build(&settings) {
+        Ok(window) => return Ok(window),
+        Err(err) => err,
+    };
+    for (api, name) in FALLBACK_OPENGL {
+        if settings.get_maybe_graphics_api() == Some(api.into()) && settings.get_samples() == 0 {
+            continue;
+        }
+        eprintln!("Could not create a window: {}; trying OpenGL {} without multisampling", error, name);
+        match try_build(&settings.clone().graphics_api(api).samples(0)) {
+            Ok(window) => return Ok(window),
+            Err(err) => error = err,
+        }
+    }
+    Err(error)
+}
 
-        // Make the particcle more transparent over time
-        self.color[3] *= 0.995;
+// Without a display, winit panics rather than return an error, which is
+// caught here, quietly, like one
+fn try_build(settings: &WindowSettings) -> Result<PistonWindow, String> {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let built = std::panic::catch_unwind(|| settings.build::<PistonWindow>());
+    std::panic::set_hook(hook);
+    match built {
+        Ok(built) => built.map_err(|err| err.to_string()),
+        Err(panic) => Err(panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "the windowing backend panicked".to_string())),
     }
 }
 
-impl World {
-    fn new(width: f64, height: f64) -> World {
-        World {
-            current_turn: 0,
+// Runs headless when no window could be made at all, drawing the world on
+// the CPU once a second of turns to `--capture-dir`, or at the turns of
+// `--capture-frames`, so there is still something to look at
+fn run_software(cli: &Cli) {
+    set_capturing(!cli.safe_mode);
+    let turns = cli.turns.unwrap_or(HEADLESS_TURNS);
+    let every = cli.turn_rate as u64;
+    let captures = frame_capture(cli).unwrap_or_else(|| {
+        println!(
+            "drawing on the CPU instead: {} turns, one frame in {} saved to {}",
+            turns,
+            every,
+            cli.capture_dir.display()
+        );
+        FrameCapture::new((every..=turns).step_by(every as usize).collect(), cli.capture_dir.clone())
+    });
+    run_bench(cli, turns, None, 0.0, Some(captures));
+}
+
+fn run_window(cli: &Cli, mut startup: Option<StartupProfile>) {
+    // Startup allocations from Piston are part of the warm-up too
+    if cli.warmup.is_some() {
+        set_capturing(false);
+    }
+
+    // A resumed session brings its own window size and scene, and so does
+    // a journal to replay
+    let resumed = resumed_session(cli);
+    if resumed.is_some() && (cli.journal.is_some() || cli.replay.is_some()) {
+        eprintln!("--journal and --replay run from the first turn, not from a resumed session");
+        process::exit(1);
+    }
+    let replay = cli.replay.as_deref().map(open_journal);
+    let (mut width, mut height) = match (&resumed, &replay) {
+        (Some(session), _) => (session.width, session.height),
+        (None, Some(journal)) => (journal.header.width, journal.header.height),
+        (None, None) => (cli.width, cli.height),
+    };
+
+    let vsync = cli.vsync && !cli.no_vsync;
+
+    // Started first, since windows hang in their creation too
+    let timeout = cli.watchdog.map(|seconds| Duration::from_secs_f64(seconds.max(0.1)));
+    let watchdog = Watchdog::start(timeout, cli.watchdog_abort).unwrap_or_else(|err| {
+        eprintln!("Could not start the watchdog: {}", err);
+        process::exit(1);
+    });
 
-            // Use Box instead of Particle in order to use extra more memory allocation
-            particles: Vec::<Box<Particle>>::new(),
-            height: height,
-            width: width,
-            rng: thread_rng(),
+    // This does not work on Arch Linux x64 running in VirtualBox
+    // not even enabling 3D acceleration, which --safe-mode is there to narrow down,
+    // and older OpenGL versions then the CPU are tried for
+    let mut settings = WindowSettings::new(
+        "particles", [width, height]
+    )
+        .exit_on_esc(true)
+        .fullscreen(cli.fullscreen)
+        .vsync(vsync);
+    if cli.safe_mode {
+        println!("safe mode: no allocation reports, overlays, icon, multisampling or offscreen targets, OpenGL 2.1");
+        settings = settings.graphics_api(OpenGL::V2_1).samples(0);
+    }
+    let mut window = match build_window(settings) {
+        Ok(window) => window,
+        Err(err) => {
+            eprintln!("Could not create a window: {}", err);
+            drop(watchdog);
+            run_software(cli);
+            return;
+        }
+    };
+    if !cli.safe_mode {
+        set_window_icon(&window);
+    }
+    if let Some(startup) = &mut startup {
+        startup.mark("window creation");
+    }
+
+    // What the window tells of the display is logged and goes in the
+    // metadata, which stamps the allocation trace like every other export
+    let capabilities = detect_display(&window);
+    println!("{}", capabilities.describe());
+    let render_scale = if cli.safe_mode { 1.0 } else { cli.render_scale.unwrap_or_else(|| capabilities.default_render_scale()) };
+    let mut metadata = RunMetadata::new(cli.config.as_deref(), cli.seed);
+    metadata.scenario = cli.scenario;
+    metadata.vec_policy = cli.vec_policy;
+    telemetry::comment(&metadata.comment_header());
+
+    // What `--session` writes at exit, following the scenes picked meanwhile
+    let (mut world, scene_path, scene_text, mut overlays) = match resumed {
+        Some(session) => {
+            let (path, text, overlays) = (session.scene_path.clone(), session.scene_text.clone(), session.overlays);
+            (restore_session(cli, session), path, text, overlays)
         }
+        None => {
+            let scene_text = match &replay {
+                Some(journal) => Some(journal.header.scene.clone()).filter(|scene| !scene.is_empty()),
+                None => read_scene_text(cli.config.as_deref()),
+            };
+            let scene_path = cli.config.clone().filter(|_| replay.is_none());
+            (start_world(cli, replay, width, height), scene_path, scene_text, Overlays::default())
+        }
+    };
+    if cli.safe_mode {
+        overlays = Overlays::default();
+    }
+
+    // Fullscreen windows take the size of the display, which the world
+    // follows as it does a resize
+    if cli.fullscreen {
+        let size = window.size();
+        [width, height] = [size.width, size.height];
+        world.resize(width, height);
+    }
+    world.time_step = BASE_TURN_RATE / cli.turn_rate as f64;
+    world.survival.get_or_insert_with(SurvivalCurve::new);
+    if let Some(startup) = &mut startup {
+        startup.mark(if cli.preallocate { "scene, pre-allocation and initial spawn" } else { "scene and initial particle spawn" });
     }
+    bundle_config(&mut world);
+
+    // Space pauses the simulation and shows the heap timeline,
+    // which can be scrubbed with the mouse or the arrow keys
+    let mut scrubber = Scrubber::new();
+    let mut tuning = TuningPanel::new();
+    let mut paused = overlays.paused;
+
+    // Only set when it changes, the title is what taskbars show for the window
+    let mut title = window_title(None, false, None);
+    let mut status: Option<String> = None;
+    let mut logged_status: Option<String> = None;
+    let mut screenshots = Screenshots::new(cli.capture_dir.clone(), cli.screenshot_every);
+    #[cfg(feature = "metrics")]
+    let mut metrics = start_metrics(cli);
 
-    fn add_shapes(&mut self, n: i32) {
-        for _ in 0..n.abs() {
+    // Clicking while running shows the particle closest to the cursor
+    let mut cursor = [0.0, 0.0];
+    let mut cursor_inside = false;
 
-            // Create a particle as local variable in the Stack (memory)
-            let particle = Particle::new(&self);
+    // The scroll wheel zooms around the cursor and dragging with Ctrl and
+    // the left button pans, R showing the whole window again
+    let mut view = Transform2D::default();
+    let mut window_cursor = [0.0, 0.0];
+    let mut panning = false;
 
-            // Move the particle to the heap and create a reference to it
-            // in the Stack
-            let boxed_particle = Box::new(particle);
-            self.particles.push(boxed_particle);
+    // Ctrl+1..9 saves the world to a slot, 1..9 restores it
+    let mut ctrl_held = false;
+
+    // With Shift held, the left and right buttons pull the particles around
+    // the cursor in or push them away for as long as they are down, and the
+    // middle one throws a burst of particles
+    let mut shift_held = false;
+
+    // F shows the frame times, with dropped frames in red; drops are
+    // also logged, and kept for the summary at exit
+    let refresh_rate = capabilities.display.as_ref().and_then(|display| display.refresh_rate).map(u64::from);
+    let target_fps = match refresh_rate.filter(|&rate| vsync && rate < cli.target_fps) {
+        Some(rate) => {
+            println!("vsync holds the window to {} frames a second, counting dropped frames against it", rate);
+            rate
         }
+        None => cli.target_fps.max(1),
+    };
+    let target_frame_time = Duration::from_secs_f64(1.0 / target_fps as f64);
+
+    // Frames are limited here rather than by the event loop, which is only
+    // held to the limiter's rate, or to none uncapped
+    let mut frame_limiter = FrameLimiter::new(cli.max_fps.unwrap_or(cli.target_fps));
+    if !frame_limiter.is_capped() {
+        println!("frames uncapped{}", if vsync { ", though vsync still holds them to the display" } else { "" });
     }
+    window.set_max_fps(UNCAPPED_EVENT_FPS);
+    let mut frame_chart = FrameChart::new();
+    let mut show_frame_chart = overlays.frame_chart;
 
-    fn remove_shapes(&mut self, n: i32) {
-        for _ in 0..n.abs() {
-            let mut to_delete = None;
+    // C charts the particle count against the live heap
+    let mut show_correlation = overlays.correlation;
 
-            // Split into its own variable to more easily fit on the page
-            let particle_iter = self.particles
-                .iter()
-                .enumerate();
+    // L charts the survival curve, which the window always counts for the HUD
+    let mut show_survival = overlays.survival;
 
-            // Remove the fist particle if it is invisible
-            // otherwise remove the oldest
-            for (i, particle) in particle_iter {
-                if particle.color[3] < 0.02 {
-                    to_delete = Some(i);
-                }
+    // Shift+G plots the bytes and times of the allocations of every frame
+    let mut allocation_graph = AllocationGraph::new(&memory_stats());
+    let mut show_allocation_graph = overlays.allocation_graph;
+
+    // V draws the arrows of the noise forces over the world
+    let mut show_noise_field = overlays.noise_field;
+    let mut frame_drops: Vec<FrameDrop> = Vec::new();
+    let mut frames = 0;
+    let mut run_summary = RunSummary::new();
+    let mut run_report = cli.report.as_ref().map(|_| RunReport::new(bundle().frame, bundle().frames, RESERVED_STEPS as usize));
+    let mut allocated_at_frame_start = memory_stats().allocated_bytes;
+
+    // H shows the frame rate and the heap over the scene
+    let mut hud = Hud::new(&mut window, &memory_stats()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    hud.visible = overlays.hud;
+
+    // Tab opens a menu of the scenes, which takes the keyboard and holds
+    // the simulation while open
+    let mut picker = ScenePicker::new(&cli.scenes_dir, cli.config.as_deref());
+
+    // Turns keep to --turn-rate on a thread of their own, however many
+    // events and frames there are, spawning less when they run on without
+    // frames. The window sends it what the user does as commands and draws
+    // the latest frame it handed over, never a turn half updated
+    let stall_guard = Arc::new(Mutex::new(StallGuard::new(target_frame_time)));
+    let (told, notices) = mpsc::channel();
+    let mut simulation = Simulation::new(cli, world, Arc::clone(&stall_guard), told);
+    (simulation.paused, simulation.scene_path, simulation.scene_text) = (paused, scene_path, scene_text);
+    let charts = Arc::clone(&simulation.charts);
+    let (writer, mut snapshots) = handoff::handoff::<Frame>();
+    let (sender, received) = mpsc::channel();
+    let commands = Commands::new(sender);
+
+    // Of the latest frame, for the cursor to point through
+    let mut camera: Option<CameraView> = None;
+    let mut telemetry_governor = TelemetryGovernor::new(target_frame_time);
+
+    // Target of --render-scale and of translucent backgrounds, made on the
+    // first frame needing it
+    let mut offscreen_works = !cli.safe_mode;
+    let mut scaled: Option<ScaledTarget> = None;
+
+    let mut timer = FrameTimer::new();
+    let mut waiting_since = Instant::now();
+    if let Some(startup) = &mut startup {
+        startup.mark("statistics and event loop setup");
+    }
+    let simulation = thread::scope(|scope| {
+        let running = thread::Builder::new()
+            .name("simulation".to_string())
+            .spawn_scoped(scope, move || simulation.run(writer, received))
+            .unwrap_or_else(|err| {
+                eprintln!("Could not start the simulation thread: {}", err);
+                process::exit(1);
+            });
+        watchdog.enter(Phase::Wait);
+        while let Some(event) = window.next() {
+            // Ctrl+C closes the window, with everything written as on closing it
+            if shutdown::requested() {
                 break;
             }
+            let received = Instant::now();
+            timer.add(Phase::Wait, received - waiting_since);
+            watchdog.enter(Phase::Dispatch);
+
+            // The sides particles wrap around, bounce off or leave by are the window's
+            if let Some(resize) = event.resize_args() {
+                [width, height] = resize.window_size;
+                commands.send(move |simulation| simulation.world.resize(width, height));
+            }
+
+            if let Some(Button::Keyboard(Key::Tab)) = event.press_args() {
+                picker.open = !picker.open;
+                let open = picker.open;
+                commands.send(move |simulation| simulation.holding = open);
+                status = None;
+                if picker.open {
+                    picker.make_thumbnails(&mut window.create_texture_context(), |entry| scene_thumbnail(cli, entry));
+                    status = Some(picker.describe());
+                }
+            } else if let (Some(Button::Keyboard(key)), true) = (event.press_args(), picker.open) {
+                match picker.handle_key(key) {
+                    PickerAction::Ignored => {}
+                    PickerAction::Moved => status = Some(picker.describe()),
+                    PickerAction::Chosen(index) => {
+                        let entry = &picker.entries[index];
+                        let path = entry.path.clone();
+                        let loaded = commands.call(move |simulation| -> Result<(), String> {
+                            // Statistics start over with the new scene, and
+                            // the journal, of the scene before, ends
+                            let loaded = load_scene(simulation.cli, path.as_deref(), width, height)?;
+                            simulation.replace_world(loaded);
+                            simulation.scene_text = read_scene_text(path.as_deref());
+                            simulation.scene_path = path;
+                            simulation.holding = false;
+                            Ok(())
+                        });
+                        match loaded {
+                            Ok(()) => {
+                                scrubber.select_latest(&lock(&charts).timeline);
+                                status = Some(format!("loaded scene {}", entry.name));
+                                picker.open = false;
+                            }
+                            Err(err) => status = Some(err),
+                        }
+                    }
+                }
+            }
+            let press = event.press_args().filter(|_| !picker.open);
+
+            match press {
+                Some(Button::Keyboard(Key::LCtrl | Key::RCtrl)) => ctrl_held = true,
+                Some(Button::Keyboard(Key::LShift | Key::RShift)) => shift_held = true,
+                Some(Button::Keyboard(key)) => match key_action(key) {
+                    Some(KeyAction::Pause) => {
+                        paused = !paused;
+                        commands.send(move |simulation| simulation.paused = paused);
+                        scrubber.select_latest(&lock(&charts).timeline);
+                    }
+                    Some(KeyAction::Step) if paused => commands.send(|simulation| simulation.step = true),
+                    Some(KeyAction::Step) => status = Some("pause with Space to step one turn at a time".to_string()),
+                    Some(KeyAction::Burst) => commands.send(|simulation| {
+                        if let Some(journal) = &simulation.world.journal {
+                            journal.event(JournalEvent::Burst(BURST_PARTICLES));
+                        }
+                        simulation.world.add_shapes(BURST_PARTICLES);
+                        simulation.notify(format!("spawned {} particles", BURST_PARTICLES));
+                    }),
+                    Some(KeyAction::Clear) => commands.send(|simulation| {
+                        if let Some(journal) = &simulation.world.journal {
+                            journal.event(JournalEvent::Clear);
+                        }
+                        let before = DropSite::Clear.stats();
+                        let cleared = simulation.world.clear();
+                        let time = DropSite::Clear.stats().since(&before).nanos as f64 / 1e6;
+                        simulation.notify(format!("cleared {} particles in {:.2} ms", cleared, time));
+                    }),
+                    Some(KeyAction::FrameChart) => show_frame_chart = !show_frame_chart,
+                    Some(KeyAction::Correlation) => show_correlation = !show_correlation,
+                    Some(KeyAction::Survival) => show_survival = !show_survival,
+                    Some(KeyAction::NoiseField) => show_noise_field = !show_noise_field,
+                    Some(KeyAction::Hud) => hud.visible = !hud.visible,
+                    Some(KeyAction::Tuning) => {
+                        tuning.open = !tuning.open;
+                        let open = tuning.open;
+                        commands.send(move |simulation| simulation.tuning = open);
+                    }
+                    Some(KeyAction::Mutate) => commands.send(|simulation| {
+                        let changes = simulation.mutations.mutate(&mut simulation.world, &mut rand::thread_rng()).to_vec();
+                        let status = describe_mutation("mutation", simulation.mutations.len(), &changes);
+                        log_mutation(&simulation.mutations, &mut simulation.world, &status);
+                        simulation.notify(status);
+                    }),
+                    Some(KeyAction::UndoMutation) => commands.send(|simulation| {
+                        let undone = simulation.mutations.len();
+                        let status = match simulation.mutations.undo(&mut simulation.world) {
+                            Some(changes) => describe_mutation("undid mutation", undone, &changes),
+                            None => "no mutation to undo".to_string(),
+                        };
+                        log_mutation(&simulation.mutations, &mut simulation.world, &status);
+                        simulation.notify(status);
+                    }),
+                    Some(KeyAction::Storage) => commands.send(|simulation| {
+                        let status = switch_storage(&mut simulation.world);
+                        simulation.notify(status);
+                    }),
+                    Some(KeyAction::Screenshot) => screenshots.request(),
+                    Some(KeyAction::SaveCheckpoint) => commands.send(|simulation| {
+                        let path = &simulation.cli.checkpoint;
+                        let status = match simulation.world.save(path) {
+                            Ok(()) => format!("saved checkpoint {} at turn {}", path.display(), simulation.world.current_turn),
+                            Err(err) => format!("could not save checkpoint {}: {}", path.display(), err),
+                        };
+                        simulation.notify(status);
+                    }),
+                    Some(KeyAction::LoadCheckpoint) => commands.send(|simulation| {
+                        let cli = simulation.cli;
+                        match load_checkpoint(cli, &cli.checkpoint) {
+                            Ok(loaded) => {
+                                // The histories go on from the turn of the checkpoint
+                                // and the journal, of the run before, ends
+                                simulation.replace_world(loaded);
+                                simulation.scene_path = None;
+                                simulation.scene_text = simulation.world.scene_text.clone();
+                                let status = format!("loaded checkpoint {} at turn {}", cli.checkpoint.display(), simulation.world.current_turn);
+                                simulation.tell(Notice { status, latest: true });
+                            }
+                            Err(err) => simulation.notify(err),
+                        }
+                    }),
+                    Some(KeyAction::Force("gravity")) if shift_held => show_allocation_graph = !show_allocation_graph,
+                    Some(KeyAction::ResetView) => {
+                        view = Transform2D::default();
+                        status = Some("view reset".to_string());
+                    }
+                    Some(KeyAction::Force(kind)) => commands.send(move |simulation| {
+                        let status = toggle_force(&mut simulation.world, kind, cursor);
+                        simulation.notify(status);
+                    }),
+                    Some(KeyAction::Slot(slot)) => {
+                        let save = ctrl_held;
+                        commands.send(move |simulation| {
+                            let status = if save {
+                                let name = format!("turn {}", simulation.world.current_turn);
+                                let message = format!("saved snapshot {}: {}", slot + 1, name);
+                                simulation.slots.save(slot, name, &simulation.world);
+                                message
+                            } else if let Some((name, saved)) = simulation.slots.get(slot) {
+                                let message = format!("restored snapshot {}: {}", slot + 1, name);
+                                simulation.world = saved.clone();
+                                message
+                            } else {
+                                format!("snapshot {} is empty", slot + 1)
+                            };
+                            simulation.notify(status);
+                        });
+                    }
+                    None => {}
+                },
+                _ => {}
+            }
+            match event.release_args() {
+                Some(Button::Keyboard(Key::LCtrl | Key::RCtrl)) => ctrl_held = false,
+                Some(Button::Keyboard(Key::LShift | Key::RShift)) => shift_held = false,
+                Some(Button::Mouse(MouseButton::Left | MouseButton::Right)) => {
+                    commands.send(|simulation| simulation.world.cursor_force = None);
+                    panning = false;
+                }
+                _ => {}
+            }
+            if let Some([_, notches]) = event.mouse_scroll_args().filter(|_| !picker.open) {
+                view.zoom_at(window_cursor, ZOOM_STEP.powf(notches));
+                status = Some(format!("zoom {:.2}x, R resets the view", view.scale));
+            }
+
+            // The cursor points at the world under it, through the view and the camera
+            if let Some(position) = event.mouse_cursor_args() {
+                if panning {
+                    view.pan([position[0] - window_cursor[0], position[1] - window_cursor[1]]);
+                }
+                window_cursor = position;
+            }
+            let moved = event.mouse_cursor_args().is_some() || event.mouse_scroll_args().is_some();
+            if moved {
+                let local = view.to_local(window_cursor);
+                cursor = camera.map_or(local, |camera| camera.to_world(local, [width, height]));
+            }
+            if let Some(inside) = event.cursor_args() {
+                cursor_inside = inside;
+            }
+            if moved || event.cursor_args().is_some() {
+                commands.point(cursor, cursor_inside);
+            }
 
-            if let Some(i) = to_delete {
-                self.particles.remove(i);
+            // Presses on the tuning panel move its sliders rather than reach
+            // the world, which the panel waits for
+            let action = if picker.open || !tuning.concerns(&event, width) {
+                TuningAction::Ignored
             } else {
-                self.particles.remove(0);
-                
-            };    
+                let (mut panel, event) = (tuning.clone(), event.clone());
+                let (panel, action) = commands.call(move |simulation| {
+                    let action = panel.handle_event(&event, &mut simulation.world, width);
+                    (panel, action)
+                });
+                tuning = panel;
+                action
+            };
+            let press = match action {
+                TuningAction::Ignored => press,
+                TuningAction::Grabbed => None,
+                TuningAction::Tuned(assignment) => {
+                    println!("tuned {}\n  reproduce with: --set {}", assignment, assignment);
+                    status = Some(format!("tuned {}", assignment));
+                    None
+                }
+            };
+            match (press, paused) {
+                (Some(Button::Mouse(MouseButton::Left)), _) if ctrl_held => panning = true,
+                (Some(Button::Mouse(button @ (MouseButton::Left | MouseButton::Right))), false) if shift_held => {
+                    let strength = if button == MouseButton::Left { CURSOR_PULL } else { -CURSOR_PULL };
+                    commands.send(move |simulation| {
+                        simulation.world.cursor_force = Some(Force::Attractor { center: cursor, strength, radius: CURSOR_PULL_RADIUS });
+                    });
+                }
+                (Some(Button::Mouse(MouseButton::Middle)), false) if shift_held => commands.send(move |simulation| {
+                    simulation.world.burst_at(cursor, CURSOR_BURST);
+                    simulation.notify(format!("burst of {} at ({:.0}, {:.0})", CURSOR_BURST, cursor[0], cursor[1]));
+                }),
+                (Some(Button::Mouse(MouseButton::Left)), false) => commands.send(move |simulation| {
+                    let status = describe_nearest(&simulation.world, cursor);
+                    simulation.notify(status);
+                }),
+                // Right-clicking freezes the particle closest to the cursor, or frees it
+                (Some(Button::Mouse(MouseButton::Right)), false) => commands.send(move |simulation| {
+                    let status = toggle_pin_nearest(&mut simulation.world, cursor);
+                    simulation.notify(status);
+                }),
+                // Middle-clicking drops a fountain at the cursor
+                (Some(Button::Mouse(MouseButton::Middle)), false) => commands.send(move |simulation| {
+                    simulation.world.add_emitter(Emitter::fountain(cursor));
+                    simulation.notify(format!("dropped a fountain at ({:.0}, {:.0})", cursor[0], cursor[1]));
+                }),
+                _ => {}
+            }
+
+            // Keep tracking the cursor while running so a click right after
+            // pausing lands where the mouse actually is
+            if !picker.open {
+                scrubber.handle_event(&event, &lock(&charts).timeline, width, height);
+            }
+            let update_start = Instant::now();
+            timer.add(Phase::Dispatch, update_start - received);
+            watchdog.enter(Phase::Update);
+            let frame = snapshots.latest();
+            camera = frame.world.camera;
+            let update_end = Instant::now();
+            timer.add(Phase::Update, update_end - update_start);
+            watchdog.enter(Phase::Dispatch);
+
+            // What the simulation had to say since the event before
+            for notice in notices.try_iter() {
+                if notice.latest {
+                    scrubber.select_latest(&lock(&charts).timeline);
+                }
+                status = Some(notice.status);
+            }
+            let progress = cli.turns.map(|turns| (frame.captured_turns as f64 / turns.max(1) as f64).min(1.0));
+            if cli.turns.is_some_and(|turns| frame.captured_turns >= turns) {
+                window.set_should_close(true);
+            }
+
+            if debug_bundle::is_enabled() && status != logged_status {
+                if let Some(status) = &status {
+                    debug_bundle::log(status);
+                }
+                logged_status.clone_from(&status);
+            }
+
+            // The turns only wait on the charts while they are shown
+            let recorded = (paused || show_correlation).then(|| lock(&charts));
+            let mut new_title = window_title(progress, frame.under_memory_pressure, status.as_deref());
+            if arena::is_enabled() {
+                new_title.push_str(" - ");
+                new_title.push_str(&arena::arena_stats().describe());
+            } else if pool::is_enabled() {
+                new_title.push_str(" - ");
+                new_title.push_str(&pool::pool_stats().describe());
+            }
+            if let Some(particle_pool) = &frame.particle_pool {
+                new_title.push_str(" - ");
+                new_title.push_str(&particle_pool.describe());
+            }
+            if let Some(recorded) = recorded.as_ref().filter(|_| show_correlation) {
+                new_title.push_str(" - ");
+                new_title.push_str(&recorded.correlation.describe());
+            }
+            if let Some((points, median, deaths)) = frame.survival.as_ref().filter(|_| show_survival) {
+                new_title.push_str(" - ");
+                new_title.push_str(&survival_chart::describe(points, *median, *deaths));
+            }
+            if show_allocation_graph {
+                new_title.push_str(" - ");
+                new_title.push_str(&allocation_graph.describe());
+            }
+            if new_title != title {
+                window.set_title(new_title.clone());
+                title = new_title;
+            }
+
+            let draw_start = Instant::now();
+            timer.add(Phase::Dispatch, draw_start - update_end);
+            watchdog.enter(Phase::DrawOverhead);
+            let mut drawing = Duration::ZERO;
+            let shown = recorded.as_ref().filter(|_| paused).map(|recorded| (&recorded.timeline, scrubber.selected));
+
+            // With --render-scale or a translucent background, the world is drawn
+            // to a target of its own first, rebuilt when the window is resized,
+            // and stretched over the window
+            let trails = frame.scenery.background(shown_turn(frame.world.turn, shown))[3] < 1.0;
+            let offscreen = offscreen_works && (render_scale < 1.0 || trails);
+            if let Some(args) = event.render_args().filter(|_| offscreen) {
+                watchdog.enter(Phase::Draw);
+                let size = ScaledTarget::size_for(args.draw_size, render_scale);
+                if scaled.as_ref().map(ScaledTarget::size) != Some(size) {
+                    scaled = match ScaledTarget::new(&mut window.factory, size) {
+                        Ok(target) => Some(target),
+                        Err(err) => {
+                            eprintln!("{}, drawing at full resolution without trails", err);
+                            offscreen_works = false;
+                            None
+                        }
+                    };
+                }
+                if let Some(target) = &mut scaled {
+                    target.draw(&mut window, args.viewport(), |ctx, renderer| draw_world(frame, shown, &view, true, ctx, renderer));
+                }
+            }
+            window.draw_2d(&event, |ctx, renderer, device| {
+                watchdog.enter(Phase::Draw);
+                match scaled.as_ref().filter(|_| offscreen && offscreen_works) {
+                    Some(target) => target.draw_upscaled(ctx, renderer),
+                    None => draw_world(frame, shown, &view, false, ctx, renderer),
+                }
+
+                // Safe mode draws the world alone
+                if !cli.safe_mode {
+                    let scenery = &frame.scenery;
+                    if show_noise_field {
+                        draw_noise_field(frame, &view, ctx, renderer);
+                    }
+                    // Read every frame, as the picker can load a scene asking for it
+                    hud.high_contrast = scenery.accessibility.high_contrast;
+                    if let Some(max) = scenery.max_heap_bytes {
+                        draw_heap_gauge(memory_stats().live_bytes, max, scenery.accessibility.reduced_motion, width, ctx, renderer);
+                    }
+
+                    if let Some((timeline, selected)) = shown {
+                        timeline.draw(selected, width, height, ctx, renderer);
+                        timeline.draw_allocations(selected, width, height, ctx, renderer);
+                    }
+                    let mut bottom = if paused { height - PAUSED_HEIGHT - 4.0 } else { height };
+                    if show_allocation_graph {
+                        allocation_graph.draw(bottom, width, ctx, renderer);
+                        bottom -= GRAPH_HEIGHT + 4.0;
+                    }
+                    if let Some(palette) = &scenery.palette {
+                        hud.draw_legend(palette, width, bottom - 4.0, ctx, renderer, device);
+                    }
+                    if show_frame_chart {
+                        frame_chart.draw(target_frame_time, width, ctx, renderer);
+                    }
+                    if let Some(recorded) = recorded.as_ref().filter(|_| show_correlation) {
+                        recorded.correlation.draw(ctx, renderer);
+                    }
+                    if let Some((points, _, _)) = frame.survival.as_ref().filter(|_| show_survival) {
+                        survival_chart::draw(points, ctx, renderer);
+                    }
+                    if hud.visible {
+                        let median = frame.survival.as_ref().map(|survival| survival.1);
+                        hud.draw(frame.world.sprites.len(), median, telemetry_governor.fidelity(), &memory_stats(), ctx, renderer, device);
+                    }
+                    if let Some(values) = frame.tuned.filter(|_| tuning.open) {
+                        tuning.draw(&values, width, &mut hud, ctx, renderer, device);
+                    }
+                }
+                if picker.open {
+                    picker.draw(width, height, ctx, renderer);
+                }
+                drawing = draw_start.elapsed();
+                watchdog.enter(Phase::DrawOverhead);
+            });
+            drop(recorded);
+
+            // Render events end the frame
+            let (turn, particles) = (frame.world.turn, frame.world.sprites.len());
+            if let Some(args) = event.render_args() {
+                if screenshots.is_due(frames) && !screenshots.save(&mut window.device, args.draw_size, frames, turn) {
+                    status = Some(format!("screenshot of frame {} skipped, saving is behind", frames));
+                }
+                timer.add(Phase::Draw, drawing);
+                timer.add(Phase::DrawOverhead, draw_start.elapsed().saturating_sub(drawing));
+                let phases = timer.end_frame();
+                watchdog.frame_done(&phases, turn);
+                if let Some(mut startup) = startup.take() {
+                    startup.mark("events up to the first rendered frame");
+                    startup.print();
+                }
+
+                let stats = memory_stats();
+                hud.end_frame(&stats);
+                allocation_graph.push(&stats);
+                let allocated = stats.allocated_bytes;
+                let time = phases.total();
+                let dropped = FrameDrop::is_drop(time, target_frame_time) && !frame.warming_up;
+                if dropped {
+                    let drop = FrameDrop {
+                        frame: frames,
+                        turn,
+                        time,
+                        particles,
+                        allocated_bytes: allocated - allocated_at_frame_start,
+                        phases,
+                    };
+                    println!("{}", drop.describe(target_frame_time));
+                    debug_bundle::log(&drop.describe(target_frame_time));
+                    frame_drops.push(drop);
+                }
+                frame_chart.push(time, dropped);
+                if !cli.full_telemetry && !frame.warming_up {
+                    if let Some(fidelity) = telemetry_governor.frame(time) {
+                        allocator::set_fidelity(fidelity);
+                        status = Some(format!("telemetry fidelity {}", fidelity.describe()));
+                    }
+                }
+                if let Some(stall) = lock(&stall_guard).frame_rendered(Instant::now()) {
+                    let line = format!(
+                        "no frame for {:.0} ms, spawning throttled over {} turns",
+                        stall.time.as_secs_f64() * 1e3,
+                        stall.throttled_turns
+                    );
+                    println!("{}", line);
+                    debug_bundle::log(&line);
+                }
+                if let Some(run_report) = &mut run_report {
+                    run_report.record(time, particles);
+                }
+                allocated_at_frame_start = allocated;
+                let allocations = report_frame(frames);
+                debug_bundle::record(frames, turn, particles, stats.live_bytes, allocations);
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &mut metrics {
+                    metrics.publish(frames, turn, particles, time);
+                }
+                run_summary.frame(time, particles, turn);
+                frames += 1;
+            }
+
+            waiting_since = Instant::now();
+            watchdog.enter(Phase::Wait);
+            if event.render_args().is_some() {
+                frame_limiter.wait();
+            }
+        }
+        commands.stop();
+        running.join().unwrap_or_else(|_| {
+            eprintln!("The simulation thread panicked");
+            process::exit(1);
+        })
+    });
+    if let Some(run_report) = &mut run_report {
+        run_report.finish();
+    }
+    allocator::set_fidelity(Fidelity::Full);
+    let Simulation { mut world, recorder, video, population, energy, hashes, cohorts, scene_path, scene_text, .. } = simulation;
+    finish_recording(recorder);
+    finish_video(cli, video);
+    finish_journal(cli, &world);
+    write_debug_bundle(cli, &mut world);
+    if let Some(path) = &cli.session {
+        let session = Session {
+            scene_path,
+            scene_text,
+            width,
+            height,
+            overlays: Overlays {
+                paused,
+                hud: hud.visible,
+                frame_chart: show_frame_chart,
+                correlation: show_correlation,
+                survival: show_survival,
+                allocation_graph: show_allocation_graph,
+                noise_field: show_noise_field,
+            },
+            world: world.save_state(),
+        };
+        match session.save(path) {
+            Ok(()) => println!("session saved to {} at turn {}", path.display(), world.current_turn),
+            Err(err) => eprintln!("Could not write session {}: {}", path.display(), err),
+        }
+    }
+    if let Some(quarantine) = world.quarantine.as_ref().filter(|quarantine| quarantine.total > 0) {
+        println!("{} particles quarantined for non-finite values", quarantine.total);
+    }
+    print_scenario(&world);
+    let correlation = &lock(&charts).correlation;
+    if let Some(coefficient) = correlation.run.coefficient() {
+        println!(
+            "particle count and live heap correlate with r = {:.3} over {} turns",
+            coefficient,
+            correlation.run.count()
+        );
+    }
+    if cli.phase_timings {
+        timer.print_summary();
+    }
+    if !frame_drops.is_empty() {
+        let worst = frame_drops.iter().max_by_key(|drop| drop.time).map_or(Duration::ZERO, |drop| drop.time);
+        println!(
+            "{} of {} frames dropped, the worst took {:.1} ms",
+            frame_drops.len(),
+            frames,
+            worst.as_secs_f64() * 1e3
+        );
+    }
+    if let Some(path) = &cli.population_plot {
+        if let Err(err) = population.write_svg(path, &metadata) {
+            eprintln!("Could not write population plot {}: {}", path.display(), err);
+        }
+    }
+    if let Some(path) = &cli.population_csv {
+        if let Err(err) = population.write_csv(path, &metadata) {
+            eprintln!("Could not write population data {}: {}", path.display(), err);
         }
     }
+    write_energy_csv(cli, &energy, &metadata);
+    write_hashes(cli, hashes.as_ref(), &metadata);
+    write_cohorts(cli, cohorts.as_ref(), &world, &metadata);
+    write_survival(cli, &world, &metadata);
+    write_reuse_plot(cli, &metadata);
+    write_pprof(cli, &metadata);
+    run_summary.print(world.current_turn);
+    let text = bundle();
+    let mut summary = run_summary.rows(world.current_turn);
+    summary.insert(2, (text.dropped_frames, frame_drops.len().to_string()));
+    summary.push((text.particles, world.particles.len().to_string()));
+    write_report(cli, run_report.as_ref(), text.window_run, &summary, &metadata);
+}
 
-    fn update(&mut self) {
-        // Generate a random number between -3 a 3 inclusive
-        let n = self.rng.gen_range(-3..=3);
+// What the scenario of the run did, said at exit
+fn print_scenario(world: &World) {
+    let Some(run) = &world.scenario else {
+        return;
+    };
+    let scenario = run.scenario;
+    match scenario {
+        Scenario::Sawtooth => println!("scenario {}: {}, {} clears", scenario.name(), scenario.description(), run.clears),
+        Scenario::VecGrowth => println!(
+            "scenario {}: {}, {} particles of room left",
+            scenario.name(),
+            scenario.description(),
+            world.particles.capacity()
+        ),
+        _ => println!("scenario {}: {}", scenario.name(), scenario.description()),
+    }
+}
 
-        if n > 0 {
-            self.add_shapes(n);
-        } else {
-            self.remove_shapes(n);
+fn write_report(cli: &Cli, report: Option<&RunReport>, run: &str, summary: &[(&str, String)], metadata: &RunMetadata) {
+    let (Some(path), Some(report)) = (&cli.report, report) else {
+        return;
+    };
+    let scene = cli.config.as_deref().map_or(bundle().default_scene.to_string(), |path| path.display().to_string());
+    if let Err(err) = report.write_html(path, &fill(bundle().report_title, &[&run, &scene]), summary, metadata) {
+        eprintln!("Could not write report {}: {}", path.display(), err);
+    }
+}
+
+fn write_energy_csv(cli: &Cli, energy: &EnergyHistory, metadata: &RunMetadata) {
+    if let Some(path) = &cli.energy_csv {
+        if let Err(err) = energy.write_csv(path, metadata) {
+            eprintln!("Could not write energy data {}: {}", path.display(), err);
+        }
+    }
+}
+
+// The cohorts of --cohorts-csv, when asked for
+fn cohort_history(cli: &Cli) -> Option<CohortHistory> {
+    cli.cohorts_csv.as_ref().map(|_| CohortHistory::new(cli.cohort_turns))
+}
+
+// The cohorts of --cohorts-csv and the particles of --particles-csv, as the run left them
+fn write_cohorts(cli: &Cli, cohorts: Option<&CohortHistory>, world: &World, metadata: &RunMetadata) {
+    if let (Some(path), Some(cohorts)) = (&cli.cohorts_csv, cohorts) {
+        if let Err(err) = cohorts.write_csv(path, metadata) {
+            eprintln!("Could not write cohort data {}: {}", path.display(), err);
+        }
+    }
+    if let Some(path) = &cli.particles_csv {
+        if let Err(err) = population::write_particles_csv(path, world.current_turn, world.particles.iter().map(|p| &**p), metadata) {
+            eprintln!("Could not write particles {}: {}", path.display(), err);
         }
+    }
+}
 
-        self.particles.shrink_to_fit();
-        for shape in &mut self.particles {
-            shape.update();
+// The survival curve of --survival-csv, with the particles still alive
+fn write_survival(cli: &Cli, world: &World, metadata: &RunMetadata) {
+    if let (Some(path), Some(survival)) = (&cli.survival_csv, &world.survival) {
+        if let Err(err) = survival.write_csv(path, world.particles.iter().map(|p| p.age), metadata) {
+            eprintln!("Could not write the survival curve {}: {}", path.display(), err);
         }
+    }
+}
 
-        self.current_turn += 1;
+// The state hashes of --hashes, only when the run is seeded
+// With --preallocate, makes room in the histories of the window for the
+// turns it runs, as many as `RESERVED_STEPS` without --turns
+fn preallocate_histories(
+    cli: &Cli,
+    timeline: &mut Timeline,
+    population: &mut PopulationHistory,
+    energy: &mut EnergyHistory,
+    hashes: Option<&mut HashStream>,
+) {
+    if !cli.preallocate {
+        return;
+    }
+    let turns = cli.turns.unwrap_or(RESERVED_STEPS).min(RESERVED_STEPS) as usize;
+    timeline.reserve(turns);
+    population.reserve(turns);
+    if cli.energy_csv.is_some() {
+        energy.reserve(turns);
+    }
+    if let Some(hashes) = hashes {
+        hashes.reserve(turns);
     }
 }
 
-/// Render particles along the screen using the Piston game engine.
-fn main() {
-    let (width, height) = (1280.0, 960.0);
+fn hash_stream(cli: &Cli) -> Option<HashStream> {
+    cli.hashes.as_ref()?;
+    if cli.seed.is_none() {
+        eprintln!("--hashes needs --seed, or the hashes change from one run to the next");
+        process::exit(1);
+    }
+    Some(HashStream::new())
+}
 
-    // This does not work on Arch Linux x64 running in VirtualBox
-    // not even enabling 3D acceleration
-    let mut window: PistonWindow = WindowSettings::new(
-        "particles", [width, height]
-    )
+fn write_hashes(cli: &Cli, hashes: Option<&HashStream>, metadata: &RunMetadata) {
+    if let (Some(path), Some(hashes)) = (&cli.hashes, hashes) {
+        if let Err(err) = hashes.write(path, metadata) {
+            eprintln!("Could not write state hashes {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn diff_hashes(a: &Path, b: &Path) {
+    let load = |path: &Path| {
+        HashStream::load(path).unwrap_or_else(|err| {
+            eprintln!("Could not read state hashes {}: {}", path.display(), err);
+            process::exit(1);
+        })
+    };
+    let (hashes_a, hashes_b) = (load(a), load(b));
+
+    // Runs of different scenes or seeds aren't expected to agree
+    for key in ["seed", "config_hash"] {
+        let (in_a, in_b) = (hashes_a.metadata(key), hashes_b.metadata(key));
+        if in_a != in_b {
+            println!("{} differs: {} against {}", key, in_a.unwrap_or("none"), in_b.unwrap_or("none"));
+        }
+    }
+    match hashes_a.first_difference(&hashes_b) {
+        None => println!("same state after all {} turns", hashes_a.hashes.len()),
+        Some(Divergence::Turn(turn)) => {
+            println!("states differ after turn {}", turn);
+            telemetry::finish();
+            process::exit(1);
+        }
+        Some(Divergence::Length { a: turns_a, b: turns_b }) => {
+            println!(
+                "same state after the turns both ran, but {} ran {} turns and {} ran {}",
+                a.display(),
+                turns_a,
+                b.display(),
+                turns_b
+            );
+            telemetry::finish();
+            process::exit(1);
+        }
+    }
+}
+
+fn write_reuse_plot(cli: &Cli, metadata: &RunMetadata) {
+    if let Some(path) = &cli.reuse_plot {
+        if let Err(err) = reuse::distribution().write_svg(path, metadata) {
+            eprintln!("Could not write reuse distance plot {}: {}", path.display(), err);
+        }
+    }
+}
+
+// The server of --metrics, listening from the start of the run
+#[cfg(feature = "metrics")]
+fn start_metrics(cli: &Cli) -> Option<metrics::MetricsServer> {
+    let address = cli.metrics.as_ref()?;
+    match metrics::MetricsServer::bind(address) {
+        Ok(server) => {
+            println!("metrics on ws://{0} and http://{0}", server.address());
+            Some(server)
+        }
+        Err(err) => {
+            eprintln!("Could not serve metrics on {}: {}", address, err);
+            process::exit(1);
+        }
+    }
+}
+
+fn write_pprof(cli: &Cli, metadata: &RunMetadata) {
+    if let Some(path) = &cli.pprof {
+        if let Err(err) = pprof::write(path, metadata) {
+            eprintln!("Could not write pprof profile {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Steps several independent worlds at once and draws them in a grid, each
+/// cell showing a whole world scaled down.
+fn run_grid(cli: &Cli, scenes: &[PathBuf], seeds: &[u64], columns: Option<usize>) {
+    let count = scenes.len().max(seeds.len()).max(1);
+    let (width, height) = (cli.width, cli.height);
+
+    let mut worlds = Vec::with_capacity(count);
+    for i in 0..count {
+        let scene = if scenes.is_empty() { cli.config.as_deref() } else { Some(scenes[i % scenes.len()].as_path()) };
+        let seed = match seeds {
+            [] => cli.seed.map(|seed| seed.wrapping_add(i as u64)),
+            seeds => Some(seeds[i % seeds.len()]),
+        };
+
+        // Each world is its own run, so each one gets its own header
+        let metadata = RunMetadata::new(scene, seed);
+        telemetry::comment(&metadata.comment_header());
+        println!(
+            "world {}: {}, {}",
+            i + 1,
+            scene.map_or("default scene".to_string(), |path| path.display().to_string()),
+            seed.map_or("unseeded".to_string(), |seed| format!("seed {}", seed))
+        );
+        worlds.push(build_world(cli, scene, seed, width, height));
+    }
+
+    let columns = columns.unwrap_or_else(|| (count as f64).sqrt().ceil() as usize).clamp(1, count);
+    let rows = count.div_ceil(columns);
+    let (cell_width, cell_height) = (width / columns as f64, height / rows as f64);
+    let scale = (cell_width / width).min(cell_height / height);
+
+    let mut window: PistonWindow = WindowSettings::new(format!("particles: {} worlds", count), [width, height])
         .exit_on_esc(true)
         .build()
         .expect("Could not create a window.");
+    set_window_icon(&window);
 
-    let mut world = World::new(width, height);
-    world.add_shapes(1000);
-
+    for world in &mut worlds {
+        world.time_step = BASE_TURN_RATE / cli.turn_rate as f64;
+    }
+    let mut clock = TurnClock::new(cli.turn_rate as f64);
+    let mut paused = false;
     while let Some(event) = window.next() {
-        world.update();
+        if let Some(Button::Keyboard(Key::Space)) = event.press_args() {
+            paused = !paused;
+        }
+        clock.tick(Instant::now(), !paused);
+        while clock.next_turn() {
+            worlds.par_iter_mut().for_each(World::update);
+        }
 
         window.draw_2d(&event, |ctx, renderer, _device| {
-            clear([0.15, 0.17, 0.17, 0.9], renderer);
+            clear(BACKGROUND, renderer);
+
+            for (i, world) in worlds.iter().enumerate() {
+                let (x, y) = ((i % columns) as f64 * cell_width, (i / columns) as f64 * cell_height);
+                let cell = ctx.trans(x, y).scale(scale, scale);
+                let [red, green, blue, _] = world.background(world.current_turn);
+                rectangle([red, green, blue, 1.0], [0.0, 0.0, world.width, world.height], cell.transform, renderer);
+                draw_scene(world, cell, renderer);
+                draw_particles(world.particles.iter().map(|p| Sprite::of(p)), world.falloff.as_ref(), cell, renderer);
+            }
+
+            // Separates the cells
+            let border = [0.05, 0.06, 0.06, 1.0];
+            for column in 1..columns {
+                rectangle(border, [column as f64 * cell_width - 1.0, 0.0, 2.0, height], ctx.transform, renderer);
+            }
+            for row in 1..rows {
+                rectangle(border, [0.0, row as f64 * cell_height - 1.0, width, 2.0], ctx.transform, renderer);
+            }
+        });
+    }
+}
+
+// What every world of a process shares, which --compare can't change
+fn process_wide(cli: &Cli) -> (AllocatorBackend, bool, bool, bool) {
+    (cli.allocator, cli.safe_mode, cli.allocation_strategy == AllocationStrategy::Arena, cli.block_allocator || cli.preallocate)
+}
+
+// The command line of the second world of --compare: this one with the
+// options of --compare after it, which win over the same options before
+fn compared_cli(cli: &Cli, options: &str) -> Result<Cli, String> {
+    use clap::{CommandFactory, FromArgMatches};
+
+    let args = std::env::args_os().chain(options.split_whitespace().map(Into::into));
+    let matches = Cli::command().args_override_self(true).try_get_matches_from(args).map_err(|err| err.to_string())?;
+    let mut compared = Cli::from_arg_matches(&matches).map_err(|err| err.to_string())?;
+    compared.parallel |= compared.threads.is_some();
+    if compared.command.is_some() {
+        return Err("it takes options, not a command".to_string());
+    }
+    if process_wide(&compared) != process_wide(cli) {
+        return Err("the allocator, --safe-mode, the arena, the block allocator and --preallocate are the whole process's".to_string());
+    }
+    Ok(compared)
+}
+
+// Two worlds side by side, the second with the options of --compare, each
+// with a panel of what its own turns allocated and took
+fn run_compare(cli: &Cli) {
+    let options = cli.compare.as_deref().unwrap_or_default();
+    let compared = compared_cli(cli, options).unwrap_or_else(|err| {
+        eprintln!("Could not use --compare {}: {}", options, err);
+        process::exit(1);
+    });
+    let (width, height) = (cli.width, cli.height);
+    let labels = ["as given".to_string(), options.trim().to_string()];
+
+    let mut worlds = Vec::with_capacity(2);
+    for (i, side) in [cli, &compared].into_iter().enumerate() {
+        let mut metadata = RunMetadata::new(side.config.as_deref(), side.seed);
+        metadata.scenario = side.scenario;
+        metadata.vec_policy = side.vec_policy;
+        telemetry::comment(&metadata.comment_header());
+        println!("world {}: {}", i + 1, labels[i]);
+        let mut world = build_world(side, side.config.as_deref(), side.seed, width, height);
+        world.time_step = BASE_TURN_RATE / cli.turn_rate as f64;
+        worlds.push(world);
+    }
+
+    // Each world in half the window, at half its size, with its panel above it
+    let cell_width = width / 2.0;
+    let scale = cell_width / width;
+    let top = (height - height * scale) / 2.0;
+
+    let mut window: PistonWindow = WindowSettings::new("particles: compare", [width, height])
+        .exit_on_esc(true)
+        .build()
+        .expect("Could not create a window.");
+    set_window_icon(&window);
+    let mut hud = Hud::new(&mut window, &memory_stats()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    let mut meters = [WorldMeter::new(), WorldMeter::new()];
+    let mut clock = TurnClock::new(cli.turn_rate as f64);
+    let mut paused = false;
+    while let Some(event) = window.next() {
+        if shutdown::requested() {
+            break;
+        }
+        if let Some(Button::Keyboard(Key::Space)) = event.press_args() {
+            paused = !paused;
+        }
+        clock.tick(Instant::now(), !paused);
+        while clock.next_turn() {
+            // One after the other, so each meter counts its own world's allocations
+            for (world, meter) in worlds.iter_mut().zip(&mut meters) {
+                meter.update(world);
+            }
+        }
 
-            for s in &mut world.particles {
-                let size = [s.position[0], s.position[1], s.width, s.height];
-                rectangle(s.color, size, ctx.transform, renderer);
+        window.draw_2d(&event, |ctx, renderer, device| {
+            clear(BACKGROUND, renderer);
+            for (i, world) in worlds.iter().enumerate() {
+                let x = i as f64 * cell_width;
+                let cell = ctx.trans(x, top).scale(scale, scale);
+                let [red, green, blue, _] = world.background(world.current_turn);
+                rectangle([red, green, blue, 1.0], [0.0, 0.0, world.width, world.height], cell.transform, renderer);
+                draw_scene(world, cell, renderer);
+                draw_particles(world.particles.iter().map(|p| Sprite::of(p)), world.falloff.as_ref(), cell, renderer);
+
+                let mut lines = vec![labels[i].clone()];
+                lines.extend(meters[i].rows(world).into_iter().map(|(name, value)| format!("{} {}", name, value)));
+                hud.draw_panel(&lines, [x, 0.0], ctx, renderer, device);
             }
+            rectangle([0.05, 0.06, 0.06, 1.0], [cell_width - 1.0, 0.0, 2.0, height], ctx.transform, renderer);
         });
     }
-    
+
+    for (i, (world, meter)) in worlds.iter().zip(&meters).enumerate() {
+        println!("world {}: {}", i + 1, labels[i]);
+        for (name, value) in meter.rows(world) {
+            println!("  {:<22}{}", name, value);
+        }
+    }
 }
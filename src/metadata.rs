@@ -0,0 +1,147 @@
+use crate::capabilities::Capabilities;
+use crate::scenario::Scenario;
+use crate::vec_policy::StoragePolicy;
+use crate::{arena, backend, pool};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Describes the run an export comes from, so results stay interpretable
+/// when they are collected across machines.
+#[derive(Debug, Clone)]
+pub struct RunMetadata {
+    pub version: &'static str,
+    pub seed: Option<u64>,
+
+    /// FNV-1a hash of the scene file, `None` when running the default scene
+    pub config_hash: Option<u64>,
+    pub platform: String,
+    pub allocator: String,
+
+    /// Cargo features the binary was built with
+    pub features: Vec<&'static str>,
+    pub started: SystemTime,
+    pub capabilities: Capabilities,
+
+    /// Built-in scenario of `--scenario` the run drives the world with
+    pub scenario: Option<Scenario>,
+
+    /// Storage policy of `--vec-policy` the particle list follows
+    pub vec_policy: Option<StoragePolicy>,
+}
+
+impl RunMetadata {
+    pub fn new(config: Option<&Path>, seed: Option<u64>) -> RunMetadata {
+        RunMetadata {
+            version: env!("CARGO_PKG_VERSION"),
+            seed,
+            config_hash: config.and_then(|path| fs::read(path).ok()).map(|bytes| fnv1a(&bytes)),
+            platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            allocator: match (arena::is_enabled(), pool::is_enabled()) {
+                (true, _) => format!("{}+arena", backend::selected().name()),
+                (false, true) => format!("{}+block-pool", backend::selected().name()),
+                (false, false) => backend::selected().name().to_string(),
+            },
+            features: enabled_features(),
+            started: SystemTime::now(),
+            capabilities: Capabilities::detect(),
+            scenario: None,
+            vec_policy: None,
+        }
+    }
+
+    /// Field names and values, in the order they are written.
+    /// The display is only known to windowed runs.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let capabilities = &self.capabilities;
+        let mut fields = vec![
+            ("version", self.version.to_string()),
+            ("seed", self.seed.map_or("unseeded".to_string(), |seed| seed.to_string())),
+            ("config_hash", self.config_hash.map_or("default".to_string(), |hash| format!("{:016x}", hash))),
+            ("platform", self.platform.clone()),
+            ("allocator", self.allocator.clone()),
+            ("profile", build_profile().to_string()),
+            ("features", if self.features.is_empty() { "none".to_string() } else { self.features.join(",") }),
+            ("started", format_utc(self.started)),
+            ("cpu_cores", capabilities.cpu_cores.to_string()),
+            ("page_size", capabilities.page_size.map_or("unknown".to_string(), |size| size.to_string())),
+            ("simd", if capabilities.simd.is_empty() { "none".to_string() } else { capabilities.simd.join(",") }),
+        ];
+        if let Some(scenario) = self.scenario {
+            fields.push(("scenario", format!("{}, {}", scenario.name(), scenario.description())));
+        }
+        if let Some(policy) = self.vec_policy {
+            fields.push(("vec_policy", policy.to_string()));
+        }
+        if let Some(display) = &capabilities.display {
+            fields.push(("gl_version", display.gl_version.clone()));
+            fields.push(("gl_renderer", display.gl_renderer.clone()));
+            fields.push(("refresh_rate", display.refresh_rate.map_or("unknown".to_string(), |rate| format!("{} Hz", rate))));
+        }
+        fields
+    }
+
+    /// The metadata as `# key: value` lines, the header used by CSV and trace files.
+    pub fn comment_header(&self) -> String {
+        let mut header = String::from("# memory-particles run\n");
+        for (key, value) in self.fields() {
+            header.push_str(&format!("# {}: {}\n", key, value));
+        }
+        header
+    }
+}
+
+/// Cargo features the binary was built with, of those the crate has.
+pub fn enabled_features() -> Vec<&'static str> {
+    let features = [("ffi", cfg!(feature = "ffi")), ("python", cfg!(feature = "python")), ("metrics", cfg!(feature = "metrics")), ("wgpu", cfg!(feature = "wgpu"))];
+    features.iter().filter(|(_, enabled)| *enabled).map(|&(name, _)| name).collect()
+}
+
+/// `release` or `debug`, whose timings can't be compared with each other.
+pub fn build_profile() -> &'static str {
+    if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    }
+}
+
+// Stable across platforms and Rust versions, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// Formats a time as an ISO 8601 UTC timestamp like `2024-03-01T12:30:00Z`
+fn format_utc(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+    let (year, month, day) = civil_date(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// Year, month and day of `days` since 1970-01-01.
+pub fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,270 @@
+use crate::allocator::{memory_stats, MemoryStats};
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Frames waiting to be sent; a run doesn't wait for slow clients, the
+// frames that don't fit are dropped and counted
+const QUEUE_CAPACITY: usize = 256;
+
+// How long a new connection has to send its request, after which it is
+// taken for a plain TCP client, and how long a client may stall a write
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_REQUEST: usize = 8192;
+
+// Appended to the key of a WebSocket handshake before hashing, by RFC 6455
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// What a frame of the run did, as sent to the clients of `--metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FrameMetrics {
+    pub frame: u64,
+    pub turn: u64,
+    pub particles: usize,
+    pub live_bytes: usize,
+
+    /// Allocations made during the frame, and the bytes they asked for
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+    pub frame_ms: f64,
+}
+
+/// Streams a JSON object of `FrameMetrics` per frame to every client
+/// connected to it, so a dashboard or a browser page can chart a run on
+/// another machine. Clients opening a WebSocket get one text message per
+/// frame; other HTTP requests get the objects as JSON Lines, like `curl`
+/// shows them, and connections that send nothing, like `nc`, get the same
+/// lines without an HTTP header. Clients are sent frames from the moment
+/// they connect, and dropped once a write to them fails or stalls.
+pub struct MetricsServer {
+    address: SocketAddr,
+    frames: SyncSender<String>,
+    clients: Arc<Clients>,
+    last: MemoryStats,
+}
+
+// Read by the run every frame without waiting on the lock the sending
+// thread holds while it writes
+#[derive(Default)]
+struct Clients {
+    list: Mutex<Vec<Client>>,
+    connected: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+struct Client {
+    stream: TcpStream,
+    websocket: bool,
+}
+
+impl MetricsServer {
+    /// Listens on `address`, like `127.0.0.1:9000` or `0.0.0.0:9000` for
+    /// other machines, accepting clients and sending them frames from
+    /// threads of its own.
+    pub fn bind(address: &str) -> io::Result<MetricsServer> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let clients = Arc::new(Clients::default());
+        let (frames, queued) = mpsc::sync_channel(QUEUE_CAPACITY);
+
+        let accepted = Arc::clone(&clients);
+        thread::Builder::new().name("metrics-accept".to_string()).spawn(move || accept(listener, accepted))?;
+        let sent = Arc::clone(&clients);
+        thread::Builder::new().name("metrics-send".to_string()).spawn(move || send(queued, sent))?;
+        Ok(MetricsServer { address, frames, clients, last: memory_stats() })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.connected.load(Ordering::Relaxed)
+    }
+
+    /// Frames that were dropped because the clients were too slow for them.
+    pub fn dropped(&self) -> u64 {
+        self.clients.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Sends what the allocator did since the frame before, along with the
+    /// rest of `frame`. Nothing is formatted while no one is connected.
+    pub fn publish(&mut self, frame: u64, turn: u64, particles: usize, time: Duration) {
+        let stats = memory_stats();
+        let frame = FrameMetrics {
+            frame,
+            turn,
+            particles,
+            live_bytes: stats.live_bytes,
+            allocations: stats.allocations - self.last.allocations,
+            allocated_bytes: stats.allocated_bytes - self.last.allocated_bytes,
+            frame_ms: time.as_secs_f64() * 1e3,
+        };
+        self.last = stats;
+        if self.clients() == 0 {
+            return;
+        }
+        let Ok(json) = serde_json::to_string(&frame) else {
+            return;
+        };
+        if let Err(TrySendError::Full(_)) = self.frames.try_send(json) {
+            self.clients.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn accept(listener: TcpListener, clients: Arc<Clients>) {
+    for stream in listener.incoming().flatten() {
+        // Each handshake on a thread of its own, so a slow one holds up no one
+        let clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            if let Ok(client) = handshake(stream) {
+                let mut list = clients.list.lock().unwrap_or_else(|err| err.into_inner());
+                list.push(client);
+                clients.connected.store(list.len(), Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+fn send(frames: Receiver<String>, clients: Arc<Clients>) {
+    for json in frames {
+        let mut list = clients.list.lock().unwrap_or_else(|err| err.into_inner());
+        list.retain_mut(|client| {
+            let message = if client.websocket { websocket_frame(json.as_bytes()) } else { format!("{}\n", json).into_bytes() };
+            client.stream.write_all(&message).is_ok()
+        });
+        clients.connected.store(list.len(), Ordering::Relaxed);
+    }
+}
+
+// Reads the request of a new connection and answers it, as a WebSocket when
+// it asks for one
+fn handshake(mut stream: TcpStream) -> io::Result<Client> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+            // Nothing asked for: a plain TCP client
+            Err(err) if request.is_empty() && matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return Ok(Client { stream, websocket: false });
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let key = request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("sec-websocket-key").then(|| value.trim().to_string())
+    });
+    match key {
+        Some(key) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                websocket_accept(&key)
+            )?;
+            Ok(Client { stream, websocket: true })
+        }
+        None => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nCache-Control: no-cache\r\n\
+                 Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n"
+            )?;
+            Ok(Client { stream, websocket: false })
+        }
+    }
+}
+
+/// The `Sec-WebSocket-Accept` answering a handshake with `key`.
+pub fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+// A single unmasked text frame holding `payload`, as servers send them
+fn websocket_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// SHA-1, which the WebSocket handshake needs and nothing else here does
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            words[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
@@ -0,0 +1,273 @@
+use crate::config::Config;
+use std::ops::Range;
+use toml_edit::{DocumentMut, ImDocument, Item, Table, TableLike, Value};
+
+/// Version of the scene format this build reads and writes. Files without a
+/// `version` are from before there were versions, version 1.
+pub const SCHEMA_VERSION: u32 = 3;
+
+// Upgrades from version `i + 1` to `i + 2`, in order
+const MIGRATIONS: [fn(&mut DocumentMut, &mut Vec<Change>); SCHEMA_VERSION as usize - 1] =
+    [drop_ignored_keys, opaque_ambient_backgrounds];
+
+// Where version 1 read past keys it didn't know instead of rejecting them
+const LENIENT_TABLES: [&str; 4] = ["emitter", "obstacle", "zone", "sparks"];
+const LENIENT_KEYS: [&str; 2] = ["color", "fade"];
+
+/// A step of the way from the top of a scene file to a setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path as written in the file, like `obstacle.2.radius`.
+pub fn describe(path: &[Segment]) -> String {
+    let parts: Vec<String> = path
+        .iter()
+        .map(|segment| match segment {
+            Segment::Key(key) => key.clone(),
+            Segment::Index(index) => index.to_string(),
+        })
+        .collect();
+    parts.join(".")
+}
+
+/// Something a migration changed, at the setting it changed.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub path: Vec<Segment>,
+    pub message: String,
+}
+
+/// A scene file brought up to `SCHEMA_VERSION`.
+pub struct Migration {
+    /// Version the file was written for
+    pub from: u32,
+
+    /// The upgraded file, comments and layout kept; the original text when
+    /// it was already current
+    pub text: String,
+    pub changes: Vec<Change>,
+}
+
+impl Migration {
+    pub fn upgraded(&self) -> bool {
+        self.from < SCHEMA_VERSION
+    }
+}
+
+fn version_of(root: &Table) -> Result<u32, String> {
+    match root.get("version") {
+        None => Ok(1),
+        Some(item) => match item.as_integer() {
+            Some(version) if version >= 1 && version <= u32::MAX as i64 => Ok(version as u32),
+            _ => Err(format!("version must be a whole number from 1 up, not {}", item.to_string().trim())),
+        },
+    }
+}
+
+/// Upgrades a scene written for any earlier version of the format to the
+/// current one, one version at a time. Files for a newer version than this
+/// build knows are refused.
+pub fn migrate(text: &str) -> Result<Migration, String> {
+    let mut document: DocumentMut = text.parse().map_err(|err: toml_edit::TomlError| err.to_string())?;
+    let from = version_of(document.as_table())?;
+    if from > SCHEMA_VERSION {
+        return Err(format!(
+            "the scene is written for version {} of the format, newer than version {} this build reads",
+            from, SCHEMA_VERSION
+        ));
+    }
+    if from == SCHEMA_VERSION {
+        return Ok(Migration { from, text: text.to_string(), changes: Vec::new() });
+    }
+
+    let mut changes = Vec::new();
+    for migration in &MIGRATIONS[from as usize - 1..] {
+        migration(&mut document, &mut changes);
+    }
+    let text = match document.get_mut("version") {
+        Some(version) => {
+            *version = toml_edit::value(SCHEMA_VERSION as i64);
+            document.to_string()
+        }
+        None => stamp(&document.to_string()),
+    };
+    Ok(Migration { from, text, changes })
+}
+
+// Adds the version under the comment describing the scene, if there is one
+fn stamp(text: &str) -> String {
+    let comment: usize = text.split_inclusive('\n').take_while(|line| line.starts_with('#')).map(str::len).sum();
+    let (head, rest) = text.split_at(comment);
+    let rest = rest.trim_start_matches(['\n', '\r']);
+    let gap = if head.is_empty() { "" } else { "\n" };
+    format!("{}{}version = {}\n\n{}", head, gap, SCHEMA_VERSION, rest)
+}
+
+// Version 2 rejects the keys nothing reads in emitters, obstacles, zones,
+// sparks, colors and fades, which version 1 skipped without a word. Each is
+// found by loading the scene until it loads or fails for another reason.
+fn drop_ignored_keys(document: &mut DocumentMut, changes: &mut Vec<Change>) {
+    loop {
+        let text = document.to_string();
+        let Err(err) = toml::from_str::<Config>(&text) else {
+            return;
+        };
+        let (Some(key), Some(span)) = (unknown_field(err.message()), err.span()) else {
+            return;
+        };
+        let Ok(parsed) = ImDocument::parse(text.as_str()) else {
+            return;
+        };
+        let Some(table) = table_with(parsed.as_item(), &mut Vec::new(), key, span.start) else {
+            return;
+        };
+        if !lenient(&table) {
+            return;
+        }
+        let mut path = table;
+        path.push(Segment::Key(key.to_string()));
+        if !remove(document.as_item_mut(), &path) {
+            return;
+        }
+        let message = format!("unknown key {}, ignored by version 1, is dropped", describe(&path));
+        changes.push(Change { path, message });
+    }
+}
+
+// Version 3 paints the background over the frame before by its alpha, where
+// version 2 painted it opaque whatever its alpha, so the backgrounds of
+// ambient looks are made opaque to look as they did.
+fn opaque_ambient_backgrounds(document: &mut DocumentMut, changes: &mut Vec<Change>) {
+    for look in ["day", "night"] {
+        // Looked up through the tables, as indexing an item adds what it misses
+        let background = document
+            .get_mut("ambient")
+            .and_then(Item::as_table_like_mut)
+            .and_then(|ambient| ambient.get_mut(look))
+            .and_then(Item::as_table_like_mut)
+            .and_then(|look| look.get_mut("background"));
+        let Some(alpha) = background.and_then(Item::as_array_mut).and_then(|color| color.get_mut(3)) else {
+            continue;
+        };
+        let opaque = alpha.as_float().map_or(alpha.as_integer() == Some(1), |alpha| alpha >= 1.0);
+        if opaque {
+            continue;
+        }
+        let old = alpha.to_string();
+        let decor = alpha.decor().clone();
+        *alpha = Value::from(1.0);
+        *alpha.decor_mut() = decor;
+
+        let path = ["ambient", look, "background"].map(|key| Segment::Key(key.to_string())).into_iter().chain([Segment::Index(3)]).collect();
+        let message = format!(
+            "alpha {} of ambient.{}.background, drawn opaque by version 2, is made 1.0",
+            old.trim(),
+            look
+        );
+        changes.push(Change { path, message });
+    }
+}
+
+fn lenient(table: &[Segment]) -> bool {
+    let key = |segment: Option<&Segment>, names: &[&str]| match segment {
+        Some(Segment::Key(key)) => names.contains(&key.as_str()),
+        _ => false,
+    };
+    key(table.first(), &LENIENT_TABLES) || (table.len() == 1 && key(table.first(), &LENIENT_KEYS))
+}
+
+// The name in serde's "unknown field `name`, expected ..."
+fn unknown_field(message: &str) -> Option<&str> {
+    message.strip_prefix("unknown field `")?.split('`').next()
+}
+
+// Path of the table the failing key is in: the one whose key is at `offset`,
+// or else the last table with that key starting before it, since enums
+// report the start of their table
+fn table_with(item: &Item, path: &mut Vec<Segment>, key: &str, offset: usize) -> Option<Vec<Segment>> {
+    let mut found = None;
+    let mut best = None;
+    visit(item, path, &mut |path, table, start| {
+        let Some((name, _)) = table.get_key_value(key) else {
+            return;
+        };
+        if name.span().is_some_and(|span| span.contains(&offset)) {
+            found = Some(path.to_vec());
+        } else if start <= offset && best.as_ref().is_none_or(|(at, _)| start >= *at) {
+            best = Some((start, path.to_vec()));
+        }
+    });
+    found.or(best.map(|(_, path)| path))
+}
+
+// Calls `found` with every table, inline or not, and where it starts
+type Visitor<'a> = dyn FnMut(&[Segment], &dyn TableLike, usize) + 'a;
+
+fn visit(item: &Item, path: &mut Vec<Segment>, found: &mut Visitor) {
+    match item {
+        Item::Table(table) => visit_table(table, table.span(), path, found),
+        Item::ArrayOfTables(tables) => {
+            for (index, table) in tables.iter().enumerate() {
+                path.push(Segment::Index(index));
+                visit_table(table, table.span(), path, found);
+                path.pop();
+            }
+        }
+        Item::Value(value) => visit_value(value, path, found),
+        Item::None => {}
+    }
+}
+
+fn visit_value(value: &Value, path: &mut Vec<Segment>, found: &mut Visitor) {
+    match value {
+        Value::InlineTable(table) => visit_table(table, table.span(), path, found),
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                path.push(Segment::Index(index));
+                visit_value(value, path, found);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn visit_table(table: &dyn TableLike, span: Option<Range<usize>>, path: &mut Vec<Segment>, found: &mut Visitor) {
+    found(path, table, span.map_or(0, |span| span.start));
+    for (key, child) in table.iter() {
+        path.push(Segment::Key(key.to_string()));
+        visit(child, path, found);
+        path.pop();
+    }
+}
+
+fn remove(item: &mut Item, path: &[Segment]) -> bool {
+    let Some((Segment::Key(key), parents)) = path.split_last() else {
+        return false;
+    };
+    let mut item = item;
+    for segment in parents {
+        item = match segment {
+            Segment::Key(key) => match item.get_mut(key.as_str()) {
+                Some(item) => item,
+                None => return false,
+            },
+            Segment::Index(index) => match item.get_mut(*index) {
+                Some(item) => item,
+                None => return false,
+            },
+        };
+    }
+    match item {
+        // The spacing before the closing brace goes with the last key
+        Item::Value(Value::InlineTable(table)) => {
+            let removed = table.remove(key).is_some();
+            table.fmt();
+            removed
+        }
+        item => item.as_table_like_mut().and_then(|table| table.remove(key)).is_some(),
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::fd::AsRawFd;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// How huge pages back an `MmapArray`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePages {
+    /// Regular pages
+    Off,
+
+    /// Explicit huge pages from the kernel's pool (`MAP_HUGETLB`)
+    Reserved,
+
+    /// Transparent huge pages requested with `madvise`, used when no
+    /// reserved huge pages are available
+    Transparent,
+}
+
+/// A fixed-capacity array placed in an anonymous `mmap` region instead of the
+/// heap, so its memory never goes through the global allocator. Pages are only
+/// backed by physical memory when first touched.
+pub struct MmapArray<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    mapped_bytes: usize,
+    huge_pages: HugePages,
+    _owns: PhantomData<T>,
+}
+
+// Huge pages on x86-64 and most 64-bit ARM kernels
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+impl<T> MmapArray<T> {
+    /// Maps room for `capacity` elements, trying huge pages when asked to.
+    pub fn with_capacity(capacity: usize, huge_pages: bool) -> io::Result<MmapArray<T>> {
+        let bytes = (capacity.max(1) * std::mem::size_of::<T>()).max(1);
+
+        if huge_pages {
+            if let Some(array) = Self::map(capacity, round_up(bytes, HUGE_PAGE_SIZE), HugePages::Reserved) {
+                return Ok(array);
+            }
+        }
+        let mode = if huge_pages { HugePages::Transparent } else { HugePages::Off };
+        let bytes = if huge_pages { round_up(bytes, HUGE_PAGE_SIZE) } else { bytes };
+        Self::map(capacity, bytes, mode).ok_or_else(io::Error::last_os_error)
+    }
+
+    fn map(capacity: usize, bytes: usize, huge_pages: HugePages) -> Option<MmapArray<T>> {
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        #[cfg(target_os = "linux")]
+        if huge_pages == HugePages::Reserved {
+            flags |= libc::MAP_HUGETLB;
+        }
+        #[cfg(not(target_os = "linux"))]
+        if huge_pages == HugePages::Reserved {
+            return None;
+        }
+
+        // SAFETY: an anonymous private mapping with no address hint doesn't
+        // alias any memory of the program
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), bytes, libc::PROT_READ | libc::PROT_WRITE, flags, -1, 0) };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+
+        #[cfg(target_os = "linux")]
+        if huge_pages == HugePages::Transparent {
+            // Only advice: the kernel may still use regular pages
+            // SAFETY: the range is the mapping just created
+            unsafe { libc::madvise(ptr, bytes, libc::MADV_HUGEPAGE) };
+        }
+
+        Some(MmapArray {
+            ptr: NonNull::new(ptr.cast())?,
+            len: 0,
+            capacity,
+            mapped_bytes: bytes,
+            huge_pages,
+            _owns: PhantomData,
+        })
+    }
+
+    pub fn huge_pages(&self) -> HugePages {
+        self.huge_pages
+    }
+
+    pub fn mapped_bytes(&self) -> usize {
+        self.mapped_bytes
+    }
+
+    /// Appends an element, or gives it back when the array is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.len == self.capacity {
+            return Err(value);
+        }
+        // SAFETY: `len < capacity`, so the slot is inside the mapping and unused
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: the first `len` slots are initialized and borrowed mutably once
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for MmapArray<T> {
+    fn drop(&mut self) {
+        // SAFETY: drops the initialized elements once, then unmaps the region
+        // created in `map` with the same size
+        unsafe {
+            ptr::drop_in_place(self.as_mut_slice());
+            libc::munmap(self.ptr.as_ptr().cast(), self.mapped_bytes);
+        }
+    }
+}
+
+/// A whole file mapped read-only, so reading any part of it only faults in
+/// the pages touched, however big the file is.
+pub struct MappedFile {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl MappedFile {
+    pub fn open(file: &File) -> io::Result<MappedFile> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            // Empty mappings are refused, and there's nothing to read anyway
+            return Ok(MappedFile { ptr: NonNull::dangling(), len: 0 });
+        }
+        // SAFETY: a private read-only mapping of an open file; changes made
+        // to the file by other processes while mapped are the caller's risk
+        let ptr = unsafe { libc::mmap(ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error)?;
+        Ok(MappedFile { ptr, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping holds `len` readable bytes for as long as it lives
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: unmaps the region created in `open` with the same size
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
+
+fn round_up(bytes: usize, to: usize) -> usize {
+    bytes.div_ceil(to) * to
+}
+
+/// Minor and major page faults of the process so far.
+pub fn page_faults() -> (u64, u64) {
+    // SAFETY: `getrusage` only writes into the zeroed struct we pass
+    let usage = unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+        usage
+    };
+    (usage.ru_minflt as u64, usage.ru_majflt as u64)
+}
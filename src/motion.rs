@@ -0,0 +1,131 @@
+use graphics::math::Vec2d;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+/// Shape of a path followed over time.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PathShape {
+    Circle {
+        center: Vec2d<f64>,
+        radius: f64,
+    },
+    Lissajous {
+        center: Vec2d<f64>,
+        amplitude: Vec2d<f64>,
+        frequency: Vec2d<f64>,
+        #[serde(default)]
+        phase: f64,
+    },
+    Waypoints {
+        points: Vec<Vec2d<f64>>,
+    },
+}
+
+/// A parametric path advanced once per turn.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MotionPath {
+    #[serde(flatten)]
+    pub shape: PathShape,
+
+    /// Distance covered each turn: radians for circles and Lissajous
+    /// curves, pixels for waypoint lists
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+
+    /// Start over after reaching the end instead of stopping there.
+    /// A looping waypoint list also travels back from the last point to the first
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_speed() -> f64 {
+    0.02
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl MotionPath {
+    /// Position along the path after `turn` turns.
+    pub fn position(&self, turn: u64) -> Vec2d<f64> {
+        let travelled = turn as f64 * self.speed;
+
+        match &self.shape {
+            PathShape::Circle { center, radius } => {
+                let angle = self.limit(travelled, TAU);
+                [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+            }
+            PathShape::Lissajous { center, amplitude, frequency, phase } => {
+                let t = self.limit(travelled, TAU);
+                [
+                    center[0] + amplitude[0] * (frequency[0] * t + phase).sin(),
+                    center[1] + amplitude[1] * (frequency[1] * t).sin(),
+                ]
+            }
+            PathShape::Waypoints { points } => self.along_waypoints(points, travelled),
+        }
+    }
+
+    /// Stretches the path by `scale` on each axis, for a window resized by
+    /// that much. Circles stay round, scaled by the smaller factor.
+    pub fn rescale(&mut self, scale: Vec2d<f64>) {
+        let stretch = |point: &mut Vec2d<f64>| *point = [point[0] * scale[0], point[1] * scale[1]];
+        match &mut self.shape {
+            PathShape::Circle { center, radius } => {
+                stretch(center);
+                *radius *= scale[0].min(scale[1]);
+            }
+            PathShape::Lissajous { center, amplitude, .. } => {
+                stretch(center);
+                stretch(amplitude);
+            }
+            PathShape::Waypoints { points } => points.iter_mut().for_each(stretch),
+        }
+    }
+
+    // Wraps the travelled distance when looping, otherwise stops at the end
+    fn limit(&self, travelled: f64, length: f64) -> f64 {
+        if length <= 0.0 {
+            0.0
+        } else if self.looping {
+            travelled.rem_euclid(length)
+        } else {
+            travelled.min(length)
+        }
+    }
+
+    fn along_waypoints(&self, points: &[Vec2d<f64>], travelled: f64) -> Vec2d<f64> {
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return [0.0, 0.0],
+        };
+
+        // Looping paths close the polygon back to the first point.
+        // Kept as an iterator so following a path never allocates
+        let closing = (self.looping && points.len() > 2).then_some((last, first));
+        let segments = points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing);
+
+        let length: f64 = segments.clone().map(|(a, b)| distance(a, b)).sum();
+        let mut remaining = self.limit(travelled, length);
+
+        for (a, b) in segments {
+            let segment = distance(a, b);
+            if remaining <= segment && segment > 0.0 {
+                let fraction = remaining / segment;
+                return [a[0] + (b[0] - a[0]) * fraction, a[1] + (b[1] - a[1]) * fraction];
+            }
+            remaining -= segment;
+        }
+
+        if self.looping { first } else { last }
+    }
+}
+
+fn distance(a: Vec2d<f64>, b: Vec2d<f64>) -> f64 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
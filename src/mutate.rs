@@ -0,0 +1,122 @@
+use crate::params::{ParamRegistry, ParamValue, Tunable};
+use rand::seq::index;
+use rand::Rng;
+
+/// Parameters a mutation changes at once.
+pub const MUTATED_PARAMS: usize = 3;
+
+// Largest change of a parameter, as a fraction of its value; a parameter at
+// 0 moves by up to this share of a twentieth of its range instead
+const STRENGTH: f64 = 0.5;
+
+/// One parameter changed by a mutation, with its values in text form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Random changes to a few tunable parameters at once, to stumble on looks
+/// no scene was written for. Every change stays in the range of its
+/// parameter and near its value, so a mutation tweaks a scene rather than
+/// wrecking it, and the mutations applied can be undone in turn, newest
+/// first.
+#[derive(Debug, Default)]
+pub struct Mutations {
+    applied: Vec<Vec<Change>>,
+
+    // Value of every parameter mutated so far before its first mutation
+    originals: Vec<(String, String)>,
+}
+
+impl Mutations {
+    pub fn new() -> Mutations {
+        Mutations::default()
+    }
+
+    /// Mutations applied and not undone yet.
+    pub fn len(&self) -> usize {
+        self.applied.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+    }
+
+    /// Changes up to `MUTATED_PARAMS` parameters picked at random, returning
+    /// the changes, none when there is nothing to tune.
+    pub fn mutate<R: Rng>(&mut self, tunable: &mut dyn Tunable, rng: &mut R) -> &[Change] {
+        let params = ParamRegistry::collect(tunable).params;
+        let mut changes = Vec::new();
+        for picked in index::sample(rng, params.len(), MUTATED_PARAMS.min(params.len())) {
+            let param = &params[picked];
+            let after = perturb(param.value, rng);
+            if after == param.current() || ParamRegistry::set(tunable, &param.name, &after).is_err() {
+                continue;
+            }
+            if !self.originals.iter().any(|(name, _)| *name == param.name) {
+                self.originals.push((param.name.clone(), param.current()));
+            }
+            changes.push(Change { name: param.name.clone(), before: param.current(), after });
+        }
+        if changes.is_empty() {
+            return &[];
+        }
+        self.applied.push(changes);
+        self.applied.last().map_or(&[], |changes| changes.as_slice())
+    }
+
+    /// Sets the parameters of the last mutation back, returning its changes.
+    pub fn undo(&mut self, tunable: &mut dyn Tunable) -> Option<Vec<Change>> {
+        let changes = self.applied.pop()?;
+        for change in changes.iter().rev() {
+            // It was set from this value, which is in range
+            let _ = ParamRegistry::set(tunable, &change.name, &change.before);
+        }
+        Some(changes)
+    }
+
+    /// `NAME=VALUE` assignments of `--set` giving every parameter mutated so
+    /// far its current value, which reproduce the mutated scene from its file.
+    pub fn assignments(&self, tunable: &mut dyn Tunable) -> Vec<String> {
+        let params = ParamRegistry::collect(tunable).params;
+        self.originals
+            .iter()
+            .filter_map(|(name, original)| params.iter().find(|param| param.name == *name).map(|param| (param, original)))
+            .filter(|(param, original)| param.current() != **original)
+            .map(|(param, _)| format!("{}={}", param.name, param.current()))
+            .collect()
+    }
+
+    /// Forgets every mutation, when another scene is loaded.
+    pub fn clear(&mut self) {
+        self.applied.clear();
+        self.originals.clear();
+    }
+}
+
+// A nearby value in the range, in text form; floats are rounded to four
+// decimals so the log stays readable, and an unlimited one like an infinite
+// speed limit is left as it is
+fn perturb<R: Rng>(value: ParamValue, rng: &mut R) -> String {
+    match value {
+        ParamValue::Float { value, .. } if !value.is_finite() => value.to_string(),
+        ParamValue::Float { value, min, max } => {
+            let reach = if value != 0.0 {
+                value.abs() * STRENGTH
+            } else if (max - min).is_finite() {
+                (max - min) / 20.0 * STRENGTH
+            } else {
+                STRENGTH
+            };
+            let moved = value + rng.gen_range(-reach..=reach);
+            ((moved * 1e4).round() / 1e4).clamp(min, max).to_string()
+        }
+        ParamValue::Integer { value, min, max } => {
+            let reach = ((value as f64 * STRENGTH) as i128).max(1);
+            let moved = value as i128 + rng.gen_range(-reach..=reach);
+            moved.clamp(min as i128, max as i128).to_string()
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use graphics::math::Vec2d;
+
+/// Octaves of `fractal3` past which the others are left out, each finer
+/// than a pixel for any useful frequency.
+pub const MAX_OCTAVES: u32 = 8;
+
+// Seeds of the two components of `vector`, apart so they don't follow each other
+const Y_SEED: u32 = 0x9e37_79b9;
+
+/// Gradient noise in three dimensions, Perlin's improved noise with the
+/// gradients of every lattice point hashed from it and `seed` rather than
+/// looked up in a permutation table, so nothing is allocated and any seed
+/// gives another field. Smooth everywhere, about -1 to 1, and 0 on the
+/// lattice points.
+pub fn perlin3([x, y, z]: [f64; 3], seed: u32) -> f64 {
+    let (xi, yi, zi) = (x.floor(), y.floor(), z.floor());
+    let (xf, yf, zf) = (x - xi, y - yi, z - zi);
+    let (xi, yi, zi) = (xi as i32, yi as i32, zi as i32);
+    let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let hash = hash(xi.wrapping_add(dx), yi.wrapping_add(dy), zi.wrapping_add(dz), seed);
+        gradient(hash, xf - dx as f64, yf - dy as f64, zf - dz as f64)
+    };
+    let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+    lerp(
+        w,
+        lerp(v, lerp(u, corner(0, 0, 0), corner(1, 0, 0)), lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+        lerp(v, lerp(u, corner(0, 0, 1), corner(1, 0, 1)), lerp(u, corner(0, 1, 1), corner(1, 1, 1))),
+    )
+}
+
+/// `perlin3` summed over `octaves`, each of twice the frequency and half
+/// the weight of the one before, scaled back to about -1 to 1: the same
+/// large eddies with finer ones over them.
+pub fn fractal3(point: [f64; 3], octaves: u32, seed: u32) -> f64 {
+    let (mut sum, mut weight, mut total, mut scale) = (0.0, 1.0, 0.0, 1.0);
+    for octave in 0..octaves.clamp(1, MAX_OCTAVES) {
+        sum += weight * perlin3(point.map(|value| value * scale), seed.wrapping_add(octave));
+        total += weight;
+        weight /= 2.0;
+        scale *= 2.0;
+    }
+    sum / total
+}
+
+/// A vector of two independent fields of `fractal3` at `point`, each
+/// component about -1 to 1.
+pub fn vector(point: [f64; 3], octaves: u32, seed: u32) -> Vec2d<f64> {
+    [fractal3(point, octaves, seed), fractal3(point, octaves, seed ^ Y_SEED)]
+}
+
+// Smoothstep of the fifth degree, whose first two derivatives are 0 on the
+// lattice, so the noise has no creases along it
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = seed ^ (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+// Dot product of the offset from a lattice point with one of the twelve
+// gradients of Perlin, the directions to the edges of a cube
+fn gradient(hash: u32, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
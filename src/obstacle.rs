@@ -0,0 +1,82 @@
+use graphics::math::Vec2d;
+use serde::Deserialize;
+
+/// A static shape placed in the scene that particles bounce off.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Obstacle {
+    Circle {
+        center: Vec2d<f64>,
+        radius: f64,
+    },
+    Rect {
+        position: Vec2d<f64>,
+        size: Vec2d<f64>,
+    },
+}
+
+impl Obstacle {
+    /// Pushes a point that entered the obstacle back to its surface and
+    /// reflects its velocity, returning the contact point if it collided.
+    pub fn collide(&self, position: &mut Vec2d<f64>, velocity: &mut Vec2d<f64>) -> Option<Vec2d<f64>> {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let offset = [position[0] - center[0], position[1] - center[1]];
+                let distance = (offset[0] * offset[0] + offset[1] * offset[1]).sqrt();
+                if distance >= *radius || distance == 0.0 {
+                    return None;
+                }
+
+                let normal = [offset[0] / distance, offset[1] / distance];
+                let towards = velocity[0] * normal[0] + velocity[1] * normal[1];
+                *position = [center[0] + normal[0] * radius, center[1] + normal[1] * radius];
+
+                // Only moving into the surface counts as a new collision
+                if towards >= 0.0 {
+                    return None;
+                }
+                velocity[0] -= 2.0 * towards * normal[0];
+                velocity[1] -= 2.0 * towards * normal[1];
+                Some(*position)
+            }
+            Obstacle::Rect { position: corner, size } => {
+                let inside_x = position[0] > corner[0] && position[0] < corner[0] + size[0];
+                let inside_y = position[1] > corner[1] && position[1] < corner[1] + size[1];
+                if !inside_x || !inside_y {
+                    return None;
+                }
+
+                // Leave through the closest edge, remembering which way
+                // points into the rectangle from it
+                let edges = [
+                    (position[0] - corner[0], 0, corner[0], 1.0),
+                    (corner[0] + size[0] - position[0], 0, corner[0] + size[0], -1.0),
+                    (position[1] - corner[1], 1, corner[1], 1.0),
+                    (corner[1] + size[1] - position[1], 1, corner[1] + size[1], -1.0),
+                ];
+                let (_, axis, edge, inwards) = edges
+                    .into_iter()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap_or(edges[0]);
+
+                position[axis] = edge;
+                if velocity[axis] * inwards <= 0.0 {
+                    return None;
+                }
+                velocity[axis] = -velocity[axis];
+                Some(*position)
+            }
+        }
+    }
+
+    /// Like `collide`, but only cancels the part of the velocity going into
+    /// the obstacle, so points slide along and flow around it instead of
+    /// bouncing off. Makes no collision.
+    pub fn deflect(&self, position: &mut Vec2d<f64>, velocity: &mut Vec2d<f64>) {
+        let mut reflected = *velocity;
+        if self.collide(position, &mut reflected).is_some() {
+            // Halfway between going on and bouncing back runs along the surface
+            *velocity = [(velocity[0] + reflected[0]) / 2.0, (velocity[1] + reflected[1]) / 2.0];
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::color::hsv_to_rgb;
+use crate::params::{ParamVisitor, Tunable};
+use crate::Particle;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// What a palette picks the color of a particle by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteInput {
+    /// Turns since the particle spawned
+    Age,
+
+    /// Pixels per turn it is moving at
+    Speed,
+}
+
+/// A color for every point from 0 to 1 along a palette.
+pub type ColorFunction = Arc<dyn Fn(f32) -> [f32; 3] + Send + Sync>;
+
+/// Colors the particles every turn by their age or speed, instead of keeping
+/// the color they spawned with. Only the color is replaced: the opacity is
+/// left to the fade, so particles can go white, orange then red as they die
+/// out. `range` is the age, or the speed, the palette goes from its start to
+/// its end over; values outside it take the color of the nearest end.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "PaletteSpec")]
+pub struct Palette {
+    pub name: String,
+    pub by: PaletteInput,
+    pub range: [f64; 2],
+    colors: ColorFunction,
+}
+
+// A palette as written in scene files: a built-in or registered palette by
+// name, or a gradient through the listed colors
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteSpec {
+    name: Option<String>,
+    stops: Option<Vec<[f32; 3]>>,
+    by: Option<PaletteInput>,
+    range: Option<[f64; 2]>,
+}
+
+impl TryFrom<PaletteSpec> for Palette {
+    type Error = String;
+
+    fn try_from(spec: PaletteSpec) -> Result<Palette, String> {
+        let mut palette = match (spec.name, spec.stops) {
+            (Some(_), Some(_)) => return Err("a palette has either a name or stops, not both".to_string()),
+            (Some(name), None) => Palette::named(&name)?,
+            (None, Some(stops)) if stops.is_empty() => return Err("a gradient needs at least one stop".to_string()),
+            (None, Some(stops)) => Palette::new("gradient", PaletteInput::Age, gradient(stops)),
+            (None, None) => return Err("a palette needs a name or stops".to_string()),
+        };
+        if let Some(by) = spec.by {
+            palette.by = by;
+            palette.range = default_range(by);
+        }
+        if let Some(range) = spec.range {
+            palette.range = range;
+        }
+        Ok(palette)
+    }
+}
+
+impl fmt::Debug for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Palette").field("name", &self.name).field("by", &self.by).field("range", &self.range).finish()
+    }
+}
+
+fn default_range(by: PaletteInput) -> [f64; 2] {
+    match by {
+        PaletteInput::Age => [0.0, 120.0],
+        PaletteInput::Speed => [0.0, 4.0],
+    }
+}
+
+/// Names of the palettes that come with the crate.
+pub const BUILT_IN: [&str; 5] = ["fire", "ice", "heat", "rainbow", "contrast"];
+
+// Palettes added with `register`, looked up before the built-in ones
+static REGISTERED: RwLock<Vec<Palette>> = RwLock::new(Vec::new());
+
+/// Makes a palette available by name to scene files and `--palette`, in
+/// place of a built-in one of the same name. `colors` is called with a
+/// value from 0 at the start of the palette's range to 1 at its end.
+pub fn register<F>(name: &str, by: PaletteInput, colors: F)
+where
+    F: Fn(f32) -> [f32; 3] + Send + Sync + 'static,
+{
+    let palette = Palette::new(name, by, Arc::new(colors));
+    let mut registered = REGISTERED.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    registered.retain(|existing| existing.name != name);
+    registered.push(palette);
+}
+
+impl Palette {
+    pub fn new(name: &str, by: PaletteInput, colors: ColorFunction) -> Palette {
+        Palette { name: name.to_string(), by, range: default_range(by), colors }
+    }
+
+    /// A registered or built-in palette.
+    pub fn named(name: &str) -> Result<Palette, String> {
+        let registered = REGISTERED.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(palette) = registered.iter().find(|palette| palette.name == name) {
+            return Ok(palette.clone());
+        }
+        let (by, colors) = match name {
+            "fire" => (
+                PaletteInput::Age,
+                gradient(vec![[1.0, 1.0, 1.0], [1.0, 0.9, 0.4], [1.0, 0.5, 0.1], [0.8, 0.1, 0.05], [0.3, 0.05, 0.05]]),
+            ),
+            "ice" => (PaletteInput::Age, gradient(vec![[1.0, 1.0, 1.0], [0.6, 0.9, 1.0], [0.2, 0.5, 1.0], [0.1, 0.15, 0.5]])),
+            "heat" => (
+                PaletteInput::Speed,
+                gradient(vec![[0.1, 0.2, 0.8], [0.1, 0.8, 0.9], [0.9, 0.9, 0.2], [1.0, 0.2, 0.1]]),
+            ),
+            "rainbow" => (PaletteInput::Age, Arc::new(|t: f32| hsv_to_rgb(t * 300.0, 1.0, 1.0)) as ColorFunction),
+            // Bright all along against black, and told apart without telling red from green
+            "contrast" => (PaletteInput::Age, gradient(vec![[1.0, 1.0, 1.0], [1.0, 0.85, 0.1], [0.3, 0.85, 1.0]])),
+            _ => {
+                let names: Vec<&str> = registered.iter().map(|palette| palette.name.as_str()).chain(BUILT_IN).collect();
+                return Err(format!("unknown palette {:?}, expected one of {}", name, names.join(", ")));
+            }
+        };
+        Ok(Palette::new(name, by, colors))
+    }
+
+    /// Color at `value`, an age in turns or a speed in pixels per turn.
+    pub fn color_at(&self, value: f64) -> [f32; 3] {
+        let [from, to] = self.range;
+        let t = if to != from { ((value - from) / (to - from)).clamp(0.0, 1.0) } else { 1.0 };
+        (self.colors)(t as f32)
+    }
+
+    /// Labels of a legend of the palette: what it colors by, then the values
+    /// at the start and the end of its range.
+    pub fn legend(&self) -> [String; 3] {
+        let [from, to] = self.range;
+        match self.by {
+            PaletteInput::Age => [format!("{} by age", self.name), format!("{:.0} turns", from), format!("{:.0} turns", to)],
+            PaletteInput::Speed => [format!("{} by speed", self.name), format!("{:.1} px/turn", from), format!("{:.1} px/turn", to)],
+        }
+    }
+
+    pub fn paint(&self, particle: &mut Particle) {
+        let value = match self.by {
+            PaletteInput::Age => particle.age as f64,
+            PaletteInput::Speed => particle.velocity[0].hypot(particle.velocity[1]),
+        };
+        let [r, g, b] = self.color_at(value);
+        particle.color[..3].copy_from_slice(&[r, g, b]);
+    }
+}
+
+// Straight lines between evenly spaced colors
+fn gradient(stops: Vec<[f32; 3]>) -> ColorFunction {
+    Arc::new(move |t: f32| {
+        let last = stops.len() - 1;
+        let position = t.clamp(0.0, 1.0) * last as f32;
+        let index = (position as usize).min(last.saturating_sub(1));
+        let (a, b) = (stops[index], stops[(index + 1).min(last)]);
+        let along = position - index as f32;
+        std::array::from_fn(|channel| a[channel] + (b[channel] - a[channel]) * along)
+    })
+}
+
+impl Tunable for Palette {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("from", &mut self.range[0], 0.0..=f64::INFINITY);
+        visitor.float("to", &mut self.range[1], 0.0..=f64::INFINITY);
+    }
+}
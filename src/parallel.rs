@@ -0,0 +1,72 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+/// Particles are updated in chunks of this size whatever the number of threads,
+/// so a given chunk always draws from the same random stream.
+pub const CHUNK_SIZE: usize = 512;
+
+/// Random stream of one chunk for one turn, derived from a seed the world
+/// draws from its own generator every turn.
+pub fn chunk_rng(turn_seed: u64, chunk: usize) -> StdRng {
+    StdRng::seed_from_u64(splitmix64(turn_seed ^ splitmix64(chunk as u64)))
+}
+
+/// Calls `step` on every item from the rayon thread pool, chunk by chunk, each
+/// chunk with its own random stream. The result only depends on `turn_seed`,
+/// never on how the chunks are scheduled. What `step` reports is returned with
+/// the index of its item, in item order.
+pub fn for_each<T, E, F>(items: &mut [T], turn_seed: u64, step: F) -> Vec<(usize, E)>
+where
+    T: Send,
+    E: Send,
+    F: Fn(&mut T, &mut StdRng) -> Option<E> + Sync,
+{
+    items
+        .par_chunks_mut(CHUNK_SIZE)
+        .enumerate()
+        .map(|(chunk, items)| {
+            let mut rng = chunk_rng(turn_seed, chunk);
+            let mut reports = Vec::new();
+            for (i, item) in items.iter_mut().enumerate() {
+                if let Some(report) = step(item, &mut rng) {
+                    reports.push((chunk * CHUNK_SIZE + i, report));
+                }
+            }
+            reports
+        })
+        // Empty vectors don't allocate, so nothing does when there is nothing to report
+        .reduce(Vec::new, |mut a, mut b| {
+            a.append(&mut b);
+            a
+        })
+}
+
+/// Calls `visit` on every item from the rayon thread pool and gathers what it
+/// reports in item order, as a sequential loop would.
+pub fn collect<T, E, F>(items: &mut [T], visit: F) -> Vec<E>
+where
+    T: Send,
+    E: Send,
+    F: Fn(&mut T, &mut Vec<E>) + Sync,
+{
+    let chunks: Vec<Vec<E>> = items
+        .par_chunks_mut(CHUNK_SIZE)
+        .map(|items| {
+            let mut found = Vec::new();
+            for item in items {
+                visit(item, &mut found);
+            }
+            found
+        })
+        .collect();
+    chunks.into_iter().flatten().collect()
+}
+
+// Spreads nearby inputs, like consecutive chunk indices, over unrelated seeds
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
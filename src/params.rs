@@ -0,0 +1,182 @@
+use std::ops::RangeInclusive;
+
+/// Something with parameters that can be tuned while the program runs, like
+/// the world, its zones or its emitters. Each parameter is declared once, in
+/// `visit_params`, which is used both to list them and to change them.
+pub trait Tunable {
+    /// Hands every parameter to `visitor`, by name, with its valid range.
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor);
+}
+
+/// Receives the parameters of a `Tunable`, able to read or change them.
+pub trait ParamVisitor {
+    fn float(&mut self, name: &str, value: &mut f64, range: RangeInclusive<f64>);
+    fn integer(&mut self, name: &str, value: &mut u64, range: RangeInclusive<u64>);
+}
+
+/// Declares an `f32` parameter, handled as a float.
+pub fn float32(visitor: &mut dyn ParamVisitor, name: &str, value: &mut f32, range: RangeInclusive<f64>) {
+    let mut wide = *value as f64;
+    visitor.float(name, &mut wide, range);
+    *value = wide as f32;
+}
+
+/// Declares a `u32` parameter, handled as an integer. Ranges past `u32::MAX` are cut.
+pub fn integer32(visitor: &mut dyn ParamVisitor, name: &str, value: &mut u32, range: RangeInclusive<u64>) {
+    let mut wide = *value as u64;
+    let range = *range.start()..=(*range.end()).min(u32::MAX as u64);
+    visitor.integer(name, &mut wide, range);
+    *value = wide as u32;
+}
+
+/// Declares a `usize` parameter, handled as an integer.
+pub fn size(visitor: &mut dyn ParamVisitor, name: &str, value: &mut usize, range: RangeInclusive<u64>) {
+    let mut wide = *value as u64;
+    let range = *range.start()..=(*range.end()).min(usize::MAX as u64);
+    visitor.integer(name, &mut wide, range);
+    *value = wide as usize;
+}
+
+/// Visits the parameters of a part of a `Tunable`, like its first zone,
+/// under a common prefix such as `zone.0`.
+pub struct Prefixed<'a> {
+    visitor: &'a mut dyn ParamVisitor,
+    prefix: String,
+}
+
+impl<'a> Prefixed<'a> {
+    pub fn new(visitor: &'a mut dyn ParamVisitor, prefix: String) -> Prefixed<'a> {
+        Prefixed { visitor, prefix }
+    }
+}
+
+impl ParamVisitor for Prefixed<'_> {
+    fn float(&mut self, name: &str, value: &mut f64, range: RangeInclusive<f64>) {
+        self.visitor.float(&format!("{}.{}", self.prefix, name), value, range);
+    }
+
+    fn integer(&mut self, name: &str, value: &mut u64, range: RangeInclusive<u64>) {
+        self.visitor.integer(&format!("{}.{}", self.prefix, name), value, range);
+    }
+}
+
+/// Current value of a parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamValue {
+    Float { value: f64, min: f64, max: f64 },
+    Integer { value: u64, min: u64, max: u64 },
+}
+
+/// A tunable parameter, as found in the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    /// Dotted path like `zone.0.strength`
+    pub name: String,
+    pub value: ParamValue,
+}
+
+impl Param {
+    pub fn type_name(&self) -> &'static str {
+        match self.value {
+            ParamValue::Float { .. } => "float",
+            ParamValue::Integer { .. } => "integer",
+        }
+    }
+
+    /// Valid range, like `0..=1`.
+    pub fn range(&self) -> String {
+        match self.value {
+            ParamValue::Float { min, max, .. } => format!("{}..={}", min, max),
+            ParamValue::Integer { min, max, .. } => format!("{}..={}", min, max),
+        }
+    }
+
+    pub fn current(&self) -> String {
+        match self.value {
+            ParamValue::Float { value, .. } => value.to_string(),
+            ParamValue::Integer { value, .. } => value.to_string(),
+        }
+    }
+}
+
+/// Every parameter of a `Tunable`, in the order it declares them. Everything
+/// tuning the simulation at run time goes through it.
+pub struct ParamRegistry {
+    pub params: Vec<Param>,
+}
+
+impl ParamRegistry {
+    /// Lists the parameters and their current values.
+    pub fn collect(tunable: &mut dyn Tunable) -> ParamRegistry {
+        let mut collector = Collector { params: Vec::new() };
+        tunable.visit_params(&mut collector);
+        ParamRegistry { params: collector.params }
+    }
+
+    /// Sets the parameter called `name` from its text form, checking its type and range.
+    pub fn set(tunable: &mut dyn Tunable, name: &str, text: &str) -> Result<(), String> {
+        let mut setter = Setter { name, text: text.trim(), result: None };
+        tunable.visit_params(&mut setter);
+        setter.result.unwrap_or_else(|| Err(format!("no parameter called {}", name)))
+    }
+
+    /// Applies an assignment like `drag=0.1`.
+    pub fn assign(tunable: &mut dyn Tunable, assignment: &str) -> Result<(), String> {
+        let (name, text) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=VALUE, got {}", assignment))?;
+        ParamRegistry::set(tunable, name.trim(), text)
+    }
+}
+
+struct Collector {
+    params: Vec<Param>,
+}
+
+impl ParamVisitor for Collector {
+    fn float(&mut self, name: &str, value: &mut f64, range: RangeInclusive<f64>) {
+        let value = ParamValue::Float { value: *value, min: *range.start(), max: *range.end() };
+        self.params.push(Param { name: name.to_string(), value });
+    }
+
+    fn integer(&mut self, name: &str, value: &mut u64, range: RangeInclusive<u64>) {
+        let value = ParamValue::Integer { value: *value, min: *range.start(), max: *range.end() };
+        self.params.push(Param { name: name.to_string(), value });
+    }
+}
+
+struct Setter<'a> {
+    name: &'a str,
+    text: &'a str,
+    result: Option<Result<(), String>>,
+}
+
+impl ParamVisitor for Setter<'_> {
+    fn float(&mut self, name: &str, value: &mut f64, range: RangeInclusive<f64>) {
+        if name != self.name {
+            return;
+        }
+        self.result = Some(match self.text.parse::<f64>() {
+            Ok(parsed) if range.contains(&parsed) => {
+                *value = parsed;
+                Ok(())
+            }
+            Ok(parsed) => Err(format!("{} is out of the range of {} ({:?})", parsed, name, range)),
+            Err(_) => Err(format!("{} expects a number, got {}", name, self.text)),
+        });
+    }
+
+    fn integer(&mut self, name: &str, value: &mut u64, range: RangeInclusive<u64>) {
+        if name != self.name {
+            return;
+        }
+        self.result = Some(match self.text.parse::<u64>() {
+            Ok(parsed) if range.contains(&parsed) => {
+                *value = parsed;
+                Ok(())
+            }
+            Ok(parsed) => Err(format!("{} is out of the range of {} ({:?})", parsed, name, range)),
+            Err(_) => Err(format!("{} expects a whole number, got {}", name, self.text)),
+        });
+    }
+}
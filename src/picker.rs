@@ -0,0 +1,147 @@
+use memory_particles::capture::Canvas;
+use piston_window::texture::{CreateTexture, Format};
+use piston_window::*;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Thumbnails are laid out in rows of this many
+const COLUMNS: usize = 4;
+const GAP: f64 = 16.0;
+
+/// Size of the thumbnails shown by the picker, in the window's shape.
+pub const THUMBNAIL_SIZE: [usize; 2] = [240, 180];
+
+/// A scene the picker offers.
+pub struct SceneEntry {
+    pub name: String,
+
+    /// `None` for the built-in default scene
+    pub path: Option<PathBuf>,
+    thumbnail: Option<G2dTexture>,
+}
+
+/// What a key did to the picker.
+pub enum PickerAction {
+    Ignored,
+    Moved,
+
+    /// The scene at this index was chosen
+    Chosen(usize),
+}
+
+/// Tab opens a menu of the built-in scene and the scene files of a
+/// directory, shown as thumbnails and chosen with the arrow keys and Enter.
+pub struct ScenePicker {
+    pub entries: Vec<SceneEntry>,
+    pub selected: usize,
+    pub open: bool,
+}
+
+impl ScenePicker {
+    /// Lists the scenes of `directory`, selecting `current` when it is one of them.
+    /// A missing directory only leaves the built-in scene.
+    pub fn new(directory: &Path, current: Option<&Path>) -> ScenePicker {
+        let mut entries = vec![SceneEntry { name: "default".to_string(), path: None, thumbnail: None }];
+        for path in scene_files(directory).unwrap_or_default() {
+            let name = path.file_stem().map_or("scene".into(), |stem| stem.to_string_lossy().into_owned());
+            entries.push(SceneEntry { name, path: Some(path), thumbnail: None });
+        }
+
+        let selected = current
+            .and_then(|current| entries.iter().position(|entry| entry.path.as_deref() == Some(current)))
+            .unwrap_or(0);
+        ScenePicker { entries, selected, open: false }
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> PickerAction {
+        let last = self.entries.len() - 1;
+        let selected = match key {
+            Key::Left => self.selected.saturating_sub(1),
+            Key::Right => (self.selected + 1).min(last),
+            Key::Up => self.selected.saturating_sub(COLUMNS),
+            Key::Down => (self.selected + COLUMNS).min(last),
+            Key::Return => return PickerAction::Chosen(self.selected),
+            _ => return PickerAction::Ignored,
+        };
+        self.selected = selected;
+        PickerAction::Moved
+    }
+
+    /// The selected scene and how to use the picker, for the window title.
+    pub fn describe(&self) -> String {
+        format!(
+            "scene {} ({} of {}): arrows to choose, Enter to load, Tab to close",
+            self.entries[self.selected].name,
+            self.selected + 1,
+            self.entries.len()
+        )
+    }
+
+    /// Uploads a thumbnail for every scene that has none yet, drawn by
+    /// `render`. Scenes it can't draw are shown as empty cells.
+    pub fn make_thumbnails<F>(&mut self, context: &mut G2dTextureContext, mut render: F)
+    where
+        F: FnMut(&SceneEntry) -> Option<Canvas>,
+    {
+        for i in 0..self.entries.len() {
+            if self.entries[i].thumbnail.is_some() {
+                continue;
+            }
+            let Some(canvas) = render(&self.entries[i]) else {
+                continue;
+            };
+            let [width, height] = THUMBNAIL_SIZE.map(|size| size as u32);
+            let settings = TextureSettings::new();
+            match G2dTexture::create(context, Format::Rgba8, &canvas.rgba(), [width, height], &settings) {
+                Ok(texture) => self.entries[i].thumbnail = Some(texture),
+                Err(err) => eprintln!("Could not upload the thumbnail of {}: {:?}", self.entries[i].name, err),
+            }
+        }
+    }
+
+    /// Draws the menu over the whole window, the selected scene framed.
+    pub fn draw<G>(&self, width: f64, height: f64, ctx: Context, g: &mut G)
+    where
+        G: Graphics<Texture = G2dTexture>,
+    {
+        rectangle([0.0, 0.0, 0.0, 0.85], [0.0, 0.0, width, height], ctx.transform, g);
+
+        let [cell_width, cell_height] = THUMBNAIL_SIZE.map(|size| size as f64);
+        let rows = self.entries.len().div_ceil(COLUMNS);
+        let columns = self.entries.len().min(COLUMNS);
+        let grid_width = columns as f64 * (cell_width + GAP) - GAP;
+        let grid_height = rows as f64 * (cell_height + GAP) - GAP;
+
+        // Scrolls so the selected row stays in the window
+        let selected_row = (self.selected / COLUMNS) as f64;
+        let scroll = (selected_row * (cell_height + GAP) + cell_height - (height - GAP * 2.0)).max(0.0);
+        let left = ((width - grid_width) / 2.0).max(GAP);
+        let top = ((height - grid_height) / 2.0).max(GAP) - scroll;
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let x = left + (i % COLUMNS) as f64 * (cell_width + GAP);
+            let y = top + (i / COLUMNS) as f64 * (cell_height + GAP);
+            if i == self.selected {
+                let frame = [x - 4.0, y - 4.0, cell_width + 8.0, cell_height + 8.0];
+                rectangle([0.9, 0.8, 0.3, 1.0], frame, ctx.transform, g);
+            }
+            match &entry.thumbnail {
+                Some(texture) => image(texture, ctx.transform.trans(x, y), g),
+                None => rectangle([0.2, 0.2, 0.2, 1.0], [x, y, cell_width, cell_height], ctx.transform, g),
+            }
+        }
+    }
+}
+
+/// Scene files of a directory, by name.
+pub fn scene_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut scenes = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "toml") {
+            scenes.push(path);
+        }
+    }
+    scenes.sort();
+    Ok(scenes)
+}
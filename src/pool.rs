@@ -0,0 +1,152 @@
+use crate::allocator::BACKING;
+use crate::Particle;
+use std::alloc::{GlobalAlloc, Layout};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+/// Size and alignment of the blocks the pool hands out, those of a particle.
+/// Any allocation with this layout is served by the pool, particle or not.
+pub const BLOCK_SIZE: usize = std::mem::size_of::<Particle>();
+pub const BLOCK_ALIGN: usize = std::mem::align_of::<Particle>();
+
+// Free blocks hold the pointer to the next one
+const _: () = assert!(BLOCK_SIZE >= std::mem::size_of::<*mut u8>() && BLOCK_ALIGN >= std::mem::align_of::<*mut u8>());
+
+// Blocks carved out of every chunk taken from the backing allocator
+const CHUNK_BLOCKS: usize = 256;
+
+// Whether particle-sized allocations go through the pool. Never turned back
+// off, so every block freed later can still be taken back by the pool
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Singly linked list of free blocks, guarded by a spin lock since the
+// allocator can't use locks that allocate
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static FREE: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
+static FREE_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+static REFILLS: AtomicU64 = AtomicU64::new(0);
+static SERVED: AtomicU64 = AtomicU64::new(0);
+
+/// Routes every particle-sized allocation through the pool from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether the pool serves allocations with this layout.
+pub fn serves(layout: Layout) -> bool {
+    is_enabled() && layout.size() == BLOCK_SIZE && layout.align() <= BLOCK_ALIGN
+}
+
+/// Carves chunks out of the backing allocator up front until at least
+/// `blocks` blocks are free, so that many particles can be allocated without
+/// the pool taking any more memory. Stops early when out of memory.
+pub fn prefill(blocks: usize) {
+    lock();
+    while FREE_BLOCKS.load(Ordering::Relaxed) < blocks {
+        // Safe: the new chunk is linked in front of the free list, under the lock
+        let head = unsafe { refill(FREE.load(Ordering::Relaxed)) };
+        if head.is_null() {
+            break;
+        }
+        FREE.store(head, Ordering::Relaxed);
+    }
+    unlock();
+}
+
+/// Takes a block from the free list, refilling it from the backing allocator
+/// when empty. Returns null when the backing allocator is out of memory.
+///
+/// # Safety
+/// Called from the global allocator, for a layout the pool `serves`.
+pub unsafe fn alloc() -> *mut u8 {
+    lock();
+    let mut block = FREE.load(Ordering::Relaxed);
+    if block.is_null() {
+        block = refill(ptr::null_mut());
+    }
+    if !block.is_null() {
+        FREE.store(*(block as *mut *mut u8), Ordering::Relaxed);
+        FREE_BLOCKS.fetch_sub(1, Ordering::Relaxed);
+        SERVED.fetch_add(1, Ordering::Relaxed);
+    }
+    unlock();
+    block
+}
+
+/// Puts a block back on the free list. Blocks are never given back to the
+/// backing allocator, including ones it allocated before the pool was enabled.
+///
+/// # Safety
+/// `block` was allocated with a layout the pool `serves` and is no longer used.
+pub unsafe fn dealloc(block: *mut u8) {
+    lock();
+    *(block as *mut *mut u8) = FREE.load(Ordering::Relaxed);
+    FREE.store(block, Ordering::Relaxed);
+    FREE_BLOCKS.fetch_add(1, Ordering::Relaxed);
+    unlock();
+}
+
+// Carves a new chunk into free blocks ending in `tail`, returning the head
+// of the list, with the lock held
+unsafe fn refill(tail: *mut u8) -> *mut u8 {
+    let layout = Layout::from_size_align_unchecked(BLOCK_SIZE * CHUNK_BLOCKS, BLOCK_ALIGN);
+    let chunk = BACKING.alloc(layout);
+    if chunk.is_null() {
+        return chunk;
+    }
+
+    for i in 0..CHUNK_BLOCKS {
+        let block = chunk.add(i * BLOCK_SIZE);
+        let next = if i + 1 < CHUNK_BLOCKS { chunk.add((i + 1) * BLOCK_SIZE) } else { tail };
+        *(block as *mut *mut u8) = next;
+    }
+    FREE_BLOCKS.fetch_add(CHUNK_BLOCKS, Ordering::Relaxed);
+    REFILLS.fetch_add(1, Ordering::Relaxed);
+    chunk
+}
+
+fn lock() {
+    while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        std::hint::spin_loop();
+    }
+}
+
+fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+/// Internal counters of the pool.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Blocks on the free list, ready to be handed out
+    pub free_blocks: usize,
+
+    /// Chunks taken from the backing allocator
+    pub refills: u64,
+
+    /// Allocations served by the pool
+    pub served: u64,
+}
+
+pub fn pool_stats() -> PoolStats {
+    PoolStats {
+        free_blocks: FREE_BLOCKS.load(Ordering::Relaxed),
+        refills: REFILLS.load(Ordering::Relaxed),
+        served: SERVED.load(Ordering::Relaxed),
+    }
+}
+
+impl PoolStats {
+    /// Short summary of the counters, for the window title and reports.
+    pub fn describe(&self) -> String {
+        format!(
+            "block pool: {} free, {} refills of {} blocks, {} served",
+            self.free_blocks, self.refills, CHUNK_BLOCKS, self.served
+        )
+    }
+}
@@ -0,0 +1,230 @@
+use crate::metadata::RunMetadata;
+use crate::Particle;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Population change during a single turn.
+#[derive(Debug, Clone, Copy)]
+pub struct PopulationSample {
+    pub turn: u64,
+    pub spawned: u64,
+    pub died: u64,
+    pub population: usize,
+}
+
+/// Records spawns, deaths and the resulting population for every turn of the run,
+/// so the behavior of the spawn controller can be audited afterwards.
+pub struct PopulationHistory {
+    samples: Vec<PopulationSample>,
+    last_spawned: u64,
+    last_died: u64,
+}
+
+impl PopulationHistory {
+    /// Starts recording from the world's current totals of spawned and dead particles.
+    pub fn new(total_spawned: u64, total_died: u64) -> PopulationHistory {
+        PopulationHistory {
+            samples: Vec::new(),
+            last_spawned: total_spawned,
+            last_died: total_died,
+        }
+    }
+
+    /// Makes room for `turns` more turns, so recording them allocates nothing.
+    pub fn reserve(&mut self, turns: usize) {
+        self.samples.reserve(turns);
+    }
+
+    /// Records a turn from the world's running totals of spawned and dead particles.
+    pub fn record(&mut self, turn: u64, total_spawned: u64, total_died: u64, population: usize) {
+        self.samples.push(PopulationSample {
+            turn,
+            spawned: total_spawned - self.last_spawned,
+            died: total_died - self.last_died,
+            population,
+        });
+        self.last_spawned = total_spawned;
+        self.last_died = total_died;
+    }
+
+    pub fn write_csv(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}", metadata.comment_header())?;
+        writeln!(out, "turn,spawned,died,population")?;
+        for s in &self.samples {
+            writeln!(out, "{},{},{},{}", s.turn, s.spawned, s.died, s.population)?;
+        }
+        out.flush()
+    }
+
+    /// Writes a standalone SVG plot of the whole run: the net population on
+    /// top, and the spawns and deaths per turn below it.
+    pub fn write_svg(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        const WIDTH: f64 = 1000.0;
+        const PANEL: f64 = 200.0;
+        const MARGIN: f64 = 40.0;
+
+        let mut svg = String::new();
+        let height = MARGIN * 3.0 + PANEL * 2.0;
+        // Writing to a String can't fail
+        let _ = writeln!(
+            svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"##,
+            WIDTH + MARGIN * 2.0,
+            height
+        );
+        let _ = writeln!(svg, "<!--\n{}-->", metadata.comment_header());
+        let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#262b2b"/>"##);
+
+        let turns = self.samples.len();
+        let max_population = self.samples.iter().map(|s| s.population).max().unwrap_or(0);
+
+        // Spawns and deaths are summed per pixel column so bursts stay visible
+        // when the run has more turns than the plot has columns
+        let columns = (WIDTH as usize).min(turns.max(1));
+        let mut spawned = vec![0u64; columns];
+        let mut died = vec![0u64; columns];
+        for (i, s) in self.samples.iter().enumerate() {
+            let column = i * columns / turns;
+            spawned[column] += s.spawned;
+            died[column] += s.died;
+        }
+        let max_change = spawned.iter().chain(&died).copied().max().unwrap_or(0);
+
+        let panel_top = MARGIN;
+        let population: Vec<f64> = (0..columns)
+            .map(|c| self.samples.get(c * turns / columns).map_or(0.0, |s| s.population as f64))
+            .collect();
+        plot_line(&mut svg, &population, max_population as f64, panel_top, PANEL, MARGIN, WIDTH, "#f0f0f0");
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#f0f0f0">population (max {})</text>"##,
+            MARGIN, panel_top - 8.0, max_population
+        );
+
+        let panel_top = MARGIN * 2.0 + PANEL;
+        let as_f64 = |values: &[u64]| values.iter().map(|&v| v as f64).collect::<Vec<f64>>();
+        plot_line(&mut svg, &as_f64(&spawned), max_change as f64, panel_top, PANEL, MARGIN, WIDTH, "#5fd35f");
+        plot_line(&mut svg, &as_f64(&died), max_change as f64, panel_top, PANEL, MARGIN, WIDTH, "#e05050");
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#f0f0f0"><tspan fill="#5fd35f">spawned</tspan> / <tspan fill="#e05050">died</tspan> per column (max {}), {} turns</text>"##,
+            MARGIN, panel_top - 8.0, max_change, turns
+        );
+
+        let footer: Vec<String> = metadata.fields().iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#888">{}</text>"##,
+            MARGIN, height - 12.0, footer.join("  ")
+        );
+        let _ = writeln!(svg, "</svg>");
+        std::fs::write(path, svg)
+    }
+}
+
+/// Particles grouped by the turn they were spawned on, `turns` turns to a
+/// cohort, and followed for the rest of the run: how many of every cohort
+/// spawned, and how many of them were still alive every `turns` turns,
+/// which are the survival curves of the population by spawn turn.
+///
+/// A particle counts as spawned when it is still there at the end of the
+/// turn it was spawned on, so the few dying on that very turn are left out,
+/// and so are the particles spawned before recording started.
+pub struct CohortHistory {
+    turns: u64,
+    first_turn: Option<u64>,
+
+    // By cohort, from the one of the first turn recorded
+    spawned: Vec<u64>,
+
+    // Turn of every checkpoint and the particles alive then of every cohort
+    checkpoints: Vec<(u64, Vec<u64>)>,
+}
+
+impl CohortHistory {
+    pub fn new(turns: u64) -> CohortHistory {
+        CohortHistory { turns: turns.max(1), first_turn: None, spawned: Vec::new(), checkpoints: Vec::new() }
+    }
+
+    /// Records the end of `turn` from the spawn turns of the particles alive.
+    pub fn record<I: IntoIterator<Item = u64> + Clone>(&mut self, turn: u64, born: I) {
+        let first = *self.first_turn.get_or_insert(turn);
+        let (turns, first_cohort) = (self.turns, first / self.turns);
+        let index = (turn / turns - first_cohort) as usize;
+        if self.spawned.len() <= index {
+            self.spawned.resize(index + 1, 0);
+        }
+        self.spawned[index] += born.clone().into_iter().filter(|&born| born == turn).count() as u64;
+
+        if (turn + 1).is_multiple_of(turns) {
+            let mut alive = vec![0; self.spawned.len()];
+            for born in born.into_iter().filter(|&born| born >= first && born <= turn) {
+                alive[(born / turns - first_cohort) as usize] += 1;
+            }
+            self.checkpoints.push((turn, alive));
+        }
+    }
+
+    /// One row per cohort and checkpoint after it started: the first turn of
+    /// the cohort, its spawns, the turn of the checkpoint, and how many and
+    /// what share of its particles were still alive then.
+    pub fn write_csv(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}", metadata.comment_header())?;
+        writeln!(out, "cohort,spawned,turn,alive,survival")?;
+        let first_cohort = self.first_turn.unwrap_or(0) / self.turns;
+        for (turn, alive) in &self.checkpoints {
+            for (index, (&spawned, &alive)) in self.spawned.iter().zip(alive).enumerate().filter(|(_, (&spawned, _))| spawned > 0) {
+                let cohort = (first_cohort + index as u64) * self.turns;
+                writeln!(out, "{},{},{},{},{:.4}", cohort, spawned, turn, alive, alive as f64 / spawned as f64)?;
+            }
+        }
+        out.flush()
+    }
+}
+
+/// Writes every particle with the turn it was spawned on and its age, as
+/// the world was at `turn`.
+pub fn write_particles_csv<'a, I>(path: &Path, turn: u64, particles: I, metadata: &RunMetadata) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a Particle>,
+{
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "{}", metadata.comment_header())?;
+    writeln!(out, "turn,index,x,y,velocity_x,velocity_y,alpha,born,age")?;
+    for (index, particle) in particles.into_iter().enumerate() {
+        let ([x, y], [vx, vy]) = (particle.position, particle.velocity);
+        writeln!(
+            out,
+            "{},{},{:.3},{:.3},{:.4},{:.4},{:.4},{},{}",
+            turn, index, x, y, vx, vy, particle.color[3], particle.born, particle.age
+        )?;
+    }
+    out.flush()
+}
+
+// Draws `values` as a polyline filling a panel, scaled so `max` reaches its top
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn plot_line(svg: &mut String, values: &[f64], max: f64, top: f64, height: f64, left: f64, width: f64, color: &str) {
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#555"/>"##,
+        left, top, width, height
+    );
+    if values.is_empty() {
+        return;
+    }
+
+    let max = max.max(1.0);
+    let step = if values.len() > 1 { width / (values.len() - 1) as f64 } else { 0.0 };
+    let _ = write!(svg, r##"<polyline fill="none" stroke="{}" points=""##, color);
+    for (i, value) in values.iter().enumerate() {
+        let x = left + i as f64 * step;
+        let y = top + height - value / max * height;
+        let _ = write!(svg, "{:.1},{:.1} ", x, y);
+    }
+    let _ = writeln!(svg, r##""/>"##);
+}
@@ -0,0 +1,192 @@
+use crate::allocator::AllocationTag;
+use crate::metadata::RunMetadata;
+use crate::trace::{bucket_label, size_bucket, SIZE_BUCKETS};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// What the allocations were made for, as told by `tagged`, untagged first
+const TAGS: usize = 4;
+
+// Allocations, bytes and nanoseconds by tag and power-of-two size class.
+// Filled by the allocator, so fixed-size like the other counters; the
+// allocator knows no call stacks, so these are what the profile is made of
+static COUNTS: [[AtomicU64; SIZE_BUCKETS]; TAGS] = [const { [const { AtomicU64::new(0) }; SIZE_BUCKETS] }; TAGS];
+static BYTES: [[AtomicU64; SIZE_BUCKETS]; TAGS] = [const { [const { AtomicU64::new(0) }; SIZE_BUCKETS] }; TAGS];
+static NANOS: [[AtomicU64; SIZE_BUCKETS]; TAGS] = [const { [const { AtomicU64::new(0) }; SIZE_BUCKETS] }; TAGS];
+
+static PROFILING: AtomicBool = AtomicBool::new(false);
+static STARTED: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+/// Starts counting allocations for a pprof profile.
+pub fn enable() {
+    reset();
+    PROFILING.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    PROFILING.load(Ordering::Relaxed)
+}
+
+/// Forgets the allocations counted so far, such as those of a warm-up.
+pub fn reset() {
+    for counters in [&COUNTS, &BYTES, &NANOS] {
+        counters.iter().flatten().for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+    *STARTED.lock().unwrap_or_else(|err| err.into_inner()) = Some(SystemTime::now());
+}
+
+/// Counts an allocation, when profiling.
+pub fn on_alloc(tag: Option<AllocationTag>, size: usize, nanos: u64) {
+    if !is_enabled() {
+        return;
+    }
+    let (tag, bucket) = (AllocationTag::to_u8(tag) as usize, size_bucket(size));
+    COUNTS[tag][bucket].fetch_add(1, Ordering::Relaxed);
+    BYTES[tag][bucket].fetch_add(size as u64, Ordering::Relaxed);
+    NANOS[tag][bucket].fetch_add(nanos, Ordering::Relaxed);
+}
+
+/// The allocations of one tag and size class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileSample {
+    pub tag: Option<AllocationTag>,
+    pub bucket: usize,
+    pub count: u64,
+    pub bytes: u64,
+    pub nanos: u64,
+}
+
+/// What was counted since profiling started, for every tag and size class
+/// with allocations.
+pub fn samples() -> Vec<ProfileSample> {
+    let mut samples = Vec::new();
+    for tag in 0..TAGS {
+        for bucket in (0..SIZE_BUCKETS).filter(|&bucket| COUNTS[tag][bucket].load(Ordering::Relaxed) > 0) {
+            samples.push(ProfileSample {
+                tag: AllocationTag::from_u8(tag as u8),
+                bucket,
+                count: COUNTS[tag][bucket].load(Ordering::Relaxed),
+                bytes: BYTES[tag][bucket].load(Ordering::Relaxed),
+                nanos: NANOS[tag][bucket].load(Ordering::Relaxed),
+            });
+        }
+    }
+    samples
+}
+
+/// Writes the allocations counted since profiling started as a pprof
+/// profile, for `go tool pprof` and the other pprof visualizers.
+pub fn write(path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+    let started = STARTED.lock().unwrap_or_else(|err| err.into_inner()).unwrap_or(metadata.started);
+    fs::write(path, encode(&samples(), started, metadata))
+}
+
+/// The pprof protobuf of `samples`: three sample types, allocations, bytes
+/// and nanoseconds, and for a stack the size class called from the tag, so
+/// flame graphs split the allocations by what they were made for, then by
+/// size. The run metadata goes in the comments.
+pub fn encode(samples: &[ProfileSample], started: SystemTime, metadata: &RunMetadata) -> Vec<u8> {
+    let mut strings = Strings(vec![String::new()]);
+    let mut profile = Vec::new();
+
+    for (kind, unit) in [("alloc_objects", "count"), ("alloc_space", "bytes"), ("alloc_time", "nanoseconds")] {
+        let mut value_type = Vec::new();
+        varint_field(&mut value_type, 1, strings.index(kind));
+        varint_field(&mut value_type, 2, strings.index(unit));
+        bytes_field(&mut profile, 1, &value_type);
+    }
+
+    // One function and location per tag and per size class, with the same id
+    let tag_id = |tag: Option<AllocationTag>| 1 + AllocationTag::to_u8(tag) as u64;
+    let bucket_id = |bucket: usize| 1 + TAGS as u64 + bucket as u64;
+    for sample in samples {
+        let mut sample_message = Vec::new();
+        packed_field(&mut sample_message, 1, &[bucket_id(sample.bucket), tag_id(sample.tag)]);
+        packed_field(&mut sample_message, 2, &[sample.count, sample.bytes, sample.nanos]);
+        bytes_field(&mut profile, 2, &sample_message);
+    }
+
+    let mut functions: Vec<(u64, String)> = Vec::new();
+    for tag in 0..TAGS as u8 {
+        let tag = AllocationTag::from_u8(tag);
+        if samples.iter().any(|sample| sample.tag == tag) {
+            functions.push((tag_id(tag), tag.map_or("untagged", AllocationTag::label).to_string()));
+        }
+    }
+    for bucket in 0..SIZE_BUCKETS {
+        if samples.iter().any(|sample| sample.bucket == bucket) {
+            functions.push((bucket_id(bucket), bucket_label(bucket)));
+        }
+    }
+    for (id, _) in &functions {
+        let mut line = Vec::new();
+        varint_field(&mut line, 1, *id);
+        let mut location = Vec::new();
+        varint_field(&mut location, 1, *id);
+        bytes_field(&mut location, 4, &line);
+        bytes_field(&mut profile, 4, &location);
+    }
+    for (id, name) in &functions {
+        let mut function = Vec::new();
+        varint_field(&mut function, 1, *id);
+        varint_field(&mut function, 2, strings.index(name));
+        varint_field(&mut function, 3, strings.index(name));
+        bytes_field(&mut profile, 5, &function);
+    }
+
+    let comments: Vec<u64> = metadata.fields().iter().map(|(key, value)| strings.index(&format!("{}: {}", key, value))).collect();
+    let nanos_since = |time: SystemTime| time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+    let duration = SystemTime::now().duration_since(started).map_or(0, |duration| duration.as_nanos() as u64);
+    let default_type = strings.index("alloc_space");
+
+    for string in &strings.0 {
+        bytes_field(&mut profile, 6, string.as_bytes());
+    }
+    varint_field(&mut profile, 9, nanos_since(started));
+    varint_field(&mut profile, 10, duration);
+    packed_field(&mut profile, 13, &comments);
+    varint_field(&mut profile, 14, default_type);
+    profile
+}
+
+// The string table, every string once, the empty one first as pprof needs
+struct Strings(Vec<String>);
+
+impl Strings {
+    fn index(&mut self, text: &str) -> u64 {
+        let index = self.0.iter().position(|string| string == text).unwrap_or_else(|| {
+            self.0.push(text.to_string());
+            self.0.len() - 1
+        });
+        index as u64
+    }
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn varint_field(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn packed_field(out: &mut Vec<u8>, field: u64, values: &[u64]) {
+    let mut packed = Vec::new();
+    values.iter().for_each(|&value| varint(&mut packed, value));
+    bytes_field(out, field, &packed);
+}
@@ -0,0 +1,308 @@
+use crate::force::Force;
+use crate::shutdown;
+use crate::{World, ACCELERATION_DECAY};
+use std::hint::black_box;
+use std::ops::{Add, Div, Mul, Sub};
+use std::time::{Duration, Instant};
+
+/// Number type the motion of particles is computed in by `bench --precision`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Precision {
+    /// Double precision, as the world itself; the baseline of the others
+    F64,
+
+    /// Single precision floats
+    F32,
+
+    /// Q16.16 fixed point in an `i32`: 16 bits of fraction, positions and
+    /// speeds up to ±32768
+    Fixed,
+}
+
+impl Precision {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::F64 => "f64",
+            Precision::F32 => "f32",
+            Precision::Fixed => "fixed",
+        }
+    }
+}
+
+/// What the motion of particles needs of a number type.
+pub trait Scalar: Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    const ZERO: Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn hypot(self, other: Self) -> Self;
+}
+
+impl Scalar for f64 {
+    const ZERO: f64 = 0.0;
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn hypot(self, other: f64) -> f64 {
+        f64::hypot(self, other)
+    }
+}
+
+impl Scalar for f32 {
+    const ZERO: f32 = 0.0;
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn hypot(self, other: f32) -> f32 {
+        f32::hypot(self, other)
+    }
+}
+
+const FRACTION_BITS: u32 = 16;
+const ONE: f64 = (1 << FRACTION_BITS) as f64;
+
+/// Q16.16 fixed-point number. Products and quotients go through an `i64`,
+/// overflowing values wrap around and division by zero gives zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(pub i32);
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * other.0 as i64) >> FRACTION_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, other: Fixed) -> Fixed {
+        if other.0 == 0 {
+            return Fixed(0);
+        }
+        Fixed((((self.0 as i64) << FRACTION_BITS) / other.0 as i64) as i32)
+    }
+}
+
+impl Scalar for Fixed {
+    const ZERO: Fixed = Fixed(0);
+    fn from_f64(value: f64) -> Fixed {
+        Fixed((value * ONE).round() as i32)
+    }
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / ONE
+    }
+    // The square root of a Q32.32 sum of squares is the Q16.16 length
+    fn hypot(self, other: Fixed) -> Fixed {
+        let squares = (self.0 as i64).unsigned_abs().pow(2).saturating_add((other.0 as i64).unsigned_abs().pow(2));
+        Fixed(squares.isqrt().min(i32::MAX as u64) as i32)
+    }
+}
+
+// Position, velocity and acceleration of one particle
+#[derive(Clone, Copy)]
+struct Motion<S> {
+    position: [S; 2],
+    velocity: [S; 2],
+    acceleration: [S; 2],
+    max_speed: S,
+}
+
+// What acts on every particle on every turn, in the number type
+#[derive(Clone, Copy)]
+struct Model<S> {
+    dt: S,
+    keep_after_drag: Option<S>,
+    pull: [S; 2],
+    decay: S,
+    size: [S; 2],
+}
+
+impl<S: Scalar> Motion<S> {
+    // Drag, gravity and the integrator of `Particle::step`, then a bounce off
+    // the sides of the window so every number type stays in range
+    fn step(&mut self, model: &Model<S>) {
+        let Model { dt, keep_after_drag, pull, decay, size } = *model;
+        self.velocity = [self.velocity[0] + pull[0] * dt, self.velocity[1] + pull[1] * dt];
+        if let Some(keep) = keep_after_drag {
+            self.velocity = [self.velocity[0] * keep, self.velocity[1] * keep];
+        }
+        self.velocity = [self.velocity[0] + self.acceleration[0] * dt, self.velocity[1] + self.acceleration[1] * dt];
+        let speed = self.velocity[0].hypot(self.velocity[1]);
+        if speed > self.max_speed {
+            let scale = self.max_speed / speed;
+            self.velocity = [self.velocity[0] * scale, self.velocity[1] * scale];
+        }
+        self.position = [self.position[0] + self.velocity[0] * dt, self.position[1] + self.velocity[1] * dt];
+        self.acceleration = [self.acceleration[0] * decay, self.acceleration[1] * decay];
+
+        for (axis, &side) in size.iter().enumerate() {
+            if self.position[axis] < S::ZERO {
+                self.position[axis] = S::ZERO - self.position[axis];
+                self.velocity[axis] = S::ZERO - self.velocity[axis];
+            } else if self.position[axis] > side {
+                self.position[axis] = side + side - self.position[axis];
+                self.velocity[axis] = S::ZERO - self.velocity[axis];
+            }
+        }
+    }
+}
+
+/// Throughput and drift of one number type after a run.
+pub struct PrecisionReport {
+    pub precision: Precision,
+    pub particles: usize,
+    pub turns: u64,
+    pub elapsed: Duration,
+
+    /// Mean and largest distance in pixels between where the particles ended
+    /// up and where they did in `f64`
+    pub mean_drift: f64,
+    pub max_drift: f64,
+
+    /// Of the kinetic energy left at the end against the `f64` run's, as a
+    /// fraction of it
+    pub energy_error: f64,
+}
+
+impl PrecisionReport {
+    /// Particles moved per second, in millions.
+    pub fn million_updates_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            (self.particles as u64 * self.turns) as f64 / seconds / 1e6
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Moves the particles of the world for `turns` turns in each number type,
+/// from the same starting state, and compares where they end up with the
+/// `f64` run. Only drag, gravity and the integrator are modelled, with
+/// particles bouncing off the sides of the window rather than leaving it:
+/// nothing spawns or dies, so every type moves the same particles.
+pub fn compare(world: &World, turns: u64, precisions: &[Precision]) -> Vec<PrecisionReport> {
+    let baseline = run::<f64>(world, turns);
+    let mut reports = Vec::with_capacity(precisions.len());
+    for &precision in precisions {
+        let (elapsed, done, positions, energy) = match precision {
+            Precision::F64 => baseline.clone(),
+            Precision::F32 => run::<f32>(world, turns),
+            Precision::Fixed => run::<Fixed>(world, turns),
+        };
+        let drifts: Vec<f64> = positions
+            .iter()
+            .zip(&baseline.2)
+            .map(|(a, b)| (a[0] - b[0]).hypot(a[1] - b[1]))
+            .collect();
+        let count = drifts.len().max(1) as f64;
+        reports.push(PrecisionReport {
+            precision,
+            particles: positions.len(),
+            turns: done,
+            elapsed,
+            mean_drift: drifts.iter().sum::<f64>() / count,
+            max_drift: drifts.iter().copied().fold(0.0, f64::max),
+            energy_error: if baseline.3 > 0.0 { (energy - baseline.3) / baseline.3 } else { 0.0 },
+        });
+    }
+    reports
+}
+
+// Time taken, turns run, final positions and kinetic energy
+fn run<S: Scalar>(world: &World, turns: u64) -> (Duration, u64, Vec<[f64; 2]>, f64) {
+    let v = |[x, y]: [f64; 2]| [S::from_f64(x), S::from_f64(y)];
+    let mut motions: Vec<Motion<S>> = world
+        .particles
+        .iter()
+        .filter(|particle| !particle.pinned)
+        .map(|particle| Motion {
+            position: v(particle.position),
+            velocity: v(particle.velocity),
+            acceleration: v(particle.acceleration),
+            max_speed: S::from_f64(particle.max_speed),
+        })
+        .collect();
+
+    let pull = world
+        .forces
+        .iter()
+        .filter_map(|force| match force {
+            Force::Gravity { acceleration } => Some(*acceleration),
+            _ => None,
+        })
+        .fold([0.0, 0.0], |sum, [x, y]| [sum[0] + x, sum[1] + y]);
+    let dt = world.time_step;
+    let model = Model {
+        dt: S::from_f64(dt),
+        keep_after_drag: (world.drag > 0.0).then(|| S::from_f64(1.0 - world.drag)),
+        pull: v(pull),
+        decay: S::from_f64(ACCELERATION_DECAY.powf(dt)),
+        size: v([world.width, world.height]),
+    };
+
+    let start = Instant::now();
+    let mut done = 0;
+    while done < turns && !shutdown::requested() {
+        for motion in &mut motions {
+            motion.step(&model);
+        }
+        black_box(&mut motions);
+        done += 1;
+    }
+    let elapsed = start.elapsed();
+
+    let positions = motions.iter().map(|m| [m.position[0].to_f64(), m.position[1].to_f64()]).collect();
+    let energy = motions
+        .iter()
+        .map(|m| {
+            let [x, y] = [m.velocity[0].to_f64(), m.velocity[1].to_f64()];
+            0.5 * (x * x + y * y)
+        })
+        .sum();
+    (elapsed, done, positions, energy)
+}
+
+/// Prints the reports side by side, one row per number type, with the
+/// throughput relative to the first `f64` row when there is one.
+pub fn print_reports(reports: &[PrecisionReport]) {
+    let baseline = reports.iter().find(|report| report.precision == Precision::F64).map(|r| r.million_updates_per_second());
+    println!(
+        "{:<10}{:>8}{:>10}{:>12}{:>10}{:>16}{:>16}{:>14}",
+        "precision", "turns", "particles", "M updates/s", "speed-up", "mean drift (px)", "max drift (px)", "energy error"
+    );
+    for report in reports {
+        let speed = report.million_updates_per_second();
+        let speedup = baseline.filter(|&b| b > 0.0).map_or("-".to_string(), |b| format!("{:.2}x", speed / b));
+        println!(
+            "{:<10}{:>8}{:>10}{:>12.1}{:>10}{:>16.6}{:>16.6}{:>13.4}%",
+            report.precision.name(),
+            report.turns,
+            report.particles,
+            speed,
+            speedup,
+            report.mean_drift,
+            report.max_drift,
+            report.energy_error * 100.0
+        );
+    }
+}
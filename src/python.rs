@@ -0,0 +1,144 @@
+//! Python module of the `python` feature, to drive controlled experiments
+//! and analyze them from Python. Built as an extension module with
+//! `cargo rustc --release --lib --features python --crate-type cdylib`, then
+//! imported as `memory_particles` once the library is renamed to
+//! `memory_particles.so` (`.pyd` on Windows).
+//!
+//! The particle state comes out as packed records numpy reads as they are:
+//! `numpy.frombuffer(world.particles(), dtype=PARTICLE_DTYPE)`.
+
+use crate::allocator::{memory_stats as stats, set_capturing};
+use crate::config::Config;
+use crate::{World, INITIAL_PARTICLES};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::PathBuf;
+
+/// Fields of the records of `World.particles`, as a numpy structured dtype:
+/// position, velocity and size as little-endian doubles, then the color.
+const PARTICLE_DTYPE: [(&str, &str); 10] = [
+    ("x", "<f8"),
+    ("y", "<f8"),
+    ("velocity_x", "<f8"),
+    ("velocity_y", "<f8"),
+    ("width", "<f8"),
+    ("height", "<f8"),
+    ("r", "<f4"),
+    ("g", "<f4"),
+    ("b", "<f4"),
+    ("a", "<f4"),
+];
+const RECORD_SIZE: usize = 6 * 8 + 4 * 4;
+
+/// A world of particles, made from a scene file or the default scene.
+#[pyclass(name = "World")]
+pub struct PyWorld {
+    world: World,
+}
+
+#[pymethods]
+impl PyWorld {
+    #[new]
+    #[pyo3(signature = (config=None, seed=0, width=1280.0, height=960.0))]
+    fn new(config: Option<PathBuf>, seed: u64, width: f64, height: f64) -> PyResult<PyWorld> {
+        set_capturing(false);
+        let config = match &config {
+            Some(path) => Config::load(path).map_err(|err| PyValueError::new_err(format!("{}: {}", path.display(), err)))?,
+            None => Config::default(),
+        };
+        let initial_particles = config.initial_particles.unwrap_or(INITIAL_PARTICLES);
+        let mut world = World::from_config(width, height, config);
+        world.rng = StdRng::seed_from_u64(seed);
+        world.add_shapes(initial_particles);
+        Ok(PyWorld { world })
+    }
+
+    /// Runs `turns` turns, releasing the GIL meanwhile.
+    #[pyo3(signature = (turns=1))]
+    fn step(&mut self, py: Python<'_>, turns: u64) {
+        let world = &mut self.world;
+        py.allow_threads(|| {
+            for _ in 0..turns {
+                world.update();
+            }
+        });
+    }
+
+    #[getter]
+    fn turn(&self) -> u64 {
+        self.world.current_turn
+    }
+
+    #[getter]
+    fn total_spawned(&self) -> u64 {
+        self.world.total_spawned
+    }
+
+    #[getter]
+    fn total_died(&self) -> u64 {
+        self.world.total_died
+    }
+
+    fn __len__(&self) -> usize {
+        self.world.particles.len()
+    }
+
+    /// One record of `PARTICLE_DTYPE` per particle, in the order they are drawn.
+    fn particles<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let particles = &self.world.particles;
+        PyBytes::new_with(py, particles.len() * RECORD_SIZE, |bytes| {
+            for (record, particle) in bytes.chunks_exact_mut(RECORD_SIZE).zip(particles.iter()) {
+                let [x, y] = particle.position;
+                let [velocity_x, velocity_y] = particle.velocity;
+                let doubles = [x, y, velocity_x, velocity_y, particle.width, particle.height];
+                for (slot, value) in record.chunks_exact_mut(8).zip(doubles) {
+                    slot.copy_from_slice(&value.to_le_bytes());
+                }
+                for (slot, channel) in record[48..].chunks_exact_mut(4).zip(particle.color) {
+                    slot.copy_from_slice(&channel.to_le_bytes());
+                }
+            }
+            Ok(())
+        })
+        .expect("filling the records can't fail")
+    }
+
+    /// Kinetic energy of the particles, sparks left out.
+    fn kinetic_energy(&self) -> f64 {
+        self.world.energy().kinetic
+    }
+
+    /// Hash of the state, equal at the same turn of runs with the same seed
+    /// and scene.
+    fn state_hash(&self) -> u64 {
+        self.world.state_hash()
+    }
+}
+
+/// The allocator counters of the whole process, by name.
+#[pyfunction]
+fn memory_stats(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let stats = stats();
+    let dict = PyDict::new(py);
+    dict.set_item("live_bytes", stats.live_bytes)?;
+    dict.set_item("peak_live_bytes", stats.peak_live_bytes)?;
+    dict.set_item("allocations", stats.allocations)?;
+    dict.set_item("allocated_bytes", stats.allocated_bytes)?;
+    dict.set_item("deallocations", stats.deallocations)?;
+    dict.set_item("freed_bytes", stats.freed_bytes)?;
+    dict.set_item("vec_reallocs", stats.vec_reallocs)?;
+    dict.set_item("reallocations", stats.reallocations)?;
+    dict.set_item("zeroed_allocations", stats.zeroed_allocations)?;
+    Ok(dict)
+}
+
+#[pymodule]
+fn memory_particles(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyWorld>()?;
+    module.add_function(wrap_pyfunction!(memory_stats, module)?)?;
+    module.add("PARTICLE_DTYPE", PARTICLE_DTYPE.to_vec())?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use graphics::math::Vec2d;
+
+// Nodes with at most this many points are not split any further
+const LEAF_SIZE: usize = 8;
+
+// Keeps many particles on the same spot from splitting forever
+const MAX_DEPTH: usize = 16;
+
+// Nodes waiting to be searched by `Near`: every level down takes one off
+// and puts its four children on
+const STACK_SIZE: usize = 3 * MAX_DEPTH + 1;
+
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Vec2d<f64>,
+    max: Vec2d<f64>,
+}
+
+impl Bounds {
+    // Squared distance from a point to the closest point of the box
+    fn distance_squared(&self, point: Vec2d<f64>) -> f64 {
+        let dx = (self.min[0] - point[0]).max(point[0] - self.max[0]).max(0.0);
+        let dy = (self.min[1] - point[1]).max(point[1] - self.max[1]).max(0.0);
+        dx * dx + dy * dy
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Bounds,
+
+    // Range of `QuadTree::points` inside this node
+    start: usize,
+    end: usize,
+    children: Option<[usize; 4]>,
+}
+
+/// Spatial index over a set of points, like the particle positions of a turn,
+/// answering proximity queries without looking at every point.
+///
+/// The tree doesn't follow the points as they move: build a new one when the
+/// positions change, or rebuild this one, which keeps its buffers.
+#[derive(Debug, Clone, Default)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+
+    // Points with the index they were given in, grouped by node
+    points: Vec<(Vec2d<f64>, usize)>,
+}
+
+impl QuadTree {
+    /// Indexes the points, which are referred to by their position in the iterator.
+    pub fn build<I: IntoIterator<Item = Vec2d<f64>>>(positions: I) -> QuadTree {
+        let mut tree = QuadTree::default();
+        tree.rebuild(positions);
+        tree
+    }
+
+    /// Indexes the points in place of those indexed so far, in the room of
+    /// the tree, which only allocates when there are more points than before.
+    pub fn rebuild<I: IntoIterator<Item = Vec2d<f64>>>(&mut self, positions: I) {
+        self.points.clear();
+        self.points.extend(positions.into_iter().enumerate().map(|(index, position)| (position, index)));
+
+        let mut bounds = Bounds { min: [f64::INFINITY; 2], max: [f64::NEG_INFINITY; 2] };
+        for ([x, y], _) in &self.points {
+            bounds.min = [bounds.min[0].min(*x), bounds.min[1].min(*y)];
+            bounds.max = [bounds.max[0].max(*x), bounds.max[1].max(*y)];
+        }
+
+        self.nodes.clear();
+        let len = self.points.len();
+        if len > 0 {
+            split(&mut self.nodes, &mut self.points, bounds, 0, len, 0);
+        }
+    }
+
+    /// Index of the point closest to `point`, `None` when the tree is empty.
+    pub fn nearest(&self, point: Vec2d<f64>) -> Option<usize> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut best = (f64::INFINITY, None);
+        self.search_nearest(0, point, &mut best);
+        best.1
+    }
+
+    /// Indices of every point at most `radius` away from `point`, in no particular order.
+    pub fn within(&self, point: Vec2d<f64>, radius: f64, found: &mut Vec<usize>) {
+        if !self.nodes.is_empty() {
+            self.search_within(0, point, radius * radius, found);
+        }
+    }
+
+    /// Like `within`, as an iterator, which allocates nothing.
+    pub fn near(&self, point: Vec2d<f64>, radius: f64) -> Near<'_> {
+        // The root, when there is one, is the first node
+        let depth = (!self.nodes.is_empty()) as usize;
+        Near { tree: self, point, radius_squared: radius * radius, stack: [0; STACK_SIZE], depth, leaf: [].iter() }
+    }
+
+    fn search_nearest(&self, node: usize, point: Vec2d<f64>, best: &mut (f64, Option<usize>)) {
+        let node = &self.nodes[node];
+        if node.bounds.distance_squared(point) >= best.0 {
+            return;
+        }
+
+        match node.children {
+            None => {
+                for &(position, index) in &self.points[node.start..node.end] {
+                    let distance = distance_squared(position, point);
+                    if distance < best.0 {
+                        *best = (distance, Some(index));
+                    }
+                }
+            }
+            Some(mut children) => {
+                // Closest quadrants first, so the farther ones are usually pruned
+                children.sort_by(|&a, &b| {
+                    let a = self.nodes[a].bounds.distance_squared(point);
+                    let b = self.nodes[b].bounds.distance_squared(point);
+                    a.total_cmp(&b)
+                });
+                for child in children {
+                    self.search_nearest(child, point, best);
+                }
+            }
+        }
+    }
+
+    fn search_within(&self, node: usize, point: Vec2d<f64>, radius_squared: f64, found: &mut Vec<usize>) {
+        let node = &self.nodes[node];
+        if node.bounds.distance_squared(point) > radius_squared {
+            return;
+        }
+
+        match node.children {
+            None => found.extend(
+                self.points[node.start..node.end]
+                    .iter()
+                    .filter(|(position, _)| distance_squared(*position, point) <= radius_squared)
+                    .map(|(_, index)| *index),
+            ),
+            Some(children) => {
+                for child in children {
+                    self.search_within(child, point, radius_squared, found);
+                }
+            }
+        }
+    }
+}
+
+/// The indices of the points near a point, from `QuadTree::near`.
+pub struct Near<'a> {
+    tree: &'a QuadTree,
+    point: Vec2d<f64>,
+    radius_squared: f64,
+
+    // Nodes left to search, the first `depth` of `stack`, and what is left
+    // of the points of the leaf being searched
+    stack: [usize; STACK_SIZE],
+    depth: usize,
+    leaf: std::slice::Iter<'a, (Vec2d<f64>, usize)>,
+}
+
+impl Iterator for Near<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            for &(position, index) in self.leaf.by_ref() {
+                if distance_squared(position, self.point) <= self.radius_squared {
+                    return Some(index);
+                }
+            }
+            if self.depth == 0 {
+                return None;
+            }
+            self.depth -= 1;
+            let node = &self.tree.nodes[self.stack[self.depth]];
+            if node.bounds.distance_squared(self.point) > self.radius_squared {
+                continue;
+            }
+            match node.children {
+                None => self.leaf = self.tree.points[node.start..node.end].iter(),
+                Some(children) => {
+                    self.stack[self.depth..self.depth + 4].copy_from_slice(&children);
+                    self.depth += 4;
+                }
+            }
+        }
+    }
+}
+
+// Adds the node holding `points[start..end]`, splitting it into quadrants
+// when it holds too many points, and returns its index
+fn split(
+    nodes: &mut Vec<Node>,
+    points: &mut [(Vec2d<f64>, usize)],
+    bounds: Bounds,
+    start: usize,
+    end: usize,
+    depth: usize,
+) -> usize {
+    let index = nodes.len();
+    nodes.push(Node { bounds, start, end, children: None });
+    if end - start <= LEAF_SIZE || depth >= MAX_DEPTH {
+        return index;
+    }
+
+    // Orders the points by quadrant: left before right, then top before bottom
+    let center = [(bounds.min[0] + bounds.max[0]) / 2.0, (bounds.min[1] + bounds.max[1]) / 2.0];
+    let middle = start + partition(&mut points[start..end], |p| p[0] < center[0]);
+    let top_left = start + partition(&mut points[start..middle], |p| p[1] < center[1]);
+    let top_right = middle + partition(&mut points[middle..end], |p| p[1] < center[1]);
+
+    let quadrants = [
+        (start, top_left, [bounds.min[0], bounds.min[1]], [center[0], center[1]]),
+        (top_left, middle, [bounds.min[0], center[1]], [center[0], bounds.max[1]]),
+        (middle, top_right, [center[0], bounds.min[1]], [bounds.max[0], center[1]]),
+        (top_right, end, [center[0], center[1]], [bounds.max[0], bounds.max[1]]),
+    ];
+    let mut children = [0; 4];
+    for (child, (start, end, min, max)) in children.iter_mut().zip(quadrants) {
+        *child = split(nodes, points, Bounds { min, max }, start, end, depth + 1);
+    }
+    nodes[index].children = Some(children);
+    index
+}
+
+// Moves the points matching `predicate` to the front, returning how many there are
+fn partition<F: Fn(Vec2d<f64>) -> bool>(points: &mut [(Vec2d<f64>, usize)], predicate: F) -> usize {
+    let mut matching = 0;
+    for i in 0..points.len() {
+        if predicate(points[i].0) {
+            points.swap(i, matching);
+            matching += 1;
+        }
+    }
+    matching
+}
+
+fn distance_squared(a: Vec2d<f64>, b: Vec2d<f64>) -> f64 {
+    let (dx, dy) = (a[0] - b[0], a[1] - b[1]);
+    dx * dx + dy * dy
+}
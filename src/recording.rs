@@ -0,0 +1,299 @@
+use crate::blend::{BlendMode, DRAW_ORDER};
+use crate::shape::{Shape, TRAIL_TURNS};
+use crate::Particle;
+use piston_window::*;
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// A recording is a header, one frame per turn and, once the run ends
+// cleanly, an index of where every frame starts followed by a footer.
+// Everything is little-endian
+const MAGIC: &[u8; 8] = b"MPREC\0\0\x01";
+const INDEX_MAGIC: &[u8; 8] = b"MPRINDEX";
+const HEADER_SIZE: usize = 24;
+const FRAME_HEADER_SIZE: usize = 32;
+const PARTICLE_SIZE: usize = 36;
+const FOOTER_SIZE: usize = 24;
+
+#[cfg(unix)]
+type Bytes = crate::mmap::MappedFile;
+#[cfg(not(unix))]
+type Bytes = Vec<u8>;
+
+/// Writes what the window shows of every turn, to be played back with `play`.
+pub struct RecordingWriter {
+    out: BufWriter<File>,
+    offsets: Vec<u64>,
+    position: u64,
+
+    // Particles of the frame being written, so the count can go first
+    buffer: Vec<u8>,
+}
+
+impl RecordingWriter {
+    pub fn create(path: &Path, width: f64, height: f64) -> io::Result<RecordingWriter> {
+        let mut out = BufWriter::with_capacity(1 << 20, File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        Ok(RecordingWriter { out, offsets: Vec::new(), position: HEADER_SIZE as u64, buffer: Vec::new() })
+    }
+
+    pub fn write_frame<'a, I>(&mut self, turn: u64, background: [f32; 4], particles: I) -> io::Result<()>
+    where
+        I: Iterator<Item = &'a Particle>,
+    {
+        self.buffer.clear();
+        let mut count: u32 = 0;
+        for particle in particles {
+            let [x, y] = particle.position;
+            for value in [x as f32, y as f32, particle.width as f32, particle.height as f32] {
+                self.buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            for channel in particle.color {
+                self.buffer.extend_from_slice(&channel.to_le_bytes());
+            }
+            let [heading, trail] = motion_code(particle.velocity);
+            self.buffer.extend_from_slice(&[blend_code(particle.blend), shape_code(particle.shape), heading, trail]);
+            count += 1;
+        }
+
+        self.offsets.push(self.position);
+        self.out.write_all(&turn.to_le_bytes())?;
+        for channel in background {
+            self.out.write_all(&channel.to_le_bytes())?;
+        }
+        self.out.write_all(&count.to_le_bytes())?;
+        self.out.write_all(&[0; 4])?;
+        self.out.write_all(&self.buffer)?;
+        self.position += (FRAME_HEADER_SIZE + self.buffer.len()) as u64;
+        Ok(())
+    }
+
+    /// Writes the index of the frames, without which opening the recording
+    /// has to go through every frame once.
+    pub fn finish(mut self) -> io::Result<()> {
+        for offset in &self.offsets {
+            self.out.write_all(&offset.to_le_bytes())?;
+        }
+        self.out.write_all(&(self.offsets.len() as u64).to_le_bytes())?;
+        self.out.write_all(&self.position.to_le_bytes())?;
+        self.out.write_all(INDEX_MAGIC)?;
+        self.out.flush()
+    }
+}
+
+// Where frames start: read from the index at the end of the file, or found
+// by walking the frames of a recording cut short
+enum Index {
+    Stored { start: usize, frames: usize },
+    Scanned(Vec<usize>),
+}
+
+/// A recording mapped in memory. Any frame is found in constant time from
+/// the index, so playback can jump around recordings far bigger than the RAM.
+pub struct Recording {
+    bytes: Bytes,
+    index: Index,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// One turn of a recording.
+pub struct Frame<'a> {
+    pub turn: u64,
+    pub background: [f32; 4],
+    particles: &'a [u8],
+}
+
+/// A particle as recorded: only what it takes to draw it. The velocity is
+/// kept to a heading in 256 steps and a speed to the nearest `1 /
+/// TRAIL_TURNS` pixel per turn, enough to draw triangles and trails.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedParticle {
+    pub rectangle: [f64; 4],
+    pub color: [f32; 4],
+    pub blend: BlendMode,
+    pub shape: Shape,
+    pub velocity: [f64; 2],
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> io::Result<Recording> {
+        let file = File::open(path)?;
+        #[cfg(unix)]
+        let bytes = crate::mmap::MappedFile::open(&file)?;
+        #[cfg(not(unix))]
+        let bytes = {
+            use std::io::Read;
+            let mut bytes = Vec::new();
+            (&file).read_to_end(&mut bytes)?;
+            bytes
+        };
+
+        if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+            return Err(invalid("not a particle recording"));
+        }
+        let width = f64::from_le_bytes(array(&bytes, 8));
+        let height = f64::from_le_bytes(array(&bytes, 16));
+        let index = match stored_index(&bytes) {
+            Some(index) => index,
+            None => Index::Scanned(scan(&bytes)),
+        };
+        Ok(Recording { bytes, index, width, height })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.index {
+            Index::Stored { frames, .. } => *frames,
+            Index::Scanned(offsets) => offsets.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the recording ended cleanly, with its index.
+    pub fn is_complete(&self) -> bool {
+        matches!(self.index, Index::Stored { .. })
+    }
+
+    pub fn frame(&self, index: usize) -> Option<Frame<'_>> {
+        if index >= self.len() {
+            return None;
+        }
+        let offset = match &self.index {
+            Index::Stored { start, .. } => u64::from_le_bytes(array(&self.bytes, start + index * 8)) as usize,
+            Index::Scanned(offsets) => offsets[index],
+        };
+        frame_at(&self.bytes, offset)
+    }
+}
+
+impl Frame<'_> {
+    pub fn len(&self) -> usize {
+        self.particles.len() / PARTICLE_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn particles(&self) -> impl Iterator<Item = RecordedParticle> + Clone + '_ {
+        self.particles.chunks_exact(PARTICLE_SIZE).map(|record| {
+            let float = |i: usize| f32::from_le_bytes(array(record, i * 4));
+            RecordedParticle {
+                rectangle: [0, 1, 2, 3].map(|i| float(i) as f64),
+                color: [4, 5, 6, 7].map(float),
+                blend: blend_from_code(record[32]),
+                shape: shape_from_code(record[33]),
+                velocity: velocity_from_code(record[34], record[35]),
+            }
+        })
+    }
+
+    /// Draws the frame the way the window drew the turn: one pass per
+    /// blend mode over the recorded background.
+    pub fn draw<G: Graphics>(&self, ctx: Context, g: &mut G) {
+        clear(self.background, g);
+        for mode in DRAW_ORDER {
+            let draw_state = ctx.draw_state.blend(mode.blend());
+            for particle in self.particles().filter(|particle| particle.blend == mode) {
+                particle.shape.draw(particle.rectangle, particle.velocity, particle.color, &draw_state, ctx.transform, g);
+            }
+        }
+    }
+}
+
+fn stored_index(bytes: &[u8]) -> Option<Index> {
+    let footer = bytes.len().checked_sub(FOOTER_SIZE).filter(|&footer| footer >= HEADER_SIZE)?;
+    if &bytes[footer + 16..] != INDEX_MAGIC {
+        return None;
+    }
+    let frames = u64::from_le_bytes(array(bytes, footer)) as usize;
+    let start = u64::from_le_bytes(array(bytes, footer + 8)) as usize;
+    (start.checked_add(frames.checked_mul(8)?)? == footer).then_some(Index::Stored { start, frames })
+}
+
+// Finds every whole frame by following their particle counts, leaving out
+// a last frame cut in the middle
+fn scan(bytes: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = HEADER_SIZE;
+    while let Some(frame) = frame_at(bytes, offset) {
+        offsets.push(offset);
+        offset += FRAME_HEADER_SIZE + frame.particles.len();
+    }
+    offsets
+}
+
+fn frame_at(bytes: &[u8], offset: usize) -> Option<Frame<'_>> {
+    let header = bytes.get(offset..offset.checked_add(FRAME_HEADER_SIZE)?)?;
+    let count = u32::from_le_bytes(array(header, 24)) as usize;
+    let start = offset + FRAME_HEADER_SIZE;
+    let particles = bytes.get(start..start.checked_add(count.checked_mul(PARTICLE_SIZE)?)?)?;
+    Some(Frame {
+        turn: u64::from_le_bytes(array(header, 0)),
+        background: [8, 12, 16, 20].map(|i| f32::from_le_bytes(array(header, i))),
+        particles,
+    })
+}
+
+fn array<const N: usize>(bytes: &[u8], at: usize) -> [u8; N] {
+    bytes[at..at + N].try_into().expect("slice of the array's length")
+}
+
+fn blend_code(blend: BlendMode) -> u8 {
+    match blend {
+        BlendMode::Alpha => 0,
+        BlendMode::Additive => 1,
+        BlendMode::Multiply => 2,
+    }
+}
+
+fn blend_from_code(code: u8) -> BlendMode {
+    match code {
+        1 => BlendMode::Additive,
+        2 => BlendMode::Multiply,
+        _ => BlendMode::Alpha,
+    }
+}
+
+// Recordings made before shapes have zeros there, and play back as squares
+fn shape_code(shape: Shape) -> u8 {
+    match shape {
+        Shape::Square => 0,
+        Shape::Circle => 1,
+        Shape::Triangle => 2,
+        Shape::Trail => 3,
+    }
+}
+
+fn shape_from_code(code: u8) -> Shape {
+    match code {
+        1 => Shape::Circle,
+        2 => Shape::Triangle,
+        3 => Shape::Trail,
+        _ => Shape::Square,
+    }
+}
+
+// Heading and length of the trail, in pixels up to 255
+fn motion_code([x, y]: [f64; 2]) -> [u8; 2] {
+    let heading = (y.atan2(x).rem_euclid(TAU) / TAU * 256.0).round() as u32 % 256;
+    let trail = (x.hypot(y) * TRAIL_TURNS).round().clamp(0.0, 255.0);
+    [heading as u8, trail as u8]
+}
+
+fn velocity_from_code(heading: u8, trail: u8) -> [f64; 2] {
+    let angle = heading as f64 / 256.0 * TAU;
+    let speed = trail as f64 / TRAIL_TURNS;
+    [angle.cos() * speed, angle.sin() * speed]
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
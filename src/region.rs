@@ -0,0 +1,205 @@
+use crate::boundary::Edge;
+use graphics::math::Vec2d;
+use rand::Rng;
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+// Random points tried before one inside a polygon, or inside the region for
+// Gaussian sampling, is given up on
+const MAX_TRIES: u32 = 64;
+
+/// How points are drawn inside a spawn region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sampling {
+    /// Anywhere with the same chance
+    #[default]
+    Uniform,
+
+    /// Around the middle, thinning out towards the sides
+    Gaussian,
+}
+
+/// Where particles spawn without emitters, in place of `spawn_edge`.
+/// `spread` is the standard deviation of Gaussian sampling, as a fraction
+/// of the size of the region.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SpawnRegion {
+    /// Along some sides of the window or lines, all four sides when missing,
+    /// each with a chance in proportion to its length. Particles head into
+    /// the window
+    Edges {
+        #[serde(default = "all_edges")]
+        edges: Vec<Edge>,
+        #[serde(default)]
+        sampling: Sampling,
+        #[serde(default = "default_spread")]
+        spread: f64,
+    },
+
+    /// Anywhere in the window
+    Area {
+        #[serde(default)]
+        sampling: Sampling,
+        #[serde(default = "default_spread")]
+        spread: f64,
+    },
+
+    /// Inside a rectangle, `position` being its top left corner
+    Rect {
+        position: Vec2d<f64>,
+        size: Vec2d<f64>,
+        #[serde(default)]
+        sampling: Sampling,
+        #[serde(default = "default_spread")]
+        spread: f64,
+    },
+
+    /// Inside a polygon, convex or not, given by its corners in order
+    Polygon {
+        points: Vec<Vec2d<f64>>,
+        #[serde(default)]
+        sampling: Sampling,
+        #[serde(default = "default_spread")]
+        spread: f64,
+    },
+}
+
+fn all_edges() -> Vec<Edge> {
+    vec![Edge::Top, Edge::Bottom, Edge::Left, Edge::Right]
+}
+
+fn default_spread() -> f64 {
+    0.25
+}
+
+impl SpawnRegion {
+    /// Random point in the region, and the direction the particle starts in:
+    /// into the window for edges, up otherwise, like the bottom edge.
+    pub fn spawn_point<R: Rng>(&self, rng: &mut R, width: f64, height: f64) -> (Vec2d<f64>, Vec2d<f64>) {
+        match self {
+            SpawnRegion::Edges { edges, sampling, spread } => {
+                let Some(edge) = pick_edge(edges, rng, width, height) else {
+                    return ([width / 2.0, height], [0.0, -1.0]);
+                };
+                let along = sample_unit(*sampling, *spread, rng);
+                edge.point_at(along, width, height)
+            }
+            SpawnRegion::Area { sampling, spread } => {
+                (sample_rect([0.0, 0.0], [width, height], *sampling, *spread, rng), [0.0, -1.0])
+            }
+            SpawnRegion::Rect { position, size, sampling, spread } => {
+                (sample_rect(*position, *size, *sampling, *spread, rng), [0.0, -1.0])
+            }
+            SpawnRegion::Polygon { points, sampling, spread } => {
+                (sample_polygon(points, *sampling, *spread, rng), [0.0, -1.0])
+            }
+        }
+    }
+}
+
+fn pick_edge<'a, R: Rng>(edges: &'a [Edge], rng: &mut R, width: f64, height: f64) -> Option<&'a Edge> {
+    let total: f64 = edges.iter().map(|edge| edge.length(width, height)).sum();
+    if total <= 0.0 {
+        return edges.first();
+    }
+    let mut left = rng.gen_range(0.0..total);
+    for edge in edges {
+        left -= edge.length(width, height);
+        if left < 0.0 {
+            return Some(edge);
+        }
+    }
+    edges.last()
+}
+
+// Fraction of the way across, between 0 and 1
+fn sample_unit<R: Rng>(sampling: Sampling, spread: f64, rng: &mut R) -> f64 {
+    match sampling {
+        Sampling::Uniform => rng.gen_range(0.0..=1.0),
+        Sampling::Gaussian => {
+            for _ in 0..MAX_TRIES {
+                let t = 0.5 + spread * standard_normal(rng);
+                if (0.0..=1.0).contains(&t) {
+                    return t;
+                }
+            }
+            0.5
+        }
+    }
+}
+
+fn sample_rect<R: Rng>(position: Vec2d<f64>, size: Vec2d<f64>, sampling: Sampling, spread: f64, rng: &mut R) -> Vec2d<f64> {
+    let x = sample_unit(sampling, spread, rng);
+    let y = sample_unit(sampling, spread, rng);
+    [position[0] + x * size[0], position[1] + y * size[1]]
+}
+
+// Points of the bounding box are drawn until one is inside; the first corner
+// is used when none is, which only a polygon with next to no area makes likely
+fn sample_polygon<R: Rng>(points: &[Vec2d<f64>], sampling: Sampling, spread: f64, rng: &mut R) -> Vec2d<f64> {
+    let Some(&first) = points.first() else {
+        return [0.0, 0.0];
+    };
+    let (low, high) = bounds(points);
+    let size = [high[0] - low[0], high[1] - low[1]];
+    for _ in 0..MAX_TRIES {
+        let point = sample_rect(low, size, sampling, spread, rng);
+        if contains(points, point) {
+            return point;
+        }
+    }
+    first
+}
+
+fn bounds(points: &[Vec2d<f64>]) -> (Vec2d<f64>, Vec2d<f64>) {
+    let mut low = [f64::INFINITY; 2];
+    let mut high = [f64::NEG_INFINITY; 2];
+    for point in points {
+        for axis in 0..2 {
+            low[axis] = low[axis].min(point[axis]);
+            high[axis] = high[axis].max(point[axis]);
+        }
+    }
+    (low, high)
+}
+
+/// Whether the point is inside the polygon, by the even-odd rule.
+pub fn contains(points: &[Vec2d<f64>], point: Vec2d<f64>) -> bool {
+    let [x, y] = point;
+    let mut inside = false;
+    let mut previous = match points.last() {
+        Some(&last) => last,
+        None => return false,
+    };
+    for &current in points {
+        let ([x1, y1], [x2, y2]) = (previous, current);
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Area of the polygon, whichever way round its corners go.
+pub fn area(points: &[Vec2d<f64>]) -> f64 {
+    let Some(&last) = points.last() else {
+        return 0.0;
+    };
+    let mut previous = last;
+    let mut twice = 0.0;
+    for &current in points {
+        twice += previous[0] * current[1] - current[0] * previous[1];
+        previous = current;
+    }
+    twice.abs() / 2.0
+}
+
+// Box-Muller, so that no distribution crate is needed for this one use
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+}
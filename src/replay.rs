@@ -0,0 +1,66 @@
+use crate::allocator::{AllocationKind, BACKING};
+use crate::shutdown;
+use crate::trace::{TraceEvent, TraceSummary};
+use std::alloc::{GlobalAlloc, Layout};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Replays the allocation sizes of a recorded trace, in order, directly
+/// against the backing allocator and returns the measured latencies.
+///
+/// Traces don't record when each block was freed, so the replay keeps the
+/// last `live_window` blocks alive and frees the oldest one whenever a new
+/// block would exceed that window, the frees of the trace left out. Zeroed
+/// allocations are replayed as such; reallocations as fresh allocations of
+/// their new size, the block they moved from being unknown. Ctrl+C stops the
+/// replay early and the summary covers the events replayed so far.
+pub fn replay(events: &[TraceEvent], live_window: usize) -> TraceSummary {
+    // Reserved up front so the replay itself doesn't allocate while measuring
+    let mut live: VecDeque<(*mut u8, Layout)> = VecDeque::with_capacity(live_window + 1);
+    let mut measured = Vec::with_capacity(events.len());
+
+    for event in events {
+        if shutdown::requested() {
+            break;
+        }
+
+        // Zero-sized requests never reach a global allocator
+        if event.size == 0 || !event.is_allocation() {
+            continue;
+        }
+        let layout = match Layout::from_size_align(event.size, std::mem::align_of::<usize>()) {
+            Ok(layout) => layout,
+            Err(_) => continue,
+        };
+
+        let start = Instant::now();
+        let ptr = match event.kind {
+            AllocationKind::Zeroed => unsafe { BACKING.alloc_zeroed(layout) },
+            _ => unsafe { BACKING.alloc(layout) },
+        };
+        let time_taken = start.elapsed();
+
+        measured.push(TraceEvent {
+            size: event.size,
+            nanos: time_taken.as_nanos() as u64,
+            tag: event.tag,
+            kind: event.kind,
+        });
+
+        if ptr.is_null() {
+            continue;
+        }
+        live.push_back((ptr, layout));
+        if live.len() > live_window {
+            if let Some((ptr, layout)) = live.pop_front() {
+                unsafe { BACKING.dealloc(ptr, layout) };
+            }
+        }
+    }
+
+    for (ptr, layout) in live {
+        unsafe { BACKING.dealloc(ptr, layout) };
+    }
+
+    TraceSummary::from_events(measured)
+}
@@ -0,0 +1,203 @@
+use crate::allocator::size_histogram;
+use crate::locale::{bundle, fill};
+use crate::metadata::RunMetadata;
+use crate::population::plot_line;
+use crate::trace::{bucket_label, SIZE_BUCKETS};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+const WIDTH: f64 = 1000.0;
+const PANEL: f64 = 200.0;
+const MARGIN: f64 = 40.0;
+
+/// What a run collects for `--report`: the time and population of every turn
+/// or frame, and the sizes of the allocations made since it started. Written
+/// as a single HTML file with the charts drawn in inline SVG, so it opens in
+/// any browser with no scripts, fonts or network.
+pub struct RunReport {
+    /// What is timed, the turn for bench and the frame for the window, and
+    /// its plural
+    step: &'static str,
+    steps: &'static str,
+    times: Vec<Duration>,
+    population: Vec<usize>,
+    sizes_at_start: [u64; SIZE_BUCKETS],
+    sizes_at_end: Option<[u64; SIZE_BUCKETS]>,
+}
+
+impl RunReport {
+    /// Starts counting allocations from now. Room for `capacity` steps is
+    /// made up front, so recording that many allocates nothing during the
+    /// run being measured.
+    pub fn new(step: &'static str, steps: &'static str, capacity: usize) -> RunReport {
+        RunReport {
+            step,
+            steps,
+            times: Vec::with_capacity(capacity),
+            population: Vec::with_capacity(capacity),
+            sizes_at_start: size_histogram(),
+            sizes_at_end: None,
+        }
+    }
+
+    pub fn record(&mut self, time: Duration, population: usize) {
+        self.times.push(time);
+        self.population.push(population);
+    }
+
+    /// Stops counting allocations, so what the end of the run allocates to
+    /// print its results is left out. Counting goes on until the report is
+    /// written otherwise.
+    pub fn finish(&mut self) {
+        self.sizes_at_end.get_or_insert_with(size_histogram);
+    }
+
+    /// Writes the report of a run called `title`, with the `summary` lines
+    /// printed at its end in a table above the charts, in the language set
+    /// with `locale::set_language`.
+    pub fn write_html(&self, path: &Path, title: &str, summary: &[(&str, String)], metadata: &RunMetadata) -> io::Result<()> {
+        let sizes_now = self.sizes_at_end.unwrap_or_else(size_histogram);
+        let sizes: [u64; SIZE_BUCKETS] = std::array::from_fn(|b| sizes_now[b].saturating_sub(self.sizes_at_start[b]));
+
+        let mut html = String::new();
+        // Writing to a String can't fail
+        let _ = writeln!(html, "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">", bundle().code);
+        let _ = writeln!(html, "<title>memory-particles: {}</title>", escape(title));
+        let _ = writeln!(
+            html,
+            "<style>body {{ background: #262b2b; color: #f0f0f0; font-family: monospace; margin: 2em; }} \
+             table {{ border-collapse: collapse; margin-bottom: 2em; }} td {{ padding: 2px 1.5em 2px 0; }} \
+             td:first-child {{ color: #888; }} h2 {{ font-size: 1em; margin-top: 2em; }}</style>\n</head>\n<body>"
+        );
+        let _ = writeln!(html, "<h1>{}</h1>", escape(title));
+
+        let _ = writeln!(html, "<table>");
+        for (name, value) in summary.iter().map(|(name, value)| (*name, value.clone())).chain(metadata.fields()) {
+            let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", escape(name), escape(&value));
+        }
+        let _ = writeln!(html, "</table>");
+
+        let _ = writeln!(html, "<h2>{}</h2>", escape(&fill(bundle().population_per, &[&self.step])));
+        self.population_chart(&mut html);
+        let _ = writeln!(html, "<h2>{}</h2>", escape(&fill(bundle().time_per, &[&self.step])));
+        self.time_chart(&mut html);
+        let _ = writeln!(html, "<h2>{}</h2>", escape(bundle().allocations_by_size));
+        size_chart(&mut html, &sizes);
+
+        let _ = writeln!(html, "</body>\n</html>");
+        std::fs::write(path, html)
+    }
+
+    fn population_chart(&self, html: &mut String) {
+        let columns = columns(self.population.len());
+        let values: Vec<f64> = (0..columns)
+            .map(|c| self.population.get(c * self.population.len() / columns).map_or(0.0, |&p| p as f64))
+            .collect();
+        let max = self.population.iter().copied().max().unwrap_or(0);
+        open_svg(html, PANEL + MARGIN * 2.0);
+        plot_line(html, &values, max as f64, MARGIN, PANEL, MARGIN, WIDTH, "#f0f0f0");
+        caption(html, &fill(bundle().population_caption, &[&max, &self.population.len(), &self.steps]));
+        let _ = writeln!(html, "</svg>");
+    }
+
+    // The slowest step of every pixel column is drawn, so stalls stay
+    // visible however long the run
+    fn time_chart(&self, html: &mut String) {
+        let millis: Vec<f64> = self.times.iter().map(|time| time.as_secs_f64() * 1e3).collect();
+        let columns = columns(millis.len());
+        let mut values = vec![0.0f64; columns];
+        for (i, &time) in millis.iter().enumerate() {
+            let column = i * columns / millis.len();
+            values[column] = values[column].max(time);
+        }
+
+        let mut sorted = millis.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted.get(((sorted.len() as f64 * p) as usize).min(sorted.len().saturating_sub(1))).copied().unwrap_or(0.0);
+        let max = sorted.last().copied().unwrap_or(0.0);
+
+        open_svg(html, PANEL + MARGIN * 2.0);
+        plot_line(html, &values, max.max(f64::EPSILON), MARGIN, PANEL, MARGIN, WIDTH, "#e0a040");
+        caption(
+            html,
+            &fill(
+                bundle().time_caption,
+                &[&format!("{:.3}", percentile(0.5)), &format!("{:.3}", percentile(0.99)), &format!("{:.3}", max)],
+            ),
+        );
+        let _ = writeln!(html, "</svg>");
+    }
+}
+
+// Pixel columns for `samples` values, no more than one per sample
+fn columns(samples: usize) -> usize {
+    (WIDTH as usize).min(samples.max(1))
+}
+
+fn open_svg(html: &mut String, height: f64) {
+    let _ = writeln!(
+        html,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"##,
+        WIDTH + MARGIN * 2.0,
+        height
+    );
+}
+
+fn caption(html: &mut String, text: &str) {
+    let _ = writeln!(html, r##"<text x="{}" y="{}" fill="#888">{}</text>"##, MARGIN, MARGIN + PANEL + 20.0, escape(text));
+}
+
+// Bars on a log scale, since a few sizes usually make up most allocations;
+// hovering a bar shows its exact count
+fn size_chart(html: &mut String, sizes: &[u64; SIZE_BUCKETS]) {
+    let used: Vec<usize> = (0..SIZE_BUCKETS).filter(|&bucket| sizes[bucket] > 0).collect();
+    open_svg(html, PANEL + MARGIN * 3.0);
+    let _ = writeln!(
+        html,
+        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#555"/>"##,
+        MARGIN, MARGIN, WIDTH, PANEL
+    );
+    let (Some(&first), Some(&last)) = (used.first(), used.last()) else {
+        caption(html, bundle().no_allocations);
+        let _ = writeln!(html, "</svg>");
+        return;
+    };
+
+    let max = sizes.iter().copied().max().unwrap_or(1) as f64;
+    let slot = WIDTH / (last - first + 1) as f64;
+    for (bucket, &count) in sizes.iter().enumerate().take(last + 1).skip(first) {
+        let height = (count as f64).ln_1p() / max.ln_1p() * PANEL;
+        let x = MARGIN + (bucket - first) as f64 * slot;
+        let _ = writeln!(
+            html,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#5fa0d3"><title>{}: {}</title></rect>"##,
+            x + slot * 0.1,
+            MARGIN + PANEL - height,
+            slot * 0.8,
+            height,
+            bucket_label(bucket),
+            count
+        );
+        let (label_x, label_y) = (x + slot / 2.0, MARGIN + PANEL + 14.0);
+        let _ = writeln!(
+            html,
+            r##"<text x="{:.1}" y="{:.1}" fill="#888" font-size="10" text-anchor="end" transform="rotate(-35 {:.1} {:.1})">{}</text>"##,
+            label_x, label_y, label_x, label_y, bucket_label(bucket)
+        );
+    }
+    let total: u64 = sizes.iter().sum();
+    let _ = writeln!(
+        html,
+        r##"<text x="{}" y="{}" fill="#888">{}</text>"##,
+        MARGIN,
+        MARGIN * 2.5 + PANEL + 10.0,
+        escape(&fill(bundle().sizes_caption, &[&total]))
+    );
+    let _ = writeln!(html, "</svg>");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
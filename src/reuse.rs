@@ -0,0 +1,221 @@
+use crate::metadata::RunMetadata;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+// Reuse distances are grouped by powers of two: the first bucket is the
+// very next allocation, then 1, 2-3, 4-7... and the last one everything beyond
+pub const BUCKETS: usize = 24;
+
+// Recently freed addresses, direct-mapped on a hash of the address. A free
+// landing on an occupied slot evicts the address kept there, which is then
+// never seen as reused. Fixed-size since it is filled by the allocator
+const TABLE_SIZE: usize = 1 << 14;
+static FREED_ADDRESSES: [AtomicUsize; TABLE_SIZE] = [const { AtomicUsize::new(0) }; TABLE_SIZE];
+static FREED_AT: [AtomicU64; TABLE_SIZE] = [const { AtomicU64::new(0) }; TABLE_SIZE];
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+// Allocations seen since tracking started, the clock distances are measured with
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static HISTOGRAM: [AtomicU64; BUCKETS] = [const { AtomicU64::new(0) }; BUCKETS];
+static EVICTED: AtomicU64 = AtomicU64::new(0);
+
+/// Starts recording reuse distances, from an empty history.
+pub fn enable() {
+    reset();
+    TRACKING.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    TRACKING.load(Ordering::Relaxed)
+}
+
+/// Forgets everything recorded so far, such as the allocations of a warm-up.
+pub fn reset() {
+    lock();
+    for (address, freed_at) in FREED_ADDRESSES.iter().zip(&FREED_AT) {
+        address.store(0, Ordering::Relaxed);
+        freed_at.store(0, Ordering::Relaxed);
+    }
+    for bucket in &HISTOGRAM {
+        bucket.store(0, Ordering::Relaxed);
+    }
+    SEQUENCE.store(0, Ordering::Relaxed);
+    EVICTED.store(0, Ordering::Relaxed);
+    unlock();
+}
+
+/// Records an allocation, and its reuse distance when the address was freed
+/// recently. Addresses are matched whatever the size of the blocks.
+pub fn on_alloc(address: usize) {
+    if !is_enabled() {
+        return;
+    }
+
+    lock();
+    let now = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let slot = slot(address);
+    if FREED_ADDRESSES[slot].load(Ordering::Relaxed) == address {
+        let distance = now - FREED_AT[slot].load(Ordering::Relaxed);
+        HISTOGRAM[bucket(distance)].fetch_add(1, Ordering::Relaxed);
+        FREED_ADDRESSES[slot].store(0, Ordering::Relaxed);
+    }
+    unlock();
+}
+
+/// Remembers a freed address until it is reused or evicted.
+pub fn on_free(address: usize) {
+    if !is_enabled() {
+        return;
+    }
+
+    lock();
+    let slot = slot(address);
+    if FREED_ADDRESSES[slot].swap(address, Ordering::Relaxed) != 0 {
+        EVICTED.fetch_add(1, Ordering::Relaxed);
+    }
+    FREED_AT[slot].store(SEQUENCE.load(Ordering::Relaxed), Ordering::Relaxed);
+    unlock();
+}
+
+fn slot(address: usize) -> usize {
+    // Fibonacci hashing, dropping the low bits every block shares through alignment
+    ((address as u64 >> 4).wrapping_mul(0x9e3779b97f4a7c15) >> (64 - TABLE_SIZE.trailing_zeros())) as usize
+}
+
+fn bucket(distance: u64) -> usize {
+    ((64 - distance.leading_zeros()) as usize).min(BUCKETS - 1)
+}
+
+fn lock() {
+    while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        std::hint::spin_loop();
+    }
+}
+
+fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+/// How many allocations went between freeing an address and allocating it again.
+#[derive(Debug, Clone)]
+pub struct ReuseDistribution {
+    pub buckets: [u64; BUCKETS],
+
+    /// Allocations recorded, reused addresses or not
+    pub allocations: u64,
+
+    /// Freed addresses pushed out of the table before they could be reused
+    pub evicted: u64,
+}
+
+pub fn distribution() -> ReuseDistribution {
+    ReuseDistribution {
+        buckets: std::array::from_fn(|i| HISTOGRAM[i].load(Ordering::Relaxed)),
+        allocations: SEQUENCE.load(Ordering::Relaxed),
+        evicted: EVICTED.load(Ordering::Relaxed),
+    }
+}
+
+impl ReuseDistribution {
+    pub fn reused(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// The bucket holding the median reuse distance, if any address was reused.
+    pub fn median_bucket(&self) -> Option<usize> {
+        let half = self.reused().div_ceil(2);
+        let mut seen = 0;
+        self.buckets.iter().position(|&count| {
+            seen += count;
+            count > 0 && seen >= half
+        })
+    }
+
+    /// Range of distances a bucket holds, like `4-7`.
+    pub fn bucket_label(bucket: usize) -> String {
+        match bucket {
+            0 => "0".to_string(),
+            1 => "1".to_string(),
+            _ if bucket == BUCKETS - 1 => format!("{}+", 1u64 << (bucket - 1)),
+            _ => format!("{}-{}", 1u64 << (bucket - 1), (1u64 << bucket) - 1),
+        }
+    }
+
+    /// One line summary, for reports.
+    pub fn describe(&self) -> String {
+        let share = self.reused() as f64 / self.allocations.max(1) as f64 * 100.0;
+        let median = self.median_bucket().map_or("none".to_string(), ReuseDistribution::bucket_label);
+        format!(
+            "{} of {} allocations reused a freed address ({:.1}%), median distance {}, {} evicted",
+            self.reused(),
+            self.allocations,
+            share,
+            median,
+            self.evicted
+        )
+    }
+
+    /// Writes a standalone SVG bar chart of the distribution, by bucket.
+    pub fn write_svg(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        const BAR: f64 = 40.0;
+        const PANEL: f64 = 300.0;
+        const MARGIN: f64 = 40.0;
+
+        let width = MARGIN * 2.0 + BAR * BUCKETS as f64;
+        let height = MARGIN * 3.0 + PANEL;
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"##,
+            width, height
+        );
+        let _ = writeln!(svg, "<!--\n{}-->", metadata.comment_header());
+        let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#262b2b"/>"##);
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#555"/>"##,
+            MARGIN, MARGIN, BAR * BUCKETS as f64, PANEL
+        );
+
+        let max = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        for (i, &count) in self.buckets.iter().enumerate() {
+            let x = MARGIN + i as f64 * BAR;
+            let bar = count as f64 / max as f64 * PANEL;
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#5f9fd3"><title>{}: {}</title></rect>"##,
+                x + 2.0,
+                MARGIN + PANEL - bar,
+                BAR - 4.0,
+                bar,
+                ReuseDistribution::bucket_label(i),
+                count
+            );
+            let _ = writeln!(
+                svg,
+                r##"<text x="{:.1}" y="{:.1}" fill="#aaa" font-size="9" text-anchor="middle">{}</text>"##,
+                x + BAR / 2.0,
+                MARGIN + PANEL + 14.0,
+                ReuseDistribution::bucket_label(i)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#f0f0f0">reuse distance in allocations (max {} per bucket): {}</text>"##,
+            MARGIN, MARGIN - 8.0, max, self.describe()
+        );
+
+        let footer: Vec<String> = metadata.fields().iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#888">{}</text>"##,
+            MARGIN, height - 12.0, footer.join("  ")
+        );
+        let _ = writeln!(svg, "</svg>");
+        std::fs::write(path, svg)
+    }
+}
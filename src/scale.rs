@@ -0,0 +1,84 @@
+use gfx::format::{DepthStencil, Srgba8};
+use gfx::handle::{DepthStencilView, RenderTargetView};
+use gfx::texture::{FilterMethod, SamplerInfo, WrapMode};
+use gfx::Factory;
+use gfx_device_gl::Resources;
+use piston_window::*;
+
+/// Offscreen target of `--render-scale`, a fraction of the window in every
+/// direction, which the world is drawn to before being stretched over the
+/// window. The world is still simulated and laid out in window coordinates;
+/// only the pixels it is drawn with are fewer. Unlike the window's, its
+/// pixels are kept from one frame to the next, so a translucent background
+/// leaves trails in it, at any scale.
+pub struct ScaledTarget {
+    size: [u32; 2],
+
+    // Until the first frame, when the target is cleared
+    fresh: bool,
+    color: RenderTargetView<Resources, Srgba8>,
+    stencil: DepthStencilView<Resources, DepthStencil>,
+    texture: G2dTexture,
+}
+
+/// Parses a `--render-scale`, from above 0 up to 1.
+pub fn parse_scale(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(scale) if scale > 0.0 && scale <= 1.0 => Ok(scale),
+        Ok(_) => Err("must be above 0 and at most 1".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+impl ScaledTarget {
+    /// Size of the target for a window drawn with `draw_size` pixels.
+    pub fn size_for(draw_size: [u32; 2], scale: f64) -> [u32; 2] {
+        draw_size.map(|pixels| ((pixels as f64 * scale).round() as u32).max(1))
+    }
+
+    pub fn new(factory: &mut GfxFactory, size: [u32; 2]) -> Result<ScaledTarget, String> {
+        let (width, height) = (size[0] as u16, size[1] as u16);
+        let (surface, view, color) = factory
+            .create_render_target::<Srgba8>(width, height)
+            .map_err(|err| format!("Could not create the render target: {:?}", err))?;
+        let stencil = factory
+            .create_depth_stencil_view_only::<DepthStencil>(width, height)
+            .map_err(|err| format!("Could not create the render target: {:?}", err))?;
+        let sampler = factory.create_sampler(SamplerInfo::new(FilterMethod::Bilinear, WrapMode::Clamp));
+        Ok(ScaledTarget { size, fresh: true, color, stencil, texture: Texture { surface, sampler, view } })
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Draws with `f` to the target, in the coordinates of the window of
+    /// `viewport`, which are scaled down to the target, over what was drawn
+    /// to it before.
+    pub fn draw<F>(&mut self, window: &mut PistonWindow, viewport: Viewport, f: F)
+    where
+        F: FnOnce(Context, &mut G2d),
+    {
+        if self.fresh {
+            window.encoder.clear(&self.color, [0.0, 0.0, 0.0, 1.0]);
+            self.fresh = false;
+        }
+        let viewport = Viewport {
+            rect: [0, 0, self.size[0] as i32, self.size[1] as i32],
+            draw_size: self.size,
+            window_size: viewport.window_size,
+        };
+        window.g2d.draw(&mut window.encoder, &self.color, &self.stencil, viewport, f);
+    }
+
+    /// Stretches what was drawn to the target over the window of `ctx`,
+    /// smoothed. Targets are drawn bottom row first, so it is flipped.
+    pub fn draw_upscaled<G: Graphics<Texture = G2dTexture>>(&self, ctx: Context, g: &mut G) {
+        let window_size = ctx.get_view_size();
+        let transform = ctx
+            .transform
+            .trans(0.0, window_size[1])
+            .scale(window_size[0] / self.size[0] as f64, -window_size[1] / self.size[1] as f64);
+        image(&self.texture, transform, g);
+    }
+}
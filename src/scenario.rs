@@ -0,0 +1,142 @@
+use crate::controller::PopulationController;
+use crate::vec_policy::StoragePolicy;
+use crate::World;
+
+/// Particles the sawtooth grows to before it clears them all.
+pub const SAWTOOTH_PEAK: usize = 100_000;
+
+// Particles the sawtooth spawns every turn on its way to the peak
+const SAWTOOTH_STEP: usize = 2_000;
+
+// Population of the steady scenario
+const STEADY_POPULATION: usize = 5_000;
+
+// Buffers fragmentation keeps alongside the particles, half of them freed
+// and allocated again every turn
+const BALLAST_BUFFERS: usize = 512;
+
+// Sizes of those buffers, up to but not including the larger, spread
+// evenly over the powers of two in between
+const BALLAST_SIZES: [usize; 2] = [16, 64 * 1024];
+
+/// Built-in runs for `--scenario`, each driving the world to exercise the
+/// allocator in a way of its own, over the scene's own spawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scenario {
+    /// A constant count: every particle that dies is replaced on the next turn
+    Steady,
+
+    /// Grows to `SAWTOOTH_PEAK` particles, clears them all, and again
+    Sawtooth,
+
+    /// Particles churning at random, with buffers of varied sizes freed and
+    /// allocated in between them
+    Fragmentation,
+
+    /// A population swinging up and down, with a particle list that is
+    /// never shrunk, so it keeps the room of the largest population
+    VecGrowth,
+}
+
+impl Scenario {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::Steady => "steady",
+            Scenario::Sawtooth => "sawtooth",
+            Scenario::Fragmentation => "fragmentation",
+            Scenario::VecGrowth => "vec-growth",
+        }
+    }
+
+    /// What the scenario does, for the reports.
+    pub fn description(self) -> String {
+        match self {
+            Scenario::Steady => format!("{} particles, every death replaced", STEADY_POPULATION),
+            Scenario::Sawtooth => format!("grows by {} particles a turn to {}, then clears them all", SAWTOOTH_STEP, SAWTOOTH_PEAK),
+            Scenario::Fragmentation => format!(
+                "random spawns and culls, with {} buffers of {} to {} bytes half freed every turn",
+                BALLAST_BUFFERS, BALLAST_SIZES[0], BALLAST_SIZES[1]
+            ),
+            Scenario::VecGrowth => "0 to 40000 particles and back every 600 turns, the particle list never shrunk".to_string(),
+        }
+    }
+
+    /// Sets `world` up to run the scenario from its next turn.
+    pub fn apply(self, world: &mut World) {
+        match self {
+            Scenario::Steady => {
+                world.controller =
+                    Some(PopulationController::Count { target: STEADY_POPULATION, gain: 1.0, max_step: STEADY_POPULATION });
+            }
+            Scenario::Sawtooth => {
+                world.controller = Some(PopulationController::Count { target: SAWTOOTH_PEAK, gain: 1.0, max_step: SAWTOOTH_STEP });
+            }
+            Scenario::Fragmentation => {
+                world.controller = None;
+                world.spawn_rate = 50;
+            }
+            Scenario::VecGrowth => {
+                world.controller =
+                    Some(PopulationController::Wave { mean: 20_000, amplitude: 20_000, period: 600, gain: 0.2, max_step: 1_000 });
+                world.set_storage_policy(StoragePolicy::NeverShrink);
+            }
+        }
+        world.scenario = Some(ScenarioRun::new(self));
+    }
+}
+
+/// What a scenario does on every turn, kept by the world running it.
+#[derive(Debug, Clone)]
+pub struct ScenarioRun {
+    pub scenario: Scenario,
+
+    // Times the sawtooth cleared the world
+    pub clears: u64,
+    ballast: Vec<Vec<u8>>,
+
+    // Of the sizes of the buffers, apart from the world's random numbers so
+    // that seeded runs spawn the same particles with or without them
+    state: u64,
+}
+
+impl ScenarioRun {
+    pub fn new(scenario: Scenario) -> ScenarioRun {
+        ScenarioRun { scenario, clears: 0, ballast: Vec::new(), state: 0x9e37_79b9_7f4a_7c15 }
+    }
+
+    /// Runs the scenario's part of `turn`, before the world's spawns.
+    pub fn turn(&mut self, turn: u64) {
+        if self.scenario == Scenario::Fragmentation {
+            self.churn(turn);
+        }
+    }
+
+    /// Whether the sawtooth is at its peak with `population` particles, all
+    /// but the deaths of its last turn.
+    pub fn clears_at(&self, population: usize) -> bool {
+        self.scenario == Scenario::Sawtooth && population + SAWTOOTH_STEP / 2 >= SAWTOOTH_PEAK
+    }
+
+    // Frees every other buffer, the odd ones on odd turns, and allocates
+    // each again at another size, so the freed blocks are scattered among
+    // live ones of other sizes
+    fn churn(&mut self, turn: u64) {
+        if self.ballast.is_empty() {
+            self.ballast = (0..BALLAST_BUFFERS).map(|_| vec![0; self.next_size()]).collect();
+        }
+        for index in (turn as usize % 2..BALLAST_BUFFERS).step_by(2) {
+            self.ballast[index] = Vec::new();
+            self.ballast[index] = vec![turn as u8; self.next_size()];
+        }
+    }
+
+    fn next_size(&mut self) -> usize {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        let [low, high] = BALLAST_SIZES.map(|size| size.trailing_zeros() as u64);
+        let shift = low + self.state % (high - low);
+        (1 << shift) + (self.state >> 32) as usize % (1 << shift)
+    }
+}
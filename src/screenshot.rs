@@ -0,0 +1,75 @@
+use gfx_device_gl::Device;
+use memory_particles::capture::{flip_rows, write_rgb_png};
+use memory_particles::telemetry;
+use std::fs;
+use std::path::PathBuf;
+
+/// Saves the frames of the window as it shows them, overlays and all, when
+/// `F12` asks for one and every `--screenshot-every` frames. Unlike the
+/// frames of `--capture-frames`, drawn again on the CPU, they are read back
+/// from the framebuffer, so they show what this GPU drew.
+pub struct Screenshots {
+    directory: PathBuf,
+    every: Option<u64>,
+    requested: bool,
+}
+
+impl Screenshots {
+    pub fn new(directory: PathBuf, every: Option<u64>) -> Screenshots {
+        Screenshots { directory, every: every.filter(|&every| every > 0), requested: false }
+    }
+
+    /// Saves the next frame drawn.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Whether `frame`, counted from 0, is to be saved.
+    pub fn is_due(&self, frame: u64) -> bool {
+        self.requested || self.every.is_some_and(|every| (frame + 1).is_multiple_of(every))
+    }
+
+    /// Reads back the frame just drawn to the framebuffer of `size` and hands
+    /// it to the telemetry worker to save as `screenshot-<frame>.png`.
+    /// Returns false when the worker was too far behind to take it.
+    pub fn save(&mut self, device: &mut Device, size: [u32; 2], frame: u64, turn: u64) -> bool {
+        self.requested = false;
+        let [width, height] = size;
+        if width == 0 || height == 0 {
+            return true;
+        }
+        let pixels = read_pixels(device, width, height);
+
+        let directory = self.directory.clone();
+        telemetry::submit(move || {
+            let path = directory.join(format!("screenshot-{:06}.png", frame));
+            let data = flip_rows(&pixels, width as usize * 3);
+            match fs::create_dir_all(&directory).and_then(|()| write_rgb_png(&path, width, height, &data)) {
+                Ok(()) => println!("turn {}: saved screenshot {}", turn, path.display()),
+                Err(err) => eprintln!("Could not save the screenshot of frame {}: {}", frame, err),
+            }
+        })
+    }
+}
+
+// The RGB pixels of the framebuffer, row by row from the bottom, as GL
+// reads them. The encoder was flushed by `draw_2d`, so the frame is all in it
+fn read_pixels(device: &mut Device, width: u32, height: u32) -> Vec<u8> {
+    let mut pixels = vec![0u8; width as usize * height as usize * 3];
+    // Safe: the buffer holds the whole framebuffer, packed without padding
+    unsafe {
+        device.with_gl(|gl| {
+            gl.PixelStorei(gfx_gl::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gfx_gl::RGB,
+                gfx_gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        });
+    }
+    pixels
+}
@@ -0,0 +1,146 @@
+use crate::allocator::{finish_allocation_log, memory_stats, set_capturing, start_allocation_log, AllocationLog};
+use crate::config::Config;
+use crate::trace::{size_bucket, TraceEvent, TraceSummary, SIZE_BUCKETS};
+use crate::World;
+use std::hint::black_box;
+
+// Sizes of the known allocations, one of each power-of-two bucket from 1 B
+// to 4 KiB and some in between
+const SIZES: [usize; 16] = [1, 2, 3, 5, 8, 13, 24, 40, 64, 100, 256, 500, 1024, 1500, 2048, 4096];
+const ROUNDS: usize = 8;
+const LEAKED_BYTES: usize = 4096;
+
+/// The outcome of one check of the instrumentation.
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+
+    /// What was expected and what was measured
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, passed: bool, detail: String) -> Check {
+        Check { name, passed, detail }
+    }
+}
+
+/// Makes allocations of known sizes, grows the particle `Vec` and leaks a
+/// block on purpose, checking that the allocator's counters of allocations
+/// and frees, the size histogram of traces and the live heap each see
+/// exactly that. Allocation lines stop being printed, which would otherwise
+/// allocate on the telemetry worker meanwhile.
+pub fn run() -> Vec<Check> {
+    set_capturing(false);
+    let mut checks = Vec::new();
+    known_allocations(&mut checks);
+    vec_growth(&mut checks);
+    deliberate_leak(&mut checks);
+    checks
+}
+
+fn known_allocations(checks: &mut Vec<Check>) {
+    let count = SIZES.len() * ROUNDS;
+    let bytes: usize = SIZES.iter().sum::<usize>() * ROUNDS;
+
+    // The holder is allocated up front so only the blocks are measured
+    let mut blocks: Vec<Box<[u8]>> = Vec::with_capacity(count);
+    let before = memory_stats();
+    start_allocation_log();
+    for _ in 0..ROUNDS {
+        for size in SIZES {
+            blocks.push(black_box(vec![0u8; size].into_boxed_slice()));
+        }
+    }
+    let during = memory_stats();
+    // Keeps the holder, freeing only the blocks
+    black_box(&mut blocks).clear();
+    let after = memory_stats();
+
+    // Last, since the log allocates the copy it returns
+    let log = finish_allocation_log();
+
+    let allocations = during.allocations - before.allocations;
+    checks.push(Check::new(
+        "allocation count",
+        allocations == count as u64,
+        format!("expected {}, counted {}", count, allocations),
+    ));
+    let allocated = during.allocated_bytes - before.allocated_bytes;
+    checks.push(Check::new(
+        "allocated bytes",
+        allocated == bytes as u64,
+        format!("expected {}, counted {}", bytes, allocated),
+    ));
+    let live = during.live_bytes as i64 - before.live_bytes as i64;
+    let freed = during.live_bytes as i64 - after.live_bytes as i64;
+    checks.push(Check::new(
+        "live bytes",
+        live == bytes as i64 && freed == bytes as i64,
+        format!("expected {} up and down, went up {} and down {}", bytes, live, freed),
+    ));
+
+    let frees = after.deallocations - during.deallocations;
+    let freed_bytes = after.freed_bytes - during.freed_bytes;
+    checks.push(Check::new(
+        "frees",
+        frees == count as u64 && freed_bytes == bytes as u64,
+        format!("expected {} of {} bytes, counted {} of {} bytes", count, bytes, frees, freed_bytes),
+    ));
+
+    let summary = summarize(&log);
+    let mut expected = [0u64; SIZE_BUCKETS];
+    for size in SIZES {
+        expected[size_bucket(size)] += ROUNDS as u64;
+    }
+    let wrong = (0..expected.len()).filter(|&bucket| summary.size_buckets[bucket] != expected[bucket]).count();
+    checks.push(Check::new(
+        "size histogram",
+        wrong == 0 && log.missed == 0,
+        format!("{} of {} buckets differ, {} allocations missed by the log", wrong, expected.len(), log.missed),
+    ));
+}
+
+fn vec_growth(checks: &mut Vec<Check>) {
+    let mut world = World::from_config(1280.0, 960.0, Config::default());
+    world.add_shapes(10);
+
+    // With no room left, the next particle makes the `Vec` reallocate
+    world.particles.shrink_to_fit();
+    let before = memory_stats();
+    start_allocation_log();
+    world.add_shapes(1);
+    let log = finish_allocation_log();
+    let after = memory_stats();
+
+    let reallocs = after.vec_reallocs - before.vec_reallocs;
+    let summary = summarize(&log);
+    checks.push(Check::new(
+        "vec growth",
+        reallocs == 1 && summary.vec_grows == 1,
+        format!("expected 1, counted {} and {} tagged in the log", reallocs, summary.vec_grows),
+    ));
+}
+
+fn deliberate_leak(checks: &mut Vec<Check>) {
+    let before = memory_stats();
+    let leaked: &mut [u8] = Box::leak(black_box(vec![0u8; LEAKED_BYTES].into_boxed_slice()));
+    black_box(leaked);
+    let after = memory_stats();
+
+    let left = after.live_bytes as i64 - before.live_bytes as i64;
+    checks.push(Check::new(
+        "leak",
+        left == LEAKED_BYTES as i64,
+        format!("expected {} bytes left live, found {}", LEAKED_BYTES, left),
+    ));
+}
+
+fn summarize(log: &AllocationLog) -> TraceSummary {
+    TraceSummary::from_events(log.allocations.iter().map(|allocation| TraceEvent {
+        size: allocation.size,
+        nanos: 0,
+        tag: allocation.tag,
+        kind: allocation.kind,
+    }))
+}
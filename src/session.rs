@@ -0,0 +1,99 @@
+use crate::Particle;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Everything the window needs to carry on where it was closed, written by
+/// `--session` on exit and read back by `--resume-session`: the scene, the
+/// window, the overlays shown and the state of the world.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Scene file the world was loaded from, only shown to the user; the
+    /// scene is rebuilt from `scene_text`, so editing the file afterwards
+    /// doesn't change the session
+    pub scene_path: Option<PathBuf>,
+
+    /// Text of the scene file when the session was written, the default
+    /// scene when missing
+    pub scene_text: Option<String>,
+
+    /// Size of the window, which is what the world spans
+    pub width: f64,
+    pub height: f64,
+    pub overlays: Overlays,
+    pub world: WorldState,
+}
+
+/// Overlays and modes of the window toggled by keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overlays {
+    pub paused: bool,
+    pub hud: bool,
+    pub frame_chart: bool,
+    pub correlation: bool,
+
+    /// Missing from sessions written before the survival curve
+    #[serde(default)]
+    pub survival: bool,
+
+    /// And before the allocation graph
+    #[serde(default)]
+    pub allocation_graph: bool,
+
+    /// And before the arrows of the noise field
+    #[serde(default)]
+    pub noise_field: bool,
+}
+
+/// What a world built from its scene doesn't have yet of a running one: the
+/// particles, the counters, the tunable parameters as changed since and the
+/// forces switched off. The random numbers go on from a seed drawn from the
+/// generator rather than from where it was.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldState {
+    pub turn: u64,
+    pub total_spawned: u64,
+    pub total_died: u64,
+    pub seed: u64,
+
+    /// `NAME=VALUE` of every tunable parameter
+    pub params: Vec<String>,
+    pub disabled_forces: Vec<String>,
+    pub particles: Vec<Particle>,
+    pub sparks: Vec<Particle>,
+}
+
+impl Session {
+    /// Writes the session to `path` as JSON, replacing it only once written
+    /// in full, so a crash while writing leaves the last one as it was.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&partial, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Session> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Serde of speed limits, which JSON can't write as infinite: unlimited
+/// ones are written as `null`.
+pub mod unlimited {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        Some(*value).filter(|value| value.is_finite()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::INFINITY))
+    }
+}
@@ -0,0 +1,100 @@
+use graphics::math::{Matrix2d, Vec2d};
+use graphics::{DrawState, Ellipse, Graphics, Line, Polygon, Rectangle};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// What a particle is drawn as, inside the square of its size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// A solid square, the cheapest to draw
+    #[default]
+    Square,
+
+    /// A disc filling the square
+    Circle,
+
+    /// A triangle pointing where the particle is heading, up when it is
+    /// standing still
+    Triangle,
+
+    /// A line from the particle back along its velocity, as long as the
+    /// distance it covers in `TRAIL_TURNS` turns and as thick as half its size
+    Trail,
+}
+
+/// Turns of travel a trail is drawn behind its particle.
+pub const TRAIL_TURNS: f64 = 4.0;
+
+// Sides of circles, enough for particles a few dozen pixels wide
+const CIRCLE_RESOLUTION: u32 = 16;
+
+/// The shape of new particles: always the same one, or one of a list
+/// picked at random for every particle.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ShapeSpec {
+    One(Shape),
+    Any(Vec<Shape>),
+}
+
+impl Default for ShapeSpec {
+    fn default() -> ShapeSpec {
+        ShapeSpec::One(Shape::default())
+    }
+}
+
+impl ShapeSpec {
+    /// Randomness is only drawn when there is more than one shape to pick
+    /// from, so scenes with a single one spawn the same particles as before.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Shape {
+        match self {
+            ShapeSpec::One(shape) => *shape,
+            ShapeSpec::Any(shapes) if shapes.len() > 1 => shapes[rng.gen_range(0..shapes.len())],
+            ShapeSpec::Any(shapes) => shapes.first().copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl Shape {
+    /// Whether soft particles draw the shape as the discs of their falloff.
+    /// Triangles and trails stay solid.
+    pub fn is_soft(&self) -> bool {
+        matches!(self, Shape::Square | Shape::Circle)
+    }
+
+    /// Draws a particle of this shape in `rectangle`, `[x, y, width,
+    /// height]`, moving by `velocity` every turn.
+    pub fn draw<G: Graphics>(
+        &self,
+        rectangle: [f64; 4],
+        velocity: Vec2d<f64>,
+        color: [f32; 4],
+        draw_state: &DrawState,
+        transform: Matrix2d,
+        g: &mut G,
+    ) {
+        let [x, y, width, height] = rectangle;
+        let center = [x + width / 2.0, y + height / 2.0];
+        match self {
+            Shape::Square => Rectangle::new(color).draw(rectangle, draw_state, transform, g),
+            Shape::Circle => {
+                Ellipse::new(color).resolution(CIRCLE_RESOLUTION).draw(rectangle, draw_state, transform, g)
+            }
+            Shape::Triangle => {
+                let speed = velocity[0].hypot(velocity[1]);
+                let [dx, dy] = if speed > 0.0 { [velocity[0] / speed, velocity[1] / speed] } else { [0.0, -1.0] };
+                let (along, across) = (height.max(width) / 2.0, width.min(height) / 2.0);
+                let tip = [center[0] + dx * along, center[1] + dy * along];
+                let back = [center[0] - dx * along, center[1] - dy * along];
+                let corners = [tip, [back[0] - dy * across, back[1] + dx * across], [back[0] + dy * across, back[1] - dx * across]];
+                Polygon::new(color).draw(&corners, draw_state, transform, g);
+            }
+            Shape::Trail => {
+                let tail = [center[0] - velocity[0] * TRAIL_TURNS, center[1] - velocity[1] * TRAIL_TURNS];
+                let radius = width.min(height) / 4.0;
+                Line::new(color, radius).draw([center[0], center[1], tail[0], tail[1]], draw_state, transform, g);
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Routes Ctrl+C to a flag that long-running modes and the window poll, so
+/// they can stop at a clean point, flush their output and print their report
+/// instead of dying in the middle of a write.
+pub fn install_handler() {
+    let installed = ctrlc::set_handler(|| REQUESTED.store(true, Ordering::SeqCst));
+    if let Err(err) = installed {
+        eprintln!("Could not install the Ctrl+C handler: {}", err);
+    }
+}
+
+/// Whether Ctrl+C was pressed since the handler was installed.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,429 @@
+use crate::clock::{StallGuard, TurnClock};
+use crate::correlation::CorrelationChart;
+use crate::snapshot::SnapshotSlots;
+use crate::timeline::Timeline;
+use crate::tuning::{SliderValues, TuningPanel};
+use crate::{cohort_history, finish_journal, frame_capture, hash_stream, preallocate_histories, record_turn, record_video};
+use crate::{save_frame, start_recording, start_video, Cli};
+use graphics::math::Vec2d;
+use memory_particles::accessibility::Accessibility;
+use memory_particles::allocator::{finish_allocation_log, memory_stats, set_capturing, set_thread_role, start_allocation_log, ThreadRole};
+use memory_particles::ambient::AmbientCycle;
+use memory_particles::camera::{CameraPath, CameraView};
+use memory_particles::capture::FrameCapture;
+use memory_particles::energy::EnergyHistory;
+use memory_particles::falloff::Falloff;
+use memory_particles::force::Force;
+use memory_particles::freelist::{ParticlePool, ParticlePoolStats};
+use memory_particles::handoff::{SnapshotWriter, WorldSnapshot};
+use memory_particles::mutate::Mutations;
+use memory_particles::obstacle::Obstacle;
+use memory_particles::palette::Palette;
+use memory_particles::population::{CohortHistory, PopulationHistory};
+use memory_particles::recording::RecordingWriter;
+use memory_particles::statehash::HashStream;
+use memory_particles::survival::{SurvivalCurve, SurvivalPoint};
+use memory_particles::trail::TrailSettings;
+use memory_particles::video::VideoRecorder;
+use memory_particles::zone::Zone;
+use memory_particles::{pprof, reuse, World, BASE_TURN_RATE};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// What the window tells the thread running the simulation. Everything but
+/// the cursor, which moves with every mouse event, is a closure run on the
+/// simulation between two turns.
+pub enum Command<'a> {
+    Cursor { position: Vec2d<f64>, inside: bool },
+    Run(Box<dyn FnOnce(&mut Simulation<'a>) + Send + 'a>),
+    Stop,
+}
+
+/// The end of the window, sending the simulation its commands.
+pub struct Commands<'a>(Sender<Command<'a>>);
+
+impl<'a> Commands<'a> {
+    pub fn new(sender: Sender<Command<'a>>) -> Commands<'a> {
+        Commands(sender)
+    }
+
+    /// Points the cursor of the world at `position`, in the world, and
+    /// lets it push particles away while `inside` the window.
+    pub fn point(&self, position: Vec2d<f64>, inside: bool) {
+        let _ = self.0.send(Command::Cursor { position, inside });
+    }
+
+    /// Runs `command` on the simulation after the turn it is on, without
+    /// waiting for it.
+    pub fn send(&self, command: impl FnOnce(&mut Simulation<'a>) + Send + 'a) {
+        let _ = self.0.send(Command::Run(Box::new(command)));
+    }
+
+    /// Runs `command` on the simulation like `send`, and waits for what it
+    /// returns.
+    pub fn call<R: Send + 'a>(&self, command: impl FnOnce(&mut Simulation<'a>) -> R + Send + 'a) -> R {
+        let (reply, replied) = mpsc::sync_channel(1);
+        self.send(move |simulation| {
+            let _ = reply.send(command(simulation));
+        });
+        replied.recv().unwrap_or_else(|_| {
+            eprintln!("The simulation thread stopped");
+            process::exit(1);
+        })
+    }
+
+    pub fn stop(&self) {
+        let _ = self.0.send(Command::Stop);
+    }
+}
+
+/// A status the simulation has for the title, with whether the timeline
+/// started over or was stepped through, for the scrubber to select its
+/// latest turn.
+pub struct Notice {
+    pub status: String,
+    pub latest: bool,
+}
+
+/// The charts of what the turns record, which the window draws.
+pub struct Charts {
+    pub timeline: Timeline,
+    pub correlation: CorrelationChart,
+}
+
+/// What is drawn of a scene besides its particles, the same from turn to
+/// turn until a command changes it.
+#[derive(Default)]
+pub struct Scenery {
+    pub width: f64,
+    pub height: f64,
+    pub zones: Vec<Zone>,
+    pub obstacles: Vec<Obstacle>,
+    pub background: [f32; 4],
+    pub ambient: Option<AmbientCycle>,
+    pub camera: CameraPath,
+    pub falloff: Option<Falloff>,
+    pub trail: Option<TrailSettings>,
+    pub palette: Option<Palette>,
+    pub accessibility: Accessibility,
+    pub max_heap_bytes: Option<usize>,
+
+    // The noise forces switched on, whose arrows V draws
+    pub noises: Vec<Force>,
+}
+
+impl Scenery {
+    pub fn of(world: &World) -> Scenery {
+        let field = world.force_field();
+        let noises = field.active().map(|(_, force)| force).filter(|force| matches!(force, Force::Noise { .. }));
+        Scenery {
+            width: world.width,
+            height: world.height,
+            zones: world.zones.clone(),
+            obstacles: world.obstacles.clone(),
+            background: world.background,
+            ambient: world.ambient.clone(),
+            camera: world.camera.clone(),
+            falloff: world.falloff,
+            trail: world.trail,
+            palette: world.palette.clone(),
+            accessibility: world.accessibility,
+            max_heap_bytes: world.max_heap_bytes,
+            noises: noises.cloned().collect(),
+        }
+    }
+
+    /// The background on `turn`, as `World::background`.
+    pub fn background(&self, turn: u64) -> [f32; 4] {
+        self.ambient.as_ref().map_or(self.background, |ambient| ambient.background(turn))
+    }
+
+    /// Where the camera looks on `turn`, as `World::camera_view`.
+    pub fn camera_view(&self, turn: u64) -> Option<CameraView> {
+        self.camera.view(turn, [self.width, self.height])
+    }
+}
+
+/// Everything the window draws and titles a frame with, handed over after
+/// the turns and commands of the simulation.
+#[derive(Default)]
+pub struct Frame {
+    pub world: WorldSnapshot,
+    pub scenery: Arc<Scenery>,
+    pub under_memory_pressure: bool,
+    pub particle_pool: Option<ParticlePoolStats>,
+
+    // The points of the survival curve, the median age and the deaths
+    pub survival: Option<(Vec<SurvivalPoint>, Option<u32>, u64)>,
+    pub tuned: Option<SliderValues>,
+    pub warming_up: bool,
+    pub captured_turns: u64,
+}
+
+impl Frame {
+    fn capture(&mut self, simulation: &mut Simulation) {
+        let world = &mut simulation.world;
+        self.world.capture(world);
+        self.scenery.clone_from(&simulation.scenery);
+        self.under_memory_pressure = world.under_memory_pressure();
+        self.particle_pool = world.particle_pool.as_ref().map(ParticlePool::stats);
+        self.survival = world.survival.as_ref().map(|curve| {
+            let ages = || world.particles.iter().map(|p| p.age);
+            (curve.points(ages()), curve.median(ages()), curve.deaths())
+        });
+        self.tuned = simulation.tuning.then(|| TuningPanel::values(world));
+        self.warming_up = simulation.warming_up;
+        self.captured_turns = simulation.captured_turns;
+    }
+}
+
+/// The world of the Piston window, with everything recorded of its turns,
+/// run on a thread of its own: the window sends it what the user does as
+/// commands and draws the frames it publishes, so turns keep to the turn
+/// rate however many events and frames there are.
+pub struct Simulation<'a> {
+    pub cli: &'a Cli,
+    pub world: World,
+    pub charts: Arc<Mutex<Charts>>,
+    pub population: PopulationHistory,
+    pub energy: EnergyHistory,
+    pub hashes: Option<HashStream>,
+    pub cohorts: Option<CohortHistory>,
+    pub recorder: Option<RecordingWriter>,
+    pub video: Option<VideoRecorder>,
+    pub mutations: Mutations,
+
+    // Ctrl+1..9 saves the world to a slot, 1..9 restores it
+    pub slots: SnapshotSlots<World>,
+
+    // What `--session` writes at exit, following the scenes picked meanwhile
+    pub scene_path: Option<PathBuf>,
+    pub scene_text: Option<String>,
+
+    // Paused with Space, held while the picker is open, and stepped by `.`
+    pub paused: bool,
+    pub holding: bool,
+    pub step: bool,
+
+    // Whether the frames carry the values of the tuning panel, open
+    pub tuning: bool,
+    captures: Option<FrameCapture>,
+    captured_turns: u64,
+    warming_up: bool,
+    warmup_start: Instant,
+    warmup_start_turn: u64,
+    cursor: Option<(Vec2d<f64>, bool)>,
+    stall_guard: Arc<Mutex<StallGuard>>,
+    notices: Sender<Notice>,
+    scenery: Arc<Scenery>,
+}
+
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(cli: &'a Cli, world: World, stall_guard: Arc<Mutex<StallGuard>>, notices: Sender<Notice>) -> Simulation<'a> {
+        let mut timeline = Timeline::new(cli.snapshot_every);
+        let mut population = PopulationHistory::new(world.total_spawned, world.total_died);
+        let mut energy = EnergyHistory::new();
+        let mut hashes = hash_stream(cli);
+        preallocate_histories(cli, &mut timeline, &mut population, &mut energy, hashes.as_mut());
+        Simulation {
+            cli,
+            charts: Arc::new(Mutex::new(Charts { timeline, correlation: CorrelationChart::new() })),
+            population,
+            energy,
+            hashes,
+            cohorts: cohort_history(cli),
+            recorder: start_recording(cli, &world),
+            video: start_video(cli, &world),
+            mutations: Mutations::new(),
+            slots: SnapshotSlots::new(),
+            scene_path: None,
+            scene_text: None,
+            paused: false,
+            holding: false,
+            step: false,
+            tuning: false,
+            captures: frame_capture(cli),
+            captured_turns: 0,
+            warming_up: cli.warmup.is_some(),
+            warmup_start: Instant::now(),
+            warmup_start_turn: world.current_turn,
+            cursor: None,
+            stall_guard,
+            notices,
+            scenery: Arc::new(Scenery::of(&world)),
+            world,
+        }
+    }
+
+    pub fn tell(&self, notice: Notice) {
+        let _ = self.notices.send(notice);
+    }
+
+    /// Tells the window `status`, for its title.
+    pub fn notify(&self, status: String) {
+        self.tell(Notice { status, latest: false });
+    }
+
+    /// Runs the world and its histories from `world` on, the journal of the
+    /// one before ending.
+    pub fn replace_world(&mut self, world: World) {
+        finish_journal(self.cli, &self.world);
+        self.world = world;
+        self.world.time_step = BASE_TURN_RATE / self.cli.turn_rate as f64;
+        self.world.survival.get_or_insert_with(SurvivalCurve::new);
+        self.start_over();
+        self.mutations.clear();
+    }
+
+    // Starts every history over from the turn the world is on
+    fn start_over(&mut self) {
+        *lock(&self.charts) = Charts { timeline: Timeline::new(self.cli.snapshot_every), correlation: CorrelationChart::new() };
+        self.population = PopulationHistory::new(self.world.total_spawned, self.world.total_died);
+        self.energy = EnergyHistory::new();
+        self.hashes = self.hashes.take().map(|_| HashStream::new());
+        self.cohorts = cohort_history(self.cli);
+    }
+
+    /// Runs turns at the turn rate and the commands in between, publishing
+    /// a frame after either, until told to stop or the window is gone, and
+    /// gives the simulation back for what is written at exit.
+    pub fn run(mut self, mut frames: SnapshotWriter<Frame>, commands: Receiver<Command<'a>>) -> Simulation<'a> {
+        set_thread_role(ThreadRole::Simulation);
+        let rate = self.cli.turn_rate as f64;
+        let mut clock = TurnClock::new(rate);
+        let turn = Duration::from_secs_f64(1.0 / rate);
+        frames.publish_with(|frame| frame.capture(&mut self));
+        loop {
+            self.end_warmup();
+            clock.tick(Instant::now(), !self.paused && !self.holding);
+            if self.run_turns(&mut clock) > 0 {
+                frames.publish_with(|frame| frame.capture(&mut self));
+            }
+
+            // Waits out the next turn, unless the window has something to say
+            match commands.recv_timeout(turn) {
+                Ok(Command::Cursor { position, inside }) => {
+                    self.cursor = Some((position, inside));
+                    self.point_cursor();
+                }
+                Ok(Command::Run(command)) => {
+                    command(&mut self);
+                    self.point_cursor();
+                    self.scenery = Arc::new(Scenery::of(&self.world));
+                }
+                Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => return self,
+                Err(RecvTimeoutError::Timeout) => continue,
+            }
+            frames.publish_with(|frame| frame.capture(&mut self));
+        }
+    }
+
+    // The cursor of the world where the window last pointed it, also on a
+    // world a command just loaded
+    fn point_cursor(&mut self) {
+        let Some((position, inside)) = self.cursor else {
+            return;
+        };
+        self.world.cursor_obstacle = self
+            .cli
+            .cursor_radius
+            .filter(|_| inside)
+            .map(|radius| Obstacle::Circle { center: position, radius });
+        if let Some(Force::Attractor { center, .. }) = &mut self.world.cursor_force {
+            *center = position;
+        }
+    }
+
+    fn warmup_is_over(&self) -> bool {
+        let turns = self.world.current_turn - self.warmup_start_turn;
+        self.cli.warmup.is_some_and(|warmup| warmup.is_over(turns, self.warmup_start.elapsed()))
+    }
+
+    // Starts every statistic from the state the warm-up left behind
+    fn end_warmup(&mut self) {
+        if !self.warming_up || !self.warmup_is_over() {
+            return;
+        }
+        self.warming_up = false;
+        set_capturing(true);
+        reuse::reset();
+        pprof::reset();
+        self.start_over();
+        self.world.survival = Some(SurvivalCurve::new());
+        let mut charts = lock(&self.charts);
+        preallocate_histories(self.cli, &mut charts.timeline, &mut self.population, &mut self.energy, self.hashes.as_mut());
+    }
+
+    /// Whether the turns of `--turns` have all run.
+    pub fn done(&self) -> bool {
+        self.cli.turns.is_some_and(|turns| self.captured_turns >= turns)
+    }
+
+    // Runs the turns due, recording each, and returns how many ran
+    fn run_turns(&mut self, clock: &mut TurnClock) -> u64 {
+        // Logs the allocations of the turns, kept with the timeline sample of
+        // the last of them
+        let recording = !self.paused && !self.warming_up && !self.holding;
+        if recording {
+            start_allocation_log();
+        }
+        let mut turns_run = 0;
+        while !self.done() && (clock.next_turn() || mem::take(&mut self.step)) {
+            let world = &mut self.world;
+            world.spawn_throttle = lock(&self.stall_guard).throttle(Instant::now());
+            world.update();
+            turns_run += 1;
+            if self.warming_up {
+                // The statistics start over between two turns, before the next ones
+                if self.warmup_is_over() {
+                    break;
+                }
+                continue;
+            }
+            let mut charts = lock(&self.charts);
+            charts.timeline.record(world.current_turn, world.particles.iter().map(|p| &**p));
+            self.population.record(world.current_turn, world.total_spawned, world.total_died, world.particles.len());
+            if let Some(cohorts) = &mut self.cohorts {
+                cohorts.record(world.current_turn - 1, world.particles.iter().map(|p| p.born));
+            }
+            if self.cli.energy_csv.is_some() {
+                self.energy.record(world.current_turn, world.particles.len(), world.energy());
+            }
+            if let Some(hashes) = &mut self.hashes {
+                hashes.record(world.current_turn, world.state_hash());
+            }
+            charts.correlation.push(world.particles.len(), memory_stats().live_bytes);
+            drop(charts);
+            self.captured_turns += 1;
+            if let Some(captures) = self.captures.as_mut().filter(|captures| captures.is_due(self.captured_turns)) {
+                save_frame(captures, self.captured_turns, world);
+            }
+            record_turn(&mut self.recorder, world);
+            record_video(&mut self.video, world);
+        }
+        if recording {
+            let log = finish_allocation_log();
+            if turns_run > 0 {
+                lock(&self.charts).timeline.attach_allocations(log);
+            }
+        }
+        if self.paused && turns_run > 0 {
+            self.tell(Notice { status: format!("stepped to turn {}", self.world.current_turn), latest: true });
+        }
+
+        // The turns after a replay are the world's own again
+        if self.world.replay.as_ref().is_some_and(|replay| replay.progress().0 == replay.progress().1) {
+            self.world.replay = None;
+            self.notify(format!("replay over at turn {}", self.world.current_turn));
+        }
+        turns_run
+    }
+}
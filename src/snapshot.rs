@@ -0,0 +1,31 @@
+/// Number of quick-switch slots, one per digit key
+pub const SLOT_COUNT: usize = 9;
+
+/// Named copies of the world kept in numbered slots so different states can
+/// be compared back and forth during a session.
+pub struct SnapshotSlots<T> {
+    slots: [Option<(String, T)>; SLOT_COUNT],
+}
+
+impl<T: Clone> SnapshotSlots<T> {
+    pub fn new() -> SnapshotSlots<T> {
+        SnapshotSlots {
+            slots: Default::default(),
+        }
+    }
+
+    /// Stores a copy of `state` in slot `index` (0-based), replacing what was there.
+    pub fn save(&mut self, index: usize, name: String, state: &T) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            *slot = Some((name, state.clone()));
+        }
+    }
+
+    /// Name and state kept in slot `index`, if it was saved.
+    pub fn get(&self, index: usize) -> Option<(&str, &T)> {
+        self.slots
+            .get(index)?
+            .as_ref()
+            .map(|(name, state)| (name.as_str(), state))
+    }
+}
@@ -0,0 +1,109 @@
+use crate::params::{ParamVisitor, Tunable};
+use graphics::math::Vec2d;
+use serde::Deserialize;
+
+/// Particles bouncing off each other, as discs as wide as their squares.
+/// `restitution` is the share of their speed towards each other that two
+/// particles keep after they touch, away from each other: 1 bounces them
+/// without losing any, 0 leaves them sliding along each other. Pinned
+/// particles don't move, and the others bounce off them as off a wall.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Collisions {
+    #[serde(default = "default_restitution")]
+    pub restitution: f64,
+}
+
+fn default_restitution() -> f64 {
+    0.8
+}
+
+impl Default for Collisions {
+    fn default() -> Collisions {
+        Collisions { restitution: default_restitution() }
+    }
+}
+
+impl Tunable for Collisions {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        visitor.float("restitution", &mut self.restitution, 0.0..=1.0);
+    }
+}
+
+/// Uniform grid over a set of points, which the neighbors of behaviors are
+/// found in: every point goes in the cell of `cell` pixels it is in, and
+/// cells are hashed into a table twice as long as there are points, so the
+/// grid covers any area, even points far out of the window, at the same cost.
+///
+/// Like `QuadTree`, it doesn't follow the points as they move, but it
+/// keeps its buffers from one build to the next: rebuilding it every turn
+/// allocates nothing once they have grown to the population.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialHash {
+    cell: f64,
+    mask: usize,
+
+    // Cell of every point, by index
+    cells: Vec<(i32, i32)>,
+
+    // The points of bucket `b` are `entries[starts[b]..starts[b + 1]]`
+    starts: Vec<usize>,
+    entries: Vec<u32>,
+    cursors: Vec<usize>,
+}
+
+impl SpatialHash {
+    pub fn new() -> SpatialHash {
+        SpatialHash::default()
+    }
+
+    /// Puts `points` in cells of `cell` pixels, replacing what was there.
+    pub fn build<I: IntoIterator<Item = Vec2d<f64>>>(&mut self, cell: f64, points: I) {
+        self.cell = cell;
+        self.cells.clear();
+        self.cells.extend(points.into_iter().map(|[x, y]| ((x / cell).floor() as i32, (y / cell).floor() as i32)));
+
+        let buckets = (self.cells.len() * 2).next_power_of_two();
+        self.mask = buckets - 1;
+        self.starts.clear();
+        self.starts.resize(buckets + 1, 0);
+        for &cell in &self.cells {
+            self.starts[bucket(cell, self.mask) + 1] += 1;
+        }
+        for b in 0..buckets {
+            self.starts[b + 1] += self.starts[b];
+        }
+
+        self.cursors.clear();
+        self.cursors.extend_from_slice(&self.starts[..buckets]);
+        self.entries.clear();
+        self.entries.resize(self.cells.len(), 0);
+        for (index, &cell) in self.cells.iter().enumerate() {
+            let bucket = bucket(cell, self.mask);
+            self.entries[self.cursors[bucket]] = index as u32;
+            self.cursors[bucket] += 1;
+        }
+    }
+
+    /// The points in the cell of `point` and the cells around it, which
+    /// include every point closer than a cell to it.
+    pub fn near(&self, [x, y]: Vec2d<f64>) -> impl Iterator<Item = u32> + '_ {
+        let (x, y) = ((x / self.cell).floor() as i32, (y / self.cell).floor() as i32);
+        let empty = self.cells.is_empty();
+        [-1, 0, 1]
+            .into_iter()
+            .flat_map(move |dx| [-1, 0, 1].map(|dy| (x.wrapping_add(dx), y.wrapping_add(dy))))
+            .filter(move |_| !empty)
+            .flat_map(move |cell| {
+                let bucket = bucket(cell, self.mask);
+                self.entries[self.starts[bucket]..self.starts[bucket + 1]]
+                    .iter()
+                    .copied()
+                    .filter(move |&j| self.cells[j as usize] == cell)
+            })
+    }
+}
+
+fn bucket((x, y): (i32, i32), mask: usize) -> usize {
+    ((x as u32).wrapping_mul(0x9e37_79b1) ^ (y as u32).wrapping_mul(0x85eb_ca77)) as usize & mask
+}
@@ -0,0 +1,83 @@
+use memory_particles::allocator::{memory_stats, MemoryStats};
+use std::time::{Duration, Instant};
+
+/// Allocations made during one phase of the startup.
+#[derive(Debug, Clone)]
+pub struct StartupPhase {
+    pub name: &'static str,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+
+    /// Live heap at the end of the phase
+    pub live_bytes: usize,
+
+    /// `None` for what ran before `main`, which can't be timed
+    pub time: Option<Duration>,
+}
+
+/// Splits the allocations made from process start to the first rendered
+/// frame into phases, each one ending where `mark` is called.
+pub struct StartupProfile {
+    phases: Vec<StartupPhase>,
+    last: MemoryStats,
+    last_time: Instant,
+}
+
+impl StartupProfile {
+    /// Starts profiling, with what the runtime allocated before `main` as the
+    /// first phase. Call it first thing in `main`.
+    pub fn new() -> StartupProfile {
+        let stats = memory_stats();
+        let phase = StartupPhase {
+            name: "runtime init (before main)",
+            allocations: stats.allocations,
+            allocated_bytes: stats.allocated_bytes,
+            live_bytes: stats.live_bytes,
+            time: None,
+        };
+        StartupProfile { phases: vec![phase], last: memory_stats(), last_time: Instant::now() }
+    }
+
+    /// Ends the current phase, naming it.
+    pub fn mark(&mut self, name: &'static str) {
+        let stats = memory_stats();
+        let now = Instant::now();
+        self.phases.push(StartupPhase {
+            name,
+            allocations: stats.allocations - self.last.allocations,
+            allocated_bytes: stats.allocated_bytes - self.last.allocated_bytes,
+            live_bytes: stats.live_bytes,
+            time: Some(now - self.last_time),
+        });
+
+        // Taken again so the profile's own bookkeeping counts in no phase
+        self.last = memory_stats();
+        self.last_time = Instant::now();
+    }
+
+    /// Prints the phases as a table on stdout, with their totals.
+    pub fn print(&self) {
+        println!("startup memory profile");
+        println!("{:<36}{:>12}{:>16}{:>16}{:>12}", "phase", "allocations", "bytes", "live bytes", "time");
+        for phase in &self.phases {
+            let time = phase.time.map_or("-".to_string(), |time| format!("{:.1} ms", time.as_secs_f64() * 1e3));
+            println!(
+                "{:<36}{:>12}{:>16}{:>16}{:>12}",
+                phase.name, phase.allocations, phase.allocated_bytes, phase.live_bytes, time
+            );
+        }
+
+        let allocations: u64 = self.phases.iter().map(|phase| phase.allocations).sum();
+        let bytes: u64 = self.phases.iter().map(|phase| phase.allocated_bytes).sum();
+        let time: Duration = self.phases.iter().filter_map(|phase| phase.time).sum();
+        let live = self.phases.last().map_or(0, |phase| phase.live_bytes);
+        println!(
+            "{:<36}{:>12}{:>16}{:>16}{:>12}",
+            "total",
+            allocations,
+            bytes,
+            live,
+            format!("{:.1} ms", time.as_secs_f64() * 1e3)
+        );
+    }
+}
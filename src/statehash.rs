@@ -0,0 +1,133 @@
+use crate::metadata::RunMetadata;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a over the exact bits of what it is fed: cheap, and unlike the
+/// standard library's hashers the same on every platform, build and run.
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> StateHasher {
+        StateHasher { hash: FNV_OFFSET }
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> StateHasher {
+        StateHasher::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash = (self.hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Hash of the world after every turn of a run. Two runs with the same seed
+/// and scene give the same stream, so a change to the storage of particles
+/// or to the parallel update that leaves it alone changed nothing they do.
+#[derive(Default)]
+pub struct HashStream {
+    pub hashes: Vec<(u64, u64)>,
+
+    /// `key: value` pairs of the run metadata header, when loaded from a file
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Where two hash streams stop agreeing.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The hashes after this turn differ
+    Turn(u64),
+
+    /// They agree on every turn both have, but one has turns the other hasn't
+    Length { a: usize, b: usize },
+}
+
+impl HashStream {
+    pub fn new() -> HashStream {
+        HashStream::default()
+    }
+
+    /// Makes room for `turns` more turns, so recording them allocates nothing.
+    pub fn reserve(&mut self, turns: usize) {
+        self.hashes.reserve(turns);
+    }
+
+    pub fn record(&mut self, turn: u64, hash: u64) {
+        self.hashes.push((turn, hash));
+    }
+
+    /// One `turn hash` line per turn, the hash in hexadecimal, after the run
+    /// metadata as comments.
+    pub fn write(&self, path: &Path, metadata: &RunMetadata) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}", metadata.comment_header())?;
+        for (turn, hash) in &self.hashes {
+            writeln!(out, "{} {:016x}", turn, hash)?;
+        }
+        out.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<HashStream> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |number: usize| io::Error::new(io::ErrorKind::InvalidData, format!("line {} is not `turn hash`", number));
+        let mut stream = HashStream::new();
+        for (number, line) in text.lines().enumerate() {
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some((key, value)) = comment.split_once(':') {
+                    stream.metadata.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (turn, hash) = line.split_once(' ').ok_or_else(|| invalid(number + 1))?;
+            let turn = turn.parse().map_err(|_| invalid(number + 1))?;
+            let hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| invalid(number + 1))?;
+            stream.record(turn, hash);
+        }
+        Ok(stream)
+    }
+
+    /// The first turn the streams disagree on, `None` when they are the same.
+    pub fn first_difference(&self, other: &HashStream) -> Option<Divergence> {
+        let differing = self.hashes.iter().zip(&other.hashes).find(|(a, b)| a != b);
+        match differing {
+            Some(((turn, _), _)) => Some(Divergence::Turn(*turn)),
+            None if self.hashes.len() != other.hashes.len() => {
+                Some(Divergence::Length { a: self.hashes.len(), b: other.hashes.len() })
+            }
+            None => None,
+        }
+    }
+
+    /// Value of a metadata key, when loaded from a file that has it.
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+    }
+}
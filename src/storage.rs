@@ -0,0 +1,213 @@
+use crate::allocator::memory_stats;
+use crate::behavior::{StateMachine, WorldView};
+use crate::config::ParticleSettings;
+use crate::force::ForceField;
+use crate::integrator::Integrator;
+use crate::mmap::{page_faults, HugePages, MmapArray};
+use crate::shutdown;
+use crate::store::{BoxedStore, ParticleStore, SoaStore};
+use crate::Particle;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where the storage experiment keeps its particles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// A `Vec` of boxed particles, one heap allocation each, like the world
+    Boxed,
+
+    /// One `Vec` per field of the particles, structure of arrays, each
+    /// gone through in a pass of its own
+    Soa,
+
+    /// A `Vec` of shared particles, `Rc<RefCell<Particle>>`: one heap
+    /// allocation each with reference counts, borrowed on every update
+    Rc,
+
+    /// A `Vec` of thread-safe shared particles, `Arc<Mutex<Particle>>`:
+    /// atomic reference counts, locked on every update
+    Arc,
+
+    /// A `Vec` of particles in a single heap allocation
+    Malloc,
+
+    /// A single anonymous `mmap` region, out of the allocator's sight
+    Mmap,
+
+    /// An `mmap` region backed by huge pages where the system allows
+    MmapHuge,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Boxed => "boxed",
+            Backend::Soa => "soa",
+            Backend::Rc => "rc",
+            Backend::Arc => "arc",
+            Backend::Malloc => "malloc",
+            Backend::Mmap => "mmap",
+            Backend::MmapHuge => "mmap huge",
+        }
+    }
+}
+
+/// Results of running the experiment on one backend.
+pub struct StorageReport {
+    pub backend: Backend,
+    pub huge_pages: Option<HugePages>,
+
+    /// Size of the mmap'd region, zero for heap backends
+    pub mapped_bytes: usize,
+    pub particles: usize,
+    pub turns: u64,
+    pub elapsed: Duration,
+
+    /// Part of `elapsed` spent moving the particles, without filling the backend
+    pub update_time: Duration,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+/// Fills a backend with `particles` particles and moves them for `turns`
+/// turns, respawning the ones that faded out in place so the population
+/// stays the same. Every backend sees the same particles for a given seed.
+pub fn run(backend: Backend, particles: usize, turns: u64, seed: u64) -> std::io::Result<StorageReport> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let before = memory_stats();
+    let (minor_before, major_before) = page_faults();
+    let start = Instant::now();
+
+    let mut store: Option<Box<dyn ParticleStore>> = None;
+    let mut shared: Vec<Rc<RefCell<Particle>>> = Vec::new();
+    let mut synced: Vec<Arc<Mutex<Particle>>> = Vec::new();
+    let mut inline: Vec<Particle> = Vec::new();
+    let mut mapped: Option<MmapArray<Particle>> = None;
+    match backend {
+        Backend::Boxed | Backend::Soa => {
+            let mut filled: Box<dyn ParticleStore> = if backend == Backend::Soa {
+                Box::new(SoaStore::with_capacity(particles))
+            } else {
+                Box::new(BoxedStore::with_capacity(particles))
+            };
+            for _ in 0..particles {
+                filled.push(spawn(&mut rng));
+            }
+            store = Some(filled);
+        }
+        Backend::Rc => shared = (0..particles).map(|_| Rc::new(RefCell::new(spawn(&mut rng)))).collect(),
+        Backend::Arc => synced = (0..particles).map(|_| Arc::new(Mutex::new(spawn(&mut rng)))).collect(),
+        Backend::Malloc => inline = (0..particles).map(|_| spawn(&mut rng)).collect(),
+        Backend::Mmap | Backend::MmapHuge => {
+            let mut array = MmapArray::with_capacity(particles, backend == Backend::MmapHuge)?;
+            for _ in 0..particles {
+                // Can't fail, the array was mapped for exactly this many
+                let _ = array.push(spawn(&mut rng));
+            }
+            mapped = Some(array);
+        }
+    }
+
+    let behavior = StateMachine::default();
+    let view = WorldView::new(0.0, 0.0, 0);
+    let update_start = Instant::now();
+    let mut done = 0;
+    while done < turns && !shutdown::requested() {
+        // Stores move their particles themselves, the way `step` does without forces
+        if let Some(store) = &mut store {
+            store.step(1.0, &mut || spawn(&mut rng));
+            done += 1;
+            continue;
+        }
+        let mut step = |particle: &mut Particle| {
+            particle.step(&[], ForceField::NONE, Integrator::SemiImplicit, 0.0, 0.0, &behavior, &[], &view, 1.0, false, &mut rng);
+            if particle.color[3] < crate::MIN_VISIBLE_ALPHA {
+                *particle = spawn(&mut rng);
+            }
+        };
+        match backend {
+            Backend::Boxed | Backend::Soa => {}
+            Backend::Rc => shared.iter().for_each(|p| step(&mut p.borrow_mut())),
+            // Nothing panics while holding a particle, so no lock is ever poisoned
+            Backend::Arc => synced.iter().for_each(|p| step(&mut p.lock().unwrap_or_else(|err| err.into_inner()))),
+            Backend::Malloc => inline.iter_mut().for_each(step),
+            Backend::Mmap | Backend::MmapHuge => mapped.iter_mut().flat_map(|a| a.as_mut_slice()).for_each(step),
+        }
+        done += 1;
+    }
+    let update_time = update_start.elapsed();
+
+    let huge_pages = mapped.as_ref().map(|array| array.huge_pages());
+    let mapped_bytes = mapped.as_ref().map_or(0, |array| array.mapped_bytes());
+    drop((store, shared, synced, inline, mapped));
+
+    let elapsed = start.elapsed();
+    let after = memory_stats();
+    let (minor_after, major_after) = page_faults();
+    Ok(StorageReport {
+        backend,
+        huge_pages,
+        mapped_bytes,
+        particles,
+        turns: done,
+        elapsed,
+        update_time,
+        allocations: after.allocations - before.allocations,
+        allocated_bytes: after.allocated_bytes - before.allocated_bytes,
+        minor_faults: minor_after - minor_before,
+        major_faults: major_after - major_before,
+    })
+}
+
+// A particle somewhere in a 1280x960 window, rising like the default scene
+fn spawn(rng: &mut StdRng) -> Particle {
+    let position = [rng.gen_range(0.0..1280.0), rng.gen_range(0.0..960.0)];
+    Particle::new(position, [0.0, -1.0], [1.0, 1.0, 1.0, 0.99], &ParticleSettings::default(), rng)
+}
+
+impl StorageReport {
+    /// Particles moved per second, in millions.
+    pub fn million_updates_per_second(&self) -> f64 {
+        let seconds = self.update_time.as_secs_f64();
+        if seconds > 0.0 {
+            (self.particles as u64 * self.turns) as f64 / seconds / 1e6
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Prints the reports side by side, one row per backend.
+pub fn print_reports(reports: &[StorageReport]) {
+    println!(
+        "{:<12}{:>8}{:>12}{:>12}{:>14}{:>16}{:>14}{:>14}{:>14}  huge pages",
+        "backend", "turns", "elapsed", "M updates/s", "allocations", "allocated", "mapped", "minor faults", "major faults"
+    );
+    for report in reports {
+        let huge_pages = match report.huge_pages {
+            None => "-",
+            Some(HugePages::Off) => "off",
+            Some(HugePages::Reserved) => "reserved",
+            Some(HugePages::Transparent) => "transparent",
+        };
+        println!(
+            "{:<12}{:>8}{:>10.3} s{:>12.1}{:>14}{:>16}{:>14}{:>14}{:>14}  {}",
+            report.backend.name(),
+            report.turns,
+            report.elapsed.as_secs_f64(),
+            report.million_updates_per_second(),
+            report.allocations,
+            report.allocated_bytes,
+            report.mapped_bytes,
+            report.minor_faults,
+            report.major_faults,
+            huge_pages
+        );
+    }
+}
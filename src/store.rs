@@ -0,0 +1,177 @@
+use crate::fade::Fade;
+use crate::{Particle, ACCELERATION_DECAY, MIN_VISIBLE_ALPHA};
+use graphics::math::Vec2d;
+
+/// A population of particles kept in some memory layout, for the storage
+/// experiment to compare layouts moving the very same particles. Stores
+/// move their particles like the world does without any force, and give
+/// them back whole, whatever they keep of them.
+pub trait ParticleStore {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, particle: Particle);
+
+    /// The particle at `index`, as it would be in the world.
+    fn get(&self, index: usize) -> Particle;
+
+    /// Moves every particle one turn of `dt`, then replaces the ones faded
+    /// out by particles of `respawn`, in order, so the population stays the
+    /// same.
+    fn step(&mut self, dt: f64, respawn: &mut dyn FnMut() -> Particle);
+}
+
+/// Array of structures, every particle in a box of its own like in the world.
+#[derive(Default)]
+pub struct BoxedStore {
+    #[allow(clippy::vec_box)]
+    particles: Vec<Box<Particle>>,
+}
+
+impl BoxedStore {
+    pub fn with_capacity(capacity: usize) -> BoxedStore {
+        BoxedStore { particles: Vec::with_capacity(capacity) }
+    }
+}
+
+impl ParticleStore for BoxedStore {
+    fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn push(&mut self, particle: Particle) {
+        self.particles.push(Box::new(particle));
+    }
+
+    fn get(&self, index: usize) -> Particle {
+        (*self.particles[index]).clone()
+    }
+
+    fn step(&mut self, dt: f64, respawn: &mut dyn FnMut() -> Particle) {
+        for particle in &mut self.particles {
+            particle.update(dt);
+            if particle.color[3] < MIN_VISIBLE_ALPHA {
+                **particle = respawn();
+            }
+        }
+    }
+}
+
+/// Structure of arrays: one array per field the turns read or write, each
+/// gone through in a pass of its own, so a pass only brings the field it
+/// needs into the cache. What the turns never touch, like the size or the
+/// user data, stays in a particle of its own per index, whose moving
+/// fields are stale.
+#[derive(Default)]
+pub struct SoaStore {
+    positions: Vec<Vec2d<f64>>,
+    velocities: Vec<Vec2d<f64>>,
+    accelerations: Vec<Vec2d<f64>>,
+    max_speeds: Vec<f64>,
+    colors: Vec<[f32; 4]>,
+    fades: Vec<Fade>,
+    ages: Vec<u32>,
+    pinned: Vec<bool>,
+    rest: Vec<Particle>,
+}
+
+impl SoaStore {
+    pub fn with_capacity(capacity: usize) -> SoaStore {
+        SoaStore {
+            positions: Vec::with_capacity(capacity),
+            velocities: Vec::with_capacity(capacity),
+            accelerations: Vec::with_capacity(capacity),
+            max_speeds: Vec::with_capacity(capacity),
+            colors: Vec::with_capacity(capacity),
+            fades: Vec::with_capacity(capacity),
+            ages: Vec::with_capacity(capacity),
+            pinned: Vec::with_capacity(capacity),
+            rest: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn set(&mut self, index: usize, particle: Particle) {
+        self.positions[index] = particle.position;
+        self.velocities[index] = particle.velocity;
+        self.accelerations[index] = particle.acceleration;
+        self.max_speeds[index] = particle.max_speed;
+        self.colors[index] = particle.color;
+        self.fades[index] = particle.fade;
+        self.ages[index] = particle.age;
+        self.pinned[index] = particle.pinned;
+        self.rest[index] = particle;
+    }
+}
+
+impl ParticleStore for SoaStore {
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn push(&mut self, particle: Particle) {
+        self.positions.push(particle.position);
+        self.velocities.push(particle.velocity);
+        self.accelerations.push(particle.acceleration);
+        self.max_speeds.push(particle.max_speed);
+        self.colors.push(particle.color);
+        self.fades.push(particle.fade);
+        self.ages.push(particle.age);
+        self.pinned.push(particle.pinned);
+        self.rest.push(particle);
+    }
+
+    fn get(&self, index: usize) -> Particle {
+        Particle {
+            position: self.positions[index],
+            velocity: self.velocities[index],
+            acceleration: self.accelerations[index],
+            max_speed: self.max_speeds[index],
+            color: self.colors[index],
+            fade: self.fades[index],
+            age: self.ages[index],
+            pinned: self.pinned[index],
+            ..self.rest[index].clone()
+        }
+    }
+
+    // The same arithmetic as `Particle::update`, in the same order, so both
+    // stores end up with the same particles to the bit
+    fn step(&mut self, dt: f64, respawn: &mut dyn FnMut() -> Particle) {
+        let moving = self.velocities.iter_mut().zip(&self.accelerations).zip(&self.max_speeds).zip(&self.pinned);
+        for (((velocity, &acceleration), &max_speed), &pinned) in moving {
+            if !pinned {
+                *velocity = [velocity[0] + acceleration[0] * dt, velocity[1] + acceleration[1] * dt];
+                let speed = velocity[0].hypot(velocity[1]);
+                if speed > max_speed {
+                    *velocity = [velocity[0] * (max_speed / speed), velocity[1] * (max_speed / speed)];
+                }
+            }
+        }
+        for ((position, velocity), &pinned) in self.positions.iter_mut().zip(&self.velocities).zip(&self.pinned) {
+            if !pinned {
+                *position = [position[0] + velocity[0] * dt, position[1] + velocity[1] * dt];
+            }
+        }
+        let decay = ACCELERATION_DECAY.powf(dt);
+        for (acceleration, &pinned) in self.accelerations.iter_mut().zip(&self.pinned) {
+            if !pinned {
+                *acceleration = [acceleration[0] * decay, acceleration[1] * decay];
+            }
+        }
+        for (fade, color) in self.fades.iter_mut().zip(&mut self.colors) {
+            fade.apply(&mut color[3]);
+        }
+        for age in &mut self.ages {
+            *age = age.saturating_add(1);
+        }
+
+        for index in 0..self.len() {
+            if self.colors[index][3] < MIN_VISIBLE_ALPHA {
+                self.set(index, respawn());
+            }
+        }
+    }
+}
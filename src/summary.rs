@@ -0,0 +1,80 @@
+use crate::allocator::memory_stats;
+use crate::callsite::{self, CallSites};
+use crate::histogram::AllocationHistogram;
+use crate::locale::bundle;
+use crate::shutdown;
+use std::time::Duration;
+
+/// The aggregates of a windowed run, counted frame by frame and printed
+/// when it ends, whether its window was closed or Ctrl+C was pressed, so
+/// they aren't lost with the process.
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    frames: u64,
+    frame_time: Duration,
+    slowest_frame: Duration,
+
+    // Most particles at the end of a frame, and the turn it came on
+    peak_particles: usize,
+    peak_turn: u64,
+}
+
+impl RunSummary {
+    pub fn new() -> RunSummary {
+        RunSummary::default()
+    }
+
+    /// Counts a frame that took `time`, after which the world had
+    /// `particles` particles and was on `turn`.
+    pub fn frame(&mut self, time: Duration, particles: usize, turn: u64) {
+        self.frames += 1;
+        self.frame_time += time;
+        self.slowest_frame = self.slowest_frame.max(time);
+        if particles > self.peak_particles {
+            (self.peak_particles, self.peak_turn) = (particles, turn);
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn peak_particles(&self) -> usize {
+        self.peak_particles
+    }
+
+    /// Mean time of the frames counted, none before the first.
+    pub fn mean_frame_time(&self) -> Option<Duration> {
+        (self.frames > 0).then(|| Duration::from_nanos((self.frame_time.as_nanos() / self.frames as u128) as u64))
+    }
+
+    /// The lines of the summary after `turns` turns, by name, as printed and
+    /// put in HTML reports, in the language set with `locale::set_language`.
+    pub fn rows(&self, turns: u64) -> Vec<(&'static str, String)> {
+        let text = bundle();
+        let status = if shutdown::requested() { text.interrupted } else { "" };
+        let mean = self.mean_frame_time().map_or("-".to_string(), |mean| format!("{:.2} ms", mean.as_secs_f64() * 1e3));
+        vec![
+            (text.turns, turns.to_string() + status),
+            (text.frames, self.frames.to_string()),
+            (text.peak_particles, format!("{} ({} {})", self.peak_particles, text.turn, self.peak_turn)),
+            (text.mean_frame_time, format!("{} ({} {:.2} ms)", mean, text.slowest, self.slowest_frame.as_secs_f64() * 1e3)),
+            (text.peak_live_bytes, memory_stats().peak_live_bytes.to_string()),
+        ]
+    }
+
+    /// Prints the summary after `turns` turns, then the histogram of the
+    /// sizes and times of every allocation of the process, and their call
+    /// sites with `--alloc-backtraces`.
+    pub fn print(&self, turns: u64) {
+        let rows = self.rows(turns);
+        let width = rows.iter().map(|(name, _)| name.chars().count() + 1).max().unwrap_or(0).max(20);
+        for (name, value) in rows {
+            println!("{:<width$}{}", name, value);
+        }
+        AllocationHistogram::now().print();
+        if callsite::is_enabled() {
+            CallSites::now().print();
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::metadata::RunMetadata;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Width of the age bins of a `SurvivalCurve`, in turns.
+pub const BIN_TURNS: u32 = 10;
+
+/// Ages the particles died at, counted as they die, from which the share of
+/// particles still alive at every age is worked out, Kaplan-Meier style: the
+/// particles alive when the curve is read count as having lived at least as
+/// long as they have so far, so long-lived ones aren't left out of a run
+/// they outlive.
+#[derive(Debug, Clone, Default)]
+pub struct SurvivalCurve {
+    // Deaths by bin of `BIN_TURNS` turns of age
+    deaths: Vec<u64>,
+    total: u64,
+}
+
+/// A point of the curve: of the particles that reached the bin starting at
+/// `age`, how many there were and how many died in it, and the share of
+/// all particles living past it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurvivalPoint {
+    pub age: u32,
+    pub at_risk: u64,
+    pub deaths: u64,
+    pub survival: f64,
+}
+
+impl SurvivalCurve {
+    pub fn new() -> SurvivalCurve {
+        SurvivalCurve::default()
+    }
+
+    /// Counts a particle dying at `age`.
+    pub fn record(&mut self, age: u32) {
+        let bin = (age / BIN_TURNS) as usize;
+        if self.deaths.len() <= bin {
+            self.deaths.resize(bin + 1, 0);
+        }
+        self.deaths[bin] += 1;
+        self.total += 1;
+    }
+
+    /// Deaths counted so far.
+    pub fn deaths(&self) -> u64 {
+        self.total
+    }
+
+    /// The curve, one point per bin up to the oldest particle, with the
+    /// particles still alive at the ages of `alive`.
+    pub fn points<I: IntoIterator<Item = u32>>(&self, alive: I) -> Vec<SurvivalPoint> {
+        let mut censored = vec![0u64; self.deaths.len()];
+        for age in alive {
+            let bin = (age / BIN_TURNS) as usize;
+            if censored.len() <= bin {
+                censored.resize(bin + 1, 0);
+            }
+            censored[bin] += 1;
+        }
+        let bins = censored.len();
+        let deaths = |bin: usize| self.deaths.get(bin).copied().unwrap_or(0);
+
+        let mut at_risk: u64 = (0..bins).map(|bin| deaths(bin) + censored[bin]).sum();
+        let mut survival = 1.0;
+        let mut points = Vec::with_capacity(bins);
+        for (bin, &censored) in censored.iter().enumerate() {
+            let died = deaths(bin);
+            if at_risk > 0 {
+                survival *= 1.0 - died as f64 / at_risk as f64;
+            }
+            points.push(SurvivalPoint { age: bin as u32 * BIN_TURNS, at_risk, deaths: died, survival });
+            at_risk -= died + censored;
+        }
+        points
+    }
+
+    /// The age by which half the particles died, if half of them did.
+    pub fn median<I: IntoIterator<Item = u32>>(&self, alive: I) -> Option<u32> {
+        self.points(alive).iter().find(|point| point.survival <= 0.5).map(|point| point.age + BIN_TURNS)
+    }
+
+    /// Writes the curve with the particles alive at the ages of `alive`.
+    pub fn write_csv<I: IntoIterator<Item = u32>>(&self, path: &Path, alive: I, metadata: &RunMetadata) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{}", metadata.comment_header())?;
+        writeln!(out, "age,at_risk,deaths,survival")?;
+        for point in self.points(alive) {
+            writeln!(out, "{},{},{},{:.6}", point.age, point.at_risk, point.deaths, point.survival)?;
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,39 @@
+use memory_particles::survival::{SurvivalPoint, BIN_TURNS};
+use piston_window::*;
+
+const CHART_WIDTH: f64 = 240.0;
+const CHART_HEIGHT: f64 = 80.0;
+
+const SURVIVAL_COLOR: [f32; 4] = [1.0, 0.8, 0.3, 0.9];
+const MEDIAN_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.25];
+
+/// L shows the share of particles living past every age, from the survival
+/// curve of the world, below the chart of `C`.
+pub fn draw<G: Graphics>(points: &[SurvivalPoint], ctx: Context, g: &mut G) {
+    let (left, top) = (10.0, 16.0 + CHART_HEIGHT + 10.0);
+    rectangle([0.0, 0.0, 0.0, 0.6], [left, top, CHART_WIDTH, CHART_HEIGHT], ctx.transform, g);
+
+    // Half the particles are dead where the curve crosses the line
+    let half = top + CHART_HEIGHT / 2.0;
+    line_from_to(MEDIAN_COLOR, 0.5, [left, half], [left + CHART_WIDTH, half], ctx.transform, g);
+
+    // Every bin spans the same width, the oldest one ending at the right side,
+    // and the curve steps down at its end to the share surviving it
+    let step = CHART_WIDTH / points.len().max(1) as f64;
+    let y = |survival: f64| top + CHART_HEIGHT - survival * CHART_HEIGHT;
+    let mut previous = 1.0;
+    for (i, point) in points.iter().enumerate() {
+        let (x0, x1) = (left + i as f64 * step, left + (i + 1) as f64 * step);
+        line_from_to(SURVIVAL_COLOR, 0.7, [x0, y(previous)], [x1, y(previous)], ctx.transform, g);
+        line_from_to(SURVIVAL_COLOR, 0.7, [x1, y(previous)], [x1, y(point.survival)], ctx.transform, g);
+        previous = point.survival;
+    }
+}
+
+/// What the chart can't draw without text, for the window title: the median
+/// lifetime, the deaths counted and the age the chart goes up to.
+pub fn describe(points: &[SurvivalPoint], median: Option<u32>, deaths: u64) -> String {
+    let median = median.map_or("-".to_string(), |age| format!("{} turns", age));
+    let oldest = points.last().map_or(0, |point| point.age + BIN_TURNS);
+    format!("median lifetime {} ({} deaths, ages up to {})", median, deaths, oldest)
+}
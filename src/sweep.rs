@@ -0,0 +1,157 @@
+use crate::arena;
+use crate::bench::{self, BenchReport};
+use crate::config::Config;
+use crate::freelist::AllocationStrategy;
+use crate::metadata::RunMetadata;
+use crate::shutdown;
+use crate::warmup::Warmup;
+use crate::World;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Contents of a sweep file, written in TOML: the benchmark is run once for
+/// every combination of the listed values.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sweep {
+    /// Scene every run starts from, the default scene when missing
+    pub scene: Option<PathBuf>,
+
+    /// Number of turns of every run, after the warm-up
+    #[serde(default = "default_turns")]
+    pub turns: u64,
+
+    /// Seed of every run, so runs only differ by their parameters
+    pub seed: Option<u64>,
+
+    /// Number of particles the world starts with
+    pub population: Vec<usize>,
+
+    /// Where the boxes of the particles come from, as with `--allocation-strategy`
+    #[serde(default = "default_storage")]
+    pub storage: Vec<AllocationStrategy>,
+
+    /// Overrides the `drag` of the scene
+    #[serde(default = "default_drag")]
+    pub drag: Vec<f64>,
+}
+
+fn default_turns() -> u64 {
+    2_000
+}
+
+fn default_storage() -> Vec<AllocationStrategy> {
+    vec![AllocationStrategy::Boxed]
+}
+
+fn default_drag() -> Vec<f64> {
+    vec![0.0]
+}
+
+/// Parameters of a single run of the sweep.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepPoint {
+    pub population: usize,
+    pub storage: AllocationStrategy,
+    pub drag: f64,
+}
+
+impl Sweep {
+    pub fn load(path: &Path) -> io::Result<Sweep> {
+        let text = fs::read_to_string(path)?;
+        let mut sweep: Sweep = toml::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        // The scene is found next to the sweep file, like includes in a build file
+        if let (Some(scene), Some(dir)) = (&sweep.scene, path.parent()) {
+            sweep.scene = Some(dir.join(scene));
+        }
+        Ok(sweep)
+    }
+
+    /// Every combination of the parameters, varying drag fastest and population slowest.
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = Vec::new();
+        for &population in &self.population {
+            for &storage in &self.storage {
+                for &drag in &self.drag {
+                    points.push(SweepPoint { population, storage, drag });
+                }
+            }
+        }
+        points
+    }
+}
+
+/// Runs the benchmark for every point of the sweep, printing progress on stderr.
+/// Stops after the current run on Ctrl+C, which is left out of the results.
+/// The arena serves the whole process, so it is only on for the points of
+/// the `arena` storage, and left as it was found afterwards.
+pub fn run(sweep: &Sweep, config: &Config, warmup: Option<Warmup>) -> Vec<(SweepPoint, BenchReport)> {
+    let points = sweep.points();
+    let total = points.len();
+    let mut results = Vec::with_capacity(total);
+    let arena_enabled = arena::is_enabled();
+
+    for (i, point) in points.into_iter().enumerate() {
+        let mut config = config.clone();
+        config.drag = point.drag;
+
+        let mut world = World::from_config(1280.0, 960.0, config);
+        if let Some(seed) = sweep.seed {
+            world.rng = StdRng::seed_from_u64(seed);
+        }
+        world.add_shapes(point.population as i32);
+        world.set_allocation_strategy(point.storage);
+
+        eprintln!(
+            "sweep {}/{}: population {}, storage {}, drag {}",
+            i + 1,
+            total,
+            point.population,
+            point.storage.name(),
+            point.drag
+        );
+        let report = bench::run(&mut world, sweep.turns, warmup, &mut |_, _, _| {});
+        if report.interrupted() || shutdown::requested() {
+            break;
+        }
+        results.push((point, report));
+    }
+    if arena_enabled {
+        arena::enable();
+    } else {
+        arena::disable();
+    }
+    results
+}
+
+/// Writes one row per run, after the header describing the sweep.
+pub fn write_csv(path: &Path, results: &[(SweepPoint, BenchReport)], metadata: &RunMetadata) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "{}", metadata.comment_header())?;
+    writeln!(
+        out,
+        "population,storage,drag,turns,elapsed_s,turns_per_second,allocations,allocated_bytes,peak_live_bytes,particles"
+    )?;
+    for (point, report) in results {
+        writeln!(
+            out,
+            "{},{},{},{},{:.6},{:.1},{},{},{},{}",
+            point.population,
+            point.storage.name(),
+            point.drag,
+            report.turns,
+            report.elapsed.as_secs_f64(),
+            report.turns_per_second().unwrap_or(0.0),
+            report.allocations,
+            report.allocated_bytes,
+            report.peak_live_bytes,
+            report.particles
+        )?;
+    }
+    out.flush()
+}
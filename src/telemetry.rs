@@ -0,0 +1,573 @@
+use crate::allocator::{self, AllocationKind, AllocationTag};
+use std::cell::Cell;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Mutex, MutexGuard, OnceLock, TryLockError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Allocations waiting to be written to the trace. Filled by the allocator,
+// which can't allocate, lock or write anything itself, so fixed-size and in
+// statics like the allocation log; allocations made while it is full are
+// dropped and counted
+const RING_CAPACITY: usize = 1 << 16;
+static RING_SIZES: [AtomicUsize; RING_CAPACITY] = [const { AtomicUsize::new(0) }; RING_CAPACITY];
+static RING_NANOS: [AtomicU64; RING_CAPACITY] = [const { AtomicU64::new(0) }; RING_CAPACITY];
+static RING_TAGS: [AtomicU8; RING_CAPACITY] = [const { AtomicU8::new(0) }; RING_CAPACITY];
+static RING_KINDS: [AtomicU8; RING_CAPACITY] = [const { AtomicU8::new(0) }; RING_CAPACITY];
+static RING_ALIGNS: [AtomicUsize; RING_CAPACITY] = [const { AtomicUsize::new(0) }; RING_CAPACITY];
+static RING_TIMES: [AtomicU64; RING_CAPACITY] = [const { AtomicU64::new(0) }; RING_CAPACITY];
+
+// Index plus one of the record last written to each slot, stored once the
+// record is whole, so the reader knows where the records still being
+// written by other threads start
+static RING_STAMPS: [AtomicUsize; RING_CAPACITY] = [const { AtomicUsize::new(0) }; RING_CAPACITY];
+
+// Records are claimed at `HEAD` by any thread, without locking, and read
+// from `TAIL` by whoever holds the sink, both counting up forever
+static HEAD: AtomicUsize = AtomicUsize::new(0);
+static TAIL: AtomicUsize = AtomicUsize::new(0);
+
+// Allocations are queued from the start, so the ones made before the
+// worker runs still reach the trace file. Without a worker, they are
+// written by the thread ending a frame
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static FINISHED: AtomicBool = AtomicBool::new(false);
+static STOPPING: AtomicBool = AtomicBool::new(false);
+static DROPPED_RECORDS: AtomicU64 = AtomicU64::new(0);
+static DROPPED_JOBS: AtomicU64 = AtomicU64::new(0);
+
+// Once writing to the sink failed, it is given up on and what would have
+// been written is counted instead, rather than failing again on every line
+static SINK_ERROR: OnceLock<String> = OnceLock::new();
+static LOST_RECORDS: AtomicU64 = AtomicU64::new(0);
+static DROPPED_LINES: AtomicU64 = AtomicU64::new(0);
+
+// Files waiting to be written, like frame captures
+const JOB_CAPACITY: usize = 16;
+type Job = Box<dyn FnOnce() + Send>;
+static JOBS: OnceLock<SyncSender<Job>> = OnceLock::new();
+static WORKER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+// Where the trace goes, shared with `comment` so run headers land in the
+// same file as the allocations
+static SINK: Mutex<Option<TraceSink>> = Mutex::new(None);
+static TO_FILE: AtomicBool = AtomicBool::new(false);
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+
+// How long the worker sleeps when there is nothing to write
+const IDLE: Duration = Duration::from_millis(2);
+
+/// How allocations are written to the trace. `trace diff` and `trace
+/// replay` read all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFormat {
+    /// `size<TAB>duration[<TAB>tag][<TAB>kind]`, as the allocator always
+    /// printed them, the kind left out of fresh allocations
+    Tsv,
+
+    /// `timestamp_ns,size,align,duration_ns,tag,kind` with a header row
+    Csv,
+
+    /// JSON Lines objects with `timestamp_ns`, `size`, `align`, `nanos`
+    /// and, when tagged, `tag`, and `kind` but for fresh allocations
+    Json,
+}
+
+impl TraceFormat {
+    fn to_u8(self) -> u8 {
+        match self {
+            TraceFormat::Tsv => 0,
+            TraceFormat::Csv => 1,
+            TraceFormat::Json => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> TraceFormat {
+        match value {
+            1 => TraceFormat::Csv,
+            2 => TraceFormat::Json,
+            _ => TraceFormat::Tsv,
+        }
+    }
+
+    // Writes one allocation as a line of this format
+    pub(crate) fn write(self, line: &mut String, record: &Record) -> std::fmt::Result {
+        let tag = record.tag.map(AllocationTag::label);
+        let kind = (record.kind != AllocationKind::Alloc).then(|| record.kind.label());
+        match self {
+            TraceFormat::Tsv => {
+                write!(line, "{}\t{}", record.size, record.nanos)?;
+                for label in [tag, kind].into_iter().flatten() {
+                    write!(line, "\t{}", label)?;
+                }
+                writeln!(line)
+            }
+            TraceFormat::Csv => writeln!(
+                line,
+                "{},{},{},{},{},{}",
+                record.timestamp,
+                record.size,
+                record.align,
+                record.nanos,
+                tag.unwrap_or(""),
+                record.kind.label()
+            ),
+            TraceFormat::Json => {
+                write!(
+                    line,
+                    "{{\"timestamp_ns\":{},\"size\":{},\"align\":{},\"nanos\":{}",
+                    record.timestamp, record.size, record.align, record.nanos
+                )?;
+                if let Some(tag) = tag {
+                    write!(line, ",\"tag\":\"{}\"", tag)?;
+                }
+                if let Some(kind) = kind {
+                    write!(line, ",\"kind\":\"{}\"", kind)?;
+                }
+                writeln!(line, "}}")
+            }
+        }
+    }
+}
+
+/// An allocation, or a free, as queued for the trace.
+pub struct Record {
+    pub size: usize,
+    pub align: usize,
+
+    /// Nanoseconds from the start of the allocator clock to the allocation
+    pub timestamp: u64,
+
+    /// How long the allocation took, in nanoseconds
+    pub nanos: u64,
+    pub tag: Option<AllocationTag>,
+    pub kind: AllocationKind,
+}
+
+/// Where the allocation trace is written.
+pub enum TraceSink {
+    Stderr(BufWriter<io::Stderr>),
+    File(BufWriter<File>),
+
+    /// Compressed with zstd, which `trace diff` and `trace replay` read
+    /// back as they are
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl TraceSink {
+    /// Creates the trace file at `path`, compressed at `level` when its name
+    /// ends in `.zst`.
+    pub fn create(path: &Path, level: i32) -> io::Result<TraceSink> {
+        let file = BufWriter::new(File::create(path)?);
+        if path.extension().is_some_and(|extension| extension == "zst") {
+            Ok(TraceSink::Zstd(zstd::Encoder::new(file, level)?))
+        } else {
+            Ok(TraceSink::File(file))
+        }
+    }
+
+    // Writes what is buffered, and the end of the frame of a compressed file
+    fn finish(self) -> io::Result<()> {
+        match self {
+            TraceSink::Stderr(mut out) => out.flush(),
+            TraceSink::File(mut out) => out.flush(),
+            TraceSink::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for TraceSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            TraceSink::Stderr(out) => out.write(buf),
+            TraceSink::File(out) => out.write(buf),
+            TraceSink::Zstd(out) => out.write(buf),
+        }
+    }
+
+    // Only pushes the lines to stderr: flushing an encoder would end a zstd
+    // block every few milliseconds, and files are flushed by `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            TraceSink::Stderr(out) => out.flush(),
+            TraceSink::File(_) | TraceSink::Zstd(_) => Ok(()),
+        }
+    }
+}
+
+thread_local! {
+    // Set on the worker, whose own allocations are left out of the trace
+    // so writing it never feeds it
+    static ON_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Starts the I/O worker, writing the trace to `sink` or to stderr in
+/// `format`. From then on the allocation trace and the files handed to
+/// `submit` are written by it, so the simulation and the window never wait
+/// on a slow terminal or disk; what doesn't fit in its queues is dropped
+/// and counted instead. Fails only when the worker could not be started for
+/// a file, which would otherwise get no trace at all.
+pub fn start(sink: Option<TraceSink>, format: TraceFormat) -> io::Result<()> {
+    let (sender, receiver) = mpsc::sync_channel(JOB_CAPACITY);
+    if JOBS.set(sender).is_err() {
+        return Ok(());
+    }
+    allocator::start_clock();
+    let to_file = sink.is_some();
+    let mut sink = sink.unwrap_or_else(|| TraceSink::Stderr(BufWriter::with_capacity(1 << 16, io::stderr())));
+    if format == TraceFormat::Csv {
+        sink.write_all(b"timestamp_ns,size,align,duration_ns,tag,kind\n")?;
+    }
+    FORMAT.store(format.to_u8(), Ordering::Release);
+    *lock_sink() = Some(sink);
+    TO_FILE.store(to_file, Ordering::Release);
+
+    let (ready, started) = mpsc::sync_channel(1);
+    let spawned = thread::Builder::new().name("telemetry".to_string()).spawn(move || run(receiver, ready));
+    match spawned {
+        Ok(handle) => {
+            let _ = started.recv();
+            *WORKER.lock().unwrap_or_else(|err| err.into_inner()) = Some(handle);
+            RUNNING.store(true, Ordering::Release);
+            Ok(())
+        }
+        Err(err) => {
+            flush();
+            if to_file {
+                return Err(err);
+            }
+            warn(format_args!("Could not start the telemetry writer, writing at the end of every frame: {}", err));
+            Ok(())
+        }
+    }
+}
+
+/// Whether the trace goes to a file rather than stderr.
+pub fn to_file() -> bool {
+    TO_FILE.load(Ordering::Acquire)
+}
+
+/// Writes `text`, like the header describing a run, to the trace. Counted
+/// as dropped once the trace failed.
+pub fn comment(text: &str) {
+    write_comment(lock_sink(), text);
+}
+
+/// Like `comment`, for lines written every frame: drops `text` rather than
+/// wait for the worker to be done writing, and returns whether it did not.
+pub fn comment_or_drop(text: &str) -> bool {
+    match SINK.try_lock() {
+        Ok(sink) => write_comment(sink, text),
+        Err(TryLockError::Poisoned(err)) => write_comment(err.into_inner(), text),
+        Err(TryLockError::WouldBlock) => {
+            DROPPED_LINES.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+fn write_comment(mut sink: MutexGuard<'static, Option<TraceSink>>, text: &str) -> bool {
+    // Without a worker, the allocations before the comment go first
+    if !is_running() {
+        drain_into(&mut sink, &mut String::new());
+    }
+    let written = match sink.as_mut() {
+        Some(out) => out.write_all(text.as_bytes()).map_err(|err| fail(&mut sink, &err)).is_ok(),
+        None if failed() => false,
+        None => io::stderr().write_all(text.as_bytes()).is_ok(),
+    };
+    if !written {
+        DROPPED_LINES.fetch_add(1, Ordering::Relaxed);
+    }
+    written
+}
+
+fn is_running() -> bool {
+    RUNNING.load(Ordering::Acquire)
+}
+
+/// Whether the current thread is the worker.
+pub fn on_worker() -> bool {
+    ON_WORKER.try_with(Cell::get).unwrap_or(false)
+}
+
+/// Queues an allocation for the trace, or counts it as dropped when the
+/// worker is behind. Never allocates, locks nor writes, so it is safe from
+/// the allocator of any thread, even one already in the middle of writing.
+pub fn record(record: Record) {
+    // Claims a slot: every one below `TAIL` has been read, so the slot of
+    // `head` is free as long as the ring isn't full
+    let mut head = HEAD.load(Ordering::Relaxed);
+    loop {
+        if head - TAIL.load(Ordering::Acquire) >= RING_CAPACITY {
+            DROPPED_RECORDS.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        match HEAD.compare_exchange_weak(head, head + 1, Ordering::AcqRel, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(claimed) => head = claimed,
+        }
+    }
+    let slot = head % RING_CAPACITY;
+    RING_SIZES[slot].store(record.size, Ordering::Relaxed);
+    RING_ALIGNS[slot].store(record.align, Ordering::Relaxed);
+    RING_TIMES[slot].store(record.timestamp, Ordering::Relaxed);
+    RING_NANOS[slot].store(record.nanos, Ordering::Relaxed);
+    RING_TAGS[slot].store(AllocationTag::to_u8(record.tag), Ordering::Relaxed);
+    RING_KINDS[slot].store(record.kind.to_u8(), Ordering::Relaxed);
+    RING_STAMPS[slot].store(head + 1, Ordering::Release);
+}
+
+/// Writes the queued allocations to the trace when there is no worker to,
+/// as the thread ending every frame does. Skipped while another thread is
+/// writing, which leaves them for the next frame.
+pub fn flush() {
+    if is_running() {
+        return;
+    }
+    let mut sink = match SINK.try_lock() {
+        Ok(sink) => sink,
+        Err(TryLockError::Poisoned(err)) => err.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    if drain_into(&mut sink, &mut String::new()) {
+        if let Some(out) = sink.as_mut() {
+            if let Err(err) = out.flush() {
+                fail(&mut sink, &err);
+            }
+        }
+    }
+}
+
+/// Runs `job` on the worker, like writing a file. Without a worker it runs
+/// right away. Returns false when the queue was full and the job was dropped.
+pub fn submit(job: impl FnOnce() + Send + 'static) -> bool {
+    let Some(jobs) = JOBS.get().filter(|_| is_running()) else {
+        job();
+        return true;
+    };
+    match jobs.try_send(Box::new(job)) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+            DROPPED_JOBS.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+/// What the trace is missing so far, and why.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TelemetryHealth {
+    /// Allocation records and jobs dropped because the worker was behind
+    pub dropped_records: u64,
+    pub dropped_jobs: u64,
+
+    /// Allocation records the sink failed to take or came after it failed
+    pub lost_records: u64,
+
+    /// Comments and frame summaries dropped, while the worker was writing
+    /// or after the sink failed
+    pub dropped_lines: u64,
+
+    /// What the sink failed with, after which nothing more is written to it
+    pub error: Option<&'static str>,
+}
+
+impl TelemetryHealth {
+    pub fn is_ok(&self) -> bool {
+        *self == TelemetryHealth::default()
+    }
+
+    /// Full account, as written at the end of the trace.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} allocation records, {} files and {} lines dropped while the writer was behind",
+            self.dropped_records, self.dropped_jobs, self.dropped_lines
+        );
+        if let Some(error) = self.error {
+            let _ = write!(text, ", {} records lost after the trace failed: {}", self.lost_records, error);
+        }
+        text
+    }
+
+    /// One line for the HUD.
+    pub fn headline(&self) -> String {
+        match self.error {
+            Some(error) => format!("trace failed: {}", error),
+            None => format!("telemetry: {} dropped", self.dropped_records + self.dropped_jobs + self.dropped_lines),
+        }
+    }
+}
+
+pub fn health() -> TelemetryHealth {
+    TelemetryHealth {
+        dropped_records: DROPPED_RECORDS.load(Ordering::Relaxed),
+        dropped_jobs: DROPPED_JOBS.load(Ordering::Relaxed),
+        lost_records: LOST_RECORDS.load(Ordering::Relaxed),
+        dropped_lines: DROPPED_LINES.load(Ordering::Relaxed),
+        error: SINK_ERROR.get().map(String::as_str),
+    }
+}
+
+/// Writes everything still queued and stops the worker; the allocations
+/// made from then on are left out of the trace. Reports what was dropped,
+/// if anything.
+pub fn finish() {
+    if FINISHED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let handle = WORKER.lock().unwrap_or_else(|err| err.into_inner()).take();
+    if let Some(handle) = handle {
+        STOPPING.store(true, Ordering::Release);
+        if handle.join().is_err() {
+            warn(format_args!("The telemetry writer panicked, the end of the trace may be missing"));
+        }
+        RUNNING.store(false, Ordering::Release);
+    }
+    drain_into(&mut lock_sink(), &mut String::new());
+
+    // A failed sink is gone, so what it missed is told on stderr instead
+    let health = health();
+    let Some(mut sink) = lock_sink().take() else {
+        if !health.is_ok() {
+            warn(format_args!("telemetry: {}", health.describe()));
+        }
+        return;
+    };
+    if !health.is_ok() {
+        let _ = writeln!(sink, "# telemetry: {}", health.describe());
+    }
+    if let Err(err) = sink.finish() {
+        warn(format_args!("Could not finish writing the trace: {}", err));
+    }
+}
+
+fn run(jobs: Receiver<Job>, ready: SyncSender<()>) {
+    allocator::set_thread_role(allocator::ThreadRole::Telemetry);
+    ON_WORKER.set(true);
+
+    // Longer than any line, so formatting never grows it: how often it would
+    // depends on timing, and seeded runs would not allocate alike
+    let mut line = String::with_capacity(256);
+    let drain_sink = |line: &mut String| {
+        let mut sink = lock_sink();
+        let wrote = drain_into(&mut sink, line);
+        if let Some(out) = sink.as_mut().filter(|_| wrote) {
+            if let Err(err) = out.flush() {
+                fail(&mut sink, &err);
+            }
+        }
+        wrote
+    };
+
+    // For the same reason, whatever the worker allocates once is allocated
+    // before `start` returns, while nothing is measured yet
+    drain_sink(&mut line);
+    let _ = jobs.recv_timeout(Duration::from_millis(1)).map(|job| job());
+    let _ = ready.send(());
+
+    loop {
+        let stopping = STOPPING.load(Ordering::Acquire);
+        let wrote = drain_sink(&mut line);
+
+        if stopping {
+            while let Ok(job) = jobs.try_recv() {
+                job();
+            }
+            drain_sink(&mut line);
+            return;
+        }
+        match jobs.recv_timeout(if wrote { Duration::ZERO } else { IDLE }) {
+            Ok(job) => job(),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {}
+        }
+    }
+}
+
+// Writes the queued allocations to `sink`, giving up on it when it fails,
+// or counts them lost when it already did. Returns whether there were any
+fn drain_into(sink: &mut Option<TraceSink>, line: &mut String) -> bool {
+    match sink.as_mut() {
+        Some(out) => drain(out, line).unwrap_or_else(|err| {
+            fail(sink, &err);
+            true
+        }),
+        None if failed() => discard(),
+        None => false,
+    }
+}
+
+// Where the records ready to be read end: past `TAIL`, up to the first one
+// still being written
+fn written_head() -> (usize, usize) {
+    let tail = TAIL.load(Ordering::Relaxed);
+    let head = HEAD.load(Ordering::Acquire);
+    let written = (tail..head).find(|&index| RING_STAMPS[index % RING_CAPACITY].load(Ordering::Acquire) != index + 1);
+    (tail, written.unwrap_or(head))
+}
+
+// Writes the queued allocations in the trace format, returning whether
+// there were any. Each line goes out in one write so messages printed by
+// other threads never land inside one. When `out` fails, the allocations
+// left are counted lost. Only called with the sink locked, so by one thread
+// at a time
+pub(crate) fn drain(out: &mut impl Write, line: &mut String) -> io::Result<bool> {
+    let format = TraceFormat::from_u8(FORMAT.load(Ordering::Acquire));
+    let (tail, head) = written_head();
+    for index in tail..head {
+        let slot = index % RING_CAPACITY;
+        let record = Record {
+            size: RING_SIZES[slot].load(Ordering::Relaxed),
+            align: RING_ALIGNS[slot].load(Ordering::Relaxed),
+            timestamp: RING_TIMES[slot].load(Ordering::Relaxed),
+            nanos: RING_NANOS[slot].load(Ordering::Relaxed),
+            tag: AllocationTag::from_u8(RING_TAGS[slot].load(Ordering::Relaxed)),
+            kind: AllocationKind::from_u8(RING_KINDS[slot].load(Ordering::Relaxed)),
+        };
+        line.clear();
+        let _ = format.write(line, &record);
+        if let Err(err) = out.write_all(line.as_bytes()) {
+            LOST_RECORDS.fetch_add((head - index) as u64, Ordering::Relaxed);
+            TAIL.store(head, Ordering::Release);
+            return Err(err);
+        }
+    }
+    // Only now can the producers reuse the slots
+    TAIL.store(head, Ordering::Release);
+    Ok(head > tail)
+}
+
+// Counts the queued allocations lost once the sink failed, returning
+// whether there were any
+fn discard() -> bool {
+    let (tail, head) = written_head();
+    LOST_RECORDS.fetch_add((head - tail) as u64, Ordering::Relaxed);
+    TAIL.store(head, Ordering::Release);
+    head > tail
+}
+
+// Gives up on the sink after it failed with `err`, so the trace stops there
+// rather than going on with a gap, and tells so on stderr once
+fn fail(sink: &mut Option<TraceSink>, err: &io::Error) {
+    if sink.take().is_some() && SINK_ERROR.set(err.to_string()).is_ok() {
+        warn(format_args!("Could not write the allocation trace, dropping it from now on: {}", err));
+    }
+}
+
+fn failed() -> bool {
+    SINK_ERROR.get().is_some()
+}
+
+// Like `eprintln!`, without panicking when stderr is gone too
+fn warn(message: fmt::Arguments) {
+    let _ = writeln!(io::stderr(), "{}", message);
+}
+
+fn lock_sink() -> MutexGuard<'static, Option<TraceSink>> {
+    SINK.lock().unwrap_or_else(|err| err.into_inner())
+}
@@ -0,0 +1,254 @@
+use memory_particles::allocator::{memory_stats, AllocationLog};
+use memory_particles::Particle;
+use piston_window::*;
+use std::collections::VecDeque;
+
+// Height in pixels of the scrubber drawn at the bottom of the window
+pub const TIMELINE_HEIGHT: f64 = 80.0;
+
+// Height of the allocations of the selected turn, drawn above the scrubber
+const ALLOCATIONS_HEIGHT: f64 = 40.0;
+
+// Height of both, shown while paused, with the gap between them
+pub const PAUSED_HEIGHT: f64 = TIMELINE_HEIGHT + 4.0 + ALLOCATIONS_HEIGHT;
+
+// Number of most recent turns whose allocations are kept
+const ALLOCATION_LOGS_KEPT: usize = 600;
+
+// Marks the reallocations of the particle `Vec` in both charts
+const VEC_REALLOC_COLOR: [f32; 4] = [1.0, 0.55, 0.1, 1.0];
+
+/// Memory usage recorded at the end of a turn.
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineSample {
+    pub turn: u64,
+    pub live_bytes: usize,
+
+    /// Number of allocations made during the turn
+    pub events: u64,
+
+    /// Whether the particle `Vec` grew or shrank during the turn
+    pub vec_realloc: bool,
+}
+
+/// Records the heap curve over the whole run so it can be inspected while paused.
+pub struct Timeline {
+    samples: Vec<TimelineSample>,
+    last_allocations: u64,
+    last_vec_reallocs: u64,
+
+    // Copies of the particles taken every `snapshot_every` turns, with the
+    // index of their sample. Indexes rather than turns keep them ordered
+    // even when the world is rewound to an earlier turn
+    snapshot_every: Option<u64>,
+    snapshots: Vec<(usize, Vec<Particle>)>,
+
+    // Allocations made during the most recent turns, with the index of their sample
+    allocation_logs: VecDeque<(usize, AllocationLog)>,
+}
+
+impl Timeline {
+    pub fn new(snapshot_every: Option<u64>) -> Timeline {
+        Timeline {
+            samples: Vec::new(),
+            last_allocations: memory_stats().allocations,
+            last_vec_reallocs: memory_stats().vec_reallocs,
+            snapshot_every: snapshot_every.filter(|&n| n > 0),
+            snapshots: Vec::new(),
+            allocation_logs: VecDeque::new(),
+        }
+    }
+
+    /// Makes room for `turns` more samples, so recording them allocates
+    /// nothing but the snapshots.
+    pub fn reserve(&mut self, turns: usize) {
+        self.samples.reserve(turns);
+    }
+
+    pub fn record<'a, I>(&mut self, turn: u64, particles: I)
+    where
+        I: Iterator<Item = &'a Particle>,
+    {
+        if let Some(every) = self.snapshot_every {
+            if turn.is_multiple_of(every) {
+                self.snapshots.push((self.samples.len(), particles.cloned().collect()));
+            }
+        }
+
+        // Read the counters after the snapshot so its allocation is
+        // accounted to this turn
+        let stats = memory_stats();
+        self.samples.push(TimelineSample {
+            turn,
+            live_bytes: stats.live_bytes,
+            events: stats.allocations - self.last_allocations,
+            vec_realloc: stats.vec_reallocs > self.last_vec_reallocs,
+        });
+        self.last_allocations = stats.allocations;
+        self.last_vec_reallocs = stats.vec_reallocs;
+    }
+
+    /// Keeps the allocations made during the turn of the latest sample.
+    pub fn attach_allocations(&mut self, log: AllocationLog) {
+        let Some(index) = self.samples.len().checked_sub(1) else {
+            return;
+        };
+        if self.allocation_logs.len() == ALLOCATION_LOGS_KEPT {
+            self.allocation_logs.pop_front();
+        }
+        self.allocation_logs.push_back((index, log));
+    }
+
+    /// Allocations made during the turn of the sample at `index`, if still kept.
+    pub fn allocations_at(&self, index: usize) -> Option<&AllocationLog> {
+        self.allocation_logs.iter().find(|(sample, _)| *sample == index).map(|(_, log)| log)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Turn of the world when the sample at `index` was recorded.
+    pub fn turn_at(&self, index: usize) -> Option<u64> {
+        self.samples.get(index).map(|sample| sample.turn)
+    }
+
+    /// Latest snapshot taken at or before the sample at `index`, if snapshotting is enabled.
+    pub fn snapshot_at(&self, index: usize) -> Option<&[Particle]> {
+        let after = self.snapshots.partition_point(|(taken, _)| *taken <= index);
+        after
+            .checked_sub(1)
+            .map(|i| self.snapshots[i].1.as_slice())
+    }
+
+    /// Maps a horizontal position inside the widget to a sample index.
+    pub fn index_at(&self, x: f64, width: f64) -> usize {
+        if self.samples.is_empty() {
+            return 0;
+        }
+        let fraction = (x / width).clamp(0.0, 1.0);
+        ((self.samples.len() - 1) as f64 * fraction).round() as usize
+    }
+
+    /// Draws the memory curve and event density along the bottom of the window,
+    /// with a cursor on the `selected` sample. Turns where the particle `Vec`
+    /// was reallocated are marked in orange along the top.
+    pub fn draw<G: Graphics>(&self, selected: usize, width: f64, height: f64, ctx: Context, g: &mut G) {
+        let top = height - TIMELINE_HEIGHT;
+        rectangle([0.0, 0.0, 0.0, 0.7], [0.0, top, width, TIMELINE_HEIGHT], ctx.transform, g);
+
+        if self.samples.is_empty() {
+            return;
+        }
+
+        let max_bytes = self.samples.iter().map(|s| s.live_bytes).max().unwrap_or(1).max(1) as f64;
+        let max_events = self.samples.iter().map(|s| s.events).max().unwrap_or(1).max(1) as f64;
+
+        // One column per pixel, picking the sample under each column
+        let columns = width as usize;
+        let mut previous: Option<[f64; 2]> = None;
+        for column in 0..columns {
+            let x = column as f64;
+            let sample = &self.samples[self.index_at(x, width)];
+
+            let bar = sample.events as f64 / max_events * TIMELINE_HEIGHT;
+            rectangle([0.3, 0.5, 0.9, 0.5], [x, height - bar, 1.0, bar], ctx.transform, g);
+
+            // Every sample of the column, so no reallocation is skipped when zoomed out
+            let last = self.index_at(x + 1.0, width).max(self.index_at(x, width));
+            if self.samples[self.index_at(x, width)..=last].iter().any(|s| s.vec_realloc) {
+                rectangle(VEC_REALLOC_COLOR, [x, top, 1.0, 6.0], ctx.transform, g);
+            }
+
+            let y = height - sample.live_bytes as f64 / max_bytes * (TIMELINE_HEIGHT - 2.0);
+            if let Some([px, py]) = previous {
+                line_from_to([1.0, 0.8, 0.2, 1.0], 0.5, [px, py], [x, y], ctx.transform, g);
+            }
+            previous = Some([x, y]);
+        }
+
+        let cursor = if self.samples.len() > 1 {
+            selected as f64 / (self.samples.len() - 1) as f64 * width
+        } else {
+            0.0
+        };
+        line_from_to([1.0, 0.2, 0.2, 1.0], 1.0, [cursor, top], [cursor, height], ctx.transform, g);
+    }
+}
+
+impl Timeline {
+    /// Draws the allocations made during the selected turn above the scrubber:
+    /// one bar per allocation, placed at the time it happened within the turn
+    /// and as tall as the square root of its size relative to the largest.
+    pub fn draw_allocations<G: Graphics>(&self, selected: usize, width: f64, height: f64, ctx: Context, g: &mut G) {
+        let Some(log) = self.allocations_at(selected) else {
+            return;
+        };
+
+        let bottom = height - TIMELINE_HEIGHT - 4.0;
+        let top = bottom - ALLOCATIONS_HEIGHT;
+        rectangle([0.0, 0.0, 0.0, 0.6], [0.0, top, width, ALLOCATIONS_HEIGHT], ctx.transform, g);
+
+        let span = log.span.as_secs_f64().max(f64::EPSILON);
+        let largest = log.allocations.iter().map(|a| a.size).max().unwrap_or(1).max(1) as f64;
+        for allocation in &log.allocations {
+            let x = (allocation.offset.as_secs_f64() / span).min(1.0) * (width - 2.0);
+            let bar = ((allocation.size as f64 / largest).sqrt() * (ALLOCATIONS_HEIGHT - 2.0)).max(1.0);
+            let color = if allocation.tag.is_some() { VEC_REALLOC_COLOR } else { [0.4, 0.9, 1.0, 0.8] };
+            rectangle(color, [x, bottom - bar, 2.0, bar], ctx.transform, g);
+        }
+
+        // Some allocations didn't fit in the log
+        if log.missed > 0 {
+            rectangle([1.0, 0.25, 0.2, 0.9], [width - 6.0, top, 6.0, ALLOCATIONS_HEIGHT], ctx.transform, g);
+        }
+    }
+}
+
+/// Selects a sample of the timeline from mouse drags and the arrow keys.
+pub struct Scrubber {
+    pub selected: usize,
+    dragging: bool,
+    cursor: [f64; 2],
+}
+
+impl Scrubber {
+    pub fn new() -> Scrubber {
+        Scrubber {
+            selected: 0,
+            dragging: false,
+            cursor: [0.0, 0.0],
+        }
+    }
+
+    pub fn select_latest(&mut self, timeline: &Timeline) {
+        self.selected = timeline.len().saturating_sub(1);
+    }
+
+    pub fn handle_event(&mut self, event: &Event, timeline: &Timeline, width: f64, height: f64) {
+        if let Some(position) = event.mouse_cursor_args() {
+            self.cursor = position;
+            if self.dragging {
+                self.selected = timeline.index_at(position[0], width);
+            }
+        }
+
+        match event.press_args() {
+            Some(Button::Mouse(MouseButton::Left)) if self.cursor[1] >= height - TIMELINE_HEIGHT => {
+                self.dragging = true;
+                self.selected = timeline.index_at(self.cursor[0], width);
+            }
+            Some(Button::Keyboard(Key::Left)) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            Some(Button::Keyboard(Key::Right)) => {
+                self.selected = (self.selected + 1).min(timeline.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+
+        if let Some(Button::Mouse(MouseButton::Left)) = event.release_args() {
+            self.dragging = false;
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::allocator::{AllocationKind, AllocationTag};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+// Allocation sizes are grouped in power-of-two buckets: bucket 0 holds
+// zero-sized requests and bucket `i` holds sizes in `2^(i-1)..2^i`
+pub(crate) const SIZE_BUCKETS: usize = 65;
+
+/// A single allocation event as recorded by the `ReportingAllocator`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TraceEvent {
+    pub size: usize,
+    pub nanos: u64,
+
+    /// Set on the allocations that grew or shrank the particle `Vec`
+    #[serde(default)]
+    pub tag: Option<AllocationTag>,
+
+    /// A fresh allocation in traces written before kinds were
+    #[serde(default)]
+    pub kind: AllocationKind,
+}
+
+impl TraceEvent {
+    // Accepts JSON Lines objects and `timestamp_ns,size,align,duration_ns,tag,kind`
+    // CSV rows as well as the tab-separated `size<TAB>nanos[<TAB>tag][<TAB>kind]`
+    // lines the allocator prints to stderr
+    fn parse(line: &str) -> Option<TraceEvent> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).ok();
+        }
+        if line.contains(',') {
+            let mut fields = line.split(',').skip(1);
+            let size = fields.next()?.parse().ok()?;
+            let nanos = fields.nth(1)?.parse().ok()?;
+            let tag = match fields.next() {
+                Some("") | None => None,
+                Some(label) => Some(AllocationTag::from_label(label)?),
+            };
+            let kind = match fields.next() {
+                Some("") | None => AllocationKind::Alloc,
+                Some(label) => AllocationKind::from_label(label)?,
+            };
+            return Some(TraceEvent { size, nanos, tag, kind });
+        }
+
+        let mut fields = line.split('\t');
+        let size = fields.next()?.parse().ok()?;
+        let nanos = fields.next()?.parse().ok()?;
+        let (mut tag, mut kind) = (None, AllocationKind::Alloc);
+        for label in fields {
+            match AllocationKind::from_label(label) {
+                Some(label) => kind = label,
+                None => tag = Some(AllocationTag::from_label(label)?),
+            }
+        }
+        Some(TraceEvent { size, nanos, tag, kind })
+    }
+
+    /// Whether the event gave a block, rather than freed one.
+    pub fn is_allocation(&self) -> bool {
+        self.kind != AllocationKind::Free
+    }
+}
+
+/// What a trace file contains besides its allocation events.
+#[derive(Debug, Default)]
+pub struct TraceInfo {
+    /// `# key: value` lines describing the run the trace comes from
+    pub metadata: Vec<(String, String)>,
+
+    /// Lines that are neither events nor metadata, e.g. panics or other
+    /// messages mixed into a captured stderr
+    pub skipped_lines: u64,
+}
+
+// Frames of zstd compressed files start with these bytes
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// Opens a trace file, decompressing it on the fly when it was written
+// through zstd whatever its name
+fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        return Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)));
+    }
+    Ok(Box::new(reader))
+}
+
+// First column of the header row of CSV traces
+const CSV_HEADER: &str = "timestamp_ns,";
+
+/// Calls `f` for every allocation event of a trace file, in order. Plain
+/// and zstd compressed files are both read.
+pub fn read_events<F: FnMut(TraceEvent)>(path: &Path, mut f: F) -> io::Result<TraceInfo> {
+    let mut reader = open(path)?;
+    let mut info = TraceInfo::default();
+
+    // Reuse a single buffer, otherwise every line read would itself
+    // show up as an allocation in the trace of this run
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        if let Some(comment) = line.trim().strip_prefix('#') {
+            if let Some((key, value)) = comment.split_once(':') {
+                info.metadata.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        } else {
+            match TraceEvent::parse(&line) {
+                Some(event) => f(event),
+                None if line.trim().is_empty() || line.starts_with(CSV_HEADER) => {}
+                None => info.skipped_lines += 1,
+            }
+        }
+        line.clear();
+    }
+    Ok(info)
+}
+
+pub(crate) fn size_bucket(size: usize) -> usize {
+    (usize::BITS - size.leading_zeros()) as usize
+}
+
+pub(crate) fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        0 => "0 B".to_string(),
+        1 => "1 B".to_string(),
+        _ => format!("{}-{} B", 1u128 << (bucket - 1), (1u128 << bucket) - 1),
+    }
+}
+
+/// Statistical summary of a whole allocation trace. The sizes and latencies
+/// are of the allocations, frees only counted.
+pub struct TraceSummary {
+    pub count: u64,
+    pub total_bytes: u64,
+    pub size_buckets: [u64; SIZE_BUCKETS],
+
+    /// Allocations that grew or shrank the particle `Vec`
+    pub vec_grows: u64,
+    pub vec_shrinks: u64,
+
+    /// Allocations of the positions of particle trails
+    pub trails: u64,
+
+    /// Of the allocations, those made by `realloc` and `alloc_zeroed`
+    pub reallocs: u64,
+    pub zeroed: u64,
+
+    /// Frees, and the bytes they gave back, in traces of every event
+    pub frees: u64,
+    pub freed_bytes: u64,
+    pub info: TraceInfo,
+
+    // Sorted so percentiles can be looked up directly
+    latencies: Vec<u64>,
+}
+
+impl TraceSummary {
+    fn new() -> TraceSummary {
+        TraceSummary {
+            count: 0,
+            total_bytes: 0,
+            size_buckets: [0; SIZE_BUCKETS],
+            vec_grows: 0,
+            vec_shrinks: 0,
+            trails: 0,
+            reallocs: 0,
+            zeroed: 0,
+            frees: 0,
+            freed_bytes: 0,
+            info: TraceInfo::default(),
+            latencies: Vec::new(),
+        }
+    }
+
+    pub fn from_events<I: IntoIterator<Item = TraceEvent>>(events: I) -> TraceSummary {
+        let mut summary = TraceSummary::new();
+        for event in events {
+            summary.add(event);
+        }
+        summary.latencies.sort_unstable();
+        summary
+    }
+
+    /// Reads a trace file, skipping lines that are not allocation events.
+    pub fn load(path: &Path) -> io::Result<TraceSummary> {
+        let mut summary = TraceSummary::new();
+        let info = read_events(path, |event| summary.add(event))?;
+        summary.info = info;
+        summary.latencies.sort_unstable();
+        Ok(summary)
+    }
+
+    fn add(&mut self, event: TraceEvent) {
+        match event.kind {
+            AllocationKind::Alloc => {}
+            AllocationKind::Realloc => self.reallocs += 1,
+            AllocationKind::Zeroed => self.zeroed += 1,
+            AllocationKind::Free => {
+                self.frees += 1;
+                self.freed_bytes += event.size as u64;
+                return;
+            }
+        }
+        self.count += 1;
+        self.total_bytes += event.size as u64;
+        self.size_buckets[size_bucket(event.size)] += 1;
+        match event.tag {
+            Some(AllocationTag::VecGrow) => self.vec_grows += 1,
+            Some(AllocationTag::VecShrink) => self.vec_shrinks += 1,
+            Some(AllocationTag::Trail) => self.trails += 1,
+            None => {}
+        }
+        self.latencies.push(event.nanos);
+    }
+
+    pub fn mean_size(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.total_bytes as f64 / self.count as f64
+    }
+
+    /// Latency in nanoseconds below which `p` percent of the events fall.
+    pub fn latency_percentile(&self, p: f64) -> u64 {
+        if self.latencies.is_empty() {
+            return 0;
+        }
+        let rank = (p / 100.0 * (self.latencies.len() - 1) as f64).round() as usize;
+        self.latencies[rank.min(self.latencies.len() - 1)]
+    }
+}
+
+fn delta_cell(a: f64, b: f64) -> String {
+    let delta = b - a;
+    if a == 0.0 {
+        return format!("{:+.0}", delta);
+    }
+    format!("{:+.0} ({:+.1}%)", delta, delta / a * 100.0)
+}
+
+fn print_row(metric: &str, a: f64, b: f64) {
+    println!("{:<24}{:>16.0}{:>16.0}  {}", metric, a, b, delta_cell(a, b));
+}
+
+/// Prints a table comparing two trace summaries, `b` relative to `a`.
+pub fn print_diff(a_name: &str, a: &TraceSummary, b_name: &str, b: &TraceSummary) {
+    println!("{:<24}{:>16}{:>16}  delta", "metric", a_name, b_name);
+    print_row("allocations", a.count as f64, b.count as f64);
+    print_row("bytes", a.total_bytes as f64, b.total_bytes as f64);
+    print_row("mean size (B)", a.mean_size(), b.mean_size());
+    print_row("vec growths", a.vec_grows as f64, b.vec_grows as f64);
+    print_row("vec shrinks", a.vec_shrinks as f64, b.vec_shrinks as f64);
+    print_row("trail allocations", a.trails as f64, b.trails as f64);
+    print_row("reallocs", a.reallocs as f64, b.reallocs as f64);
+    print_row("zeroed allocations", a.zeroed as f64, b.zeroed as f64);
+    print_row("frees", a.frees as f64, b.frees as f64);
+
+    for p in [50.0, 90.0, 99.0, 100.0] {
+        let metric = if p == 100.0 {
+            "latency max (ns)".to_string()
+        } else {
+            format!("latency p{} (ns)", p)
+        };
+        print_row(&metric, a.latency_percentile(p) as f64, b.latency_percentile(p) as f64);
+    }
+
+    println!();
+    println!("size distribution");
+    for bucket in 0..SIZE_BUCKETS {
+        let (in_a, in_b) = (a.size_buckets[bucket], b.size_buckets[bucket]);
+        if in_a > 0 || in_b > 0 {
+            print_row(&format!("  {}", bucket_label(bucket)), in_a as f64, in_b as f64);
+        }
+    }
+
+    // Runs recorded on different machines or versions aren't directly comparable
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in a.info.metadata.iter().chain(&b.info.metadata) {
+        if !keys.contains(&key.as_str()) {
+            keys.push(key);
+        }
+    }
+    if !keys.is_empty() {
+        println!();
+        println!("run metadata");
+        let value = |summary: &'_ TraceSummary, key: &str| {
+            summary
+                .info
+                .metadata
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or("-".to_string(), |(_, v)| v.clone())
+        };
+        for key in keys {
+            println!("  {:<14}{:<26}{}", key, value(a, key), value(b, key));
+        }
+    }
+
+    let (skipped_a, skipped_b) = (a.info.skipped_lines, b.info.skipped_lines);
+    if skipped_a > 0 || skipped_b > 0 {
+        println!();
+        println!("skipped lines: {} in {}, {} in {}", skipped_a, a_name, skipped_b, b_name);
+    }
+}
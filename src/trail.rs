@@ -0,0 +1,132 @@
+use graphics::math::Vec2d;
+use serde::Deserialize;
+
+/// Longest trail a scene can ask for.
+pub const MAX_TRAIL_LENGTH: usize = 1024;
+
+/// How the trails of a scene keep the positions of their particles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailStorage {
+    /// A ring of `length` positions allocated once, when the particle gets
+    /// its trail, and overwritten from then on
+    #[default]
+    Ring,
+
+    /// A list growing a position a turn, reallocated as it doubles, until it
+    /// holds `length` of them and is overwritten like a ring: what the ring
+    /// saves, for the memory report
+    Growing,
+}
+
+/// The `[trail]` table of a scene: every particle is followed by a fading
+/// line through the positions of its last `length` turns.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrailSettings {
+    #[serde(default = "default_length")]
+    pub length: usize,
+
+    #[serde(default)]
+    pub storage: TrailStorage,
+
+    /// Width of the line in pixels, at the particle's end
+    #[serde(default = "default_width")]
+    pub width: f64,
+}
+
+fn default_length() -> usize {
+    16
+}
+
+fn default_width() -> f64 {
+    1.0
+}
+
+/// The centers of a particle over its last turns, newest last. Recording a
+/// position never allocates once the trail is as long as its settings say,
+/// and a ring trail never does after `Trail::new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trail {
+    points: Vec<Vec2d<f64>>,
+
+    // Positions kept, at most, and where the oldest is once there are that many
+    length: u32,
+    oldest: u32,
+}
+
+impl Trail {
+    pub fn new(settings: &TrailSettings) -> Trail {
+        let length = settings.length.clamp(1, MAX_TRAIL_LENGTH);
+        let points = match settings.storage {
+            TrailStorage::Ring => Vec::with_capacity(length),
+            TrailStorage::Growing => Vec::new(),
+        };
+        Trail { points, length: length as u32, oldest: 0 }
+    }
+
+    /// Adds the newest position, in place of the oldest once the trail is
+    /// full, and says whether that took an allocation.
+    pub fn push(&mut self, point: Vec2d<f64>) -> bool {
+        if self.points.len() < self.length as usize {
+            let capacity = self.points.capacity();
+            self.points.push(point);
+            return self.points.capacity() != capacity;
+        }
+        self.points[self.oldest as usize] = point;
+        self.oldest = (self.oldest + 1) % self.length;
+        false
+    }
+
+    /// The positions from the oldest to the newest.
+    pub fn points(&self) -> impl Iterator<Item = Vec2d<f64>> + Clone + '_ {
+        let (newer, older) = self.points.split_at(self.oldest as usize);
+        older.iter().chain(newer).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Bytes of positions the trail has room for.
+    pub fn capacity_bytes(&self) -> usize {
+        self.points.capacity() * std::mem::size_of::<Vec2d<f64>>()
+    }
+}
+
+/// What the trails of a world took, for the memory report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrailStats {
+    /// Trails made, one for every particle that lived a turn
+    pub trails: u64,
+
+    /// Allocations of their positions, trails being made included
+    pub allocations: u64,
+}
+
+impl TrailStats {
+    pub fn since(&self, before: &TrailStats) -> TrailStats {
+        TrailStats { trails: self.trails - before.trails, allocations: self.allocations - before.allocations }
+    }
+
+    /// Like `5000 ring trails, 5000 allocations, 1.0 each, 1280000 B held`,
+    /// with the bytes the trails left have room for.
+    pub fn describe(&self, storage: TrailStorage, held_bytes: usize) -> String {
+        let storage = match storage {
+            TrailStorage::Ring => "ring",
+            TrailStorage::Growing => "growing",
+        };
+        format!(
+            "{} {} trails, {} allocations, {:.1} each, {} B held",
+            self.trails,
+            storage,
+            self.allocations,
+            self.allocations as f64 / self.trails.max(1) as f64,
+            held_bytes
+        )
+    }
+}
@@ -0,0 +1,194 @@
+use crate::hud::Hud;
+use memory_particles::force::Force;
+use memory_particles::params::{ParamRegistry, ParamValue};
+use memory_particles::World;
+use piston_window::*;
+
+// Width of the panel in the top right corner, and of the row of a slider
+const PANEL_WIDTH: f64 = 260.0;
+const ROW_HEIGHT: f64 = 36.0;
+const MARGIN: f64 = 10.0;
+const TRACK_HEIGHT: f64 = 6.0;
+
+/// A slider of the panel, over the part of its parameter's range worth
+/// dragging through, in steps of `step`.
+struct Slider {
+    label: &'static str,
+    param: &'static str,
+    range: [f64; 2],
+    step: f64,
+}
+
+const SLIDERS: [Slider; 4] = [
+    Slider { label: "spawn rate", param: "spawn_rate", range: [0.0, 500.0], step: 1.0 },
+    Slider { label: "gravity", param: "gravity", range: [-0.5, 0.5], step: 0.005 },
+    Slider { label: "fade", param: "particle.fade", range: [0.9, 1.0], step: 0.0005 },
+    Slider { label: "particle size", param: "particle.size", range: [0.5, 20.0], step: 0.1 },
+];
+
+/// Values of the parameters of the sliders, in their order.
+pub type SliderValues = [Option<f64>; SLIDERS.len()];
+
+/// What a mouse event did to the panel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TuningAction {
+    /// Not the panel's, for the window to handle
+    Ignored,
+
+    /// A press or a drag on the panel, which moved a slider if over one
+    Grabbed,
+
+    /// A slider let go of, with the assignment it left, like `spawn_rate=120`
+    Tuned(String),
+}
+
+/// T shows sliders for the spawn rate, gravity, fade and size of the
+/// particles in the top right corner, dragged with the mouse to tune the
+/// scene as it runs and watch the allocations follow. They go through the
+/// tunable parameters like `--set`, so what a slider was left at can be
+/// given to the next run; gravity is the vertical pull of the first gravity
+/// force, added when the scene has none.
+#[derive(Clone)]
+pub struct TuningPanel {
+    pub open: bool,
+    dragging: Option<usize>,
+    cursor: [f64; 2],
+}
+
+impl TuningPanel {
+    pub fn new() -> TuningPanel {
+        TuningPanel { open: false, dragging: None, cursor: [0.0, 0.0] }
+    }
+
+    /// Whether `handle_event` would do anything with `event` but follow the
+    /// cursor, which this follows instead, so the world is only needed for
+    /// the events moving a slider or letting go of one.
+    pub fn concerns(&mut self, event: &Event, width: f64) -> bool {
+        if let Some(position) = event.mouse_cursor_args() {
+            self.cursor = position;
+            return self.dragging.is_some() && self.open;
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = event.release_args() {
+            return self.dragging.is_some();
+        }
+        self.open && event.press_args() == Some(Button::Mouse(MouseButton::Left)) && self.over_panel(width)
+    }
+
+    pub fn handle_event(&mut self, event: &Event, world: &mut World, width: f64) -> TuningAction {
+        if let Some(position) = event.mouse_cursor_args() {
+            self.cursor = position;
+            if let Some(index) = self.dragging.filter(|_| self.open) {
+                self.set(index, world, width);
+                return TuningAction::Grabbed;
+            }
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = event.release_args() {
+            if let Some(index) = self.dragging.take() {
+                let name = param_name(&SLIDERS[index], world).unwrap_or_default();
+                let current = ParamRegistry::collect(world).params.into_iter().find(|param| param.name == name);
+                return TuningAction::Tuned(format!("{}={}", name, current.map_or(String::new(), |param| param.current())));
+            }
+        }
+        if !self.open || event.press_args() != Some(Button::Mouse(MouseButton::Left)) {
+            return TuningAction::Ignored;
+        }
+        if !self.over_panel(width) {
+            return TuningAction::Ignored;
+        }
+        let [_, y] = self.cursor;
+        let row = ((y - MARGIN) / ROW_HEIGHT).floor().max(0.0) as usize;
+        if row < SLIDERS.len() {
+            self.dragging = Some(row);
+            self.set(row, world, width);
+        }
+        TuningAction::Grabbed
+    }
+
+    /// Values of the parameters of the sliders, none for gravity without a
+    /// gravity force, read before drawing since the registry borrows the
+    /// world mutably.
+    pub fn values(world: &mut World) -> SliderValues {
+        let names = SLIDERS.each_ref().map(|slider| param_name(slider, world));
+        let params = ParamRegistry::collect(world).params;
+        names.map(|name| {
+            let param = params.iter().find(|param| Some(&param.name) == name.as_ref())?;
+            Some(match param.value {
+                ParamValue::Float { value, .. } => value,
+                ParamValue::Integer { value, .. } => value as f64,
+            })
+        })
+    }
+
+    /// Draws the panel with the slider of every parameter at `values`, its
+    /// labels through the font of `hud`.
+    pub fn draw(&self, values: &[Option<f64>], width: f64, hud: &mut Hud, ctx: Context, g: &mut G2d, device: &mut GfxDevice) {
+        let left = panel_left(width);
+        let panel = [left, MARGIN / 2.0, PANEL_WIDTH, SLIDERS.len() as f64 * ROW_HEIGHT + MARGIN];
+        rectangle([0.0, 0.0, 0.0, if hud.high_contrast { 1.0 } else { 0.6 }], panel, ctx.transform, g);
+
+        let mut labels = Vec::with_capacity(SLIDERS.len());
+        for (i, (slider, value)) in SLIDERS.iter().zip(values).enumerate() {
+            let top = MARGIN + i as f64 * ROW_HEIGHT;
+            let track = [left + MARGIN, top + ROW_HEIGHT - TRACK_HEIGHT - 8.0, track_width(), TRACK_HEIGHT];
+            rectangle([0.3, 0.3, 0.35, 1.0], track, ctx.transform, g);
+            let text = match value {
+                Some(value) => format!("{} {:.*}", slider.label, decimals(slider.step), value),
+                None => format!("{} -", slider.label),
+            };
+            labels.push((text, [left + MARGIN, top + 14.0]));
+            let Some(value) = value else {
+                continue;
+            };
+            let [low, high] = slider.range;
+            let filled = ((value - low) / (high - low)).clamp(0.0, 1.0) * track[2];
+            let color = if self.dragging == Some(i) { [1.0, 0.8, 0.2, 1.0] } else { [0.4, 0.7, 1.0, 1.0] };
+            rectangle(color, [track[0], track[1], filled, TRACK_HEIGHT], ctx.transform, g);
+            rectangle([1.0, 1.0, 1.0, 1.0], [track[0] + filled - 2.0, track[1] - 4.0, 4.0, TRACK_HEIGHT + 8.0], ctx.transform, g);
+        }
+        hud.draw_labels(&labels, ctx, g, device);
+    }
+
+    fn over_panel(&self, width: f64) -> bool {
+        let [x, y] = self.cursor;
+        let left = panel_left(width);
+        x >= left && x <= left + PANEL_WIDTH && y >= MARGIN / 2.0 && y <= MARGIN + SLIDERS.len() as f64 * ROW_HEIGHT
+    }
+
+    // Sets the parameter of the slider at `index` to the value under the
+    // cursor, adding a gravity force for gravity when there is none
+    fn set(&self, index: usize, world: &mut World, width: f64) {
+        let slider = &SLIDERS[index];
+        let [low, high] = slider.range;
+        let fraction = ((self.cursor[0] - panel_left(width) - MARGIN) / track_width()).clamp(0.0, 1.0);
+        let value = ((low + fraction * (high - low)) / slider.step).round() * slider.step;
+        if param_name(slider, world).is_none() {
+            world.add_force(Force::Gravity { acceleration: [0.0, 0.0] });
+        }
+        if let Some(name) = param_name(slider, world) {
+            // Every slider stays within the range of its parameter
+            let _ = ParamRegistry::set(world, &name, &format!("{:.*}", decimals(slider.step), value));
+        }
+    }
+}
+
+// The parameter a slider sets in `world`, none for gravity without a gravity force
+fn param_name(slider: &Slider, world: &World) -> Option<String> {
+    if slider.param != "gravity" {
+        return Some(slider.param.to_string());
+    }
+    let index = world.forces.iter().position(|force| force.kind() == "gravity")?;
+    Some(format!("force.{}.y", index))
+}
+
+fn panel_left(width: f64) -> f64 {
+    width - PANEL_WIDTH - MARGIN
+}
+
+fn track_width() -> f64 {
+    PANEL_WIDTH - 2.0 * MARGIN
+}
+
+// Decimals a value moving in steps of `step` is written with
+fn decimals(step: f64) -> usize {
+    (-step.log10()).ceil().max(0.0) as usize
+}
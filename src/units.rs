@@ -0,0 +1,148 @@
+use crate::BASE_TURN_RATE;
+use std::time::Duration;
+
+// Suffixes of sizes, both decimal (`MB`, a million bytes) and binary (`MiB`,
+// 2^20 bytes), read whatever their case
+const BYTE_UNITS: [(&str, u64); 9] = [
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("tib", 1 << 40),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("tb", 1_000_000_000_000),
+    ("b", 1),
+];
+
+// Suffixes of durations, in seconds
+const TIME_UNITS: [(&str, f64); 4] = [("ms", 0.001), ("min", 60.0), ("s", 1.0), ("h", 3600.0)];
+
+// Suffixes of rates, in the turns of `BASE_TURN_RATE` they are spread over
+const RATE_UNITS: [(&str, f64); 3] = [("turn", 1.0), ("s", BASE_TURN_RATE), ("min", 60.0 * BASE_TURN_RATE)];
+
+/// A size in bytes like `256MiB`, `1.5 GB` or `4096`, bare numbers being
+/// bytes.
+pub fn parse_bytes(text: &str) -> Result<u64, String> {
+    let (value, unit) = split_unit(text);
+    let scale = match unit {
+        "" => 1,
+        _ => lookup(&BYTE_UNITS, unit).ok_or_else(|| format!("unknown unit `{}` in `{}`, expected B, KiB, MiB, GiB, KB, MB or GB", unit, text))?,
+    };
+    let bytes = number(value, text)? * scale as f64;
+    if bytes > u64::MAX as f64 {
+        return Err(format!("`{}` is too large", text));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// A duration like `10s`, `500ms`, `2min` or `1h`. Its unit can't be left
+/// out, since bare numbers mean turns wherever durations are taken too.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (value, unit) = split_unit(text);
+    let scale = lookup(&TIME_UNITS, unit).ok_or_else(|| match unit {
+        "" => format!("`{}` needs a unit, like `{}s`", text, value),
+        _ => format!("unknown unit `{}` in `{}`, expected ms, s, min or h", unit, text),
+    })?;
+    let seconds = number(value, text)? * scale;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("`{}` is too long", text))
+}
+
+/// A rate like `500/s`, `30/min` or `2/turn` in particles per turn of
+/// `BASE_TURN_RATE`, a second being 60 of them whatever `--turn-rate` is.
+/// Bare numbers are already per turn.
+pub fn parse_rate(text: &str) -> Result<f64, String> {
+    let (count, per) = match text.split_once('/') {
+        Some((count, per)) => (count, per.trim()),
+        None => (text, "turn"),
+    };
+    let turns = lookup(&RATE_UNITS, per).ok_or_else(|| format!("unknown unit `/{}` in `{}`, expected /turn, /s or /min", per, text))?;
+    let count = count.trim().parse::<f64>().ok().filter(|count| count.is_finite());
+    count.map(|count| count / turns).ok_or_else(|| format!("expected a number of particles like `500/s`: `{}`", text))
+}
+
+/// A rate as `parse_rate` reads it, rounded to whole particles per turn,
+/// for the random spawns of `spawn_rate`.
+pub fn parse_spawn_rate(text: &str) -> Result<i32, String> {
+    let rate = parse_rate(text)?;
+    if rate < 0.0 || rate.round() > i32::MAX as f64 {
+        return Err(format!("`{}` is not a rate of 0 particles or more", text));
+    }
+    Ok(rate.round() as i32)
+}
+
+fn split_unit(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    let at = text.find(|c: char| c.is_alphabetic()).unwrap_or(text.len());
+    (text[..at].trim(), &text[at..])
+}
+
+fn lookup<T: Copy>(units: &[(&str, T)], unit: &str) -> Option<T> {
+    units.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)).map(|&(_, scale)| scale)
+}
+
+fn number(value: &str, text: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+        .ok_or_else(|| format!("expected a number of 0 or more before the unit: `{}`", text))
+}
+
+// Scene values written either as bare numbers, as before units, or as text
+// with one
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Quantity {
+    Number(f64),
+    Text(String),
+}
+
+/// Serde of sizes in bytes, written as numbers of bytes or as text like
+/// `"256MiB"`.
+pub mod bytes {
+    use super::Quantity;
+    use serde::{de, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+        let bytes = match Quantity::deserialize(deserializer)? {
+            Quantity::Number(bytes) if bytes >= 0.0 && bytes.fract() == 0.0 => bytes as u64,
+            Quantity::Number(bytes) => return Err(de::Error::custom(format!("{} is not a whole number of bytes", bytes))),
+            Quantity::Text(text) => super::parse_bytes(&text).map_err(de::Error::custom)?,
+        };
+        usize::try_from(bytes).map(Some).map_err(|_| de::Error::custom("size too large for this machine"))
+    }
+
+    /// The same for sizes that can't be left out.
+    pub fn required<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        deserialize(deserializer).map(Option::unwrap_or_default)
+    }
+}
+
+/// Serde of particles per turn, written as numbers or as text like `"30/s"`.
+pub mod rate {
+    use super::Quantity;
+    use serde::{de, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+        match Quantity::deserialize(deserializer)? {
+            Quantity::Number(rate) => Ok(Some(rate)),
+            Quantity::Text(text) => super::parse_rate(&text).map(Some).map_err(de::Error::custom),
+        }
+    }
+}
+
+/// Serde of `spawn_rate`, written as a whole number of particles per turn
+/// or as text like `"500/s"`, rounded to whole particles per turn.
+pub mod spawn_rate {
+    use super::Quantity;
+    use serde::{de, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+        match Quantity::deserialize(deserializer)? {
+            Quantity::Number(rate) if rate.fract() == 0.0 && rate.abs() <= i32::MAX as f64 => Ok(Some(rate as i32)),
+            Quantity::Number(rate) => Err(de::Error::custom(format!("{} is not a whole number of particles per turn", rate))),
+            Quantity::Text(text) => super::parse_spawn_rate(&text).map(Some).map_err(de::Error::custom),
+        }
+    }
+}
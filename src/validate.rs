@@ -0,0 +1,493 @@
+use crate::color::ColorSpec;
+use crate::config::{Config, ParticleSettings};
+use crate::controller::PopulationController;
+use crate::trail::MAX_TRAIL_LENGTH;
+use crate::fade::FadeModel;
+use crate::force::Force;
+use crate::migrate::{describe, migrate, Segment, SCHEMA_VERSION};
+use crate::motion::PathShape;
+use crate::noise::MAX_OCTAVES;
+use crate::obstacle::Obstacle;
+use crate::region::{self, SpawnRegion};
+use crate::shape::ShapeSpec;
+use crate::zone::{ZoneArea, ZoneForce};
+use std::fmt;
+use toml_edit::{ImDocument, Item, Value};
+
+/// Whether a problem keeps the scene from doing what it says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The scene doesn't load, or a setting is ignored or misread
+    Error,
+
+    /// Settings that load but cancel each other out
+    Warning,
+}
+
+/// Something wrong with a scene file, at the line it is on when known.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}", line, severity, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Reads a scene the way `Config::load` does and reports what the loading
+/// would reject or silently get wrong: syntax errors, keys that nothing
+/// reads, values out of their range and settings that have no effect
+/// together.
+pub fn validate(text: &str) -> Vec<Problem> {
+    let document = ImDocument::parse(text).ok();
+    let mut checker = Checker { text, document: document.as_ref(), problems: Vec::new() };
+
+    // Older scenes are checked the way they load, upgraded, though the lines
+    // are still those of the file. Syntax errors are left to the loading
+    // below, which knows their line
+    let mut upgraded = None;
+    if document.is_some() {
+        match migrate(text) {
+            Ok(migration) if migration.upgraded() => {
+                let message = format!(
+                    "the scene is written for version {} of the format, `config migrate` upgrades it to version {}",
+                    migration.from, SCHEMA_VERSION
+                );
+                checker.warning(&[], message);
+                for change in migration.changes {
+                    checker.warning(&change.path, change.message);
+                }
+                upgraded = Some(migration.text);
+            }
+            Ok(_) => {}
+            Err(message) => {
+                checker.error(&[Segment::Key("version".to_string())], message);
+                return checker.problems;
+            }
+        }
+    }
+
+    let mut ignored = Vec::new();
+    let source = upgraded.as_deref().unwrap_or(text);
+    let config: Config = match serde_ignored::deserialize(toml::Deserializer::new(source), |path| {
+        ignored.push(segments(&path))
+    }) {
+        Ok(config) => config,
+        Err(err) => {
+            let line = err.span().filter(|_| upgraded.is_none()).map(|span| line_at(text, span.start));
+            let message = err.message().trim().replace('\n', ", ");
+            checker.problems.push(Problem { severity: Severity::Error, line, message });
+            return checker.problems;
+        }
+    };
+
+    for path in ignored {
+        let name = describe(&path);
+        checker.error(&path, format!("unknown key {}", name));
+    }
+    checker.check(&config);
+    checker.problems.sort_by_key(|problem| problem.line);
+    checker.problems
+}
+
+struct Checker<'a> {
+    text: &'a str,
+    document: Option<&'a ImDocument<&'a str>>,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    fn check(&mut self, config: &Config) {
+        let key = |name: &str| vec![Segment::Key(name.to_string())];
+        let at = |parent: &[Segment], rest: &[&str]| {
+            let mut path = parent.to_vec();
+            path.extend(rest.iter().map(|name| Segment::Key(name.to_string())));
+            path
+        };
+
+        self.range(&key("drag"), config.drag, 0.0, 1.0);
+        self.at_least(&key("jitter"), config.jitter, 0.0);
+        if let Some(max_speed) = config.max_speed {
+            self.positive(&key("max_speed"), max_speed);
+        }
+        if let Some(spawn_rate) = config.spawn_rate {
+            self.at_least(&key("spawn_rate"), spawn_rate as f64, 0.0);
+        }
+        if let Some(initial) = config.initial_particles {
+            self.at_least(&key("initial_particles"), initial as f64, 0.0);
+        }
+
+        self.particle(&key("particle"), &config.particle);
+
+        self.color(&key("color"), &config.color);
+        if let Some(fade) = &config.fade {
+            self.fade(&key("fade"), fade);
+        }
+        if let Some(spawn_region) = &config.spawn_region {
+            self.spawn_region(&key("spawn_region"), spawn_region);
+            if self.item(&key("spawn_edge")).is_some() {
+                self.warning(&key("spawn_edge"), "spawn_edge is not used, since the scene has a spawn_region".to_string());
+            }
+        }
+        for (i, emitter) in config.emitters.iter().enumerate() {
+            let path = vec![Segment::Key("emitter".to_string()), Segment::Index(i)];
+            self.color(&at(&path, &["color"]), &emitter.color);
+            if let Some(fade) = &emitter.fade {
+                self.fade(&at(&path, &["fade"]), fade);
+            }
+            if let Some(max_speed) = emitter.max_speed {
+                self.positive(&at(&path, &["max_speed"]), max_speed);
+            }
+            if emitter.direction == [0.0, 0.0] {
+                self.error(&at(&path, &["direction"]), "direction must not be [0, 0]".to_string());
+            }
+            self.range(&at(&path, &["spread"]), emitter.spread, 0.0, 180.0);
+            if let Some(rate) = emitter.rate {
+                self.at_least(&at(&path, &["rate"]), rate, 0.0);
+            }
+            match (emitter.burst_every, emitter.rate) {
+                (Some(0), _) => self.error(&at(&path, &["burst_every"]), "burst_every must be at least 1".to_string()),
+                (Some(_), None) => {
+                    self.warning(&at(&path, &["burst_every"]), "burst_every only applies to emitters with a rate".to_string())
+                }
+                _ => {}
+            }
+            if let Some(particle) = &emitter.particle {
+                self.particle(&at(&path, &["particle"]), particle);
+            }
+            let mut stage = (at(&path, &["firework"]), emitter.firework.as_ref());
+            while let (path, Some(firework)) = stage {
+                self.interval(&at(&path, &["fuse"]), firework.fuse.map(|fuse| fuse as f64));
+                self.particle(&at(&path, &["particle"]), &firework.particle);
+                self.range(&at(&path, &["inherit"]), firework.inherit, 0.0, 1.0);
+                self.color(&at(&path, &["color"]), &firework.color);
+                if let Some(fade) = &firework.fade {
+                    self.fade(&at(&path, &["fade"]), fade);
+                }
+                stage = (at(&path, &["then"]), firework.then.as_deref());
+            }
+            // Paths take the keys of their shape too, so unknown keys go unnoticed when loading
+            if let Some(motion) = &emitter.path {
+                let known: &[&str] = match motion.shape {
+                    PathShape::Circle { .. } => &["center", "radius"],
+                    PathShape::Lissajous { .. } => &["center", "amplitude", "frequency", "phase"],
+                    PathShape::Waypoints { .. } => &["points"],
+                };
+                let path = at(&path, &["path"]);
+                for name in self.keys(&path) {
+                    if !["kind", "speed", "looping"].contains(&name.as_str()) && !known.contains(&name.as_str()) {
+                        let path = at(&path, &[&name]);
+                        self.error(&path, format!("unknown key {}", describe(&path)));
+                    }
+                }
+            }
+            match emitter.path.as_ref().map(|motion| &motion.shape) {
+                Some(PathShape::Circle { radius, .. }) => self.positive(&at(&path, &["path", "radius"]), *radius),
+                Some(PathShape::Waypoints { points }) if points.is_empty() => {
+                    self.error(&at(&path, &["path", "points"]), "a waypoint path needs at least one point".to_string())
+                }
+                _ => {}
+            }
+        }
+
+        for (i, obstacle) in config.obstacles.iter().enumerate() {
+            let path = vec![Segment::Key("obstacle".to_string()), Segment::Index(i)];
+            match obstacle {
+                Obstacle::Circle { radius, .. } => self.positive(&at(&path, &["radius"]), *radius),
+                Obstacle::Rect { size, .. } => self.size(&at(&path, &["size"]), *size),
+            }
+        }
+        for (i, zone) in config.zones.iter().enumerate() {
+            let path = vec![Segment::Key("zone".to_string()), Segment::Index(i)];
+            match &zone.area {
+                ZoneArea::Circle { radius, .. } => self.positive(&at(&path, &["area", "radius"]), *radius),
+                ZoneArea::Rect { size, .. } => self.size(&at(&path, &["area", "size"]), *size),
+            }
+            if let ZoneForce::Drag { factor } = zone.force {
+                self.range(&at(&path, &["force", "factor"]), factor, 0.0, 1.0);
+            }
+        }
+
+        for (i, force) in config.forces.iter().enumerate() {
+            let path = vec![Segment::Key("force".to_string()), Segment::Index(i)];
+            match *force {
+                Force::Gravity { .. } => {}
+                Force::Wind { gust, period, .. } => {
+                    self.at_least(&at(&path, &["gust"]), gust, 0.0);
+                    self.at_least(&at(&path, &["period"]), period as f64, 1.0);
+                }
+                Force::Attractor { radius, .. } => self.positive(&at(&path, &["radius"]), radius),
+                Force::Noise { frequency, speed, octaves, .. } => {
+                    self.positive(&at(&path, &["frequency"]), frequency);
+                    self.at_least(&at(&path, &["speed"]), speed, 0.0);
+                    self.range(&at(&path, &["octaves"]), octaves as f64, 1.0, MAX_OCTAVES as f64);
+                }
+            }
+        }
+
+        if let Some(sparks) = &config.sparks {
+            self.positive(&at(&key("sparks"), &["size"]), sparks.size);
+            self.range(&at(&key("sparks"), &["fade"]), sparks.fade as f64, 0.0, 1.0);
+            if sparks.count == 0 {
+                self.warning(&at(&key("sparks"), &["count"]), "sparks with a count of 0 are never thrown".to_string());
+            }
+            if config.obstacles.is_empty() && config.collisions.is_none() {
+                self.warning(&key("sparks"), "sparks are thrown at collisions, and the scene has neither obstacles nor `[collisions]`".to_string());
+            }
+        }
+        if let Some(controller) = &config.controller {
+            let path = key("controller");
+            let (gain, max_step) = match *controller {
+                PopulationController::Count { gain, max_step, .. } | PopulationController::Heap { gain, max_step, .. } => (gain, max_step),
+                PopulationController::Wave { mean, amplitude, period, gain, max_step } => {
+                    self.at_least(&at(&path, &["period"]), period as f64, 1.0);
+                    if amplitude > mean {
+                        self.warning(&at(&path, &["amplitude"]), "an amplitude over the mean holds the population at 0 for part of the wave".to_string());
+                    }
+                    (gain, max_step)
+                }
+            };
+            self.range(&at(&path, &["gain"]), gain, 0.0, 1.0);
+            self.at_least(&at(&path, &["max_step"]), max_step as f64, 1.0);
+            if config.spawn_rate.is_some() {
+                self.warning(&key("spawn_rate"), "spawn_rate is not used, since the scene has a controller".to_string());
+            }
+        }
+        if let Some(cap) = &config.soft_cap {
+            self.positive(&at(&key("soft_cap"), &["steepness"]), cap.steepness);
+            self.range(&at(&key("soft_cap"), &["max_rate"]), cap.max_rate, 0.0, 1.0);
+        }
+        if let Some(collisions) = &config.collisions {
+            self.range(&at(&key("collisions"), &["restitution"]), collisions.restitution, 0.0, 1.0);
+        }
+        if let Some(trail) = &config.trail {
+            self.range(&at(&key("trail"), &["length"]), trail.length as f64, 1.0, MAX_TRAIL_LENGTH as f64);
+            self.positive(&at(&key("trail"), &["width"]), trail.width);
+        }
+        if let Some(flocking) = &config.flocking {
+            self.positive(&at(&key("flocking"), &["radius"]), flocking.radius);
+            self.at_least(&at(&key("flocking"), &["separation_radius"]), flocking.separation_radius, 0.0);
+        }
+        if let Some([low, high]) = config.population.as_ref().and_then(|population| population.lifetime) {
+            let path = at(&key("population"), &["lifetime"]);
+            self.positive(&path, low as f64);
+            self.interval(&path, [low as f64, high as f64]);
+        }
+        if let Some(falloff) = &config.falloff {
+            self.positive(&at(&key("falloff"), &["sharpness"]), falloff.sharpness);
+        }
+        if let Some(ambient) = &config.ambient {
+            self.positive(&at(&key("ambient"), &["period"]), ambient.period as f64);
+        }
+        for (i, camera) in config.camera.iter().enumerate() {
+            let path = vec![Segment::Key("camera".to_string()), Segment::Index(i)];
+            self.positive(&at(&path, &["zoom"]), camera.zoom);
+        }
+
+        // Without emitters sharing the random spawns these settings are all that spawns, with them
+        // nothing reads them
+        if config.emitters.iter().any(|emitter| emitter.rate.is_none()) {
+            for name in ["spawn_edge", "spawn_region", "color", "fade", "blend"] {
+                if self.item(&key(name)).is_some() {
+                    self.warning(
+                        &key(name),
+                        format!(
+                            "{} only applies to particles spawned along the edge, and the scene has emitters without a rate",
+                            name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn particle(&mut self, path: &[Segment], particle: &ParticleSettings) {
+        let at = |name: &str| {
+            let mut path = path.to_vec();
+            path.push(Segment::Key(name.to_string()));
+            path
+        };
+        self.positive(&at("size"), particle.size);
+        self.interval(&at("speed"), particle.speed);
+        self.interval(&at("braking"), particle.braking);
+        self.range(&at("fade"), particle.fade, 0.0, 1.0);
+        if particle.shape == ShapeSpec::Any(Vec::new()) {
+            self.error(&at("shape"), "a list of shapes needs at least one shape".to_string());
+        }
+    }
+
+    fn spawn_region(&mut self, path: &[Segment], spawn_region: &SpawnRegion) {
+        let at = |name: &str| {
+            let mut path = path.to_vec();
+            path.push(Segment::Key(name.to_string()));
+            path
+        };
+        let spread = match spawn_region {
+            SpawnRegion::Edges { edges, spread, .. } => {
+                if edges.is_empty() {
+                    self.error(&at("edges"), "a spawn region of edges needs at least one edge".to_string());
+                }
+                spread
+            }
+            SpawnRegion::Area { spread, .. } => spread,
+            SpawnRegion::Rect { size, spread, .. } => {
+                self.size(&at("size"), *size);
+                spread
+            }
+            SpawnRegion::Polygon { points, spread, .. } => {
+                if points.len() < 3 {
+                    self.error(&at("points"), "a polygon needs at least three points".to_string());
+                } else if region::area(points) == 0.0 {
+                    self.error(&at("points"), "the polygon has no area, its points are all on a line".to_string());
+                }
+                spread
+            }
+        };
+        self.positive(&at("spread"), *spread);
+    }
+
+    fn color(&mut self, path: &[Segment], color: &ColorSpec) {
+        let mut channels = |name: &str, values: &[f32]| {
+            for &value in values {
+                let mut path = path.to_vec();
+                path.push(Segment::Key(name.to_string()));
+                self.range(&path, value as f64, 0.0, 1.0);
+            }
+        };
+        match color {
+            ColorSpec::Fixed { color } => channels("color", color),
+            ColorSpec::Palette { colors } if colors.is_empty() => {
+                self.error(path, "a palette needs at least one color".to_string())
+            }
+            ColorSpec::Palette { colors } => channels("colors", colors.as_flattened()),
+            ColorSpec::Hsv { saturation, value, alpha, .. } => {
+                channels("saturation", saturation);
+                channels("value", value);
+                channels("alpha", &[*alpha]);
+            }
+        }
+    }
+
+    fn fade(&mut self, path: &[Segment], fade: &FadeModel) {
+        let mut path = path.to_vec();
+        match *fade {
+            FadeModel::Exponential { half_life } => {
+                path.push(Segment::Key("half_life".to_string()));
+                self.positive(&path, half_life);
+            }
+            FadeModel::Linear { lifetime } => {
+                path.push(Segment::Key("lifetime".to_string()));
+                self.positive(&path, lifetime as f64);
+            }
+            FadeModel::Step { .. } => {}
+        }
+    }
+
+    fn range(&mut self, path: &[Segment], value: f64, low: f64, high: f64) {
+        if !(low..=high).contains(&value) {
+            self.error(path, format!("{} must be between {} and {}, not {}", describe(path), low, high, value));
+        }
+    }
+
+    fn at_least(&mut self, path: &[Segment], value: f64, low: f64) {
+        if value < low || value.is_nan() {
+            self.error(path, format!("{} must be at least {}, not {}", describe(path), low, value));
+        }
+    }
+
+    fn positive(&mut self, path: &[Segment], value: f64) {
+        if value <= 0.0 || value.is_nan() {
+            self.error(path, format!("{} must be more than 0, not {}", describe(path), value));
+        }
+    }
+
+    fn size(&mut self, path: &[Segment], [width, height]: [f64; 2]) {
+        if width <= 0.0 || height <= 0.0 || width.is_nan() || height.is_nan() {
+            self.error(path, format!("{} must be more than 0 both ways, not [{}, {}]", describe(path), width, height));
+        }
+    }
+
+    fn interval(&mut self, path: &[Segment], [low, high]: [f64; 2]) {
+        if low < 0.0 || low > high || high.is_nan() {
+            self.error(path, format!("{} must go from a value of at least 0 up, not [{}, {}]", describe(path), low, high));
+        }
+    }
+
+    fn error(&mut self, path: &[Segment], message: String) {
+        self.push(Severity::Error, path, message);
+    }
+
+    fn warning(&mut self, path: &[Segment], message: String) {
+        self.push(Severity::Warning, path, message);
+    }
+
+    fn push(&mut self, severity: Severity, path: &[Segment], message: String) {
+        let line = self.span(path).map(|start| line_at(self.text, start));
+        self.problems.push(Problem { severity, line, message });
+    }
+
+    fn item(&self, path: &[Segment]) -> Option<&Item> {
+        let mut item = self.document?.as_item();
+        for segment in path {
+            item = match segment {
+                Segment::Key(key) => item.get(key.as_str())?,
+                Segment::Index(index) => item.get(*index)?,
+            };
+        }
+        Some(item)
+    }
+
+    fn keys(&self, path: &[Segment]) -> Vec<String> {
+        let table = self.item(path).and_then(Item::as_table_like);
+        table.map_or(Vec::new(), |table| table.iter().map(|(key, _)| key.to_string()).collect())
+    }
+
+    // Start of the deepest part of the path found in the file, so a missing
+    // key is reported where its table is
+    fn span(&self, path: &[Segment]) -> Option<usize> {
+        (0..=path.len()).rev().find_map(|len| {
+            let item = self.item(&path[..len])?;
+            match item {
+                Item::Value(Value::InlineTable(table)) => table.span(),
+                Item::Table(table) => table.span(),
+                Item::ArrayOfTables(tables) => tables.span(),
+                item => item.span(),
+            }
+            .map(|span| span.start)
+        })
+    }
+}
+
+fn segments(path: &serde_ignored::Path) -> Vec<Segment> {
+    use serde_ignored::Path;
+
+    let mut segments = match path {
+        Path::Root => return Vec::new(),
+        Path::Seq { parent, .. }
+        | Path::Map { parent, .. }
+        | Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => segments(parent),
+    };
+    match path {
+        Path::Seq { index, .. } => segments.push(Segment::Index(*index)),
+        Path::Map { key, .. } => segments.push(Segment::Key(key.clone())),
+        _ => {}
+    }
+    segments
+}
+
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
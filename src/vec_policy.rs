@@ -0,0 +1,67 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// When the world hands the spare room of its particle list back to the
+/// allocator, written as `--vec-policy` takes it: `prealloc:5000`,
+/// `shrink-every:60` or `never-shrink`. However the list shrinks, it never
+/// does below the room made by `World::reserve`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoragePolicy {
+    /// Makes room for `n` particles up front, and shrinks the list back to
+    /// that every turn it holds more room than particles, never below
+    Prealloc(usize),
+
+    /// Shrinks the list to its length every `k` turns, every turn by
+    /// default: a reallocation whenever the population dropped since
+    ShrinkEvery(u64),
+
+    /// Never shrinks it, the list keeping the room of its largest population
+    NeverShrink,
+}
+
+impl Default for StoragePolicy {
+    fn default() -> StoragePolicy {
+        StoragePolicy::ShrinkEvery(1)
+    }
+}
+
+impl StoragePolicy {
+    /// Whether the list is shrunk on `turn`.
+    pub fn shrinks_on(&self, turn: u64) -> bool {
+        match *self {
+            StoragePolicy::Prealloc(_) => true,
+            StoragePolicy::ShrinkEvery(turns) => turn.is_multiple_of(turns.max(1)),
+            StoragePolicy::NeverShrink => false,
+        }
+    }
+}
+
+impl FromStr for StoragePolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<StoragePolicy, String> {
+        let text = text.trim();
+        if text == "never-shrink" {
+            return Ok(StoragePolicy::NeverShrink);
+        }
+        let expected = || format!("expected `prealloc:N`, `shrink-every:K` or `never-shrink`: {}", text);
+        let (kind, count) = text.split_once(':').ok_or_else(expected)?;
+        let count: u64 = count.trim().parse().map_err(|_| expected())?;
+        match kind {
+            "prealloc" => Ok(StoragePolicy::Prealloc(count as usize)),
+            "shrink-every" if count == 0 => Err(format!("shrink-every takes a number of turns of at least 1: {}", text)),
+            "shrink-every" => Ok(StoragePolicy::ShrinkEvery(count)),
+            _ => Err(expected()),
+        }
+    }
+}
+
+impl fmt::Display for StoragePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoragePolicy::Prealloc(particles) => write!(f, "prealloc:{}", particles),
+            StoragePolicy::ShrinkEvery(turns) => write!(f, "shrink-every:{}", turns),
+            StoragePolicy::NeverShrink => write!(f, "never-shrink"),
+        }
+    }
+}
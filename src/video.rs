@@ -0,0 +1,177 @@
+use crate::capture::Canvas;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+
+// Frames drawn ahead of the encoder before the run waits for it
+const QUEUED_FRAMES: usize = 8;
+
+// Quantization of GIF frames, from 1, best and slowest, to 30
+const GIF_SPEED: i32 = 10;
+
+/// What `--record` writes when its file is an animation rather than a
+/// recording for `play`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// An animated GIF, encoded here, looping
+    Gif,
+
+    /// Anything ffmpeg writes, like MP4 or WebM, the frames being piped to it
+    Ffmpeg,
+}
+
+impl VideoFormat {
+    /// The format of `path` by its extension, none for recordings.
+    pub fn of(path: &Path) -> Option<VideoFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(VideoFormat::Gif),
+            "mp4" | "webm" | "mkv" | "mov" | "avi" => Some(VideoFormat::Ffmpeg),
+            _ => None,
+        }
+    }
+}
+
+/// Draws a frame every so many turns for `--record out.gif`, at
+/// `--record-fps` frames per second of simulated time for `--record-seconds`,
+/// and encodes them on a thread of its own. Frames are counted in turns, not
+/// in real time, so the animation plays at the speed of the simulation
+/// however fast the run went, and seeded runs give the same one.
+pub struct VideoRecorder {
+    width: usize,
+    height: usize,
+    every: u64,
+    turns: u64,
+    frames_left: u64,
+    frames: Option<SyncSender<Vec<u8>>>,
+    encoder: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl VideoRecorder {
+    /// Starts encoding frames of `width` by `height` pixels to `path`, for
+    /// a world running `turn_rate` turns a second.
+    pub fn create(
+        path: &Path,
+        format: VideoFormat,
+        size: [usize; 2],
+        fps: u32,
+        seconds: f64,
+        turn_rate: u32,
+    ) -> io::Result<VideoRecorder> {
+        let [width, height] = size;
+        if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(io::Error::other(format!("frames of {}x{} can't be encoded", width, height)));
+        }
+        let fps = fps.clamp(1, turn_rate.max(1));
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUED_FRAMES);
+        let encoder = match format {
+            VideoFormat::Gif => {
+                let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), width as u16, height as u16, &[])
+                    .map_err(io::Error::other)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+                let delay = (100 / fps).max(1) as u16;
+                thread::Builder::new().name("video".to_string()).spawn(move || {
+                    for mut rgba in receiver {
+                        let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut rgba, GIF_SPEED);
+                        frame.delay = delay;
+                        encoder.write_frame(&frame).map_err(io::Error::other)?;
+                    }
+                    encoder.into_inner().map_err(io::Error::other)?.flush()
+                })?
+            }
+            VideoFormat::Ffmpeg => {
+                let mut ffmpeg = spawn_ffmpeg(path, width, height, fps)?;
+                let mut stdin = ffmpeg.stdin.take().ok_or_else(|| io::Error::other("ffmpeg has no input"))?;
+                thread::Builder::new().name("video".to_string()).spawn(move || {
+                    let written = receiver.into_iter().try_for_each(|rgba| stdin.write_all(&rgba));
+                    drop(stdin);
+                    let status = ffmpeg.wait()?;
+                    written?;
+                    match status.success() {
+                        true => Ok(()),
+                        false => Err(io::Error::other(format!("ffmpeg failed with {}", status))),
+                    }
+                })?
+            }
+        };
+        Ok(VideoRecorder {
+            width,
+            height,
+            every: (turn_rate.max(1) / fps) as u64,
+            turns: 0,
+            frames_left: (seconds.max(0.0) * fps as f64).ceil() as u64,
+            frames: Some(sender),
+            encoder: Some(encoder),
+        })
+    }
+
+    /// Counts a turn, and says whether a frame is due on it.
+    pub fn turn(&mut self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        let due = self.turns.is_multiple_of(self.every);
+        self.turns += 1;
+        due
+    }
+
+    /// Whether every frame asked for was taken.
+    pub fn is_done(&self) -> bool {
+        self.frames_left == 0 || self.frames.is_none()
+    }
+
+    /// Passes `canvas` to the encoder, waiting for it when it is behind, so
+    /// no frame is lost. Once every frame is taken the encoder is let finish.
+    pub fn push(&mut self, canvas: &Canvas) -> io::Result<()> {
+        let Some(frames) = &self.frames else {
+            return Ok(());
+        };
+        if frames.send(canvas.rgba()).is_err() {
+            self.frames = None;
+            return self.finish_encoder();
+        }
+        self.frames_left -= 1;
+        if self.frames_left == 0 {
+            self.frames = None;
+        }
+        Ok(())
+    }
+
+    /// The size of the frames, that of the world when recording started.
+    pub fn size(&self) -> [usize; 2] {
+        [self.width, self.height]
+    }
+
+    /// Waits for the encoder to write the last frames and close the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.frames = None;
+        self.finish_encoder()
+    }
+
+    fn finish_encoder(&mut self) -> io::Result<()> {
+        match self.encoder.take().map(JoinHandle::join) {
+            Some(Ok(written)) => written,
+            Some(Err(_)) => Err(io::Error::other("the encoder panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+// ffmpeg reading raw RGBA frames from its input, padded to even sizes for
+// the encoders of yuv420p
+fn spawn_ffmpeg(path: &Path, width: usize, height: usize, fps: u32) -> io::Result<Child> {
+    Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", width, height), "-r", &fps.to_string(), "-i", "-"])
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::other("ffmpeg isn't installed, which videos other than GIF need"),
+            _ => err,
+        })
+}
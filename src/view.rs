@@ -0,0 +1,57 @@
+use graphics::math::Vec2d;
+use graphics::{Context, Transformed};
+
+// Zoom of the view is kept between these, short of losing the world in a
+// pixel or a particle filling the window
+const MIN_SCALE: f64 = 0.1;
+const MAX_SCALE: f64 = 50.0;
+
+/// How much closer a notch of the scroll wheel brings the view.
+pub const ZOOM_STEP: f64 = 1.15;
+
+/// The view of the window the user zooms with the scroll wheel and pans by
+/// dragging with Ctrl, on top of the camera of the scene: a point drawn at
+/// `p` is shown at `offset + p * scale`. Only the window has one; frame
+/// captures and thumbnails show the world as the scene does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform2D {
+    pub offset: Vec2d,
+    pub scale: f64,
+}
+
+impl Default for Transform2D {
+    fn default() -> Transform2D {
+        Transform2D { offset: [0.0, 0.0], scale: 1.0 }
+    }
+}
+
+impl Transform2D {
+    pub fn is_identity(&self) -> bool {
+        *self == Transform2D::default()
+    }
+
+    /// `ctx` drawing through the view.
+    pub fn apply(&self, ctx: Context) -> Context {
+        ctx.trans(self.offset[0], self.offset[1]).zoom(self.scale)
+    }
+
+    /// Where `point` of the window was drawn before the view moved it, the
+    /// inverse of `apply`.
+    pub fn to_local(&self, point: Vec2d) -> Vec2d {
+        [0, 1].map(|i| (point[i] - self.offset[i]) / self.scale)
+    }
+
+    /// Zooms by `factor` around `point` of the window, which stays over the
+    /// same part of the world.
+    pub fn zoom_at(&mut self, point: Vec2d, factor: f64) {
+        let scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        let ratio = scale / self.scale;
+        self.offset = [0, 1].map(|i| point[i] - (point[i] - self.offset[i]) * ratio);
+        self.scale = scale;
+    }
+
+    /// Moves what is shown by `delta` pixels of the window.
+    pub fn pan(&mut self, delta: Vec2d) {
+        self.offset = [0, 1].map(|i| self.offset[i] + delta[i]);
+    }
+}
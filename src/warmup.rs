@@ -0,0 +1,35 @@
+use crate::units;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How long the simulation runs before statistics and telemetry are captured,
+/// written as a number of turns (`300`) or a duration (`5s`, `500ms`, `2min`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warmup {
+    Turns(u64),
+    Time(Duration),
+}
+
+impl Warmup {
+    pub fn is_over(&self, turns: u64, elapsed: Duration) -> bool {
+        match *self {
+            Warmup::Turns(n) => turns >= n,
+            Warmup::Time(duration) => elapsed >= duration,
+        }
+    }
+}
+
+impl FromStr for Warmup {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Warmup, String> {
+        let text = text.trim();
+        if let Ok(turns) = text.parse() {
+            return Ok(Warmup::Turns(turns));
+        }
+        if !text.contains(|c: char| c.is_alphabetic()) {
+            return Err(format!("expected a number of turns or a duration like `5s`: {}", text));
+        }
+        units::parse_duration(text).map(Warmup::Time)
+    }
+}
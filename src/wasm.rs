@@ -0,0 +1,163 @@
+//! Browser interface to the simulation and the allocator counters, through
+//! wasm-bindgen, for the page in `www/`. Build it with
+//! `cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`,
+//! then `wasm-bindgen --target web --out-dir www/pkg` on the `.wasm` file.
+//!
+//! The page draws the particles itself, on a 2D canvas, from the buffer
+//! `WasmWorld::particles` fills in the module's memory, so a frame copies
+//! nothing across to JavaScript but the numbers.
+
+use crate::allocator::{memory_stats, set_capturing};
+use crate::config::Config;
+use crate::{World, INITIAL_PARTICLES};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wasm_bindgen::prelude::*;
+
+// Numbers `WasmWorld::particles` writes per particle: x, y, width, height,
+// then the color
+const PARTICLE_FLOATS: usize = 8;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(line: &str);
+}
+
+/// A world of the page, stepped and read by its animation frames.
+#[wasm_bindgen]
+pub struct WasmWorld {
+    world: World,
+
+    // The particles as the page reads them, kept from frame to frame so
+    // reading them allocates nothing once it has room for them all
+    particles: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl WasmWorld {
+    /// A world of `width` by `height` pixels from the scene file `scene`,
+    /// the default scene when empty, with its initial particles spawned from
+    /// `seed`. Allocations are counted but not reported one by one.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: f64, height: f64, seed: u32, scene: &str) -> Result<WasmWorld, String> {
+        set_capturing(false);
+        let config = match scene.trim() {
+            "" => Config::default(),
+            scene => Config::from_toml(scene).map_err(|err| err.to_string())?,
+        };
+        let initial_particles = config.initial_particles.unwrap_or(INITIAL_PARTICLES);
+        let mut world = World::from_config(width, height, config);
+        world.rng = StdRng::seed_from_u64(seed as u64);
+        world.add_shapes(initial_particles);
+        Ok(WasmWorld { world, particles: Vec::new() })
+    }
+
+    /// Runs `turns` turns of the world.
+    pub fn step(&mut self, turns: u32) {
+        for _ in 0..turns {
+            self.world.update();
+        }
+    }
+
+    /// Turns the world has run.
+    pub fn turn(&self) -> f64 {
+        self.world.current_turn as f64
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.world.particles.len()
+    }
+
+    /// Follows the canvas to its new size, as the window does.
+    pub fn resize(&mut self, width: f64, height: f64) {
+        self.world.resize(width, height);
+    }
+
+    /// The background of the current turn, as red, green, blue and alpha.
+    pub fn background(&self) -> Vec<f32> {
+        self.world.background(self.world.current_turn).to_vec()
+    }
+
+    /// Writes the particles to the module's memory, eight numbers each in
+    /// the order they are drawn, and returns where they start, for a
+    /// `Float32Array` over `memory.buffer` of `particle_count() * 8` numbers.
+    pub fn particles(&mut self) -> *const f32 {
+        self.particles.clear();
+        for particle in &self.world.particles {
+            self.particles.extend_from_slice(&[
+                particle.position[0] as f32,
+                particle.position[1] as f32,
+                particle.width as f32,
+                particle.height as f32,
+            ]);
+            self.particles.extend_from_slice(&particle.color);
+        }
+        debug_assert_eq!(self.particles.len(), self.world.particles.len() * PARTICLE_FLOATS);
+        self.particles.as_ptr()
+    }
+}
+
+/// The counters of `allocator::memory_stats`, as numbers JavaScript takes.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct WasmMemoryStats {
+    pub live_bytes: f64,
+    pub peak_live_bytes: f64,
+    pub allocations: f64,
+    pub allocated_bytes: f64,
+    pub deallocations: f64,
+    pub freed_bytes: f64,
+}
+
+/// The allocator counters of the whole module.
+#[wasm_bindgen]
+pub fn wasm_memory_stats() -> WasmMemoryStats {
+    let stats = memory_stats();
+    WasmMemoryStats {
+        live_bytes: stats.live_bytes as f64,
+        peak_live_bytes: stats.peak_live_bytes as f64,
+        allocations: stats.allocations as f64,
+        allocated_bytes: stats.allocated_bytes as f64,
+        deallocations: stats.deallocations as f64,
+        freed_bytes: stats.freed_bytes as f64,
+    }
+}
+
+/// Writes the allocator counters to the console, one line.
+#[wasm_bindgen]
+pub fn log_memory_stats() {
+    let stats = memory_stats();
+    log(&format!(
+        "live {} B, peak {} B, {} allocations of {} B, {} frees",
+        stats.live_bytes, stats.peak_live_bytes, stats.allocations, stats.allocated_bytes, stats.deallocations
+    ));
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// What the allocator times allocations with in the browser, where
+/// `std::time::Instant` panics: the milliseconds of `performance.now()`.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Instant(f64);
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant(performance_now())
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(((self.0 - earlier.0) / 1e3).max(0.0))
+    }
+}
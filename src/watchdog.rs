@@ -0,0 +1,194 @@
+use crate::dashboard::format_bytes;
+use crate::frame::{Phase, PhaseTimes, PHASES};
+use memory_particles::allocator::memory_stats;
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// How often the watchdog looks at the event loop, at most
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// Phase of the window until its event loop starts, from its creation on
+const STARTING: u8 = u8::MAX;
+
+// What the event loop last did, written with atomics only so keeping it up
+// to date never allocates, nor slows down a frame
+struct Heartbeat {
+    started: Instant,
+    frames: AtomicU64,
+    turn: AtomicU64,
+
+    // Nanoseconds since `started`
+    last_frame: AtomicU64,
+    phase_since: AtomicU64,
+    phase: AtomicU8,
+
+    // Nanoseconds the last complete frame spent in each phase
+    last_phases: [AtomicU64; PHASES.len()],
+    stopping: AtomicBool,
+}
+
+impl Heartbeat {
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_nanos() as u64
+    }
+}
+
+/// Notices when the window goes `timeout` without completing a frame, like
+/// when the GL driver of a virtual machine hangs, and prints where the event
+/// loop was stuck, how long the last frame spent in each phase, the state of
+/// the allocator and the stacks of all threads when a debugger can get them.
+/// With `abort`, the process is then aborted so it leaves a core dump.
+///
+/// The event loop reports its phases and frames whether or not the watchdog
+/// runs, which costs a few atomic writes.
+pub struct Watchdog {
+    heartbeat: Arc<Heartbeat>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn start(timeout: Option<Duration>, abort: bool) -> io::Result<Watchdog> {
+        let heartbeat = Arc::new(Heartbeat {
+            started: Instant::now(),
+            frames: AtomicU64::new(0),
+            turn: AtomicU64::new(0),
+            last_frame: AtomicU64::new(0),
+            phase_since: AtomicU64::new(0),
+            phase: AtomicU8::new(STARTING),
+            last_phases: [const { AtomicU64::new(0) }; PHASES.len()],
+            stopping: AtomicBool::new(false),
+        });
+        let thread = match timeout {
+            Some(timeout) => {
+                let watched = Arc::clone(&heartbeat);
+                let spawned = thread::Builder::new().name("watchdog".to_string()).spawn(move || watch(&watched, timeout, abort));
+                Some(spawned?)
+            }
+            None => None,
+        };
+        Ok(Watchdog { heartbeat, thread })
+    }
+
+    /// The event loop starts a phase of the frame.
+    pub fn enter(&self, phase: Phase) {
+        self.heartbeat.phase_since.store(self.heartbeat.now(), Ordering::Relaxed);
+        self.heartbeat.phase.store(phase as u8, Ordering::Relaxed);
+    }
+
+    /// The event loop completed a frame, at `turn`.
+    pub fn frame_done(&self, phases: &PhaseTimes, turn: u64) {
+        for phase in PHASES {
+            self.heartbeat.last_phases[phase as usize].store(phases.get(phase).as_nanos() as u64, Ordering::Relaxed);
+        }
+        self.heartbeat.turn.store(turn, Ordering::Relaxed);
+        self.heartbeat.frames.fetch_add(1, Ordering::Relaxed);
+        self.heartbeat.last_frame.store(self.heartbeat.now(), Ordering::Release);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.heartbeat.stopping.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(heartbeat: &Heartbeat, timeout: Duration, abort: bool) {
+    // Every stall is reported once, by the frame count it stalled at
+    let mut reported = None;
+    while !heartbeat.stopping.load(Ordering::Acquire) {
+        thread::park_timeout(CHECK_INTERVAL.min(timeout / 4));
+        let frames = heartbeat.frames.load(Ordering::Relaxed);
+        let last_frame = Duration::from_nanos(heartbeat.last_frame.load(Ordering::Acquire));
+        let stalled = heartbeat.started.elapsed().saturating_sub(last_frame);
+        if stalled < timeout || reported == Some(frames) || heartbeat.stopping.load(Ordering::Acquire) {
+            continue;
+        }
+        reported = Some(frames);
+        report(heartbeat, stalled);
+        if abort {
+            eprintln!("watchdog: aborting");
+            process::abort();
+        }
+    }
+}
+
+fn report(heartbeat: &Heartbeat, stalled: Duration) {
+    let millis = |nanos: u64| nanos as f64 / 1e6;
+    let frames = heartbeat.frames.load(Ordering::Relaxed);
+    if frames == 0 {
+        eprintln!("watchdog: no frame completed in the {:.1} s since the window was started", stalled.as_secs_f64());
+    } else {
+        eprintln!(
+            "watchdog: no frame completed for {:.1} s, since frame {} at turn {}",
+            stalled.as_secs_f64(),
+            frames,
+            heartbeat.turn.load(Ordering::Relaxed)
+        );
+    }
+
+    let in_phase = heartbeat.now().saturating_sub(heartbeat.phase_since.load(Ordering::Relaxed)) as f64 / 1e9;
+    match PHASES.get(heartbeat.phase.load(Ordering::Relaxed) as usize) {
+        Some(phase) => {
+            let side = if phase.is_engine() { "in Piston" } else { "in this crate" };
+            eprintln!("watchdog: in {} for {:.1} s, {}", phase.name(), in_phase, side);
+        }
+        None => eprintln!("watchdog: still creating the window or setting up its event loop"),
+    }
+
+    if frames > 0 {
+        let phases: Vec<String> = PHASES
+            .iter()
+            .map(|&phase| format!("{} {:.1}", phase.name(), millis(heartbeat.last_phases[phase as usize].load(Ordering::Relaxed))))
+            .collect();
+        eprintln!("watchdog: ms per phase of the last frame: {}", phases.join(", "));
+    }
+
+    let stats = memory_stats();
+    eprintln!(
+        "watchdog: heap {} live (peak {}), {} allocations and {} frees so far, {} vec reallocations",
+        format_bytes(stats.live_bytes as u64),
+        format_bytes(stats.peak_live_bytes as u64),
+        stats.allocations,
+        stats.deallocations,
+        stats.vec_reallocs
+    );
+    print_stacks();
+}
+
+// Only a debugger can read the stacks of the other threads; gdb is used
+// when it is installed
+#[cfg(unix)]
+fn print_stacks() {
+    let pid = process::id();
+
+    // Where ptrace is limited to descendants, the debugger needs leave to
+    // attach to its parent
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::prctl(libc::PR_SET_PTRACER, libc::PR_SET_PTRACER_ANY, 0, 0, 0);
+    }
+    let output = process::Command::new("gdb")
+        .args(["-p", &pid.to_string(), "-batch", "-nx", "-ex", "thread apply all bt"])
+        .stdin(process::Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            eprintln!("watchdog: stacks of all threads:");
+            eprint!("{}", String::from_utf8_lossy(&output.stdout));
+        }
+        _ => eprintln!("watchdog: no stacks, since gdb could not attach; try `gdb -p {} -batch -ex 'thread apply all bt'`", pid),
+    }
+}
+
+#[cfg(not(unix))]
+fn print_stacks() {
+    eprintln!("watchdog: no stacks on this platform; attach a debugger to process {}", process::id());
+}
@@ -0,0 +1,118 @@
+use crate::params::{ParamVisitor, Tunable};
+use graphics::math::Vec2d;
+use serde::Deserialize;
+
+/// Region of the scene a zone covers.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ZoneArea {
+    Circle {
+        center: Vec2d<f64>,
+        radius: f64,
+    },
+    Rect {
+        position: Vec2d<f64>,
+        size: Vec2d<f64>,
+    },
+}
+
+impl ZoneArea {
+    pub fn contains(&self, point: Vec2d<f64>) -> bool {
+        match self {
+            ZoneArea::Circle { center, radius } => {
+                let (dx, dy) = (point[0] - center[0], point[1] - center[1]);
+                dx * dx + dy * dy <= radius * radius
+            }
+            ZoneArea::Rect { position, size } => {
+                point[0] >= position[0]
+                    && point[0] <= position[0] + size[0]
+                    && point[1] >= position[1]
+                    && point[1] <= position[1] + size[1]
+            }
+        }
+    }
+
+    pub fn center(&self) -> Vec2d<f64> {
+        match self {
+            ZoneArea::Circle { center, .. } => *center,
+            ZoneArea::Rect { position, size } => [position[0] + size[0] / 2.0, position[1] + size[1] / 2.0],
+        }
+    }
+}
+
+/// What a zone does to the particles inside it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ZoneForce {
+    /// Same push everywhere in the zone, like a wind tunnel or an accelerator strip
+    Constant { vector: Vec2d<f64> },
+
+    /// Pushes away from the center of the zone, or pulls towards it when negative
+    Radial { strength: f64 },
+
+    /// Removes this fraction of the velocity every turn; `1.0` makes a dead zone
+    Drag { factor: f64 },
+}
+
+impl ZoneForce {
+    /// Name of the force as written in scene files.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ZoneForce::Constant { .. } => "constant",
+            ZoneForce::Radial { .. } => "radial",
+            ZoneForce::Drag { .. } => "drag",
+        }
+    }
+}
+
+impl Tunable for ZoneForce {
+    fn visit_params(&mut self, visitor: &mut dyn ParamVisitor) {
+        match self {
+            ZoneForce::Constant { vector } => {
+                visitor.float("x", &mut vector[0], -10.0..=10.0);
+                visitor.float("y", &mut vector[1], -10.0..=10.0);
+            }
+            ZoneForce::Radial { strength } => visitor.float("strength", strength, -10.0..=10.0),
+            ZoneForce::Drag { factor } => visitor.float("factor", factor, 0.0..=1.0),
+        }
+    }
+}
+
+/// A region of the scene that applies a force only to the particles inside it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Zone {
+    pub area: ZoneArea,
+    pub force: ZoneForce,
+}
+
+impl Zone {
+    /// Changes the velocity of a particle at `position` if it is inside the zone.
+    /// Zones are applied one after the other, so overlapping zones add up.
+    pub fn apply(&self, position: Vec2d<f64>, velocity: &mut Vec2d<f64>) {
+        if !self.area.contains(position) {
+            return;
+        }
+
+        match self.force {
+            ZoneForce::Constant { vector } => {
+                velocity[0] += vector[0];
+                velocity[1] += vector[1];
+            }
+            ZoneForce::Radial { strength } => {
+                let center = self.area.center();
+                let offset = [position[0] - center[0], position[1] - center[1]];
+                let distance = (offset[0] * offset[0] + offset[1] * offset[1]).sqrt();
+                if distance > 0.0 {
+                    velocity[0] += offset[0] / distance * strength;
+                    velocity[1] += offset[1] / distance * strength;
+                }
+            }
+            ZoneForce::Drag { factor } => {
+                let keep = 1.0 - factor.clamp(0.0, 1.0);
+                velocity[0] *= keep;
+                velocity[1] *= keep;
+            }
+        }
+    }
+}
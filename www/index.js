@@ -0,0 +1,83 @@
+// Runs the world of the wasm module and draws it on the canvas, with the
+// allocator counters in the panel and, once a second, in the console.
+// Built into pkg/ as the README describes; Space pauses, as in the window.
+
+import init, { WasmWorld, wasm_memory_stats, log_memory_stats } from "./pkg/memory_particles.js";
+
+// Turns a second, as `--turn-rate` defaults to
+const TURN_RATE = 60;
+
+// Numbers a particle takes in the buffer of `WasmWorld.particles`
+const PARTICLE_FLOATS = 8;
+
+const { memory } = await init();
+const canvas = document.getElementById("world");
+const stats = document.getElementById("stats");
+const ctx = canvas.getContext("2d");
+
+const params = new URLSearchParams(location.search);
+const seed = Number(params.get("seed") ?? 1);
+const scene = params.has("scene") ? await (await fetch(params.get("scene"))).text() : "";
+
+canvas.width = canvas.clientWidth;
+canvas.height = canvas.clientHeight;
+const world = new WasmWorld(canvas.width, canvas.height, seed, scene);
+
+addEventListener("resize", () => {
+  canvas.width = canvas.clientWidth;
+  canvas.height = canvas.clientHeight;
+  world.resize(canvas.width, canvas.height);
+});
+
+let paused = false;
+addEventListener("keydown", (event) => {
+  if (event.code === "Space") {
+    paused = !paused;
+  }
+});
+
+const color = (r, g, b, a) => `rgba(${r * 255}, ${g * 255}, ${b * 255}, ${a})`;
+
+// Time not yet spent in whole turns, like the window's turn clock
+let behind = 0;
+let last = performance.now();
+let logged = last;
+
+function frame(now) {
+  if (!paused) {
+    behind = Math.min(behind + (now - last) / 1000, 5 / TURN_RATE);
+    const turns = Math.floor(behind * TURN_RATE);
+    behind -= turns / TURN_RATE;
+    world.step(turns);
+  }
+  last = now;
+
+  const [r, g, b] = world.background();
+  ctx.fillStyle = color(r, g, b, 1);
+  ctx.fillRect(0, 0, canvas.width, canvas.height);
+
+  // A view of the module's memory, made after `particles`, which may grow it
+  const count = world.particle_count();
+  const particles = new Float32Array(memory.buffer, world.particles(), count * PARTICLE_FLOATS);
+  for (let i = 0; i < particles.length; i += PARTICLE_FLOATS) {
+    ctx.fillStyle = color(particles[i + 4], particles[i + 5], particles[i + 6], particles[i + 7]);
+    ctx.fillRect(particles[i], particles[i + 1], particles[i + 2], particles[i + 3]);
+  }
+
+  const heap = wasm_memory_stats();
+  stats.textContent = [
+    `turn         ${world.turn()}${paused ? " (paused)" : ""}`,
+    `particles    ${count}`,
+    `live heap    ${heap.live_bytes} B`,
+    `peak heap    ${heap.peak_live_bytes} B`,
+    `allocations  ${heap.allocations} (${heap.allocated_bytes} B)`,
+    `frees        ${heap.deallocations} (${heap.freed_bytes} B)`,
+  ].join("\n");
+  heap.free();
+  if (now - logged >= 1000) {
+    log_memory_stats();
+    logged = now;
+  }
+  requestAnimationFrame(frame);
+}
+requestAnimationFrame(frame);